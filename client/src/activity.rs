@@ -0,0 +1,49 @@
+use common::i18n::{tr, trf};
+use common::ActivityReport;
+use ratatui::{
+    prelude::*,
+    widgets::{Bar, BarChart, BarGroup, Block, BorderType, Borders, Clear, Paragraph, Sparkline},
+};
+
+// Popup for an `/activity` reply; any key closes it. The server counts by UTC hour, so the
+// hours of the day are turned to local time with `utc_offset` (in seconds) before drawing.
+pub fn draw(f: &mut Frame, area: Rect, report: &ActivityReport, utc_offset: i32, locale: &str) {
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", trf(locale, "ui.activity", &[&report.room])))
+        .title_bottom(format!(" {} ", tr(locale, "ui.close_hint")))
+        .style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [summary, day, day_labels, by_hour_title, by_hour] =
+        Layout::vertical([Constraint::Length(2), Constraint::Length(3), Constraint::Length(1), Constraint::Length(2), Constraint::Min(4)]).areas(inner);
+
+    let local_hour = |utc_hour: usize| (utc_hour as i64 + (utc_offset / 3600) as i64).rem_euclid(24) as usize;
+    let mut text = trf(locale, "ui.activity_total", &[&report.last_day.iter().sum::<u64>().to_string()]);
+    if let Some(hour) = report.busiest_hour() {
+        text.push_str(&format!(" · {}", trf(locale, "ui.activity_busiest", &[&format!("{:02}:00", local_hour(hour))])));
+    }
+    f.render_widget(Paragraph::new(text).style(Style::default().fg(Color::White)), summary);
+
+    // A sparkline draws a column per value, so each hour is repeated to fill the width
+    let stretch = (day.width as usize / report.last_day.len().max(1)).max(1);
+    let columns: Vec<u64> = report.last_day.iter().flat_map(|count| std::iter::repeat_n(*count, stretch)).collect();
+    f.render_widget(Sparkline::default().data(&columns).style(Style::default().fg(Color::Green)), day);
+    let ago = tr(locale, "ui.activity_day_ago");
+    let now = tr(locale, "ui.activity_now");
+    let padding = columns.len().min(day_labels.width as usize).saturating_sub(ago.chars().count() + now.chars().count());
+    f.render_widget(Paragraph::new(format!("{}{}{}", ago, " ".repeat(padding), now)).style(Style::default().fg(Color::DarkGray)), day_labels);
+
+    f.render_widget(Paragraph::new(tr(locale, "ui.activity_by_hour")).style(Style::default().fg(Color::DarkGray)), by_hour_title);
+    let mut counts = [0; 24];
+    for (hour, count) in report.by_hour.iter().enumerate().take(24) {
+        counts[local_hour(hour)] = *count;
+    }
+    let bars: Vec<Bar> = counts.iter().enumerate().map(|(hour, count)| Bar::default().value(*count).label(Line::from(format!("{:02}", hour))).text_value(String::new())).collect();
+    // Two columns a bar fit the labels; a gap between them if there is room
+    let gap = if by_hour.width >= 72 { 1 } else { 0 };
+    let chart = BarChart::default().data(BarGroup::default().bars(&bars)).bar_width(2).bar_gap(gap).bar_style(Style::default().fg(Color::Yellow));
+    f.render_widget(chart, by_hour);
+}
@@ -0,0 +1,92 @@
+use common::{i18n::tr, CommandInfo};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+// Commands the client handles itself, offered next to the server's: name, arguments, help key
+const LOCAL_COMMANDS: &[(&str, &str, &str)] = &[
+    ("/find", "<text>", "help.find"),
+    ("/export", "<file>", "help.export"),
+    ("/ignore", "[user]", "help.ignore"),
+    ("/quiet", "[summary|hide|off]", "help.quiet"),
+    ("/dnd", "[on|off]", "help.dnd"),
+    ("/notify", "[all|mentions|none]", "help.notify"),
+    ("/quiethours", "<HH:MM-HH:MM|off>", "help.quiethours"),
+    ("/receipts", "[on|off]", "help.receipts"),
+    ("/goto", "<#room/number>", "help.goto"),
+    ("/spell", "[on|off|add <word>]", "help.spell"),
+    ("/alias", "[name] [expansion]", "help.alias"),
+    ("/collapse", "[category]", "help.collapse"),
+    ("/expand", "[category]", "help.collapse"),
+];
+
+// Popup state while a command name is being typed
+#[derive(Default)]
+pub struct Completion {
+    pub selected: usize,
+    pub dismissed: Option<String>, // Esc closes the popup until the input changes
+}
+
+// Commands starting with what was typed, as long as only the name has been typed so far
+pub fn matches(input: &str, server: &[CommandInfo]) -> Vec<CommandInfo> {
+    if !input.starts_with('/') || input.contains(char::is_whitespace) {
+        return Vec::new();
+    }
+    let typed = input.to_lowercase();
+    let local = LOCAL_COMMANDS.iter().map(|(name, args, help)| CommandInfo { name: name.to_string(), args: args.to_string(), help: help.to_string() });
+    let mut found: Vec<CommandInfo> = server.iter().cloned().chain(local).filter(|command| command.name.starts_with(&typed)).collect();
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found.dedup_by(|a, b| a.name == b.name);
+    found
+}
+
+pub fn draw(f: &mut Frame, area: Rect, matches: &[CommandInfo], selected: usize, locale: &str) {
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", tr(locale, "ui.commands")))
+        .style(Style::default().fg(Color::Cyan));
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|command| {
+            // Newer servers may offer commands this client has no text for
+            let help = Some(tr(locale, &command.help)).filter(|help| *help != "???").unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::styled(command.name.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled(format!(" {}", command.args), Style::default().fg(Color::Yellow)),
+                Span::styled(format!("  {}", help), Style::default().fg(Color::Gray)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol("› ");
+    f.render_stateful_widget(list, area, &mut ListState::default().with_selected(Some(selected)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(input: &str, server: &[CommandInfo]) -> Vec<String> {
+        matches(input, server).into_iter().map(|command| command.name).collect()
+    }
+
+    #[test]
+    fn only_the_command_name_is_completed() {
+        let server = [CommandInfo { name: "/join".to_string(), args: "<room>".to_string(), help: "help.join".to_string() }];
+        assert_eq!(names("/J", &server), vec!["/join"]);
+        assert!(names("/join ", &server).is_empty());
+        assert!(names("join", &server).is_empty());
+        assert_eq!(names("/e", &server), vec!["/expand", "/export"]);
+    }
+
+    #[test]
+    fn server_commands_are_merged_with_local_ones() {
+        let server = [CommandInfo { name: "/find".to_string(), args: "<text>".to_string(), help: "help.find".to_string() }];
+        assert_eq!(names("/fi", &server), vec!["/find"]);
+    }
+}
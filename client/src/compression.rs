@@ -0,0 +1,59 @@
+use common::deflate::{self, Inflater};
+use common::ChatMessage;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+
+const PIPE_SIZE: usize = 64 * 1024; // Decompressed or decoded text waiting for the line reader
+
+// What the client reads server lines from; boxed so a decompressing stream can replace the
+// plain one when the server switches to compression or MessagePack after the handshake
+pub type ServerReader = BufReader<Box<dyn AsyncRead + Unpin + Send>>;
+
+pub fn plain(reader: impl AsyncRead + Unpin + Send + 'static) -> ServerReader {
+    BufReader::new(Box::new(reader))
+}
+
+// Unframes and decompresses the rest of `reader` in a task, handing the text on through a
+// pipe. The pipe closes when the connection does, or at
+// the first frame that doesn't decompress, which the reader sees as a disconnect.
+pub fn inflating(reader: &mut ServerReader) -> ServerReader {
+    let mut compressed = std::mem::replace(reader, plain(tokio::io::empty()));
+    let (mut pipe, output) = tokio::io::duplex(PIPE_SIZE);
+    tokio::spawn(async move {
+        let mut inflater = Inflater::default();
+        let mut header = [0; deflate::FRAME_HEADER];
+        while compressed.read_exact(&mut header).await.is_ok() {
+            let Ok(len) = deflate::frame_len(header) else { break };
+            let mut frame = vec![0; len];
+            if compressed.read_exact(&mut frame).await.is_err() {
+                break;
+            }
+            let Ok(text) = inflater.inflate(&frame) else { break };
+            if pipe.write_all(&text).await.is_err() {
+                break;
+            }
+        }
+    });
+    plain(output)
+}
+
+// Decodes the framed MessagePack messages in the rest of `reader` back into JSON lines for
+// the line reader, in a task like `inflating`. Stacks on top of it when both are on.
+pub fn unpacking(reader: &mut ServerReader) -> ServerReader {
+    let mut packed = std::mem::replace(reader, plain(tokio::io::empty()));
+    let (mut pipe, output) = tokio::io::duplex(PIPE_SIZE);
+    tokio::spawn(async move {
+        let mut header = [0; deflate::FRAME_HEADER];
+        while packed.read_exact(&mut header).await.is_ok() {
+            let Ok(len) = deflate::frame_len(header) else { break };
+            let mut frame = vec![0; len];
+            if packed.read_exact(&mut frame).await.is_err() {
+                break;
+            }
+            let Ok(json) = ChatMessage::from_msgpack(&frame).and_then(|msg| msg.to_json()) else { break };
+            if pipe.write_all(format!("{}\n", json).as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+    plain(output)
+}
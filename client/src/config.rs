@@ -0,0 +1,265 @@
+use chrono::{DateTime, Local, Utc};
+use common::i18n;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::login::Login;
+use crate::notify::NotificationConfig;
+
+// Client settings persisted as JSON under the user's config directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    pub layout: LayoutConfig,
+    pub ignored: Vec<String>, // Usernames whose messages are collapsed client-side
+    pub highlights: Vec<HighlightRule>,
+    pub filters: Vec<FilterRule>,
+    pub quiet_rooms: HashMap<String, QuietMode>, // Rooms where join/leave noise is reduced
+    pub scrollback_limit: usize, // Messages per room kept on disk between sessions, 0 disables
+    pub notifications: NotificationConfig,
+    pub read_receipts: bool, // Opt-in: tell the server which messages we have seen
+    pub timestamps: TimestampStyle,
+    pub locale: Option<String>, // UI and server text language, e.g. "es"; defaults to $LANG
+    pub triggers: Vec<TriggerRule>,
+    pub aliases: HashMap<String, String>, // "/name args" runs the expansion, `$*` marks where args go
+    pub auto_away: AutoAwayConfig,
+    pub profiles: Vec<Profile>, // Saved from the login screen
+    pub last_profile: Option<String>, // Preselected at the next start
+    pub spellcheck: SpellCheckConfig,
+    pub compression: bool, // Ask the server to compress what it sends
+    pub msgpack: bool,     // Ask the server for MessagePack instead of JSON lines
+}
+
+// A saved server and username; passwords are never written to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default)]
+    pub username: String,
+}
+
+impl Profile {
+    pub fn name_for(username: &str, host: &str, port: u16) -> String {
+        format!("{}@{}:{}", username, host, port)
+    }
+
+    pub fn from_login(login: &Login) -> Self {
+        Self {
+            name: Self::name_for(&login.username, &login.host, login.port),
+            host: login.host.clone(),
+            port: login.port,
+            tls: login.tls,
+            username: login.username.clone(),
+        }
+    }
+}
+
+// Marks us away automatically; the next keypress brings us back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoAwayConfig {
+    pub idle_minutes: u64, // 0 disables the idle timer
+    pub on_focus_lost: bool,
+}
+
+impl Default for AutoAwayConfig {
+    fn default() -> Self {
+        Self {
+            idle_minutes: 10,
+            on_focus_lost: true,
+        }
+    }
+}
+
+// Underlines misspelled words in the input box; F7 offers suggestions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpellCheckConfig {
+    pub enabled: bool,
+    pub language: Option<String>, // Dictionary to use, e.g. "en_GB"; defaults to the UI language
+    pub words: Vec<String>,       // Personal dictionary, added to from the F7 popup or /spell add
+}
+
+impl Default for SpellCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            language: None,
+            words: Vec::new(),
+        }
+    }
+}
+
+// How message times are shown, always converted to the local timezone
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampStyle {
+    #[default]
+    #[serde(rename = "24h")]
+    H24,
+    #[serde(rename = "12h")]
+    H12,
+    Seconds,
+    Date,
+    Relative, // "2m ago", recomputed on every redraw
+}
+
+impl TimestampStyle {
+    pub fn format(self, timestamp: DateTime<Utc>) -> String {
+        let local = timestamp.with_timezone(&Local);
+        match self {
+            TimestampStyle::H24 => local.format("%H:%M").to_string(),
+            TimestampStyle::H12 => local.format("%I:%M %p").to_string(),
+            TimestampStyle::Seconds => local.format("%H:%M:%S").to_string(),
+            TimestampStyle::Date => local.format("%Y-%m-%d %H:%M").to_string(),
+            TimestampStyle::Relative => {
+                let secs = Utc::now().signed_duration_since(timestamp).num_seconds().max(0);
+                let relative = match secs {
+                    0..=9 => "now".to_string(),
+                    10..=59 => format!("{}s ago", secs),
+                    60..=3599 => format!("{}m ago", secs / 60),
+                    3600..=86399 => format!("{}h ago", secs / 3600),
+                    _ => format!("{}d ago", secs / 86400),
+                };
+                // Fixed width keeps message text aligned as labels change
+                format!("{:>7}", relative)
+            }
+        }
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            layout: LayoutConfig::default(),
+            ignored: Vec::new(),
+            highlights: Vec::new(),
+            filters: Vec::new(),
+            quiet_rooms: HashMap::new(),
+            scrollback_limit: 500,
+            notifications: NotificationConfig::default(),
+            read_receipts: false,
+            timestamps: TimestampStyle::default(),
+            locale: None,
+            triggers: Vec::new(),
+            aliases: HashMap::new(),
+            auto_away: AutoAwayConfig::default(),
+            profiles: Vec::new(),
+            last_profile: None,
+            spellcheck: SpellCheckConfig::default(),
+            compression: true,
+            msgpack: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuietMode {
+    Summary, // Runs of joins/leaves collapse to "3 joined, 1 left"
+    Hide,
+}
+
+// Messages whose content matches `pattern` are drawn in `color` (a ratatui
+// color name or #rrggbb); `notify` also rings the terminal bell
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightRule {
+    pub pattern: String,
+    #[serde(default = "default_highlight_color")]
+    pub color: String,
+    #[serde(default)]
+    pub notify: bool,
+}
+
+// Messages whose content matches `pattern` are hidden
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterRule {
+    pub pattern: String,
+}
+
+// Incoming chat messages and PMs from others matching `pattern` run `action`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerRule {
+    pub pattern: String,
+    pub action: TriggerAction,
+}
+
+// Written as e.g. {"reply": "hi $user"}; $user, $room and $message expand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerAction {
+    Reply(String),   // Sent where the message came from (the room, or back as a PM)
+    Command(String), // Any line typed in the input box, e.g. "/join ops"
+    Exec(String),    // Shell command, with the message in $CHAT_USER, $CHAT_ROOM and $CHAT_MESSAGE
+}
+
+fn default_highlight_color() -> String {
+    "yellow".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub sidebar_width: u16, // Percentage of the screen width
+    pub show_sidebar: bool,
+    pub show_users: bool,
+    pub collapsed: Vec<String>, // Room categories folded in the sidebar, e.g. "work"
+}
+
+impl LayoutConfig {
+    pub const MIN_SIDEBAR: u16 = 10;
+    pub const MAX_SIDEBAR: u16 = 50;
+
+    pub fn resize_sidebar(&mut self, delta: i16) {
+        let width = self.sidebar_width as i16 + delta;
+        self.sidebar_width = width.clamp(Self::MIN_SIDEBAR as i16, Self::MAX_SIDEBAR as i16) as u16;
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            sidebar_width: 20,
+            show_sidebar: true,
+            show_users: true,
+            collapsed: Vec::new(),
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("ultimate-chat").join("client.json"))
+    }
+
+    // A missing or unreadable config falls back to defaults rather than blocking startup
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn locale(&self) -> &'static str {
+        let env_locale = ["LC_ALL", "LC_MESSAGES", "LANG"].iter().find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()));
+        i18n::normalize(self.locale.as_deref().or(env_locale.as_deref()).unwrap_or_default())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
@@ -0,0 +1,144 @@
+use common::i18n::{tr, trf};
+use common::{ChatMessage, Handshake};
+use crossterm::event::{self, Event, KeyCode};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+
+use crate::centered_rect;
+use crate::compression::{self, ServerReader};
+use crate::login::{self, Login};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+// A connection that has completed the handshake
+pub struct Session {
+    pub reader: ServerReader,
+    pub writer: OwnedWriteHalf,
+    pub first: ChatMessage, // First message after the handshake, e.g. the room change
+}
+
+pub enum Choice {
+    Retry,
+    EditServer,
+    Quit,
+}
+
+// Connects with a progress screen, then runs the handshake (which may prompt for
+// credentials and updates `handshake` with them); failures come back as a message
+pub async fn open_session(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    login: &Login,
+    handshake: &mut Handshake,
+    locale: &str,
+) -> io::Result<Result<Session, String>> {
+    let stream = match connect(terminal, login, locale).await? {
+        Ok(stream) => stream,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = compression::plain(reader);
+    let first = login::handshake(terminal, &mut reader, &mut writer, handshake, locale).await?;
+    Ok(first.map(|first| Session { reader, writer, first }))
+}
+
+async fn connect(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, login: &Login, locale: &str) -> io::Result<Result<TcpStream, String>> {
+    let addr = login.addr();
+    if login.tls {
+        return Ok(Err(tr(locale, "ui.tls_unsupported").to_string()));
+    }
+    let started = Instant::now();
+    // Resolve separately so a bad host name gets its own explanation
+    let attempt = tokio::time::timeout(CONNECT_TIMEOUT, async {
+        let addrs: Vec<_> = tokio::net::lookup_host(&addr).await.map_err(|_| trf(locale, "ui.err_dns", &[&login.host]))?.collect();
+        let mut last_error = None;
+        for candidate in addrs {
+            match TcpStream::connect(candidate).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(match last_error {
+            Some(e) => describe(&e, &addr, locale),
+            None => trf(locale, "ui.err_dns", &[&login.host]),
+        })
+    });
+    tokio::pin!(attempt);
+    let mut tick = tokio::time::interval(Duration::from_millis(100));
+    let mut frame = 0;
+    loop {
+        tokio::select! {
+            result = &mut attempt => {
+                return Ok(result.unwrap_or_else(|_| Err(trf(locale, "ui.err_timeout", &[&addr, &CONNECT_TIMEOUT.as_secs().to_string()]))));
+            }
+            _ = tick.tick() => {
+                let label = trf(locale, "ui.connecting", &[&addr, &started.elapsed().as_secs().to_string()]);
+                terminal.draw(|f| draw_progress(f, SPINNER[frame % SPINNER.len()], &label, locale))?;
+                frame += 1;
+                while event::poll(Duration::ZERO)? {
+                    if matches!(event::read()?, Event::Key(key) if key.code == KeyCode::Esc) {
+                        return Ok(Err(tr(locale, "ui.connect_cancelled").to_string()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn describe(e: &io::Error, addr: &str, locale: &str) -> String {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused => trf(locale, "ui.err_refused", &[addr]),
+        io::ErrorKind::NetworkUnreachable | io::ErrorKind::HostUnreachable => trf(locale, "ui.err_unreachable", &[addr]),
+        _ => trf(locale, "ui.err_connect", &[addr, &e.to_string()]),
+    }
+}
+
+fn draw_progress(f: &mut Frame, spinner: &str, label: &str, locale: &str) {
+    let area = centered_rect(60, 20, f.area());
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::Cyan));
+    let text = vec![
+        Line::from(format!("{} {}", spinner, label)),
+        Line::from(""),
+        Line::from(Span::styled(tr(locale, "ui.connect_hint"), Style::default().fg(Color::DarkGray))),
+    ];
+    f.render_widget(Paragraph::new(text).block(block).alignment(Alignment::Center).wrap(Wrap { trim: true }), area);
+}
+
+// Explains a failed connection or handshake and asks what to do next
+pub fn error_dialog(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, reason: &str, locale: &str) -> io::Result<Choice> {
+    loop {
+        terminal.draw(|f| {
+            let area = centered_rect(60, 30, f.area());
+            f.render_widget(Clear, area);
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(format!(" {} ", tr(locale, "ui.connect_failed")))
+                .style(Style::default().fg(Color::Red));
+            let text = vec![
+                Line::from(Span::styled(reason, Style::default().fg(Color::White))),
+                Line::from(""),
+                Line::from(Span::styled(tr(locale, "ui.connect_failed_hint"), Style::default().fg(Color::DarkGray))),
+            ];
+            f.render_widget(Paragraph::new(text).block(block).wrap(Wrap { trim: true }), area);
+        })?;
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Char('r') | KeyCode::Char('R') | KeyCode::Enter => return Ok(Choice::Retry),
+                KeyCode::Char('e') | KeyCode::Char('E') => return Ok(Choice::EditServer),
+                KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => return Ok(Choice::Quit),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use common::i18n::tr;
+use common::{format_bytes, format_elapsed, ChatMessage, ConsoleState, Handshake, MessageType, ServerStats};
+use crossterm::{
+    event::{Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Cell, Paragraph, Row, Table},
+};
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::config::ClientConfig;
+use crate::events::TerminalEvents;
+use crate::login::{DEFAULT_HOST, DEFAULT_PORT};
+use crate::stats;
+
+// `client --console [--server host:port] <admin name>`: a live view of the server for admins,
+// refreshed from `/console`, with a prompt for admin commands. There is nobody to prompt
+// before the screen is up, so credentials come from the environment as for `--headless`.
+const USAGE: &str = "Usage: client --console [--server host:port] <admin name>";
+const REFRESH: Duration = Duration::from_secs(2);
+const OUTPUT_LINES: usize = 200; // Command replies kept for the output pane
+
+pub struct Options {
+    pub username: String,
+    pub server: String,
+}
+
+impl Options {
+    // None when --console was not requested
+    pub fn parse(args: &[String]) -> Option<anyhow::Result<Self>> {
+        if !args.iter().any(|a| a == "--console") {
+            return None;
+        }
+        let mut server = format!("{}:{}", DEFAULT_HOST, DEFAULT_PORT);
+        let mut username = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--console" => {}
+                "--server" => match args.next() {
+                    Some(addr) => server = addr.clone(),
+                    None => return Some(Err(anyhow::anyhow!(USAGE))),
+                },
+                _ => username = Some(arg.clone()),
+            }
+        }
+        Some(username.map(|username| Self { username, server }).ok_or_else(|| anyhow::anyhow!(USAGE)))
+    }
+}
+
+// What the screen shows
+pub struct Console {
+    pub state: ConsoleState,
+    pub output: VecDeque<String>, // Oldest first
+    pub input: String,
+    pub locale: &'static str,
+}
+
+impl Console {
+    pub fn new(locale: &'static str) -> Self {
+        Self { state: ConsoleState::default(), output: VecDeque::new(), input: String::new(), locale }
+    }
+
+    // Console snapshots replace the view; replies to commands go to the output pane
+    pub fn receive(&mut self, msg: &ChatMessage) {
+        let line = match msg.msg_type {
+            MessageType::Console => {
+                self.state = serde_json::from_str(&msg.content).unwrap_or_default();
+                return;
+            }
+            MessageType::System => msg.content.clone(),
+            MessageType::Error => format!("! {}", msg.content),
+            MessageType::PrivateMessage => format!("[pm] {}: {}", msg.username, msg.content),
+            MessageType::Stats => {
+                let stats: ServerStats = serde_json::from_str(&msg.content).unwrap_or_default();
+                stats::rows(&stats, self.locale).into_iter().map(|(label, value)| format!("{}: {}", label, value)).collect::<Vec<_>>().join(", ")
+            }
+            _ => return,
+        };
+        self.note(&line);
+    }
+
+    pub fn note(&mut self, line: &str) {
+        for line in line.lines() {
+            if self.output.len() == OUTPUT_LINES {
+                self.output.pop_front();
+            }
+            self.output.push_back(line.to_string());
+        }
+    }
+}
+
+pub async fn run(options: Options, config: ClientConfig) -> anyhow::Result<()> {
+    let addr = &options.server;
+    let stream = TcpStream::connect(addr).await.with_context(|| format!("Failed to connect to {}", addr))?;
+    let (reader, mut writer) = stream.into_split();
+    let handshake = Handshake {
+        username: options.username.clone(),
+        locale: Some(config.locale().to_string()),
+        password: std::env::var("CHAT_PASSWORD").ok(),
+        totp: std::env::var("CHAT_TOTP").ok(),
+        ..Default::default()
+    };
+    writer.write_all(format!("{}\n", serde_json::to_string(&handshake)?).as_bytes()).await?;
+    let mut server = BufReader::new(reader).lines();
+    // Logged in once the server moves us into a room
+    loop {
+        let Some(line) = server.next_line().await? else { bail!("The server closed the connection") };
+        if let Some(error) = line.strip_prefix("Error:") {
+            bail!("{}", error.trim());
+        }
+        let Ok(msg) = ChatMessage::from_json(&line) else { continue };
+        match msg.msg_type {
+            MessageType::AuthRequired => bail!("The server wants a {} for {}; set CHAT_PASSWORD or CHAT_TOTP", msg.content, options.username),
+            MessageType::RoomChange => break,
+            _ => {}
+        }
+    }
+    writer.write_all(b"/console\n").await?;
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let mut console = Console::new(config.locale());
+    let mut events = TerminalEvents::spawn();
+    let mut refresh = tokio::time::interval(REFRESH);
+    let result: anyhow::Result<()> = async {
+        loop {
+            terminal.draw(|f| draw(f, &console, Utc::now()))?;
+            tokio::select! {
+                line = server.next_line() => {
+                    let Some(line) = line? else { bail!("The server closed the connection") };
+                    if let Ok(msg) = ChatMessage::from_json(&line) {
+                        console.receive(&msg);
+                    }
+                }
+                _ = refresh.tick() => writer.write_all(b"/console\n").await?,
+                event = events.next() => {
+                    let Some(Event::Key(key)) = event.transpose()? else { continue };
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Esc => break,
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                        KeyCode::Enter if !console.input.trim().is_empty() => {
+                            // Everything typed here is a command, slash or not
+                            let command = std::mem::take(&mut console.input);
+                            let command = command.trim();
+                            let command = if command.starts_with('/') { command.to_string() } else { format!("/{}", command) };
+                            console.note(&format!("> {}", command));
+                            writer.write_all(format!("{}\n/console\n", command).as_bytes()).await?;
+                        }
+                        KeyCode::Backspace => {
+                            console.input.pop();
+                        }
+                        KeyCode::Char(c) => console.input.push(c),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        let _ = writer.write_all(b"/quit\n").await;
+        Ok(())
+    }
+    .await;
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    result
+}
+
+pub fn draw(f: &mut Frame, console: &Console, now: DateTime<Utc>) {
+    let locale = console.locale;
+    let block = |key: &str| Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(format!(" {} ", tr(locale, key)));
+    let header = |keys: &[&str]| Row::new(keys.iter().map(|key| Cell::from(tr(locale, key)))).style(Style::default().fg(Color::DarkGray));
+    let [top, connections, bottom, input] = Layout::vertical([Constraint::Length(8), Constraint::Min(4), Constraint::Length(8), Constraint::Length(3)]).areas(f.area());
+    let [stats_area, rooms_area] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(top);
+    let [actions_area, output_area] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(bottom);
+
+    let stat_rows = stats::rows(&console.state.stats, locale).into_iter().map(|(label, value)| Row::new(vec![Cell::from(label).style(Style::default().fg(Color::DarkGray)), Cell::from(value)]));
+    f.render_widget(Table::new(stat_rows, [Constraint::Percentage(60), Constraint::Percentage(40)]).block(block("ui.console").style(Style::default().fg(Color::Cyan))), stats_area);
+
+    let room_rows = console.state.rooms.iter().map(|room| Row::new(vec![format!("#{}", room.room), room.users.to_string(), room.messages_per_minute.to_string()]));
+    let rooms = Table::new(room_rows, [Constraint::Fill(1), Constraint::Length(6), Constraint::Length(9)]).header(header(&["ui.console_room", "ui.console_users", "ui.console_rate"])).block(block("ui.console_rooms"));
+    f.render_widget(rooms, rooms_area);
+
+    let connection_rows = console.state.connections.iter().map(|c| {
+        let connected = c.since.map(|since| format_elapsed((now - since).num_seconds().max(0) as u64)).unwrap_or_default();
+        let traffic = format!("{} / {}", format_bytes(c.bytes_in), format_bytes(c.bytes_out));
+        let mut flags: Vec<&str> = Vec::new();
+        flags.extend(c.admin.then_some("admin"));
+        flags.extend(c.guest.then_some("guest"));
+        flags.extend(c.away.is_some().then_some("away"));
+        let style = if c.admin { Style::default().fg(Color::Green) } else { Style::default() };
+        Row::new(vec![c.username.clone(), format!("#{}", c.room), c.addr.clone(), connected, traffic, flags.join(" ")]).style(style)
+    });
+    let widths = [Constraint::Length(16), Constraint::Length(16), Constraint::Length(22), Constraint::Length(10), Constraint::Length(20), Constraint::Fill(1)];
+    let header_keys = ["ui.console_user", "ui.console_room", "ui.console_address", "ui.console_connected", "ui.console_traffic"];
+    f.render_widget(Table::new(connection_rows, widths).header(header(&header_keys)).block(block("ui.console_connections")), connections);
+
+    f.render_widget(Paragraph::new(tail(console.state.actions.iter(), actions_area)).block(block("ui.console_actions")), actions_area);
+    f.render_widget(Paragraph::new(tail(console.output.iter(), output_area)).block(block("ui.console_output")), output_area);
+
+    let prompt = Paragraph::new(format!("/{}", console.input.strip_prefix('/').unwrap_or(&console.input)))
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title_bottom(format!(" {} ", tr(locale, "ui.console_hint"))));
+    f.render_widget(prompt, input);
+    let typed = console.input.strip_prefix('/').unwrap_or(&console.input).chars().count() as u16;
+    f.set_cursor_position((input.x + 2 + typed, input.y + 1));
+}
+
+// The newest lines that fit in a bordered `area`, oldest at the top
+fn tail<'a>(lines: impl DoubleEndedIterator<Item = &'a String>, area: Rect) -> Vec<Line<'a>> {
+    let mut shown: Vec<Line> = lines.rev().take(area.height.saturating_sub(2) as usize).map(|line| Line::from(line.as_str())).collect();
+    shown.reverse();
+    shown
+}
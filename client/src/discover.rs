@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+use common::i18n::{tr, trf};
+use common::Discovery;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+// Browser for the `/discover` reply; Enter joins the selected room
+pub struct DiscoverPanel {
+    pub discovery: Discovery,
+    pub selected: usize,
+}
+
+impl DiscoverPanel {
+    pub fn from_json(content: &str) -> Self {
+        Self { discovery: serde_json::from_str(content).unwrap_or_default(), selected: 0 }
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        if !self.discovery.rooms.is_empty() {
+            self.selected = (self.selected as isize + delta).rem_euclid(self.discovery.rooms.len() as isize) as usize;
+        }
+    }
+
+    pub fn selected_room(&self) -> Option<&str> {
+        self.discovery.rooms.get(self.selected).map(|room| room.name.as_str())
+    }
+}
+
+// "5m", "3h" or "2d" since `then`
+fn since(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = now.signed_duration_since(then).num_seconds().max(0);
+    match secs {
+        0..=59 => "<1m".to_string(),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+pub fn draw(f: &mut Frame, area: Rect, panel: &DiscoverPanel, now: DateTime<Utc>, locale: &str) {
+    f.render_widget(Clear, area);
+    let title = if panel.discovery.query.is_empty() { tr(locale, "ui.discover").to_string() } else { trf(locale, "ui.discover_matching", &[&panel.discovery.query]) };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", title))
+        .title_bottom(format!(" {} ", tr(locale, "ui.discover_hint")))
+        .style(Style::default().fg(Color::Cyan));
+    if panel.discovery.rooms.is_empty() {
+        let empty = List::new([ListItem::new(tr(locale, "ui.discover_empty")).style(Style::default().fg(Color::DarkGray))]).block(block);
+        f.render_widget(empty, area);
+        return;
+    }
+    let items: Vec<ListItem> = panel
+        .discovery
+        .rooms
+        .iter()
+        .map(|room| {
+            let active = match room.last_active {
+                Some(at) => trf(locale, "ui.discover_active", &[&since(at, now)]),
+                None => tr(locale, "ui.discover_quiet").to_string(),
+            };
+            let counts = trf(locale, "ui.discover_counts", &[&room.users.to_string(), &room.messages_today.to_string()]);
+            let tags: String = room.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+            let mut lines = vec![Line::from(vec![
+                Span::styled(format!("#{} ", room.name), Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+                Span::styled(format!("{} · {}", counts, active), Style::default().fg(Color::DarkGray)),
+                Span::styled(tags, Style::default().fg(Color::Green)),
+            ])];
+            if let Some(description) = &room.description {
+                lines.push(Line::from(Span::styled(format!("  {}", description), Style::default().fg(Color::Gray))));
+            }
+            ListItem::new(lines)
+        })
+        .collect();
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol("› ");
+    f.render_stateful_widget(list, area, &mut ListState::default().with_selected(Some(panel.selected)));
+}
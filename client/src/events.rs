@@ -0,0 +1,57 @@
+use crossterm::event::{self, Event};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+// How long the input thread waits for a key before checking whether it was paused
+const PAUSE_CHECK: Duration = Duration::from_millis(100);
+
+// Terminal input as an async stream. crossterm's own EventStream needs the futures crates,
+// which this build doesn't have, so a thread blocks on the terminal and forwards events.
+pub struct TerminalEvents {
+    rx: mpsc::UnboundedReceiver<io::Result<Event>>,
+    paused: Arc<AtomicBool>,
+    thread: thread::Thread,
+}
+
+impl TerminalEvents {
+    pub fn spawn() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let paused = Arc::new(AtomicBool::new(false));
+        let thread_paused = paused.clone();
+        let handle = thread::spawn(move || loop {
+            if thread_paused.load(Ordering::Acquire) {
+                thread::park();
+                continue;
+            }
+            let event = match event::poll(PAUSE_CHECK) {
+                Ok(false) => continue,
+                Ok(true) => event::read(),
+                Err(e) => Err(e),
+            };
+            let failed = event.is_err();
+            if tx.send(event).is_err() || failed {
+                break;
+            }
+        });
+        Self { rx, paused, thread: handle.thread().clone() }
+    }
+
+    // None once the terminal can't be read
+    pub async fn next(&mut self) -> Option<io::Result<Event>> {
+        self.rx.recv().await
+    }
+
+    // Leaves the terminal to code that reads it directly, like the reconnect prompts
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Release);
+        self.thread.unpark();
+    }
+}
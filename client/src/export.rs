@@ -0,0 +1,74 @@
+use common::ChatMessage;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Transcript format, picked from the file extension
+enum Format {
+    Text,
+    Json,
+    Html,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase()).as_deref() {
+            Some("json") => Format::Json,
+            Some("html") | Some("htm") => Format::Html,
+            _ => Format::Text,
+        }
+    }
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn write_transcript(path: &Path, room: &str, messages: &[&ChatMessage]) -> io::Result<()> {
+    let contents = match Format::from_path(path) {
+        Format::Text => to_text(messages),
+        Format::Json => serde_json::to_string_pretty(messages)?,
+        Format::Html => to_html(room, messages),
+    };
+    fs::write(path, contents)
+}
+
+fn timestamp(msg: &ChatMessage) -> String {
+    msg.timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+fn to_text(messages: &[&ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|msg| format!("[{}] {}: {}\n", timestamp(msg), msg.username, msg.content_lines().collect::<Vec<_>>().join("\n    ")))
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn to_html(room: &str, messages: &[&ChatMessage]) -> String {
+    let rows: String = messages
+        .iter()
+        .map(|msg| {
+            let content = msg.content_lines().map(escape_html).collect::<Vec<_>>().join("<br>");
+            format!(
+                "<tr><td class=\"time\">{}</td><td class=\"user\">{}</td><td>{}</td></tr>\n",
+                timestamp(msg),
+                escape_html(&msg.username),
+                content
+            )
+        })
+        .collect();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>#{room} transcript</title>\n\
+         <style>body{{font-family:sans-serif}} td{{padding:2px 8px;vertical-align:top}} .time{{color:#888}} .user{{font-weight:bold}}</style>\n\
+         </head>\n<body>\n<h1>#{room}</h1>\n<table>\n{rows}</table>\n</body>\n</html>\n",
+        room = escape_html(room),
+        rows = rows
+    )
+}
@@ -0,0 +1,115 @@
+use common::i18n::{tr, trf};
+use common::{Prompt, PromptField};
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+};
+use tui_input::{backend::crossterm::EventHandler, Input};
+
+// Popup for a `Prompt` from the server: a text box or a list to pick from
+pub struct Form {
+    pub prompt: Prompt,
+    input: Input,
+    highlighted: usize,
+    picked: Vec<String>, // For choices that allow several
+}
+
+impl Form {
+    pub fn new(prompt: Prompt) -> Self {
+        let (input, highlighted, picked) = match &prompt.field {
+            PromptField::Text { value, .. } => (Input::new(value.clone()), 0, Vec::new()),
+            PromptField::Choice { options, selected, .. } => {
+                let highlighted = options.iter().position(|option| selected.contains(&option.value)).unwrap_or_default();
+                (Input::default(), highlighted, selected.clone())
+            }
+        };
+        Self { prompt, input, highlighted, picked }
+    }
+
+    // The value Enter would send
+    pub fn value(&self) -> String {
+        match &self.prompt.field {
+            PromptField::Text { .. } => self.input.value().trim().to_string(),
+            PromptField::Choice { multiple: true, .. } => self.picked.join(" "),
+            PromptField::Choice { options, .. } => options.get(self.highlighted).map(|option| option.value.clone()).unwrap_or_default(),
+        }
+    }
+
+    // The `/answer` to send once a key submits the form: Enter answers, Esc skips
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<String> {
+        let value = match key.code {
+            KeyCode::Esc => String::new(),
+            KeyCode::Enter => self.value(),
+            _ => {
+                match &self.prompt.field {
+                    PromptField::Text { max_len, .. } => {
+                        if !matches!(key.code, KeyCode::Char(_)) || self.input.value().chars().count() < *max_len {
+                            self.input.handle_event(&Event::Key(key));
+                        }
+                    }
+                    PromptField::Choice { options, multiple, .. } => match key.code {
+                        KeyCode::Up => self.highlighted = (self.highlighted + options.len().max(1) - 1) % options.len().max(1),
+                        KeyCode::Down | KeyCode::Tab => self.highlighted = (self.highlighted + 1) % options.len().max(1),
+                        KeyCode::Char(' ') if *multiple => {
+                            let option = options.get(self.highlighted)?;
+                            if let Some(at) = self.picked.iter().position(|value| *value == option.value) {
+                                self.picked.remove(at);
+                            } else {
+                                self.picked.push(option.value.clone());
+                            }
+                        }
+                        _ => {}
+                    },
+                }
+                return None;
+            }
+        };
+        Some(format!("/answer {} {}", self.prompt.id, value).trim_end().to_string())
+    }
+}
+
+pub fn draw(f: &mut Frame, area: Rect, form: &Form, locale: &str) {
+    f.render_widget(Clear, area);
+    let prompt = &form.prompt;
+    let hint = match prompt.field {
+        PromptField::Text { .. } => "ui.form_text_hint",
+        PromptField::Choice { multiple: false, .. } => "ui.form_choice_hint",
+        PromptField::Choice { multiple: true, .. } => "ui.form_multiple_hint",
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ({}) ", tr(locale, &prompt.title), trf(locale, "ui.onboarding_step", &[&prompt.step.to_string(), &prompt.steps.to_string()])))
+        .title_bottom(format!(" {} ", tr(locale, hint)))
+        .style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [question, field] = Layout::vertical([Constraint::Length(2), Constraint::Min(1)]).areas(inner);
+    f.render_widget(Paragraph::new(tr(locale, &prompt.text)).style(Style::default().fg(Color::White)).wrap(Wrap { trim: true }), question);
+
+    match &prompt.field {
+        PromptField::Text { .. } => {
+            f.render_widget(Paragraph::new(format!("> {}", form.input.value())).style(Style::default().fg(Color::Yellow)), field);
+            f.set_cursor_position(Position::new(field.x + 2 + form.input.visual_cursor() as u16, field.y));
+        }
+        PromptField::Choice { options, multiple, .. } => {
+            let items: Vec<ListItem> = options
+                .iter()
+                .map(|option| {
+                    let label = option.label.as_deref().map_or(option.value.as_str(), |key| tr(locale, key));
+                    match multiple {
+                        true if form.picked.contains(&option.value) => ListItem::new(format!("[x] {}", label)),
+                        true => ListItem::new(format!("[ ] {}", label)),
+                        false => ListItem::new(label.to_string()),
+                    }
+                })
+                .collect();
+            let list = List::new(items)
+                .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+                .highlight_symbol("› ");
+            let mut state = ListState::default().with_selected((!options.is_empty()).then_some(form.highlighted));
+            f.render_stateful_widget(list, field, &mut state);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{bail, Context};
+use common::i18n::tr;
+use common::{ActivityReport, ChatMessage, ConsoleState, Discovery, Handshake, MessageType, ModerationState, PreviewUpdate, Prompt, PromptField, RoomEntry, ServerStats, UserProfile};
+use std::io::{self, Write};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::config::ClientConfig;
+use crate::login::{DEFAULT_HOST, DEFAULT_PORT};
+use crate::rooms;
+use crate::stats;
+
+// `client --headless [--json] [--server host:port] <username|--guest>`: stdin lines are sent as typed in the
+// TUI (text or /commands) and received messages are printed one per line, for
+// scripting, pipes and dumb terminals
+const USAGE: &str = "Usage: client --headless [--json] [--server host:port] <username|--guest>";
+
+pub struct Options {
+    pub username: String,
+    pub server: String,
+    pub json: bool, // Print each message as its wire JSON instead of plain text
+    pub guest: bool,
+}
+
+impl Options {
+    // None when --headless was not requested
+    pub fn parse(args: &[String]) -> Option<anyhow::Result<Self>> {
+        if !args.iter().any(|a| a == "--headless") {
+            return None;
+        }
+        let mut json = false;
+        let mut guest = false;
+        let mut server = format!("{}:{}", DEFAULT_HOST, DEFAULT_PORT);
+        let mut username = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => {}
+                "--json" => json = true,
+                "--guest" => guest = true,
+                "--server" => match args.next() {
+                    Some(addr) => server = addr.clone(),
+                    None => return Some(Err(anyhow::anyhow!(USAGE))),
+                },
+                _ => username = Some(arg.clone()),
+            }
+        }
+        Some(match username {
+            Some(username) => Ok(Self { username, server, json, guest }),
+            None if guest => Ok(Self { username: String::new(), server, json, guest }),
+            None => Err(anyhow::anyhow!(USAGE)),
+        })
+    }
+}
+
+pub async fn run(options: Options, config: ClientConfig) -> anyhow::Result<()> {
+    let addr = &options.server;
+    let stream = TcpStream::connect(addr).await.with_context(|| format!("Failed to connect to {}", addr))?;
+    let (reader, mut writer) = stream.into_split();
+    // There is nobody to prompt, so credentials come from the environment
+    let mut handshake = Handshake {
+        username: options.username.clone(),
+        locale: Some(config.locale().to_string()),
+        password: std::env::var("CHAT_PASSWORD").ok(),
+        totp: std::env::var("CHAT_TOTP").ok(),
+        captcha: None,
+        register: false,
+        guest: options.guest,
+        reconnects: 0,
+        compression: Vec::new(), // Output goes to a pipe as it arrives, so there is little to gain
+        encoding: Vec::new(),    // `--json` prints the lines as they come
+    };
+    writer.write_all(format!("{}\n", serde_json::to_string(&handshake)?).as_bytes()).await?;
+
+    let mut server = BufReader::new(reader).lines();
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = true;
+    let mut rooms_requested = false; // Room lists also arrive after every join; only print asked-for ones
+    loop {
+        tokio::select! {
+            line = server.next_line() => {
+                let Some(line) = line? else { break };
+                if let Some(error) = line.strip_prefix("Error:") {
+                    // Raw errors only happen before registration, so there is nothing left to do
+                    bail!("{}", error.trim());
+                }
+                let Ok(msg) = ChatMessage::from_json(&line) else { continue };
+                // The first line of stdin answers a captcha
+                if let Some(question) = msg.content.strip_prefix("captcha: ").filter(|_| msg.msg_type == MessageType::AuthRequired) {
+                    eprintln!("{}", question);
+                    let Some(answer) = stdin.next_line().await? else { bail!("No answer to the server's question") };
+                    handshake.captcha = Some(answer.trim().to_string());
+                    writer.write_all(format!("{}\n", serde_json::to_string(&handshake)?).as_bytes()).await?;
+                    continue;
+                }
+                if msg.msg_type == MessageType::AuthRequired {
+                    bail!("The server wants a {} for {}; set CHAT_PASSWORD or CHAT_TOTP", msg.content, options.username);
+                }
+                if msg.msg_type == MessageType::RoomList && !std::mem::take(&mut rooms_requested) {
+                    continue;
+                }
+                let text = if options.json { Some(line) } else { format_plain(&msg, &config) };
+                // A closed stdout (e.g. piped into `head`) ends the session
+                if text.is_some_and(|text| writeln!(io::stdout(), "{}", text).is_err()) {
+                    break;
+                }
+            }
+            line = stdin.next_line(), if stdin_open => match line? {
+                Some(line) if line.trim().is_empty() => {}
+                Some(line) => {
+                    rooms_requested |= line.trim() == "/rooms";
+                    writer.write_all(format!("{}\n", line.trim_end()).as_bytes()).await?
+                }
+                None => {
+                    // Keep printing until the server closes the connection after /quit
+                    stdin_open = false;
+                    writer.write_all(b"/quit\n").await?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+// Protocol bookkeeping (pongs, member lists, receipts) is left out of plain output
+fn format_plain(msg: &ChatMessage, config: &ClientConfig) -> Option<String> {
+    let time = config.timestamps.format(msg.timestamp);
+    let mut content = msg.content_lines().collect::<Vec<_>>().join("\n    ");
+    if let Some(forwarded) = &msg.forwarded {
+        content = format!("[forwarded from {}] {}", forwarded.label(), content);
+    }
+    if let Some(origin) = &msg.mirrored_from {
+        content = format!("[via #{}] {}", origin, content);
+    }
+    if let Some(warning) = &msg.link_warning {
+        content = format!("{}\n    ! {} is flagged as {}", content, warning.url, warning.threat);
+    }
+    if let Some(preview) = &msg.preview {
+        content = format!("{}\n    -> {}", content, preview.summary());
+    }
+    match msg.msg_type {
+        MessageType::Chat => Some(format!("{} #{} <{}> {}", time, msg.room, msg.username, content)),
+        MessageType::PrivateMessage => {
+            let recipient = msg.recipient.as_deref().unwrap_or_default();
+            Some(format!("{} [pm] {} -> {}: {}", time, msg.username, recipient, content))
+        }
+        MessageType::Error => Some(format!("{} ! {}", time, content)),
+        MessageType::Dice => Some(format!("{} #{} * {} {}", time, msg.room, msg.username, content)),
+        MessageType::System | MessageType::UserJoin | MessageType::UserLeave | MessageType::RoomChange => {
+            Some(format!("{} * {}", time, content))
+        }
+        MessageType::Discover => {
+            let discovery: Discovery = serde_json::from_str(&msg.content).unwrap_or_default();
+            let lines: Vec<String> = discovery
+                .rooms
+                .iter()
+                .map(|room| {
+                    let tags: String = room.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+                    let description = room.description.as_ref().map(|d| format!(" - {}", d)).unwrap_or_default();
+                    format!("* #{} ({} here, {} today){}{}", room.name, room.users, room.messages_today, tags, description)
+                })
+                .collect();
+            Some(format!("{} * {} rooms found\n{}", time, discovery.rooms.len(), lines.join("\n")).trim_end().to_string())
+        }
+        MessageType::Starred => {
+            let saved: Vec<ChatMessage> = serde_json::from_str(&msg.content).unwrap_or_default();
+            let lines: Vec<String> = saved.iter().map(|m| format!("* {} {} <{}> {}", m.id, m.room, m.username, m.content)).collect();
+            Some(format!("{} * {} starred\n{}", time, saved.len(), lines.join("\n")).trim_end().to_string())
+        }
+        MessageType::Profile => {
+            let profile: UserProfile = serde_json::from_str(&msg.content).unwrap_or_default();
+            let fields = [("display name", &profile.display_name), ("pronouns", &profile.pronouns), ("timezone", &profile.timezone), ("bio", &profile.bio)];
+            let details: Vec<String> = fields.iter().filter_map(|(label, value)| value.as_ref().map(|v| format!("{}: {}", label, v))).collect();
+            Some(format!("{} * profile of {}: {}", time, msg.username, if details.is_empty() { "empty".to_string() } else { details.join(", ") }))
+        }
+        MessageType::RoomList => {
+            let entries: Vec<RoomEntry> = serde_json::from_str(&msg.content).unwrap_or_default();
+            Some(format!("{} * rooms:\n{}", time, rooms::plain_lines(&entries).join("\n")))
+        }
+        MessageType::Stats => {
+            let stats: ServerStats = serde_json::from_str(&msg.content).unwrap_or_default();
+            let lines: Vec<String> = stats::rows(&stats, "en").into_iter().map(|(label, value)| format!("{}: {}", label, value)).collect();
+            Some(format!("{} * server stats:\n{}", time, lines.join("\n")))
+        }
+        MessageType::Console => {
+            let console: ConsoleState = serde_json::from_str(&msg.content).unwrap_or_default();
+            let lines: Vec<String> = console.connections.iter().map(|c| format!("{} #{} {}", c.username, c.room, c.addr)).collect();
+            Some(format!("{} * {} connections\n{}", time, console.connections.len(), lines.join("\n")).trim_end().to_string())
+        }
+        MessageType::Activity => {
+            let report: ActivityReport = serde_json::from_str(&msg.content).unwrap_or_default();
+            let busiest = report.busiest_hour().map(|hour| format!(", busiest around {:02}:00 UTC", hour)).unwrap_or_default();
+            let counts: Vec<String> = report.last_day.iter().map(u64::to_string).collect();
+            Some(format!("{} * #{}: {} messages in the last 24 hours{}\nper hour: {}", time, report.room, report.last_day.iter().sum::<u64>(), busiest, counts.join(" ")))
+        }
+        MessageType::Moderation => {
+            let moderation: ModerationState = serde_json::from_str(&msg.content).unwrap_or_default();
+            let reports = moderation.reports.iter().map(|r| format!("* {} {} <{}> {} ({})", r.message.id, r.message.room, r.message.username, r.message.content, r.reason));
+            let pins = moderation.pins.iter().map(|m| format!("* pinned {} <{}> {}", m.id, m.username, m.content));
+            let lines: Vec<String> = reports.chain(pins).collect();
+            Some(format!("{} * {} reports, {} pinned in {}\n{}", time, moderation.reports.len(), moderation.pins.len(), msg.room, lines.join("\n")).trim_end().to_string())
+        }
+        MessageType::Preview => {
+            let update: PreviewUpdate = serde_json::from_str(&msg.content).ok()?;
+            Some(format!("{} * link: {}", time, update.preview.summary()))
+        }
+        MessageType::Expired => Some(format!("{} * {} expired messages removed from #{}", time, msg.content.split(',').count(), msg.room)),
+        // e.g. "? Pick a room to start in [general, lounge] (/answer room <value>)"
+        MessageType::Prompt => {
+            let prompt: Prompt = serde_json::from_str(&msg.content).ok()?;
+            let choices = match &prompt.field {
+                PromptField::Text { value, .. } if value.is_empty() => String::new(),
+                PromptField::Text { value, .. } => format!(" [{}]", value),
+                PromptField::Choice { options, .. } => format!(" [{}]", options.iter().map(|option| option.value.as_str()).collect::<Vec<_>>().join(", ")),
+            };
+            Some(format!("{} ? {}{} (/answer {} <value>)", time, tr("en", &prompt.text), choices, prompt.id))
+        }
+        MessageType::Pong | MessageType::UserList | MessageType::AutoJoin | MessageType::Commands | MessageType::Karma | MessageType::ReadReceipt | MessageType::Presence | MessageType::AuthRequired | MessageType::Capabilities | MessageType::Compression | MessageType::Encoding | MessageType::Unknown => None,
+    }
+}
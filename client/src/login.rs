@@ -0,0 +1,357 @@
+use common::i18n::{tr, trf};
+use common::{ChatMessage, Handshake, MessageType};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Paragraph, Wrap},
+};
+use std::io;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::tcp::OwnedWriteHalf;
+use tui_input::{backend::crossterm::EventHandler, Input};
+
+use crate::centered_rect;
+use crate::compression::{self, ServerReader};
+use crate::config::{ClientConfig, Profile};
+
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 8080;
+
+// What the login form hands to the connection code
+pub struct Login {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+    pub username: String,
+    pub password: Option<String>, // Never stored in profiles
+    pub guest: bool,              // The server picks a temporary name; `username` is unused
+}
+
+impl Login {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Profiles,
+    Host,
+    Port,
+    Tls,
+    Username,
+    Password,
+}
+
+const FIELDS: [Field; 6] = [Field::Profiles, Field::Host, Field::Port, Field::Tls, Field::Username, Field::Password];
+
+struct Form {
+    host: Input,
+    port: Input,
+    tls: bool,
+    username: Input,
+    password: Input,
+    focus: Field,
+    selected: usize, // Highlighted saved profile
+    notice: Option<String>,
+}
+
+impl Form {
+    fn new(config: &ClientConfig) -> Self {
+        let mut form = Form {
+            host: Input::new(DEFAULT_HOST.to_string()),
+            port: Input::new(DEFAULT_PORT.to_string()),
+            tls: false,
+            username: Input::default(),
+            password: Input::default(),
+            focus: Field::Username,
+            selected: 0,
+            notice: None,
+        };
+        let last = config.profiles.iter().position(|p| Some(&p.name) == config.last_profile.as_ref());
+        if let Some(index) = last {
+            form.selected = index;
+            form.load(&config.profiles[index]);
+            // Straight to the password when the profile already names the user
+            form.focus = if form.username.value().is_empty() { Field::Username } else { Field::Password };
+        }
+        form
+    }
+
+    fn load(&mut self, profile: &Profile) {
+        self.host = Input::new(profile.host.clone());
+        self.port = Input::new(profile.port.to_string());
+        self.tls = profile.tls;
+        self.username = Input::new(profile.username.clone());
+        self.password.reset();
+    }
+
+    fn focused_input(&mut self) -> Option<&mut Input> {
+        match self.focus {
+            Field::Host => Some(&mut self.host),
+            Field::Port => Some(&mut self.port),
+            Field::Username => Some(&mut self.username),
+            Field::Password => Some(&mut self.password),
+            Field::Profiles | Field::Tls => None,
+        }
+    }
+
+    // Tab order, skipping the profile list while there is nothing in it
+    fn move_focus(&mut self, forward: bool, has_profiles: bool) {
+        let fields: Vec<Field> = FIELDS.iter().copied().filter(|f| has_profiles || *f != Field::Profiles).collect();
+        let pos = fields.iter().position(|f| *f == self.focus).unwrap_or(0);
+        let next = if forward { (pos + 1) % fields.len() } else { (pos + fields.len() - 1) % fields.len() };
+        self.focus = fields[next];
+    }
+
+    fn submit(&self, guest: bool, locale: &str) -> Result<Login, String> {
+        let username = if guest { "" } else { self.username.value().trim() };
+        if username.is_empty() && !guest {
+            return Err(tr(locale, "ui.username_required").to_string());
+        }
+        let port = match self.port.value().trim().parse::<u16>() {
+            Ok(port) if port > 0 => port,
+            _ => return Err(tr(locale, "ui.invalid_port").to_string()),
+        };
+        let password = if guest { "" } else { self.password.value() };
+        Ok(Login {
+            host: self.host.value().trim().to_string(),
+            port,
+            tls: self.tls,
+            username: username.to_string(),
+            password: (!password.is_empty()).then(|| password.to_string()),
+            guest,
+        })
+    }
+}
+
+// Multi-field login form with saved profiles; profiles are written to the client config
+pub fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, config: &mut ClientConfig) -> io::Result<Login> {
+    let locale = config.locale();
+    let mut form = Form::new(config);
+    loop {
+        terminal.draw(|f| draw(f, &form, &config.profiles, locale))?;
+
+        let Event::Key(key) = event::read()? else { continue };
+        let has_profiles = !config.profiles.is_empty();
+        form.notice = None;
+        match key.code {
+            KeyCode::Esc => return Err(io::Error::new(io::ErrorKind::Interrupted, "Quit")),
+            KeyCode::Enter => match form.submit(false, locale) {
+                Ok(login) => {
+                    let profile_name = Profile::name_for(&login.username, &login.host, login.port);
+                    if config.profiles.iter().any(|p| p.name == profile_name) && config.last_profile.as_ref() != Some(&profile_name) {
+                        config.last_profile = Some(profile_name);
+                        let _ = config.save();
+                    }
+                    return Ok(login);
+                }
+                Err(e) => form.notice = Some(e),
+            },
+            KeyCode::Tab => form.move_focus(true, has_profiles),
+            KeyCode::BackTab => form.move_focus(false, has_profiles),
+            KeyCode::Up | KeyCode::Down if form.focus == Field::Profiles => {
+                let len = config.profiles.len();
+                form.selected = if key.code == KeyCode::Down { (form.selected + 1) % len } else { (form.selected + len - 1) % len };
+                form.load(&config.profiles[form.selected]);
+            }
+            KeyCode::Up => form.move_focus(false, has_profiles),
+            KeyCode::Down => form.move_focus(true, has_profiles),
+            KeyCode::Delete if form.focus == Field::Profiles => {
+                let removed = config.profiles.remove(form.selected);
+                form.selected = form.selected.min(config.profiles.len().saturating_sub(1));
+                if config.profiles.is_empty() {
+                    form.focus = Field::Host;
+                }
+                form.notice = Some(match config.save() {
+                    Ok(()) => trf(locale, "ui.profile_removed", &[&removed.name]),
+                    Err(e) => e.to_string(),
+                });
+            }
+            KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => match form.submit(true, locale) {
+                Ok(login) => return Ok(login),
+                Err(e) => form.notice = Some(e),
+            },
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => match form.submit(false, locale) {
+                Ok(login) => {
+                    let profile = Profile::from_login(&login);
+                    let name = profile.name.clone();
+                    match config.profiles.iter().position(|p| p.name == name) {
+                        Some(index) => config.profiles[index] = profile,
+                        None => config.profiles.push(profile),
+                    }
+                    form.selected = config.profiles.iter().position(|p| p.name == name).unwrap_or(0);
+                    config.last_profile = Some(name.clone());
+                    form.notice = Some(match config.save() {
+                        Ok(()) => trf(locale, "ui.profile_saved", &[&name]),
+                        Err(e) => e.to_string(),
+                    });
+                }
+                Err(e) => form.notice = Some(e),
+            },
+            KeyCode::Char(' ') if form.focus == Field::Tls => form.tls = !form.tls,
+            _ => {
+                if let Some(input) = form.focused_input() {
+                    input.handle_event(&Event::Key(key));
+                }
+            }
+        }
+    }
+}
+
+// Sends the handshake and answers password, two-factor, registration and captcha challenges
+// until the server lets us in; returns its first message, or why it refused
+pub async fn handshake(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    reader: &mut ServerReader,
+    writer: &mut OwnedWriteHalf,
+    handshake: &mut Handshake,
+    locale: &str,
+) -> io::Result<Result<ChatMessage, String>> {
+    let mut notice = None;
+    let mut line = String::new();
+    loop {
+        writer.write_all(format!("{}\n", serde_json::to_string(handshake)?).as_bytes()).await?;
+        let kind = loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(Err(tr(locale, "ui.connection_closed").to_string()));
+            }
+            let text = line.trim();
+            if let Some(error) = text.strip_prefix("Error:") {
+                return Ok(Err(error.trim().to_string()));
+            }
+            let Ok(msg) = ChatMessage::from_json(text) else { continue };
+            match msg.msg_type {
+                MessageType::AuthRequired => break msg.content,
+                MessageType::Compression => *reader = compression::inflating(reader),
+                MessageType::Encoding => *reader = compression::unpacking(reader),
+                MessageType::Error => notice = Some(msg.content), // Why the last attempt failed
+                _ => return Ok(Ok(msg)),
+            }
+        };
+        if let Some(question) = kind.strip_prefix("captcha: ") {
+            let message = trf(locale, "ui.captcha_required", &[question]);
+            handshake.captcha = Some(prompt(terminal, &message, notice.take(), false, locale)?);
+            continue;
+        }
+        match kind.as_str() {
+            "totp" => handshake.totp = Some(prompt(terminal, tr(locale, "ui.totp_required"), notice.take(), false, locale)?),
+            "register" => loop {
+                let message = trf(locale, "ui.confirm_register", &[&handshake.username]);
+                let again = prompt(terminal, &message, notice.take(), true, locale)?;
+                if handshake.password.as_ref() == Some(&again) {
+                    handshake.register = true;
+                    break;
+                }
+                notice = Some(tr(locale, "ui.password_mismatch").to_string());
+            },
+            _ => handshake.password = Some(prompt(terminal, tr(locale, "ui.password_required"), notice.take(), true, locale)?),
+        }
+    }
+}
+
+// Single-field dialog for secrets requested mid-handshake
+fn prompt(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, message: &str, notice: Option<String>, masked: bool, locale: &str) -> io::Result<String> {
+    let mut input = Input::default();
+    loop {
+        terminal.draw(|f| {
+            let area = centered_rect(60, 40, f.area());
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" {} ", tr(locale, "ui.login")))
+                .border_type(BorderType::Rounded)
+                .style(Style::default().fg(Color::Cyan));
+            f.render_widget(block, area);
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .margin(2)
+                .constraints([Constraint::Length(2), Constraint::Length(3), Constraint::Min(1)])
+                .split(area);
+            f.render_widget(Paragraph::new(message).wrap(Wrap { trim: true }), chunks[0]);
+            let text = if masked { "•".repeat(input.value().chars().count()) } else { input.value().to_string() };
+            f.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), chunks[1]);
+            f.set_cursor_position(Position::new(chunks[1].x + 1 + input.visual_cursor() as u16, chunks[1].y + 1));
+            let hint = match &notice {
+                Some(notice) => Paragraph::new(notice.as_str()).style(Style::default().fg(Color::Yellow)),
+                None => Paragraph::new(tr(locale, "ui.prompt_hint")).style(Style::default().fg(Color::DarkGray)),
+            };
+            f.render_widget(hint, chunks[2]);
+        })?;
+
+        let Event::Key(key) = event::read()? else { continue };
+        match key.code {
+            KeyCode::Esc => return Err(io::Error::new(io::ErrorKind::Interrupted, "Quit")),
+            KeyCode::Enter if !input.value().is_empty() => return Ok(input.value().to_string()),
+            _ => {
+                input.handle_event(&Event::Key(key));
+            }
+        }
+    }
+}
+
+fn draw(f: &mut Frame, form: &Form, profiles: &[Profile], locale: &str) {
+    let area = centered_rect(60, 70, f.area());
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" {} ", tr(locale, "ui.login")))
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::Cyan));
+    f.render_widget(block, area);
+
+    let profile_height = if profiles.is_empty() { 0 } else { profiles.len().min(4) as u16 + 2 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(profile_height),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(2),
+        ])
+        .split(area);
+
+    f.render_widget(Paragraph::new(tr(locale, "ui.welcome")).alignment(Alignment::Center), chunks[0]);
+
+    let field_block = |field: Field, title: &str| {
+        let style = if form.focus == field { Style::default().fg(Color::Yellow) } else { Style::default() };
+        Block::default().borders(Borders::ALL).title(format!(" {} ", title)).border_style(style)
+    };
+
+    if !profiles.is_empty() {
+        let items: Vec<ListItem> = profiles.iter().map(|p| ListItem::new(p.name.as_str())).collect();
+        let list = List::new(items)
+            .block(field_block(Field::Profiles, tr(locale, "ui.profiles")))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(form.selected));
+        f.render_stateful_widget(list, chunks[1], &mut state);
+    }
+
+    let server_row = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(10), Constraint::Length(10), Constraint::Length(9)])
+        .split(chunks[2]);
+    let tls = if form.tls { "[x]" } else { "[ ]" };
+    let password = "•".repeat(form.password.value().chars().count());
+    let inputs = [
+        (Field::Host, server_row[0], tr(locale, "ui.server"), form.host.value().to_string(), &form.host),
+        (Field::Port, server_row[1], tr(locale, "ui.port"), form.port.value().to_string(), &form.port),
+        (Field::Username, chunks[3], tr(locale, "ui.username"), form.username.value().to_string(), &form.username),
+        (Field::Password, chunks[4], tr(locale, "ui.password"), password, &form.password),
+    ];
+    for (field, rect, title, text, input) in inputs {
+        f.render_widget(Paragraph::new(text).block(field_block(field, title)), rect);
+        if form.focus == field {
+            f.set_cursor_position(Position::new(rect.x + 1 + input.visual_cursor() as u16, rect.y + 1));
+        }
+    }
+    f.render_widget(Paragraph::new(tls).block(field_block(Field::Tls, tr(locale, "ui.tls"))), server_row[2]);
+
+    let hint = match &form.notice {
+        Some(notice) => Paragraph::new(notice.as_str()).style(Style::default().fg(Color::Yellow)),
+        None => Paragraph::new(tr(locale, "ui.login_hint")).style(Style::default().fg(Color::DarkGray)),
+    };
+    f.render_widget(hint, chunks[5]);
+}
@@ -1,231 +1,1560 @@
-use common::{ChatMessage, MessageType, Handshake};
+mod activity;
+mod complete;
+mod compression;
+mod config;
+mod connect;
+mod discover;
+mod console;
+mod events;
+mod export;
+mod form;
+mod headless;
+mod login;
+mod moderation;
+mod notify;
+mod profile_card;
+mod render_cache;
+mod rooms;
+mod rules;
+mod scrollback;
+mod search;
+mod spell;
+mod spoiler;
+mod starred;
+mod stats;
+mod switcher;
+mod triggers;
+#[cfg(test)]
+mod ui_tests;
+
+use common::{deflate, msgpack, i18n::{tr, trf}, ActivityReport, ChatError, ChatMessage, CommandError, CommandInfo, ErrorReason, KarmaScores, MessageId, MessageType, Handshake, ModerationState, PreviewUpdate, Prompt, RoomEntry, RoomName, ServerCapabilities, ServerStats, LINE_SEPARATOR};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, DisableBracketedPaste, DisableFocusChange, EnableBracketedPaste, EnableFocusChange, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, List, ListItem, Paragraph, BorderType, Clear},
+    widgets::{Block, Borders, List, ListItem, Paragraph, BorderType, Clear, Wrap},
 };
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
-use tui_input::{backend::crossterm::EventHandler, Input};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, Mutex};
+use tui_input::{backend::crossterm::EventHandler, Input, InputRequest};
+
+use complete::Completion;
+use compression::ServerReader;
+use config::{ClientConfig, QuietMode};
+use discover::DiscoverPanel;
+use events::TerminalEvents;
+use form::Form;
+use moderation::ModPanel;
+use notify::{NotifyLevel, QuietHours};
+use rules::Rules;
+use scrollback::Scrollback;
+use search::Search;
+use spell::{SpellChecker, SpellPopup};
+use profile_card::ProfileCard;
+use render_cache::RenderCache;
+use starred::StarredPanel;
+use switcher::{Switcher, Target};
+use triggers::Triggers;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+const TICK: Duration = Duration::from_secs(1); // Redraws the clock and countdowns while nothing else happens
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+const APP_EVENT_QUEUE: usize = 1024; // Server messages waiting for the UI; the reader waits when it's full
+
+// What the network reader hands the UI loop, which owns the app state
+enum AppEvent {
+    Message(Box<ChatMessage>),
+    Disconnected,
+}
 
 // UI State
 struct App {
     messages: Vec<ChatMessage>,
     input: Input,
     username: String,
-    current_room: String,
-    users_in_room: Vec<String>, // Maintained via system messages for simplicity in this demo
+    current_room: RoomName,
+    users_in_room: Vec<String>, // Seeded by the server's UserList, then kept current via joins/leaves
+    karma: HashMap<String, u64>, // Scores in the current room, when it has karma on
+    rooms: Vec<RoomEntry>, // From the server's RoomList, refreshed on every join and by /rooms
+    rooms_requested: bool, // /rooms was typed, so the reply is also shown as a message
     connected: bool,
+    reconnect_at: Option<Instant>, // Next automatic reconnect, set once the connection drops
+    reconnect_delay: Duration,     // Doubles after every failed attempt
+    reconnect_error: Option<String>,
+    latency: Option<Duration>,
+    pending_ping: Option<(String, Instant)>,
+    unread: usize, // Messages that arrived while scrolled up
     scroll_offset: usize,
     auto_scroll: bool,
     show_help: bool,
+    multiline: bool, // Set once the draft contains a newline (paste or Alt+Enter)
+    drafts: HashMap<RoomName, String>, // Unsent input per room, restored when switching back
+    visited_rooms: Vec<RoomName>,
+    config: ClientConfig,
+    locale: &'static str,
+    reveal_ignored: bool,
+    rules: Rules,
+    triggers: Triggers,
+    outbox: Vec<String>, // Lines queued by triggers, sent by the main loop
+    away_users: HashMap<String, String>, // Room members (possibly us) -> away reason
+    auto_away: bool, // We went away automatically, so the next keypress returns us
+    last_input: Instant,
+    scrollback: Scrollback,
+    seen_ids: HashSet<MessageId>, // Dedupes server history against restored scrollback
+    switcher: Option<Switcher>,
+    pm_contacts: Vec<String>, // Most recent first
+    search: Option<Search>,
+    spell: Option<SpellChecker>, // None when disabled or no dictionary is installed
+    spell_popup: Option<SpellPopup>,
+    jump_to: Option<MessageId>, // Message ID to scroll into view on the next draw
+    focused: Option<MessageId>, // Last search hit or Alt+Up/Down selection, drawn highlighted
+    revealed: HashSet<MessageId>, // Messages whose spoilers were revealed
+    starred: Option<StarredPanel>,
+    mod_panel: Option<ModPanel>,
+    profile_card: Option<ProfileCard>,
+    render_cache: RenderCache,
+    stats: Option<ServerStats>,
+    activity: Option<ActivityReport>,
+    discover: Option<DiscoverPanel>,
+    form: Option<Form>, // The server's latest question, e.g. while onboarding
+    capabilities: Option<ServerCapabilities>, // None until the server says, or from servers that never do
+    pending_jump: Option<MessageId>, // Message to jump to once it arrives, e.g. after joining its room
+    pending_goto: Option<(String, u64)>, // Same for a `/goto #room/seq` permalink
+    read_markers: HashMap<String, MessageId>, // Room member -> last message they read
+    pm_read: HashMap<String, MessageId>,   // PM partner -> last of our PMs they read
+    last_read_sent: Option<MessageId>,
+    last_pm_read_sent: Option<MessageId>,
+    last_sent: Option<String>, // The last chat line sent, put back if the server turns it away
+    cooldown: Option<(chrono::DateTime<chrono::Utc>, &'static str)>, // Rate-limited or muted until then; the hint to show
+    length_limit: Option<usize>, // Set once a message came back too long, so the counter stays up
+    commands: Vec<CommandInfo>, // The server's registry, offered as `/` is typed
+    completion: Completion,
+    usage: Option<CommandError>, // The last command the server said was used wrong, shown under the input until the next send
 }
 
 impl App {
-    fn new(username: String) -> Self {
+    // `profile` keys the scrollback on disk, e.g. `alice@localhost:8080`
+    fn new(username: String, profile: &str, config: ClientConfig) -> Self {
+        let (rules, rule_errors) = Rules::compile(&config);
+        let (triggers, trigger_errors) = Triggers::compile(&config);
+        let spell = load_spellchecker(&config);
         Self {
-            messages: vec![],
+            messages: rule_errors.into_iter().chain(trigger_errors).map(ChatMessage::error).collect(),
             input: Input::default(),
             username,
-            current_room: "general".to_string(),
+            current_room: RoomName::general(),
             users_in_room: vec![], 
+            karma: HashMap::new(),
+            rooms: Vec::new(),
+            rooms_requested: false,
             connected: false,
+            reconnect_at: None,
+            reconnect_delay: RECONNECT_DELAY,
+            reconnect_error: None,
+            latency: None,
+            pending_ping: None,
+            unread: 0,
             scroll_offset: 0,
             auto_scroll: true,
             show_help: false,
+            multiline: false,
+            drafts: HashMap::new(),
+            visited_rooms: vec![RoomName::general()],
+            scrollback: Scrollback::new(config.scrollback_limit, profile),
+            locale: config.locale(),
+            config,
+            reveal_ignored: false,
+            rules,
+            triggers,
+            outbox: Vec::new(),
+            away_users: HashMap::new(),
+            auto_away: false,
+            last_input: Instant::now(),
+            seen_ids: HashSet::new(),
+            switcher: None,
+            pm_contacts: Vec::new(),
+            search: None,
+            spell,
+            spell_popup: None,
+            jump_to: None,
+            focused: None,
+            revealed: HashSet::new(),
+            starred: None,
+            mod_panel: None,
+            profile_card: None,
+            render_cache: RenderCache::default(),
+            stats: None,
+            activity: None,
+            discover: None,
+            form: None,
+            capabilities: None,
+            pending_jump: None,
+            pending_goto: None,
+            read_markers: HashMap::new(),
+            pm_read: HashMap::new(),
+            last_read_sent: None,
+            last_pm_read_sent: None,
+            last_sent: None,
+            cooldown: None,
+            length_limit: None,
+            commands: Vec::new(),
+            completion: Completion::default(),
+            usage: None,
+        }
+    }
+
+    fn apply(&mut self, event: AppEvent) {
+        match event {
+            AppEvent::Message(msg) => self.handle_message(*msg),
+            AppEvent::Disconnected => self.connected = false,
+        }
+    }
+
+    // Most characters the server takes in one message
+    fn max_message_len(&self) -> usize {
+        self.length_limit
+            .or(self.capabilities.as_ref().map(|capabilities| capabilities.max_message_len).filter(|len| *len > 0))
+            .unwrap_or(common::MAX_CONTENT_LEN)
+    }
+
+    // Whole seconds, rounded up, until the server takes chat again
+    fn cooldown_left(&self, now: chrono::DateTime<chrono::Utc>) -> Option<i64> {
+        let (until, _) = self.cooldown?;
+        let left = (until - now).num_milliseconds();
+        (left > 0).then(|| (left + 999) / 1000)
+    }
+
+    // Chat the server would refuse anyway: too soon, or too long. Commands always go through
+    fn chat_blocked(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        !self.input.value().trim_start().starts_with('/') && (self.cooldown_left(now).is_some() || self.input.value().chars().count() > self.max_message_len())
+    }
+
+    // Whether the server said this user may not post in the current room
+    fn read_only(&self) -> bool {
+        self.rooms.iter().any(|room| room.name == self.current_room.as_str() && room.read_only)
+    }
+
+    fn supports(&self, feature: &str) -> bool {
+        self.capabilities.as_ref().is_none_or(|capabilities| capabilities.supports(feature))
+    }
+
+    fn handle_message(&mut self, msg: ChatMessage) {
+        // Handle room changes to clear/update UI state
+        if msg.msg_type == MessageType::RoomChange && msg.username == self.username {
+            self.switch_room(msg.room.clone());
+            // Start from the persisted scrollback; the server's history replay fills in the rest
+            self.messages = self.scrollback.load(&msg.room);
+            self.seen_ids = self.messages.iter().map(|m| m.id).collect();
+            self.users_in_room.clear();
+            self.karma.clear();
+            self.read_markers.clear();
+            self.away_users.clear();
+            self.unread = 0;
+        }
+
+        match msg.msg_type {
+            // Its content may be a payload meant for newer clients, so it isn't shown
+            MessageType::Unknown => return,
+            // Shown by the input instead of as a line: a countdown, or a character counter
+            MessageType::Error if msg.reason.is_some() => {
+                let restore = match msg.reason {
+                    Some(ErrorReason::RateLimited { retry_after }) => {
+                        self.cooldown = Some((chrono::Utc::now() + chrono::Duration::seconds(retry_after as i64), "ui.cooldown"));
+                        true
+                    }
+                    Some(ErrorReason::TooLong { limit }) => {
+                        self.length_limit = Some(limit);
+                        true
+                    }
+                    Some(ErrorReason::Muted { until }) => {
+                        self.cooldown = Some((until, "ui.muted_until"));
+                        false
+                    }
+                    Some(ErrorReason::Usage(error)) => {
+                        self.usage = Some(error);
+                        true
+                    }
+                    None => false,
+                };
+                // A mute can come unasked; the others answer what was just sent
+                if restore && self.input.value().is_empty() {
+                    if let Some(sent) = self.last_sent.take() {
+                        let sent = sent.replace(LINE_SEPARATOR, "\n");
+                        self.multiline = sent.contains('\n');
+                        self.input = Input::new(sent);
+                    }
+                }
+                return;
+            }
+            MessageType::Pong => {
+                if let Some((token, sent)) = self.pending_ping.take() {
+                    if token == msg.content {
+                        self.latency = Some(sent.elapsed());
+                    }
+                }
+                return;
+            }
+            MessageType::UserList => {
+                self.users_in_room = msg.content.split(',').filter(|u| !u.is_empty()).map(String::from).collect();
+                return;
+            }
+            MessageType::Expired => {
+                let ids: HashSet<MessageId> = msg.content.split(',').filter_map(|id| id.parse().ok()).collect();
+                self.messages.retain(|m| m.room != msg.room || !ids.contains(&m.id));
+                if let Err(e) = self.scrollback.remove(&msg.room, &ids) {
+                    self.messages.push(ChatMessage::error(format!("Could not update scrollback: {}", e)));
+                }
+                return;
+            }
+            MessageType::Preview => {
+                let Ok(update) = serde_json::from_str::<PreviewUpdate>(&msg.content) else { return };
+                if let Some(target) = self.messages.iter_mut().find(|m| m.id == update.message) {
+                    target.preview = Some(update.preview);
+                    self.render_cache.forget(update.message);
+                }
+                return;
+            }
+            MessageType::RoomList => {
+                self.rooms = serde_json::from_str(&msg.content).unwrap_or_default();
+                if std::mem::take(&mut self.rooms_requested) {
+                    let tree = rooms::plain_lines(&self.rooms).join(&LINE_SEPARATOR.to_string());
+                    self.messages.push(ChatMessage::system(format!("Rooms:{}{}", LINE_SEPARATOR, tree), self.current_room.clone()));
+                }
+                return;
+            }
+            MessageType::ReadReceipt => {
+                let Ok(id) = msg.content.parse() else { return };
+                if msg.room == RoomName::private() {
+                    self.pm_read.insert(msg.username, id);
+                } else if msg.room == self.current_room && msg.username != self.username {
+                    self.read_markers.insert(msg.username, id);
+                }
+                return;
+            }
+            MessageType::UserJoin if !self.users_in_room.contains(&msg.username) => {
+                self.users_in_room.push(msg.username.clone());
+            }
+            MessageType::Starred => {
+                self.starred = Some(StarredPanel::from_json(&msg.content));
+                return;
+            }
+            MessageType::Profile => {
+                self.profile_card = Some(ProfileCard::from_json(msg.username, &msg.content));
+                return;
+            }
+            MessageType::Stats => {
+                self.stats = Some(serde_json::from_str(&msg.content).unwrap_or_default());
+                return;
+            }
+            MessageType::Activity => {
+                self.activity = serde_json::from_str(&msg.content).ok();
+                return;
+            }
+            // Kept at hand for Alt+Left/Right and Ctrl+K, as if visited already
+            MessageType::AutoJoin => {
+                let rooms: Vec<RoomName> = serde_json::from_str(&msg.content).unwrap_or_default();
+                for room in rooms {
+                    if !self.visited_rooms.contains(&room) {
+                        self.visited_rooms.push(room);
+                    }
+                }
+                return;
+            }
+            MessageType::Commands => {
+                self.commands = serde_json::from_str(&msg.content).unwrap_or_default();
+                return;
+            }
+            MessageType::Discover => {
+                self.discover = Some(DiscoverPanel::from_json(&msg.content));
+                return;
+            }
+            MessageType::Prompt => {
+                self.form = serde_json::from_str::<Prompt>(&msg.content).ok().map(Form::new);
+                return;
+            }
+            MessageType::Karma if msg.room == self.current_room => {
+                let update: KarmaScores = serde_json::from_str(&msg.content).unwrap_or_default();
+                if update.replace {
+                    self.karma.clear();
+                }
+                self.karma.extend(update.scores);
+                return;
+            }
+            MessageType::Karma => return,
+            MessageType::Moderation => {
+                let state: ModerationState = serde_json::from_str(&msg.content).unwrap_or_default();
+                self.mod_panel.get_or_insert_with(ModPanel::default).refresh(state);
+                return;
+            }
+            MessageType::Console => {
+                self.messages.push(ChatMessage::system(tr(self.locale, "ui.console_elsewhere").to_string(), self.current_room.clone()));
+                return;
+            }
+            MessageType::Capabilities => {
+                self.capabilities = serde_json::from_str(&msg.content).ok();
+                return;
+            }
+            MessageType::Presence => {
+                if msg.content.is_empty() {
+                    self.away_users.remove(&msg.username);
+                    if msg.username == self.username {
+                        self.auto_away = false;
+                    }
+                } else {
+                    self.away_users.insert(msg.username, msg.content);
+                }
+                return;
+            }
+            MessageType::UserLeave => {
+                self.users_in_room.retain(|u| *u != msg.username);
+                self.away_users.remove(&msg.username);
+            }
+            MessageType::PrivateMessage => {
+                let contact = if msg.username == self.username { msg.recipient.clone().map(String::from) } else { Some(msg.username.clone()) };
+                if let Some(contact) = contact {
+                    self.pm_contacts.retain(|c| *c != contact);
+                    self.pm_contacts.insert(0, contact);
+                }
+            }
+            _ => {}
+        }
+
+        if !self.seen_ids.insert(msg.id) {
+            return;
+        }
+        let replies = self.triggers.fire(&msg, &self.username);
+        self.outbox.extend(replies);
+        // Older messages fetched by /goto would land out of order in the scrollback file
+        let newest = self.messages.last().is_none_or(|m| m.timestamp <= msg.timestamp);
+        // Rooms with a time-to-live are never written to disk
+        let ephemeral = self.rooms.iter().any(|r| r.name == msg.room && r.ttl.is_some());
+        if msg.room == self.current_room && newest && !ephemeral && Scrollback::should_persist(&msg) && self.config.scrollback_limit > 0 {
+            if let Err(e) = self.scrollback.append(&msg) {
+                self.messages.push(ChatMessage::error(format!("Could not save scrollback: {}", e)));
+            }
+        }
+
+        // Replayed history is old news and shouldn't alert
+        let fresh = chrono::Utc::now().signed_duration_since(msg.timestamp) < chrono::Duration::seconds(30);
+        let decision = self.config.notifications.decide(&msg, &self.username, self.rules.should_notify(&msg));
+        if decision.alert && fresh {
+            self.config.notifications.alert(&msg);
+        }
+
+        let goto = self.pending_goto.as_ref().is_some_and(|(room, seq)| msg.room == *room && msg.seq == Some(*seq));
+        if goto || self.pending_jump.as_ref() == Some(&msg.id) {
+            self.pending_jump = None;
+            self.pending_goto = None;
+            self.jump_to = Some(msg.id);
+            self.focused = Some(msg.id);
+        }
+        // History replays can interleave with restored scrollback, so keep timestamp order
+        let pos = self.messages.iter().rposition(|m| m.timestamp <= msg.timestamp).map_or(0, |i| i + 1);
+        self.messages.insert(pos, msg);
+        if self.auto_scroll {
+            self.scroll_offset = 0;
+        } else if decision.unread {
+            self.unread += 1;
+        }
+    }
+
+    // `/read` commands for the newest room message and PM from others, once each
+    fn pending_read_receipts(&mut self) -> Vec<String> {
+        let mut commands = Vec::new();
+        let from_others = |m: &&ChatMessage| m.username != self.username;
+        let latest_room = self.messages.iter().rev().filter(from_others).find(|m| m.msg_type == MessageType::Chat && m.room == self.current_room).map(|m| m.id);
+        let latest_pm = self.messages.iter().rev().filter(from_others).find(|m| m.msg_type == MessageType::PrivateMessage).map(|m| m.id);
+        if latest_room.is_some() && latest_room != self.last_read_sent {
+            commands.extend(latest_room.iter().map(|id| format!("/read {}", id)));
+            self.last_read_sent = latest_room;
+        }
+        if latest_pm.is_some() && latest_pm != self.last_pm_read_sent {
+            commands.extend(latest_pm.iter().map(|id| format!("/read {}", id)));
+            self.last_pm_read_sent = latest_pm;
+        }
+        commands
+    }
+
+    // "seen by N" labels for our own room messages and ✓✓ for PMs the recipient has read
+    fn receipt_labels(&self) -> HashMap<MessageId, String> {
+        let mut labels = HashMap::new();
+        if self.read_markers.is_empty() && self.pm_read.is_empty() {
+            return labels;
+        }
+        let position: HashMap<MessageId, usize> = self.messages.iter().enumerate().map(|(i, m)| (m.id, i)).collect();
+        let read_up_to: Vec<usize> = self.read_markers.values().filter_map(|id| position.get(id).copied()).collect();
+        for (i, msg) in self.messages.iter().enumerate().filter(|(_, m)| m.username == self.username) {
+            match msg.msg_type {
+                MessageType::Chat => {
+                    let seen = read_up_to.iter().filter(|&&read| read >= i).count();
+                    if seen > 0 {
+                        labels.insert(msg.id, format!(" ✓ seen by {}", seen));
+                    }
+                }
+                MessageType::PrivateMessage => {
+                    let read = msg.recipient.as_ref().and_then(|r| self.pm_read.get(r.as_str())).and_then(|id| position.get(id));
+                    if read.is_some_and(|&read| read >= i) {
+                        labels.insert(msg.id, " ✓✓".to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        labels
+    }
+
+    // Keys while a server form is open; returns the answer once it is submitted. The
+    // notifications question is kept here, as `/notify` would.
+    fn handle_form_key(&mut self, key: event::KeyEvent) -> Option<String> {
+        let answer = self.form.as_mut()?.handle_key(key)?;
+        let form = self.form.take()?;
+        let level = match form.value().as_str() {
+            "all" => Some(NotifyLevel::All),
+            "mentions" => Some(NotifyLevel::Mentions),
+            "none" => Some(NotifyLevel::Nothing),
+            _ => None,
+        };
+        if let Some(level) = level.filter(|_| form.prompt.id == "notifications" && key.code != KeyCode::Esc) {
+            self.config.notifications.default_level = level;
+            self.save_config();
+        }
+        Some(answer)
+    }
+
+    // Keys while the Ctrl+K switcher is open; returns a command to send, if any
+    fn handle_switcher_key(&mut self, key: event::KeyEvent) -> Option<String> {
+        let switcher = self.switcher.as_mut()?;
+        let targets = switcher.matches(&self.visited_rooms, &self.pm_contacts);
+        match key.code {
+            KeyCode::Esc => self.switcher = None,
+            KeyCode::Up => switcher.move_selection(-1, targets.len()),
+            KeyCode::Down | KeyCode::Tab => switcher.move_selection(1, targets.len()),
+            KeyCode::Enter => {
+                let target = targets.get(switcher.selected).cloned();
+                let forward = switcher.forward.take();
+                self.switcher = None;
+                if let Some(id) = forward {
+                    return Some(match target? {
+                        Target::Room(room) => format!("/forward {} #{}", id, room),
+                        Target::Contact(user) => format!("/forward {} @{}", id, user),
+                    });
+                }
+                match target? {
+                    Target::Room(room) if room == self.current_room => {}
+                    Target::Room(room) => return Some(format!("/join {}", room)),
+                    Target::Contact(user) => {
+                        // Turn the current draft (if any) into a PM to that contact
+                        let draft = format!("/msg {} {}", user, self.input.value());
+                        self.input = Input::new(draft);
+                    }
+                }
+            }
+            _ => {
+                switcher.input.handle_event(&Event::Key(key));
+                switcher.selected = 0;
+            }
         }
+        None
+    }
+
+    // Scrolls to a loaded message now, or to one in another room once its history arrives;
+    // returns the command that switches rooms, if needed
+    fn jump_to_message(&mut self, msg: &ChatMessage) -> Option<String> {
+        if self.messages.iter().any(|m| m.id == msg.id) {
+            self.jump_to = Some(msg.id);
+            self.focused = Some(msg.id);
+            return None;
+        }
+        self.pending_jump = Some(msg.id);
+        let other_room = msg.msg_type != MessageType::PrivateMessage && msg.room != self.current_room;
+        other_room.then(|| format!("/join {}", msg.room))
+    }
+
+    // `/goto #room/seq`: scroll to a loaded message, otherwise join its room if needed
+    // and ask the server for the history around it
+    fn goto(&mut self, reference: &str) {
+        let Some((room, seq)) = common::parse_reference(reference) else {
+            self.usage = Some(CommandError::new("/goto", "<#room/number>"));
+            return;
+        };
+        if let Some(msg) = self.messages.iter().find(|m| m.room == room && m.seq == Some(seq)) {
+            self.jump_to = Some(msg.id);
+            self.focused = Some(msg.id);
+            return;
+        }
+        self.pending_goto = Some((room.to_string(), seq));
+        if room != self.current_room {
+            self.outbox.push(format!("/join {}", room));
+        }
+        self.outbox.push(format!("/history #{}/{}", room, seq));
+    }
+
+    // Keys while the starred panel is open; returns a command to send, if any
+    fn handle_starred_key(&mut self, key: event::KeyEvent) -> Option<String> {
+        let panel = self.starred.as_mut()?;
+        match key.code {
+            KeyCode::Esc => self.starred = None,
+            KeyCode::Up => panel.move_selection(-1),
+            KeyCode::Down | KeyCode::Tab => panel.move_selection(1),
+            KeyCode::Delete => return panel.remove_selected().map(|msg| format!("/unstar {}", msg.id)),
+            KeyCode::Enter => {
+                let msg = panel.messages.get(panel.selected).cloned();
+                self.starred = None;
+                return self.jump_to_message(&msg?);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // Keys while the `/discover` results are open; returns a command to send, if any
+    fn handle_discover_key(&mut self, key: event::KeyEvent) -> Option<String> {
+        let panel = self.discover.as_mut()?;
+        match key.code {
+            KeyCode::Esc => self.discover = None,
+            KeyCode::Up => panel.move_selection(-1),
+            KeyCode::Down | KeyCode::Tab => panel.move_selection(1),
+            KeyCode::Enter => {
+                let room = panel.selected_room().map(str::to_string);
+                self.discover = None;
+                return room.filter(|room| *room != self.current_room).map(|room| format!("/join {}", room));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // Keys while the moderation panel is open; returns a command to send, if any
+    fn handle_mod_key(&mut self, key: event::KeyEvent) -> Option<String> {
+        let panel = self.mod_panel.as_mut()?;
+        match key.code {
+            KeyCode::Esc => self.mod_panel = None,
+            KeyCode::Up => panel.move_selection(-1),
+            KeyCode::Down => panel.move_selection(1),
+            KeyCode::Tab | KeyCode::Right => panel.switch_tab(1),
+            KeyCode::BackTab | KeyCode::Left => panel.switch_tab(-1),
+            KeyCode::Enter => {
+                let msg = panel.message().cloned();
+                self.mod_panel = None;
+                return self.jump_to_message(&msg?);
+            }
+            // A warning needs a reason, so it is left in the input to finish
+            KeyCode::Char('w') => {
+                let user = panel.member()?.username.clone();
+                self.mod_panel = None;
+                self.input = Input::new(format!("/warn {} ", user));
+            }
+            // Followed by a refresh, so the panel shows what the action changed
+            code => return panel.command(code).map(|command| format!("{}\n/modpanel", command)),
+        }
+        None
+    }
+
+    fn is_moderator(&self) -> bool {
+        self.capabilities.as_ref().is_some_and(|capabilities| capabilities.moderator)
+    }
+
+    // Commands to offer for what is typed so far; none once Esc closed the popup for this input
+    fn completions(&self) -> Vec<CommandInfo> {
+        if self.completion.dismissed.as_deref() == Some(self.input.value()) {
+            return Vec::new();
+        }
+        complete::matches(self.input.value(), &self.commands)
+    }
+
+    // Up/Down pick a command, Tab or Enter puts it in the input, Esc closes the popup. False
+    // when the key is not the popup's, e.g. Enter on a command already typed out in full
+    fn handle_completion_key(&mut self, key: event::KeyEvent) -> bool {
+        if key.modifiers.intersects(KeyModifiers::ALT | KeyModifiers::CONTROL) {
+            return false;
+        }
+        let matches = self.completions();
+        if matches.is_empty() {
+            return false;
+        }
+        let selected = self.completion.selected.min(matches.len() - 1);
+        match key.code {
+            KeyCode::Up => self.completion.selected = (selected + matches.len() - 1) % matches.len(),
+            KeyCode::Down => self.completion.selected = (selected + 1) % matches.len(),
+            KeyCode::Esc => self.completion.dismissed = Some(self.input.value().to_string()),
+            KeyCode::Enter if matches[selected].name == self.input.value() => return false,
+            KeyCode::Tab | KeyCode::Enter => {
+                self.input = Input::new(format!("{} ", matches[selected].name));
+                self.completion = Completion::default();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // F7: suggestions for the misspelled word at (or just before) the cursor
+    fn open_spell_popup(&mut self) {
+        let Some(spell) = &self.spell else { return };
+        if let Some((range, word)) = spell.word_near(self.input.value(), self.input.cursor()) {
+            let suggestions = spell.suggest(&word);
+            self.spell_popup = Some(SpellPopup { range, word, suggestions, selected: 0 });
+        }
+    }
+
+    fn handle_spell_key(&mut self, key: event::KeyEvent) {
+        let Some(popup) = self.spell_popup.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Up => popup.move_selection(-1),
+            KeyCode::Down | KeyCode::Tab => popup.move_selection(1),
+            KeyCode::Enter => {
+                let Some(popup) = self.spell_popup.take() else { return };
+                match popup.suggestions.get(popup.selected) {
+                    Some(replacement) => {
+                        let mut chars: Vec<char> = self.input.value().chars().collect();
+                        chars.splice(popup.range.clone(), replacement.chars());
+                        let cursor = popup.range.start + replacement.chars().count();
+                        self.input = Input::new(chars.into_iter().collect()).with_cursor(cursor);
+                    }
+                    None => self.add_spell_word(&popup.word),
+                }
+            }
+            _ => self.spell_popup = None,
+        }
+    }
+
+    fn add_spell_word(&mut self, word: &str) {
+        if let Some(spell) = self.spell.as_mut() {
+            spell.add(word);
+        }
+        if !self.config.spellcheck.words.iter().any(|w| w.to_lowercase() == word.to_lowercase()) {
+            self.config.spellcheck.words.push(word.to_string());
+            self.save_config();
+        }
+    }
+
+    fn handle_search_key(&mut self, key: event::KeyEvent) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let hits = search.matches(&self.messages).len();
+        match key.code {
+            KeyCode::Esc => self.search = None,
+            KeyCode::Up => search.move_selection(-1, hits),
+            KeyCode::Down | KeyCode::Tab => search.move_selection(1, hits),
+            KeyCode::Enter => {
+                if let Some(hit) = search.matches(&self.messages).get(search.selected) {
+                    self.jump_to = Some(hit.id);
+                    self.focused = Some(hit.id);
+                }
+                self.search = None;
+            }
+            _ => {
+                search.input.handle_event(&Event::Key(key));
+                search.selected = 0;
+            }
+        }
+    }
+
+    // Commands handled entirely in the client; returns true if the input was consumed
+    fn handle_local_command(&mut self, input: &str) -> bool {
+        let mut parts = input.split_whitespace();
+        let command = parts.next().unwrap_or_default();
+        let arg = parts.next();
+        match (command, arg) {
+            ("/ignore", Some(user)) => {
+                if !self.config.ignored.iter().any(|u| u == user) {
+                    self.config.ignored.push(user.to_string());
+                    self.save_config();
+                }
+                self.messages.push(ChatMessage::system(format!("Ignoring {}", user), self.current_room.clone()));
+            }
+            ("/ignore", None) => {
+                let list = if self.config.ignored.is_empty() { "nobody".to_string() } else { self.config.ignored.join(", ") };
+                self.messages.push(ChatMessage::system(format!("Ignored users: {}", list), self.current_room.clone()));
+            }
+            ("/quiet", mode) => {
+                let mode = match mode {
+                    None if self.config.quiet_rooms.contains_key(self.current_room.as_str()) => None,
+                    None | Some("summary") => Some(QuietMode::Summary),
+                    Some("hide") => Some(QuietMode::Hide),
+                    Some("off") => None,
+                    Some(_) => {
+                        self.usage = Some(CommandError::new("/quiet", "[summary|hide|off]"));
+                        return true;
+                    }
+                };
+                let status = match mode {
+                    Some(mode) => {
+                        self.config.quiet_rooms.insert(self.current_room.to_string(), mode);
+                        if mode == QuietMode::Hide { "hidden" } else { "summarized" }
+                    }
+                    None => {
+                        self.config.quiet_rooms.remove(self.current_room.as_str());
+                        "shown"
+                    }
+                };
+                self.save_config();
+                self.messages.push(ChatMessage::system(format!("Join/leave messages in #{} are now {}", self.current_room, status), self.current_room.clone()));
+            }
+            ("/export", Some(file)) => {
+                let path = export::expand_home(file);
+                let room = self.current_room.clone();
+                let messages: Vec<&ChatMessage> = self.messages.iter().filter(|m| m.room == room).collect();
+                let count = messages.len();
+                let notice = match export::write_transcript(&path, &room, &messages) {
+                    Ok(()) => ChatMessage::system(format!("Exported {} messages to {}", count, path.display()), room),
+                    Err(e) => ChatMessage::error(format!("Export failed: {}", e)),
+                };
+                self.messages.push(notice);
+            }
+            ("/export", None) => {
+                self.usage = Some(CommandError::new("/export", "<file.txt|file.json|file.html>"));
+            }
+            ("/find", _) => {
+                let query = input.trim_start_matches("/find").trim();
+                self.search = Some(Search::with_query(query));
+            }
+            ("/dnd", state) => {
+                let notifications = &mut self.config.notifications;
+                notifications.dnd = match state {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => !notifications.dnd,
+                };
+                let status = if notifications.dnd { "Do not disturb is on" } else { "Do not disturb is off" };
+                self.save_config();
+                self.messages.push(ChatMessage::system(status.to_string(), self.current_room.clone()));
+            }
+            ("/notify", level) => {
+                let level = match level {
+                    Some("all") => NotifyLevel::All,
+                    Some("mentions") => NotifyLevel::Mentions,
+                    Some("none") => NotifyLevel::Nothing,
+                    Some(_) => {
+                        self.usage = Some(CommandError::new("/notify", "[all|mentions|none]"));
+                        return true;
+                    }
+                    None => {
+                        let level = self.config.notifications.level_for(&self.current_room);
+                        self.messages.push(ChatMessage::system(format!("Notifications in #{}: {:?}", self.current_room, level), self.current_room.clone()));
+                        return true;
+                    }
+                };
+                self.config.notifications.rooms.insert(self.current_room.to_string(), level);
+                self.save_config();
+                self.messages.push(ChatMessage::system(format!("Notifications in #{} set to {:?}", self.current_room, level), self.current_room.clone()));
+            }
+            ("/quiethours", Some(spec)) => {
+                let notice = if spec == "off" {
+                    self.config.notifications.quiet_hours = None;
+                    "Quiet hours disabled".to_string()
+                } else if let Some(hours) = QuietHours::parse(spec) {
+                    let notice = format!("Quiet hours set to {}-{}", hours.start, hours.end);
+                    self.config.notifications.quiet_hours = Some(hours);
+                    notice
+                } else {
+                    self.usage = Some(CommandError::new("/quiethours", "<HH:MM-HH:MM|off>"));
+                    return true;
+                };
+                self.save_config();
+                self.messages.push(ChatMessage::system(notice, self.current_room.clone()));
+            }
+            ("/receipts", state) => {
+                self.config.read_receipts = match state {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => !self.config.read_receipts,
+                };
+                let status = if self.config.read_receipts { "Read receipts are on" } else { "Read receipts are off" };
+                self.save_config();
+                self.messages.push(ChatMessage::system(status.to_string(), self.current_room.clone()));
+            }
+            ("/goto", reference) => self.goto(reference.unwrap_or_default()),
+            ("/spell", Some("add")) => match parts.next() {
+                Some(word) => {
+                    self.add_spell_word(word);
+                    self.messages.push(ChatMessage::system(format!("Added {} to your dictionary", word), self.current_room.clone()));
+                }
+                None => self.usage = Some(CommandError::new("/spell", "[on|off|add <word>]")),
+            },
+            ("/spell", state) => {
+                self.config.spellcheck.enabled = match state {
+                    Some("on") => true,
+                    Some("off") => false,
+                    None => !self.config.spellcheck.enabled,
+                    Some(_) => {
+                        self.usage = Some(CommandError::new("/spell", "[on|off|add <word>]"));
+                        return true;
+                    }
+                };
+                self.save_config();
+                self.spell = load_spellchecker(&self.config);
+                let status = match &self.spell {
+                    Some(_) => format!("Spell checking is on ({})", spell_language(&self.config)),
+                    None if self.config.spellcheck.enabled => format!("No dictionary found for {}; spell checking is off", spell_language(&self.config)),
+                    None => "Spell checking is off".to_string(),
+                };
+                self.messages.push(ChatMessage::system(status, self.current_room.clone()));
+            }
+            ("/alias", None) => {
+                let mut aliases: Vec<String> = self.config.aliases.iter().map(|(name, expansion)| format!("/{} → {}", name, expansion)).collect();
+                aliases.sort();
+                let list = if aliases.is_empty() { "none".to_string() } else { aliases.join(", ") };
+                self.messages.push(ChatMessage::system(format!("Aliases: {}", list), self.current_room.clone()));
+            }
+            ("/alias", Some(name)) => {
+                let name = name.trim_start_matches('/').to_string();
+                let expansion = input.splitn(3, ' ').nth(2).unwrap_or_default().trim();
+                let notice = if expansion.is_empty() {
+                    match self.config.aliases.get(&name) {
+                        Some(expansion) => format!("/{} → {}", name, expansion),
+                        None => format!("No alias /{}", name),
+                    }
+                } else {
+                    self.config.aliases.insert(name.clone(), expansion.to_string());
+                    self.save_config();
+                    format!("/{} now runs {}", name, expansion)
+                };
+                self.messages.push(ChatMessage::system(notice, self.current_room.clone()));
+            }
+            ("/unalias", Some(name)) => {
+                let name = name.trim_start_matches('/');
+                let notice = match self.config.aliases.remove(name) {
+                    Some(_) => format!("Removed alias /{}", name),
+                    None => format!("No alias /{}", name),
+                };
+                self.save_config();
+                self.messages.push(ChatMessage::system(notice, self.current_room.clone()));
+            }
+            // Sent on to the server; this only marks the reply for display
+            ("/rooms", _) => {
+                self.rooms_requested = true;
+                return false;
+            }
+            ("/collapse", category) | ("/expand", category) => {
+                let category = category.map(|c| c.trim_matches('/'));
+                let collapsed = &mut self.config.layout.collapsed;
+                match (command, category) {
+                    ("/collapse", Some(category)) if !collapsed.iter().any(|c| c == category) => collapsed.push(category.to_string()),
+                    ("/collapse", None) => {
+                        // Every top-level category
+                        let mut all: Vec<String> = self.rooms.iter().filter_map(|r| Some(r.name.split_once('/')?.0.to_string())).collect();
+                        all.sort();
+                        all.dedup();
+                        *collapsed = all;
+                    }
+                    ("/expand", Some(category)) => collapsed.retain(|c| c != category),
+                    ("/expand", None) => collapsed.clear(),
+                    _ => {}
+                }
+                self.save_config();
+            }
+            ("/unignore", Some(user)) => {
+                self.config.ignored.retain(|u| u != user);
+                self.save_config();
+                self.messages.push(ChatMessage::system(format!("No longer ignoring {}", user), self.current_room.clone()));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // Schedules a reconnect after a drop; true once it is time to try
+    fn reconnect_due(&mut self) -> bool {
+        if self.connected {
+            return false;
+        }
+        let at = *self.reconnect_at.get_or_insert_with(|| Instant::now() + self.reconnect_delay);
+        Instant::now() >= at
+    }
+
+    fn reconnect_failed(&mut self, reason: String) {
+        self.reconnect_delay = (self.reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+        self.reconnect_at = None;
+        self.reconnect_error = Some(reason);
+    }
+
+    fn reconnected(&mut self) {
+        self.connected = true;
+        self.reconnect_at = None;
+        self.reconnect_delay = RECONNECT_DELAY;
+        self.reconnect_error = None;
+        self.auto_away = false;
+        self.pending_ping = None;
+    }
+
+    fn is_away(&self) -> bool {
+        self.away_users.contains_key(&self.username)
+    }
+
+    // `/away` once idle for the configured time, or right away when focus is lost
+    fn check_idle(&mut self, focus_lost: bool) -> Option<String> {
+        let auto_away = &self.config.auto_away;
+        let idle = auto_away.idle_minutes > 0 && self.last_input.elapsed() >= Duration::from_secs(auto_away.idle_minutes * 60);
+        if !self.connected || self.auto_away || self.is_away() || !(idle || focus_lost && auto_away.on_focus_lost) {
+            return None;
+        }
+        self.auto_away = true;
+        Some(if focus_lost { "/away unfocused" } else { "/away idle" }.to_string())
+    }
+
+    // Any keypress resets the idle timer and ends an automatic away (but not a manual one)
+    fn record_activity(&mut self) -> Option<String> {
+        self.last_input = Instant::now();
+        if !std::mem::take(&mut self.auto_away) || !self.connected {
+            return None;
+        }
+        Some("/back".to_string())
+    }
+
+    // `/name args` becomes the configured expansion; `$*` marks where args go, otherwise
+    // they are appended. Expansions are not expanded again, so aliases cannot loop
+    fn expand_alias(&self, input: String) -> String {
+        let Some(rest) = input.strip_prefix('/') else { return input };
+        let (name, args) = rest.split_once(' ').unwrap_or((rest, ""));
+        let args = args.trim();
+        match self.config.aliases.get(name) {
+            Some(expansion) if expansion.contains("$*") => expansion.replace("$*", args),
+            Some(expansion) if args.is_empty() => expansion.clone(),
+            Some(expansion) => format!("{} {}", expansion, args),
+            None => input,
+        }
+    }
+
+    fn save_config(&mut self) {
+        if let Err(e) = self.config.save() {
+            self.messages.push(ChatMessage::error(format!("Could not save config: {}", e)));
+        }
+    }
+
+    fn switch_room(&mut self, room: RoomName) {
+        let draft = self.input.value().to_string();
+        if draft.is_empty() {
+            self.drafts.remove(&self.current_room);
+        } else {
+            self.drafts.insert(self.current_room.clone(), draft);
+        }
+
+        let restored = self.drafts.remove(&room).unwrap_or_default();
+        self.multiline = restored.contains('\n');
+        self.input = Input::new(restored);
+
+        if !self.visited_rooms.contains(&room) {
+            self.visited_rooms.push(room.clone());
+        }
+        self.current_room = room;
+    }
+
+    // Alt+Up/Down walks a selection through chat messages and PMs; moving past
+    // the newest clears it and returns to the bottom
+    fn move_selection(&mut self, up: bool) {
+        let ids: Vec<MessageId> = self
+            .messages
+            .iter()
+            .filter(|m| matches!(m.msg_type, MessageType::Chat | MessageType::PrivateMessage) && !self.rules.is_filtered(m))
+            .map(|m| m.id)
+            .collect();
+        let current = self.focused.and_then(|id| ids.iter().position(|i| *i == id));
+        let next = match (current, up) {
+            (None, true) => ids.len().checked_sub(1),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => (i + 1 < ids.len()).then_some(i + 1),
+        };
+        self.focused = next.map(|i| ids[i]);
+        self.jump_to = self.focused;
+        if self.focused.is_none() {
+            self.scroll_offset = 0;
+            self.auto_scroll = true;
+        }
+    }
+
+    // Neighbouring room in visit order, used by Alt+Left/Right
+    fn adjacent_room(&self, forward: bool) -> Option<&RoomName> {
+        let len = self.visited_rooms.len();
+        if len < 2 {
+            return None;
+        }
+        let pos = self.visited_rooms.iter().position(|r| *r == self.current_room).unwrap_or(0);
+        let next = if forward { (pos + 1) % len } else { (pos + len - 1) % len };
+        self.visited_rooms.get(next)
+    }
+
+    fn insert_text(&mut self, text: &str) {
+        // Terminals deliver pasted newlines as \r\n or bare \r
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        for c in text.chars() {
+            self.input.handle(InputRequest::InsertChar(c));
+        }
+        if text.contains('\n') {
+            self.multiline = true;
+        }
+    }
+
+    fn take_draft(&mut self) -> String {
+        let draft = self.input.value().trim_end_matches('\n').replace('\n', &LINE_SEPARATOR.to_string());
+        self.input.reset();
+        self.multiline = false;
+        draft
     }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), ChatError> {
+    let config = ClientConfig::load();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(options) = console::Options::parse(&args) {
+        let result = match options {
+            Ok(options) => console::run(options, config).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(options) = headless::Options::parse(&args) {
+        let result = match options {
+            Ok(options) => headless::run(options, config).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Setup Terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste, EnableFocusChange)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Login Screen
+    let result = run(&mut terminal, config).await;
+
+    // Cleanup, however the session ended
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableBracketedPaste, DisableFocusChange)?;
+    match result {
+        // Esc on the login form
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+async fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut config: ClientConfig) -> io::Result<()> {
+    let locale = config.locale();
+
+    // Login form, then connect and handshake; failures offer a retry or a trip back to the form
     terminal.clear()?;
-    let username = login_screen(&mut terminal)?;
-    
-    // Connect
-    let stream = match TcpStream::connect("127.0.0.1:8080").await {
-        Ok(s) => s,
-        Err(e) => {
-            disable_raw_mode()?;
-            execute!(io::stdout(), LeaveAlternateScreen)?;
-            eprintln!("Failed to connect: {}", e);
-            return Ok(());
+    let mut login = login::run(terminal, &mut config)?;
+    let (mut handshake, session) = loop {
+        // Including any password / two-factor / registration prompts
+        let mut handshake = Handshake {
+            username: login.username.clone(),
+            locale: Some(locale.to_string()),
+            password: login.password.clone(),
+            guest: login.guest,
+            compression: if config.compression { vec![deflate::NAME.to_string()] } else { Vec::new() },
+            encoding: if config.msgpack { vec![msgpack::NAME.to_string()] } else { Vec::new() },
+            ..Default::default()
+        };
+        match connect::open_session(terminal, &login, &mut handshake, locale).await? {
+            Ok(session) => break (handshake, session),
+            Err(reason) => match connect::error_dialog(terminal, &reason, locale)? {
+                connect::Choice::Retry => {}
+                connect::Choice::EditServer => login = login::run(terminal, &mut config)?,
+                connect::Choice::Quit => return Ok(()),
+            },
         }
     };
-
-    let (reader, writer) = stream.into_split();
-    let writer = Arc::new(Mutex::new(writer));
-
-    // Send Handshake
-    let handshake = Handshake { username: username.clone() };
-    writer.lock().await.write_all(format!("{}\n", serde_json::to_string(&handshake)?).as_bytes()).await?;
+    let writer = Arc::new(Mutex::new(session.writer));
 
     // Init App State
-    let app = Arc::new(Mutex::new(App::new(username)));
-    app.lock().await.connected = true;
+    // Guests learn their name from the first room change
+    let username = if login.guest { session.first.username.clone() } else { login.username.clone() };
+    let profile = config::Profile::name_for(&username, &login.host, login.port);
+    let mut app = App::new(username, &profile, config);
+    app.connected = true;
+    app.handle_message(session.first);
+    let (app_tx, mut app_rx) = mpsc::channel(APP_EVENT_QUEUE);
+    spawn_reader(app_tx.clone(), session.reader);
 
-    // Network Reader Task
-    let app_clone = app.clone();
-    tokio::spawn(async move {
-        let mut reader = BufReader::new(reader);
-        let mut line = String::new();
-        loop {
-            line.clear();
-            match reader.read_line(&mut line).await {
-                Ok(0) => break,
-                Ok(_) => {
-                    let text = line.trim();
-                    if text.starts_with("Error:") {
-                        // Handle raw errors
-                        // In a real app, handle gracefully. Here just print to chat.
-                    } else if let Ok(msg) = ChatMessage::from_json(text) {
-                        let mut state = app_clone.lock().await;
-                        
-                        // Handle room changes to clear/update UI state
-                        if msg.msg_type == MessageType::RoomChange && msg.username == state.username {
-                            state.current_room = msg.room.clone();
-                            state.messages.clear(); // Clear history on room switch
-                        }
-                        
-                        // Handle joins/leaves for user list (Naive implementation)
-                        if msg.msg_type == MessageType::UserJoin {
-                           if !state.users_in_room.contains(&msg.username) {
-                               state.users_in_room.push(msg.username.clone());
-                           }
-                        }
+    // Main UI Loop
+    let mut events = TerminalEvents::spawn();
+    let mut tick = tokio::time::interval(TICK);
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    let mut ping_seq = 0u64;
+    loop {
+        // Draw
+        terminal.draw(|f| draw_ui(f, &mut app, chrono::Local::now()))?;
 
-                        state.messages.push(msg);
-                        if state.auto_scroll {
-                            state.scroll_offset = 0;
-                        }
+        // Reconnect with the same credentials and return to the room we were in
+        if app.reconnect_due() {
+            let room = app.current_room.clone();
+            handshake.totp = None; // Codes expire, so prompt for a fresh one if needed
+            handshake.reconnects += 1;
+            events.pause();
+            let opened = connect::open_session(terminal, &login, &mut handshake, locale).await;
+            events.resume();
+            match opened? {
+                Ok(session) => {
+                    *writer.lock().await = session.writer;
+                    spawn_reader(app_tx.clone(), session.reader);
+                    app.reconnected();
+                    // A reconnecting guest is handed a fresh name
+                    if login.guest {
+                        app.username = session.first.username.clone();
+                    }
+                    app.handle_message(session.first);
+                    if room != app.current_room {
+                        send_line(&writer, &format!("/join {}", room)).await;
                     }
                 }
-                Err(_) => break,
+                Err(reason) => app.reconnect_failed(reason),
             }
+            terminal.clear()?;
+            continue;
         }
-        app_clone.lock().await.connected = false;
-    });
 
-    // Main UI Loop
-    loop {
-        let mut app_guard = app.lock().await;
-        
-        // Draw
-        terminal.draw(|f| draw_ui(f, &mut app_guard))?;
+        // Trigger output goes through local commands first, like typed input
+        for line in std::mem::take(&mut app.outbox) {
+            if !app.handle_local_command(&line) && app.connected {
+                send_line(&writer, &line).await;
+            }
+        }
+
+        // Only report what is actually on screen: at the bottom with no overlay open
+        if app.config.read_receipts && app.connected && app.scroll_offset == 0 && app.search.is_none() {
+            for command in app.pending_read_receipts() {
+                send_line(&writer, &command).await;
+            }
+        }
 
-        if !app_guard.connected {
-            break; // Exit if server dies
+        if let Some(command) = app.check_idle(false) {
+            send_line(&writer, &command).await;
         }
 
-        // Input Handling
-        if event::poll(std::time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
+        // Sleep until there is something to show: a key, a message, or the clock moving on
+        let event = tokio::select! {
+            event = events.next() => match event {
+                Some(event) => event?,
+                None => break,
+            },
+            Some(event) = app_rx.recv() => {
+                // Take whatever else has arrived too, so a flood of history costs one redraw
+                app.apply(event);
+                for _ in 1..APP_EVENT_QUEUE {
+                    let Ok(event) = app_rx.try_recv() else { break };
+                    app.apply(event);
+                }
+                continue;
+            }
+            _ = ping.tick() => {
+                if app.connected {
+                    // The last round trip goes along, so the server can show it in `/conninfo`
+                    let token = ping_seq.to_string();
+                    ping_seq += 1;
+                    let last = app.latency.map(|latency| format!(" {}", latency.as_millis())).unwrap_or_default();
+                    app.pending_ping = Some((token.clone(), Instant::now()));
+                    // A dead connection is noticed by the reader, which starts the reconnect
+                    send_line(&writer, &format!("/ping {}{}", token, last)).await;
+                }
+                continue;
+            }
+            _ = tick.tick() => continue,
+        };
+        let presence = match event {
+            Event::Key(_) | Event::Paste(_) => app.record_activity(),
+            Event::FocusLost => app.check_idle(true),
+            _ => None,
+        };
+        if let Some(command) = presence {
+            send_line(&writer, &command).await;
+        }
+        match event {
+            Event::Paste(text) => app.insert_text(&text),
+            Event::Key(key) if app.search.is_some() => app.handle_search_key(key),
+            Event::Key(key) if app.spell_popup.is_some() => app.handle_spell_key(key),
+            Event::Key(_) if app.profile_card.is_some() => app.profile_card = None,
+            Event::Key(_) if app.stats.is_some() => app.stats = None,
+            Event::Key(_) if app.activity.is_some() => app.activity = None,
+            Event::Key(key) if app.starred.is_some() => {
+                if let Some(command) = app.handle_starred_key(key) {
+                    send_line(&writer, &command).await;
+                }
+            },
+            Event::Key(key) if app.discover.is_some() => {
+                if let Some(command) = app.handle_discover_key(key) {
+                    send_line(&writer, &command).await;
+                }
+            },
+            Event::Key(key) if app.mod_panel.is_some() => {
+                if let Some(command) = app.handle_mod_key(key) {
+                    send_line(&writer, &command).await;
+                }
+            },
+            Event::Key(key) if app.switcher.is_some() => {
+                if let Some(command) = app.handle_switcher_key(key) {
+                    send_line(&writer, &command).await;
+                }
+            },
+            Event::Key(key) if app.form.is_some() => {
+                if let Some(command) = app.handle_form_key(key) {
+                    send_line(&writer, &command).await;
+                }
+            },
+            Event::Key(key) => {
+                if app.handle_completion_key(key) {
+                    continue;
+                }
                 match key.code {
+                    KeyCode::Esc if app.focused.is_some() => {
+                        app.focused = None;
+                    },
                     KeyCode::Esc => {
-                        app_guard.show_help = !app_guard.show_help;
+                        app.show_help = !app.show_help;
+                    },
+                    KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => {
+                        app.insert_text("\n");
+                    },
+                    // Takes the server's example, or its correction, in place of the draft
+                    KeyCode::Tab if app.usage.as_ref().is_some_and(|usage| usage.hint.is_some()) => {
+                        if let Some(hint) = app.usage.take().and_then(|usage| usage.hint) {
+                            app.input = Input::new(hint);
+                            app.multiline = false;
+                        }
                     },
                     KeyCode::Enter => {
-                        let input: String = app_guard.input.value().into();
-                        if !input.is_empty() {
+                        if app.input.value().trim().is_empty() {
+                            if !app.connected {
+                                app.reconnect_at = Some(Instant::now());
+                            }
+                        } else if app.read_only() && !app.input.value().trim_start().starts_with('/') {
+                            // Kept as a draft; commands still go through
+                            app.messages.push(ChatMessage::error(tr(app.locale, "ui.read_only").to_string()));
+                        } else if app.chat_blocked(chrono::Utc::now()) {
+                            // Kept as a draft; the input's hint says why
+                        } else {
+                            let input = app.take_draft();
+                            let input = app.expand_alias(input);
                             // Command handling on client side if needed, otherwise send
                             if input == "/quit" {
-                                drop(app_guard);
                                 break;
                             }
-                            let payload = format!("{}\n", input);
-                            writer.lock().await.write_all(payload.as_bytes()).await?;
-                            app_guard.input.reset();
+                            if app.handle_local_command(&input) {
+                                continue;
+                            }
+                            if !app.connected {
+                                app.messages.push(ChatMessage::error("Not connected to the server".to_string()));
+                                continue;
+                            }
+                            app.last_sent = Some(input.clone());
+                            app.usage = None;
+                            if !send_line(&writer, &input).await {
+                                app.messages.push(ChatMessage::error("Not connected to the server".to_string()));
+                            }
+                        }
+                    },
+                    KeyCode::Left | KeyCode::Right if key.modifiers.contains(KeyModifiers::ALT) => {
+                        // The draft is stashed once the server confirms the room change
+                        if let Some(room) = app.adjacent_room(key.code == KeyCode::Right) {
+                            send_line(&writer, &format!("/join {}", room)).await;
+                        }
+                    },
+                    KeyCode::Up | KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+                        app.move_selection(key.code == KeyCode::Up);
+                    },
+                    // Actions on the selected message, while there is no draft to type into
+                    KeyCode::Char('r') if app.focused.is_some() && app.input.value().is_empty() => {
+                        if let Some(id) = app.focused {
+                            if !app.revealed.remove(&id) {
+                                app.revealed.insert(id);
+                            }
+                        }
+                    },
+                    // Follow the first permalink in the selected message, or cite the message itself
+                    KeyCode::Char('g') if app.focused.is_some() && app.input.value().is_empty() => {
+                        let focused = app.focused;
+                        let reference = app.messages.iter().find(|m| Some(&m.id) == focused.as_ref()).and_then(|m| {
+                            let pattern = common::pattern::Pattern::new(rules::REFERENCE_PATTERN).ok()?;
+                            let (start, end) = pattern.find(&m.content)?;
+                            Some(m.content[start..end].to_string())
+                        });
+                        if let Some(reference) = reference {
+                            app.goto(&reference);
+                        }
+                    },
+                    KeyCode::Char('l') if app.focused.is_some() && app.input.value().is_empty() => {
+                        let focused = app.focused;
+                        if let Some(reference) = app.messages.iter().find(|m| Some(&m.id) == focused.as_ref()).and_then(ChatMessage::reference) {
+                            app.insert_text(&format!("{} ", reference));
+                            app.focused = None;
+                        }
+                    },
+                    KeyCode::Char('s') if app.focused.is_some() && app.input.value().is_empty() && app.supports(ServerCapabilities::STARS) => {
+                        if let Some(id) = app.focused {
+                            send_line(&writer, &format!("/star {}", id)).await;
+                        }
+                    },
+                    KeyCode::Char('p') if app.focused.is_some() && app.input.value().is_empty() && app.supports(ServerCapabilities::MODERATION) => {
+                        if let Some(id) = app.focused {
+                            send_line(&writer, &format!("/pin {}", id)).await;
                         }
                     },
+                    KeyCode::Char('t') if app.focused.is_some() && app.input.value().is_empty() && app.supports(ServerCapabilities::TRANSLATE) => {
+                        if let Some(id) = app.focused {
+                            send_line(&writer, &format!("/translate {} {}", id, app.locale)).await;
+                        }
+                    },
+                    KeyCode::Char('f') if app.focused.is_some() && app.input.value().is_empty() && app.supports(ServerCapabilities::FORWARD) => {
+                        let forward = app.focused;
+                        app.switcher = Some(Switcher { forward, ..Default::default() });
+                    },
+                    KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.switcher = Some(Switcher::default());
+                    },
+                    KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.search = Some(Search::default());
+                    },
+                    KeyCode::Char('x') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.reveal_ignored = !app.reveal_ignored;
+                    },
+                    KeyCode::F(7) => app.open_spell_popup(),
+                    KeyCode::F(4) if app.is_moderator() => {
+                        send_line(&writer, "/modpanel").await;
+                    },
+                    KeyCode::F(2) => {
+                        app.config.layout.show_sidebar = !app.config.layout.show_sidebar;
+                        app.save_config();
+                    },
+                    KeyCode::F(3) => {
+                        app.config.layout.show_users = !app.config.layout.show_users;
+                        app.save_config();
+                    },
+                    KeyCode::Char(c @ ('-' | '=')) if key.modifiers.contains(KeyModifiers::ALT) => {
+                        app.config.layout.resize_sidebar(if c == '-' { -5 } else { 5 });
+                        app.save_config();
+                    },
                     KeyCode::PageUp => {
-                        app_guard.auto_scroll = false;
-                        app_guard.scroll_offset = app_guard.scroll_offset.saturating_add(5);
+                        app.auto_scroll = false;
+                        app.scroll_offset = app.scroll_offset.saturating_add(5);
                     },
                     KeyCode::PageDown => {
-                        app_guard.scroll_offset = app_guard.scroll_offset.saturating_sub(5);
-                        if app_guard.scroll_offset == 0 {
-                            app_guard.auto_scroll = true;
+                        app.scroll_offset = app.scroll_offset.saturating_sub(5);
+                        if app.scroll_offset == 0 {
+                            app.auto_scroll = true;
+                            app.unread = 0;
                         }
                     },
                     _ => {
-                        app_guard.input.handle_event(&Event::Key(key));
+                        app.input.handle_event(&Event::Key(key));
+                        app.completion.selected = 0;
                     }
                 }
-            }
+            },
+            _ => {}
         }
     }
 
-    // Cleanup
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     Ok(())
 }
 
-fn login_screen(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<String, io::Error> {
-    let mut input = Input::default();
-    loop {
-        terminal.draw(|f| {
-            let area = centered_rect(60, 20, f.area());
-            let block = Block::default().borders(Borders::ALL).title(" Login ").border_type(BorderType::Rounded).style(Style::default().fg(Color::Cyan));
-            f.render_widget(block, area);
-
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(2)
-                .constraints([Constraint::Length(1), Constraint::Length(3), Constraint::Min(1)])
-                .split(area);
-            
-            f.render_widget(Paragraph::new("Welcome to Ultimate Chat").alignment(Alignment::Center), chunks[0]);
-            
-            let input_block = Block::default().borders(Borders::ALL).title(" Username ");
-            f.render_widget(Paragraph::new(input.value()).block(input_block), chunks[1]);
-            
-            f.render_widget(Paragraph::new("Press Enter to join\nEsc to quit").style(Style::default().fg(Color::DarkGray)), chunks[2]);
-        })?;
-
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Enter => {
-                    if !input.value().is_empty() {
-                        return Ok(input.value().to_string());
+fn spell_language(config: &ClientConfig) -> String {
+    config.spellcheck.language.clone().unwrap_or_else(|| config.locale().to_string())
+}
+
+fn load_spellchecker(config: &ClientConfig) -> Option<SpellChecker> {
+    if !config.spellcheck.enabled {
+        return None;
+    }
+    SpellChecker::load(&spell_language(config), &config.spellcheck.words)
+}
+
+// One line to the server; false if it couldn't be written. A failed write means the connection
+// is going down, and the reader notices that too and starts the reconnect, so the error stops here
+async fn send_line(writer: &Mutex<tokio::net::tcp::OwnedWriteHalf>, line: &str) -> bool {
+    writer.lock().await.write_all(format!("{}\n", line).as_bytes()).await.is_ok()
+}
+
+// Passes server messages to the UI loop until the connection drops
+fn spawn_reader(app_tx: mpsc::Sender<AppEvent>, mut reader: ServerReader) {
+    tokio::spawn(async move {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line).await {
+                Ok(0) => break,
+                Ok(_) => {
+                    if let Ok(msg) = ChatMessage::from_json(line.trim()) {
+                        if app_tx.send(AppEvent::Message(Box::new(msg))).await.is_err() {
+                            return;
+                        }
                     }
                 }
-                KeyCode::Esc => return Err(io::Error::new(io::ErrorKind::Interrupted, "Quit")),
-                _ => { input.handle_event(&Event::Key(key)); }
+                Err(_) => break,
             }
         }
+        let _ = app_tx.send(AppEvent::Disconnected).await;
+    });
+}
+
+// Draws onto any backend; `now` is the status bar clock, fixed in the snapshot tests
+// Under the input: how long until chat is taken again, and a character counter once the draft
+// nears the limit
+fn input_hint(app: &App, now: chrono::DateTime<chrono::Utc>) -> Line<'static> {
+    let mut spans = Vec::new();
+    if let (Some(left), Some((_, key))) = (app.cooldown_left(now), app.cooldown) {
+        spans.push(Span::styled(format!(" {} ", trf(app.locale, key, &[&common::format_elapsed(left as u64)])), Style::default().fg(Color::LightRed)));
+    }
+    let (len, limit) = (app.input.value().chars().count(), app.max_message_len());
+    if len * 4 >= limit * 3 || (app.length_limit.is_some() && len > 0) {
+        let style = if len > limit { Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD) } else { Style::default().fg(Color::DarkGray) };
+        spans.push(Span::styled(format!(" {}/{} ", len, limit), style));
     }
+    Line::from(spans)
 }
 
-fn draw_ui(f: &mut Frame, app: &mut App) {
+// Under the input, left: how the last command the server refused is used, and what Tab puts in
+fn usage_hint(app: &App) -> Line<'static> {
+    let Some(usage) = &app.usage else { return Line::default() };
+    let mut spans = vec![
+        Span::styled(format!(" {}", usage.command), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+        Span::styled(format!(" {} ", usage.expected_args), Style::default().fg(Color::Cyan)),
+    ];
+    if let Some(hint) = &usage.hint {
+        spans.push(Span::styled(format!("· {} ", trf(app.locale, "ui.usage_tab", &[hint])), Style::default().fg(Color::DarkGray)));
+    }
+    Line::from(spans)
+}
+
+fn draw_ui(f: &mut Frame, app: &mut App, now: chrono::DateTime<chrono::Local>) {
+    // Multi-line drafts grow the input box, capped so the chat stays visible
+    let input_lines = if app.multiline { app.input.value().split('\n').count().clamp(1, 8) } else { 1 };
     let main_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Min(0),
-            Constraint::Length(3),
+            Constraint::Length(input_lines as u16 + 2),
+            Constraint::Length(1),
         ])
         .split(f.area());
 
+    let layout = &app.config.layout;
+    let sidebar_width = if layout.show_sidebar { layout.sidebar_width } else { 0 };
     let content_layout = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(20), // Sidebar
-            Constraint::Percentage(80), // Chat
+            Constraint::Percentage(sidebar_width), // Sidebar
+            Constraint::Percentage(100 - sidebar_width), // Chat
         ])
         .split(main_layout[0]);
 
@@ -233,55 +1562,105 @@ fn draw_ui(f: &mut Frame, app: &mut App) {
     let sidebar_block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .title(" Info ")
+        .title(format!(" {} ", tr(app.locale, "ui.info")))
         .style(Style::default().fg(Color::Blue));
 
-    let room_info = vec![
-        Line::from(vec![Span::raw("Room: "), Span::styled(&app.current_room, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))]),
-        Line::from(""),
-        Line::from(Span::styled("Users:", Style::default().add_modifier(Modifier::UNDERLINED))),
-        // Note: Real user list requires syncing from server, using simplified placeholder or captured joins
-        Line::from(vec![Span::raw("• "), Span::raw(&app.username)]),
+    let mut room_info = vec![
+        Line::from(vec![Span::raw(tr(app.locale, "ui.room")), Span::styled(app.current_room.as_str(), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))]),
     ];
+    if !app.rooms.is_empty() {
+        room_info.extend([
+            Line::from(""),
+            Line::from(Span::styled(tr(app.locale, "ui.rooms"), Style::default().add_modifier(Modifier::UNDERLINED))),
+        ]);
+        room_info.extend(rooms::sidebar_lines(&app.rooms, &app.current_room, &layout.collapsed));
+    }
+    if layout.show_users {
+        room_info.extend([
+            Line::from(""),
+            Line::from(Span::styled(tr(app.locale, "ui.users"), Style::default().add_modifier(Modifier::UNDERLINED))),
+        ]);
+        room_info.extend(app.users_in_room.iter().map(|user| {
+            let mut line = Line::from(vec![Span::raw("• "), Span::raw(user)]);
+            if let Some(score) = app.karma.get(user) {
+                line.push_span(Span::styled(format!(" +{}", score), Style::default().fg(Color::Green)));
+            }
+            if app.away_users.contains_key(user) {
+                line.push_span(Span::styled(format!(" ({})", tr(app.locale, "ui.away_short")), Style::default().fg(Color::DarkGray)));
+            }
+            line
+        }));
+    }
 
-    let info_paragraph = Paragraph::new(room_info).block(sidebar_block);
-    f.render_widget(info_paragraph, content_layout[0]);
+    if layout.show_sidebar {
+        let info_paragraph = Paragraph::new(room_info).block(sidebar_block);
+        f.render_widget(info_paragraph, content_layout[0]);
+    }
 
     // --- Chat Area (Right) ---
     let chat_block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .title(format!(" Messages ({}) ", app.messages.len()));
+        .title(format!(" {} ", trf(app.locale, "ui.messages", &[&app.messages.len().to_string()])));
     
-    let messages: Vec<ListItem> = app.messages.iter().rev().skip(app.scroll_offset).take(f.area().height as usize).map(|msg| {
-        let (sender_style, content_style) = match msg.msg_type {
-            MessageType::Chat => if msg.username == app.username {
-                (Style::default().fg(Color::Green).add_modifier(Modifier::BOLD), Style::default())
-            } else {
-                (Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD), Style::default())
-            },
-            MessageType::System | MessageType::UserJoin | MessageType::UserLeave | MessageType::RoomChange => 
-                (Style::default().fg(Color::Yellow), Style::default().fg(Color::Yellow)),
-            MessageType::PrivateMessage => 
-                (Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD), Style::default().fg(Color::LightMagenta)),
-            MessageType::Error => 
-                (Style::default().fg(Color::Red), Style::default().fg(Color::Red)),
-        };
-
-        let prefix = match msg.msg_type {
-            MessageType::PrivateMessage => "🔒 ",
-            MessageType::System => "ℹ ",
-            _ => ""
-        };
-
-        let line = Line::from(vec![
-            Span::styled(format!("{} ", msg.format_time()), Style::default().fg(Color::DarkGray)),
-            Span::raw(prefix),
-            Span::styled(format!("{}: ", msg.username), sender_style),
-            Span::styled(&msg.content, content_style),
-        ]);
-        ListItem::new(line)
-    }).collect();
+    // Consecutive messages from ignored users collapse into a single stub line, and
+    // in quiet rooms runs of joins/leaves collapse into a summary (or disappear)
+    let quiet = app.config.quiet_rooms.get(app.current_room.as_str()).copied();
+    let jump_to = app.jump_to.take();
+    let mut scroll_offset = app.scroll_offset;
+    let mut jump_index = None;
+    // A pending jump has to walk back until it finds its target
+    let visible = if jump_to.is_some() { usize::MAX } else { scroll_offset + f.area().height as usize };
+    // Taken out while drawing so it can be filled while `app` is borrowed
+    let mut cache = std::mem::take(&mut app.render_cache);
+    cache.begin(content_layout[1].width.saturating_sub(2));
+    let width = cache.width() as usize;
+    let mut items: Vec<Row> = Vec::new();
+    let mut hidden = 0;
+    let mut presence = (0, 0); // (joined, left)
+    let receipts = app.receipt_labels();
+    for msg in app.messages.iter().rev() {
+        if items.len() >= visible {
+            break;
+        }
+        if app.rules.is_filtered(msg) {
+            continue;
+        }
+        let is_presence = matches!(msg.msg_type, MessageType::UserJoin | MessageType::UserLeave | MessageType::RoomChange);
+        if let (true, Some(mode)) = (is_presence, quiet) {
+            if mode == QuietMode::Summary {
+                flush_ignored(&mut items, &mut hidden, app.locale);
+                if msg.msg_type == MessageType::UserLeave { presence.1 += 1 } else { presence.0 += 1 }
+            }
+            continue;
+        }
+        if !app.reveal_ignored && app.config.ignored.contains(&msg.username) {
+            flush_presence(&mut items, &mut presence, app.locale);
+            hidden += 1;
+            continue;
+        }
+        flush_ignored(&mut items, &mut hidden, app.locale);
+        flush_presence(&mut items, &mut presence, app.locale);
+        if jump_to.as_ref() == Some(&msg.id) {
+            jump_index = Some(items.len());
+        }
+        cache.ensure(msg.id, app.revealed.contains(&msg.id), || message_text(msg, app, width));
+        items.push(Row::Message(msg));
+    }
+    flush_ignored(&mut items, &mut hidden, app.locale);
+    flush_presence(&mut items, &mut presence, app.locale);
+    if let Some(index) = jump_index {
+        // Park the hit roughly mid-screen
+        scroll_offset = index.saturating_sub(content_layout[1].height as usize / 3);
+    }
+    let messages: Vec<ListItem> = items
+        .into_iter()
+        .skip(scroll_offset)
+        .map(|row| match row {
+            Row::Message(msg) => message_item(cache.text(msg.id), msg, app, receipts.get(&msg.id)),
+            Row::Note(item) => item,
+        })
+        .collect();
 
     // Reverse list for chat effect (newest at bottom)
     // Actually, we are iterating rev(), so we need to render them top-down but logic is inverted.
@@ -292,47 +1671,380 @@ fn draw_ui(f: &mut Frame, app: &mut App) {
         .direction(ratatui::widgets::ListDirection::BottomToTop); // New feature in Ratatui
     
     f.render_widget(list, content_layout[1]);
+    cache.prune();
+    app.render_cache = cache;
+    if jump_index.is_some() {
+        app.scroll_offset = scroll_offset;
+        app.auto_scroll = scroll_offset == 0;
+    }
 
     // --- Input Area (Bottom) ---
     let input_block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .title(" Input ");
+        .title(format!(" {} ", tr(app.locale, if app.read_only() { "ui.input_read_only" } else if app.multiline { "ui.input_multiline" } else { "ui.input" })))
+        .title_bottom(usage_hint(app).left_aligned())
+        .title_bottom(input_hint(app, now.with_timezone(&chrono::Utc)).right_aligned());
     
-    let input_para = Paragraph::new(app.input.value())
-        .block(input_block)
-        .style(Style::default().fg(Color::Yellow));
+    // Misspelled words are underlined, line by line for multi-line drafts
+    let misspelled = app.spell.as_ref().map(|spell| spell.misspelled(app.input.value(), app.input.cursor())).unwrap_or_default();
+    let mut input_text = Text::default();
+    let mut offset = 0;
+    for line in app.input.value().split('\n') {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        let chars: Vec<char> = line.chars().collect();
+        for range in misspelled.iter().filter(|r| r.start >= offset && r.end <= offset + chars.len()) {
+            let (begin, end) = (range.start - offset, range.end - offset);
+            spans.push(Span::raw(chars[start..begin].iter().collect::<String>()));
+            spans.push(Span::styled(chars[begin..end].iter().collect::<String>(), Style::default().fg(Color::LightRed).add_modifier(Modifier::UNDERLINED)));
+            start = end;
+        }
+        spans.push(Span::raw(chars[start..].iter().collect::<String>()));
+        input_text.lines.push(Line::from(spans));
+        offset += chars.len() + 1;
+    }
+    let input_para = if app.read_only() && app.input.value().is_empty() {
+        Paragraph::new(tr(app.locale, "ui.read_only")).block(input_block).style(Style::default().fg(Color::DarkGray))
+    } else {
+        Paragraph::new(input_text).block(input_block).style(Style::default().fg(Color::Yellow))
+    };
     
     f.render_widget(input_para, main_layout[1]);
 
+    draw_status_bar(f, app, main_layout[2], now);
+
     // Cursor
+    let before_cursor: String = app.input.value().chars().take(app.input.cursor()).collect();
+    let cursor_row = before_cursor.matches('\n').count().min(input_lines - 1);
+    let cursor_col = Span::raw(before_cursor.rsplit('\n').next().unwrap_or_default()).width();
     f.set_cursor_position(Position::new(
-        main_layout[1].x + 1 + app.input.visual_cursor() as u16,
-        main_layout[1].y + 1,
+        main_layout[1].x + 1 + cursor_col as u16,
+        main_layout[1].y + 1 + cursor_row as u16,
     ));
 
+    if let Some(search) = &app.search {
+        let hits = search.matches(&app.messages);
+        search::draw(f, centered_rect(70, 60, f.area()), search, &hits, app.config.timestamps, app.locale);
+    }
+
+    // Suggestions open just above the word
+    if let Some(popup) = &app.spell_popup {
+        let before: String = app.input.value().chars().take(popup.range.start).collect();
+        let column = Span::raw(before.rsplit('\n').next().unwrap_or_default()).width() as u16;
+        let height = (popup.rows().max(2) as u16 + 2).min(main_layout[1].y);
+        let width = 30.min(f.area().width);
+        let x = (main_layout[1].x + 1 + column).min(f.area().width - width);
+        spell::draw(f, Rect::new(x, main_layout[1].y - height, width, height), popup, app.locale);
+    }
+
+    // Commands open just above the input while a name is typed
+    let completions = app.completions();
+    if !completions.is_empty() {
+        let height = (completions.len() as u16 + 2).min(10).min(main_layout[1].y);
+        let width = 70.min(f.area().width);
+        let selected = app.completion.selected.min(completions.len() - 1);
+        complete::draw(f, Rect::new(main_layout[1].x, main_layout[1].y - height, width, height), &completions, selected, app.locale);
+    }
+
+    if let Some(card) = &app.profile_card {
+        profile_card::draw(f, centered_rect(50, 40, f.area()), card, app.locale);
+    }
+
+    if let Some(stats) = &app.stats {
+        stats::draw(f, centered_rect(50, 40, f.area()), stats, app.locale);
+    }
+
+    if let Some(report) = &app.activity {
+        activity::draw(f, centered_rect(80, 60, f.area()), report, now.offset().local_minus_utc(), app.locale);
+    }
+
+    if let Some(panel) = &app.starred {
+        starred::draw(f, centered_rect(70, 60, f.area()), panel, app.config.timestamps, app.locale);
+    }
+
+    if let Some(panel) = &app.discover {
+        discover::draw(f, centered_rect(70, 60, f.area()), panel, now.with_timezone(&chrono::Utc), app.locale);
+    }
+
+    if let Some(panel) = &app.mod_panel {
+        moderation::draw(f, centered_rect(70, 60, f.area()), panel, app.locale);
+    }
+
+    if let Some(switcher) = &app.switcher {
+        let targets = switcher.matches(&app.visited_rooms, &app.pm_contacts);
+        switcher::draw(f, centered_rect(50, 50, f.area()), switcher, &targets, app.locale);
+    }
+
+    if let Some(form) = &app.form {
+        form::draw(f, centered_rect(60, 40, f.area()), form, app.locale);
+    }
+
+    // Reconnect countdown, until the connection is back
+    if !app.connected {
+        let area = centered_rect(50, 25, f.area());
+        let remaining = app.reconnect_at.unwrap_or_else(Instant::now).saturating_duration_since(Instant::now());
+        let seconds = remaining.as_secs_f32().ceil().to_string();
+        let mut text = vec![Line::from(trf(app.locale, "ui.reconnecting_in", &[&seconds]))];
+        if let Some(error) = &app.reconnect_error {
+            text.extend([Line::from(""), Line::from(Span::styled(error.as_str(), Style::default().fg(Color::Red)))]);
+        }
+        text.extend([Line::from(""), Line::from(Span::styled(tr(app.locale, "ui.reconnect_hint"), Style::default().fg(Color::DarkGray)))]);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(format!(" {} ", tr(app.locale, "ui.connection_lost")))
+            .style(Style::default().fg(Color::Yellow));
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(text).block(block).alignment(Alignment::Center).wrap(Wrap { trim: true }), area);
+    }
+
     // Help Overlay
     if app.show_help {
-        let area = centered_rect(60, 60, f.area());
-        let help_text = vec![
-            "Commands:",
-            "/join <room> - Switch rooms",
-            "/msg <user> <msg> - Private Message",
-            "/users - List users",
-            "/quit - Exit",
-            "",
-            "Keys:",
-            "PgUp/PgDn - Scroll History",
-            "Esc - Toggle Help",
-        ].join("\n");
+        let area = centered_rect(70, 80, f.area());
+        // (usage, catalog key) pairs; the usage column stays untranslated
+        let commands = [
+            ("/join <room>", "help.join"),
+            ("/join --code <code>", "help.join_code"),
+            ("/rooms", "help.rooms"),
+            ("/discover [keyword]", "help.discover"),
+            ("/autojoin [add|remove <room>]", "help.autojoin"),
+            ("/ttl [24h|off]", "help.ttl"),
+            ("/collapse|/expand [category]", "help.collapse"),
+            ("/invitecode create <24h>|list|revoke|off", "help.invitecode"),
+            ("/msg <user> <msg>", "help.msg"),
+            ("/users", "help.users"),
+            ("/activity [room]", "help.activity"),
+            ("/thank <user>, <user>++", "help.thank"),
+            ("/leaderboard", "help.leaderboard"),
+            ("/trivia start|stop|scores", "help.trivia"),
+            ("/roll 2d20+3", "help.roll"),
+            ("/flip, /choose a|b|c", "help.choose"),
+            ("/ignore [user]", "help.ignore"),
+            ("/quiet [summary|hide|off]", "help.quiet"),
+            ("/export <file>", "help.export"),
+            ("/find <text>", "help.find"),
+            ("/dnd [on|off]", "help.dnd"),
+            ("/notify [all|mentions|none]", "help.notify"),
+            ("/quiethours <HH:MM-HH:MM|off>", "help.quiethours"),
+            ("/receipts [on|off]", "help.receipts"),
+            ("/starred", "help.starred"),
+            ("/report <id> [reason]", "help.report"),
+            ("/pins", "help.pins"),
+            ("/profile [user]", "help.profile"),
+            ("/translate <id> <language>", "help.translate"),
+            ("/time <when>", "help.time"),
+            ("/profile set <field> [value]", "help.profile_set"),
+            ("/goto <#room/number>", "help.goto"),
+            ("/spell [on|off|add <word>]", "help.spell"),
+            ("/alias [name] [expansion]", "help.alias"),
+            ("/away [reason]", "help.away"),
+            ("/totp [on|off]", "help.totp"),
+            ("/conninfo", "help.conninfo"),
+            ("/quit", "help.quit"),
+        ];
+        let keys = [
+            ("PgUp/PgDn", "help.scroll"),
+            ("Alt+Enter", "help.newline"),
+            ("Alt+Left/Right", "help.cycle_rooms"),
+            ("Alt+Up/Down", "help.select"),
+            ("r", "help.reveal_spoiler"),
+            ("f", "help.forward"),
+            ("s", "help.star"),
+            ("p", "help.pin"),
+            ("t", "help.translate_focused"),
+            ("g", "help.follow_ref"),
+            ("l", "help.cite"),
+            ("Ctrl+K", "help.switcher"),
+            ("Alt+- / Alt+=", "help.resize"),
+            ("F2", "help.zen"),
+            ("F3", "help.user_list"),
+            ("F4", "help.mod_panel"),
+            ("Ctrl+X", "help.reveal"),
+            ("F7", "help.spell_suggest"),
+            ("Esc", "help.toggle_help"),
+        ];
+        let entry = |(usage, key): &(&str, &str)| format!("{} - {}", usage, tr(app.locale, key));
+        let help_text = std::iter::once(tr(app.locale, "help.commands").to_string())
+            .chain(commands.iter().map(entry))
+            .chain([String::new(), tr(app.locale, "help.keys").to_string()])
+            .chain(keys.iter().map(entry))
+            .collect::<Vec<_>>()
+            .join("\n");
         
         let block = Paragraph::new(help_text)
-            .block(Block::default().borders(Borders::ALL).title(" Help ").style(Style::default().bg(Color::DarkGray)));
+            .block(Block::default().borders(Borders::ALL).title(format!(" {} ", tr(app.locale, "ui.help"))).style(Style::default().bg(Color::DarkGray)));
         f.render_widget(Clear, area);
         f.render_widget(block, area);
     }
 }
 
+// A message to draw from the render cache, or a stub standing in for collapsed messages
+enum Row<'a> {
+    Message(&'a ChatMessage),
+    Note(ListItem<'static>),
+}
+
+// Receipts and focus go on top of the cached layout, as they change without the message changing
+fn message_item<'a>(mut text: Text<'a>, msg: &ChatMessage, app: &App, receipt: Option<&'a String>) -> ListItem<'a> {
+    if let (Some(receipt), Some(last)) = (receipt, text.lines.last_mut()) {
+        // Receipts trail the last line of the message
+        last.push_span(Span::styled(receipt.as_str(), Style::default().fg(Color::DarkGray)));
+    }
+    let item = ListItem::new(text);
+    if app.focused.as_ref() == Some(&msg.id) {
+        item.style(Style::default().add_modifier(Modifier::REVERSED))
+    } else {
+        item
+    }
+}
+
+// Styles and wraps a message to `width` columns; cached by the caller, so this only runs
+// for new messages, after a resize, or when a spoiler is revealed
+fn message_text(msg: &ChatMessage, app: &App, width: usize) -> Text<'static> {
+    let (sender_style, content_style) = match msg.msg_type {
+        MessageType::Chat => if msg.username == app.username {
+            (Style::default().fg(Color::Green).add_modifier(Modifier::BOLD), Style::default())
+        } else {
+            (Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD), Style::default())
+        },
+        MessageType::System | MessageType::UserJoin | MessageType::UserLeave | MessageType::RoomChange
+        | MessageType::Pong | MessageType::UserList | MessageType::ReadReceipt | MessageType::Presence | MessageType::AuthRequired
+        | MessageType::Starred | MessageType::Profile | MessageType::RoomList | MessageType::Expired | MessageType::Stats | MessageType::Console | MessageType::Moderation | MessageType::Activity | MessageType::Karma | MessageType::AutoJoin | MessageType::Commands | MessageType::Discover | MessageType::Prompt | MessageType::Capabilities | MessageType::Compression | MessageType::Encoding | MessageType::Preview | MessageType::Unknown => 
+            (Style::default().fg(Color::Yellow), Style::default().fg(Color::Yellow)),
+        MessageType::PrivateMessage => 
+            (Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD), Style::default().fg(Color::LightMagenta)),
+        MessageType::Dice => 
+            (Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD), Style::default().fg(Color::LightBlue).add_modifier(Modifier::ITALIC)),
+        MessageType::Error => 
+            (Style::default().fg(Color::Red), Style::default().fg(Color::Red)),
+    };
+
+    let prefix = match msg.msg_type {
+        MessageType::PrivateMessage => "🔒 ",
+        MessageType::System => "ℹ ",
+        MessageType::Dice => "🎲 ",
+        _ => ""
+    };
+
+    let time = app.config.timestamps.format(msg.timestamp);
+    let indent = " ".repeat(Span::raw(&time).width() + 1);
+    // Spoilers stay hidden until the message is selected and revealed with r
+    let concealed = spoiler::has_spoiler(&msg.content) && !app.revealed.contains(&msg.id);
+    let content = if concealed { spoiler::conceal(&msg.content) } else { spoiler::reveal(&msg.content) };
+    let highlighted = |line: &str| -> Vec<Span<'static>> {
+        app.rules.spans(line, content_style).into_iter().map(|s| Span::styled(s.content.into_owned(), s.style)).collect()
+    };
+    let mut content_lines = content.split(LINE_SEPARATOR);
+    let mut first = vec![
+        Span::styled(format!("{} ", time), Style::default().fg(Color::DarkGray)),
+        Span::raw(prefix),
+        Span::styled(format!("{}: ", msg.sender_name()), sender_style),
+    ];
+    first.extend(highlighted(content_lines.next().unwrap_or_default()));
+    let mut lines = vec![Line::from(first)];
+    if let Some(forwarded) = &msg.forwarded {
+        lines.insert(0, Line::from(Span::styled(
+            format!("{}↪ {}", indent, trf(app.locale, "ui.forwarded_from", &[&forwarded.label()])),
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+        )));
+    }
+    if let Some(origin) = &msg.mirrored_from {
+        lines.insert(0, Line::from(Span::styled(
+            format!("{}⇉ {}", indent, trf(app.locale, "ui.mirrored_from", &[origin])),
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+        )));
+    }
+    // Continuation lines of multi-line messages are indented under the timestamp
+    lines.extend(content_lines.map(|l| {
+        let mut spans = vec![Span::raw(indent.clone())];
+        spans.extend(highlighted(l));
+        Line::from(spans)
+    }));
+    if let (true, Some(last)) = (concealed, lines.last_mut()) {
+        last.push_span(Span::styled(format!(" {}", tr(app.locale, "ui.spoiler_hint")), Style::default().fg(Color::DarkGray)));
+    }
+    if let Some(warning) = &msg.link_warning {
+        lines.push(Line::from(Span::styled(
+            format!("{}⚠ {}", indent, trf(app.locale, "ui.link_warning", &[&warning.url, &warning.threat])),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )));
+    }
+    // A subtitle under the message, once the server has looked at its link
+    if let Some(preview) = &msg.preview {
+        lines.push(Line::from(Span::styled(format!("{}↳ {}", indent, preview.summary()), Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC))));
+    }
+    Text::from(lines.into_iter().flat_map(|line| render_cache::wrap(line, width, &indent)).collect::<Vec<_>>())
+}
+
+fn flush_ignored(items: &mut Vec<Row>, hidden: &mut usize, locale: &str) {
+    if *hidden > 0 {
+        let key = if *hidden == 1 { "ui.ignored_one" } else { "ui.ignored_many" };
+        items.push(Row::Note(ListItem::new(Line::from(Span::styled(
+            trf(locale, key, &[&hidden.to_string()]),
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+        )))));
+        *hidden = 0;
+    }
+}
+
+fn flush_presence(items: &mut Vec<Row>, presence: &mut (usize, usize), locale: &str) {
+    let (joined, left) = *presence;
+    if joined + left == 0 {
+        return;
+    }
+    let mut parts = Vec::new();
+    if joined > 0 {
+        let key = if joined == 1 { "ui.joined_one" } else { "ui.joined_many" };
+        parts.push(trf(locale, key, &[&joined.to_string()]));
+    }
+    if left > 0 {
+        parts.push(trf(locale, "ui.left", &[&left.to_string()]));
+    }
+    items.push(Row::Note(ListItem::new(Line::from(Span::styled(
+        format!("↔ {}", parts.join(", ")),
+        Style::default().fg(Color::DarkGray),
+    )))));
+    *presence = (0, 0);
+}
+
+fn draw_status_bar(f: &mut Frame, app: &App, area: Rect, now: chrono::DateTime<chrono::Local>) {
+    let separator = Span::styled(" │ ", Style::default().fg(Color::DarkGray));
+    let connection = if app.connected {
+        Span::styled(format!("● {}", tr(app.locale, "ui.connected")), Style::default().fg(Color::Green))
+    } else {
+        Span::styled(format!("● {}", tr(app.locale, "ui.disconnected")), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+    };
+    let latency = match app.latency {
+        Some(latency) if app.connected => format!("{} ms", latency.as_millis()),
+        _ => "-- ms".to_string(),
+    };
+    let unread_style = if app.unread > 0 { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default() };
+
+    let mut status = Line::from(vec![
+        connection,
+        separator.clone(),
+        Span::raw(latency),
+        separator.clone(),
+        Span::raw(format!("#{}", app.current_room)),
+        separator.clone(),
+        Span::raw(trf(app.locale, "ui.members", &[&app.users_in_room.len().to_string()])),
+        separator.clone(),
+        Span::styled(trf(app.locale, "ui.unread", &[&app.unread.to_string()]), unread_style),
+        separator.clone(),
+    ]);
+    if app.config.notifications.is_silenced() {
+        status.push_span(Span::styled(tr(app.locale, "ui.dnd"), Style::default().fg(Color::Magenta)));
+        status.push_span(separator.clone());
+    }
+    if let Some(reason) = app.away_users.get(&app.username) {
+        status.push_span(Span::styled(trf(app.locale, "ui.away", &[reason]), Style::default().fg(Color::DarkGray)));
+        status.push_span(separator);
+    }
+    status.push_span(Span::raw(now.format("%H:%M").to_string()));
+    f.render_widget(Paragraph::new(status).style(Style::default().bg(Color::Black)), area);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -352,3 +2064,66 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut config = ClientConfig::default();
+        config.spellcheck.enabled = false;
+        config.aliases = HashMap::from([("ops".to_string(), "/join ops".to_string()), ("shout".to_string(), "/msg $* !!".to_string())]);
+        App::new("alice".to_string(), "alice@localhost:8080", config)
+    }
+
+    fn room(name: &str) -> RoomName {
+        RoomName::new(name).unwrap()
+    }
+
+    #[test]
+    fn pastes_keep_their_lines_and_send_as_one_message() {
+        let mut app = app();
+        app.insert_text("one\r\ntwo\rthree\n");
+        assert!(app.multiline);
+        assert_eq!(app.input.value(), "one\ntwo\nthree\n");
+        assert_eq!(app.take_draft(), format!("one{0}two{0}three", LINE_SEPARATOR));
+        assert!(app.input.value().is_empty() && !app.multiline);
+        app.insert_text("plain");
+        assert!(!app.multiline);
+    }
+
+    #[test]
+    fn aliases_expand_with_or_without_arguments() {
+        let app = app();
+        assert_eq!(app.expand_alias("/ops".to_string()), "/join ops");
+        assert_eq!(app.expand_alias("/ops now".to_string()), "/join ops now");
+        assert_eq!(app.expand_alias("/shout bob  hey ".to_string()), "/msg bob  hey !!");
+        assert_eq!(app.expand_alias("/unknown x".to_string()), "/unknown x");
+        assert_eq!(app.expand_alias("ops".to_string()), "ops");
+    }
+
+    #[test]
+    fn each_room_keeps_its_own_draft() {
+        let mut app = app();
+        app.insert_text("for general");
+        app.switch_room(room("ops"));
+        assert!(app.input.value().is_empty());
+        app.insert_text("line one\nline two");
+        app.switch_room(RoomName::general());
+        assert_eq!(app.input.value(), "for general");
+        assert!(!app.multiline);
+        app.switch_room(room("ops"));
+        assert_eq!(app.input.value(), "line one\nline two");
+        assert!(app.multiline);
+    }
+
+    #[test]
+    fn alt_arrows_cycle_through_visited_rooms() {
+        let mut app = app();
+        assert_eq!(app.adjacent_room(true), None);
+        app.switch_room(room("ops"));
+        app.switch_room(room("dev"));
+        assert_eq!(app.adjacent_room(true), Some(&RoomName::general()));
+        assert_eq!(app.adjacent_room(false), Some(&room("ops")));
+    }
+}
@@ -0,0 +1,154 @@
+use common::i18n::{tr, trf};
+use common::{format_elapsed, ChatMessage, ModerationMember, ModerationState, Report, LINE_SEPARATOR};
+use crossterm::event::KeyCode;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Tabs},
+};
+
+use crate::spoiler;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Tab {
+    #[default]
+    Members,
+    Reports,
+    Pins,
+}
+
+const TABS: [Tab; 3] = [Tab::Members, Tab::Reports, Tab::Pins];
+
+// F4 panel for admins, filled by the `/modpanel` reply. Its actions are the slash commands
+// they stand for, so the server checks them as if they were typed.
+#[derive(Default)]
+pub struct ModPanel {
+    pub state: ModerationState,
+    pub tab: Tab,
+    pub selected: usize,
+}
+
+impl ModPanel {
+    // A fresh reply keeps the tab, and the selection where it still fits
+    pub fn refresh(&mut self, state: ModerationState) {
+        self.state = state;
+        self.selected = self.selected.min(self.len().saturating_sub(1));
+    }
+
+    fn len(&self) -> usize {
+        match self.tab {
+            Tab::Members => self.state.members.len(),
+            Tab::Reports => self.state.reports.len(),
+            Tab::Pins => self.state.pins.len(),
+        }
+    }
+
+    pub fn switch_tab(&mut self, delta: isize) {
+        let index = TABS.iter().position(|tab| *tab == self.tab).unwrap_or_default();
+        self.tab = TABS[(index as isize + delta).rem_euclid(TABS.len() as isize) as usize];
+        self.selected = 0;
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        if self.len() > 0 {
+            self.selected = (self.selected as isize + delta).rem_euclid(self.len() as isize) as usize;
+        }
+    }
+
+    pub fn member(&self) -> Option<&ModerationMember> {
+        self.state.members.get(self.selected).filter(|_| self.tab == Tab::Members)
+    }
+
+    // The selected report's or pin's message, for jumping to it
+    pub fn message(&self) -> Option<&ChatMessage> {
+        match self.tab {
+            Tab::Members => None,
+            Tab::Reports => self.state.reports.get(self.selected).map(|report| &report.message),
+            Tab::Pins => self.state.pins.get(self.selected),
+        }
+    }
+
+    // What a key does to the selected row
+    pub fn command(&self, key: KeyCode) -> Option<String> {
+        let user = self.member().map(|member| member.username.as_str());
+        let message = self.message();
+        match (self.tab, key) {
+            (Tab::Members, KeyCode::Char('k')) => user.map(|user| format!("/kick {}", user)),
+            (Tab::Members, KeyCode::Char('m')) => user.map(|user| format!("/mute {} 10m", user)),
+            (Tab::Members, KeyCode::Char('u')) => user.map(|user| format!("/unmute {}", user)),
+            (Tab::Members, KeyCode::Char('b')) => user.map(|user| format!("/ban {} 1d", user)),
+            (Tab::Reports, KeyCode::Char('d')) => message.map(|msg| format!("/flagged dismiss {}", msg.id)),
+            (Tab::Reports, KeyCode::Char('p')) => message.map(|msg| format!("/pin {}", msg.id)),
+            (Tab::Reports, KeyCode::Char('k')) => message.map(|msg| format!("/kick {}", msg.username)),
+            (Tab::Pins, KeyCode::Delete) => message.map(|msg| format!("/unpin {}", msg.id)),
+            _ => None,
+        }
+    }
+}
+
+pub fn draw(f: &mut Frame, area: Rect, panel: &ModPanel, locale: &str) {
+    f.render_widget(Clear, area);
+    let hint = match panel.tab {
+        Tab::Members => "ui.mod_hint_members",
+        Tab::Reports => "ui.mod_hint_reports",
+        Tab::Pins => "ui.mod_hint_pins",
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", tr(locale, "ui.mod_panel")))
+        .title_bottom(format!(" {} ", tr(locale, hint)))
+        .style(Style::default().fg(Color::Magenta));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let [tabs_area, list_area] = Layout::vertical([Constraint::Length(2), Constraint::Min(0)]).areas(inner);
+
+    let counts = [panel.state.members.len(), panel.state.reports.len(), panel.state.pins.len()];
+    let titles = ["ui.mod_members", "ui.mod_reports", "ui.mod_pins"].iter().zip(counts).map(|(key, count)| format!("{} ({})", tr(locale, key), count));
+    let selected = TABS.iter().position(|tab| *tab == panel.tab);
+    let tabs = Tabs::new(titles).select(selected).style(Style::default().fg(Color::DarkGray)).highlight_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD));
+    f.render_widget(tabs, tabs_area);
+
+    let items: Vec<ListItem> = match panel.tab {
+        Tab::Members => panel.state.members.iter().map(|member| member_line(member, locale)).collect(),
+        Tab::Reports => panel.state.reports.iter().map(report_line).collect(),
+        Tab::Pins => panel.state.pins.iter().map(|msg| ListItem::new(Line::from(message_spans(msg, true)))).collect(),
+    };
+    if items.is_empty() {
+        f.render_widget(List::new([ListItem::new(tr(locale, "ui.mod_empty")).style(Style::default().fg(Color::DarkGray))]), list_area);
+        return;
+    }
+    let list = List::new(items).highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD)).highlight_symbol("› ");
+    f.render_stateful_widget(list, list_area, &mut ListState::default().with_selected(Some(panel.selected)));
+}
+
+fn member_line(member: &ModerationMember, locale: &str) -> ListItem<'static> {
+    let mut spans = vec![Span::styled(member.username.clone(), Style::default().fg(Color::White).add_modifier(Modifier::BOLD))];
+    if member.admin {
+        spans.push(Span::styled(" admin", Style::default().fg(Color::Green)));
+    }
+    if member.warnings > 0 {
+        spans.push(Span::styled(format!(" · {}", trf(locale, "ui.mod_warnings", &[&member.warnings.to_string()])), Style::default().fg(Color::Yellow)));
+    }
+    if let Some(left) = member.muted_secs {
+        spans.push(Span::styled(format!(" · {}", trf(locale, "ui.mod_muted", &[&format_elapsed(left)])), Style::default().fg(Color::Red)));
+    }
+    ListItem::new(Line::from(spans))
+}
+
+fn report_line(report: &Report) -> ListItem<'static> {
+    let mut spans = message_spans(&report.message, false);
+    spans.push(Span::styled(format!(" ({})", report.reason), Style::default().fg(Color::DarkGray)));
+    ListItem::new(Line::from(spans))
+}
+
+// Where and who from, then the first line with spoilers kept hidden
+fn message_spans(msg: &ChatMessage, pinned: bool) -> Vec<Span<'static>> {
+    let content = spoiler::conceal(&msg.content);
+    let first_line = content.split(LINE_SEPARATOR).next().unwrap_or_default().to_string();
+    let place = if pinned { msg.timestamp.format("%Y-%m-%d ").to_string() } else { format!("#{} ", msg.room) };
+    vec![
+        Span::styled(place, Style::default().fg(Color::Cyan)),
+        Span::styled(format!("{}: ", msg.sender_name()), Style::default().fg(Color::White).add_modifier(Modifier::BOLD)),
+        Span::styled(first_line, Style::default().fg(Color::White)),
+    ]
+}
@@ -0,0 +1,122 @@
+use chrono::{Local, NaiveTime};
+use common::{mentions, ChatMessage, MessageType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::spoiler;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyLevel {
+    All,
+    Mentions, // PMs, @mentions and highlight rules with `notify`
+    Nothing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    pub dnd: bool,
+    pub bell: bool,
+    pub desktop: bool,
+    pub default_level: NotifyLevel,
+    pub rooms: HashMap<String, NotifyLevel>,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+// Local-time window ("22:00" to "07:00" wraps past midnight) treated like DND
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            dnd: false,
+            bell: true,
+            desktop: false,
+            default_level: NotifyLevel::Mentions,
+            rooms: HashMap::new(),
+            quiet_hours: None,
+        }
+    }
+}
+
+// What an incoming message is allowed to do
+pub struct Decision {
+    pub alert: bool,  // Bell and/or desktop notification
+    pub unread: bool, // Counts towards the unread badge
+}
+
+impl QuietHours {
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (start, end) = spec.split_once('-')?;
+        NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+        NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+        Some(Self { start: start.trim().to_string(), end: end.trim().to_string() })
+    }
+
+    fn contains(&self, now: NaiveTime) -> bool {
+        let (Ok(start), Ok(end)) = (NaiveTime::parse_from_str(&self.start, "%H:%M"), NaiveTime::parse_from_str(&self.end, "%H:%M")) else {
+            return false;
+        };
+        if start <= end {
+            start <= now && now < end
+        } else {
+            now >= start || now < end
+        }
+    }
+}
+
+impl NotificationConfig {
+    pub fn level_for(&self, room: &str) -> NotifyLevel {
+        self.rooms.get(room).copied().unwrap_or(self.default_level)
+    }
+
+    pub fn is_silenced(&self) -> bool {
+        self.dnd || self.quiet_hours.as_ref().is_some_and(|q| q.contains(Local::now().time()))
+    }
+
+    pub fn decide(&self, msg: &ChatMessage, username: &str, highlighted: bool) -> Decision {
+        if msg.username == username || !matches!(msg.msg_type, MessageType::Chat | MessageType::PrivateMessage) {
+            return Decision { alert: false, unread: msg.msg_type != MessageType::Error };
+        }
+        let mentioned = msg.msg_type == MessageType::PrivateMessage || highlighted || mentions(&msg.content, username) || msg.mentions.iter().any(|u| u == username);
+        let relevant = match self.level_for(&msg.room) {
+            NotifyLevel::All => true,
+            NotifyLevel::Mentions => mentioned,
+            // PMs always get through a muted room
+            NotifyLevel::Nothing => msg.msg_type == MessageType::PrivateMessage,
+        };
+        Decision { alert: relevant && !self.is_silenced(), unread: relevant }
+    }
+
+    pub fn alert(&self, msg: &ChatMessage) {
+        if self.bell {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+        }
+        if self.desktop {
+            desktop_notification(&format!("{} in #{}", msg.username, msg.room), &spoiler::conceal(&msg.content));
+        }
+    }
+}
+
+// Best effort: notify-send on Linux, osascript on macOS; failures are ignored
+fn desktop_notification(title: &str, body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!("display notification {:?} with title {:?}", body, title);
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(script);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg(title).arg(body);
+        command
+    };
+    let _ = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+}
@@ -0,0 +1,59 @@
+use chrono::Utc;
+use common::i18n::tr;
+use common::{parse_utc_offset, UserProfile};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+};
+
+// Popup for a `/profile` reply; any key closes it
+pub struct ProfileCard {
+    pub username: String,
+    pub profile: UserProfile,
+}
+
+impl ProfileCard {
+    pub fn from_json(username: String, content: &str) -> Self {
+        Self {
+            username,
+            profile: serde_json::from_str(content).unwrap_or_default(),
+        }
+    }
+}
+
+pub fn draw(f: &mut Frame, area: Rect, card: &ProfileCard, locale: &str) {
+    f.render_widget(Clear, area);
+    let profile = &card.profile;
+    let title = match &profile.display_name {
+        Some(name) => format!(" {} (@{}) ", name, card.username),
+        None => format!(" @{} ", card.username),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .title_bottom(format!(" {} ", tr(locale, "ui.close_hint")))
+        .style(Style::default().fg(Color::Cyan));
+
+    let label = |key: &str| Span::styled(format!("{}: ", tr(locale, key)), Style::default().fg(Color::DarkGray));
+    let mut lines = Vec::new();
+    if let Some(pronouns) = &profile.pronouns {
+        lines.push(Line::from(vec![label("ui.pronouns"), Span::raw(pronouns.as_str())]));
+    }
+    if let Some(timezone) = &profile.timezone {
+        let mut spans = vec![label("ui.timezone"), Span::raw(timezone.as_str())];
+        if let Some(offset) = parse_utc_offset(timezone) {
+            let now = Utc::now().with_timezone(&offset).format("%H:%M");
+            spans.push(Span::styled(format!(" ({})", now), Style::default().fg(Color::DarkGray)));
+        }
+        lines.push(Line::from(spans));
+    }
+    if let Some(bio) = &profile.bio {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(bio.as_str(), Style::default().fg(Color::White))));
+    }
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(tr(locale, "ui.profile_empty"), Style::default().fg(Color::DarkGray))));
+    }
+    f.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: true }), area);
+}
@@ -0,0 +1,119 @@
+use common::MessageId;
+use ratatui::prelude::*;
+use std::collections::HashMap;
+use unicode_width::UnicodeWidthChar;
+
+// Cached entries beyond this are dropped unless the last frame drew them
+const MAX_ENTRIES: usize = 2000;
+
+// Laid-out message text keyed by message ID, so a redraw only styles and wraps messages
+// it hasn't seen at this width. Receipts and focus change often and are applied on top.
+#[derive(Default)]
+pub struct RenderCache {
+    width: u16,
+    frame: u64,
+    entries: HashMap<MessageId, Entry>,
+}
+
+struct Entry {
+    revealed: bool, // Spoilers shown, which changes the text
+    text: Text<'static>,
+    used: u64,
+}
+
+impl RenderCache {
+    // Call once per frame with the width messages are wrapped to; a resize re-lays out everything
+    pub fn begin(&mut self, width: u16) {
+        if width != self.width {
+            self.entries.clear();
+            self.width = width;
+        }
+        self.frame += 1;
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    // Lays out the message only if it isn't cached in this state already
+    pub fn ensure(&mut self, id: MessageId, revealed: bool, layout: impl FnOnce() -> Text<'static>) {
+        let frame = self.frame;
+        match self.entries.get_mut(&id) {
+            Some(entry) if entry.revealed == revealed => entry.used = frame,
+            _ => {
+                self.entries.insert(id, Entry { revealed, text: layout(), used: frame });
+            }
+        }
+    }
+
+    // Lays the message out again next time, after something beyond spoilers changed
+    pub fn forget(&mut self, id: MessageId) {
+        self.entries.remove(&id);
+    }
+
+    // The cached text, borrowing its strings rather than copying them
+    pub fn text(&self, id: MessageId) -> Text<'_> {
+        let Some(entry) = self.entries.get(&id) else { return Text::default() };
+        Text::from(
+            entry.text.lines.iter()
+                .map(|line| Line::from(line.spans.iter().map(|span| Span::styled(span.content.as_ref(), span.style)).collect::<Vec<_>>()))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    // Forgets messages scrolled far out of view
+    pub fn prune(&mut self) {
+        if self.entries.len() > MAX_ENTRIES {
+            let frame = self.frame;
+            self.entries.retain(|_, entry| entry.used == frame);
+        }
+    }
+}
+
+// Breaks a line to fit `width` columns, preferring the last space; continuation rows start
+// with `indent` so they line up under the message text
+pub fn wrap(line: Line<'static>, width: usize, indent: &str) -> Vec<Line<'static>> {
+    let indent_width = indent.chars().filter_map(|c| c.width()).sum::<usize>();
+    if line.width() <= width || width <= indent_width + 1 {
+        return vec![line];
+    }
+    let chars: Vec<(char, Style)> = line.spans.iter().flat_map(|span| span.content.chars().map(move |c| (c, span.style))).collect();
+    let mut rows: Vec<Vec<(char, Style)>> = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let room = if rows.is_empty() { width } else { width - indent_width };
+        let mut used = 0;
+        let mut end = start;
+        while end < chars.len() && used + chars[end].0.width().unwrap_or(0) <= room {
+            used += chars[end].0.width().unwrap_or(0);
+            end += 1;
+        }
+        if end < chars.len() {
+            // Break after the last space in the row, unless that would leave it empty
+            if let Some(space) = chars[start..end].iter().rposition(|(c, _)| *c == ' ').filter(|&i| i > 0) {
+                end = start + space + 1;
+            }
+        }
+        rows.push(chars[start..end.max(start + 1)].to_vec());
+        start = end.max(start + 1);
+    }
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut spans: Vec<Span<'static>> = if i == 0 { Vec::new() } else { vec![Span::raw(indent.to_string())] };
+            let mut run = String::new();
+            let mut run_style = row.first().map(|(_, style)| *style).unwrap_or_default();
+            for (c, style) in row {
+                if style != run_style && !run.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut run), run_style));
+                }
+                run_style = style;
+                run.push(c);
+            }
+            if !run.is_empty() {
+                spans.push(Span::styled(run, run_style));
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
@@ -0,0 +1,55 @@
+use common::{room_tree, RoomEntry, RoomRow};
+use ratatui::prelude::*;
+
+// True when the row sits inside a collapsed category
+fn hidden(name: &str, collapsed: &[String]) -> bool {
+    collapsed.iter().any(|category| name.strip_prefix(category.as_str()).is_some_and(|rest| rest.starts_with('/')))
+}
+
+// Sidebar rows: "▾ work" headers (▸ with a room count when collapsed) and indented rooms
+pub fn sidebar_lines<'a>(entries: &'a [RoomEntry], current: &str, collapsed: &[String]) -> Vec<Line<'a>> {
+    let mut lines = Vec::new();
+    for row in room_tree(entries) {
+        match row {
+            RoomRow::Category { path, depth } if !hidden(path, collapsed) => {
+                let folded = collapsed.iter().any(|c| c == path);
+                let label = path.rsplit('/').next().unwrap_or_default();
+                let text = if folded {
+                    let rooms = entries.iter().filter(|e| hidden(&e.name, &[path.to_string()])).count();
+                    format!("{}▸ {} ({})", "  ".repeat(depth), label, rooms)
+                } else {
+                    format!("{}▾ {}", "  ".repeat(depth), label)
+                };
+                lines.push(Line::from(Span::styled(text, Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD))));
+            }
+            RoomRow::Room { entry, depth } if !hidden(&entry.name, collapsed) => {
+                let style = if entry.name == current { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default() };
+                let lock = if entry.invite_only { " 🔒" } else { "" };
+                let ttl = if entry.ttl.is_some() { " ⏳" } else { "" };
+                let read_only = if entry.read_only { " 📢" } else { "" };
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{}# {}{}{}{}", "  ".repeat(depth), entry.short_name(), lock, ttl, read_only), style),
+                    Span::styled(format!(" {}", entry.users), Style::default().fg(Color::DarkGray)),
+                ]));
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+// The same tree as indented text, for messages and headless output
+pub fn plain_lines(entries: &[RoomEntry]) -> Vec<String> {
+    room_tree(entries)
+        .into_iter()
+        .map(|row| match row {
+            RoomRow::Category { path, depth } => format!("{}{}/", "  ".repeat(depth), path.rsplit('/').next().unwrap_or_default()),
+            RoomRow::Room { entry, depth } => {
+                let lock = if entry.invite_only { ", invite-only" } else { "" };
+                let ttl = entry.ttl.as_ref().map(|ttl| format!(", messages expire after {}", ttl)).unwrap_or_default();
+                let read_only = if entry.read_only { ", read-only" } else { "" };
+                format!("{}#{} ({} online{}{}{})", "  ".repeat(depth), entry.name, entry.users, lock, ttl, read_only)
+            }
+        })
+        .collect()
+}
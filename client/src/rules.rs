@@ -0,0 +1,159 @@
+use common::pattern::Pattern;
+use common::ChatMessage;
+use ratatui::prelude::*;
+use std::str::FromStr;
+
+use crate::config::ClientConfig;
+
+// `#room/seq` permalinks; namespaced rooms add more segments, as in `#work/standup/12`
+pub const REFERENCE_PATTERN: &str = r"#[\w.-]+(/[\w.-]+)*/\d+\b";
+
+pub struct Highlight {
+    pub pattern: Pattern,
+    pub style: Style,
+    pub notify: bool,
+}
+
+// Highlight and filter rules from the client config, compiled once at startup
+#[derive(Default)]
+pub struct Rules {
+    pub highlights: Vec<Highlight>,
+    pub filters: Vec<Pattern>,
+}
+
+impl Rules {
+    // Invalid rules are skipped and reported so one typo doesn't disable the rest
+    pub fn compile(config: &ClientConfig) -> (Self, Vec<String>) {
+        let mut rules = Rules::default();
+        let mut errors = Vec::new();
+
+        for rule in &config.highlights {
+            let color = match Color::from_str(&rule.color) {
+                Ok(color) => color,
+                Err(_) => {
+                    errors.push(format!("Highlight '{}': unknown color '{}'", rule.pattern, rule.color));
+                    continue;
+                }
+            };
+            match Pattern::new(&rule.pattern) {
+                Ok(pattern) => rules.highlights.push(Highlight {
+                    pattern,
+                    style: Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    notify: rule.notify,
+                }),
+                Err(e) => errors.push(format!("Highlight '{}': {}", rule.pattern, e)),
+            }
+        }
+        // Message permalinks (`#room/42`) last, so user highlights win where they overlap
+        if let Ok(pattern) = Pattern::new(REFERENCE_PATTERN) {
+            rules.highlights.push(Highlight {
+                pattern,
+                style: Style::default().fg(Color::LightBlue).add_modifier(Modifier::UNDERLINED),
+                notify: false,
+            });
+        }
+        for rule in &config.filters {
+            match Pattern::new(&rule.pattern) {
+                Ok(pattern) => rules.filters.push(pattern),
+                Err(e) => errors.push(format!("Filter '{}': {}", rule.pattern, e)),
+            }
+        }
+        (rules, errors)
+    }
+
+    pub fn is_filtered(&self, msg: &ChatMessage) -> bool {
+        self.filters.iter().any(|pattern| pattern.is_match(&msg.content))
+    }
+
+    pub fn should_notify(&self, msg: &ChatMessage) -> bool {
+        self.highlights.iter().any(|h| h.notify && h.pattern.is_match(&msg.content))
+    }
+
+    // Splits text into spans, styling highlight matches; earlier rules win on overlap
+    pub fn spans<'a>(&self, text: &'a str, base: Style) -> Vec<Span<'a>> {
+        let mut matches: Vec<(usize, usize, Style)> = Vec::new();
+        for highlight in &self.highlights {
+            for (start, end) in highlight.pattern.find_iter(text) {
+                let overlaps = matches.iter().any(|(s, e, _)| start < *e && *s < end);
+                if end > start && !overlaps {
+                    matches.push((start, end, highlight.style));
+                }
+            }
+        }
+        matches.sort_by_key(|(start, _, _)| *start);
+
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        for (start, end, style) in matches {
+            if start > pos {
+                spans.push(Span::styled(&text[pos..start], base));
+            }
+            spans.push(Span::styled(&text[start..end], base.patch(style)));
+            pos = end;
+        }
+        if pos < text.len() || spans.is_empty() {
+            spans.push(Span::styled(&text[pos..], base));
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FilterRule, HighlightRule};
+    use common::RoomName;
+    use std::time::{Duration, Instant};
+
+    fn rules(highlights: &[&str], filters: &[&str]) -> Rules {
+        let config = ClientConfig {
+            highlights: highlights.iter().map(|pattern| HighlightRule { pattern: pattern.to_string(), color: "red".to_string(), notify: true }).collect(),
+            filters: filters.iter().map(|pattern| FilterRule { pattern: pattern.to_string() }).collect(),
+            ..Default::default()
+        };
+        let (rules, errors) = Rules::compile(&config);
+        assert!(errors.is_empty(), "{:?}", errors);
+        rules
+    }
+
+    fn chat(content: &str) -> ChatMessage {
+        ChatMessage::chat("bob".to_string(), content.to_string(), RoomName::general())
+    }
+
+    fn styled<'a>(spans: &[Span<'a>]) -> Vec<String> {
+        spans.iter().filter(|span| span.style != Style::default()).map(|span| span.content.to_string()).collect()
+    }
+
+    #[test]
+    fn highlights_and_permalinks_style_their_matches() {
+        let rules = rules(&[r"(?i)\balice\b"], &[]);
+        assert_eq!(styled(&rules.spans("hey Alice, see #ops/12 and malice", Style::default())), vec!["Alice", "#ops/12"]);
+        assert!(rules.should_notify(&chat("ALICE?")));
+        assert!(!rules.should_notify(&chat("#ops/12")));
+    }
+
+    #[test]
+    fn earlier_rules_win_where_matches_overlap() {
+        let rules = rules(&["ab", "bc"], &[]);
+        assert_eq!(styled(&rules.spans("abc bc", Style::default())), vec!["ab", "bc"]);
+    }
+
+    #[test]
+    fn filters_hide_matching_messages() {
+        let rules = rules(&[], &["(joined|left) the room$"]);
+        assert!(rules.is_filtered(&chat("bob joined the room")));
+        assert!(!rules.is_filtered(&chat("bob joined the room late")));
+    }
+
+    // A message from someone else can't hang the client through a user's rules
+    #[test]
+    fn pathological_rules_give_up_on_hostile_messages() {
+        let rules = rules(&[r"(\w+\s?)+$"], &["(a|a)*b"]);
+        let hostile = format!("{}!", "a".repeat(24));
+        let started = Instant::now();
+        assert!(!rules.should_notify(&chat(&hostile)));
+        assert!(!rules.is_filtered(&chat(&"a".repeat(22))));
+        assert!(styled(&rules.spans(&hostile, Style::default())).is_empty());
+        assert!(started.elapsed() < Duration::from_secs(1), "took {:?}", started.elapsed());
+    }
+}
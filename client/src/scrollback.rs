@@ -0,0 +1,109 @@
+use common::{ChatMessage, MessageId, MessageType};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+// Recent room messages kept on disk as one JSONL file per room, so a restarted
+// client shows context before the server's history replay arrives. Each profile
+// (`user@host:port`) has its own directory, since room names repeat across servers
+pub struct Scrollback {
+    dir: Option<PathBuf>,
+    limit: usize,
+}
+
+impl Scrollback {
+    pub fn new(limit: usize, profile: &str) -> Self {
+        let base = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")));
+        Self::in_dir(base.map(|base| base.join("ultimate-chat").join("scrollback")), limit, profile)
+    }
+
+    fn in_dir(base: Option<PathBuf>, limit: usize, profile: &str) -> Self {
+        Self { dir: base.map(|base| base.join(common::encode_file_name(profile))), limit }
+    }
+
+    // Only room traffic is kept; PMs, errors and protocol replies are not scrollback
+    pub fn should_persist(msg: &ChatMessage) -> bool {
+        matches!(msg.msg_type, MessageType::Chat | MessageType::System | MessageType::UserJoin | MessageType::UserLeave)
+    }
+
+    fn room_path(&self, room: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{}.jsonl", common::encode_file_name(room))))
+    }
+
+    // Unreadable lines are skipped so a truncated write doesn't lose the whole file
+    pub fn load(&self, room: &str) -> Vec<ChatMessage> {
+        let Some(contents) = self.room_path(room).and_then(|path| fs::read_to_string(path).ok()) else {
+            return Vec::new();
+        };
+        let messages: Vec<ChatMessage> = contents.lines().filter_map(|line| ChatMessage::from_json(line).ok()).collect();
+        let skip = messages.len().saturating_sub(self.limit);
+        messages.into_iter().skip(skip).collect()
+    }
+
+    // Rewrites the room's file without the given messages
+    pub fn remove(&self, room: &str, ids: &HashSet<MessageId>) -> io::Result<()> {
+        let Some(path) = self.room_path(room).filter(|path| path.exists()) else {
+            return Ok(());
+        };
+        let kept = self.load(room).iter().filter(|m| !ids.contains(&m.id)).map(ChatMessage::to_json).collect::<Result<Vec<_>, _>>().map_err(io::Error::other)?;
+        fs::write(&path, kept.iter().map(|line| format!("{}\n", line)).collect::<String>())
+    }
+
+    pub fn append(&self, msg: &ChatMessage) -> io::Result<()> {
+        let Some(path) = self.room_path(&msg.room) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", msg.to_json().map_err(io::Error::other)?)?;
+
+        // Appends are cheap; trim the file back to the limit once it is roughly twice that
+        if file.metadata()?.len() > (self.limit as u64 * 2 * 256).max(64 * 1024) {
+            let kept = self.load(&msg.room).iter().map(ChatMessage::to_json).collect::<Result<Vec<_>, _>>().map_err(io::Error::other)?;
+            fs::write(&path, kept.join("\n") + "\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::RoomName;
+
+    fn scrollback(name: &str, profile: &str) -> Scrollback {
+        let dir = std::env::temp_dir().join(format!("chat-scrollback-{}-{}", std::process::id(), name));
+        Scrollback::in_dir(Some(dir), 100, profile)
+    }
+
+    fn path(scrollback: &Scrollback, room: &str) -> PathBuf {
+        scrollback.room_path(room).unwrap()
+    }
+
+    #[test]
+    fn rooms_that_used_to_collide_get_their_own_files() {
+        let scrollback = scrollback("paths", "alice@localhost:8080");
+        assert_ne!(path(&scrollback, "work/dev"), path(&scrollback, "work_dev"));
+        assert_ne!(path(&scrollback, "café"), path(&scrollback, "cafè"));
+        assert_ne!(path(&scrollback, "日本"), path(&scrollback, "中国"));
+        assert_eq!(path(&scrollback, "work/dev").file_name().unwrap(), "work%2Fdev.jsonl");
+        // Nothing in a room name can climb out of the directory
+        assert_eq!(path(&scrollback, "../x").parent(), scrollback.dir.as_deref());
+    }
+
+    #[test]
+    fn profiles_keep_their_rooms_apart() {
+        let (alice, bob) = (scrollback("profiles", "alice@localhost:8080"), scrollback("profiles", "bob@example.org:8080"));
+        let _ = fs::remove_dir_all(alice.dir.as_ref().unwrap().parent().unwrap());
+        alice.append(&ChatMessage::chat("alice".to_string(), "hi".to_string(), RoomName::general())).unwrap();
+        assert_eq!(alice.load("general").len(), 1);
+        assert!(bob.load("general").is_empty());
+        assert_eq!(alice.dir.as_ref().unwrap().file_name().unwrap(), "alice%40localhost%3A8080");
+        fs::remove_dir_all(alice.dir.as_ref().unwrap().parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+use common::{i18n::trf, ChatMessage, MessageType};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph},
+};
+use tui_input::Input;
+
+use crate::config::TimestampStyle;
+
+// Ctrl+F / `/find` overlay over the locally loaded scrollback
+#[derive(Default)]
+pub struct Search {
+    pub input: Input,
+    pub selected: usize,
+}
+
+impl Search {
+    pub fn with_query(query: &str) -> Self {
+        Self { input: Input::new(query.to_string()), selected: 0 }
+    }
+
+    // Case-insensitive match on content or sender, newest first
+    pub fn matches<'a>(&self, messages: &'a [ChatMessage]) -> Vec<&'a ChatMessage> {
+        let query = self.input.value().trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        messages
+            .iter()
+            .rev()
+            .filter(|msg| matches!(msg.msg_type, MessageType::Chat | MessageType::PrivateMessage | MessageType::System))
+            .filter(|msg| msg.content.to_lowercase().contains(&query) || msg.username.to_lowercase().contains(&query))
+            .collect()
+    }
+
+    pub fn move_selection(&mut self, delta: isize, len: usize) {
+        if len == 0 {
+            self.selected = 0;
+        } else {
+            self.selected = (self.selected as isize + delta).clamp(0, len as isize - 1) as usize;
+        }
+    }
+}
+
+pub fn draw(f: &mut Frame, area: Rect, search: &Search, hits: &[&ChatMessage], timestamps: TimestampStyle, locale: &str) {
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", trf(locale, "ui.find", &[&hits.len().to_string()])))
+        .style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    f.render_widget(Paragraph::new(format!("/ {}", search.input.value())).style(Style::default().fg(Color::Yellow)), chunks[0]);
+    f.set_cursor_position(Position::new(chunks[0].x + 2 + search.input.visual_cursor() as u16, chunks[0].y));
+
+    let items: Vec<ListItem> = hits
+        .iter()
+        .map(|msg| {
+            let snippet = msg.content_lines().collect::<Vec<_>>().join(" ⏎ ");
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", timestamps.format(msg.timestamp)), Style::default().fg(Color::DarkGray)),
+                Span::styled(format!("{}: ", msg.sender_name()), Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(snippet),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol("› ");
+    let mut state = ListState::default().with_selected((!hits.is_empty()).then_some(search.selected));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
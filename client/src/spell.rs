@@ -0,0 +1,245 @@
+use common::i18n::tr;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+
+const MAX_SUGGESTIONS: usize = 8;
+
+// Word-list dictionaries (one word per line), looked up per language in the
+// user's data directory first, then the system's /usr/share/dict lists
+pub struct SpellChecker {
+    words: HashSet<String>, // Lowercased
+}
+
+impl SpellChecker {
+    // None when no dictionary for the language is installed
+    pub fn load(language: &str, personal: &[String]) -> Option<Self> {
+        let contents = dictionary_paths(language).into_iter().find_map(|path| fs::read_to_string(path).ok())?;
+        let mut words: HashSet<String> = contents.lines().map(|line| line.trim().to_lowercase()).filter(|word| !word.is_empty()).collect();
+        words.extend(personal.iter().map(|word| word.to_lowercase()));
+        Some(Self { words })
+    }
+
+    pub fn add(&mut self, word: &str) {
+        self.words.insert(word.to_lowercase());
+    }
+
+    pub fn is_correct(&self, word: &str) -> bool {
+        // Acronyms and single letters are never flagged
+        word.chars().count() < 2 || !word.chars().any(char::is_lowercase) || self.words.contains(&word.to_lowercase())
+    }
+
+    // Character ranges of misspelled words; the word being typed at the cursor is left alone
+    pub fn misspelled(&self, text: &str, cursor: usize) -> Vec<Range<usize>> {
+        checked_words(text)
+            .into_iter()
+            .filter(|(range, word)| range.end != cursor && !self.is_correct(word))
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    // The misspelled word under the cursor, or else the closest one before it
+    pub fn word_near(&self, text: &str, cursor: usize) -> Option<(Range<usize>, String)> {
+        let words: Vec<_> = checked_words(text).into_iter().filter(|(_, word)| !self.is_correct(word)).collect();
+        let under = words.iter().find(|(range, _)| range.start <= cursor && cursor <= range.end);
+        under.or_else(|| words.iter().rev().find(|(range, _)| range.end <= cursor)).cloned()
+    }
+
+    // Closest dictionary words by edit distance, keeping the word's capitalization
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let len = lower.chars().count();
+        let max_distance = if len <= 3 { 1 } else { 2 };
+        let mut scored: Vec<(usize, &String)> = self
+            .words
+            .iter()
+            .filter(|candidate| candidate.chars().count().abs_diff(len) <= max_distance)
+            .map(|candidate| (strsim::damerau_levenshtein(&lower, candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        scored.sort();
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        scored
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, candidate)| if capitalized { capitalize(candidate) } else { candidate.clone() })
+            .collect()
+    }
+}
+
+fn dictionary_paths(language: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")));
+    if let Some(data) = data {
+        paths.push(data.join("ultimate-chat").join("dictionaries").join(format!("{}.txt", language)));
+    }
+    let system = PathBuf::from("/usr/share/dict");
+    paths.push(system.join(language));
+    // Names used by the distribution word-list packages
+    let known: &[&str] = match language.split(['_', '-']).next().unwrap_or_default() {
+        "en" => &["american-english", "british-english", "words"],
+        "de" => &["ngerman", "ogerman", "swiss"],
+        "es" => &["spanish"],
+        _ => &[],
+    };
+    paths.extend(known.iter().map(|name| system.join(name)));
+    paths
+}
+
+// Words worth checking, as character ranges: commands, their room/user arguments,
+// mentions, channels, links and anything with digits are skipped
+fn checked_words(text: &str) -> Vec<(Range<usize>, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in chars.iter().chain([&' ']).enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                tokens.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    // Only the free-text part of /msg and /away is prose
+    let skip = if !text.starts_with('/') {
+        0
+    } else if text.starts_with("/msg ") {
+        2
+    } else if text.starts_with("/away ") {
+        1
+    } else {
+        tokens.len()
+    };
+
+    let mut words = Vec::new();
+    for token in tokens.into_iter().skip(skip) {
+        let token_text: String = chars[token.clone()].iter().collect();
+        if token_text.starts_with(['@', '#', '/']) || token_text.contains("://") || token_text.starts_with("www.") || token_text.contains(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        // Letters with inner apostrophes ("don't"); other punctuation splits words
+        let mut i = token.start;
+        while i < token.end {
+            if !chars[i].is_alphabetic() {
+                i += 1;
+                continue;
+            }
+            let begin = i;
+            while i < token.end && (chars[i].is_alphabetic() || chars[i] == '\'' && i + 1 < token.end && chars[i + 1].is_alphabetic()) {
+                i += 1;
+            }
+            words.push((begin..i, chars[begin..i].iter().collect()));
+        }
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+// F7 popup: replacements for one word, with adding it to the personal dictionary as the last row
+pub struct SpellPopup {
+    pub range: Range<usize>,
+    pub word: String,
+    pub suggestions: Vec<String>,
+    pub selected: usize,
+}
+
+impl SpellPopup {
+    pub fn rows(&self) -> usize {
+        self.suggestions.len() + 1
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        self.selected = (self.selected as isize + delta).rem_euclid(self.rows() as isize) as usize;
+    }
+}
+
+pub fn draw(f: &mut Frame, area: Rect, popup: &SpellPopup, locale: &str) {
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", popup.word))
+        .style(Style::default().fg(Color::Cyan));
+    let mut items: Vec<ListItem> = popup.suggestions.iter().map(|word| ListItem::new(word.as_str())).collect();
+    if popup.suggestions.is_empty() {
+        items.push(ListItem::new(tr(locale, "ui.no_suggestions")).style(Style::default().fg(Color::DarkGray)));
+    }
+    items.push(ListItem::new(tr(locale, "ui.add_to_dictionary")).style(Style::default().fg(Color::Green)));
+    // With no suggestions the placeholder row is skipped over
+    let selected = if popup.suggestions.is_empty() { popup.selected + 1 } else { popup.selected };
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+        .highlight_symbol("› ");
+    f.render_stateful_widget(list, area, &mut ListState::default().with_selected(Some(selected)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker(words: &[&str]) -> SpellChecker {
+        SpellChecker { words: words.iter().map(|word| word.to_string()).collect() }
+    }
+
+    fn words(text: &str) -> Vec<String> {
+        checked_words(text).into_iter().map(|(_, word)| word).collect()
+    }
+
+    #[test]
+    fn commands_names_links_and_numbers_are_not_prose() {
+        assert_eq!(words("see @bob in #ops at https://x.org or www.x.org, v2 ok"), vec!["see", "in", "at", "or", "ok"]);
+        assert_eq!(words("/join lounge"), Vec::<String>::new());
+        assert_eq!(words("/msg bob helo there"), vec!["helo", "there"]);
+        assert_eq!(words("/away out for lunch"), vec!["out", "for", "lunch"]);
+        assert_eq!(words("don't stop-it 'quoted'"), vec!["don't", "stop", "it", "quoted"]);
+    }
+
+    #[test]
+    fn the_word_being_typed_and_acronyms_are_left_alone() {
+        let spell = checker(&["hello", "world"]);
+        assert_eq!(spell.misspelled("helo wrld", 9), vec![0..4]);
+        assert_eq!(spell.misspelled("helo wrld ", 10), vec![0..4, 5..9]);
+        assert!(spell.misspelled("NASA I x", 0).is_empty());
+        assert!(spell.is_correct("Hello"));
+    }
+
+    #[test]
+    fn the_word_near_the_cursor_is_picked() {
+        let spell = checker(&["hello"]);
+        assert_eq!(spell.word_near("helo hello wrld", 2), Some((0..4, "helo".to_string())));
+        assert_eq!(spell.word_near("helo hello wrld", 8), Some((0..4, "helo".to_string())));
+        assert_eq!(spell.word_near("hello", 5), None);
+    }
+
+    #[test]
+    fn suggestions_rank_by_distance_then_name_and_keep_capitals() {
+        let spell = checker(&["cart", "cast", "cat", "coat", "dog"]);
+        assert_eq!(spell.suggest("cta"), vec!["cat"]);
+        // Ties at one edit go alphabetically, ahead of the word two edits away
+        assert_eq!(spell.suggest("caet"), vec!["cart", "cast", "cat", "coat"]);
+        assert_eq!(spell.suggest("Caet")[0], "Cart");
+        assert!(spell.suggest("zzzzzz").is_empty());
+    }
+
+    #[test]
+    fn added_words_count_in_any_case() {
+        let mut spell = checker(&[]);
+        assert!(!spell.is_correct("rustacean"));
+        spell.add("Rustacean");
+        assert!(spell.is_correct("rustacean"));
+    }
+}
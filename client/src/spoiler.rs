@@ -0,0 +1,33 @@
+// `||text||` marks a spoiler. The server stores it verbatim; the client hides it until
+// revealed. An unmatched `||` is shown as typed.
+const HIDDEN: &str = "▒▒▒";
+
+pub fn has_spoiler(content: &str) -> bool {
+    content.matches("||").count() >= 2
+}
+
+// Every spoiler replaced by a block, including any line breaks inside it
+pub fn conceal(content: &str) -> String {
+    transform(content, |_| HIDDEN)
+}
+
+// Spoiler text shown with the markers dropped
+pub fn reveal(content: &str) -> String {
+    transform(content, |spoiler| spoiler)
+}
+
+fn transform<'a>(content: &'a str, spoiler: impl Fn(&'a str) -> &'a str) -> String {
+    let parts: Vec<&str> = content.split("||").collect();
+    let mut out = String::with_capacity(content.len());
+    for (i, part) in parts.iter().enumerate() {
+        if i % 2 == 1 && i + 1 < parts.len() {
+            out.push_str(spoiler(part));
+        } else {
+            if i % 2 == 1 {
+                out.push_str("||");
+            }
+            out.push_str(part);
+        }
+    }
+    out
+}
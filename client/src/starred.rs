@@ -0,0 +1,75 @@
+use common::i18n::tr;
+use common::{ChatMessage, MessageType, LINE_SEPARATOR};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState},
+};
+
+use crate::config::TimestampStyle;
+use crate::spoiler;
+
+// Browser for the `/starred` reply; Enter jumps to a message, Del unstars it
+pub struct StarredPanel {
+    pub messages: Vec<ChatMessage>,
+    pub selected: usize,
+}
+
+impl StarredPanel {
+    pub fn from_json(content: &str) -> Self {
+        Self {
+            messages: serde_json::from_str(content).unwrap_or_default(),
+            selected: 0,
+        }
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        if !self.messages.is_empty() {
+            self.selected = (self.selected as isize + delta).rem_euclid(self.messages.len() as isize) as usize;
+        }
+    }
+
+    // Drops the selected entry locally; the caller tells the server
+    pub fn remove_selected(&mut self) -> Option<ChatMessage> {
+        if self.selected >= self.messages.len() {
+            return None;
+        }
+        let msg = self.messages.remove(self.selected);
+        self.selected = self.selected.min(self.messages.len().saturating_sub(1));
+        Some(msg)
+    }
+}
+
+pub fn draw(f: &mut Frame, area: Rect, panel: &StarredPanel, timestamps: TimestampStyle, locale: &str) {
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", tr(locale, "ui.starred")))
+        .title_bottom(format!(" {} ", tr(locale, "ui.starred_hint")))
+        .style(Style::default().fg(Color::Yellow));
+    if panel.messages.is_empty() {
+        let empty = List::new([ListItem::new(tr(locale, "ui.starred_empty")).style(Style::default().fg(Color::DarkGray))]).block(block);
+        f.render_widget(empty, area);
+        return;
+    }
+    let items: Vec<ListItem> = panel
+        .messages
+        .iter()
+        .map(|msg| {
+            let place = if msg.msg_type == MessageType::PrivateMessage { "🔒".to_string() } else { format!("#{}", msg.room) };
+            let content = spoiler::conceal(&msg.content);
+            let first_line = content.split(LINE_SEPARATOR).next().unwrap_or_default().to_string();
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", timestamps.format(msg.timestamp)), Style::default().fg(Color::DarkGray)),
+                Span::styled(format!("{} ", place), Style::default().fg(Color::Cyan)),
+                Span::styled(format!("{}: ", msg.sender_name()), Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled(first_line, Style::default().fg(Color::White)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+        .highlight_symbol("› ");
+    f.render_stateful_widget(list, area, &mut ListState::default().with_selected(Some(panel.selected)));
+}
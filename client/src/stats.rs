@@ -0,0 +1,34 @@
+use common::i18n::tr;
+use common::{format_bytes, format_elapsed, ServerStats};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Cell, Clear, Row, Table},
+};
+
+// Label and value pairs, shared by the popup and headless output
+pub fn rows(stats: &ServerStats, locale: &str) -> Vec<(String, String)> {
+    vec![
+        (tr(locale, "ui.stats_uptime").to_string(), format_elapsed(stats.uptime_secs)),
+        (tr(locale, "ui.stats_clients").to_string(), stats.clients.to_string()),
+        (tr(locale, "ui.stats_rooms").to_string(), stats.rooms.to_string()),
+        (tr(locale, "ui.stats_rate").to_string(), stats.messages_per_minute.to_string()),
+        (tr(locale, "ui.stats_history").to_string(), format!("{} ({})", stats.history_messages, format_bytes(stats.history_bytes as u64))),
+        (tr(locale, "ui.stats_queue").to_string(), stats.broadcast_depth.to_string()),
+    ]
+}
+
+// Popup for a `/stats` reply; any key closes it
+pub fn draw(f: &mut Frame, area: Rect, stats: &ServerStats, locale: &str) {
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", tr(locale, "ui.stats")))
+        .title_bottom(format!(" {} ", tr(locale, "ui.close_hint")))
+        .style(Style::default().fg(Color::Cyan));
+    let table_rows = rows(stats, locale).into_iter().map(|(label, value)| {
+        Row::new(vec![Cell::from(label).style(Style::default().fg(Color::DarkGray)), Cell::from(value).style(Style::default().fg(Color::White))])
+    });
+    let table = Table::new(table_rows, [Constraint::Percentage(60), Constraint::Percentage(40)]).block(block);
+    f.render_widget(table, area);
+}
@@ -0,0 +1,175 @@
+use common::{i18n::tr, MessageId, RoomName};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph},
+};
+use tui_input::Input;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Room(String),
+    Contact(String), // Recent PM partner
+}
+
+impl Target {
+    fn label(&self) -> String {
+        match self {
+            Target::Room(room) => format!("#{}", room),
+            Target::Contact(user) => format!("@{}", user),
+        }
+    }
+}
+
+// Ctrl+K popup state: fuzzy query plus the selected row
+#[derive(Default)]
+pub struct Switcher {
+    pub input: Input,
+    pub selected: usize,
+    pub forward: Option<MessageId>, // Picking where to forward this message ID instead of where to go
+}
+
+impl Switcher {
+    // Candidates ranked by fuzzy score; with no matches, the query itself becomes a room to join
+    pub fn matches(&self, rooms: &[RoomName], contacts: &[String]) -> Vec<Target> {
+        let query = self.input.value().trim().trim_start_matches(['#', '@']);
+        let mut scored: Vec<(i32, Target)> = rooms
+            .iter()
+            .map(|room| Target::Room(room.to_string()))
+            .chain(contacts.iter().map(|user| Target::Contact(user.clone())))
+            .filter_map(|target| {
+                let name = match &target {
+                    Target::Room(name) | Target::Contact(name) => name,
+                };
+                fuzzy_score(query, name).map(|score| (score, target))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        let mut targets: Vec<Target> = scored.into_iter().map(|(_, target)| target).collect();
+        if targets.is_empty() && common::is_valid_room_name(query) {
+            targets.push(Target::Room(query.to_string()));
+        }
+        targets
+    }
+
+    pub fn move_selection(&mut self, delta: isize, len: usize) {
+        if len == 0 {
+            self.selected = 0;
+        } else {
+            self.selected = (self.selected as isize + delta).rem_euclid(len as isize) as usize;
+        }
+    }
+}
+
+// Subsequence match: every query char must appear in order. Consecutive runs,
+// word starts and an exact prefix score higher; shorter names break ties.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let candidate_chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+    for q in query.chars() {
+        let found = (pos..candidate_chars.len()).find(|&i| candidate_chars[i].to_lowercase().eq(q.to_lowercase()))?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !candidate_chars[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        pos = found + 1;
+    }
+    if candidate.to_lowercase().starts_with(&query.to_lowercase()) {
+        score += 10;
+    }
+    Some(score * 100 - candidate_chars.len() as i32)
+}
+
+pub fn draw(f: &mut Frame, area: Rect, switcher: &Switcher, targets: &[Target], locale: &str) {
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(" {} ", tr(locale, if switcher.forward.is_some() { "ui.forward_to" } else { "ui.jump_to" })))
+        .style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    f.render_widget(Paragraph::new(format!("> {}", switcher.input.value())).style(Style::default().fg(Color::Yellow)), chunks[0]);
+    f.set_cursor_position(Position::new(chunks[0].x + 2 + switcher.input.visual_cursor() as u16, chunks[0].y));
+
+    let items: Vec<ListItem> = targets.iter().map(|target| ListItem::new(target.label())).collect();
+    let list = List::new(items)
+        .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+        .highlight_symbol("› ");
+    let mut state = ListState::default().with_selected((!targets.is_empty()).then_some(switcher.selected));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rooms(names: &[&str]) -> Vec<RoomName> {
+        names.iter().map(|name| RoomName::new(*name).unwrap()).collect()
+    }
+
+    fn labels(switcher: &Switcher, rooms: &[RoomName], contacts: &[&str]) -> Vec<String> {
+        let contacts: Vec<String> = contacts.iter().map(|c| c.to_string()).collect();
+        switcher.matches(rooms, &contacts).iter().map(Target::label).collect()
+    }
+
+    fn query(text: &str) -> Switcher {
+        Switcher { input: Input::new(text.to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn scores_need_every_character_in_order() {
+        assert!(fuzzy_score("gnl", "general").is_some());
+        assert!(fuzzy_score("lng", "general").is_none());
+        assert!(fuzzy_score("GEN", "general").is_some());
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn prefixes_beat_word_starts_beat_scattered_letters() {
+        let prefix = fuzzy_score("dev", "devops").unwrap();
+        let word_start = fuzzy_score("dev", "web-dev").unwrap();
+        let scattered = fuzzy_score("dev", "dremove").unwrap();
+        assert!(prefix > word_start && word_start > scattered, "{} {} {}", prefix, word_start, scattered);
+        // Between equal matches the shorter name wins
+        assert!(fuzzy_score("dev", "dev").unwrap() > fuzzy_score("dev", "devops").unwrap());
+    }
+
+    #[test]
+    fn ties_keep_rooms_before_contacts_in_their_given_order() {
+        let rooms = rooms(&["ops", "opt"]);
+        assert_eq!(labels(&query("op"), &rooms, &["opa"]), vec!["#ops", "#opt", "@opa"]);
+        assert_eq!(labels(&query("#op"), &rooms, &[]), vec!["#ops", "#opt"]);
+    }
+
+    #[test]
+    fn with_no_match_the_query_is_a_room_to_join() {
+        assert_eq!(labels(&query("#newroom"), &rooms(&["general"]), &["bob"]), vec!["#newroom"]);
+        assert!(labels(&query("no spaces"), &rooms(&["general"]), &[]).is_empty());
+    }
+
+    #[test]
+    fn selection_wraps_both_ways() {
+        let mut switcher = Switcher::default();
+        switcher.move_selection(-1, 3);
+        assert_eq!(switcher.selected, 2);
+        switcher.move_selection(1, 3);
+        assert_eq!(switcher.selected, 0);
+        switcher.move_selection(1, 0);
+        assert_eq!(switcher.selected, 0);
+    }
+}
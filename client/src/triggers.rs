@@ -0,0 +1,191 @@
+use chrono::{DateTime, Utc};
+use common::pattern::Pattern;
+use common::{ChatMessage, MessageType};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::{ClientConfig, TriggerAction};
+
+// Keeps two clients with answering triggers from replying to each other forever
+const COOLDOWN: Duration = Duration::from_secs(5);
+
+struct Trigger {
+    pattern: Pattern,
+    action: TriggerAction,
+    last_fired: Option<Instant>,
+}
+
+// Trigger rules from the client config, compiled once at startup
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+    since: DateTime<Utc>, // Replayed history from before startup never fires
+}
+
+impl Triggers {
+    // Invalid patterns are skipped and reported, like highlight rules
+    pub fn compile(config: &ClientConfig) -> (Self, Vec<String>) {
+        let mut triggers = Vec::new();
+        let mut errors = Vec::new();
+        for rule in &config.triggers {
+            match Pattern::new(&rule.pattern) {
+                Ok(pattern) => triggers.push(Trigger { pattern, action: rule.action.clone(), last_fired: None }),
+                Err(e) => errors.push(format!("Trigger '{}': {}", rule.pattern, e)),
+            }
+        }
+        (Self { triggers, since: Utc::now() }, errors)
+    }
+
+    // Runs every trigger matching a chat message or PM from someone else and
+    // returns the lines to send; `exec` actions are spawned here
+    pub fn fire(&mut self, msg: &ChatMessage, username: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        let incoming = matches!(msg.msg_type, MessageType::Chat | MessageType::PrivateMessage);
+        if !incoming || msg.username == username || msg.timestamp < self.since {
+            return lines;
+        }
+        for trigger in &mut self.triggers {
+            if trigger.last_fired.is_some_and(|at| at.elapsed() < COOLDOWN) || !trigger.pattern.is_match(&msg.content) {
+                continue;
+            }
+            trigger.last_fired = Some(Instant::now());
+            match &trigger.action {
+                TriggerAction::Reply(template) => {
+                    let text = expand(template, msg);
+                    // Message text must never turn a reply into a command
+                    if text.starts_with('/') && !template.starts_with('/') {
+                        continue;
+                    }
+                    if msg.msg_type == MessageType::PrivateMessage {
+                        lines.push(format!("/msg {} {}", msg.username, text));
+                    } else {
+                        lines.push(text);
+                    }
+                }
+                // Commands only see $user and $room, which cannot contain spaces
+                TriggerAction::Command(command) => lines.push(command.replace("$user", &msg.username).replace("$room", &msg.room)),
+                TriggerAction::Exec(program) => run(program, msg),
+            }
+        }
+        lines
+    }
+}
+
+fn expand(template: &str, msg: &ChatMessage) -> String {
+    template.replace("$user", &msg.username).replace("$room", &msg.room).replace("$message", &msg.content)
+}
+
+// The message is passed in CHAT_* environment variables rather than spliced into
+// the command line, so its text cannot inject shell syntax; failures are ignored.
+// A thread waits for each child, so finished ones don't linger as zombies
+fn run(program: &str, msg: &ChatMessage) {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(program);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(program);
+        command
+    };
+    let child = command
+        .env("CHAT_USER", &msg.username)
+        .env("CHAT_ROOM", msg.room.as_str())
+        .env("CHAT_MESSAGE", &msg.content)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Ok(mut child) = child {
+        std::thread::spawn(move || child.wait());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TriggerRule;
+    use common::RoomName;
+
+    fn compile(rules: &[(&str, TriggerAction)]) -> Triggers {
+        let config = ClientConfig {
+            triggers: rules.iter().map(|(pattern, action)| TriggerRule { pattern: pattern.to_string(), action: action.clone() }).collect(),
+            ..Default::default()
+        };
+        let (mut triggers, errors) = Triggers::compile(&config);
+        assert!(errors.is_empty(), "{:?}", errors);
+        triggers.since = DateTime::<Utc>::MIN_UTC;
+        triggers
+    }
+
+    fn chat(username: &str, content: &str) -> ChatMessage {
+        ChatMessage::chat(username.to_string(), content.to_string(), RoomName::general())
+    }
+
+    #[test]
+    fn replies_go_back_where_the_message_came_from() {
+        let mut triggers = compile(&[("(?i)^!ping$", TriggerAction::Reply("pong, $user".to_string()))]);
+        assert_eq!(triggers.fire(&chat("bob", "!PING"), "alice"), vec!["pong, bob"]);
+        let mut triggers = compile(&[("!ping", TriggerAction::Reply("pong".to_string()))]);
+        let pm = ChatMessage::private("bob".to_string(), common::Username::new("alice").unwrap(), "!ping".to_string());
+        assert_eq!(triggers.fire(&pm, "alice"), vec!["/msg bob pong"]);
+    }
+
+    #[test]
+    fn message_text_never_becomes_a_command() {
+        let mut triggers = compile(&[("echo", TriggerAction::Reply("$message".to_string())), ("hello", TriggerAction::Command("/thank $user".to_string()))]);
+        assert!(triggers.fire(&chat("bob", "/kick alice echo"), "alice").is_empty());
+        assert_eq!(triggers.fire(&chat("bob", "hello"), "alice"), vec!["/thank bob"]);
+    }
+
+    #[test]
+    fn own_messages_old_history_and_repeats_dont_fire() {
+        let mut triggers = compile(&[("hi", TriggerAction::Reply("hi!".to_string()))]);
+        assert!(triggers.fire(&chat("alice", "hi"), "alice").is_empty());
+        assert_eq!(triggers.fire(&chat("bob", "hi"), "alice"), vec!["hi!"]);
+        // Within the cooldown, so two answering clients can't loop
+        assert!(triggers.fire(&chat("carol", "hi"), "alice").is_empty());
+
+        let mut triggers = compile(&[("hi", TriggerAction::Reply("hi!".to_string()))]);
+        triggers.since = Utc::now() + chrono::Duration::minutes(1);
+        assert!(triggers.fire(&chat("bob", "hi"), "alice").is_empty());
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported_and_skipped() {
+        let config = ClientConfig {
+            triggers: vec![TriggerRule { pattern: "(oops".to_string(), action: TriggerAction::Reply("x".to_string()) }],
+            ..Default::default()
+        };
+        let (triggers, errors) = Triggers::compile(&config);
+        assert!(triggers.triggers.is_empty());
+        assert_eq!(errors, vec!["Trigger '(oops': unbalanced parenthesis at 0"]);
+    }
+
+    // Children of this process that have exited but were never waited for
+    #[cfg(target_os = "linux")]
+    fn zombies() -> usize {
+        let tasks = std::fs::read_dir("/proc/self/task").unwrap();
+        let children: Vec<String> = tasks
+            .filter_map(|task| std::fs::read_to_string(task.ok()?.path().join("children")).ok())
+            .flat_map(|list| list.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+            .collect();
+        children
+            .iter()
+            .filter_map(|pid| std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok())
+            .filter(|stat| stat.rsplit_once(')').is_some_and(|(_, rest)| rest.trim_start().starts_with('Z')))
+            .count()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn exec_triggers_leave_no_zombies() {
+        let mut triggers = compile(&[("deploy", TriggerAction::Exec("exit 0".to_string()))]);
+        assert!(triggers.fire(&chat("bob", "deploy failed"), "alice").is_empty());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        std::thread::sleep(Duration::from_millis(100));
+        while zombies() > 0 {
+            assert!(Instant::now() < deadline, "the exec trigger's process was never reaped");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
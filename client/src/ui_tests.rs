@@ -0,0 +1,454 @@
+// Snapshot tests for `draw_ui`: each renders an app into ratatui's `TestBackend` and compares
+// the screen text line by line. When a UI change is intended, copy the actual screen from the
+// failure message into the expected lines.
+
+use super::*;
+use chrono::{Local, TimeZone, Utc};
+use common::{ActivityReport, ConsoleConnection, DiscoveredRoom, Discovery, ModerationMember, ModerationState, PromptField, PromptOption, Report, RoomActivity};
+use config::HighlightRule;
+use ratatui::backend::TestBackend;
+use unicode_width::UnicodeWidthStr;
+
+// Nothing here depends on the machine: English text, no dictionary, and a fixed clock
+fn app_with(mut config: ClientConfig) -> App {
+    config.locale = Some("en".to_string());
+    config.spellcheck.enabled = false;
+    let mut app = App::new("alice".to_string(), "alice@localhost:8080", config);
+    app.connected = true;
+    app.latency = Some(Duration::from_millis(12));
+    app
+}
+
+fn app() -> App {
+    app_with(ClientConfig::default())
+}
+
+// Message times are shown in local time, so they are made from local time too
+fn chat(username: &str, content: &str, minute: u32) -> ChatMessage {
+    let mut msg = ChatMessage::chat(username.to_string(), content.to_string(), RoomName::general());
+    msg.timestamp = Local.with_ymd_and_hms(2024, 1, 15, 12, minute, 0).unwrap().with_timezone(&Utc);
+    msg
+}
+
+fn room(name: &str, users: usize) -> RoomEntry {
+    RoomEntry { name: name.to_string(), users, invite_only: false, ttl: None, read_only: false }
+}
+
+fn render(app: &mut App, width: u16, height: u16) -> Terminal<TestBackend> {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(|f| draw_ui(f, app, Local.with_ymd_and_hms(2024, 1, 15, 12, 30, 0).unwrap())).unwrap();
+    terminal
+}
+
+// The screen as text, one string per row; styles are checked separately
+fn screen(terminal: &Terminal<TestBackend>) -> Vec<String> {
+    let buffer = terminal.backend().buffer();
+    (0..buffer.area.height)
+        .map(|y| {
+            let mut line = String::new();
+            let mut x = 0;
+            while x < buffer.area.width {
+                let symbol = buffer[(x, y)].symbol();
+                line.push_str(symbol);
+                // Wide characters cover the next cell too
+                x += symbol.width().max(1) as u16;
+            }
+            line
+        })
+        .collect()
+}
+
+fn assert_screen(terminal: &Terminal<TestBackend>, expected: &[&str]) {
+    let actual = screen(terminal);
+    assert!(actual == expected, "screen differs, actual:\n{}", actual.iter().map(|line| format!("    {:?},", line)).collect::<Vec<_>>().join("\n"));
+}
+
+// Where `text` first appears on screen
+fn find(terminal: &Terminal<TestBackend>, text: &str) -> Position {
+    let screen = screen(terminal);
+    let (y, row, x) = screen.iter().enumerate().find_map(|(y, row)| row.find(text).map(|x| (y, row, x))).unwrap_or_else(|| panic!("{:?} not on screen", text));
+    Position::new(row[..x].width() as u16, y as u16)
+}
+
+#[test]
+fn chat_list_puts_the_newest_message_at_the_bottom() {
+    let mut app = app();
+    app.config.layout.show_sidebar = false;
+    app.messages.push(chat("bob", "hi alice", 1));
+    app.messages.push(chat("alice", "hello bob", 2));
+    app.messages.push(chat("carol", "morning all", 3));
+    let terminal = render(&mut app, 60, 12);
+    assert_screen(&terminal, &[
+        "╭ Messages (3) ────────────────────────────────────────────╮",
+        "│                                                          │",
+        "│                                                          │",
+        "│                                                          │",
+        "│12:01 bob: hi alice                                       │",
+        "│12:02 alice: hello bob                                    │",
+        "│12:03 carol: morning all                                  │",
+        "╰──────────────────────────────────────────────────────────╯",
+        "╭ Input ───────────────────────────────────────────────────╮",
+        "│                                                          │",
+        "╰──────────────────────────────────────────────────────────╯",
+        "● Connected │ 12 ms │ #general │ 0 members │ 0 unread │ 12:3",
+    ]);
+}
+
+#[test]
+fn sidebar_lists_rooms_categories_and_users() {
+    let mut app = app();
+    app.rooms = vec![room("general", 3), room("work/dev", 1), room("work/ops", 0)];
+    app.users_in_room = vec!["alice".to_string(), "bob".to_string(), "carol".to_string()];
+    app.away_users.insert("bob".to_string(), "lunch".to_string());
+    let terminal = render(&mut app, 80, 19);
+    assert_screen(&terminal, &[
+        "╭ Info ────────╮╭ Messages (0) ────────────────────────────────────────────────╮",
+        "│Room: general ││                                                              │",
+        "│              ││                                                              │",
+        "│Rooms:        ││                                                              │",
+        "│# general 3   ││                                                              │",
+        "│▾ work        ││                                                              │",
+        "│  # dev 1     ││                                                              │",
+        "│  # ops 0     ││                                                              │",
+        "│              ││                                                              │",
+        "│Users:        ││                                                              │",
+        "│• alice       ││                                                              │",
+        "│• bob (away)  ││                                                              │",
+        "│• carol       ││                                                              │",
+        "│              ││                                                              │",
+        "╰──────────────╯╰──────────────────────────────────────────────────────────────╯",
+        "╭ Input ───────────────────────────────────────────────────────────────────────╮",
+        "│                                                                              │",
+        "╰──────────────────────────────────────────────────────────────────────────────╯",
+        "● Connected │ 12 ms │ #general │ 3 members │ 0 unread │ 12:30                   ",
+    ]);
+}
+
+#[test]
+fn help_overlay_covers_the_chat() {
+    let mut app = app();
+    app.messages.push(chat("bob", "hi alice", 1));
+    app.show_help = true;
+    let terminal = render(&mut app, 80, 24);
+    assert_screen(&terminal, &[
+        "╭ Info ────────╮╭ Messages (1) ────────────────────────────────────────────────╮",
+        "│Room: general ││                                                              │",
+        "│           ┌ Help ────────────────────────────────────────────────┐           │",
+        "│Users:     │Commands:                                             │           │",
+        "│           │/join <room> - Switch rooms                           │           │",
+        "│           │/join --code <code> - Join an invite-only room with a │           │",
+        "│           │/rooms - List rooms, grouped by category              │           │",
+        "│           │/discover [keyword] - Find open rooms by name, tag or │           │",
+        "│           │/autojoin [add|remove <room>] - Rooms you start in eac│           │",
+        "│           │/ttl [24h|off] - Show or set how long this room keeps │           │",
+        "│           │/collapse|/expand [category] - Fold or unfold a sideba│           │",
+        "│           │/invitecode create <24h>|list|revoke|off - Codes for a│           │",
+        "│           │/msg <user> <msg> - Private Message                   │           │",
+        "│           │/users - List users                                   │           │",
+        "│           │/activity [room] - Graph when this room, or another, i│           │",
+        "│           │/thank <user>, <user>++ - Thank someone in a room with│           │",
+        "│           │/leaderboard - Who has the most karma here            │           │",
+        "│           │/trivia start|stop|scores - Play trivia in this room  │           │",
+        "│           │/roll 2d20+3 - Roll dice for the room to see          │           │",
+        "╰───────────│/flip, /choose a|b|c - Flip a coin, or pick one of the│───────────╯",
+        "╭ Input ────│/ignore [user] - Hide a user's messages (/unignore to │───────────╮",
+        "│           └──────────────────────────────────────────────────────┘           │",
+        "╰──────────────────────────────────────────────────────────────────────────────╯",
+        "● Connected │ 12 ms │ #general │ 0 members │ 0 unread │ 12:30                   ",
+    ]);
+}
+
+#[test]
+fn long_messages_wrap_under_the_timestamp() {
+    let mut app = app();
+    app.config.layout.show_sidebar = false;
+    app.messages.push(chat("bob", "a long message that has to wrap onto a second line and then a third", 4));
+    app.messages.push(chat("alice", "short", 5));
+    let terminal = render(&mut app, 40, 11);
+    assert_screen(&terminal, &[
+        "╭ Messages (2) ────────────────────────╮",
+        "│                                      │",
+        "│12:04 bob: a long message that has to │",
+        "│      wrap onto a second line and     │",
+        "│      then a third                    │",
+        "│12:05 alice: short                    │",
+        "╰──────────────────────────────────────╯",
+        "╭ Input ───────────────────────────────╮",
+        "│                                      │",
+        "╰──────────────────────────────────────╯",
+        "● Connected │ 12 ms │ #general │ 0 membe",
+    ]);
+}
+
+#[test]
+fn senders_and_highlights_are_styled() {
+    let mut config = ClientConfig::default();
+    config.highlights.push(HighlightRule { pattern: "deploy".to_string(), color: "red".to_string(), notify: false });
+    let mut app = app_with(config);
+    app.config.layout.show_sidebar = false;
+    app.messages.push(chat("bob", "time to deploy", 1));
+    app.messages.push(chat("alice", "ok", 2));
+    let terminal = render(&mut app, 40, 9);
+    let buffer = terminal.backend().buffer();
+    let style = |at: Position| buffer[(at.x, at.y)].style();
+
+    let (bob, mine) = (find(&terminal, "bob:"), find(&terminal, "alice:"));
+    assert_eq!(style(bob).fg, Some(Color::Cyan));
+    assert!(style(bob).add_modifier.contains(Modifier::BOLD));
+    assert_eq!(style(mine).fg, Some(Color::Green));
+    assert_eq!(style(find(&terminal, "12:01")).fg, Some(Color::DarkGray));
+    let deploy = find(&terminal, "deploy");
+    assert_eq!(style(deploy).fg, Some(Color::Red));
+    // Only the match is colored
+    assert_eq!(style(Position::new(deploy.x - 2, deploy.y)).fg, Some(Color::Reset));
+}
+
+#[test]
+fn console_lists_connections_rooms_and_command_output() {
+    let now = Utc.with_ymd_and_hms(2024, 1, 15, 12, 30, 0).unwrap();
+    let mut console = console::Console::new("en");
+    let connection = |username: &str, admin: bool, minutes: i64| ConsoleConnection {
+        username: username.to_string(),
+        room: "general".to_string(),
+        addr: "127.0.0.1:50000".to_string(),
+        since: Some(now - chrono::Duration::minutes(minutes)),
+        admin,
+        bytes_in: 2048,
+        ..Default::default()
+    };
+    console.state.connections = vec![connection("alice", true, 5), connection("bob", false, 90)];
+    console.state.rooms = vec![RoomActivity { room: "general".to_string(), users: 2, messages_per_minute: 14 }];
+    console.state.actions = vec!["alice kicked mallory".to_string()];
+    console.note("> /stats");
+    console.input = "kick bob".to_string();
+    let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+    terminal.draw(|f| console::draw(f, &console, now)).unwrap();
+
+    find(&terminal, "Connections");
+    assert_eq!(find(&terminal, "bob").y, find(&terminal, "alice ").y + 1);
+    find(&terminal, "1h 30m");
+    find(&terminal, "2.0 KiB / 0 B");
+    find(&terminal, "alice kicked mallory");
+    find(&terminal, "> /stats");
+    find(&terminal, "/kick bob");
+    let rate = find(&terminal, "14");
+    assert_eq!(rate.y, find(&terminal, "#general").y);
+    let alice = find(&terminal, "alice ");
+    assert_eq!(terminal.backend().buffer()[(alice.x, alice.y)].style().fg, Some(Color::Green));
+}
+
+#[test]
+fn moderation_panel_tabs_through_members_and_reports() {
+    let mut app = app();
+    let member = |username: &str, admin: bool, warnings: usize, muted_secs: Option<u64>| ModerationMember { username: username.to_string(), admin, warnings, muted_secs };
+    let spam = chat("bob", "buy my stuff", 5);
+    app.mod_panel = Some(moderation::ModPanel {
+        state: ModerationState {
+            members: vec![member("alice", true, 0, None), member("bob", false, 2, Some(600))],
+            reports: vec![Report { message: spam.clone(), reason: "reported by alice: spam".to_string() }],
+            pins: Vec::new(),
+        },
+        ..Default::default()
+    });
+    let terminal = render(&mut app, 100, 20);
+    find(&terminal, "Members (2)");
+    find(&terminal, "bob · 2 warnings · muted 10m");
+    find(&terminal, "k kick");
+    assert_eq!(app.mod_panel.as_ref().unwrap().command(KeyCode::Char('m')), Some("/mute alice 10m".to_string()));
+
+    let panel = app.mod_panel.as_mut().unwrap();
+    panel.switch_tab(1);
+    assert_eq!(panel.command(KeyCode::Char('d')), Some(format!("/flagged dismiss {}", spam.id)));
+    let terminal = render(&mut app, 100, 20);
+    find(&terminal, "#general bob: buy my stuff (reported by alice: spam)");
+    app.mod_panel.as_mut().unwrap().switch_tab(1);
+    let terminal = render(&mut app, 100, 20);
+    find(&terminal, "Nothing here");
+}
+
+#[test]
+fn activity_popup_graphs_the_day_and_the_hours() {
+    let mut app = app();
+    let mut by_hour = vec![0; 24];
+    by_hour[14] = 9;
+    by_hour[3] = 2;
+    let mut last_day = vec![0; 24];
+    last_day[20] = 4;
+    last_day[23] = 1;
+    app.activity = Some(ActivityReport { room: "general".to_string(), until: None, last_day, by_hour });
+    let terminal = render(&mut app, 100, 30);
+    find(&terminal, "Activity in #general");
+    find(&terminal, "5 messages in the last 24 hours");
+    let offset = Local.with_ymd_and_hms(2024, 1, 15, 12, 30, 0).unwrap().offset().local_minus_utc() / 3600;
+    find(&terminal, &format!("busiest around {:02}:00", (14 + offset).rem_euclid(24)));
+    let (ago, now) = (find(&terminal, "24h ago"), find(&terminal, "now"));
+    assert_eq!(ago.y, now.y);
+    find(&terminal, "00 01 02");
+}
+
+#[test]
+fn karma_badges_follow_the_room_scores() {
+    let mut app = app();
+    app.users_in_room = vec!["alice".to_string(), "bob".to_string()];
+    let scores = |pairs: &[(&str, u64)], replace: bool| {
+        let scores = KarmaScores { scores: pairs.iter().map(|(user, score)| (user.to_string(), *score)).collect(), replace };
+        ChatMessage::new("System".to_string(), serde_json::to_string(&scores).unwrap(), app.current_room.clone(), MessageType::Karma)
+    };
+    let (everyone, thanked, off) = (scores(&[("alice", 3), ("bob", 1)], true), scores(&[("bob", 2)], false), scores(&[], true));
+    app.handle_message(everyone);
+    app.handle_message(thanked);
+    let terminal = render(&mut app, 100, 30);
+    find(&terminal, "alice +3");
+    find(&terminal, "bob +2");
+    app.handle_message(off);
+    assert!(app.karma.is_empty());
+}
+
+#[test]
+fn dice_results_stand_out_from_chat() {
+    let mut app = app();
+    let mut roll = chat("bob", "rolled 2d20+3: 14 + 7 + 3 = 24", 2);
+    roll.msg_type = MessageType::Dice;
+    app.messages.push(roll);
+    let terminal = render(&mut app, 80, 12);
+    let at = find(&terminal, "🎲 bob: rolled 2d20+3: 14 + 7 + 3 = 24");
+    let result = &terminal.backend().buffer()[(at.x + 8, at.y)];
+    assert_eq!((result.fg, result.modifier.contains(Modifier::ITALIC)), (Color::LightBlue, true));
+}
+
+#[test]
+fn server_prompts_open_as_forms() {
+    let mut app = app();
+    let prompt = Prompt {
+        id: "room".to_string(),
+        step: 2,
+        steps: 3,
+        title: "ui.onboarding_title".to_string(),
+        text: "ui.onboarding_room".to_string(),
+        field: PromptField::Choice {
+            options: ["lounge", "general"].iter().map(|room| PromptOption { value: room.to_string(), label: None }).collect(),
+            selected: vec!["lounge".to_string()],
+            multiple: false,
+        },
+    };
+    let content = serde_json::to_string(&prompt).unwrap();
+    app.handle_message(ChatMessage::new("System".to_string(), content, RoomName::global(), MessageType::Prompt));
+    let terminal = render(&mut app, 80, 24);
+    find(&terminal, "Welcome (2 of 3)");
+    find(&terminal, "Pick the rooms to start with");
+    find(&terminal, "› lounge");
+    find(&terminal, "↑↓: choose · Enter: next · Esc: skip");
+
+    let key = |code| event::KeyEvent::new(code, KeyModifiers::NONE);
+    assert_eq!(app.handle_form_key(key(KeyCode::Down)), None);
+    assert_eq!(app.handle_form_key(key(KeyCode::Enter)).as_deref(), Some("/answer room general"));
+    assert!(app.form.is_none());
+}
+
+#[test]
+fn discovered_rooms_show_who_is_there_and_when_they_were_active() {
+    let mut app = app();
+    let active = Local.with_ymd_and_hms(2024, 1, 15, 12, 25, 0).unwrap().with_timezone(&Utc);
+    let discovery = Discovery {
+        query: "music".to_string(),
+        rooms: vec![
+            DiscoveredRoom { name: "lounge".to_string(), description: Some("Records and mixes".to_string()), tags: vec!["chill".to_string(), "music".to_string()], users: 3, messages_today: 12, last_active: Some(active) },
+            DiscoveredRoom { name: "bandcamp".to_string(), tags: vec!["music".to_string()], ..Default::default() },
+        ],
+    };
+    let content = serde_json::to_string(&discovery).unwrap();
+    app.handle_message(ChatMessage::new("System".to_string(), content, RoomName::general(), MessageType::Discover));
+    let terminal = render(&mut app, 100, 24);
+    find(&terminal, "Rooms matching \"music\"");
+    find(&terminal, "› #lounge 3 here · 12 today · active 5m ago #chill #music");
+    find(&terminal, "Records and mixes");
+    find(&terminal, "#bandcamp 0 here · 0 today · no recent messages #music");
+
+    let key = |code| event::KeyEvent::new(code, KeyModifiers::NONE);
+    assert_eq!(app.handle_discover_key(key(KeyCode::Down)), None);
+    assert_eq!(app.handle_discover_key(key(KeyCode::Enter)).as_deref(), Some("/join bandcamp"));
+    assert!(app.discover.is_none());
+}
+
+#[test]
+fn read_only_rooms_say_so_in_place_of_the_input() {
+    let mut app = app();
+    app.rooms = vec![RoomEntry { read_only: true, ..room("general", 3) }];
+    let terminal = render(&mut app, 100, 24);
+    find(&terminal, "╭ Read-only ");
+    find(&terminal, "Read-only: only voiced users and moderators post here");
+
+    app.rooms = vec![room("general", 3)];
+    let terminal = render(&mut app, 100, 24);
+    find(&terminal, "╭ Input ");
+}
+
+#[test]
+fn refusals_show_beside_the_input() {
+    let mut app = app();
+    app.last_sent = Some("too soon".to_string());
+    let refused = ChatMessage::error("Slow down".to_string()).with_reason(ErrorReason::RateLimited { retry_after: 30 });
+    app.handle_message(refused);
+    assert!(app.messages.is_empty());
+    assert_eq!(app.input.value(), "too soon");
+    assert!(app.cooldown_left(Utc::now()).is_some_and(|left| (29..=30).contains(&left)));
+
+    // Drawn against the test clock, a mute ending in 90 seconds
+    let until = Local.with_ymd_and_hms(2024, 1, 15, 12, 31, 30).unwrap().with_timezone(&Utc);
+    app.handle_message(ChatMessage::error("You are muted".to_string()).with_reason(ErrorReason::Muted { until }));
+    app.input = Input::new("x".repeat(3990));
+    let terminal = render(&mut app, 80, 24);
+    find(&terminal, "Muted: 1m");
+    find(&terminal, "3990/4000");
+}
+
+#[test]
+fn usage_errors_show_under_the_input_and_tab_takes_the_example() {
+    let mut app = app();
+    app.last_sent = Some("/roll 2d".to_string());
+    let usage = CommandError::new("/roll", "<dice>").with_hint("/roll 2d20+3");
+    app.handle_message(usage.to_message());
+    assert!(app.messages.is_empty());
+    assert_eq!(app.input.value(), "/roll 2d");
+    let terminal = render(&mut app, 80, 24);
+    find(&terminal, "/roll <dice> · Tab: /roll 2d20+3");
+
+    // Local commands say how they are used in the same place
+    app.input.reset();
+    assert!(app.handle_local_command("/notify loud"));
+    let terminal = render(&mut app, 80, 24);
+    find(&terminal, "/notify [all|mentions|none]");
+}
+
+#[test]
+fn typing_a_slash_offers_matching_commands() {
+    let mut app = app();
+    app.handle_message(ChatMessage::new(
+        "System".to_string(),
+        r#"[{"name":"/join","args":"<room>","help":"help.join"},{"name":"/kick","args":"<user>","help":"help.kick"}]"#.to_string(),
+        RoomName::global(),
+        MessageType::Commands,
+    ));
+    assert!(app.messages.is_empty());
+    app.input = Input::new("/j".to_string());
+    let terminal = render(&mut app, 80, 24);
+    find(&terminal, "/join <room>  Switch rooms");
+    assert!(!screen(&terminal).iter().any(|row| row.contains("/kick")));
+
+    // Client-side commands are offered too; Down picks the next, Tab takes it
+    let key = |code| event::KeyEvent::new(code, KeyModifiers::NONE);
+    app.input = Input::new("/".to_string());
+    assert_eq!(app.completions().first().map(|command| command.name.as_str()), Some("/alias"));
+    assert!(app.handle_completion_key(key(KeyCode::Down)));
+    assert!(app.handle_completion_key(key(KeyCode::Tab)));
+    assert_eq!(app.input.value(), "/collapse ");
+    assert!(app.completions().is_empty());
+
+    // Enter on a name typed out in full sends it; Esc closes the popup until the input changes
+    app.input = Input::new("/kick".to_string());
+    assert!(!app.handle_completion_key(key(KeyCode::Enter)));
+    app.input = Input::new("/ki".to_string());
+    assert!(app.handle_completion_key(key(KeyCode::Esc)));
+    assert!(app.completions().is_empty());
+    assert!(!app.handle_completion_key(key(KeyCode::Enter)));
+}
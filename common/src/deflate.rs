@@ -0,0 +1,344 @@
+use crate::ChatError;
+
+// Raw DEFLATE (RFC 1951) for compressed connections. Each chunk is a complete stream, but
+// both ends remember the last 32 KiB that went through, and later chunks may refer back
+// into it like a preset dictionary; that is where JSON lines find most of their repeats.
+// The compressor only writes fixed-code blocks; the decompressor reads any block type.
+//
+// On the wire each chunk is framed as a 4-byte big-endian length and the compressed bytes.
+
+pub const NAME: &str = "deflate"; // As offered in `Handshake::compression`
+pub const FRAME_HEADER: usize = 4;
+pub const MAX_FRAME: usize = 4 << 20; // Larger frames are refused as corrupt
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_SIZE: usize = 1 << 15;
+const MAX_CHAIN: usize = 64; // Earlier positions tried per match; more compresses better, slower
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// Order the code length code lengths are sent in by dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// A chunk ready to write: length header, then the compressed bytes
+pub fn frame(compressed: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER + compressed.len());
+    frame.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+    frame.extend_from_slice(compressed);
+    frame
+}
+
+// The compressed length a frame header announces
+pub fn frame_len(header: [u8; FRAME_HEADER]) -> Result<usize, ChatError> {
+    let len = u32::from_be_bytes(header) as usize;
+    if len > MAX_FRAME {
+        return Err(ChatError::Compression("frame too large"));
+    }
+    Ok(len)
+}
+
+#[derive(Default)]
+pub struct Deflater {
+    history: Vec<u8>, // The last `WINDOW` bytes compressed
+}
+
+impl Deflater {
+    pub fn compress(&mut self, data: &[u8]) -> Vec<u8> {
+        let start = self.history.len();
+        let mut buf = std::mem::take(&mut self.history);
+        buf.extend_from_slice(data);
+
+        let mut out = BitWriter::default();
+        out.bits(1, 1); // Final block
+        out.bits(1, 2); // Fixed codes
+        let mut head = vec![usize::MAX; HASH_SIZE];
+        let mut prev = vec![usize::MAX; buf.len()];
+        for pos in 0..start {
+            insert(&buf, pos, &mut head, &mut prev);
+        }
+        let mut pos = start;
+        while pos < buf.len() {
+            match longest_match(&buf, pos, &head, &prev) {
+                Some((len, dist)) => {
+                    write_match(&mut out, len, dist);
+                    for p in pos..pos + len {
+                        insert(&buf, p, &mut head, &mut prev);
+                    }
+                    pos += len;
+                }
+                None => {
+                    write_literal(&mut out, buf[pos] as u16);
+                    insert(&buf, pos, &mut head, &mut prev);
+                    pos += 1;
+                }
+            }
+        }
+        write_literal(&mut out, 256); // End of block
+
+        buf.drain(..buf.len().saturating_sub(WINDOW));
+        self.history = buf;
+        out.finish()
+    }
+}
+
+fn hash(buf: &[u8], pos: usize) -> Option<usize> {
+    let bytes = buf.get(pos..pos + MIN_MATCH)?;
+    Some(((bytes[0] as usize) << 10 ^ (bytes[1] as usize) << 5 ^ bytes[2] as usize) % HASH_SIZE)
+}
+
+fn insert(buf: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if let Some(h) = hash(buf, pos) {
+        prev[pos] = head[h];
+        head[h] = pos;
+    }
+}
+
+// Longest earlier occurrence of what starts at `pos`, as (length, distance)
+fn longest_match(buf: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> Option<(usize, usize)> {
+    let max = (buf.len() - pos).min(MAX_MATCH);
+    if max < MIN_MATCH {
+        return None;
+    }
+    let mut best: Option<(usize, usize)> = None;
+    let mut candidate = head[hash(buf, pos)?];
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || pos - candidate > WINDOW {
+            break;
+        }
+        let len = buf[candidate..].iter().zip(&buf[pos..pos + max]).take_while(|(a, b)| a == b).count();
+        if len >= MIN_MATCH && best.is_none_or(|(best_len, _)| len > best_len) {
+            best = Some((len, pos - candidate));
+            if len == max {
+                break;
+            }
+        }
+        candidate = prev[candidate];
+    }
+    best
+}
+
+// Fixed literal/length codes (RFC 1951 3.2.6)
+fn write_literal(out: &mut BitWriter, symbol: u16) {
+    match symbol {
+        0..=143 => out.code(0x30 + symbol as u32, 8),
+        144..=255 => out.code(0x190 + (symbol - 144) as u32, 9),
+        256..=279 => out.code((symbol - 256) as u32, 7),
+        _ => out.code(0xc0 + (symbol - 280) as u32, 8),
+    }
+}
+
+fn write_match(out: &mut BitWriter, len: usize, dist: usize) {
+    let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= len).unwrap_or_default();
+    write_literal(out, 257 + code as u16);
+    out.bits((len - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code]);
+    let code = DIST_BASE.iter().rposition(|&base| base as usize <= dist).unwrap_or_default();
+    out.code(code as u32, 5);
+    out.bits((dist - DIST_BASE[code] as usize) as u32, DIST_EXTRA[code]);
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    count: u8,
+}
+
+impl BitWriter {
+    // Values go least significant bit first
+    fn bits(&mut self, value: u32, count: u8) {
+        self.acc |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes go most significant bit first
+    fn code(&mut self, code: u32, len: u8) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+#[derive(Default)]
+pub struct Inflater {
+    history: Vec<u8>, // The last `WINDOW` bytes decompressed
+}
+
+impl Inflater {
+    pub fn inflate(&mut self, data: &[u8]) -> Result<Vec<u8>, ChatError> {
+        let start = self.history.len();
+        let mut out = std::mem::take(&mut self.history);
+        let result = inflate_into(&mut BitReader { data, pos: 0, acc: 0, count: 0 }, &mut out);
+        let chunk = out[start..].to_vec();
+        out.drain(..out.len().saturating_sub(WINDOW));
+        self.history = out;
+        result.map(|()| chunk)
+    }
+}
+
+fn inflate_into(input: &mut BitReader, out: &mut Vec<u8>) -> Result<(), ChatError> {
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                input.align();
+                let len = input.bits(16)? as usize;
+                if input.bits(16)? as usize != !len & 0xffff {
+                    return Err(ChatError::Compression("stored block length mismatch"));
+                }
+                for _ in 0..len {
+                    out.push(input.bits(8)? as u8);
+                }
+            }
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                inflate_block(input, out, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(input)?;
+                inflate_block(input, out, &lengths, &distances)?;
+            }
+            _ => return Err(ChatError::Compression("invalid block type")),
+        }
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+fn inflate_block(input: &mut BitReader, out: &mut Vec<u8>, lengths: &Huffman, distances: &Huffman) -> Result<(), ChatError> {
+    loop {
+        let symbol = lengths.decode(input)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let code = symbol - 257;
+                let len = LENGTH_BASE[code] as usize + input.bits(LENGTH_EXTRA[code])? as usize;
+                let code = distances.decode(input)? as usize;
+                if code >= DIST_BASE.len() {
+                    return Err(ChatError::Compression("invalid distance code"));
+                }
+                let dist = DIST_BASE[code] as usize + input.bits(DIST_EXTRA[code])? as usize;
+                if dist > out.len() {
+                    return Err(ChatError::Compression("distance before the start of the data"));
+                }
+                // Byte by byte, since a match may overlap what it is copying
+                for _ in 0..len {
+                    out.push(out[out.len() - dist]);
+                }
+            }
+            _ => return Err(ChatError::Compression("invalid length code")),
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(input: &mut BitReader) -> Result<(Huffman, Huffman), ChatError> {
+    let literals = input.bits(5)? as usize + 257;
+    let distances = input.bits(5)? as usize + 1;
+    let code_lengths = input.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[index] = input.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_code.decode(input)? {
+            length @ 0..=15 => (length as u8, 1),
+            16 => (*lengths.last().ok_or(ChatError::Compression("repeat with no previous length"))?, 3 + input.bits(2)?),
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err(ChatError::Compression("too many code lengths"));
+    }
+    if lengths[256] == 0 {
+        return Err(ChatError::Compression("no end-of-block code"));
+    }
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+// Canonical Huffman code, decoded a bit at a time
+struct Huffman {
+    counts: [u16; 16],  // Codes of each length
+    symbols: Vec<u16>, // Ordered by code
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16).filter(|&s| lengths[s as usize] != 0).collect();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, input: &mut BitReader) -> Result<u16, ChatError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= input.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(ChatError::Compression("invalid Huffman code"))
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    count: u8,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u8) -> Result<u32, ChatError> {
+        while self.count < count {
+            let byte = *self.data.get(self.pos).ok_or(ChatError::Compression("unexpected end of data"))?;
+            self.acc |= (byte as u32) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.acc & ((1u64 << count) - 1) as u32;
+        self.acc >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    // Stored blocks start on a byte boundary
+    fn align(&mut self) {
+        self.acc = 0;
+        self.count = 0;
+    }
+}
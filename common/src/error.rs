@@ -0,0 +1,112 @@
+use crate::{i18n, ChatMessage, ErrorReason};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+// Errors shared by the server, the client and the tools
+#[derive(Debug, Error)]
+pub enum ChatError {
+    #[error("Could not encode message: {0}")]
+    Encode(#[source] serde_json::Error),
+    #[error("Malformed message: {0}")]
+    Decode(#[source] serde_json::Error),
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Could not {action} {}: {source}", path.display())]
+    File { action: &'static str, path: PathBuf, source: io::Error },
+    #[error("{0}")]
+    Config(String), // A config file that can't be used as it is
+    #[error("Corrupt compressed data: {0}")]
+    Compression(&'static str),
+    #[error("Malformed MessagePack: {0}")]
+    MessagePack(&'static str),
+}
+
+// Something a client sent that breaks the protocol. Each has a catalog entry, so the server
+// can explain the problem in the client's language; `Display` gives the English text.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{}", self.localized(i18n::DEFAULT_LOCALE))]
+pub enum ProtocolError {
+    LineTooLong(usize), // Limit in bytes
+    NotUtf8,
+    MessageTooLong(usize), // Limit in characters
+    InvalidUsername(String),
+    InvalidRoomName(String),
+    InvalidMessageId(String),
+    MalformedHandshake(String), // Looked like JSON but wasn't a handshake; says why
+}
+
+impl ProtocolError {
+    pub fn key(&self) -> &'static str {
+        match self {
+            ProtocolError::LineTooLong(_) => "err.line_too_long",
+            ProtocolError::NotUtf8 => "err.not_utf8",
+            ProtocolError::MessageTooLong(_) => "err.message_too_long",
+            ProtocolError::InvalidUsername(_) => "err.invalid_username",
+            ProtocolError::InvalidRoomName(_) => "err.invalid_room",
+            ProtocolError::InvalidMessageId(_) => "err.invalid_message_id",
+            ProtocolError::MalformedHandshake(_) => "err.malformed_handshake",
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self {
+            ProtocolError::LineTooLong(limit) | ProtocolError::MessageTooLong(limit) => vec![limit.to_string()],
+            ProtocolError::InvalidRoomName(value) | ProtocolError::InvalidMessageId(value) | ProtocolError::MalformedHandshake(value) => vec![value.clone()],
+            ProtocolError::NotUtf8 | ProtocolError::InvalidUsername(_) => Vec::new(),
+        }
+    }
+
+    pub fn localized(&self, locale: &str) -> String {
+        let args = self.args();
+        i18n::trf(locale, self.key(), &args.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    // As an error message, rendered in each recipient's locale like other templated text
+    pub fn to_message(&self) -> ChatMessage {
+        let args = self.args();
+        let msg = ChatMessage::error(String::new()).with_template(self.key(), &args.iter().map(String::as_str).collect::<Vec<_>>());
+        match self {
+            ProtocolError::MessageTooLong(limit) => msg.with_reason(ErrorReason::TooLong { limit: *limit }),
+            _ => msg,
+        }
+    }
+}
+
+// A command used the wrong way. `hint` is a whole example command line, or the one the user
+// probably meant, which clients may offer to put in the input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandError {
+    pub command: String,       // e.g. "/msg"
+    pub expected_args: String, // e.g. "<user> <text>"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl CommandError {
+    pub fn new(command: &str, expected_args: &str) -> Self {
+        Self { command: command.to_string(), expected_args: expected_args.to_string(), hint: None }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    // "/msg <user> <text>"
+    pub fn usage(&self) -> String {
+        format!("{} {}", self.command, self.expected_args).trim_end().to_string()
+    }
+
+    // Reads "Usage: ..." for clients that show it as a line
+    pub fn to_message(&self) -> ChatMessage {
+        let msg = match &self.hint {
+            Some(hint) => ChatMessage::error(String::new()).with_template("err.usage_example", &[&self.usage(), hint]),
+            None => ChatMessage::error(String::new()).with_template("err.usage", &[&self.usage()]),
+        };
+        msg.with_reason(ErrorReason::Usage(self.clone()))
+    }
+}
@@ -0,0 +1,1311 @@
+// Message catalogs for client UI labels and server-generated text. Keys
+// missing from a locale fall back to English; placeholders are {0}, {1}, ...
+
+pub const DEFAULT_LOCALE: &str = "en";
+pub const SUPPORTED_LOCALES: &[&str] = &["en", "es", "de"];
+
+// Maps e.g. "es_ES.UTF-8" or "de-AT" to a supported catalog, defaulting to English
+pub fn normalize(locale: &str) -> &'static str {
+    let language = locale.split(['_', '-', '.']).next().unwrap_or_default().to_ascii_lowercase();
+    SUPPORTED_LOCALES.iter().find(|l| **l == language).copied().unwrap_or(DEFAULT_LOCALE)
+}
+
+pub fn tr(locale: &str, key: &str) -> &'static str {
+    let lookup = |catalog: &[(&str, &'static str)]| catalog.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+    let localized = match normalize(locale) {
+        "es" => lookup(ES),
+        "de" => lookup(DE),
+        _ => None,
+    };
+    localized.or_else(|| lookup(EN)).unwrap_or("???")
+}
+
+pub fn trf(locale: &str, key: &str, args: &[&str]) -> String {
+    args.iter()
+        .enumerate()
+        .fold(tr(locale, key).to_string(), |text, (i, arg)| text.replace(&format!("{{{}}}", i), arg))
+}
+
+static EN: &[(&str, &str)] = &[
+    // Server-generated messages
+    ("sys.joined_room", "{0} joined the room"),
+    ("sys.left_room", "{0} left the room"),
+    ("sys.room_change", "Joined #{0}"),
+    ("sys.users_in_room", "Users in #{0}: {1}"),
+    ("sys.kicked", "{0} was kicked by {1}"),
+    ("sys.away_notice", "{0} is away: {1}"),
+    ("err.kicked", "You were kicked by {0}"),
+    ("err.usage", "Usage: {0}"),
+    ("err.usage_example", "Usage: {0}, e.g. {1}"),
+    ("err.karma_off", "Karma is off in #{0}"),
+    ("err.game_running", "A game is already being played in #{0}"),
+    ("err.translate_off", "This server has no translation service"),
+    ("err.time_no_zone", "Add a timezone such as UTC+2 to the time, or set yours with /profile set timezone UTC+2"),
+    ("err.translate_failed", "The translation service is unavailable"),
+    ("err.translate_rate_limited", "You can ask for {0} translations a minute; try again shortly"),
+    ("err.no_game", "No game is being played in #{0}"),
+    ("err.may_not_stop_game", "Only {0}, who started the game, or a moderator can stop it"),
+    ("err.thank_self", "You can't thank yourself"),
+    ("err.not_in_room", "{0} is not in #{1}"),
+    ("err.thank_too_soon", "You thanked {0} less than a minute ago"),
+    ("err.not_online", "User '{0}' is not online"),
+    ("err.not_online_suggest", "User '{0}' is not online. Did you mean {1}?"),
+    ("err.maintenance", "The server is down for maintenance, please check back soon. {0}"),
+    ("err.no_maintenance", "No maintenance is scheduled"),
+    ("sys.maintenance_soon", "The server goes down for maintenance in {0}. {1}"),
+    ("sys.maintenance_now", "The server is going down for maintenance now, see you soon. {0}"),
+    ("sys.maintenance_off", "Maintenance was called off"),
+    ("err.banned", "You are banned from this server"),
+    ("err.rate_limited", "Slow down: at most {0} messages a minute"),
+    ("err.config_invalid", "The configuration was not reloaded: {0}"),
+    ("sys.config_reloaded", "Configuration reloaded"),
+    ("sys.snapshot_saved", "Snapshot saved to {0}"),
+    ("err.snapshot_failed", "Could not write the snapshot to {0}: {1}"),
+    ("err.backup_failed", "Backup to {0} failed: {1}"),
+    ("sys.conninfo", "{0}: latency {1}, sent {2}, received {3}, {4} reconnects, online {5} from {6} ({7})"),
+    ("err.admin_only", "Only admins can do that"),
+    ("err.unknown_command", "Unknown command: {0}"),
+    ("sys.help", "Commands: {0}. Anything else is sent to the room"),
+    ("sys.digest", "#{0}: {1} new since you last read there {3} ago, {2} mentioning you"),
+    ("err.owner_only_ttl", "Only the owner of #{0} can change how long its messages are kept"),
+    ("sys.ttl_set", "Messages in #{0} disappear after {1}"),
+    ("sys.ttl_off", "Messages in #{0} are kept"),
+    ("err.mirror_invalid", "A room can't mirror into itself, and only targets can be whole categories"),
+    ("err.mirror_not_found", "#{0} is not mirrored into #{1}"),
+    ("sys.mirror_added", "Messages in #{0} are now mirrored into #{1}"),
+    ("sys.mirror_both", "#{0} and #{1} now mirror each other"),
+    ("sys.mirror_removed", "Stopped mirroring between #{0} and #{1}"),
+    ("sys.mirror_list", "Mirrors: {0}"),
+    ("sys.no_mirrors", "No rooms are mirrored"),
+    ("err.invalid_room", "Invalid room name {0}: up to 64 characters, no spaces, commas, # or @, / between categories as in work/standup"),
+    ("err.invalid_username", "Invalid username"),
+    ("err.username_taken", "Username already taken"),
+    ("err.guests_disabled", "Guest access is disabled on this server"),
+    ("err.guest_name_reserved", "Names starting with guest- are reserved for guests"),
+    ("err.guests_full", "No guest names are free right now, try again later"),
+    ("err.guest_no_new_rooms", "Guests can only join rooms that already exist"),
+    ("err.invite_only", "#{0} is invite-only; ask its owner for a code"),
+    ("err.invite_invalid", "That invite code is unknown or has expired"),
+    ("err.invite_lifetime", "Invite codes last from 1m up to 30d, e.g. 30m, 24h or 7d"),
+    ("err.may_not_invite", "You may not manage invitations to #{0}"),
+    ("sys.group_mention", "{0} mentioned a group you are in, in #{1}: {2}"),
+    ("sys.group_list", "Groups: {0}"),
+    ("sys.no_groups", "There are no groups"),
+    ("sys.group_members", "Members of @{0}: {1}"),
+    ("sys.group_created", "Created group @{0}"),
+    ("sys.group_deleted", "Deleted group @{0}"),
+    ("sys.group_added", "{0} is in @{1}"),
+    ("sys.group_removed", "{0} is not in @{1}"),
+    ("sys.group_allowed", "Members of @{0} may now enter #{1}"),
+    ("sys.group_denied", "Being in @{0} no longer lets anyone into #{1}"),
+    ("sys.room_groups", "Groups allowed into #{0}: {1}"),
+    ("err.group_not_found", "There is no group @{0}"),
+    ("err.group_exists", "The group @{0} exists already"),
+    ("err.group_name", "\"{0}\" can't be a group name: up to 32 characters without spaces"),
+    ("err.group_not_allowed", "@{0} was not allowed into #{1}"),
+    ("sys.roomset", "#{0}: posting is open to {1}, inviting to {2}. Moderators: {3}. Voiced: {4}. Moderation: {5}. Karma: {6}. Broadcast: {7}. Description: {8}. Tags: {9}"),
+    ("sys.roomset_permission", "Who may {0} in #{1}: {2}"),
+    ("sys.roomset_level", "{0}'s level in #{1} is now {2}"),
+    ("err.may_not_post", "You may not post in #{0}"),
+    ("err.may_not_roomset", "You may not change the settings of #{0}"),
+    ("err.roomset_unchanged", "Nothing to change for {0} in #{1}"),
+    ("sys.warned", "{0} now has {1} warnings"),
+    ("sys.warned_mute", "{0} now has {1} warnings and is muted for {2}"),
+    ("sys.warned_kick", "{0} now has {1} warnings and was disconnected"),
+    ("sys.warned_ban", "{0} now has {1} warnings and is banned for {2}"),
+    ("sys.warnings", "{0} has {1} warnings: {2}"),
+    ("sys.no_warnings", "{0} has no warnings"),
+    ("sys.warnings_cleared", "Warnings, mutes and bans for {0} cleared"),
+    ("err.warned", "{0} warned you: {1}. You have {2} warnings"),
+    ("err.muted", "You are muted for another {0}"),
+    ("err.banned_for", "You are banned for another {0}"),
+    ("err.kicked_warnings", "You were disconnected after {0} warnings"),
+    ("err.may_not_warn", "You may not warn {0}"),
+    ("err.may_not_review", "Only moderators and admins can see other users' warnings"),
+    ("sys.roomset_moderation", "Moderation in #{0}: {1}"),
+    ("sys.roomset_karma", "Karma in #{0}: {1}"),
+    ("sys.roomset_broadcast", "Broadcast in #{0}: {1}"),
+    ("sys.trivia_start", "Trivia! {0} questions, {1} seconds each. Answer in the chat"),
+    ("sys.trivia_question", "Question {0}/{1}: {2}"),
+    ("sys.trivia_correct", "{0} got it: {1} ({0} has {2})"),
+    ("sys.trivia_timeout", "Time's up! The answer was {0}"),
+    ("sys.trivia_over", "Trivia over: {0}"),
+    ("sys.trivia_over_no_winner", "Trivia over, and nobody scored"),
+    ("sys.trivia_scores", "Trivia, question {0}/{1}: {2}"),
+    ("sys.trivia_no_scores", "Trivia, question {0}/{1}: nobody has scored yet"),
+    ("sys.game_stopped", "{1} stopped the {0} game"),
+    ("sys.rolled", "rolled {0}: {1}"),
+    ("sys.flipped_heads", "flipped a coin: heads"),
+    ("sys.flipped_tails", "flipped a coin: tails"),
+    ("sys.chose", "asked for a choice: {0} (from {1})"),
+    ("sys.translation", "Translation ({0}) of {1}: {2}"),
+    ("sys.time", "{0} means {1} UTC: {2}"),
+    ("sys.time_some_unset", "{0} means {1} UTC: {2} ({3} more with no timezone in their profile)"),
+    ("sys.time_no_zones", "{0} means {1} UTC; nobody here has a timezone in their profile"),
+    ("sys.thanked", "{0} thanked {1} ({1} now has {2} karma)"),
+    ("sys.leaderboard", "Top in #{0}: {1}"),
+    ("sys.no_karma", "Nobody in #{0} has been thanked yet"),
+    ("sys.flagged", "Flagged for review: {0} in #{1} ({2}): {3}"),
+    ("sys.flagged_list", "{0} flagged messages: {1}"),
+    ("sys.no_flagged", "No flagged messages"),
+    ("sys.reported", "Reported to the admins"),
+    ("sys.dismissed", "Dismissed {0} flagged entries"),
+    ("err.not_flagged", "That message is not flagged"),
+    ("sys.muted", "{0} is muted for {1}"),
+    ("sys.banned", "{0} is banned for {1}"),
+    ("sys.unmuted", "{0} may post again"),
+    ("sys.unbanned", "{0} may log in again"),
+    ("err.not_muted", "{0} is not muted"),
+    ("err.not_banned", "{0} is not banned"),
+    ("err.may_not_punish", "You may not mute or ban {0}"),
+    ("sys.pinned", "{0} pinned a message by {1}: {2}"),
+    ("sys.unpinned", "{0} unpinned a message"),
+    ("sys.pins", "Pinned in #{0} ({1}): {2}"),
+    ("sys.no_pins", "Nothing is pinned in #{0}"),
+    ("err.may_not_pin", "Only moderators of #{0} and admins can pin messages"),
+    ("err.may_not_moderate", "Only moderators of #{0} and admins can open the moderation panel"),
+    ("err.already_pinned", "That message is already pinned"),
+    ("err.not_pinned", "That message is not pinned"),
+    ("err.blocked", "Your message was not posted: {0}"),
+    ("sys.link_hits", "{0} screened links: {1}"),
+    ("sys.no_link_hits", "No screened links"),
+    ("err.link_blocked", "Your message was not sent: {0} is {1}"),
+    ("sys.reserved", "{0} is reserved for registered accounts"),
+    ("sys.unreserved", "{0} is no longer reserved"),
+    ("sys.reserved_list", "Reserved names: {0}"),
+    ("sys.no_reserved", "No reserved names"),
+    ("err.already_reserved", "{0} is already reserved"),
+    ("err.not_reserved", "{0} is not reserved"),
+    ("err.name_reserved", "This name is reserved; log in with its account to use it"),
+    ("err.name_lookalike", "This name looks too much like {0}; pick another"),
+    ("sys.lookalike_refused", "Refused {0} at login: it looks like {1}"),
+    ("sys.captcha_sum", "What is {0} + {1}?"),
+    ("sys.captcha_backwards", "Type {0} backwards"),
+    ("err.captcha_wrong", "That answer is wrong"),
+    ("err.captcha_required", "This server asks new users a question first; connect with the chat client to answer it"),
+    ("ui.captcha_required", "Answer to show you are not a bot: {0}"),
+    ("sys.audit_intact", "Audit log intact: {0} chained entries in {2} file(s), the newest hashing to {1}"),
+    ("err.audit_broken", "The audit log was tampered with: the chain breaks at line {0} of {1}, so that entry or one before it was changed or removed"),
+    ("err.audit_missing", "The audit log was tampered with: the rotated log {0} is gone, and it was never pruned"),
+    ("err.audit_unreadable", "Could not read the audit log: {0}"),
+    ("sys.archive_replayed", "{0} archived messages from {1} to {2}"),
+    ("sys.archive_truncated", "Showing the first {0} archived messages from {1} to {2}; ask for fewer days to see the rest"),
+    ("err.archive_off", "This server does not archive history"),
+    ("err.archive_unreadable", "Could not read the archive"),
+    ("sys.invite_created", "Invite code {0} for #{1}, valid for {2}. Others join with /join --code {0}"),
+    ("sys.invite_list", "Invite codes for #{0}: {1}"),
+    ("sys.no_invites", "No active invite codes for #{0}"),
+    ("sys.invite_revoked", "Invite code {0} revoked"),
+    ("sys.room_opened", "#{0} is open to everyone again"),
+    ("err.auth_required", "This account needs a password"),
+    ("err.bad_password", "Wrong password"),
+    ("err.bad_totp", "Wrong or expired code"),
+    ("err.account_save", "Could not save the account"),
+    ("err.not_registered", "Register this username first by logging in with a password"),
+    ("sys.totp_enabled", "Two-factor login is on. Add this secret to your authenticator app: {0} ({1})"),
+    ("err.message_not_found", "That message is no longer available"),
+    ("sys.forwarded", "Forwarded to #{0}"),
+    ("ui.mirrored_from", "mirrored from #{0}"),
+    ("ui.forwarded_from", "forwarded from {0}"),
+    ("ui.link_warning", "Careful: {0} is flagged as {1}"),
+    ("ui.forward_to", "Forward to"),
+    ("help.forward", "Forward the selected message to a room or person"),
+    ("sys.starred", "Starred a message from {0}"),
+    ("sys.unstarred", "Removed from starred messages"),
+    ("err.not_starred", "That message is not starred"),
+    ("sys.profile_set", "Profile {0} set to {1}"),
+    ("sys.profile_cleared", "Profile {0} cleared"),
+    ("err.line_too_long", "Lines can be at most {0} bytes; that one was ignored"),
+    ("err.not_utf8", "That line was not valid UTF-8 and was ignored"),
+    ("err.malformed_handshake", "Malformed handshake: {0}"),
+    ("err.invalid_message_id", "Not a message ID: {0}"),
+    ("err.message_too_long", "Messages can be at most {0} characters"),
+    ("err.profile_too_long", "{0} can be at most {1} characters"),
+    ("err.display_name_taken", "{0} is someone else's username"),
+    ("err.guest_autojoin", "Guests start in the server's rooms; register to choose your own"),
+    ("err.autojoin_unchanged", "#{0} is on your list already, or it has {1} rooms"),
+    ("err.autojoin_missing", "#{0} isn't on your list"),
+    ("sys.autojoin_list", "You start with {0}"),
+    ("sys.autojoin_empty", "You start in #general. Add rooms with /autojoin add"),
+    ("sys.autojoin_added", "#{0} added to the rooms you start with"),
+    ("sys.autojoin_removed", "#{0} removed from the rooms you start with"),
+    ("sys.autojoin_cleared", "You'll start in #general"),
+    ("err.description_too_long", "Room descriptions can be at most {0} characters"),
+    ("err.bad_tags", "Rooms can have up to {0} tags, each a word of up to {1} letters, digits or dashes"),
+    ("sys.roomset_description", "#{0} is now described as: {1}"),
+    ("sys.roomset_description_cleared", "#{0} no longer has a description"),
+    ("sys.roomset_tags", "#{0} is now tagged {1}"),
+    ("sys.roomset_tags_cleared", "#{0} no longer has tags"),
+    ("err.no_prompt", "There is no question {0} to answer"),
+    ("err.answer_invalid", "{0} isn't one of the choices"),
+    ("sys.onboarded", "You're all set. /help lists everything you can do"),
+    ("err.no_profile", "No profile for {0}"),
+    ("sys.totp_disabled", "Two-factor login is off"),
+    // Client UI
+    ("ui.login", "Login"),
+    ("ui.welcome", "Welcome to Ultimate Chat"),
+    ("ui.username", "Username"),
+    ("ui.login_hint", "Tab: next field · Space: toggle TLS · Ctrl+S: save profile · Del: remove profile\nEnter: connect · Ctrl+G: join as guest · Esc: quit"),
+    ("ui.profiles", "Saved profiles"),
+    ("ui.server", "Server"),
+    ("ui.port", "Port"),
+    ("ui.tls", "TLS"),
+    ("ui.password", "Password (optional)"),
+    ("ui.profile_saved", "Saved profile {0}"),
+    ("ui.profile_removed", "Removed profile {0}"),
+    ("ui.username_required", "Enter a username"),
+    ("ui.invalid_port", "Port must be a number between 1 and 65535"),
+    ("ui.password_required", "This account needs a password"),
+    ("ui.totp_required", "Enter the 6-digit code from your authenticator app"),
+    ("ui.confirm_register", "{0} is not registered yet. Enter the password again to register it"),
+    ("ui.password_mismatch", "Passwords do not match"),
+    ("ui.prompt_hint", "Enter: continue · Esc: quit"),
+    ("ui.connection_closed", "The server closed the connection"),
+    ("ui.connecting", "Connecting to {0}… {1}s"),
+    ("ui.connect_hint", "Esc: cancel"),
+    ("ui.connect_cancelled", "Connection cancelled"),
+    ("ui.connect_failed", "Could not connect"),
+    ("ui.connect_failed_hint", "R: retry · E: edit server · Esc: quit"),
+    ("ui.err_dns", "Could not find a server called {0}. Check the host name."),
+    ("ui.err_refused", "Nothing is accepting connections at {0}. Is the server running?"),
+    ("ui.err_unreachable", "{0} is unreachable. Check your network connection."),
+    ("ui.err_timeout", "{0} did not answer within {1} seconds"),
+    ("ui.err_connect", "Could not connect to {0}: {1}"),
+    ("ui.tls_unsupported", "TLS is not supported by this build"),
+    ("ui.connection_lost", "Connection lost"),
+    ("ui.reconnecting_in", "Reconnecting in {0}s…"),
+    ("ui.reconnect_hint", "Enter: reconnect now · /quit: exit"),
+    ("ui.info", "Info"),
+    ("ui.room", "Room: "),
+    ("ui.rooms", "Rooms:"),
+    ("ui.users", "Users:"),
+    ("ui.messages", "Messages ({0})"),
+    ("ui.input", "Input"),
+    ("ui.usage_tab", "Tab: {0}"),
+    ("ui.commands", "Commands"),
+    ("ui.cooldown", "Slow down: {0}"),
+    ("ui.muted_until", "Muted: {0}"),
+    ("ui.input_read_only", "Read-only"),
+    ("ui.read_only", "Read-only: only voiced users and moderators post here. Commands still work"),
+    ("ui.input_multiline", "Input (multi-line: Enter sends, Alt+Enter adds a line)"),
+    ("ui.help", "Help"),
+    ("ui.connected", "Connected"),
+    ("ui.disconnected", "Disconnected (/quit to exit)"),
+    ("ui.members", "{0} members"),
+    ("ui.unread", "{0} unread"),
+    ("ui.dnd", "DND"),
+    ("ui.away", "Away ({0})"),
+    ("ui.away_short", "away"),
+    ("ui.find", "Find: {0} matches (Enter to jump, Esc to close)"),
+    ("ui.jump_to", "Jump to (Enter to open, Esc to close)"),
+    ("ui.ignored_one", "1 message from ignored users — press Ctrl+X to reveal"),
+    ("ui.ignored_many", "{0} messages from ignored users — press Ctrl+X to reveal"),
+    ("ui.joined_one", "1 user joined"),
+    ("ui.joined_many", "{0} users joined"),
+    ("ui.left", "{0} left"),
+    // Help overlay
+    ("help.commands", "Commands:"),
+    ("help.keys", "Keys:"),
+    ("help.join", "Switch rooms"),
+    ("help.msg", "Private Message"),
+    ("help.ttl", "Show or set how long this room keeps messages"),
+    ("help.rooms", "List rooms, grouped by category"),
+    ("help.discover", "Find open rooms by name, tag or description"),
+    ("help.autojoin", "Rooms you start in each session"),
+    ("help.collapse", "Fold or unfold a sidebar category (all without one)"),
+    ("help.conninfo", "Latency and traffic of your connection"),
+    ("help.users", "List users"),
+    ("help.thank", "Thank someone in a room with karma"),
+    ("help.leaderboard", "Who has the most karma here"),
+    ("help.trivia", "Play trivia in this room"),
+    ("help.roll", "Roll dice for the room to see"),
+    ("help.choose", "Flip a coin, or pick one of the options"),
+    ("help.translate", "Translate a message, just for you"),
+    ("help.time", "Show a time in everyone's local time"),
+    ("help.translate_focused", "Translate the selected message into your language"),
+    ("help.activity", "Graph when this room, or another, is busiest"),
+    ("help.ignore", "Hide a user's messages (/unignore to undo)"),
+    ("help.quiet", "Reduce join/leave noise in this room"),
+    ("help.export", "Save this room's scrollback (.txt/.json/.html)"),
+    ("help.find", "Search loaded messages (also Ctrl+F)"),
+    ("help.dnd", "Toggle do not disturb"),
+    ("help.notify", "Notification level for this room"),
+    ("help.quiethours", "Daily do-not-disturb window"),
+    ("help.receipts", "Share read receipts (opt-in)"),
+    ("help.spell", "Toggle spell checking or add a word to your dictionary"),
+    ("help.spell_suggest", "Spelling suggestions for the underlined word"),
+    ("ui.no_suggestions", "No suggestions"),
+    ("ui.add_to_dictionary", "Add to dictionary"),
+    ("help.select", "Select a message"),
+    ("help.reveal_spoiler", "Reveal or hide spoilers in the selected message"),
+    ("ui.spoiler_hint", "(press r to reveal)"),
+    ("help.starred", "List your starred messages"),
+    ("help.star", "Star the selected message"),
+    ("help.report", "Report a message to the admins"),
+    ("help.pins", "List the messages pinned in this room"),
+    ("help.pin", "Pin the selected message (room moderators)"),
+    ("help.mod_panel", "Moderation panel (admins)"),
+    ("ui.starred", "Starred messages"),
+    ("ui.starred_hint", "Enter: jump · Del: unstar · Esc: close"),
+    ("ui.starred_empty", "Nothing starred yet. Select a message with Alt+Up and press s"),
+    ("ui.discover", "Rooms"),
+    ("ui.discover_matching", "Rooms matching \"{0}\""),
+    ("ui.discover_hint", "Enter: join · Esc: close"),
+    ("ui.discover_empty", "No open rooms match. Try another word, or /discover alone for all of them"),
+    ("ui.discover_counts", "{0} here · {1} today"),
+    ("ui.discover_active", "active {0} ago"),
+    ("ui.discover_quiet", "no recent messages"),
+    ("help.goto", "Jump to a message by its permalink"),
+    ("help.follow_ref", "Go to the message the selected one links to"),
+    ("help.cite", "Insert a link to the selected message"),
+    ("help.profile", "Show someone's profile card (yours by default)"),
+    ("help.join_code", "Join an invite-only room with a code"),
+    ("help.invitecode", "Codes for a room you own; makes it invite-only"),
+    ("help.profile_set", "Set display_name, bio, pronouns or timezone; no value clears it"),
+    ("ui.pronouns", "Pronouns"),
+    ("ui.timezone", "Time zone"),
+    ("ui.stats", "Server stats"),
+    ("ui.stats_uptime", "Uptime"),
+    ("ui.stats_clients", "Connected clients"),
+    ("ui.stats_rooms", "Rooms"),
+    ("ui.stats_rate", "Messages per minute"),
+    ("ui.stats_history", "History held"),
+    ("ui.stats_queue", "Broadcast queue"),
+    ("ui.console", "Server console"),
+    ("ui.console_connections", "Connections"),
+    ("ui.console_rooms", "Busy rooms"),
+    ("ui.console_actions", "Recent moderation"),
+    ("ui.console_output", "Command output"),
+    ("ui.console_hint", "Enter: run a command · Esc: quit"),
+    ("ui.console_user", "User"),
+    ("ui.console_room", "Room"),
+    ("ui.console_address", "Address"),
+    ("ui.console_connected", "Connected"),
+    ("ui.console_traffic", "In / out"),
+    ("ui.console_users", "Users"),
+    ("ui.console_rate", "Msgs/min"),
+    ("ui.console_elsewhere", "The server console has a screen of its own: run client --console <admin name>"),
+    ("ui.mod_panel", "Moderation"),
+    ("ui.mod_members", "Members"),
+    ("ui.mod_reports", "Reports"),
+    ("ui.mod_pins", "Pins"),
+    ("ui.mod_empty", "Nothing here"),
+    ("ui.mod_warnings", "{0} warnings"),
+    ("ui.mod_muted", "muted {0}"),
+    ("ui.mod_hint_members", "k kick · m mute 10m · u unmute · b ban 1d · w warn · Tab next · Esc close"),
+    ("ui.mod_hint_reports", "Enter go to · d dismiss · p pin · k kick author · Tab next · Esc close"),
+    ("ui.mod_hint_pins", "Enter go to · Del unpin · Tab next · Esc close"),
+    ("ui.activity", "Activity in #{0}"),
+    ("ui.activity_total", "{0} messages in the last 24 hours"),
+    ("ui.activity_busiest", "busiest around {0}"),
+    ("ui.activity_day_ago", "24h ago"),
+    ("ui.activity_now", "now"),
+    ("ui.activity_by_hour", "By hour of the day, over the last 7 days"),
+    ("ui.profile_empty", "Nothing here yet"),
+    ("ui.close_hint", "any key: close"),
+    ("ui.onboarding_title", "Welcome"),
+    ("ui.onboarding_step", "{0} of {1}"),
+    ("ui.onboarding_display_name", "What name should others see?"),
+    ("ui.onboarding_room", "Pick the rooms to start with each time you connect"),
+    ("ui.onboarding_notifications", "When should the client notify you?"),
+    ("ui.onboarding_notify_all", "Every message"),
+    ("ui.onboarding_notify_mentions", "Mentions and private messages"),
+    ("ui.onboarding_notify_none", "Never"),
+    ("ui.form_text_hint", "Enter: next · Esc: skip"),
+    ("ui.form_choice_hint", "↑↓: choose · Enter: next · Esc: skip"),
+    ("ui.form_multiple_hint", "↑↓: choose · Space: pick · Enter: next · Esc: skip"),
+    ("help.alias", "Personal command shortcuts (/unalias to remove)"),
+    ("help.away", "Mark yourself away (/back to return)"),
+    ("help.totp", "Two-factor login for your registered account"),
+    ("help.quit", "Exit"),
+    ("help.group", "Manage named user groups"),
+    ("help.roomset", "Change this room's settings"),
+    ("help.back", "Return from away"),
+    ("help.history", "Fetch older or archived messages"),
+    ("help.star_id", "Star a message"),
+    ("help.unstar", "Remove a star"),
+    ("help.pin_id", "Pin a message to the room"),
+    ("help.unpin", "Unpin a message"),
+    ("help.warn", "Warn a user"),
+    ("help.warnings", "List or clear warnings"),
+    ("help.help", "List the commands"),
+    ("help.kick", "Disconnect a user"),
+    ("help.mute", "Mute a user for a while"),
+    ("help.unmute", "Lift a mute"),
+    ("help.ban", "Ban a user for a while"),
+    ("help.unban", "Lift a ban"),
+    ("help.flagged", "Review flagged messages"),
+    ("help.linkhits", "Show blocked link counts"),
+    ("help.reserve", "Reserve a name or pattern"),
+    ("help.unreserve", "Release a reserved name"),
+    ("help.mirror", "Mirror one room into another"),
+    ("help.unmirror", "Stop mirroring"),
+    ("help.maintenance", "Announce maintenance"),
+    ("help.stats", "Show server statistics"),
+    ("help.snapshot", "Save a state snapshot"),
+    ("help.reload", "Reload the configuration"),
+    ("help.audit", "Verify the audit log"),
+    ("help.scroll", "Scroll History"),
+    ("help.newline", "New line in message"),
+    ("help.cycle_rooms", "Cycle visited rooms (drafts are kept)"),
+    ("help.switcher", "Quick switcher for rooms and PM contacts"),
+    ("help.resize", "Shrink/Grow sidebar"),
+    ("help.zen", "Toggle sidebar (zen mode)"),
+    ("help.user_list", "Toggle user list"),
+    ("help.reveal", "Reveal messages from ignored users"),
+    ("help.toggle_help", "Toggle Help"),
+];
+
+static ES: &[(&str, &str)] = &[
+    ("sys.joined_room", "{0} se unió a la sala"),
+    ("sys.left_room", "{0} salió de la sala"),
+    ("sys.room_change", "Entraste en #{0}"),
+    ("sys.users_in_room", "Usuarios en #{0}: {1}"),
+    ("sys.kicked", "{0} fue expulsado por {1}"),
+    ("sys.away_notice", "{0} está ausente: {1}"),
+    ("err.kicked", "{0} te ha expulsado"),
+    ("err.usage", "Uso: {0}"),
+    ("err.usage_example", "Uso: {0}, p. ej. {1}"),
+    ("err.karma_off", "El karma está desactivado en #{0}"),
+    ("err.game_running", "Ya se está jugando una partida en #{0}"),
+    ("err.translate_off", "Este servidor no tiene servicio de traducción"),
+    ("err.time_no_zone", "Añade una zona horaria como UTC+2 a la hora, o configura la tuya con /profile set timezone UTC+2"),
+    ("err.translate_failed", "El servicio de traducción no está disponible"),
+    ("err.translate_rate_limited", "Puedes pedir {0} traducciones por minuto; inténtalo de nuevo en breve"),
+    ("err.no_game", "No se está jugando ninguna partida en #{0}"),
+    ("err.may_not_stop_game", "Solo {0}, que empezó la partida, o un moderador pueden detenerla"),
+    ("err.thank_self", "No puedes agradecerte a ti mismo"),
+    ("err.not_in_room", "{0} no está en #{1}"),
+    ("err.thank_too_soon", "Agradeciste a {0} hace menos de un minuto"),
+    ("err.not_online", "El usuario '{0}' no está conectado"),
+    ("err.not_online_suggest", "El usuario '{0}' no está conectado. ¿Querías decir {1}?"),
+    ("err.maintenance", "El servidor está en mantenimiento, vuelve pronto. {0}"),
+    ("err.no_maintenance", "No hay mantenimiento programado"),
+    ("sys.maintenance_soon", "El servidor entra en mantenimiento en {0}. {1}"),
+    ("sys.maintenance_now", "El servidor entra en mantenimiento ahora, hasta pronto. {0}"),
+    ("sys.maintenance_off", "Se canceló el mantenimiento"),
+    ("err.banned", "Tienes prohibida la entrada a este servidor"),
+    ("err.rate_limited", "Más despacio: como mucho {0} mensajes por minuto"),
+    ("err.config_invalid", "No se recargó la configuración: {0}"),
+    ("sys.config_reloaded", "Configuración recargada"),
+    ("sys.snapshot_saved", "Instantánea guardada en {0}"),
+    ("err.snapshot_failed", "No se pudo escribir la instantánea en {0}: {1}"),
+    ("err.backup_failed", "Falló la copia de seguridad en {0}: {1}"),
+    ("sys.conninfo", "{0}: latencia {1}, enviado {2}, recibido {3}, {4} reconexiones, conectado {5} desde {6} ({7})"),
+    ("err.admin_only", "Solo los administradores pueden hacer eso"),
+    ("err.unknown_command", "Comando desconocido: {0}"),
+    ("sys.help", "Comandos: {0}. Todo lo demás se envía a la sala"),
+    ("sys.digest", "#{0}: {1} nuevos desde tu última lectura hace {3}, {2} te mencionan"),
+    ("err.owner_only_ttl", "Solo el propietario de #{0} puede cambiar cuánto se guardan sus mensajes"),
+    ("sys.ttl_set", "Los mensajes de #{0} desaparecen tras {1}"),
+    ("sys.ttl_off", "Los mensajes de #{0} se conservan"),
+    ("err.mirror_invalid", "Una sala no puede reflejarse en sí misma, y solo el destino puede ser una categoría entera"),
+    ("err.mirror_not_found", "#{0} no se refleja en #{1}"),
+    ("sys.mirror_added", "Los mensajes de #{0} ahora se reflejan en #{1}"),
+    ("sys.mirror_both", "#{0} y #{1} ahora se reflejan mutuamente"),
+    ("sys.mirror_removed", "Se dejó de reflejar entre #{0} y #{1}"),
+    ("sys.mirror_list", "Reflejos: {0}"),
+    ("sys.no_mirrors", "No hay salas reflejadas"),
+    ("err.invalid_room", "Nombre de sala no válido {0}: hasta 64 caracteres, sin espacios, comas, # ni @, / entre categorías como en work/standup"),
+    ("err.invalid_username", "Nombre de usuario no válido"),
+    ("err.username_taken", "El nombre de usuario ya está en uso"),
+    ("err.guests_disabled", "El acceso de invitados está desactivado en este servidor"),
+    ("err.guest_name_reserved", "Los nombres que empiezan por guest- están reservados para invitados"),
+    ("err.guests_full", "No quedan nombres de invitado libres, inténtalo más tarde"),
+    ("err.guest_no_new_rooms", "Los invitados solo pueden unirse a salas que ya existen"),
+    ("err.invite_only", "#{0} es solo por invitación; pide un código a su propietario"),
+    ("err.invite_invalid", "Ese código de invitación no existe o ha caducado"),
+    ("err.invite_lifetime", "Los códigos duran de 1m a 30d, p. ej. 30m, 24h o 7d"),
+    ("err.may_not_invite", "No puedes gestionar las invitaciones a #{0}"),
+    ("sys.group_mention", "{0} mencionó a un grupo al que perteneces, en #{1}: {2}"),
+    ("sys.group_list", "Grupos: {0}"),
+    ("sys.no_groups", "No hay grupos"),
+    ("sys.group_members", "Miembros de @{0}: {1}"),
+    ("sys.group_created", "Grupo @{0} creado"),
+    ("sys.group_deleted", "Grupo @{0} eliminado"),
+    ("sys.group_added", "{0} está en @{1}"),
+    ("sys.group_removed", "{0} no está en @{1}"),
+    ("sys.group_allowed", "Los miembros de @{0} ahora pueden entrar en #{1}"),
+    ("sys.group_denied", "Ser miembro de @{0} ya no da acceso a #{1}"),
+    ("sys.room_groups", "Grupos con acceso a #{0}: {1}"),
+    ("err.group_not_found", "No existe el grupo @{0}"),
+    ("err.group_exists", "El grupo @{0} ya existe"),
+    ("err.group_name", "\"{0}\" no puede ser un nombre de grupo: hasta 32 caracteres sin espacios"),
+    ("err.group_not_allowed", "@{0} no tenía acceso a #{1}"),
+    ("sys.roomset", "#{0}: pueden publicar {1}, invitar {2}. Moderadores: {3}. Con voz: {4}. Moderación: {5}. Karma: {6}. Difusión: {7}. Descripción: {8}. Etiquetas: {9}"),
+    ("sys.roomset_permission", "Quién puede {0} en #{1}: {2}"),
+    ("sys.roomset_level", "El nivel de {0} en #{1} ahora es {2}"),
+    ("err.may_not_post", "No puedes publicar en #{0}"),
+    ("err.may_not_roomset", "No puedes cambiar los ajustes de #{0}"),
+    ("err.roomset_unchanged", "Nada que cambiar para {0} en #{1}"),
+    ("sys.warned", "{0} tiene ahora {1} advertencias"),
+    ("sys.warned_mute", "{0} tiene ahora {1} advertencias y está silenciado durante {2}"),
+    ("sys.warned_kick", "{0} tiene ahora {1} advertencias y ha sido desconectado"),
+    ("sys.warned_ban", "{0} tiene ahora {1} advertencias y está vetado durante {2}"),
+    ("sys.warnings", "{0} tiene {1} advertencias: {2}"),
+    ("sys.no_warnings", "{0} no tiene advertencias"),
+    ("sys.warnings_cleared", "Advertencias, silencios y vetos de {0} borrados"),
+    ("err.warned", "{0} te ha advertido: {1}. Tienes {2} advertencias"),
+    ("err.muted", "Estás silenciado durante {0} más"),
+    ("err.banned_for", "Estás vetado durante {0} más"),
+    ("err.kicked_warnings", "Has sido desconectado tras {0} advertencias"),
+    ("err.may_not_warn", "No puedes advertir a {0}"),
+    ("err.may_not_review", "Solo moderadores y administradores pueden ver las advertencias de otros"),
+    ("sys.roomset_moderation", "Moderación en #{0}: {1}"),
+    ("sys.roomset_karma", "Karma en #{0}: {1}"),
+    ("sys.roomset_broadcast", "Difusión en #{0}: {1}"),
+    ("sys.trivia_start", "¡Trivia! {0} preguntas, {1} segundos cada una. Responde en el chat"),
+    ("sys.trivia_question", "Pregunta {0}/{1}: {2}"),
+    ("sys.trivia_correct", "{0} acertó: {1} ({0} tiene {2})"),
+    ("sys.trivia_timeout", "¡Se acabó el tiempo! La respuesta era {0}"),
+    ("sys.trivia_over", "Fin de la trivia: {0}"),
+    ("sys.trivia_over_no_winner", "Fin de la trivia, y nadie ha puntuado"),
+    ("sys.trivia_scores", "Trivia, pregunta {0}/{1}: {2}"),
+    ("sys.trivia_no_scores", "Trivia, pregunta {0}/{1}: nadie ha puntuado todavía"),
+    ("sys.game_stopped", "{1} detuvo el juego de {0}"),
+    ("sys.rolled", "tiró {0}: {1}"),
+    ("sys.flipped_heads", "lanzó una moneda: cara"),
+    ("sys.flipped_tails", "lanzó una moneda: cruz"),
+    ("sys.chose", "pidió una elección: {0} (entre {1})"),
+    ("sys.translation", "Traducción ({0}) de {1}: {2}"),
+    ("sys.time", "{0} se refiere a {1} UTC: {2}"),
+    ("sys.time_some_unset", "{0} se refiere a {1} UTC: {2} ({3} más sin zona horaria en su perfil)"),
+    ("sys.time_no_zones", "{0} se refiere a {1} UTC; nadie aquí tiene zona horaria en su perfil"),
+    ("sys.thanked", "{0} agradeció a {1} ({1} tiene ahora {2} de karma)"),
+    ("sys.leaderboard", "Los mejores en #{0}: {1}"),
+    ("sys.no_karma", "Nadie en #{0} ha recibido agradecimientos todavía"),
+    ("sys.flagged", "Marcado para revisión: {0} en #{1} ({2}): {3}"),
+    ("sys.flagged_list", "{0} mensajes marcados: {1}"),
+    ("sys.no_flagged", "No hay mensajes marcados"),
+    ("sys.reported", "Denunciado a los administradores"),
+    ("sys.dismissed", "{0} entradas marcadas descartadas"),
+    ("err.not_flagged", "Ese mensaje no está marcado"),
+    ("sys.muted", "{0} está silenciado durante {1}"),
+    ("sys.banned", "{0} está vetado durante {1}"),
+    ("sys.unmuted", "{0} puede volver a escribir"),
+    ("sys.unbanned", "{0} puede volver a entrar"),
+    ("err.not_muted", "{0} no está silenciado"),
+    ("err.not_banned", "{0} no está vetado"),
+    ("err.may_not_punish", "No puedes silenciar ni vetar a {0}"),
+    ("sys.pinned", "{0} fijó un mensaje de {1}: {2}"),
+    ("sys.unpinned", "{0} quitó un mensaje fijado"),
+    ("sys.pins", "Fijados en #{0} ({1}): {2}"),
+    ("sys.no_pins", "No hay nada fijado en #{0}"),
+    ("err.may_not_pin", "Solo los moderadores de #{0} y los administradores pueden fijar mensajes"),
+    ("err.may_not_moderate", "Solo los moderadores de #{0} y los administradores pueden abrir el panel de moderación"),
+    ("err.already_pinned", "Ese mensaje ya está fijado"),
+    ("err.not_pinned", "Ese mensaje no está fijado"),
+    ("err.blocked", "Tu mensaje no se publicó: {0}"),
+    ("sys.link_hits", "{0} enlaces revisados: {1}"),
+    ("sys.no_link_hits", "No hay enlaces revisados"),
+    ("err.link_blocked", "Tu mensaje no se envió: {0} está marcado como {1}"),
+    ("sys.reserved", "{0} queda reservado para cuentas registradas"),
+    ("sys.unreserved", "{0} ya no está reservado"),
+    ("sys.reserved_list", "Nombres reservados: {0}"),
+    ("sys.no_reserved", "No hay nombres reservados"),
+    ("err.already_reserved", "{0} ya está reservado"),
+    ("err.not_reserved", "{0} no está reservado"),
+    ("err.name_reserved", "Este nombre está reservado; inicia sesión con su cuenta para usarlo"),
+    ("err.name_lookalike", "Este nombre se parece demasiado a {0}; elige otro"),
+    ("sys.lookalike_refused", "Rechazado {0} al iniciar sesión: se parece a {1}"),
+    ("sys.captcha_sum", "¿Cuánto es {0} + {1}?"),
+    ("sys.captcha_backwards", "Escribe {0} al revés"),
+    ("err.captcha_wrong", "Esa respuesta no es correcta"),
+    ("err.captcha_required", "Este servidor hace una pregunta a los usuarios nuevos; conéctate con el cliente de chat para responderla"),
+    ("ui.captcha_required", "Responde para demostrar que no eres un bot: {0}"),
+    ("sys.audit_intact", "Registro de auditoría intacto: {0} entradas encadenadas en {2} archivo(s), la más reciente con hash {1}"),
+    ("err.audit_broken", "El registro de auditoría fue manipulado: la cadena se rompe en la línea {0} de {1}, así que esa entrada o una anterior se cambió o se borró"),
+    ("err.audit_missing", "El registro de auditoría fue manipulado: el registro rotado {0} ha desaparecido sin haberse podado"),
+    ("err.audit_unreadable", "No se pudo leer el registro de auditoría: {0}"),
+    ("sys.archive_replayed", "{0} mensajes archivados del {1} al {2}"),
+    ("sys.archive_truncated", "Mostrando los primeros {0} mensajes archivados del {1} al {2}; pide menos días para ver el resto"),
+    ("err.archive_off", "Este servidor no archiva el historial"),
+    ("err.archive_unreadable", "No se pudo leer el archivo"),
+    ("sys.invite_created", "Código de invitación {0} para #{1}, válido durante {2}. Otros entran con /join --code {0}"),
+    ("sys.invite_list", "Códigos de invitación de #{0}: {1}"),
+    ("sys.no_invites", "No hay códigos de invitación activos para #{0}"),
+    ("sys.invite_revoked", "Código de invitación {0} revocado"),
+    ("sys.room_opened", "#{0} vuelve a estar abierta a todos"),
+    ("err.auth_required", "Esta cuenta necesita contraseña"),
+    ("err.bad_password", "Contraseña incorrecta"),
+    ("err.bad_totp", "Código incorrecto o caducado"),
+    ("err.account_save", "No se pudo guardar la cuenta"),
+    ("err.not_registered", "Primero registra este nombre entrando con una contraseña"),
+    ("sys.totp_enabled", "Verificación en dos pasos activada. Añade este secreto a tu app de autenticación: {0} ({1})"),
+    ("err.message_not_found", "Ese mensaje ya no está disponible"),
+    ("sys.forwarded", "Reenviado a #{0}"),
+    ("ui.mirrored_from", "reflejado desde #{0}"),
+    ("ui.forwarded_from", "reenviado desde {0}"),
+    ("ui.link_warning", "Cuidado: {0} está marcado como {1}"),
+    ("ui.forward_to", "Reenviar a"),
+    ("help.forward", "Reenviar el mensaje seleccionado a una sala o persona"),
+    ("sys.starred", "Mensaje de {0} destacado"),
+    ("sys.unstarred", "Quitado de los mensajes destacados"),
+    ("err.not_starred", "Ese mensaje no está destacado"),
+    ("sys.profile_set", "Perfil: {0} ahora es {1}"),
+    ("sys.profile_cleared", "Perfil: {0} borrado"),
+    ("err.line_too_long", "Las líneas pueden tener como máximo {0} bytes; esa se ha ignorado"),
+    ("err.not_utf8", "Esa línea no era UTF-8 válido y se ha ignorado"),
+    ("err.malformed_handshake", "Saludo inicial mal formado: {0}"),
+    ("err.invalid_message_id", "No es un ID de mensaje: {0}"),
+    ("err.message_too_long", "Los mensajes pueden tener como máximo {0} caracteres"),
+    ("err.profile_too_long", "{0} puede tener como máximo {1} caracteres"),
+    ("err.display_name_taken", "{0} es el nombre de usuario de otra persona"),
+    ("err.guest_autojoin", "Los invitados empiezan en las salas del servidor; regístrate para elegir las tuyas"),
+    ("err.autojoin_unchanged", "#{0} ya está en tu lista, o esta tiene {1} salas"),
+    ("err.autojoin_missing", "#{0} no está en tu lista"),
+    ("sys.autojoin_list", "Empiezas con {0}"),
+    ("sys.autojoin_empty", "Empiezas en #general. Añade salas con /autojoin add"),
+    ("sys.autojoin_added", "#{0} añadida a las salas con las que empiezas"),
+    ("sys.autojoin_removed", "#{0} quitada de las salas con las que empiezas"),
+    ("sys.autojoin_cleared", "Empezarás en #general"),
+    ("err.description_too_long", "Las descripciones de sala pueden tener como máximo {0} caracteres"),
+    ("err.bad_tags", "Las salas pueden tener hasta {0} etiquetas, cada una una palabra de hasta {1} letras, dígitos o guiones"),
+    ("sys.roomset_description", "#{0} ahora se describe como: {1}"),
+    ("sys.roomset_description_cleared", "#{0} ya no tiene descripción"),
+    ("sys.roomset_tags", "#{0} ahora tiene las etiquetas {1}"),
+    ("sys.roomset_tags_cleared", "#{0} ya no tiene etiquetas"),
+    ("err.no_prompt", "No hay ninguna pregunta {0} que responder"),
+    ("err.answer_invalid", "{0} no es una de las opciones"),
+    ("sys.onboarded", "Todo listo. /help muestra todo lo que puedes hacer"),
+    ("err.no_profile", "No hay perfil de {0}"),
+    ("sys.totp_disabled", "Verificación en dos pasos desactivada"),
+    ("ui.login", "Acceso"),
+    ("ui.welcome", "Bienvenido a Ultimate Chat"),
+    ("ui.username", "Usuario"),
+    ("ui.login_hint", "Tab: siguiente campo · Espacio: TLS · Ctrl+S: guardar perfil · Supr: borrar perfil\nEnter: conectar · Ctrl+G: entrar como invitado · Esc: salir"),
+    ("ui.profiles", "Perfiles guardados"),
+    ("ui.server", "Servidor"),
+    ("ui.port", "Puerto"),
+    ("ui.tls", "TLS"),
+    ("ui.password", "Contraseña (opcional)"),
+    ("ui.profile_saved", "Perfil {0} guardado"),
+    ("ui.profile_removed", "Perfil {0} borrado"),
+    ("ui.username_required", "Introduce un nombre de usuario"),
+    ("ui.invalid_port", "El puerto debe ser un número entre 1 y 65535"),
+    ("ui.password_required", "Esta cuenta necesita contraseña"),
+    ("ui.totp_required", "Introduce el código de 6 dígitos de tu app de autenticación"),
+    ("ui.confirm_register", "{0} aún no está registrado. Repite la contraseña para registrarlo"),
+    ("ui.password_mismatch", "Las contraseñas no coinciden"),
+    ("ui.prompt_hint", "Enter: continuar · Esc: salir"),
+    ("ui.connection_closed", "El servidor cerró la conexión"),
+    ("ui.connecting", "Conectando a {0}… {1}s"),
+    ("ui.connect_hint", "Esc: cancelar"),
+    ("ui.connect_cancelled", "Conexión cancelada"),
+    ("ui.connect_failed", "No se pudo conectar"),
+    ("ui.connect_failed_hint", "R: reintentar · E: editar servidor · Esc: salir"),
+    ("ui.err_dns", "No se encontró ningún servidor llamado {0}. Revisa el nombre del host."),
+    ("ui.err_refused", "Nada acepta conexiones en {0}. ¿Está el servidor en marcha?"),
+    ("ui.err_unreachable", "{0} no es accesible. Revisa tu conexión de red."),
+    ("ui.err_timeout", "{0} no respondió en {1} segundos"),
+    ("ui.err_connect", "No se pudo conectar a {0}: {1}"),
+    ("ui.tls_unsupported", "Esta versión no admite TLS"),
+    ("ui.connection_lost", "Conexión perdida"),
+    ("ui.reconnecting_in", "Reconectando en {0}s…"),
+    ("ui.reconnect_hint", "Enter: reconectar ahora · /quit: salir"),
+    ("ui.info", "Info"),
+    ("ui.room", "Sala: "),
+    ("ui.rooms", "Salas:"),
+    ("ui.users", "Usuarios:"),
+    ("ui.messages", "Mensajes ({0})"),
+    ("ui.input", "Entrada"),
+    ("ui.usage_tab", "Tab: {0}"),
+    ("ui.commands", "Comandos"),
+    ("ui.cooldown", "Más despacio: {0}"),
+    ("ui.muted_until", "Silenciado: {0}"),
+    ("ui.input_read_only", "Solo lectura"),
+    ("ui.read_only", "Solo lectura: aquí solo publican usuarios con voz y moderadores. Los comandos siguen funcionando"),
+    ("ui.input_multiline", "Entrada (multilínea: Enter envía, Alt+Enter añade una línea)"),
+    ("ui.help", "Ayuda"),
+    ("ui.connected", "Conectado"),
+    ("ui.disconnected", "Desconectado (/quit para salir)"),
+    ("ui.members", "{0} miembros"),
+    ("ui.unread", "{0} sin leer"),
+    ("ui.dnd", "No molestar"),
+    ("ui.away", "Ausente ({0})"),
+    ("ui.away_short", "ausente"),
+    ("ui.find", "Buscar: {0} resultados (Enter para ir, Esc para cerrar)"),
+    ("ui.jump_to", "Ir a (Enter para abrir, Esc para cerrar)"),
+    ("ui.ignored_one", "1 mensaje de usuarios ignorados — pulsa Ctrl+X para mostrar"),
+    ("ui.ignored_many", "{0} mensajes de usuarios ignorados — pulsa Ctrl+X para mostrar"),
+    ("ui.joined_one", "1 usuario entró"),
+    ("ui.joined_many", "{0} usuarios entraron"),
+    ("ui.left", "{0} salieron"),
+    ("help.commands", "Comandos:"),
+    ("help.keys", "Teclas:"),
+    ("help.join", "Cambiar de sala"),
+    ("help.msg", "Mensaje privado"),
+    ("help.ttl", "Ver o fijar cuánto guarda esta sala los mensajes"),
+    ("help.rooms", "Listar salas por categoría"),
+    ("help.discover", "Buscar salas abiertas por nombre, etiqueta o descripción"),
+    ("help.autojoin", "Salas con las que empiezas cada sesión"),
+    ("help.collapse", "Plegar o desplegar una categoría (todas si no se indica)"),
+    ("help.conninfo", "Latencia y tráfico de tu conexión"),
+    ("help.users", "Listar usuarios"),
+    ("help.thank", "Agradecer a alguien en una sala con karma"),
+    ("help.leaderboard", "Quién tiene más karma aquí"),
+    ("help.trivia", "Jugar a trivia en esta sala"),
+    ("help.roll", "Tirar dados a la vista de la sala"),
+    ("help.choose", "Lanzar una moneda, o elegir una de las opciones"),
+    ("help.translate", "Traducir un mensaje, solo para ti"),
+    ("help.time", "Mostrar una hora en la hora local de cada uno"),
+    ("help.translate_focused", "Traducir el mensaje seleccionado a tu idioma"),
+    ("help.activity", "Gráfica de cuándo hay más actividad en esta sala u otra"),
+    ("help.ignore", "Ocultar los mensajes de un usuario (/unignore para deshacer)"),
+    ("help.quiet", "Reducir avisos de entradas/salidas en esta sala"),
+    ("help.export", "Guardar el historial de esta sala (.txt/.json/.html)"),
+    ("help.find", "Buscar en los mensajes cargados (también Ctrl+F)"),
+    ("help.dnd", "Activar/desactivar no molestar"),
+    ("help.notify", "Nivel de notificación de esta sala"),
+    ("help.quiethours", "Horario diario de no molestar"),
+    ("help.receipts", "Compartir confirmaciones de lectura (opcional)"),
+    ("help.spell", "Activa la corrección ortográfica o añade una palabra a tu diccionario"),
+    ("help.spell_suggest", "Sugerencias para la palabra subrayada"),
+    ("ui.no_suggestions", "Sin sugerencias"),
+    ("ui.add_to_dictionary", "Añadir al diccionario"),
+    ("help.select", "Seleccionar un mensaje"),
+    ("help.reveal_spoiler", "Mostrar u ocultar los spoilers del mensaje seleccionado"),
+    ("ui.spoiler_hint", "(pulsa r para mostrar)"),
+    ("help.starred", "Lista tus mensajes destacados"),
+    ("help.star", "Destacar el mensaje seleccionado"),
+    ("help.report", "Denunciar un mensaje a los administradores"),
+    ("help.pins", "Listar los mensajes fijados en esta sala"),
+    ("help.pin", "Fijar el mensaje seleccionado (moderadores de la sala)"),
+    ("help.mod_panel", "Panel de moderación (administradores)"),
+    ("ui.starred", "Mensajes destacados"),
+    ("ui.starred_hint", "Enter: ir · Supr: quitar · Esc: cerrar"),
+    ("ui.starred_empty", "Aún no hay nada destacado. Selecciona un mensaje con Alt+Arriba y pulsa s"),
+    ("ui.discover", "Salas"),
+    ("ui.discover_matching", "Salas que coinciden con \"{0}\""),
+    ("ui.discover_hint", "Enter: entrar · Esc: cerrar"),
+    ("ui.discover_empty", "Ninguna sala abierta coincide. Prueba otra palabra, o /discover solo para verlas todas"),
+    ("ui.discover_counts", "{0} aquí · {1} hoy"),
+    ("ui.discover_active", "activa hace {0}"),
+    ("ui.discover_quiet", "sin mensajes recientes"),
+    ("help.goto", "Ir a un mensaje por su enlace"),
+    ("help.follow_ref", "Ir al mensaje enlazado en el seleccionado"),
+    ("help.cite", "Insertar un enlace al mensaje seleccionado"),
+    ("help.profile", "Ver la ficha de perfil de alguien (la tuya por defecto)"),
+    ("help.join_code", "Entrar en una sala solo por invitación con un código"),
+    ("help.invitecode", "Códigos para tu sala; la hace solo por invitación"),
+    ("help.profile_set", "Define display_name, bio, pronouns o timezone; sin valor se borra"),
+    ("ui.pronouns", "Pronombres"),
+    ("ui.timezone", "Zona horaria"),
+    ("ui.stats", "Estadísticas del servidor"),
+    ("ui.stats_uptime", "Tiempo activo"),
+    ("ui.stats_clients", "Clientes conectados"),
+    ("ui.stats_rooms", "Salas"),
+    ("ui.stats_rate", "Mensajes por minuto"),
+    ("ui.stats_history", "Historial en memoria"),
+    ("ui.stats_queue", "Cola de difusión"),
+    ("ui.console", "Consola del servidor"),
+    ("ui.console_connections", "Conexiones"),
+    ("ui.console_rooms", "Salas activas"),
+    ("ui.console_actions", "Moderación reciente"),
+    ("ui.console_output", "Salida de comandos"),
+    ("ui.console_hint", "Intro: ejecutar un comando · Esc: salir"),
+    ("ui.console_user", "Usuario"),
+    ("ui.console_room", "Sala"),
+    ("ui.console_address", "Dirección"),
+    ("ui.console_connected", "Conectado"),
+    ("ui.console_traffic", "Entrada / salida"),
+    ("ui.console_users", "Usuarios"),
+    ("ui.console_rate", "Msjs/min"),
+    ("ui.console_elsewhere", "La consola del servidor tiene su propia pantalla: ejecuta client --console <nombre de admin>"),
+    ("ui.mod_panel", "Moderación"),
+    ("ui.mod_members", "Miembros"),
+    ("ui.mod_reports", "Denuncias"),
+    ("ui.mod_pins", "Fijados"),
+    ("ui.mod_empty", "No hay nada"),
+    ("ui.mod_warnings", "{0} advertencias"),
+    ("ui.mod_muted", "silenciado {0}"),
+    ("ui.mod_hint_members", "k expulsar · m silenciar 10m · u quitar silencio · b vetar 1d · w advertir · Tab siguiente · Esc cerrar"),
+    ("ui.mod_hint_reports", "Enter ir · d descartar · p fijar · k expulsar al autor · Tab siguiente · Esc cerrar"),
+    ("ui.mod_hint_pins", "Enter ir · Supr quitar · Tab siguiente · Esc cerrar"),
+    ("ui.activity", "Actividad en #{0}"),
+    ("ui.activity_total", "{0} mensajes en las últimas 24 horas"),
+    ("ui.activity_busiest", "más activa hacia las {0}"),
+    ("ui.activity_day_ago", "hace 24 h"),
+    ("ui.activity_now", "ahora"),
+    ("ui.activity_by_hour", "Por hora del día, en los últimos 7 días"),
+    ("ui.profile_empty", "Aún no hay nada"),
+    ("ui.close_hint", "cualquier tecla: cerrar"),
+    ("ui.onboarding_title", "Bienvenida"),
+    ("ui.onboarding_step", "{0} de {1}"),
+    ("ui.onboarding_display_name", "¿Qué nombre deben ver los demás?"),
+    ("ui.onboarding_room", "Elige las salas con las que empezar cada vez que te conectes"),
+    ("ui.onboarding_notifications", "¿Cuándo debe avisarte el cliente?"),
+    ("ui.onboarding_notify_all", "Cada mensaje"),
+    ("ui.onboarding_notify_mentions", "Menciones y mensajes privados"),
+    ("ui.onboarding_notify_none", "Nunca"),
+    ("ui.form_text_hint", "Enter: siguiente · Esc: omitir"),
+    ("ui.form_choice_hint", "↑↓: elegir · Enter: siguiente · Esc: omitir"),
+    ("ui.form_multiple_hint", "↑↓: elegir · Espacio: marcar · Enter: siguiente · Esc: omitir"),
+    ("help.alias", "Atajos de comandos personales (/unalias para quitar)"),
+    ("help.away", "Marcarte como ausente (/back para volver)"),
+    ("help.totp", "Verificación en dos pasos para tu cuenta registrada"),
+    ("help.quit", "Salir"),
+    ("help.group", "Gestionar grupos de usuarios"),
+    ("help.roomset", "Cambiar los ajustes de esta sala"),
+    ("help.back", "Volver de ausente"),
+    ("help.history", "Traer mensajes antiguos o archivados"),
+    ("help.star_id", "Marcar un mensaje con estrella"),
+    ("help.unstar", "Quitar una estrella"),
+    ("help.pin_id", "Fijar un mensaje en la sala"),
+    ("help.unpin", "Desfijar un mensaje"),
+    ("help.warn", "Advertir a un usuario"),
+    ("help.warnings", "Ver o borrar advertencias"),
+    ("help.help", "Listar los comandos"),
+    ("help.kick", "Desconectar a un usuario"),
+    ("help.mute", "Silenciar a un usuario un tiempo"),
+    ("help.unmute", "Quitar un silencio"),
+    ("help.ban", "Expulsar a un usuario un tiempo"),
+    ("help.unban", "Levantar una expulsión"),
+    ("help.flagged", "Revisar mensajes marcados"),
+    ("help.linkhits", "Ver enlaces bloqueados"),
+    ("help.reserve", "Reservar un nombre o patrón"),
+    ("help.unreserve", "Liberar un nombre reservado"),
+    ("help.mirror", "Reflejar una sala en otra"),
+    ("help.unmirror", "Dejar de reflejar"),
+    ("help.maintenance", "Anunciar mantenimiento"),
+    ("help.stats", "Ver estadísticas del servidor"),
+    ("help.snapshot", "Guardar una instantánea del estado"),
+    ("help.reload", "Recargar la configuración"),
+    ("help.audit", "Verificar el registro de auditoría"),
+    ("help.scroll", "Desplazar el historial"),
+    ("help.newline", "Nueva línea en el mensaje"),
+    ("help.cycle_rooms", "Recorrer salas visitadas (se conservan los borradores)"),
+    ("help.switcher", "Selector rápido de salas y contactos"),
+    ("help.resize", "Reducir/Ampliar la barra lateral"),
+    ("help.zen", "Mostrar/ocultar la barra lateral (modo zen)"),
+    ("help.user_list", "Mostrar/ocultar la lista de usuarios"),
+    ("help.reveal", "Mostrar mensajes de usuarios ignorados"),
+    ("help.toggle_help", "Mostrar/ocultar la ayuda"),
+];
+
+static DE: &[(&str, &str)] = &[
+    ("sys.joined_room", "{0} hat den Raum betreten"),
+    ("sys.left_room", "{0} hat den Raum verlassen"),
+    ("sys.room_change", "#{0} betreten"),
+    ("sys.users_in_room", "Benutzer in #{0}: {1}"),
+    ("sys.kicked", "{0} wurde von {1} entfernt"),
+    ("sys.away_notice", "{0} ist abwesend: {1}"),
+    ("err.kicked", "Du wurdest von {0} entfernt"),
+    ("err.usage", "Verwendung: {0}"),
+    ("err.usage_example", "Verwendung: {0}, z. B. {1}"),
+    ("err.karma_off", "Karma ist in #{0} ausgeschaltet"),
+    ("err.game_running", "In #{0} läuft schon ein Spiel"),
+    ("err.translate_off", "Dieser Server hat keinen Übersetzungsdienst"),
+    ("err.time_no_zone", "Gib eine Zeitzone wie UTC+2 mit an, oder setze deine mit /profile set timezone UTC+2"),
+    ("err.translate_failed", "Der Übersetzungsdienst ist nicht erreichbar"),
+    ("err.translate_rate_limited", "Du kannst {0} Übersetzungen pro Minute anfordern; versuch es gleich noch einmal"),
+    ("err.no_game", "In #{0} läuft kein Spiel"),
+    ("err.may_not_stop_game", "Nur wer das Spiel gestartet hat ({0}) oder ein Moderator kann es beenden"),
+    ("err.thank_self", "Du kannst dich nicht bei dir selbst bedanken"),
+    ("err.not_in_room", "{0} ist nicht in #{1}"),
+    ("err.thank_too_soon", "Du hast dich vor weniger als einer Minute bei {0} bedankt"),
+    ("err.not_online", "Benutzer '{0}' ist nicht online"),
+    ("err.not_online_suggest", "Benutzer '{0}' ist nicht online. Meintest du {1}?"),
+    ("err.maintenance", "Der Server wird gerade gewartet, schau bald wieder vorbei. {0}"),
+    ("err.no_maintenance", "Es ist keine Wartung geplant"),
+    ("sys.maintenance_soon", "Der Server geht in {0} in die Wartung. {1}"),
+    ("sys.maintenance_now", "Der Server geht jetzt in die Wartung, bis bald. {0}"),
+    ("sys.maintenance_off", "Die Wartung wurde abgesagt"),
+    ("err.banned", "Du bist auf diesem Server gesperrt"),
+    ("err.rate_limited", "Langsamer: höchstens {0} Nachrichten pro Minute"),
+    ("err.config_invalid", "Die Konfiguration wurde nicht neu geladen: {0}"),
+    ("sys.config_reloaded", "Konfiguration neu geladen"),
+    ("sys.snapshot_saved", "Snapshot in {0} gespeichert"),
+    ("err.snapshot_failed", "Snapshot konnte nicht nach {0} geschrieben werden: {1}"),
+    ("err.backup_failed", "Sicherung nach {0} fehlgeschlagen: {1}"),
+    ("sys.conninfo", "{0}: Latenz {1}, gesendet {2}, empfangen {3}, {4} Neuverbindungen, online seit {5} von {6} ({7})"),
+    ("err.admin_only", "Nur Administratoren dürfen das"),
+    ("err.unknown_command", "Unbekannter Befehl: {0}"),
+    ("sys.help", "Befehle: {0}. Alles andere geht an den Raum"),
+    ("sys.digest", "#{0}: {1} neue seit du dort vor {3} zuletzt gelesen hast, {2} erwähnen dich"),
+    ("err.owner_only_ttl", "Nur der Besitzer von #{0} kann ändern, wie lange Nachrichten aufbewahrt werden"),
+    ("sys.ttl_set", "Nachrichten in #{0} verschwinden nach {1}"),
+    ("sys.ttl_off", "Nachrichten in #{0} werden aufbewahrt"),
+    ("err.mirror_invalid", "Ein Raum kann nicht in sich selbst gespiegelt werden, und nur das Ziel darf eine ganze Kategorie sein"),
+    ("err.mirror_not_found", "#{0} wird nicht nach #{1} gespiegelt"),
+    ("sys.mirror_added", "Nachrichten aus #{0} werden jetzt nach #{1} gespiegelt"),
+    ("sys.mirror_both", "#{0} und #{1} spiegeln sich jetzt gegenseitig"),
+    ("sys.mirror_removed", "Spiegelung zwischen #{0} und #{1} beendet"),
+    ("sys.mirror_list", "Spiegelungen: {0}"),
+    ("sys.no_mirrors", "Keine Räume werden gespiegelt"),
+    ("err.invalid_room", "Ungültiger Raumname {0}: bis zu 64 Zeichen, keine Leerzeichen, Kommas, # oder @, / zwischen Kategorien wie in work/standup"),
+    ("err.invalid_username", "Ungültiger Benutzername"),
+    ("err.username_taken", "Benutzername ist bereits vergeben"),
+    ("err.guests_disabled", "Gastzugang ist auf diesem Server deaktiviert"),
+    ("err.guest_name_reserved", "Namen, die mit guest- beginnen, sind Gästen vorbehalten"),
+    ("err.guests_full", "Gerade sind keine Gastnamen frei, bitte später erneut versuchen"),
+    ("err.guest_no_new_rooms", "Gäste können nur bestehende Räume betreten"),
+    ("err.invite_only", "#{0} ist nur mit Einladung zugänglich; frag den Besitzer nach einem Code"),
+    ("err.invite_invalid", "Dieser Einladungscode ist unbekannt oder abgelaufen"),
+    ("err.invite_lifetime", "Einladungscodes gelten 1m bis 30d, z. B. 30m, 24h oder 7d"),
+    ("err.may_not_invite", "Du darfst keine Einladungen für #{0} verwalten"),
+    ("sys.group_mention", "{0} hat eine Gruppe erwähnt, in der du bist, in #{1}: {2}"),
+    ("sys.group_list", "Gruppen: {0}"),
+    ("sys.no_groups", "Es gibt keine Gruppen"),
+    ("sys.group_members", "Mitglieder von @{0}: {1}"),
+    ("sys.group_created", "Gruppe @{0} angelegt"),
+    ("sys.group_deleted", "Gruppe @{0} gelöscht"),
+    ("sys.group_added", "{0} ist in @{1}"),
+    ("sys.group_removed", "{0} ist nicht in @{1}"),
+    ("sys.group_allowed", "Mitglieder von @{0} dürfen jetzt #{1} betreten"),
+    ("sys.group_denied", "Die Mitgliedschaft in @{0} gibt keinen Zugang mehr zu #{1}"),
+    ("sys.room_groups", "Gruppen mit Zugang zu #{0}: {1}"),
+    ("err.group_not_found", "Es gibt keine Gruppe @{0}"),
+    ("err.group_exists", "Die Gruppe @{0} gibt es schon"),
+    ("err.group_name", "\"{0}\" kann kein Gruppenname sein: bis zu 32 Zeichen ohne Leerzeichen"),
+    ("err.group_not_allowed", "@{0} hatte keinen Zugang zu #{1}"),
+    ("sys.roomset", "#{0}: Schreiben dürfen {1}, Einladen {2}. Moderatoren: {3}. Mit Stimme: {4}. Moderation: {5}. Karma: {6}. Rundfunk: {7}. Beschreibung: {8}. Tags: {9}"),
+    ("sys.roomset_permission", "Wer in #{1} {0} darf: {2}"),
+    ("sys.roomset_level", "{0} hat in #{1} jetzt die Stufe {2}"),
+    ("err.may_not_post", "Du darfst in #{0} nicht schreiben"),
+    ("err.may_not_roomset", "Du darfst die Einstellungen von #{0} nicht ändern"),
+    ("err.roomset_unchanged", "Für {0} in #{1} gibt es nichts zu ändern"),
+    ("sys.warned", "{0} hat jetzt {1} Verwarnungen"),
+    ("sys.warned_mute", "{0} hat jetzt {1} Verwarnungen und ist für {2} stummgeschaltet"),
+    ("sys.warned_kick", "{0} hat jetzt {1} Verwarnungen und wurde getrennt"),
+    ("sys.warned_ban", "{0} hat jetzt {1} Verwarnungen und ist für {2} gesperrt"),
+    ("sys.warnings", "{0} hat {1} Verwarnungen: {2}"),
+    ("sys.no_warnings", "{0} hat keine Verwarnungen"),
+    ("sys.warnings_cleared", "Verwarnungen, Stummschaltungen und Sperren für {0} gelöscht"),
+    ("err.warned", "{0} hat dich verwarnt: {1}. Du hast {2} Verwarnungen"),
+    ("err.muted", "Du bist noch {0} stummgeschaltet"),
+    ("err.banned_for", "Du bist noch {0} gesperrt"),
+    ("err.kicked_warnings", "Du wurdest nach {0} Verwarnungen getrennt"),
+    ("err.may_not_warn", "Du darfst {0} nicht verwarnen"),
+    ("err.may_not_review", "Nur Moderatoren und Admins können die Verwarnungen anderer sehen"),
+    ("sys.roomset_moderation", "Moderation in #{0}: {1}"),
+    ("sys.roomset_karma", "Karma in #{0}: {1}"),
+    ("sys.roomset_broadcast", "Ankündigungsmodus in #{0}: {1}"),
+    ("sys.trivia_start", "Quiz! {0} Fragen, je {1} Sekunden. Antwortet im Chat"),
+    ("sys.trivia_question", "Frage {0}/{1}: {2}"),
+    ("sys.trivia_correct", "{0} hat es: {1} ({0} hat {2})"),
+    ("sys.trivia_timeout", "Die Zeit ist um! Die Antwort war {0}"),
+    ("sys.trivia_over", "Quiz vorbei: {0}"),
+    ("sys.trivia_over_no_winner", "Quiz vorbei, und niemand hat gepunktet"),
+    ("sys.trivia_scores", "Quiz, Frage {0}/{1}: {2}"),
+    ("sys.trivia_no_scores", "Quiz, Frage {0}/{1}: noch hat niemand gepunktet"),
+    ("sys.game_stopped", "{1} hat das Spiel {0} beendet"),
+    ("sys.rolled", "würfelte {0}: {1}"),
+    ("sys.flipped_heads", "warf eine Münze: Kopf"),
+    ("sys.flipped_tails", "warf eine Münze: Zahl"),
+    ("sys.chose", "ließ wählen: {0} (aus {1})"),
+    ("sys.translation", "Übersetzung ({0}) von {1}: {2}"),
+    ("sys.time", "{0} meint {1} UTC: {2}"),
+    ("sys.time_some_unset", "{0} meint {1} UTC: {2} ({3} weitere ohne Zeitzone im Profil)"),
+    ("sys.time_no_zones", "{0} meint {1} UTC; hier hat niemand eine Zeitzone im Profil"),
+    ("sys.thanked", "{0} hat sich bei {1} bedankt ({1} hat jetzt {2} Karma)"),
+    ("sys.leaderboard", "Spitze in #{0}: {1}"),
+    ("sys.no_karma", "In #{0} hat sich noch niemand bedankt"),
+    ("sys.flagged", "Zur Prüfung markiert: {0} in #{1} ({2}): {3}"),
+    ("sys.flagged_list", "{0} markierte Nachrichten: {1}"),
+    ("sys.no_flagged", "Keine markierten Nachrichten"),
+    ("sys.reported", "An die Admins gemeldet"),
+    ("sys.dismissed", "{0} markierte Einträge verworfen"),
+    ("err.not_flagged", "Diese Nachricht ist nicht zur Prüfung markiert"),
+    ("sys.muted", "{0} ist für {1} stummgeschaltet"),
+    ("sys.banned", "{0} ist für {1} gesperrt"),
+    ("sys.unmuted", "{0} darf wieder schreiben"),
+    ("sys.unbanned", "{0} darf sich wieder anmelden"),
+    ("err.not_muted", "{0} ist nicht stummgeschaltet"),
+    ("err.not_banned", "{0} ist nicht gesperrt"),
+    ("err.may_not_punish", "Du darfst {0} weder stummschalten noch sperren"),
+    ("sys.pinned", "{0} hat eine Nachricht von {1} angeheftet: {2}"),
+    ("sys.unpinned", "{0} hat eine Nachricht losgelöst"),
+    ("sys.pins", "Angeheftet in #{0} ({1}): {2}"),
+    ("sys.no_pins", "In #{0} ist nichts angeheftet"),
+    ("err.may_not_pin", "Nur Moderatoren von #{0} und Admins können Nachrichten anheften"),
+    ("err.may_not_moderate", "Nur Moderatoren von #{0} und Admins können das Moderationsfenster öffnen"),
+    ("err.already_pinned", "Diese Nachricht ist schon angeheftet"),
+    ("err.not_pinned", "Diese Nachricht ist nicht angeheftet"),
+    ("err.blocked", "Deine Nachricht wurde nicht gesendet: {0}"),
+    ("sys.link_hits", "{0} geprüfte Links: {1}"),
+    ("sys.no_link_hits", "Keine geprüften Links"),
+    ("err.link_blocked", "Deine Nachricht wurde nicht gesendet: {0} ist als {1} eingestuft"),
+    ("sys.reserved", "{0} ist registrierten Konten vorbehalten"),
+    ("sys.unreserved", "{0} ist nicht mehr reserviert"),
+    ("sys.reserved_list", "Reservierte Namen: {0}"),
+    ("sys.no_reserved", "Keine reservierten Namen"),
+    ("err.already_reserved", "{0} ist bereits reserviert"),
+    ("err.not_reserved", "{0} ist nicht reserviert"),
+    ("err.name_reserved", "Dieser Name ist reserviert; melde dich mit seinem Konto an, um ihn zu nutzen"),
+    ("err.name_lookalike", "Dieser Name sieht {0} zu ähnlich; wähle einen anderen"),
+    ("sys.lookalike_refused", "{0} bei der Anmeldung abgewiesen: sieht aus wie {1}"),
+    ("sys.captcha_sum", "Was ist {0} + {1}?"),
+    ("sys.captcha_backwards", "Schreibe {0} rückwärts"),
+    ("err.captcha_wrong", "Diese Antwort ist falsch"),
+    ("err.captcha_required", "Dieser Server stellt neuen Nutzern zuerst eine Frage; verbinde dich mit dem Chat-Client, um sie zu beantworten"),
+    ("ui.captcha_required", "Antworte, um zu zeigen, dass du kein Bot bist: {0}"),
+    ("sys.audit_intact", "Audit-Log unverändert: {0} verkettete Einträge in {2} Datei(en), der neueste mit Hash {1}"),
+    ("err.audit_broken", "Das Audit-Log wurde manipuliert: Die Kette bricht in Zeile {0} von {1}, dieser oder ein früherer Eintrag wurde geändert oder entfernt"),
+    ("err.audit_missing", "Das Audit-Log wurde manipuliert: Das rotierte Log {0} fehlt, wurde aber nie aufgeräumt"),
+    ("err.audit_unreadable", "Das Audit-Log konnte nicht gelesen werden: {0}"),
+    ("sys.archive_replayed", "{0} archivierte Nachrichten vom {1} bis {2}"),
+    ("sys.archive_truncated", "Die ersten {0} archivierten Nachrichten vom {1} bis {2}; frage nach weniger Tagen, um den Rest zu sehen"),
+    ("err.archive_off", "Dieser Server archiviert keinen Verlauf"),
+    ("err.archive_unreadable", "Das Archiv konnte nicht gelesen werden"),
+    ("sys.invite_created", "Einladungscode {0} für #{1}, gültig für {2}. Andere treten mit /join --code {0} bei"),
+    ("sys.invite_list", "Einladungscodes für #{0}: {1}"),
+    ("sys.no_invites", "Keine aktiven Einladungscodes für #{0}"),
+    ("sys.invite_revoked", "Einladungscode {0} widerrufen"),
+    ("sys.room_opened", "#{0} ist wieder für alle offen"),
+    ("err.auth_required", "Dieses Konto benötigt ein Passwort"),
+    ("err.bad_password", "Falsches Passwort"),
+    ("err.bad_totp", "Falscher oder abgelaufener Code"),
+    ("err.account_save", "Konto konnte nicht gespeichert werden"),
+    ("err.not_registered", "Registriere diesen Namen zuerst, indem du dich mit einem Passwort anmeldest"),
+    ("sys.totp_enabled", "Zwei-Faktor-Anmeldung ist aktiv. Füge dieses Geheimnis deiner Authenticator-App hinzu: {0} ({1})"),
+    ("err.message_not_found", "Diese Nachricht ist nicht mehr verfügbar"),
+    ("sys.forwarded", "Weitergeleitet an #{0}"),
+    ("ui.mirrored_from", "gespiegelt aus #{0}"),
+    ("ui.forwarded_from", "weitergeleitet von {0}"),
+    ("ui.link_warning", "Vorsicht: {0} ist als {1} eingestuft"),
+    ("ui.forward_to", "Weiterleiten an"),
+    ("help.forward", "Ausgewählte Nachricht an einen Raum oder eine Person weiterleiten"),
+    ("sys.starred", "Nachricht von {0} markiert"),
+    ("sys.unstarred", "Aus den markierten Nachrichten entfernt"),
+    ("err.not_starred", "Diese Nachricht ist nicht markiert"),
+    ("sys.profile_set", "Profil: {0} ist jetzt {1}"),
+    ("sys.profile_cleared", "Profil: {0} gelöscht"),
+    ("err.line_too_long", "Zeilen dürfen höchstens {0} Bytes lang sein; diese wurde ignoriert"),
+    ("err.not_utf8", "Diese Zeile war kein gültiges UTF-8 und wurde ignoriert"),
+    ("err.malformed_handshake", "Ungültiger Handshake: {0}"),
+    ("err.invalid_message_id", "Keine Nachrichten-ID: {0}"),
+    ("err.message_too_long", "Nachrichten dürfen höchstens {0} Zeichen lang sein"),
+    ("err.profile_too_long", "{0} darf höchstens {1} Zeichen lang sein"),
+    ("err.display_name_taken", "{0} ist der Benutzername von jemand anderem"),
+    ("err.guest_autojoin", "Gäste starten in den Räumen des Servers; registriere dich, um eigene zu wählen"),
+    ("err.autojoin_unchanged", "#{0} ist schon auf deiner Liste, oder sie hat {1} Räume"),
+    ("err.autojoin_missing", "#{0} ist nicht auf deiner Liste"),
+    ("sys.autojoin_list", "Du startest mit {0}"),
+    ("sys.autojoin_empty", "Du startest in #general. Räume fügst du mit /autojoin add hinzu"),
+    ("sys.autojoin_added", "#{0} zu deinen Starträumen hinzugefügt"),
+    ("sys.autojoin_removed", "#{0} aus deinen Starträumen entfernt"),
+    ("sys.autojoin_cleared", "Du startest in #general"),
+    ("err.description_too_long", "Raumbeschreibungen dürfen höchstens {0} Zeichen haben"),
+    ("err.bad_tags", "Räume können bis zu {0} Tags haben, jeder ein Wort aus bis zu {1} Buchstaben, Ziffern oder Bindestrichen"),
+    ("sys.roomset_description", "#{0} wird jetzt so beschrieben: {1}"),
+    ("sys.roomset_description_cleared", "#{0} hat keine Beschreibung mehr"),
+    ("sys.roomset_tags", "#{0} hat jetzt die Tags {1}"),
+    ("sys.roomset_tags_cleared", "#{0} hat keine Tags mehr"),
+    ("err.no_prompt", "Es gibt keine Frage {0} zu beantworten"),
+    ("err.answer_invalid", "{0} ist keine der Möglichkeiten"),
+    ("sys.onboarded", "Alles bereit. /help zeigt alles, was du tun kannst"),
+    ("err.no_profile", "Kein Profil für {0}"),
+    ("sys.totp_disabled", "Zwei-Faktor-Anmeldung ist aus"),
+    ("ui.login", "Anmeldung"),
+    ("ui.welcome", "Willkommen bei Ultimate Chat"),
+    ("ui.username", "Benutzername"),
+    ("ui.login_hint", "Tab: nächstes Feld · Leertaste: TLS · Strg+S: Profil speichern · Entf: Profil löschen\nEnter: verbinden · Strg+G: als Gast beitreten · Esc: beenden"),
+    ("ui.profiles", "Gespeicherte Profile"),
+    ("ui.server", "Server"),
+    ("ui.port", "Port"),
+    ("ui.tls", "TLS"),
+    ("ui.password", "Passwort (optional)"),
+    ("ui.profile_saved", "Profil {0} gespeichert"),
+    ("ui.profile_removed", "Profil {0} gelöscht"),
+    ("ui.username_required", "Bitte einen Benutzernamen eingeben"),
+    ("ui.invalid_port", "Der Port muss eine Zahl zwischen 1 und 65535 sein"),
+    ("ui.password_required", "Dieses Konto benötigt ein Passwort"),
+    ("ui.totp_required", "Gib den 6-stelligen Code aus deiner Authenticator-App ein"),
+    ("ui.confirm_register", "{0} ist noch nicht registriert. Passwort zur Registrierung erneut eingeben"),
+    ("ui.password_mismatch", "Passwörter stimmen nicht überein"),
+    ("ui.prompt_hint", "Enter: weiter · Esc: beenden"),
+    ("ui.connection_closed", "Der Server hat die Verbindung geschlossen"),
+    ("ui.connecting", "Verbinde mit {0}… {1}s"),
+    ("ui.connect_hint", "Esc: abbrechen"),
+    ("ui.connect_cancelled", "Verbindung abgebrochen"),
+    ("ui.connect_failed", "Verbindung fehlgeschlagen"),
+    ("ui.connect_failed_hint", "R: erneut versuchen · E: Server ändern · Esc: beenden"),
+    ("ui.err_dns", "Kein Server namens {0} gefunden. Bitte den Hostnamen prüfen."),
+    ("ui.err_refused", "Unter {0} nimmt niemand Verbindungen an. Läuft der Server?"),
+    ("ui.err_unreachable", "{0} ist nicht erreichbar. Bitte die Netzwerkverbindung prüfen."),
+    ("ui.err_timeout", "{0} hat nicht innerhalb von {1} Sekunden geantwortet"),
+    ("ui.err_connect", "Verbindung zu {0} fehlgeschlagen: {1}"),
+    ("ui.tls_unsupported", "TLS wird von dieser Version nicht unterstützt"),
+    ("ui.connection_lost", "Verbindung verloren"),
+    ("ui.reconnecting_in", "Neuer Versuch in {0}s…"),
+    ("ui.reconnect_hint", "Enter: jetzt verbinden · /quit: beenden"),
+    ("ui.info", "Info"),
+    ("ui.room", "Raum: "),
+    ("ui.rooms", "Räume:"),
+    ("ui.users", "Benutzer:"),
+    ("ui.messages", "Nachrichten ({0})"),
+    ("ui.input", "Eingabe"),
+    ("ui.usage_tab", "Tab: {0}"),
+    ("ui.commands", "Befehle"),
+    ("ui.cooldown", "Langsamer: {0}"),
+    ("ui.muted_until", "Stummgeschaltet: {0}"),
+    ("ui.input_read_only", "Nur lesen"),
+    ("ui.read_only", "Nur lesen: hier schreiben nur Nutzer mit Stimme und Moderatoren. Befehle funktionieren weiterhin"),
+    ("ui.input_multiline", "Eingabe (mehrzeilig: Enter sendet, Alt+Enter fügt eine Zeile hinzu)"),
+    ("ui.help", "Hilfe"),
+    ("ui.connected", "Verbunden"),
+    ("ui.disconnected", "Getrennt (/quit zum Beenden)"),
+    ("ui.members", "{0} Mitglieder"),
+    ("ui.unread", "{0} ungelesen"),
+    ("ui.dnd", "Nicht stören"),
+    ("ui.away", "Abwesend ({0})"),
+    ("ui.away_short", "abwesend"),
+    ("ui.find", "Suche: {0} Treffer (Enter zum Springen, Esc zum Schließen)"),
+    ("ui.jump_to", "Springen zu (Enter zum Öffnen, Esc zum Schließen)"),
+    ("ui.ignored_one", "1 Nachricht von ignorierten Benutzern — Strg+X zum Anzeigen"),
+    ("ui.ignored_many", "{0} Nachrichten von ignorierten Benutzern — Strg+X zum Anzeigen"),
+    ("ui.joined_one", "1 Benutzer beigetreten"),
+    ("ui.joined_many", "{0} Benutzer beigetreten"),
+    ("ui.left", "{0} gegangen"),
+    ("help.commands", "Befehle:"),
+    ("help.keys", "Tasten:"),
+    ("help.join", "Raum wechseln"),
+    ("help.msg", "Private Nachricht"),
+    ("help.ttl", "Anzeigen oder festlegen, wie lange dieser Raum Nachrichten behält"),
+    ("help.rooms", "Räume nach Kategorie auflisten"),
+    ("help.discover", "Offene Räume nach Name, Tag oder Beschreibung finden"),
+    ("help.autojoin", "Räume, mit denen du jede Sitzung startest"),
+    ("help.collapse", "Kategorie in der Seitenleiste ein-/ausklappen (ohne Angabe alle)"),
+    ("help.conninfo", "Latenz und Datenverkehr deiner Verbindung"),
+    ("help.users", "Benutzer auflisten"),
+    ("help.thank", "Sich in einem Raum mit Karma bedanken"),
+    ("help.leaderboard", "Wer hier das meiste Karma hat"),
+    ("help.trivia", "Ein Quiz in diesem Raum spielen"),
+    ("help.roll", "Für alle im Raum sichtbar würfeln"),
+    ("help.choose", "Eine Münze werfen oder eine der Optionen wählen"),
+    ("help.translate", "Eine Nachricht nur für dich übersetzen"),
+    ("help.time", "Eine Uhrzeit in der Ortszeit aller zeigen"),
+    ("help.translate_focused", "Die ausgewählte Nachricht in deine Sprache übersetzen"),
+    ("help.activity", "Diagramm, wann in diesem oder einem anderen Raum am meisten los ist"),
+    ("help.ignore", "Nachrichten eines Benutzers ausblenden (/unignore zum Rückgängigmachen)"),
+    ("help.quiet", "Beitritts-/Austrittsmeldungen in diesem Raum reduzieren"),
+    ("help.export", "Verlauf dieses Raums speichern (.txt/.json/.html)"),
+    ("help.find", "Geladene Nachrichten durchsuchen (auch Strg+F)"),
+    ("help.dnd", "Nicht stören umschalten"),
+    ("help.notify", "Benachrichtigungsstufe für diesen Raum"),
+    ("help.quiethours", "Tägliches Nicht-stören-Zeitfenster"),
+    ("help.receipts", "Lesebestätigungen teilen (optional)"),
+    ("help.spell", "Rechtschreibprüfung umschalten oder ein Wort zum Wörterbuch hinzufügen"),
+    ("help.spell_suggest", "Vorschläge für das unterstrichene Wort"),
+    ("ui.no_suggestions", "Keine Vorschläge"),
+    ("ui.add_to_dictionary", "Zum Wörterbuch hinzufügen"),
+    ("help.select", "Nachricht auswählen"),
+    ("help.reveal_spoiler", "Spoiler der ausgewählten Nachricht zeigen oder verbergen"),
+    ("ui.spoiler_hint", "(r zum Aufdecken)"),
+    ("help.starred", "Markierte Nachrichten anzeigen"),
+    ("help.star", "Ausgewählte Nachricht markieren"),
+    ("help.report", "Eine Nachricht an die Admins melden"),
+    ("help.pins", "Die in diesem Raum angehefteten Nachrichten auflisten"),
+    ("help.pin", "Die ausgewählte Nachricht anheften (Raummoderatoren)"),
+    ("help.mod_panel", "Moderationsbereich (Admins)"),
+    ("ui.starred", "Markierte Nachrichten"),
+    ("ui.starred_hint", "Enter: springen · Entf: entfernen · Esc: schließen"),
+    ("ui.starred_empty", "Noch nichts markiert. Nachricht mit Alt+Hoch auswählen und s drücken"),
+    ("ui.discover", "Räume"),
+    ("ui.discover_matching", "Räume zu \"{0}\""),
+    ("ui.discover_hint", "Enter: betreten · Esc: schließen"),
+    ("ui.discover_empty", "Kein offener Raum passt. Versuch ein anderes Wort, oder /discover allein für alle"),
+    ("ui.discover_counts", "{0} hier · {1} heute"),
+    ("ui.discover_active", "aktiv vor {0}"),
+    ("ui.discover_quiet", "keine neuen Nachrichten"),
+    ("help.goto", "Per Permalink zu einer Nachricht springen"),
+    ("help.follow_ref", "Zur verlinkten Nachricht der Auswahl springen"),
+    ("help.cite", "Link zur ausgewählten Nachricht einfügen"),
+    ("help.profile", "Profilkarte anzeigen (standardmäßig die eigene)"),
+    ("help.join_code", "Einem Raum nur mit Einladung per Code beitreten"),
+    ("help.invitecode", "Codes für deinen Raum; macht ihn einladungspflichtig"),
+    ("help.profile_set", "display_name, bio, pronouns oder timezone setzen; ohne Wert löschen"),
+    ("ui.pronouns", "Pronomen"),
+    ("ui.timezone", "Zeitzone"),
+    ("ui.stats", "Serverstatistik"),
+    ("ui.stats_uptime", "Laufzeit"),
+    ("ui.stats_clients", "Verbundene Clients"),
+    ("ui.stats_rooms", "Räume"),
+    ("ui.stats_rate", "Nachrichten pro Minute"),
+    ("ui.stats_history", "Gespeicherter Verlauf"),
+    ("ui.stats_queue", "Broadcast-Warteschlange"),
+    ("ui.console", "Serverkonsole"),
+    ("ui.console_connections", "Verbindungen"),
+    ("ui.console_rooms", "Aktive Räume"),
+    ("ui.console_actions", "Letzte Moderation"),
+    ("ui.console_output", "Befehlsausgabe"),
+    ("ui.console_hint", "Enter: Befehl ausführen · Esc: beenden"),
+    ("ui.console_user", "Benutzer"),
+    ("ui.console_room", "Raum"),
+    ("ui.console_address", "Adresse"),
+    ("ui.console_connected", "Verbunden"),
+    ("ui.console_traffic", "Ein / aus"),
+    ("ui.console_users", "Benutzer"),
+    ("ui.console_rate", "Nachr./min"),
+    ("ui.console_elsewhere", "Die Serverkonsole hat einen eigenen Bildschirm: starte client --console <Admin-Name>"),
+    ("ui.mod_panel", "Moderation"),
+    ("ui.mod_members", "Mitglieder"),
+    ("ui.mod_reports", "Meldungen"),
+    ("ui.mod_pins", "Angeheftet"),
+    ("ui.mod_empty", "Nichts da"),
+    ("ui.mod_warnings", "{0} Verwarnungen"),
+    ("ui.mod_muted", "stumm {0}"),
+    ("ui.mod_hint_members", "k rauswerfen · m 10m stumm · u Stumm aufheben · b 1d sperren · w verwarnen · Tab weiter · Esc schließen"),
+    ("ui.mod_hint_reports", "Enter hingehen · d verwerfen · p anheften · k Autor rauswerfen · Tab weiter · Esc schließen"),
+    ("ui.mod_hint_pins", "Enter hingehen · Entf loslösen · Tab weiter · Esc schließen"),
+    ("ui.activity", "Aktivität in #{0}"),
+    ("ui.activity_total", "{0} Nachrichten in den letzten 24 Stunden"),
+    ("ui.activity_busiest", "am meisten gegen {0}"),
+    ("ui.activity_day_ago", "vor 24 Std."),
+    ("ui.activity_now", "jetzt"),
+    ("ui.activity_by_hour", "Nach Tageszeit, über die letzten 7 Tage"),
+    ("ui.profile_empty", "Noch nichts hier"),
+    ("ui.close_hint", "beliebige Taste: schließen"),
+    ("ui.onboarding_title", "Willkommen"),
+    ("ui.onboarding_step", "{0} von {1}"),
+    ("ui.onboarding_display_name", "Welchen Namen sollen andere sehen?"),
+    ("ui.onboarding_room", "Wähle die Räume, mit denen du bei jeder Verbindung startest"),
+    ("ui.onboarding_notifications", "Wann soll der Client dich benachrichtigen?"),
+    ("ui.onboarding_notify_all", "Bei jeder Nachricht"),
+    ("ui.onboarding_notify_mentions", "Erwähnungen und private Nachrichten"),
+    ("ui.onboarding_notify_none", "Nie"),
+    ("ui.form_text_hint", "Enter: weiter · Esc: überspringen"),
+    ("ui.form_choice_hint", "↑↓: wählen · Enter: weiter · Esc: überspringen"),
+    ("ui.form_multiple_hint", "↑↓: wählen · Leertaste: markieren · Enter: weiter · Esc: überspringen"),
+    ("help.alias", "Eigene Befehlskürzel (/unalias zum Entfernen)"),
+    ("help.away", "Als abwesend markieren (/back zum Zurückkehren)"),
+    ("help.totp", "Zwei-Faktor-Anmeldung für dein registriertes Konto"),
+    ("help.quit", "Beenden"),
+    ("help.group", "Benannte Benutzergruppen verwalten"),
+    ("help.roomset", "Einstellungen dieses Raums ändern"),
+    ("help.back", "Nicht mehr abwesend"),
+    ("help.history", "Ältere oder archivierte Nachrichten laden"),
+    ("help.star_id", "Nachricht markieren"),
+    ("help.unstar", "Markierung entfernen"),
+    ("help.pin_id", "Nachricht im Raum anheften"),
+    ("help.unpin", "Nachricht lösen"),
+    ("help.warn", "Benutzer verwarnen"),
+    ("help.warnings", "Verwarnungen anzeigen oder löschen"),
+    ("help.help", "Befehle auflisten"),
+    ("help.kick", "Benutzer trennen"),
+    ("help.mute", "Benutzer zeitweise stummschalten"),
+    ("help.unmute", "Stummschaltung aufheben"),
+    ("help.ban", "Benutzer zeitweise sperren"),
+    ("help.unban", "Sperre aufheben"),
+    ("help.flagged", "Gemeldete Nachrichten prüfen"),
+    ("help.linkhits", "Blockierte Links anzeigen"),
+    ("help.reserve", "Namen oder Muster reservieren"),
+    ("help.unreserve", "Reservierten Namen freigeben"),
+    ("help.mirror", "Raum in einen anderen spiegeln"),
+    ("help.unmirror", "Spiegelung beenden"),
+    ("help.maintenance", "Wartung ankündigen"),
+    ("help.stats", "Serverstatistik anzeigen"),
+    ("help.snapshot", "Zustandsabbild speichern"),
+    ("help.reload", "Konfiguration neu laden"),
+    ("help.audit", "Prüfprotokoll verifizieren"),
+    ("help.scroll", "Verlauf blättern"),
+    ("help.newline", "Neue Zeile in der Nachricht"),
+    ("help.cycle_rooms", "Besuchte Räume durchschalten (Entwürfe bleiben erhalten)"),
+    ("help.switcher", "Schnellwechsel für Räume und Kontakte"),
+    ("help.resize", "Seitenleiste verkleinern/vergrößern"),
+    ("help.zen", "Seitenleiste umschalten (Zen-Modus)"),
+    ("help.user_list", "Benutzerliste umschalten"),
+    ("help.reveal", "Nachrichten ignorierter Benutzer anzeigen"),
+    ("help.toggle_help", "Hilfe umschalten"),
+];
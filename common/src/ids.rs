@@ -0,0 +1,197 @@
+use crate::ProtocolError;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use uuid::Uuid;
+
+// Identifiers carried in messages. Each is checked when it is built or parsed, so a message
+// that deserialized at all has a well-formed ID, room and recipient.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MessageId(Uuid);
+
+impl MessageId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for MessageId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for MessageId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.hyphenated().fmt(f)
+    }
+}
+
+impl FromStr for MessageId {
+    type Err = ProtocolError;
+
+    fn from_str(s: &str) -> Result<Self, ProtocolError> {
+        Uuid::parse_str(s).map(Self).map_err(|_| ProtocolError::InvalidMessageId(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for MessageId {
+    type Error = ProtocolError;
+
+    fn try_from(s: String) -> Result<Self, ProtocolError> {
+        s.parse()
+    }
+}
+
+impl From<MessageId> for String {
+    fn from(id: MessageId) -> Self {
+        id.to_string()
+    }
+}
+
+// Names that read like strings: they deref to `str` and compare with string types
+macro_rules! name_type {
+    ($name:ident, $valid:path, $error:path) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn new(name: impl Into<String>) -> Result<Self, ProtocolError> {
+                let name = name.into();
+                if $valid(&name) {
+                    Ok(Self(name))
+                } else {
+                    Err($error(name))
+                }
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ProtocolError;
+
+            fn from_str(s: &str) -> Result<Self, ProtocolError> {
+                Self::new(s)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = ProtocolError;
+
+            fn try_from(s: String) -> Result<Self, ProtocolError> {
+                Self::new(s)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(name: $name) -> Self {
+                name.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                &self.0 == other
+            }
+        }
+
+        impl PartialEq<$name> for str {
+            fn eq(&self, other: &$name) -> bool {
+                self == other.0
+            }
+        }
+
+        impl PartialEq<$name> for &str {
+            fn eq(&self, other: &$name) -> bool {
+                *self == other.0
+            }
+        }
+
+        impl PartialEq<$name> for String {
+            fn eq(&self, other: &$name) -> bool {
+                *self == other.0
+            }
+        }
+    };
+}
+
+name_type!(RoomName, is_valid_room_name, ProtocolError::InvalidRoomName);
+name_type!(Username, is_valid_username, ProtocolError::InvalidUsername);
+
+// Characters the protocol and commands give a meaning around names: `,` separates user lists and
+// message IDs, `#` marks rooms and `@` users, and a control character could steer a terminal
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c.is_control() || matches!(c, ',' | '#' | '@')
+}
+
+// Room names are `/`-separated paths without empty segments
+pub fn is_valid_room_name(room: &str) -> bool {
+    room.len() <= 64 && !room.contains(is_delimiter) && room.split('/').all(|segment| !segment.is_empty())
+}
+
+// Usernames also can't hold `/`, so one never reads as a command or a room path
+pub fn is_valid_username(username: &str) -> bool {
+    !username.is_empty() && !username.contains(|c| is_delimiter(c) || c == '/') && username.len() <= 32
+}
+
+impl RoomName {
+    // Where every client starts
+    pub fn general() -> Self {
+        Self("general".to_string())
+    }
+
+    // Where private messages and errors are filed; not rooms anyone can join
+    pub fn private() -> Self {
+        Self("private".to_string())
+    }
+
+    pub fn global() -> Self {
+        Self("global".to_string())
+    }
+}
@@ -1,5 +1,25 @@
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
+use std::collections::BTreeMap;
+
+pub use error::{ChatError, CommandError, ProtocolError};
+pub use ids::{is_valid_room_name, is_valid_username, MessageId, RoomName, Username};
+
+pub mod deflate;
+pub mod error;
+pub mod i18n;
+pub mod ids;
+pub mod msgpack;
+pub mod pattern;
+pub mod recording;
+
+// Newlines inside a message are sent as U+2028 so the line-based protocol
+// still carries one message per line.
+pub const LINE_SEPARATOR: char = '\u{2028}';
+
+// The protocol only grows: new fields are optional with a default, unknown fields are
+// ignored, and message types this build doesn't know parse as `Unknown`. The fixtures in
+// `common/tests/fixtures` are messages as released versions sent them and must keep parsing.
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum MessageType {
@@ -10,56 +30,617 @@ pub enum MessageType {
     PrivateMessage,
     RoomChange,
     Error,
+    Pong,     // Reply to `/ping <token>`, content echoes the token
+    UserList, // Comma-separated members of `room`, sent after joining it
+    ReadReceipt, // `username` has read up to message `content` in `room` (or a PM when room is "private")
+    Presence,    // `username` is away with reason `content`, or back online when it is empty
+    AuthRequired, // Handshake reply: resend it with `content` ("password", "totp" or "register") filled in, or for "captcha: <question>" the answer
+    Starred,      // Reply to `/starred`: `content` is a JSON array of the saved messages, newest first
+    Profile,      // Reply to `/profile <user>`: `content` is that user's `UserProfile` as JSON
+    RoomList,     // Reply to `/rooms` and sent after joining: `content` is a JSON array of `RoomEntry`
+    Expired,      // `content` is the comma-separated IDs of messages in `room` removed by its time-to-live
+    Stats,        // Reply to `/stats`: `content` is a `ServerStats` as JSON
+    Compression,  // Handshake reply: everything after this line is framed and compressed with `content`, e.g. "deflate"
+    Capabilities, // Sent once after joining: `content` is a `ServerCapabilities` as JSON
+    Encoding,     // Handshake reply: every message after this one is a framed `content` document, e.g. "msgpack"
+    Preview,      // `content` is a `PreviewUpdate` as JSON: a preview found for a message already sent in `room`
+    Console,      // Reply to `/console`: `content` is a `ConsoleState` as JSON
+    Moderation,   // Reply to `/modpanel`: `content` is a `ModerationState` as JSON for `room`
+    Activity,     // Reply to `/activity [room]`: `content` is an `ActivityReport` as JSON
+    Karma,        // Scores in a room with karma on: `content` is a `KarmaScores` as JSON
+    Dice,         // `username` rolled dice, flipped a coin or had the server choose, with the result in `content`
+    AutoJoin,     // Sent after joining and on `/autojoin` changes: `content` is a JSON array of the rooms the user starts with
+    Discover,     // Reply to `/discover [keyword]`: `content` is a `Discovery` as JSON
+    Prompt,       // A question for a form: `content` is a `Prompt` as JSON, answered with `/answer <id> [value]`
+    Commands,     // Sent after joining: `content` is a JSON array of the `CommandInfo` this user may use
+    #[serde(other)]
+    Unknown, // Added by a newer server; clients skip it
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
-    pub id: String,
+    pub id: MessageId,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub content: String,
-    pub room: String,
+    pub room: RoomName,
     pub timestamp: DateTime<Utc>,
     pub msg_type: MessageType,
-    pub recipient: Option<String>,
+    #[serde(default)]
+    pub recipient: Option<Username>,
+    // Server-generated text that recipients may render in their own locale;
+    // `content` always carries the English rendering for older clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<Template>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwarded: Option<Forwarded>,
+    // The author's display name at the time of sending; `username` still identifies them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    // Position in the room's history, assigned by the server when stored; see `reference`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    // Set on copies delivered through a room mirror: the room the message was posted in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirrored_from: Option<RoomName>,
+    // Members of the groups `@mentioned` in `content`, filled in by the server; clients treat
+    // being listed like being mentioned by name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<Username>,
+    // What the first link in `content` points to; filled in after the message was sent, and
+    // carried by it from then on, e.g. in history replays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<LinkPreview>,
+    // Set by the server when a link in `content` is on a warn list or matched by its lookup
+    // service; clients show it beside the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_warning: Option<LinkWarning>,
+    // Why an error happened, for clients that show it beside the input rather than as a line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<ErrorReason>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkWarning {
+    pub url: String,
+    pub threat: String, // e.g. "phishing", "malware", or "suspicious" for the warn list
+}
+
+// A page's title, or for other files their type and size, as fetched by the server
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct LinkPreview {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl LinkPreview {
+    // "Title · example.com", or "image/png, 1.2 MiB · example.com" for files
+    pub fn summary(&self) -> String {
+        let host = self.url.split("://").nth(1).unwrap_or(&self.url).split(['/', '?', '#']).next().unwrap_or_default();
+        let described = match (&self.title, &self.content_type, self.size) {
+            (Some(title), _, _) => title.clone(),
+            (None, Some(content_type), Some(size)) => format!("{}, {}", content_type, format_bytes(size)),
+            (None, Some(content_type), None) => content_type.clone(),
+            (None, None, Some(size)) => format_bytes(size),
+            (None, None, None) => return host.to_string(),
+        };
+        format!("{} · {}", described, host)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PreviewUpdate {
+    pub message: MessageId,
+    pub preview: LinkPreview,
+}
+
+// Where a forwarded message first appeared; kept from the original when forwarded again
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Forwarded {
+    pub username: String,
+    #[serde(default)]
+    pub room: Option<RoomName>, // None for a private message
+}
+
+impl Forwarded {
+    // "#room / @user" for room messages, "@user" for private ones
+    pub fn label(&self) -> String {
+        match &self.room {
+            Some(room) => format!("#{} / @{}", room, self.username),
+            None => format!("@{}", self.username),
+        }
+    }
+}
+
+// Self-described details shown on a user's profile card; every field is optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct UserProfile {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub timezone: Option<String>, // As entered, e.g. "Europe/Berlin" or "UTC+2"
+}
+
+impl UserProfile {
+    pub const FIELDS: [&'static str; 4] = ["display_name", "bio", "pronouns", "timezone"];
+
+    // Longest value accepted for each field, in characters
+    pub fn max_len(field: &str) -> usize {
+        match field {
+            "bio" => 200,
+            "display_name" => 32,
+            _ => 40,
+        }
+    }
+
+    pub fn field_mut(&mut self, field: &str) -> Option<&mut Option<String>> {
+        match field {
+            "display_name" => Some(&mut self.display_name),
+            "bio" => Some(&mut self.bio),
+            "pronouns" => Some(&mut self.pronouns),
+            "timezone" => Some(&mut self.timezone),
+            _ => None,
+        }
+    }
+}
+
+// A room in a `RoomList`; names may be namespaced into categories with `/`, e.g. "work/standup"
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoomEntry {
+    pub name: String,
+    #[serde(default)]
+    pub users: usize,
+    #[serde(default)]
+    pub invite_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>, // Messages disappear after this long, e.g. "24h"
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool, // The user may read but not post there
+}
+
+impl RoomEntry {
+    // The part after the last `/`
+    pub fn short_name(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or_default()
+    }
+}
+
+// Reply to `/stats`, for admins
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ServerStats {
+    pub uptime_secs: u64,
+    pub clients: usize,
+    pub rooms: usize,
+    pub messages_per_minute: usize, // Posted in the last minute, not counting mirrored copies
+    pub history_messages: usize,
+    pub history_bytes: usize,   // Approximate: the text held in stored messages
+    pub broadcast_depth: usize, // Messages queued for the slowest client
+}
+
+// Reply to `/console`, for admins: what the server is doing right now
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ConsoleState {
+    pub stats: ServerStats,
+    pub connections: Vec<ConsoleConnection>, // Oldest first
+    pub rooms: Vec<RoomActivity>,            // Rooms with someone in them or posted in lately, busiest first
+    pub actions: Vec<String>,                // Newest audit log entries, oldest first
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ConsoleConnection {
+    pub username: String,
+    pub room: String,
+    pub addr: String,
+    pub since: Option<DateTime<Utc>>,
+    pub admin: bool,
+    pub guest: bool,
+    pub away: Option<String>,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+// Reply to `/activity [room]`: chat messages posted in a room, counted per hour
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ActivityReport {
+    pub room: String,
+    pub until: Option<DateTime<Utc>>, // End of the current hour, which the last counts include so far
+    pub last_day: Vec<u64>,           // The last 24 hours, oldest first
+    pub by_hour: Vec<u64>,            // By hour of the day in UTC, 0 to 23, over the last 7 days
+}
+
+impl ActivityReport {
+    // The UTC hour of the day with the most messages; the earliest one on a tie
+    pub fn busiest_hour(&self) -> Option<usize> {
+        let (hour, count) = self.by_hour.iter().enumerate().max_by_key(|(hour, count)| (**count, std::cmp::Reverse(*hour)))?;
+        (*count > 0).then_some(hour)
+    }
+}
+
+// Karma in `room`: all of it on entering the room and when karma is turned on or off, then each change
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct KarmaScores {
+    pub scores: BTreeMap<String, u64>,
+    pub replace: bool, // These are all the room's scores, rather than changes to them
+}
+
+// One entry of the server's command registry
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CommandInfo {
+    pub name: String, // e.g. "/msg"
+    pub args: String, // e.g. "<user> <text>"; empty when it takes none
+    pub help: String, // Catalog key of a one-line description, e.g. "help.msg"
+}
+
+// Reply to `/discover [keyword]`: rooms anyone may join whose name, tags or description
+// match, busiest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Discovery {
+    pub query: String, // Empty for every room
+    pub rooms: Vec<DiscoveredRoom>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DiscoveredRoom {
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub users: usize,
+    pub messages_today: u64, // Chat messages in the last 24 hours
+    pub last_active: Option<DateTime<Utc>>, // Newest message still in history
+}
+
+// One step of a server-driven form, such as onboarding a new user. `title` and `text` are
+// catalog keys, for clients to show in their own locale; an empty answer skips the step.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Prompt {
+    pub id: String, // What `/answer` names, e.g. "display_name"
+    pub step: usize, // From 1
+    pub steps: usize,
+    pub title: String,
+    pub text: String,
+    pub field: PromptField,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PromptField {
+    Text {
+        #[serde(default)]
+        value: String, // Filled in to start with
+        max_len: usize,
+    },
+    Choice {
+        options: Vec<PromptOption>,
+        #[serde(default)]
+        selected: Vec<String>,
+        #[serde(default)]
+        multiple: bool, // Answered with the values joined by spaces
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptOption {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>, // A catalog key; the value itself is shown without one
+}
+
+// Reply to `/modpanel`, for admins: who is in the room, what was reported, and what is pinned there
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModerationState {
+    pub members: Vec<ModerationMember>,
+    pub reports: Vec<Report>,     // Server-wide, oldest first
+    pub pins: Vec<ChatMessage>,   // Newest first
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ModerationMember {
+    pub username: String,
+    pub admin: bool,
+    pub warnings: usize,
+    pub muted_secs: Option<u64>, // What is left of a mute
+}
+
+// A message someone reported with `/report`, or that moderation flagged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub message: ChatMessage,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RoomActivity {
+    pub room: String,
+    pub users: usize,
+    pub messages_per_minute: usize,
+}
+
+// What a server offers, so clients only show what will work there. Clients treat a server
+// that never sent one as supporting everything, as servers did before this existed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ServerCapabilities {
+    pub version: String,
+    pub features: Vec<String>, // Names below; unknown ones are for newer clients
+    pub max_message_len: usize, // In characters, as `check_content` counts them
+    pub max_line_bytes: usize,  // Longest line the server reads
+    pub history_limit: usize,   // Messages replayed on joining a room
+    // This user may kick, mute and ban, so the client offers its moderation panel. Left out
+    // when false, so capabilities read the same as before it existed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub moderator: bool,
+}
+
+impl ServerCapabilities {
+    pub const STARS: &'static str = "stars";
+    pub const FORWARD: &'static str = "forward";
+    pub const READ_RECEIPTS: &'static str = "read_receipts";
+    pub const PRESENCE: &'static str = "presence";
+    pub const PROFILES: &'static str = "profiles";
+    pub const HISTORY: &'static str = "history"; // `/history` around a message
+    pub const MIRRORS: &'static str = "mirrors";
+    pub const TTL: &'static str = "ttl";
+    pub const INVITE_CODES: &'static str = "invite_codes";
+    pub const TOTP: &'static str = "totp";
+    pub const GUESTS: &'static str = "guests";
+    pub const GROUPS: &'static str = "groups";
+    pub const PERMISSIONS: &'static str = "permissions"; // `/roomset`
+    pub const MODERATION: &'static str = "moderation"; // `/report`, `/pin` and `/pins`; `/modpanel` for moderators
+    pub const ACTIVITY: &'static str = "activity"; // `/activity`
+    pub const KARMA: &'static str = "karma"; // `++`, `/thank` and `/leaderboard` in rooms that turn it on
+    pub const GAMES: &'static str = "games"; // `/trivia`
+    pub const DICE: &'static str = "dice"; // `/roll`, `/flip` and `/choose`
+    pub const TIME: &'static str = "time"; // `/time`
+    pub const TRANSLATE: &'static str = "translate"; // `/translate`, when the server has a translation service
+    pub const DISCOVER: &'static str = "discover"; // `/discover`, and `/roomset description|tags`
+    pub const AUTO_JOIN: &'static str = "auto_join"; // `/autojoin`
+    pub const ONBOARDING: &'static str = "onboarding"; // `Prompt` messages for new users, and `/answer`
+    pub const COMMANDS: &'static str = "commands"; // A `Commands` registry after joining
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+// One line of the grouped room list
+pub enum RoomRow<'a> {
+    Category { path: &'a str, depth: usize }, // e.g. "work" or "work/eng"
+    Room { entry: &'a RoomEntry, depth: usize },
+}
+
+// Rooms ordered by category, with a header row where each category begins
+pub fn room_tree(entries: &[RoomEntry]) -> Vec<RoomRow<'_>> {
+    let mut sorted: Vec<&RoomEntry> = entries.iter().collect();
+    sorted.sort_by(|a, b| a.name.split('/').cmp(b.name.split('/')));
+    let mut rows = Vec::new();
+    let mut open: Vec<&str> = Vec::new(); // Segments of the category of the previous room
+    for entry in sorted {
+        let segments: Vec<&str> = entry.name.split('/').collect();
+        let category = &segments[..segments.len() - 1];
+        let shared = open.iter().zip(category).take_while(|(a, b)| a == b).count();
+        for depth in shared..category.len() {
+            // Category paths are prefixes of the room name, so they can borrow from it
+            let len = category[..=depth].iter().map(|s| s.len()).sum::<usize>() + depth;
+            rows.push(RoomRow::Category { path: &entry.name[..len], depth });
+        }
+        open = category.to_vec();
+        rows.push(RoomRow::Room { entry, depth: category.len() });
+    }
+    rows
+}
+
+// Longest chat or private message accepted, in characters
+pub const MAX_CONTENT_LEN: usize = 4000;
+
+pub fn check_content(content: &str) -> Result<(), ProtocolError> {
+    if content.chars().count() > MAX_CONTENT_LEN {
+        return Err(ProtocolError::MessageTooLong(MAX_CONTENT_LEN));
+    }
+    Ok(())
+}
+
+// A profile timezone such as "UTC+2", "+05:30", "-3" or "UTC" as an offset; named zones
+// can't be resolved without a tz database
+pub fn parse_utc_offset(timezone: &str) -> Option<FixedOffset> {
+    let offset = timezone.trim().trim_start_matches("UTC").trim_start_matches("GMT");
+    let (sign, rest) = match offset.chars().next() {
+        None => return FixedOffset::east_opt(0),
+        Some('+') => (1, &offset[1..]),
+        Some('-') => (-1, &offset[1..]),
+        Some(_) => return None,
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+    FixedOffset::east_opt(sign * seconds)
+}
+
+// "3d 4h", "2h 5m" or "42s"
+pub fn format_elapsed(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+// A name made safe to use as one file or directory name. Letters, digits, `-` and `_` pass as
+// they are and every other byte is percent-encoded, so different names never share a file
+pub fn encode_file_name(name: &str) -> String {
+    name.bytes()
+        .map(|b| if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' { (b as char).to_string() } else { format!("%{:02X}", b) })
+        .collect()
+}
+
+// "512 B", "3.2 KiB" or "1.5 MiB"
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KiB", b as f64 / (1 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
+
+// Whether `content` names `username` as a whole word, with or without an `@`
+pub fn mentions(content: &str, username: &str) -> bool {
+    let username = username.to_lowercase();
+    content
+        .to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .any(|word| word == username)
+}
+
+// Splits a `#room/seq` permalink (the leading `#` is optional)
+pub fn parse_reference(reference: &str) -> Option<(&str, u64)> {
+    let (room, seq) = reference.trim_start_matches('#').rsplit_once('/')?;
+    let seq = seq.parse().ok()?;
+    (!room.is_empty()).then_some((room, seq))
+}
+
+// The machine-readable side of an error about something the user tried to send
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ErrorReason {
+    RateLimited { retry_after: u64 }, // Seconds until a message would be accepted
+    TooLong { limit: usize },         // In characters, as `check_content` counts them
+    Muted { until: DateTime<Utc> },
+    Usage(CommandError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Template {
+    pub key: String,
+    #[serde(default)]
+    pub args: Vec<String>,
 }
 
 impl ChatMessage {
-    pub fn new(username: String, content: String, room: String, msg_type: MessageType) -> Self {
+    pub fn new(username: String, content: String, room: RoomName, msg_type: MessageType) -> Self {
         Self {
-            id: uuid::Uuid::new_v4().to_string(),
+            id: MessageId::new(),
             username,
             content,
             room,
             timestamp: Utc::now(),
             msg_type,
             recipient: None,
+            template: None,
+            forwarded: None,
+            display_name: None,
+            seq: None,
+            mirrored_from: None,
+            mentions: Vec::new(),
+            preview: None,
+            link_warning: None,
+            reason: None,
         }
     }
 
-    pub fn chat(username: String, content: String, room: String) -> Self {
+    pub fn chat(username: String, content: String, room: RoomName) -> Self {
         Self::new(username, content, room, MessageType::Chat)
     }
 
-    pub fn system(content: String, room: String) -> Self {
+    pub fn system(content: String, room: RoomName) -> Self {
         Self::new("System".to_string(), content, room, MessageType::System)
     }
 
-    pub fn private(username: String, recipient: String, content: String) -> Self {
-        let mut msg = Self::new(username, content, "private".to_string(), MessageType::PrivateMessage);
+    pub fn private(username: String, recipient: Username, content: String) -> Self {
+        let mut msg = Self::new(username, content, RoomName::private(), MessageType::PrivateMessage);
         msg.recipient = Some(recipient);
         msg
     }
 
     pub fn error(content: String) -> Self {
-        Self::new("Error".to_string(), content, "global".to_string(), MessageType::Error)
+        Self::new("Error".to_string(), content, RoomName::global(), MessageType::Error)
+    }
+
+    pub fn with_reason(mut self, reason: ErrorReason) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn with_template(mut self, key: &str, args: &[&str]) -> Self {
+        self.content = i18n::trf(i18n::DEFAULT_LOCALE, key, args);
+        self.template = Some(Template {
+            key: key.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        });
+        self
+    }
+
+    pub fn with_forwarded(mut self, forwarded: Forwarded) -> Self {
+        self.forwarded = Some(forwarded);
+        self
+    }
+
+    // What to show as the author: the display name if they set one
+    pub fn sender_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
     }
 
-    pub fn to_json(&self) -> String {
-        serde_json::to_string(self).unwrap()
+    // Permalink like `#general/42`, for stored room messages only
+    pub fn reference(&self) -> Option<String> {
+        self.seq.map(|seq| format!("#{}/{}", self.room, seq))
     }
 
-    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json)
+    // Provenance for forwarding this message on
+    pub fn origin(&self) -> Forwarded {
+        self.forwarded.clone().unwrap_or_else(|| Forwarded {
+            username: self.username.clone(),
+            room: (self.msg_type != MessageType::PrivateMessage).then(|| self.room.clone()),
+        })
+    }
+
+    // Copy of this message with templated content rendered for `locale`
+    pub fn localized(&self, locale: &str) -> Self {
+        let mut msg = self.clone();
+        if let Some(template) = &self.template {
+            let args: Vec<&str> = template.args.iter().map(String::as_str).collect();
+            msg.content = i18n::trf(locale, &template.key, &args);
+        }
+        msg
+    }
+
+    pub fn to_json(&self) -> Result<String, ChatError> {
+        serde_json::to_string(self).map_err(ChatError::Encode)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ChatError> {
+        serde_json::from_str(json).map_err(ChatError::Decode)
+    }
+
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, ChatError> {
+        let mut bytes = Vec::new();
+        msgpack::write(&serde_json::to_value(self).map_err(ChatError::Encode)?, &mut bytes);
+        Ok(bytes)
+    }
+
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, ChatError> {
+        serde_json::from_value(msgpack::read(bytes)?).map_err(ChatError::Decode)
+    }
+
+    pub fn content_lines(&self) -> impl Iterator<Item = &str> {
+        self.content.split(LINE_SEPARATOR)
     }
 
     pub fn format_time(&self) -> String {
@@ -68,7 +649,30 @@ impl ChatMessage {
 }
 
 // Request struct for initial connection/handshake
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Handshake {
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
+    pub locale: Option<String>, // e.g. "es"; server text falls back to English
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub totp: Option<String>, // Current authenticator code for accounts with two-factor login
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captcha: Option<String>, // The answer to a "captcha" challenge
+    #[serde(default)]
+    pub register: bool, // Claim an unregistered username with `password`
+    #[serde(default)]
+    pub guest: bool, // Ask for a temporary guest name instead; `username` is ignored
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub reconnects: u32, // Reconnect attempts so far this session, for `/conninfo`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compression: Vec<String>, // What the client can decompress, preferred first; see `deflate`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encoding: Vec<String>, // Formats the client reads besides JSON lines, preferred first; see `msgpack`
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
 }
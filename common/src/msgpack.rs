@@ -0,0 +1,240 @@
+use crate::ChatError;
+use serde_json::{Map, Number, Value};
+
+// MessagePack for connections that negotiate it: the same document a message serializes to
+// as JSON, written in the binary format. Each message goes out framed like a compressed
+// chunk (see `deflate::frame`), so readers know where it ends without parsing it.
+//
+// Only the types JSON has are written or read; binary, extension and timestamp values are
+// refused as malformed.
+
+pub const NAME: &str = "msgpack"; // As offered in `Handshake::encoding`
+
+const MAX_DEPTH: usize = 64; // Deeper nesting is refused rather than recursed into
+
+pub fn write(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(b) => out.push(if *b { 0xc3 } else { 0xc2 }),
+        Value::Number(n) => {
+            if let Some(n) = n.as_u64() {
+                write_uint(n, out);
+            } else if let Some(n) = n.as_i64() {
+                write_int(n, out);
+            } else {
+                out.push(0xcb);
+                out.extend_from_slice(&n.as_f64().unwrap_or_default().to_be_bytes());
+            }
+        }
+        Value::String(s) => write_str(s, out),
+        Value::Array(items) => {
+            write_len(items.len(), 0x90, 0xdc, out);
+            for item in items {
+                write(item, out);
+            }
+        }
+        Value::Object(map) => {
+            write_len(map.len(), 0x80, 0xde, out);
+            for (key, value) in map {
+                write_str(key, out);
+                write(value, out);
+            }
+        }
+    }
+}
+
+fn write_uint(n: u64, out: &mut Vec<u8>) {
+    match n {
+        0..=0x7f => out.push(n as u8),
+        0x80..=0xff => out.extend_from_slice(&[0xcc, n as u8]),
+        0x100..=0xffff => {
+            out.push(0xcd);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xce);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xcf);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+// Only called for negative numbers; the rest are written unsigned
+fn write_int(n: i64, out: &mut Vec<u8>) {
+    match n {
+        -32..=-1 => out.push(n as u8),
+        -0x80..=-33 => out.extend_from_slice(&[0xd0, n as u8]),
+        -0x8000..=-0x81 => {
+            out.push(0xd1);
+            out.extend_from_slice(&(n as i16).to_be_bytes());
+        }
+        -0x8000_0000..=-0x8001 => {
+            out.push(0xd2);
+            out.extend_from_slice(&(n as i32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xd3);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    match s.len() {
+        len @ 0..=31 => out.push(0xa0 | len as u8),
+        len @ 32..=0xff => out.extend_from_slice(&[0xd9, len as u8]),
+        len @ 0x100..=0xffff => {
+            out.push(0xda);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(0xdb);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(s.as_bytes());
+}
+
+// Arrays and maps: a fix type below 16 entries, else the 16- or 32-bit one after `wide`
+fn write_len(len: usize, fix: u8, wide: u8, out: &mut Vec<u8>) {
+    match len {
+        0..=15 => out.push(fix | len as u8),
+        16..=0xffff => {
+            out.push(wide);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(wide + 1);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+// Exactly one value, with nothing after it
+pub fn read(bytes: &[u8]) -> Result<Value, ChatError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let value = reader.value(0)?;
+    if reader.pos != bytes.len() {
+        return Err(ChatError::MessagePack("trailing bytes"));
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ChatError> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len()).ok_or(ChatError::MessagePack("unexpected end"))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ChatError> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, ChatError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<usize, ChatError> {
+        Ok(u16::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn u32(&mut self) -> Result<usize, ChatError> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, ChatError> {
+        if depth > MAX_DEPTH {
+            return Err(ChatError::MessagePack("nested too deeply"));
+        }
+        let marker = self.u8()?;
+        Ok(match marker {
+            0x00..=0x7f => Value::from(marker),
+            0x80..=0x8f => self.map(marker as usize & 0x0f, depth)?,
+            0x90..=0x9f => self.seq(marker as usize & 0x0f, depth)?,
+            0xa0..=0xbf => self.str(marker as usize & 0x1f)?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xca => float(f32::from_be_bytes(self.array()?) as f64)?,
+            0xcb => float(f64::from_be_bytes(self.array()?))?,
+            0xcc => Value::from(self.u8()?),
+            0xcd => Value::from(u16::from_be_bytes(self.array()?)),
+            0xce => Value::from(u32::from_be_bytes(self.array()?)),
+            0xcf => Value::from(u64::from_be_bytes(self.array()?)),
+            0xd0 => Value::from(i8::from_be_bytes(self.array()?)),
+            0xd1 => Value::from(i16::from_be_bytes(self.array()?)),
+            0xd2 => Value::from(i32::from_be_bytes(self.array()?)),
+            0xd3 => Value::from(i64::from_be_bytes(self.array()?)),
+            0xd9 => {
+                let len = self.u8()? as usize;
+                self.str(len)?
+            }
+            0xda => {
+                let len = self.u16()?;
+                self.str(len)?
+            }
+            0xdb => {
+                let len = self.u32()?;
+                self.str(len)?
+            }
+            0xdc => {
+                let len = self.u16()?;
+                self.seq(len, depth)?
+            }
+            0xdd => {
+                let len = self.u32()?;
+                self.seq(len, depth)?
+            }
+            0xde => {
+                let len = self.u16()?;
+                self.map(len, depth)?
+            }
+            0xdf => {
+                let len = self.u32()?;
+                self.map(len, depth)?
+            }
+            0xe0..=0xff => Value::from(marker as i8),
+            _ => return Err(ChatError::MessagePack("unsupported type")),
+        })
+    }
+
+    fn str(&mut self, len: usize) -> Result<Value, ChatError> {
+        let bytes = self.take(len)?;
+        let s = std::str::from_utf8(bytes).map_err(|_| ChatError::MessagePack("string is not UTF-8"))?;
+        Ok(Value::String(s.to_string()))
+    }
+
+    // Lengths aren't trusted for preallocation: each entry takes at least a byte
+    fn seq(&mut self, len: usize, depth: usize) -> Result<Value, ChatError> {
+        let mut items = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+        for _ in 0..len {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> Result<Value, ChatError> {
+        let mut map = Map::new();
+        for _ in 0..len {
+            let Value::String(key) = self.value(depth + 1)? else {
+                return Err(ChatError::MessagePack("map key is not a string"));
+            };
+            map.insert(key, self.value(depth + 1)?);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+fn float(f: f64) -> Result<Value, ChatError> {
+    Number::from_f64(f).map(Value::Number).ok_or(ChatError::MessagePack("number is not finite"))
+}
@@ -0,0 +1,449 @@
+// A small backtracking regex engine for user-configured rules (highlights,
+// filters, triggers). Supports literals, `.`, classes (`[a-z]`, `[^...]`,
+// `\d \w \s` and their negations), anchors `^ $ \b`, groups with `|`,
+// greedy/lazy `* + ? {n,m}` and a leading `(?i)` for case-insensitivity.
+// Intended for short patterns matched against chat-sized text.
+
+use std::cell::Cell;
+use thiserror::Error;
+
+// Backtracking steps one search may take. Patterns like `(a*)*b` take exponential time, and
+// rules run on text from other users, so a search over budget fails closed: no match
+const MAX_STEPS: usize = 200_000;
+// Nested calls one search may make, so long texts can't overflow the stack either. Runs of
+// single characters (`.*`, `\w+`) take one call; each repeat of a group takes several
+const MAX_DEPTH: usize = 1000;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PatternError {
+    #[error("unexpected end of pattern")]
+    UnexpectedEnd,
+    #[error("unbalanced parenthesis at {0}")]
+    UnbalancedParen(usize),
+    #[error("nothing to repeat at {0}")]
+    NothingToRepeat(usize),
+    #[error("invalid repetition at {0}")]
+    InvalidRepeat(usize),
+    #[error("invalid class range at {0}")]
+    InvalidRange(usize),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class { items: Vec<ClassItem>, negated: bool },
+    Start,
+    End,
+    WordBoundary,
+    Group(Vec<Vec<Node>>), // Alternatives, each a sequence
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool), // bool: negated
+    Word(bool),
+    Space(bool),
+}
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    alternatives: Vec<Vec<Node>>,
+    case_insensitive: bool,
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Self, PatternError> {
+        let (case_insensitive, body) = match source.strip_prefix("(?i)") {
+            Some(rest) => (true, rest),
+            None => (false, source),
+        };
+        let mut parser = Parser { chars: body.chars().collect(), pos: 0 };
+        let alternatives = parser.parse_alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(PatternError::UnbalancedParen(parser.pos));
+        }
+        Ok(Self { source: source.to_string(), alternatives, case_insensitive })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    // Byte range of the leftmost match
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        self.find_iter_limited(text, 1).pop()
+    }
+
+    // Byte ranges of all non-overlapping matches
+    pub fn find_iter(&self, text: &str) -> Vec<(usize, usize)> {
+        self.find_iter_limited(text, usize::MAX)
+    }
+
+    // One budget covers the whole text, however many matches are taken from it
+    fn find_iter_limited(&self, text: &str, limit: usize) -> Vec<(usize, usize)> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let offset = |i: usize| chars.get(i).map_or(text.len(), |(b, _)| *b);
+        let matcher = Matcher { chars: &chars, case_insensitive: self.case_insensitive, steps: Cell::new(0), depth: Cell::new(0) };
+        let mut matches = Vec::new();
+        let mut from = 0;
+        while matches.len() < limit {
+            let Some((start, end)) = self.find_from(&matcher, from) else { break };
+            matches.push((offset(start), offset(end)));
+            from = if end > start { end } else { end + 1 };
+            if from > chars.len() {
+                break;
+            }
+        }
+        matches
+    }
+
+    // Character positions of the leftmost match at or after `from`
+    fn find_from(&self, matcher: &Matcher, from: usize) -> Option<(usize, usize)> {
+        for start in from..=matcher.chars.len() {
+            let mut end = None;
+            let found = self.alternatives.iter().any(|seq| {
+                matcher.match_seq(seq, start, &mut |p| {
+                    end = Some(p);
+                    true
+                })
+            });
+            if found {
+                return end.map(|e| (start, e));
+            }
+            if matcher.steps.get() > MAX_STEPS {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<char, PatternError> {
+        let c = self.peek().ok_or(PatternError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn parse_alternation(&mut self) -> Result<Vec<Vec<Node>>, PatternError> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn parse_sequence(&mut self) -> Result<Vec<Node>, PatternError> {
+        let mut seq = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let start = self.pos;
+            let atom = self.parse_atom()?;
+            seq.push(self.parse_quantifier(atom, start)?);
+        }
+        Ok(seq)
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, PatternError> {
+        let start = self.pos;
+        match self.next()? {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '(' => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let alternatives = self.parse_alternation()?;
+                if self.peek() != Some(')') {
+                    return Err(PatternError::UnbalancedParen(start));
+                }
+                self.pos += 1;
+                Ok(Node::Group(alternatives))
+            }
+            ')' => Err(PatternError::UnbalancedParen(start)),
+            '*' | '+' | '?' => Err(PatternError::NothingToRepeat(start)),
+            '[' => self.parse_class(),
+            '\\' => match self.next()? {
+                'b' => Ok(Node::WordBoundary),
+                c => Ok(match escape_class(c) {
+                    Some(item) => Node::Class { items: vec![item], negated: false },
+                    None => Node::Char(escape_char(c)),
+                }),
+            },
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Node, PatternError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let start = self.pos;
+            let c = self.next()?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = if c == '\\' {
+                let escaped = self.next()?;
+                if let Some(item) = escape_class(escaped) {
+                    items.push(item);
+                    continue;
+                }
+                escape_char(escaped)
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let high = match self.next()? {
+                    '\\' => escape_char(self.next()?),
+                    c => c,
+                };
+                if high < low {
+                    return Err(PatternError::InvalidRange(start));
+                }
+                items.push(ClassItem::Range(low, high));
+            } else {
+                items.push(ClassItem::Range(low, low));
+            }
+        }
+        Ok(Node::Class { items, negated })
+    }
+
+    fn parse_quantifier(&mut self, atom: Node, start: usize) -> Result<Node, PatternError> {
+        let (min, max) = match self.peek() {
+            Some(c @ ('*' | '+' | '?')) => {
+                self.pos += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            Some('{') => match self.parse_braces() {
+                Some(bounds) => bounds,
+                None => return Ok(atom), // Not a repetition, e.g. a literal "{"
+            },
+            _ => return Ok(atom),
+        };
+        if matches!(atom, Node::Start | Node::End | Node::WordBoundary) {
+            return Err(PatternError::NothingToRepeat(start));
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(PatternError::InvalidRepeat(start));
+        }
+        let greedy = self.peek() != Some('?');
+        if !greedy {
+            self.pos += 1;
+        }
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    // Parses `{n}`, `{n,}` or `{n,m}`, consuming it only if well-formed
+    fn parse_braces(&mut self) -> Option<(usize, Option<usize>)> {
+        let close = self.chars[self.pos..].iter().position(|c| *c == '}')? + self.pos;
+        let inner: String = self.chars[self.pos + 1..close].iter().collect();
+        let bounds = match inner.split_once(',') {
+            None => {
+                let n = inner.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        self.pos = close + 1;
+        Some(bounds)
+    }
+}
+
+fn escape_class(c: char) -> Option<ClassItem> {
+    match c {
+        'd' => Some(ClassItem::Digit(false)),
+        'D' => Some(ClassItem::Digit(true)),
+        'w' => Some(ClassItem::Word(false)),
+        'W' => Some(ClassItem::Word(true)),
+        's' => Some(ClassItem::Space(false)),
+        'S' => Some(ClassItem::Space(true)),
+        _ => None,
+    }
+}
+
+fn escape_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct Matcher<'a> {
+    chars: &'a [(usize, char)],
+    case_insensitive: bool,
+    steps: Cell<usize>,
+    depth: Cell<usize>,
+}
+
+impl Matcher<'_> {
+    fn char_at(&self, pos: usize) -> Option<char> {
+        self.chars.get(pos).map(|(_, c)| *c)
+    }
+
+    fn eq(&self, a: char, b: char) -> bool {
+        a == b || (self.case_insensitive && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    fn class_matches(&self, items: &[ClassItem], c: char) -> bool {
+        let test = |c: char| {
+            items.iter().any(|item| match item {
+                ClassItem::Range(low, high) => (*low..=*high).contains(&c),
+                ClassItem::Digit(negated) => c.is_ascii_digit() != *negated,
+                ClassItem::Word(negated) => is_word(c) != *negated,
+                ClassItem::Space(negated) => c.is_whitespace() != *negated,
+            })
+        };
+        test(c) || (self.case_insensitive && (c.to_lowercase().any(test) || c.to_uppercase().any(test)))
+    }
+
+    // Matches a single-width node or zero-width assertion, returning the next position
+    fn step(&self, node: &Node, pos: usize) -> Option<usize> {
+        match node {
+            Node::Char(expected) => self.char_at(pos).filter(|c| self.eq(*c, *expected)).map(|_| pos + 1),
+            Node::Any => self.char_at(pos).filter(|c| *c != '\n').map(|_| pos + 1),
+            Node::Class { items, negated } => self
+                .char_at(pos)
+                .filter(|c| self.class_matches(items, *c) != *negated)
+                .map(|_| pos + 1),
+            Node::Start => (pos == 0).then_some(pos),
+            Node::End => (pos == self.chars.len()).then_some(pos),
+            Node::WordBoundary => {
+                let before = pos > 0 && self.char_at(pos - 1).is_some_and(is_word);
+                let after = self.char_at(pos).is_some_and(is_word);
+                (before != after).then_some(pos)
+            }
+            Node::Group(_) | Node::Repeat { .. } => None,
+        }
+    }
+
+    fn match_seq(&self, seq: &[Node], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+        // Once over budget every branch fails, which unwinds quickly
+        self.steps.set(self.steps.get() + 1);
+        let depth = self.depth.get();
+        if depth >= MAX_DEPTH {
+            self.steps.set(MAX_STEPS + 1);
+        }
+        if self.steps.get() > MAX_STEPS {
+            return false;
+        }
+        self.depth.set(depth + 1);
+        let matched = self.match_node(seq, pos, k);
+        self.depth.set(depth);
+        matched
+    }
+
+    fn match_node(&self, seq: &[Node], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+        let Some((node, rest)) = seq.split_first() else {
+            return k(pos);
+        };
+        match node {
+            // Runs of single characters are walked in a loop rather than recursed through
+            Node::Repeat { node, min, max, greedy } if matches!(**node, Node::Char(_) | Node::Any | Node::Class { .. }) => {
+                let max = max.unwrap_or(usize::MAX);
+                if *greedy {
+                    let mut ends = vec![pos];
+                    while ends.len() <= max {
+                        match self.step(node, ends[ends.len() - 1]) {
+                            Some(next) => ends.push(next),
+                            None => break,
+                        }
+                    }
+                    ends.iter().skip(*min).rev().any(|end| self.match_seq(rest, *end, k))
+                } else {
+                    let (mut count, mut end) = (0, pos);
+                    loop {
+                        if count >= *min && self.match_seq(rest, end, k) {
+                            return true;
+                        }
+                        match self.step(node, end) {
+                            Some(next) if count < max => (count, end) = (count + 1, next),
+                            _ => return false,
+                        }
+                    }
+                }
+            }
+            Node::Group(alternatives) => alternatives
+                .iter()
+                .any(|alt| self.match_seq(alt, pos, &mut |p| self.match_seq(rest, p, k))),
+            Node::Repeat { node, min, max, greedy } => {
+                self.match_repeat(node, *min, *max, *greedy, 0, pos, rest, k)
+            }
+            _ => match self.step(node, pos) {
+                Some(next) => self.match_seq(rest, next, k),
+                None => false,
+            },
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn match_repeat(
+        &self,
+        node: &Node,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+        count: usize,
+        pos: usize,
+        rest: &[Node],
+        k: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        let can_repeat = max.is_none_or(|max| count < max);
+        let once_more = |k: &mut dyn FnMut(usize) -> bool| {
+            can_repeat
+                && self.match_seq(std::slice::from_ref(node), pos, &mut |p| {
+                    // An empty iteration past the minimum would loop forever
+                    (p != pos || count < min) && self.match_repeat(node, min, max, greedy, count + 1, p, rest, k)
+                })
+        };
+        if count < min {
+            once_more(k)
+        } else if greedy {
+            once_more(k) || self.match_seq(rest, pos, k)
+        } else {
+            // Lazy: prefer handing off to the rest of the pattern
+            match self.match_seq(rest, pos, k) {
+                true => true,
+                false => once_more(k),
+            }
+        }
+    }
+}
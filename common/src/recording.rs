@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+// One line of a session recording: what a client sent and when. The server writes these
+// when `RECORD_FILE` is set, and the `replay` tool feeds them back.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordedFrame {
+    pub at_ms: u64, // Since the server started
+    pub conn: u64,  // Connections are numbered in the order they were accepted
+    #[serde(flatten)]
+    pub frame: Inbound,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Inbound {
+    Open { addr: String },
+    Line { line: String }, // Exactly as read, newline included
+    TooLong { bytes: usize }, // Refused unread, so only the size is kept
+    NotUtf8 { bytes: usize },
+    Close,
+}
@@ -0,0 +1,77 @@
+// Messages as released versions sent them must keep parsing, and ones from newer versions
+// must parse too. The fixtures are frozen: when the protocol grows, add lines, don't edit them.
+
+use common::{ChatMessage, Handshake, MessageType, PreviewUpdate, RoomEntry, ServerCapabilities, ServerStats, UserProfile};
+use serde_json::Value;
+
+const ORIGINAL: &str = include_str!("fixtures/original.jsonl"); // The first protocol version
+const MESSAGES: &str = include_str!("fixtures/messages.jsonl"); // Every message type and field sent today
+const NEWER: &str = include_str!("fixtures/newer.jsonl"); // From a future server, with types and fields this build lacks
+const HANDSHAKES: &str = include_str!("fixtures/handshakes.jsonl");
+
+fn parse_all(fixture: &str) -> Vec<ChatMessage> {
+    fixture.lines().map(|line| ChatMessage::from_json(line).unwrap_or_else(|e| panic!("{}: {}", e, line))).collect()
+}
+
+// Re-encoding gives back the same JSON, so nothing a client relays or stores is lost
+fn assert_round_trips(fixture: &str) {
+    for line in fixture.lines() {
+        let msg = ChatMessage::from_json(line).unwrap_or_else(|e| panic!("{}: {}", e, line));
+        let again: Value = serde_json::from_str(&msg.to_json().unwrap()).unwrap();
+        assert_eq!(again, serde_json::from_str::<Value>(line).unwrap(), "{}", line);
+    }
+}
+
+#[test]
+fn original_messages_round_trip() {
+    assert_round_trips(ORIGINAL);
+    let types: Vec<MessageType> = parse_all(ORIGINAL).into_iter().map(|m| m.msg_type).collect();
+    assert!(!types.contains(&MessageType::Unknown));
+}
+
+#[test]
+fn current_messages_round_trip() {
+    assert_round_trips(MESSAGES);
+    let messages = parse_all(MESSAGES);
+    assert!(messages.iter().all(|m| m.msg_type != MessageType::Unknown));
+    assert_eq!(messages[0].content_lines().collect::<Vec<_>>(), ["line one", "line two"]);
+    assert_eq!(messages[6].recipient.as_deref(), Some("bob"));
+}
+
+#[test]
+fn payloads_in_content_round_trip() {
+    for msg in parse_all(MESSAGES) {
+        let again = match msg.msg_type {
+            MessageType::Profile => serde_json::to_string(&serde_json::from_str::<UserProfile>(&msg.content).unwrap()),
+            MessageType::RoomList => serde_json::to_string(&serde_json::from_str::<Vec<RoomEntry>>(&msg.content).unwrap()),
+            MessageType::Stats => serde_json::to_string(&serde_json::from_str::<ServerStats>(&msg.content).unwrap()),
+            MessageType::Capabilities => serde_json::to_string(&serde_json::from_str::<ServerCapabilities>(&msg.content).unwrap()),
+            MessageType::Preview => serde_json::to_string(&serde_json::from_str::<PreviewUpdate>(&msg.content).unwrap()),
+            MessageType::Starred => serde_json::to_string(&serde_json::from_str::<Vec<ChatMessage>>(&msg.content).unwrap()),
+            _ => continue,
+        };
+        assert_eq!(serde_json::from_str::<Value>(&again.unwrap()).unwrap(), serde_json::from_str::<Value>(&msg.content).unwrap());
+    }
+}
+
+#[test]
+fn newer_messages_parse_with_what_this_build_knows() {
+    let messages = parse_all(NEWER);
+    assert_eq!(messages[0].msg_type, MessageType::Unknown);
+    assert_eq!(messages[0].content, "👍");
+    assert_eq!(messages[1].msg_type, MessageType::Chat);
+    assert_eq!(messages[1].seq, Some(43));
+    // Fields that were once required may be left out by then
+    assert_eq!(messages[2].msg_type, MessageType::Unknown);
+    assert_eq!((messages[2].username.as_str(), messages[2].content.as_str()), ("", ""));
+}
+
+#[test]
+fn handshakes_from_any_version_parse() {
+    let handshakes: Vec<Handshake> = HANDSHAKES.lines().map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {}", e, line))).collect();
+    assert_eq!(handshakes[0].username, "alice");
+    assert_eq!(handshakes[0].locale, None);
+    assert_eq!(handshakes[1].reconnects, 3);
+    assert!(handshakes[2].guest);
+    assert_eq!(handshakes[3].locale.as_deref(), Some("de"));
+}
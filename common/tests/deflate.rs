@@ -0,0 +1,46 @@
+// Compressed connections: what the server compresses, the client must get back byte for byte
+
+use common::deflate::{self, Deflater, Inflater};
+
+const MESSAGES: &str = include_str!("fixtures/messages.jsonl");
+const ORIGINAL: &str = include_str!("fixtures/original.jsonl");
+
+#[test]
+fn chunks_round_trip_and_refer_back_to_earlier_ones() {
+    let (mut deflater, mut inflater) = (Deflater::default(), Inflater::default());
+    let mut sizes = Vec::new();
+    for line in MESSAGES.lines() {
+        let chunk = format!("{}\n", line);
+        let compressed = deflater.compress(chunk.as_bytes());
+        assert_eq!(inflater.inflate(&compressed).unwrap(), chunk.as_bytes());
+        sizes.push((chunk.len(), compressed.len()));
+    }
+    // Later lines share their keys and IDs with earlier ones
+    let (plain, compressed) = sizes.iter().skip(1).fold((0, 0), |(p, c), (plain, compressed)| (p + plain, c + compressed));
+    assert!(compressed * 2 < plain, "{} bytes compressed to {}", plain, compressed);
+}
+
+#[test]
+fn empty_and_repetitive_chunks_round_trip() {
+    let (mut deflater, mut inflater) = (Deflater::default(), Inflater::default());
+    for chunk in [Vec::new(), vec![b'a'; 100_000], (0..=255u8).cycle().take(70_000).collect(), b"ab".repeat(300)] {
+        assert_eq!(inflater.inflate(&deflater.compress(&chunk)).unwrap(), chunk);
+    }
+}
+
+// Streams from zlib, which uses dynamic and stored blocks this compressor doesn't write
+#[test]
+fn reads_what_zlib_writes() {
+    for fixture in [&include_bytes!("fixtures/original.jsonl.deflate")[..], &include_bytes!("fixtures/original.jsonl.stored")[..]] {
+        assert_eq!(Inflater::default().inflate(fixture).unwrap(), ORIGINAL.as_bytes());
+    }
+}
+
+#[test]
+fn corrupt_data_is_an_error() {
+    let compressed = Deflater::default().compress(MESSAGES.as_bytes());
+    assert!(Inflater::default().inflate(&compressed[..compressed.len() / 2]).is_err());
+    assert!(Inflater::default().inflate(&[0xff; 16]).is_err());
+    assert!(deflate::frame_len([0xff; deflate::FRAME_HEADER]).is_err());
+    assert_eq!(deflate::frame_len(deflate::frame(&compressed)[..4].try_into().unwrap()).unwrap(), compressed.len());
+}
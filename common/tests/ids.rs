@@ -0,0 +1,69 @@
+// Names are checked when they are built or parsed; these are the characters they must refuse
+
+use common::{ChatMessage, MessageId, RoomName, Username};
+
+#[test]
+fn ordinary_names_are_accepted() {
+    for name in ["alice", "Bob_2", "zoë", "a-b.c", "x"] {
+        assert!(Username::new(name).is_ok(), "{}", name);
+    }
+    for room in ["general", "work/standup", "a/b/c", "café"] {
+        assert!(RoomName::new(room).is_ok(), "{}", room);
+    }
+}
+
+#[test]
+fn lengths_and_empty_segments() {
+    assert!(Username::new("").is_err());
+    assert!(Username::new("a".repeat(32)).is_ok());
+    assert!(Username::new("a".repeat(33)).is_err());
+    assert!(RoomName::new("a".repeat(64)).is_ok());
+    assert!(RoomName::new("a".repeat(65)).is_err());
+    for room in ["", "/work", "work/", "work//standup"] {
+        assert!(RoomName::new(room).is_err(), "{:?}", room);
+    }
+}
+
+#[test]
+fn whitespace_is_refused() {
+    for name in ["al ice", "alice\t", "\u{a0}alice", "al\u{2003}ice"] {
+        assert!(Username::new(name).is_err(), "{:?}", name);
+        assert!(RoomName::new(name).is_err(), "{:?}", name);
+    }
+}
+
+// An escape sequence in a name would be written straight to other users' terminals
+#[test]
+fn control_characters_are_refused() {
+    for name in ["al\u{1b}[2Jice", "alice\u{7}", "\u{0}alice", "al\u{7f}ice", "al\u{9b}2Jice"] {
+        assert!(Username::new(name).is_err(), "{:?}", name);
+        assert!(RoomName::new(name).is_err(), "{:?}", name);
+    }
+}
+
+// A comma would split one user into two in a user list
+#[test]
+fn list_separators_are_refused() {
+    assert!(Username::new("alice,mallory").is_err());
+    assert!(RoomName::new("a,b").is_err());
+}
+
+#[test]
+fn room_and_user_markers_are_refused() {
+    for name in ["#general", "al#ice", "@alice", "alice@example.org"] {
+        assert!(Username::new(name).is_err(), "{:?}", name);
+        assert!(RoomName::new(name).is_err(), "{:?}", name);
+    }
+    // `/` separates categories in a room name but has no place in a username
+    assert!(Username::new("/quit").is_err());
+    assert!(Username::new("work/alice").is_err());
+}
+
+#[test]
+fn messages_carrying_bad_names_do_not_parse() {
+    let id = MessageId::new();
+    let line = |room: &str, recipient: &str| format!(r#"{{"id":"{}","username":"alice","content":"hi","timestamp":"2024-01-01T00:00:00Z","room":"{}","msg_type":"Chat","recipient":"{}"}}"#, id, room, recipient);
+    assert!(ChatMessage::from_json(&line("general", "bob")).is_ok());
+    assert!(ChatMessage::from_json(&line("gen,eral", "bob")).is_err());
+    assert!(ChatMessage::from_json(&line("general", "b\\u001b[31mob")).is_err());
+}
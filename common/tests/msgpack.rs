@@ -0,0 +1,51 @@
+// MessagePack connections: every message must decode to what it would have been as JSON
+
+use common::{msgpack, ChatMessage};
+use serde_json::{json, Value};
+
+const MESSAGES: &str = include_str!("fixtures/messages.jsonl");
+
+#[test]
+fn messages_round_trip() {
+    for line in MESSAGES.lines() {
+        let msg = ChatMessage::from_json(line).unwrap();
+        let packed = msg.to_msgpack().unwrap();
+        assert!(packed.len() < line.len(), "{} bytes packed to {}", line.len(), packed.len());
+        assert_eq!(ChatMessage::from_msgpack(&packed).unwrap().to_json().unwrap(), msg.to_json().unwrap());
+    }
+}
+
+// Byte for byte what other MessagePack implementations write, smallest form first
+#[test]
+fn values_use_the_standard_encodings() {
+    let cases = [
+        (json!({"a": 1, "b": [true, null, -1]}), vec![0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0x93, 0xc3, 0xc0, 0xff]),
+        (json!(200), vec![0xcc, 200]),
+        (json!(-200), vec![0xd1, 0xff, 0x38]),
+        (json!(70000), vec![0xce, 0x00, 0x01, 0x11, 0x70]),
+        (json!(1.5), vec![0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]),
+        (json!("x".repeat(40)), [vec![0xd9, 40], vec![b'x'; 40]].concat()),
+    ];
+    for (value, bytes) in cases {
+        let mut packed = Vec::new();
+        msgpack::write(&value, &mut packed);
+        assert_eq!(packed, bytes, "{}", value);
+        assert_eq!(msgpack::read(&bytes).unwrap(), value);
+    }
+    let long: Value = (0..70_000).collect();
+    let mut packed = Vec::new();
+    msgpack::write(&long, &mut packed);
+    assert_eq!(&packed[..5], [0xdd, 0x00, 0x01, 0x11, 0x70]);
+    assert_eq!(msgpack::read(&packed).unwrap(), long);
+}
+
+#[test]
+fn malformed_data_is_an_error() {
+    let packed = ChatMessage::from_json(MESSAGES.lines().next().unwrap()).unwrap().to_msgpack().unwrap();
+    assert!(ChatMessage::from_msgpack(&packed[..packed.len() - 1]).is_err());
+    assert!(ChatMessage::from_msgpack(&[packed.as_slice(), &[0xc0]].concat()).is_err());
+    assert!(msgpack::read(&[0xc4, 0x01, 0x00]).is_err()); // Binary
+    assert!(msgpack::read(&[0xdd, 0xff, 0xff, 0xff, 0xff]).is_err());
+    assert!(msgpack::read(&[0x91; 100]).is_err());
+    assert!(msgpack::read(&[0x81, 0x01, 0x01]).is_err());
+}
@@ -0,0 +1,118 @@
+// The rule engine behind highlights, filters and triggers: what it matches, and that text from
+// other users can't make it hang
+
+use common::pattern::{Pattern, PatternError};
+use std::time::{Duration, Instant};
+
+fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+    Pattern::new(pattern).unwrap().find(text)
+}
+
+fn matched<'a>(pattern: &str, text: &'a str) -> Option<&'a str> {
+    find(pattern, text).map(|(start, end)| &text[start..end])
+}
+
+#[test]
+fn literals_dots_and_case() {
+    assert_eq!(matched("cat", "concatenate"), Some("cat"));
+    assert_eq!(matched("c.t", "a cut above"), Some("cut"));
+    assert_eq!(matched("a.b", "a\nb"), None);
+    assert_eq!(matched("CAT", "cat"), None);
+    assert_eq!(matched("(?i)CAT", "Cat"), Some("Cat"));
+    assert_eq!(matched("(?i)[A-C]+", "xAbCd"), Some("AbC"));
+    assert_eq!(matched(r"a\.b", "axb a.b"), Some("a.b"));
+}
+
+#[test]
+fn classes() {
+    assert_eq!(matched("[a-c]+", "xxabcabd"), Some("abcab"));
+    assert_eq!(matched("[^a-c ]+", "abc def"), Some("def"));
+    assert_eq!(matched(r"\d+", "room 42b"), Some("42"));
+    assert_eq!(matched(r"\D+", "42b7"), Some("b"));
+    assert_eq!(matched(r"\w+", "  hi_there!"), Some("hi_there"));
+    assert_eq!(matched(r"\W", "ab-c"), Some("-"));
+    assert_eq!(matched(r"\s+", "a \t b"), Some(" \t "));
+    assert_eq!(matched(r"[\d\s]+", "x1 2y"), Some("1 2"));
+    assert_eq!(matched("[-a]+", "b-a-"), Some("-a-"));
+    assert_eq!(matched("[]a]+", "x]a]"), Some("]a]"));
+    assert_eq!(matched("[é-ë]", "café"), Some("é"));
+}
+
+#[test]
+fn anchors_and_boundaries() {
+    assert_eq!(matched("^hi", "hi there"), Some("hi"));
+    assert_eq!(matched("^hi", "oh hi"), None);
+    assert_eq!(matched("end$", "the end"), Some("end"));
+    assert_eq!(matched("end$", "ending"), None);
+    assert_eq!(matched("^$", ""), Some(""));
+    assert_eq!(matched(r"\bcat\b", "concat cat"), Some("cat"));
+    assert_eq!(find(r"\bcat\b", "concat cat"), Some((7, 10)));
+    assert_eq!(matched(r"\bcat\b", "concatenate"), None);
+}
+
+#[test]
+fn alternation_and_groups() {
+    assert_eq!(matched("cat|dog", "hotdog"), Some("dog"));
+    assert_eq!(matched("gr(a|e)y", "grey"), Some("grey"));
+    assert_eq!(matched("(?:ab)+", "xababa"), Some("abab"));
+    assert_eq!(matched("a(b|bc)d", "abcd"), Some("abcd"));
+    assert_eq!(matched("(|x)y", "y"), Some("y"));
+    // The leftmost match wins over the first alternative
+    assert_eq!(matched("b|a", "ab"), Some("a"));
+}
+
+#[test]
+fn repetition() {
+    assert_eq!(matched("ab*", "abbbc"), Some("abbb"));
+    assert_eq!(matched("ab+", "ac ab"), Some("ab"));
+    assert_eq!(matched("colou?r", "color"), Some("color"));
+    assert_eq!(matched("a{2}", "aaa"), Some("aa"));
+    assert_eq!(matched("a{2,}", "aaaa"), Some("aaaa"));
+    assert_eq!(matched("a{1,2}", "aaa"), Some("aa"));
+    assert_eq!(matched("<.+?>", "<a><b>"), Some("<a>"));
+    assert_eq!(matched("<.+>", "<a><b>"), Some("<a><b>"));
+    assert_eq!(matched("x{", "x{"), Some("x{"));
+    assert_eq!(matched("(a*)*b", "aab"), Some("aab"));
+}
+
+#[test]
+fn find_iter_takes_every_match_once() {
+    let pattern = Pattern::new(r"\d+").unwrap();
+    assert_eq!(pattern.find_iter("1 22 333"), vec![(0, 1), (2, 4), (5, 8)]);
+    // Empty matches move on by a character, multi-byte ones included
+    assert_eq!(Pattern::new("x*").unwrap().find_iter("éx"), vec![(0, 0), (2, 3), (3, 3)]);
+}
+
+#[test]
+fn malformed_patterns_are_errors() {
+    assert_eq!(Pattern::new("(ab").unwrap_err(), PatternError::UnbalancedParen(0));
+    assert_eq!(Pattern::new("ab)").unwrap_err(), PatternError::UnbalancedParen(2));
+    assert_eq!(Pattern::new("*a").unwrap_err(), PatternError::NothingToRepeat(0));
+    assert_eq!(Pattern::new("^*").unwrap_err(), PatternError::NothingToRepeat(0));
+    assert_eq!(Pattern::new("a{3,1}").unwrap_err(), PatternError::InvalidRepeat(0));
+    assert_eq!(Pattern::new("[z-a]").unwrap_err(), PatternError::InvalidRange(1));
+    assert_eq!(Pattern::new("[ab").unwrap_err(), PatternError::UnexpectedEnd);
+    assert_eq!(Pattern::new(r"a\").unwrap_err(), PatternError::UnexpectedEnd);
+}
+
+// These took seconds, or overflowed the stack, before searches had a budget
+#[test]
+fn pathological_patterns_give_up_quickly() {
+    let cases = [
+        (r"(\w+\s?)+$", format!("{}!", "a".repeat(24))),
+        ("(a|a)*b", "a".repeat(22)),
+        ("(a*)*b", "a".repeat(20)),
+        ("(a+)+b", "a".repeat(4000)),
+    ];
+    for (pattern, text) in cases {
+        let started = Instant::now();
+        assert_eq!(find(pattern, &text), None, "{}", pattern);
+        assert!(started.elapsed() < Duration::from_secs(1), "{} took {:?}", pattern, started.elapsed());
+    }
+    // Ordinary rules still match across a whole message
+    let message = format!("{} needle", "hay ".repeat(1000));
+    assert_eq!(matched(r"(?i)\bNEEDLE\b", &message), Some("needle"));
+    assert_eq!(Pattern::new("hay").unwrap().find_iter(&message).len(), 1000);
+    assert_eq!(matched(".*needle", &message), Some(message.as_str()));
+    assert_eq!(matched("(?:ha)+!", &format!("{}!", "ha".repeat(100))).map(str::len), Some(201));
+}
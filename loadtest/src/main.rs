@@ -0,0 +1,222 @@
+use anyhow::{bail, Context};
+use common::{ChatMessage, Handshake, MessageType};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+
+// `loadtest [--server host:port] [--clients N] [--rooms M] [--rate msgs/s] [--duration secs]`:
+// connects N clients spread over M rooms, has each send at the given rate, and reports
+// how long messages took to reach the room and how many never arrived
+const USAGE: &str = "Usage: loadtest [--server host:port] [--clients N] [--rooms M] [--rate msgs/s] [--duration secs]";
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+const GRACE: Duration = Duration::from_secs(2); // Time for the last messages to arrive after sending stops
+const MARKER: &str = "loadtest";
+
+struct Options {
+    server: String,
+    clients: usize,
+    rooms: usize,
+    rate: f64, // Messages per second, per client
+    duration: Duration,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut options = Self { server: "127.0.0.1:8080".to_string(), clients: 10, rooms: 1, rate: 1.0, duration: Duration::from_secs(30) };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = args.next().context(USAGE)?;
+            match arg.as_str() {
+                "--server" => options.server = value.clone(),
+                "--clients" => options.clients = value.parse().context(USAGE)?,
+                "--rooms" => options.rooms = value.parse().context(USAGE)?,
+                "--rate" => options.rate = value.parse().context(USAGE)?,
+                "--duration" => options.duration = Duration::from_secs_f64(value.parse().context(USAGE)?),
+                _ => bail!(USAGE),
+            }
+        }
+        if options.clients == 0 || options.rooms == 0 || options.rate <= 0.0 || !options.rate.is_finite() {
+            bail!(USAGE);
+        }
+        Ok(options)
+    }
+}
+
+// A client that has joined its room and is ready to send
+struct Connection {
+    room: usize,
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+// What one client's reader saw
+#[derive(Default)]
+struct Received {
+    latencies: Vec<Duration>,
+    disconnected: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(args).await {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Vec<String>) -> anyhow::Result<()> {
+    let options = Options::parse(&args)?;
+    // Names are unique per run, so runs against the same server don't collide
+    let run_id = std::process::id();
+    let mut joining = JoinSet::new();
+    for i in 0..options.clients {
+        let server = options.server.clone();
+        let (username, room) = (format!("load{}-{}", run_id, i), i % options.rooms);
+        joining.spawn(async move { tokio::time::timeout(JOIN_TIMEOUT, connect(&server, &username, room)).await.unwrap_or_else(|_| bail!("timed out joining")) });
+    }
+    let mut connections = Vec::new();
+    let mut failed = 0;
+    while let Some(joined) = joining.join_next().await {
+        match joined? {
+            Ok(connection) => connections.push(connection),
+            Err(e) => {
+                failed += 1;
+                eprintln!("Client failed to connect: {:#}", e);
+            }
+        }
+    }
+    if connections.is_empty() {
+        bail!("No client could connect to {}", options.server);
+    }
+    let mut members = vec![0u64; options.rooms];
+    for connection in &connections {
+        members[connection.room] += 1;
+    }
+    println!(
+        "{} clients in {} rooms ({} failed to connect), each sending {} msg/s for {}s",
+        connections.len(),
+        options.rooms,
+        failed,
+        options.rate,
+        options.duration.as_secs_f64()
+    );
+
+    // Timestamps in messages count from here, so latency needs no clock sync beyond this process
+    let start = Instant::now();
+    let sent: Arc<Vec<AtomicU64>> = Arc::new((0..options.rooms).map(|_| AtomicU64::new(0)).collect());
+    let stop_sending = start + options.duration;
+    let stop_reading = stop_sending + GRACE;
+    let mut senders = JoinSet::new();
+    let mut readers = JoinSet::new();
+    for connection in connections {
+        let Connection { room, lines, writer } = connection;
+        // The run ID tells this run's messages from ones replayed out of room history
+        let tag = format!("{} {}", MARKER, run_id);
+        readers.spawn(read_messages(lines, tag.clone(), start, stop_reading));
+        senders.spawn(send_messages(writer, tag, room, options.rate, start, stop_sending, sent.clone()));
+    }
+    let mut writers = Vec::new();
+    while let Some(writer) = senders.join_next().await {
+        writers.extend(writer?);
+    }
+    let mut latencies = Vec::new();
+    let mut disconnected = 0;
+    while let Some(received) = readers.join_next().await {
+        let received = received?;
+        latencies.extend(received.latencies);
+        disconnected += received.disconnected as usize;
+    }
+    for mut writer in writers {
+        let _ = writer.write_all(b"/quit\n").await;
+    }
+
+    let sent: Vec<u64> = sent.iter().map(|count| count.load(Ordering::Relaxed)).collect();
+    let expected: u64 = sent.iter().zip(&members).map(|(sent, members)| sent * members).sum();
+    let delivered = latencies.len() as u64;
+    println!(
+        "sent {}, delivered {} of {} expected, dropped {}, {} clients disconnected",
+        sent.iter().sum::<u64>(),
+        delivered,
+        expected,
+        expected.saturating_sub(delivered),
+        disconnected
+    );
+    latencies.sort();
+    if latencies.is_empty() {
+        println!("no messages arrived");
+    } else {
+        let percentile = |q: f64| format_latency(latencies[((latencies.len() - 1) as f64 * q).round() as usize]);
+        println!("latency p50 {}, p90 {}, p99 {}, max {}", percentile(0.5), percentile(0.9), percentile(0.99), percentile(1.0));
+    }
+    Ok(())
+}
+
+// Logs in and moves to the client's room, waiting until the server confirms it
+async fn connect(server: &str, username: &str, room: usize) -> anyhow::Result<Connection> {
+    let stream = TcpStream::connect(server).await.with_context(|| format!("Failed to connect to {}", server))?;
+    let (reader, mut writer) = stream.into_split();
+    let handshake = Handshake { username: username.to_string(), ..Default::default() };
+    writer.write_all(format!("{}\n", serde_json::to_string(&handshake)?).as_bytes()).await?;
+    let room_name = format!("{}-{}", MARKER, room);
+    writer.write_all(format!("/join {}\n", room_name).as_bytes()).await?;
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        let Some(line) = lines.next_line().await? else { bail!("the server closed the connection") };
+        if let Some(error) = line.strip_prefix("Error:") {
+            bail!("{}", error.trim());
+        }
+        let Ok(msg) = ChatMessage::from_json(&line) else { continue };
+        if msg.msg_type == MessageType::RoomChange && msg.room == room_name {
+            return Ok(Connection { room, lines, writer });
+        }
+    }
+}
+
+// Sends `<tag> <micros since start>` at a steady rate; the writer comes back unless it failed
+async fn send_messages(mut writer: OwnedWriteHalf, tag: String, room: usize, rate: f64, start: Instant, stop: Instant, sent: Arc<Vec<AtomicU64>>) -> Option<OwnedWriteHalf> {
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if Instant::now() >= stop {
+            return Some(writer);
+        }
+        let line = format!("{} {}\n", tag, start.elapsed().as_micros());
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            return None;
+        }
+        sent[room].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Times every load message that arrives until `stop`
+async fn read_messages(mut lines: Lines<BufReader<OwnedReadHalf>>, tag: String, start: Instant, stop: Instant) -> Received {
+    let mut received = Received::default();
+    loop {
+        let line = match tokio::time::timeout_at(stop.into(), lines.next_line()).await {
+            Err(_) => return received,
+            Ok(Ok(Some(line))) => line,
+            Ok(_) => {
+                received.disconnected = true;
+                return received;
+            }
+        };
+        let Ok(msg) = ChatMessage::from_json(&line) else { continue };
+        if msg.msg_type != MessageType::Chat {
+            continue;
+        }
+        let sent_at = msg.content.strip_prefix(tag.as_str()).and_then(|micros| micros.trim().parse::<u64>().ok());
+        if let Some(sent_at) = sent_at {
+            received.latencies.push(start.elapsed().saturating_sub(Duration::from_micros(sent_at)));
+        }
+    }
+}
+
+fn format_latency(latency: Duration) -> String {
+    format!("{:.1} ms", latency.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,129 @@
+use anyhow::{bail, Context};
+use common::recording::{Inbound, RecordedFrame};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+// `replay <recording> [--server host:port] [--speed N]`: plays a `RECORD_FILE` recording
+// back against a server, one connection per recorded client, at the recorded pace divided
+// by N, and prints what each connection sent and got back
+const USAGE: &str = "Usage: replay <recording> [--server host:port] [--speed N]";
+const GRACE: Duration = Duration::from_secs(2); // Time for the last replies to arrive after the last frame
+
+struct Options {
+    recording: String,
+    server: String,
+    speed: f64, // 2 plays twice as fast as recorded
+}
+
+impl Options {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut options = Self { recording: String::new(), server: "127.0.0.1:8080".to_string(), speed: 1.0 };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--server" => options.server = args.next().context(USAGE)?.clone(),
+                "--speed" => options.speed = args.next().context(USAGE)?.parse().context(USAGE)?,
+                _ if options.recording.is_empty() && !arg.starts_with("--") => options.recording = arg.clone(),
+                _ => bail!(USAGE),
+            }
+        }
+        if options.recording.is_empty() || options.speed <= 0.0 || !options.speed.is_finite() {
+            bail!(USAGE);
+        }
+        Ok(options)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(args).await {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Vec<String>) -> anyhow::Result<()> {
+    let options = Options::parse(&args)?;
+    let text = std::fs::read_to_string(&options.recording).with_context(|| format!("Failed to read {}", options.recording))?;
+    let frames = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| serde_json::from_str::<RecordedFrame>(line).with_context(|| format!("{} line {}", options.recording, i + 1)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let connections = frames.iter().filter(|recorded| matches!(recorded.frame, Inbound::Open { .. })).count();
+    println!("Replaying {} frames from {} connections against {} at {}x", frames.len(), connections, options.server, options.speed);
+
+    let start = Instant::now();
+    let mut writers: HashMap<u64, OwnedWriteHalf> = HashMap::new();
+    let mut readers = JoinSet::new();
+    for recorded in frames {
+        tokio::time::sleep_until(start + Duration::from_secs_f64(recorded.at_ms as f64 / 1000.0 / options.speed)).await;
+        let conn = recorded.conn;
+        let bytes = match recorded.frame {
+            Inbound::Open { addr } => {
+                match TcpStream::connect(&options.server).await {
+                    Ok(stream) => {
+                        let (reader, writer) = stream.into_split();
+                        println!("{} #{} connected (recorded from {})", elapsed(start), conn, addr);
+                        readers.spawn(async move {
+                            let mut lines = BufReader::new(reader).lines();
+                            while let Ok(Some(line)) = lines.next_line().await {
+                                println!("{} #{} < {}", elapsed(start), conn, line);
+                            }
+                            println!("{} #{} closed by the server", elapsed(start), conn);
+                        });
+                        writers.insert(conn, writer);
+                    }
+                    Err(e) => eprintln!("{} #{} could not connect, its frames are skipped: {}", elapsed(start), conn, e),
+                }
+                continue;
+            }
+            Inbound::Close => {
+                if writers.remove(&conn).is_some() {
+                    println!("{} #{} hung up", elapsed(start), conn);
+                }
+                continue;
+            }
+            Inbound::Line { line } => line.into_bytes(),
+            // The originals weren't kept, so these are stand-ins of the same size
+            Inbound::TooLong { bytes } => filler(b'x', bytes),
+            Inbound::NotUtf8 { bytes } => filler(0xff, bytes),
+        };
+        let Some(writer) = writers.get_mut(&conn) else { continue };
+        println!("{} #{} > {}", elapsed(start), conn, describe(&bytes));
+        if writer.write_all(&bytes).await.is_err() {
+            writers.remove(&conn);
+        }
+    }
+    tokio::time::sleep(GRACE).await;
+    drop(writers);
+    readers.abort_all();
+    Ok(())
+}
+
+// `bytes` of `byte`, ending in a newline
+fn filler(byte: u8, bytes: usize) -> Vec<u8> {
+    let mut filler = vec![byte; bytes.saturating_sub(1)];
+    filler.push(b'\n');
+    filler
+}
+
+// Long lines are cut short in the transcript
+fn describe(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let text = text.trim_end_matches('\n');
+    match text.char_indices().nth(200) {
+        Some((cut, _)) => format!("{}... ({} bytes)", &text[..cut], bytes.len()),
+        None => text.to_string(),
+    }
+}
+
+fn elapsed(start: Instant) -> String {
+    format!("{:>9.3}s", start.elapsed().as_secs_f64())
+}
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Arbitrary bytes from the first line on: JSON and raw handshakes, bad UTF-8, huge or cut-off lines
+fuzz_target!(|data: &[u8]| {
+    server_fuzz::run_session(data);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use common::{ChatMessage, Handshake};
+use libfuzzer_sys::fuzz_target;
+
+// The JSON both ends parse from the wire
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = serde_json::from_str::<Handshake>(text);
+        if let Ok(msg) = ChatMessage::from_json(text) {
+            let _ = msg.to_json();
+            let _ = msg.content_lines().count();
+        }
+    }
+});
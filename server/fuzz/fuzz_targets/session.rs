@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// A valid login followed by arbitrary bytes, to reach chat messages and commands
+fuzz_target!(|data: &[u8]| {
+    let mut input = b"{\"username\":\"fuzzer\"}\n".to_vec();
+    input.extend_from_slice(data);
+    server_fuzz::run_session(&input);
+});
@@ -0,0 +1,52 @@
+use server::{ChatServer, ServerOptions};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::Runtime;
+
+// Longer than any session should take once its input has ended; past this the server hung
+const HANG: Duration = Duration::from_secs(10);
+
+// One server for the whole fuzzing run, with its files in a temporary directory
+fn server() -> &'static (Runtime, ChatServer) {
+    static SERVER: OnceLock<(Runtime, ChatServer)> = OnceLock::new();
+    SERVER.get_or_init(|| {
+        let runtime = Runtime::new().unwrap();
+        let dir = std::env::temp_dir().join(format!("chat-fuzz-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let options = ServerOptions {
+            admins: vec!["root".to_string()],
+            accounts_file: dir.join("accounts.json"),
+            audit_log: dir.join("audit.log"),
+            config_file: dir.join("server.json"),
+            snapshot_file: dir.join("snapshot.json"),
+            ..Default::default()
+        };
+        let server = runtime.block_on(ChatServer::new(options)).unwrap();
+        (runtime, server)
+    })
+}
+
+// Plays `input` as everything a client sends, then hangs up. The server may reject it or
+// end the session with an error, but it must not panic and must let go of the connection.
+pub fn run_session(input: &[u8]) {
+    let (runtime, server) = server();
+    runtime.block_on(async {
+        let (client, connection) = tokio::io::duplex(64 * 1024);
+        let (reader, writer) = tokio::io::split(connection);
+        let session = tokio::spawn(async move {
+            let _ = server.serve(reader, writer, ([127, 0, 0, 1], 9).into()).await;
+        });
+        let (mut from_server, mut to_server) = tokio::io::split(client);
+        // Replies are drained as they come, so a full pipe can't stall the server
+        let drain = tokio::spawn(async move {
+            let mut sink = Vec::new();
+            let _ = from_server.read_to_end(&mut sink).await;
+        });
+        let _ = to_server.write_all(input).await;
+        let _ = to_server.shutdown().await;
+        drop(to_server);
+        tokio::time::timeout(HANG, session).await.expect("server hung on input").expect("server panicked");
+        drain.abort();
+    });
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use common::{ActivityReport, RoomName};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const KEEP_HOURS: i64 = 7 * 24; // A week, so a room's busy hours show through a quiet day
+const DAY_HOURS: i64 = 24;
+
+// Chat messages posted per room per hour, for `/activity`. Only counts are kept, so rooms
+// with a time-to-live are counted like any other.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Activity {
+    hours: HashMap<RoomName, BTreeMap<i64, u64>>, // Room -> hours since the epoch -> messages
+}
+
+impl Activity {
+    pub fn record(&mut self, room: &RoomName, at: DateTime<Utc>) {
+        let hour = at.timestamp().div_euclid(3600);
+        let hours = self.hours.entry(room.clone()).or_default();
+        *hours.entry(hour).or_default() += 1;
+        // Older hours go as the room is posted in; reports skip them until then
+        *hours = hours.split_off(&(hour - KEEP_HOURS + 1));
+    }
+
+    // Messages in the last 24 hours, as the report's `last_day` adds up to
+    pub fn last_day(&self, room: &RoomName, now: DateTime<Utc>) -> u64 {
+        let current = now.timestamp().div_euclid(3600);
+        self.hours.get(room).map_or(0, |hours| hours.range(current - DAY_HOURS + 1..=current).map(|(_, count)| count).sum())
+    }
+
+    pub fn report(&self, room: &RoomName, now: DateTime<Utc>) -> ActivityReport {
+        let current = now.timestamp().div_euclid(3600);
+        let hours = self.hours.get(room);
+        let count = |hour: i64| hours.and_then(|hours| hours.get(&hour)).copied().unwrap_or_default();
+        let last_day = (current - DAY_HOURS + 1..=current).map(count).collect();
+        let mut by_hour = vec![0; DAY_HOURS as usize];
+        for hour in current - KEEP_HOURS + 1..=current {
+            by_hour[hour.rem_euclid(DAY_HOURS) as usize] += count(hour);
+        }
+        let until = now.duration_trunc(Duration::hours(1)).ok().map(|start| start + Duration::hours(1));
+        ActivityReport { room: room.to_string(), until, last_day, by_hour }
+    }
+}
@@ -0,0 +1,185 @@
+use chrono::NaiveDate;
+use common::{ChatMessage, RoomName};
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+pub const MAX_DAYS: i64 = 31; // Longest range `/history --archived` reads at once
+
+// `archive` in the server config: room messages leaving memory, because a room went over
+// `history_limit`, its history was dropped as idle, or they are older than `after`, are
+// appended to `<dir>/<room>/<YYYY-MM-DD>.jsonl` instead of being lost. Rooms with a TTL
+// forget as promised and are never archived.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ArchiveSettings {
+    #[serde(default = "default_dir")]
+    pub dir: PathBuf,
+    #[serde(default)]
+    pub after: Option<String>, // As for `/ttl`, e.g. "30d"
+}
+
+fn default_dir() -> PathBuf {
+    "archive".into()
+}
+
+struct Batch {
+    dir: PathBuf,
+    room: RoomName,
+    messages: Vec<ChatMessage>,
+    done: Option<oneshot::Sender<io::Result<()>>>, // Told how it went; otherwise failures are only reported
+}
+
+// Writes to the archive from a background task, like the journal, so posting never waits on
+// the disk. Batches are written in the order they are sent, so a day's file stays in order.
+pub struct Archiver {
+    tx: std::sync::Mutex<Option<mpsc::UnboundedSender<Batch>>>,
+    task: tokio::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Archiver {
+    pub fn start() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(write_batches(rx));
+        Self { tx: std::sync::Mutex::new(Some(tx)), task: tokio::sync::Mutex::new(Some(task)) }
+    }
+
+    pub fn store(&self, dir: &Path, room: &RoomName, messages: Vec<ChatMessage>) {
+        self.send(Batch { dir: dir.to_path_buf(), room: room.clone(), messages, done: None });
+    }
+
+    // For callers that keep the messages when the write fails
+    pub async fn store_and_wait(&self, dir: &Path, room: &RoomName, messages: Vec<ChatMessage>) -> io::Result<()> {
+        let (done, result) = oneshot::channel();
+        self.send(Batch { dir: dir.to_path_buf(), room: room.clone(), messages, done: Some(done) });
+        result.await.unwrap_or_else(|_| Err(io::Error::other("the archive is closed")))
+    }
+
+    // Waits until everything sent so far is on disk, so reading the archive sees it
+    pub async fn flush(&self) {
+        let (done, result) = oneshot::channel();
+        self.send(Batch { dir: PathBuf::new(), room: RoomName::general(), messages: Vec::new(), done: Some(done) });
+        let _ = result.await;
+    }
+
+    fn send(&self, batch: Batch) {
+        if let Some(tx) = self.tx.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            let _ = tx.send(batch);
+        }
+    }
+
+    // Writes out everything queued so far and stops; later batches are dropped
+    pub async fn close(&self) {
+        self.tx.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(task) = self.task.lock().await.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+async fn write_batches(mut rx: mpsc::UnboundedReceiver<Batch>) {
+    while let Some(Batch { dir, room, messages, done }) = rx.recv().await {
+        let count = messages.len();
+        let result = if messages.is_empty() {
+            Ok(())
+        } else {
+            let (dir, room) = (dir.clone(), room.clone());
+            tokio::task::spawn_blocking(move || store(&dir, &room, &messages)).await.unwrap_or_else(|e| Err(io::Error::other(e)))
+        };
+        match (done, result) {
+            (Some(done), result) => {
+                let _ = done.send(result);
+            }
+            (None, Err(e)) => eprintln!("Could not archive {} messages from #{} to {}: {}", count, room, dir.display(), e),
+            (None, Ok(())) => {}
+        }
+    }
+}
+
+// One file per room and UTC day, one JSON message per line
+fn store(dir: &Path, room: &RoomName, messages: &[ChatMessage]) -> io::Result<()> {
+    let room_dir = dir.join(encode(room));
+    fs::create_dir_all(&room_dir)?;
+    let mut open: Option<(NaiveDate, File)> = None;
+    for msg in messages {
+        let day = msg.timestamp.date_naive();
+        let file = match &mut open {
+            Some((open_day, file)) if *open_day == day => file,
+            _ => {
+                let file = OpenOptions::new().create(true).append(true).open(room_dir.join(format!("{}.jsonl", day)))?;
+                &mut open.insert((day, file)).1
+            }
+        };
+        writeln!(file, "{}", serde_json::to_string(msg)?)?;
+    }
+    Ok(())
+}
+
+// Up to `limit` messages from `from` to `to` (inclusive), oldest first, and whether there were more
+pub fn load(dir: &Path, room: &RoomName, from: NaiveDate, to: NaiveDate, limit: usize) -> io::Result<(Vec<ChatMessage>, bool)> {
+    let room_dir = dir.join(encode(room));
+    let mut found = Vec::new();
+    for day in from.iter_days().take_while(|day| *day <= to) {
+        let text = match fs::read_to_string(room_dir.join(format!("{}.jsonl", day))) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for msg in text.lines().filter_map(|line| ChatMessage::from_json(line).ok()) {
+            if found.len() == limit {
+                return Ok((found, true));
+            }
+            found.push(msg);
+        }
+    }
+    Ok((found, false))
+}
+
+// Room names may contain `/` and `..` segments
+fn encode(room: &RoomName) -> String {
+    common::encode_file_name(room.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chat-archive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn messages(texts: &[&str]) -> Vec<ChatMessage> {
+        texts.iter().map(|text| ChatMessage::chat("alice".to_string(), text.to_string(), RoomName::general())).collect()
+    }
+
+    #[tokio::test]
+    async fn batches_land_in_order_before_a_flush_returns() {
+        let dir = dir("order");
+        let archiver = Archiver::start();
+        for text in ["one", "two", "three"] {
+            archiver.store(&dir, &RoomName::general(), messages(&[text]));
+        }
+        archiver.flush().await;
+        let today = chrono::Utc::now().date_naive();
+        let (found, more) = load(&dir, &RoomName::general(), today, today, 10).unwrap();
+        assert_eq!(found.iter().map(|msg| msg.content.as_str()).collect::<Vec<_>>(), ["one", "two", "three"]);
+        assert!(!more);
+    }
+
+    #[tokio::test]
+    async fn failed_writes_are_reported_to_whoever_waits() {
+        let dir = dir("fail");
+        fs::create_dir_all(dir.parent().unwrap()).unwrap();
+        fs::write(&dir, "not a directory").unwrap();
+        let archiver = Archiver::start();
+        assert!(archiver.store_and_wait(&dir, &RoomName::general(), messages(&["lost"])).await.is_err());
+        archiver.close().await;
+        assert!(archiver.store_and_wait(&dir, &RoomName::general(), messages(&["late"])).await.is_err());
+        fs::remove_file(&dir).unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+use crate::auth::{hex, sha256};
+use chrono::{SecondsFormat, Utc};
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const LINK: &str = " sha256="; // Ends every chained line
+const RECENT: usize = 20; // Entries kept in memory for `/console`
+const ROTATED: &str = "audit_rotated"; // First entry of a log that carries on from a rotated one
+const PRUNED: &str = "audit_pruned"; // A rotated log deleted on purpose
+
+// Append-only record of moderation and access events, one line per event:
+// `<RFC 3339 time> <actor> <action> <detail> sha256=<hash>`. Each hash covers the line
+// before it and the previous hash, so editing or removing an entry breaks every later one.
+// A rotated log is named in the first entry of the next, with its entry count and last hash,
+// and the chain carries on from that hash.
+pub struct AuditLog {
+    path: PathBuf,
+    tip: Mutex<Tip>,
+    recent: Mutex<VecDeque<String>>, // Newest entries, without their hashes
+}
+
+// The end of the current file's chain
+#[derive(Default)]
+struct Tip {
+    hash: String, // Of the newest line; empty for a new chain
+    entries: usize,
+}
+
+// What `verify` found
+pub enum Chain {
+    Intact { entries: usize, head: String, files: usize },
+    Broken { file: PathBuf, line: usize }, // 1-based; that line or one before it was changed or removed
+    Missing { file: PathBuf }, // A rotated log that is gone without having been pruned
+}
+
+// One file's chain as read back
+struct FileChain {
+    entries: usize,
+    head: String,
+    rotated_from: Option<(PathBuf, Tip)>, // The previous file and how its chain ended
+    pruned: Vec<PathBuf>,
+}
+
+impl AuditLog {
+    // Carries on the chain of an existing log
+    pub fn new(path: PathBuf) -> Self {
+        let log = fs::read_to_string(&path).unwrap_or_default();
+        let chained: Vec<&str> = log.lines().filter_map(|line| split(line).map(|(_, hash)| hash)).collect();
+        let tip = Tip { hash: chained.last().map(|hash| hash.to_string()).unwrap_or_default(), entries: chained.len() };
+        Self { path, tip: Mutex::new(tip), recent: Mutex::new(VecDeque::new()) }
+    }
+
+    // A failed write is reported but never stops the chat
+    pub fn record(&self, actor: &str, action: &str, detail: &str) {
+        let entry = format!("{} {} {} {}", Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true), actor, action, detail.replace('\n', " "));
+        // Held while writing, so lines land in the order they are chained
+        let mut tip = self.tip.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = self.append(&mut tip, &entry) {
+            eprintln!("Could not write audit log {}: {}", self.path.display(), e);
+        }
+        drop(tip);
+        self.remember(entry);
+    }
+
+    fn append(&self, tip: &mut Tip, entry: &str) -> io::Result<()> {
+        let hash = link(&tip.hash, entry);
+        let line = format!("{}{}{}\n", entry, LINK, hash);
+        OpenOptions::new().create(true).append(true).open(&self.path).and_then(|mut file| file.write_all(line.as_bytes()))?;
+        tip.hash = hash;
+        tip.entries += 1;
+        Ok(())
+    }
+
+    fn remember(&self, entry: String) {
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.len() == RECENT {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+
+    // Entries recorded since startup, up to the newest 20, oldest first
+    pub fn recent(&self) -> Vec<String> {
+        self.recent.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    // Moves the log aside and starts a new file with an entry naming it, so that removing or
+    // cutting short the old file shows up in `verify`
+    pub fn rotate(&self, to: &Path) -> io::Result<()> {
+        let mut tip = self.tip.lock().unwrap_or_else(|e| e.into_inner());
+        match fs::rename(&self.path, to) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            result => result?,
+        }
+        let ended = std::mem::take(&mut *tip);
+        let entry = format!("{} server {} entries={} head={} from={}", Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true), ROTATED, ended.entries, ended.hash, to.display());
+        tip.hash = ended.hash;
+        self.append(&mut tip, &entry)?;
+        drop(tip);
+        self.remember(entry);
+        Ok(())
+    }
+
+    // For rotated logs deleted to make room, so `verify` doesn't take them for tampering
+    pub fn pruned(&self, path: &Path) {
+        self.record("server", PRUNED, &path.display().to_string());
+    }
+
+    // Follows the chain back through rotated logs until one that was pruned, or one from
+    // before rotation was chained. Lines written before chaining existed are skipped, as long
+    // as they all come first.
+    pub fn verify(&self) -> io::Result<Chain> {
+        let _tip = self.tip.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut path, mut expected) = (self.path.clone(), None::<Tip>);
+        let (mut entries, mut head, mut files) = (0, None, 0);
+        let mut pruned = HashSet::new();
+        loop {
+            let log = match fs::read_to_string(&path) {
+                Ok(log) => log,
+                Err(e) if e.kind() == io::ErrorKind::NotFound && files == 0 => String::new(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Chain::Missing { file: path }),
+                Err(e) => return Err(e),
+            };
+            let chain = match check(&log) {
+                Ok(chain) => chain,
+                Err(line) => return Ok(Chain::Broken { file: path, line }),
+            };
+            // Entries cut off the end, or the whole file swapped for another
+            if expected.as_ref().is_some_and(|tip| tip.entries != chain.entries || tip.hash != chain.head) {
+                return Ok(Chain::Broken { line: log.lines().count() + 1, file: path });
+            }
+            entries += chain.entries;
+            head.get_or_insert(chain.head);
+            files += 1;
+            pruned.extend(chain.pruned);
+            match chain.rotated_from {
+                Some((from, _)) if pruned.contains(&from) => break,
+                Some((from, tip)) => (path, expected) = (from, Some(tip)),
+                None => break,
+            }
+        }
+        Ok(Chain::Intact { entries, head: head.unwrap_or_default(), files })
+    }
+}
+
+// A file's own chain; a rotation entry can only come first, and seeds it with the hash the
+// previous file ended on
+fn check(log: &str) -> Result<FileChain, usize> {
+    let mut chain = FileChain { entries: 0, head: String::new(), rotated_from: None, pruned: Vec::new() };
+    for (i, line) in log.lines().enumerate() {
+        let Some((entry, hash)) = split(line) else {
+            if chain.entries == 0 {
+                continue;
+            }
+            return Err(i + 1);
+        };
+        if chain.entries == 0 {
+            if let Some((from, tip)) = server_entry(entry, ROTATED).and_then(rotation) {
+                chain.head = tip.hash.clone();
+                chain.rotated_from = Some((from, tip));
+            }
+        }
+        if link(&chain.head, entry) != hash {
+            return Err(i + 1);
+        }
+        if let Some(path) = server_entry(entry, PRUNED) {
+            chain.pruned.push(PathBuf::from(path));
+        }
+        chain.head = hash.to_string();
+        chain.entries += 1;
+    }
+    Ok(chain)
+}
+
+// The detail of an entry the server itself recorded with this action
+fn server_entry<'a>(entry: &'a str, action: &str) -> Option<&'a str> {
+    let mut fields = entry.splitn(4, ' ').skip(1);
+    (fields.next()? == "server" && fields.next()? == action).then_some(())?;
+    fields.next()
+}
+
+// `entries=<n> head=<hash> from=<path>`
+fn rotation(detail: &str) -> Option<(PathBuf, Tip)> {
+    let rest = detail.strip_prefix("entries=")?;
+    let (entries, rest) = rest.split_once(" head=")?;
+    let (hash, from) = rest.split_once(" from=")?;
+    Some((PathBuf::from(from), Tip { hash: hash.to_string(), entries: entries.parse().ok()? }))
+}
+
+fn link(previous: &str, entry: &str) -> String {
+    hex(&sha256(format!("{}\n{}", previous, entry).as_bytes()))
+}
+
+fn split(line: &str) -> Option<(&str, &str)> {
+    line.rsplit_once(LINK).filter(|(_, hash)| hash.len() == 64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chat-audit-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Three files: two rotated ones and the current log
+    fn rotated_twice(dir: &Path) -> AuditLog {
+        let log = AuditLog::new(dir.join("audit.log"));
+        log.record("root", "kick", "bob");
+        log.rotate(&dir.join("audit-1.log")).unwrap();
+        log.record("root", "reserve", "mod*");
+        log.record("root", "kick", "carol");
+        log.rotate(&dir.join("audit-2.log")).unwrap();
+        log.record("root", "ban", "dave");
+        log
+    }
+
+    fn intact(chain: Chain) -> (usize, String, usize) {
+        match chain {
+            Chain::Intact { entries, head, files } => (entries, head, files),
+            Chain::Broken { file, line } => panic!("broken at line {} of {}", line, file.display()),
+            Chain::Missing { file } => panic!("{} missing", file.display()),
+        }
+    }
+
+    #[test]
+    fn the_chain_carries_on_across_rotations() {
+        let dir = dir("rotate");
+        let log = rotated_twice(&dir);
+        let (entries, head, files) = intact(log.verify().unwrap());
+        assert_eq!((entries, files), (6, 3));
+        let current = fs::read_to_string(dir.join("audit.log")).unwrap();
+        assert!(current.lines().next().unwrap().contains(" server audit_rotated entries=3 head="), "{}", current);
+        assert!(current.ends_with(&format!("{}\n", head)));
+        // A restart picks up where the chain ended
+        let log = AuditLog::new(dir.join("audit.log"));
+        log.record("root", "unban", "dave");
+        assert_eq!(intact(log.verify().unwrap()).0, 7);
+    }
+
+    #[test]
+    fn deleting_a_rotated_log_is_caught() {
+        let dir = dir("delete");
+        let log = rotated_twice(&dir);
+        fs::remove_file(dir.join("audit-1.log")).unwrap();
+        assert!(matches!(log.verify().unwrap(), Chain::Missing { file } if file == dir.join("audit-1.log")));
+    }
+
+    #[test]
+    fn truncating_a_rotated_log_is_caught() {
+        let dir = dir("truncate");
+        let log = rotated_twice(&dir);
+        let rotated = dir.join("audit-2.log");
+        let kept: Vec<String> = fs::read_to_string(&rotated).unwrap().lines().take(2).map(|line| format!("{}\n", line)).collect();
+        fs::write(&rotated, kept.concat()).unwrap();
+        assert!(matches!(log.verify().unwrap(), Chain::Broken { file, line: 3 } if file == rotated));
+        // Emptied rather than deleted
+        fs::write(dir.join("audit-2.log"), "").unwrap();
+        assert!(matches!(log.verify().unwrap(), Chain::Broken { line: 1, .. }));
+    }
+
+    #[test]
+    fn editing_a_rotated_log_is_caught() {
+        let dir = dir("edit");
+        let log = rotated_twice(&dir);
+        let rotated = dir.join("audit-1.log");
+        fs::write(&rotated, fs::read_to_string(&rotated).unwrap().replace("kick bob", "kick eve")).unwrap();
+        assert!(matches!(log.verify().unwrap(), Chain::Broken { file, line: 1 } if file == rotated));
+    }
+
+    #[test]
+    fn pruned_logs_end_the_check_without_breaking_it() {
+        let dir = dir("prune");
+        let log = rotated_twice(&dir);
+        fs::remove_file(dir.join("audit-1.log")).unwrap();
+        log.pruned(&dir.join("audit-1.log"));
+        let (entries, _, files) = intact(log.verify().unwrap());
+        assert_eq!((entries, files), (6, 2));
+    }
+
+    #[test]
+    fn logs_from_before_chaining_still_verify() {
+        let dir = dir("legacy");
+        fs::write(dir.join("audit.log"), "2024-01-01T00:00:00Z root kick bob\n").unwrap();
+        let log = AuditLog::new(dir.join("audit.log"));
+        log.record("root", "kick", "carol");
+        log.rotate(&dir.join("audit-1.log")).unwrap();
+        assert_eq!(intact(log.verify().unwrap()).2, 2);
+    }
+}
@@ -0,0 +1,412 @@
+use crate::migrate::{self, Migration, Object, Schema};
+use common::Handshake;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Work factor for the iterated, salted SHA-256 password hash. No KDF crate is
+// available to this build, so this only slows down offline guessing.
+const HASH_ROUNDS: u32 = 20_000;
+const TOTP_STEP: u64 = 30;
+const ISSUER: &str = "ultimate-chat";
+const SCHEMA: Schema = Schema {
+    name: "accounts file",
+    migrations: &[Migration { what: "move accounts under \"accounts\"", apply: nest_accounts }],
+};
+
+// Registered usernames, persisted as JSON. Unregistered names stay open to anyone.
+pub struct Accounts {
+    path: PathBuf,
+    accounts: HashMap<String, Account>,
+}
+
+#[derive(Deserialize)]
+struct AccountsFile {
+    accounts: HashMap<String, Account>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Account {
+    salt: String, // Hex
+    hash: String, // Hex
+    #[serde(default)]
+    totp_secret: Option<String>, // Base32, set by `/totp on`
+}
+
+// What the client must supply before it is let in; `error` explains a failed attempt
+pub struct Challenge {
+    pub kind: &'static str, // "password", "totp" or "register"
+    pub error: Option<&'static str>,
+}
+
+impl Challenge {
+    fn new(kind: &'static str, error: Option<&'static str>) -> Self {
+        Self { kind, error }
+    }
+}
+
+impl Accounts {
+    // A missing file starts with no accounts. One in an older layout is upgraded and saved,
+    // and one that can't be read stops the server rather than being overwritten.
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self { path, accounts: HashMap::new() }),
+            Err(e) => return Err(e),
+        };
+        let (value, from) = SCHEMA.upgrade(serde_json::from_str(&json)?)?;
+        let file: AccountsFile = serde_json::from_value(value)?;
+        let accounts = Self { path, accounts: file.accounts };
+        if from < SCHEMA.current() {
+            let old = migrate::keep_old(&accounts.path, from)?;
+            accounts.save()?;
+            println!("Upgraded {} to schema version {}; the old file is kept as {}", accounts.path.display(), SCHEMA.current(), old.display());
+        }
+        Ok(accounts)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let file = SCHEMA.stamp(serde_json::json!({ "accounts": self.accounts }));
+        fs::write(&self.path, serde_json::to_string_pretty(&file)?)
+    }
+
+    pub fn authenticate(&mut self, username: &str, handshake: &Handshake) -> Result<(), Challenge> {
+        let Some(account) = self.accounts.get(username) else {
+            return match (&handshake.password, handshake.register) {
+                // Guests keep working exactly as before accounts existed
+                (None, _) => Ok(()),
+                (Some(_), false) => Err(Challenge::new("register", None)),
+                (Some(password), true) => {
+                    let salt = hex(&random_bytes(16));
+                    let hash = hash_password(&salt, password);
+                    self.accounts.insert(username.to_string(), Account { salt, hash, totp_secret: None });
+                    self.save().map_err(|_| Challenge::new("register", Some("err.account_save")))
+                }
+            };
+        };
+        let Some(password) = &handshake.password else {
+            return Err(Challenge::new("password", None));
+        };
+        if !constant_time_eq(hash_password(&account.salt, password).as_bytes(), account.hash.as_bytes()) {
+            return Err(Challenge::new("password", Some("err.bad_password")));
+        }
+        if let Some(secret) = &account.totp_secret {
+            match &handshake.totp {
+                None => return Err(Challenge::new("totp", None)),
+                Some(code) if !verify_totp(secret, code) => return Err(Challenge::new("totp", Some("err.bad_totp"))),
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Accounts as stored on disk, for snapshots
+    pub fn export(&self) -> serde_json::Value {
+        serde_json::to_value(&self.accounts).unwrap_or_default()
+    }
+
+    // Adds a snapshot's accounts, replacing local ones with the same name, and saves
+    pub fn import(&mut self, accounts: serde_json::Value) -> io::Result<()> {
+        let accounts: HashMap<String, Account> = serde_json::from_value(accounts)?;
+        self.accounts.extend(accounts);
+        self.save()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.accounts.keys()
+    }
+
+    pub fn is_registered(&self, username: &str) -> bool {
+        self.accounts.keys().any(|name| name.eq_ignore_ascii_case(username))
+    }
+
+    // Returns the new base32 secret, or None for unregistered users
+    pub fn enable_totp(&mut self, username: &str) -> io::Result<Option<String>> {
+        let Some(account) = self.accounts.get_mut(username) else { return Ok(None) };
+        let secret = base32_encode(&random_bytes(20));
+        account.totp_secret = Some(secret.clone());
+        self.save()?;
+        Ok(Some(secret))
+    }
+
+    pub fn disable_totp(&mut self, username: &str) -> io::Result<bool> {
+        let Some(account) = self.accounts.get_mut(username) else { return Ok(false) };
+        account.totp_secret = None;
+        self.save()?;
+        Ok(true)
+    }
+}
+
+// Version 0 was the map of accounts on its own
+fn nest_accounts(accounts: Object) -> Result<Object, String> {
+    Ok(Map::from_iter([("accounts".to_string(), Value::Object(accounts))]))
+}
+
+pub fn otpauth_uri(username: &str, secret: &str) -> String {
+    format!("otpauth://totp/{}:{}?secret={}&issuer={}", ISSUER, username, secret, ISSUER)
+}
+
+fn hash_password(salt: &str, password: &str) -> String {
+    let mut digest = sha256(format!("{}:{}", salt, password).as_bytes());
+    for _ in 1..HASH_ROUNDS {
+        let mut input = digest.to_vec();
+        input.extend_from_slice(password.as_bytes());
+        digest = sha256(&input);
+    }
+    hex(&digest)
+}
+
+// v4 UUIDs come from the OS random source, so they double as salt and secret material
+fn random_bytes(len: usize) -> Vec<u8> {
+    std::iter::repeat_with(|| *uuid::Uuid::new_v4().as_bytes()).flatten().take(len).collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// RFC 6238 with HMAC-SHA1, 6 digits, accepting one step of clock drift either way
+fn verify_totp(secret: &str, code: &str) -> bool {
+    verify_totp_at(secret, code, SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default())
+}
+
+fn verify_totp_at(secret: &str, code: &str, unix_secs: u64) -> bool {
+    let (Some(key), Ok(code)) = (base32_decode(secret), code.trim().parse::<u32>()) else { return false };
+    let now = unix_secs / TOTP_STEP;
+    [now.saturating_sub(1), now, now + 1].iter().any(|&counter| totp(&key, counter) == code)
+}
+
+fn totp(key: &[u8], counter: u64) -> u32 {
+    let mac = hmac_sha1(key, &counter.to_be_bytes());
+    let offset = (mac[19] & 0x0f) as usize;
+    let value = u32::from_be_bytes([mac[offset] & 0x7f, mac[offset + 1], mac[offset + 2], mac[offset + 3]]);
+    value % 1_000_000
+}
+
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = u64::from_be_bytes([0, 0, 0, buf[0], buf[1], buf[2], buf[3], buf[4]]);
+        let chars = (chunk.len() * 8).div_ceil(5);
+        out.extend((0..chars).map(|i| BASE32[((bits >> (35 - i * 5)) & 31) as usize] as char));
+    }
+    out
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bits = 0u64;
+    let mut count = 0;
+    let mut out = Vec::new();
+    for c in text.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let value = BASE32.iter().position(|b| *b as char == c.to_ascii_uppercase())? as u64;
+        bits = (bits << 5) | value;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn hmac_sha1(key: &[u8], message: &[u8]) -> [u8; 20] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..20].copy_from_slice(&sha1(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    inner.extend_from_slice(message);
+    let mut outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend_from_slice(&sha1(&inner));
+    sha1(&outer)
+}
+
+// Message padding shared by SHA-1 and SHA-256
+fn pad(data: &[u8]) -> Vec<u8> {
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    msg
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    for block in pad(data).chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([block[i * 4], block[i * 4 + 1], block[i * 4 + 2], block[i * 4 + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut out = [0u8; 20];
+    for (chunk, word) in out.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    for block in pad(data).chunks(64) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([block[i * 4], block[i * 4 + 1], block[i * 4 + 2], block[i * 4 + 3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut out = [0u8; 32];
+    for (chunk, word) in out.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+    }
+
+    // FIPS 180-2 appendix examples, plus the empty message
+    #[test]
+    fn sha1_matches_the_fips_vectors() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(&sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+        assert_eq!(hex(&sha1(&[b'a'; 1_000_000])), "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+    }
+
+    #[test]
+    fn sha256_matches_the_fips_vectors() {
+        assert_eq!(hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(hex(&sha256(&[b'a'; 1_000_000])), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    // RFC 2202 section 3
+    #[test]
+    fn hmac_sha1_matches_rfc_2202() {
+        let cases: [(Vec<u8>, Vec<u8>, &str); 7] = [
+            (vec![0x0b; 20], b"Hi There".to_vec(), "b617318655057264e28bc0b6fb378c8ef146be00"),
+            (b"Jefe".to_vec(), b"what do ya want for nothing?".to_vec(), "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"),
+            (vec![0xaa; 20], vec![0xdd; 50], "125d7342b9ac11cd91a39af48aa17b4f63f175d3"),
+            (unhex("0102030405060708090a0b0c0d0e0f10111213141516171819"), vec![0xcd; 50], "4c9007f4026250c6bc8414f9bf50c86c2d7235da"),
+            (vec![0x0c; 20], b"Test With Truncation".to_vec(), "4c1a03424b55e07fe7f27be1d58bb9324a9a5a04"),
+            (vec![0xaa; 80], b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(), "aa4ae5e15272d00e95705637ce8a3b55ed402112"),
+            (vec![0xaa; 80], b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data".to_vec(), "e8e99d0f45237d786d6bbaa7965c7808bbff1a91"),
+        ];
+        for (key, message, expected) in cases {
+            assert_eq!(hex(&hmac_sha1(&key, &message)), expected);
+        }
+    }
+
+    // RFC 6238 appendix B, SHA-1, cut to the 6 digits used here
+    #[test]
+    fn totp_matches_rfc_6238() {
+        let key = b"12345678901234567890";
+        for (time, expected) in [(59, 287082), (1111111109, 81804), (1111111111, 50471), (1234567890, 5924), (2000000000, 279037)] {
+            assert_eq!(totp(key, time / TOTP_STEP), expected, "at {}", time);
+        }
+    }
+
+    #[test]
+    fn codes_one_step_either_side_are_accepted() {
+        let secret = base32_encode(b"12345678901234567890");
+        assert_eq!(base32_decode(&secret).unwrap(), b"12345678901234567890");
+        let now = 1111111111;
+        let code_at = |time: u64| format!("{:06}", totp(b"12345678901234567890", time / TOTP_STEP));
+        assert_eq!(code_at(now), "050471");
+        for offset in [-30i64, 0, 30] {
+            assert!(verify_totp_at(&secret, &code_at(now.saturating_add_signed(offset)), now), "offset {}", offset);
+        }
+        for offset in [-90i64, -60, 60, 90] {
+            assert!(!verify_totp_at(&secret, &code_at(now.saturating_add_signed(offset)), now), "offset {}", offset);
+        }
+        assert!(verify_totp_at(&secret, " 050471 ", now));
+        assert!(!verify_totp_at(&secret, "five", now));
+        assert!(!verify_totp_at("not base32!", "050471", now));
+    }
+
+    #[test]
+    fn base32_matches_rfc_4648() {
+        for (plain, encoded) in [("", ""), ("f", "MY"), ("fo", "MZXQ"), ("foo", "MZXW6"), ("foob", "MZXW6YQ"), ("fooba", "MZXW6YTB"), ("foobar", "MZXW6YTBOI")] {
+            assert_eq!(base32_encode(plain.as_bytes()), encoded);
+            assert_eq!(base32_decode(&format!("{}==", encoded.to_lowercase())).unwrap(), plain.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use common::RoomName;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const MAX_ROOMS: usize = 10; // In one user's list, and in the config's `auto_join`
+
+// Rooms each user starts a session with: they land in the first one they may enter, and
+// clients keep the others at hand. Users who never changed theirs get the config's `auto_join`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct AutoJoin {
+    lists: HashMap<String, Vec<RoomName>>,
+}
+
+impl AutoJoin {
+    pub fn rooms(&self, username: &str, defaults: &[RoomName]) -> Vec<RoomName> {
+        self.lists.get(username).cloned().unwrap_or_else(|| defaults.to_vec())
+    }
+
+    // False when it is there already, or the list is full
+    pub fn add(&mut self, username: &str, room: &RoomName, defaults: &[RoomName]) -> bool {
+        let list = self.lists.entry(username.to_string()).or_insert_with(|| defaults.to_vec());
+        if list.contains(room) || list.len() >= MAX_ROOMS {
+            return false;
+        }
+        list.push(room.clone());
+        true
+    }
+
+    // False when it wasn't there
+    pub fn remove(&mut self, username: &str, room: &RoomName, defaults: &[RoomName]) -> bool {
+        let list = self.lists.entry(username.to_string()).or_insert_with(|| defaults.to_vec());
+        let before = list.len();
+        list.retain(|r| r != room);
+        list.len() < before
+    }
+
+    pub fn set(&mut self, username: &str, mut rooms: Vec<RoomName>) {
+        rooms.truncate(MAX_ROOMS);
+        self.lists.insert(username.to_string(), rooms);
+    }
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// `<prefix>-20250101-120000.<ext>`, so generations sort by name
+pub fn generation_path(dir: &Path, prefix: &str, ext: &str, at: DateTime<Utc>) -> PathBuf {
+    dir.join(format!("{}-{}.{}", prefix, at.format("%Y%m%d-%H%M%S"), ext))
+}
+
+// Deletes all but the newest `keep` generations with this prefix and extension; returns those deleted
+pub fn prune(dir: &Path, prefix: &str, ext: &str, keep: usize) -> io::Result<Vec<PathBuf>> {
+    let mut generations: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == ext))
+        .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(&format!("{}-", prefix))))
+        .collect();
+    generations.sort();
+    generations.truncate(generations.len().saturating_sub(keep));
+    for old in &generations {
+        fs::remove_file(old)?;
+    }
+    Ok(generations)
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+// Ends a connection. Whatever ends one, whether a kick, a ban, a server drain, the client
+// leaving or a failed read or write, cancels its token. The reader loop and the writer task
+// both watch it, so every connection goes down the same way. Like tokio-util's type of the
+// same name, which this build doesn't have, plus the reason: the first one given is kept.
+#[derive(Clone)]
+pub struct CancellationToken(Arc<watch::Sender<Option<&'static str>>>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self(Arc::new(watch::Sender::new(None)))
+    }
+
+    pub fn cancel(&self, reason: &'static str) {
+        self.0.send_if_modified(|cancelled| {
+            let first = cancelled.is_none();
+            if first {
+                *cancelled = Some(reason);
+            }
+            first
+        });
+    }
+
+    // Resolves with the reason once cancelled, straight away if it already is
+    pub async fn cancelled(&self) -> &'static str {
+        let mut rx = self.0.subscribe();
+        // The sender lives as long as `self`, so this only ends by cancelling
+        let reason = rx.wait_for(Option::is_some).await.map(|reason| reason.unwrap_or_default());
+        match reason {
+            Ok(reason) => reason,
+            Err(_) => std::future::pending().await,
+        }
+    }
+
+    // Cancels with `reason` when dropped, unless already cancelled; for tasks that may end in a panic
+    pub fn drop_guard(&self, reason: &'static str) -> DropGuard {
+        DropGuard { token: self.clone(), reason }
+    }
+}
+
+pub struct DropGuard {
+    token: CancellationToken,
+    reason: &'static str,
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        self.token.cancel(self.reason);
+    }
+}
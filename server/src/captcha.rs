@@ -0,0 +1,45 @@
+use common::i18n;
+use serde::Deserialize;
+
+// Short words that read the same in every supported language
+const WORDS: &[&str] = &["lemon", "piano", "tiger", "robot", "radio", "pizza", "camera", "banana", "guitar", "taxi", "hotel", "tomato"];
+
+// `captcha` in the server config: the question guests and users without an account answer
+// before they are let in
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptchaKind {
+    Arithmetic, // "What is 4 + 7?"
+    Words,      // "Type lemon backwards"
+}
+
+pub struct Captcha {
+    key: &'static str,
+    args: Vec<String>,
+    answer: String,
+}
+
+impl Captcha {
+    pub fn new(kind: CaptchaKind) -> Self {
+        let random = uuid::Uuid::new_v4().as_u128();
+        match kind {
+            CaptchaKind::Arithmetic => {
+                let (a, b) = (2 + random % 18, 2 + (random >> 8) % 9);
+                Self { key: "sys.captcha_sum", args: vec![a.to_string(), b.to_string()], answer: (a + b).to_string() }
+            }
+            CaptchaKind::Words => {
+                let word = WORDS[(random % WORDS.len() as u128) as usize];
+                Self { key: "sys.captcha_backwards", args: vec![word.to_string()], answer: word.chars().rev().collect() }
+            }
+        }
+    }
+
+    pub fn question(&self, locale: &str) -> String {
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        i18n::trf(locale, self.key, &args)
+    }
+
+    pub fn accepts(&self, answer: &str) -> bool {
+        answer.trim().eq_ignore_ascii_case(&self.answer)
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+// Opt-in fault injection for resilience testing (`CHAOS=<percent>`): that share of
+// connections gets slow writes, lost room messages and an early disconnect, so client
+// reconnects and gap recovery can be exercised against a real server
+#[derive(Clone, Copy)]
+pub struct Chaos {
+    pub percent: u8,
+}
+
+// What happens to one unlucky connection
+#[derive(Clone, Copy)]
+pub struct ClientChaos {
+    pub lifetime: Duration, // Dropped without warning after this long
+}
+
+impl Chaos {
+    pub fn parse(value: &str) -> Option<Self> {
+        value.trim().trim_end_matches('%').parse().ok().filter(|p| (1..=100).contains(p)).map(|percent| Self { percent })
+    }
+
+    // Decides, once per connection, whether it is affected
+    pub fn pick(&self) -> Option<ClientChaos> {
+        (roll(100) < self.percent as u64).then(|| ClientChaos { lifetime: Duration::from_secs(10 + roll(110)) })
+    }
+}
+
+impl ClientChaos {
+    // One flush in five is held back for up to a second
+    pub fn write_delay(&self) -> Option<Duration> {
+        (roll(5) == 0).then(|| Duration::from_millis(50 + roll(950)))
+    }
+
+    // One room message in twenty starts a lag spike: it and up to nine more are skipped,
+    // as happens when a client falls behind the broadcast channel
+    pub fn lag(&self) -> usize {
+        if roll(20) == 0 { 1 + roll(10) as usize } else { 0 }
+    }
+}
+
+// v4 UUIDs come from the OS random source; no other randomness is available to this build
+fn roll(below: u64) -> u64 {
+    (uuid::Uuid::new_v4().as_u128() % below as u128) as u64
+}
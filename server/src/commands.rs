@@ -0,0 +1,84 @@
+use common::{CommandInfo, ServerCapabilities};
+
+// Who a command is offered to; the handlers check again either way
+#[derive(Clone, Copy, PartialEq)]
+enum Audience {
+    Everyone,
+    Moderators, // Of the room the user is in, and admins
+    Admins,
+}
+
+// The commands clients offer as `/` is typed: name, arguments, the catalog key of a one-line
+// description, the feature it belongs to, and who it is for. Ones that only clients send
+// (`/answer`, `/read`, `/ping`, `/console`) are left out.
+const COMMANDS: &[(&str, &str, &str, Option<&str>, Audience)] = &[
+    ("/join", "<room> | --code <code>", "help.join", None, Audience::Everyone),
+    ("/rooms", "", "help.rooms", None, Audience::Everyone),
+    ("/discover", "[keyword]", "help.discover", Some(ServerCapabilities::DISCOVER), Audience::Everyone),
+    ("/autojoin", "[add|remove [room]|clear]", "help.autojoin", Some(ServerCapabilities::AUTO_JOIN), Audience::Everyone),
+    ("/users", "", "help.users", None, Audience::Everyone),
+    ("/msg", "<user> <text>", "help.msg", None, Audience::Everyone),
+    ("/forward", "<message id> <#room|@user>", "help.forward", Some(ServerCapabilities::FORWARD), Audience::Everyone),
+    ("/ttl", "[30m|24h|7d|off]", "help.ttl", Some(ServerCapabilities::TTL), Audience::Everyone),
+    ("/invitecode", "create <24h> | list | revoke <code> | off", "help.invitecode", Some(ServerCapabilities::INVITE_CODES), Audience::Everyone),
+    ("/group", "[list [name]] | create|delete <name> | add|remove <name> <user> | allow|deny [name]", "help.group", Some(ServerCapabilities::GROUPS), Audience::Everyone),
+    ("/roomset", "[post|invite <level>] | mod|demod|voice|devoice <user> | moderation|karma|broadcast on|off | description|tags [...]", "help.roomset", Some(ServerCapabilities::PERMISSIONS), Audience::Everyone),
+    ("/activity", "[room]", "help.activity", Some(ServerCapabilities::ACTIVITY), Audience::Everyone),
+    ("/thank", "<user>", "help.thank", Some(ServerCapabilities::KARMA), Audience::Everyone),
+    ("/leaderboard", "", "help.leaderboard", Some(ServerCapabilities::KARMA), Audience::Everyone),
+    ("/trivia", "start|stop|scores", "help.trivia", Some(ServerCapabilities::GAMES), Audience::Everyone),
+    ("/roll", "<dice>", "help.roll", Some(ServerCapabilities::DICE), Audience::Everyone),
+    ("/flip", "", "help.choose", Some(ServerCapabilities::DICE), Audience::Everyone),
+    ("/choose", "<option>|<option>[|...]", "help.choose", Some(ServerCapabilities::DICE), Audience::Everyone),
+    ("/time", "<when>", "help.time", Some(ServerCapabilities::TIME), Audience::Everyone),
+    ("/translate", "<id> <language>", "help.translate", Some(ServerCapabilities::TRANSLATE), Audience::Everyone),
+    ("/away", "[reason]", "help.away", Some(ServerCapabilities::PRESENCE), Audience::Everyone),
+    ("/back", "", "help.back", Some(ServerCapabilities::PRESENCE), Audience::Everyone),
+    ("/history", "<#room/number> | --archived <from> [to]", "help.history", Some(ServerCapabilities::HISTORY), Audience::Everyone),
+    ("/star", "<id>", "help.star_id", Some(ServerCapabilities::STARS), Audience::Everyone),
+    ("/unstar", "<id>", "help.unstar", Some(ServerCapabilities::STARS), Audience::Everyone),
+    ("/starred", "", "help.starred", Some(ServerCapabilities::STARS), Audience::Everyone),
+    ("/report", "<id> [reason]", "help.report", Some(ServerCapabilities::MODERATION), Audience::Everyone),
+    ("/pins", "", "help.pins", Some(ServerCapabilities::MODERATION), Audience::Everyone),
+    ("/pin", "<id>", "help.pin_id", Some(ServerCapabilities::MODERATION), Audience::Everyone),
+    ("/unpin", "<id>", "help.unpin", Some(ServerCapabilities::MODERATION), Audience::Everyone),
+    ("/warn", "<user> <reason>", "help.warn", Some(ServerCapabilities::MODERATION), Audience::Everyone),
+    ("/warnings", "[user] | clear <user>", "help.warnings", Some(ServerCapabilities::MODERATION), Audience::Everyone),
+    ("/profile", "[user] | set <field> [value]", "help.profile", Some(ServerCapabilities::PROFILES), Audience::Everyone),
+    ("/totp", "on|off", "help.totp", Some(ServerCapabilities::TOTP), Audience::Everyone),
+    ("/conninfo", "", "help.conninfo", None, Audience::Everyone),
+    ("/help", "", "help.help", None, Audience::Everyone),
+    ("/quit", "", "help.quit", None, Audience::Everyone),
+    ("/kick", "<user>", "help.kick", None, Audience::Admins),
+    ("/mute", "<user> <30m|2h|1d>", "help.mute", Some(ServerCapabilities::MODERATION), Audience::Admins),
+    ("/unmute", "<user>", "help.unmute", Some(ServerCapabilities::MODERATION), Audience::Admins),
+    ("/ban", "<user> <30m|2h|1d>", "help.ban", Some(ServerCapabilities::MODERATION), Audience::Admins),
+    ("/unban", "<user>", "help.unban", Some(ServerCapabilities::MODERATION), Audience::Admins),
+    ("/modpanel", "", "help.mod_panel", Some(ServerCapabilities::MODERATION), Audience::Moderators),
+    ("/flagged", "[dismiss <id>]", "help.flagged", Some(ServerCapabilities::MODERATION), Audience::Admins),
+    ("/linkhits", "", "help.linkhits", Some(ServerCapabilities::MODERATION), Audience::Admins),
+    ("/reserve", "<name|pattern*>", "help.reserve", None, Audience::Admins),
+    ("/unreserve", "<name|pattern*>", "help.unreserve", None, Audience::Admins),
+    ("/mirror", "<from> <to> [both]", "help.mirror", Some(ServerCapabilities::MIRRORS), Audience::Admins),
+    ("/unmirror", "<from> <to>", "help.unmirror", Some(ServerCapabilities::MIRRORS), Audience::Admins),
+    ("/maintenance", "<5m|1h> [reason] | off", "help.maintenance", None, Audience::Admins),
+    ("/stats", "", "help.stats", None, Audience::Admins),
+    ("/snapshot", "", "help.snapshot", None, Audience::Admins),
+    ("/reload", "", "help.reload", None, Audience::Admins),
+    ("/audit", "verify", "help.audit", None, Audience::Admins),
+];
+
+// What this server offers a user: commands of features it has, moderator ones to those the
+// capabilities call a moderator, and admin ones only to admins
+pub fn registry(capabilities: &ServerCapabilities, admin: bool) -> Vec<CommandInfo> {
+    let offered = |audience: Audience| match audience {
+        Audience::Everyone => true,
+        Audience::Moderators => admin || capabilities.moderator,
+        Audience::Admins => admin,
+    };
+    COMMANDS
+        .iter()
+        .filter(|(_, _, _, feature, audience)| feature.is_none_or(|feature| capabilities.supports(feature)) && offered(*audience))
+        .map(|(name, args, help, _, _)| CommandInfo { name: name.to_string(), args: args.to_string(), help: help.to_string() })
+        .collect()
+}
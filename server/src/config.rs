@@ -0,0 +1,228 @@
+use crate::archive::ArchiveSettings;
+use crate::autojoin;
+use crate::captcha::CaptchaKind;
+use crate::links::LinkSettings;
+use crate::moderation::ModerationSettings;
+use crate::onboarding::OnboardingSettings;
+use crate::preview::PreviewSettings;
+use crate::rooms::{self, Level};
+use crate::translate::TranslateSettings;
+use crate::trivia::TriviaSettings;
+use common::RoomName;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+// Settings that can change while the server runs, read from `CONFIG_FILE` at startup
+// and again on SIGHUP or `/reload`
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub motd: Option<String>,       // Sent to each user when they connect
+    pub rate_limit: Option<usize>,  // Chat messages per user per minute
+    pub filters: Vec<String>,       // Words masked in chat messages, ignoring case
+    pub bans: Vec<String>,          // Usernames turned away, and disconnected on reload
+    pub rooms: HashMap<RoomName, RoomSettings>,
+    pub backup: Option<BackupSettings>,
+    pub slow_clients: Overflow,
+    pub history_limit: usize,           // Messages kept per room
+    pub history_idle: Option<String>,   // As for `/ttl`: rooms nobody has posted in or joined for this long lose their history
+    pub history_rooms: Option<usize>,   // Rooms with history beyond this lose it, least recently active first
+    pub escalation: Vec<Escalation>,    // What `/warn` leads to as warnings add up
+    pub moderation: Option<ModerationSettings>, // Checks on room messages before they are posted
+    pub previews: Option<PreviewSettings>,     // Link previews, off without this
+    pub links: Option<LinkSettings>,           // Screening of links against blocklists
+    pub captcha: Option<CaptchaKind>,          // Asked of guests and users without an account
+    pub archive: Option<ArchiveSettings>,      // Where history goes instead of being dropped
+    pub trivia: Option<TriviaSettings>,        // Questions and timing for `/trivia`
+    pub translate: Option<TranslateSettings>,  // The service behind `/translate`, off without this
+    pub onboarding: OnboardingSettings,        // Questions put to users on their first visit
+    pub auto_join: Vec<RoomName>,              // Where users without their own `/autojoin` list start
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            motd: None,
+            rate_limit: None,
+            filters: Vec::new(),
+            bans: Vec::new(),
+            rooms: HashMap::new(),
+            backup: None,
+            slow_clients: Overflow::default(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            history_idle: None,
+            history_rooms: None,
+            escalation: default_escalation(),
+            moderation: None,
+            previews: None,
+            links: None,
+            captcha: None,
+            archive: None,
+            trivia: None,
+            translate: None,
+            onboarding: OnboardingSettings::default(),
+            auto_join: Vec::new(),
+        }
+    }
+}
+
+// What happens when a client's outgoing queue is full; read as each client connects
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    #[default]
+    Disconnect, // It can reconnect and catch up from history
+    Drop,       // Keep the connection, lose what doesn't fit
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomSettings {
+    pub ttl: Option<String>, // As for `/ttl`: "24h", or "off"
+    pub post: Option<Level>, // As for `/roomset`
+    pub invite: Option<Level>,
+    pub moderation: Option<bool>,
+    pub karma: Option<bool>, // `++` and `/thank` count toward a leaderboard
+    pub description: Option<String>, // As for `/roomset description`
+    pub tags: Option<Vec<String>>,
+    pub broadcast: Option<bool>, // As for `/roomset broadcast`
+    pub feed: bool, // Published as RSS on the HTTP listener
+}
+
+// Reaching `warnings` warnings brings on `action`; a mute or ban lasts `for`, as for `/ttl`
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Escalation {
+    pub warnings: usize,
+    pub action: Penalty,
+    #[serde(rename = "for")]
+    pub lasting: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Penalty {
+    Mute, // No chat, forwards or private messages
+    Kick,
+    Ban, // Disconnected and turned away at login
+}
+
+fn default_escalation() -> Vec<Escalation> {
+    let step = |warnings, action, lasting: Option<&str>| Escalation { warnings, action, lasting: lasting.map(str::to_string) };
+    vec![step(2, Penalty::Mute, Some("10m")), step(3, Penalty::Kick, None), step(5, Penalty::Ban, Some("1d"))]
+}
+
+// Snapshots and rotated audit logs, kept side by side in `dir`
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BackupSettings {
+    pub every: String, // As for `/ttl`: "6h", "1d"
+    #[serde(default = "default_keep")]
+    pub keep: usize, // Generations of each
+    #[serde(default = "default_backup_dir")]
+    pub dir: PathBuf,
+}
+
+fn default_keep() -> usize {
+    7
+}
+
+fn default_backup_dir() -> PathBuf {
+    "backups".into()
+}
+
+impl Config {
+    // A missing file means defaults, but a broken one is an error so a typo never
+    // silently drops every ban
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let config: Self = serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if config.auto_join.len() > autojoin::MAX_ROOMS {
+            return Err(format!("{}: auto_join can have at most {} rooms", path.display(), autojoin::MAX_ROOMS));
+        }
+        for (room, settings) in &config.rooms {
+            if let Some(ttl) = settings.ttl.as_deref().filter(|ttl| *ttl != "off" && rooms::parse_lifetime(ttl).is_none()) {
+                return Err(format!("{}: bad ttl \"{}\" for #{}", path.display(), ttl, room));
+            }
+            if settings.tags.as_ref().is_some_and(|tags| rooms::parse_tags(tags.iter().map(String::as_str)).is_none()) {
+                return Err(format!("{}: #{} has more than {} tags, or one that isn't a word", path.display(), room, rooms::MAX_TAGS));
+            }
+            if settings.description.as_ref().is_some_and(|description| description.chars().count() > rooms::MAX_DESCRIPTION_LEN) {
+                return Err(format!("{}: the description of #{} is over {} characters", path.display(), room, rooms::MAX_DESCRIPTION_LEN));
+            }
+        }
+        if let Some(backup) = &config.backup {
+            if rooms::parse_lifetime(&backup.every).is_none() || backup.keep == 0 {
+                return Err(format!("{}: backups need an interval like \"24h\" and keep at least 1", path.display()));
+            }
+        }
+        if config.history_limit == 0 || config.history_idle.as_deref().is_some_and(|idle| rooms::parse_lifetime(idle).is_none()) {
+            return Err(format!("{}: history_limit must be at least 1 and history_idle like \"7d\"", path.display()));
+        }
+        for step in &config.escalation {
+            let lasting = step.lasting.as_deref().map(rooms::parse_lifetime);
+            let valid = match step.action {
+                Penalty::Kick => lasting.is_none(),
+                Penalty::Mute | Penalty::Ban => matches!(lasting, Some(Some(_))),
+            };
+            if step.warnings == 0 || !valid {
+                return Err(format!("{}: escalation steps need warnings of at least 1, and a \"for\" like \"10m\" for mutes and bans only", path.display()));
+            }
+        }
+        if config.archive.as_ref().and_then(|archive| archive.after.as_deref()).is_some_and(|after| rooms::parse_lifetime(after).is_none()) {
+            return Err(format!("{}: archive after must be like \"30d\"", path.display()));
+        }
+        if config.moderation.as_ref().is_some_and(|moderation| !moderation.valid_url()) {
+            return Err(format!("{}: the moderation url must be http://", path.display()));
+        }
+        if config.translate.as_ref().is_some_and(|translate| !translate.valid_url()) {
+            return Err(format!("{}: the translate url must be http://", path.display()));
+        }
+        if config.links.as_ref().is_some_and(|links| !links.valid_url()) {
+            return Err(format!("{}: the links lookup url must be http://", path.display()));
+        }
+        Ok(config)
+    }
+
+    // The step for the most warnings up to `count`, so warnings past the last step repeat it
+    pub fn escalation(&self, count: usize) -> Option<(Penalty, Option<chrono::Duration>)> {
+        let step = self.escalation.iter().filter(|step| step.warnings <= count).max_by_key(|step| step.warnings)?;
+        Some((step.action, step.lasting.as_deref().and_then(rooms::parse_lifetime)))
+    }
+
+    pub fn is_banned(&self, username: &str) -> bool {
+        self.bans.iter().any(|ban| ban.eq_ignore_ascii_case(username))
+    }
+
+    // Replaces each filtered word with asterisks, leaving punctuation and spacing alone
+    pub fn mask(&self, text: &str) -> String {
+        if self.filters.is_empty() {
+            return text.to_string();
+        }
+        let mut masked = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(char::is_alphanumeric) {
+            masked.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find(|c: char| !c.is_alphanumeric()).unwrap_or(rest.len());
+            let word = &rest[..end];
+            if self.filters.iter().any(|filter| filter.to_lowercase() == word.to_lowercase()) {
+                masked.push_str(&"*".repeat(word.chars().count()));
+            } else {
+                masked.push_str(word);
+            }
+            rest = &rest[end..];
+        }
+        masked.push_str(rest);
+        masked
+    }
+}
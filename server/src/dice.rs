@@ -0,0 +1,82 @@
+// `/roll`, `/flip` and `/choose`, decided by the server so everyone in the room sees the
+// same result and nobody can claim a better one
+
+const MAX_DICE: u32 = 100; // Per term, so a roll stays readable
+const MAX_SIDES: u32 = 1000;
+const MAX_TERMS: usize = 10;
+
+// A die to throw `count` times, or a number to add, each with its sign
+enum Term {
+    Dice { count: u32, sides: u32, negative: bool },
+    Number(i64),
+}
+
+// A parsed expression such as "2d20+3" or "d6-1+1d4"
+pub struct Expression(Vec<Term>);
+
+impl Expression {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+        let mut terms = Vec::new();
+        // Split before every sign, keeping it with the term after it
+        let mut start = 0;
+        for (i, c) in text.char_indices().skip(1).chain([(text.len(), '+')]) {
+            if c != '+' && c != '-' {
+                continue;
+            }
+            let (negative, term) = match &text[start..i] {
+                term if term.starts_with('-') => (true, &term[1..]),
+                term => (false, term.strip_prefix('+').unwrap_or(term)),
+            };
+            terms.push(Term::parse(term, negative)?);
+            start = i;
+        }
+        let dice = terms.iter().any(|term| matches!(term, Term::Dice { .. }));
+        (dice && terms.len() <= MAX_TERMS).then_some(Self(terms))
+    }
+
+    // Throws the dice with `random` giving a number from 1 to its argument: "14 + 7 + 3 = 24"
+    pub fn roll(&self, mut random: impl FnMut(u32) -> u32) -> String {
+        let mut total: i64 = 0;
+        let mut shown = String::new();
+        for term in &self.0 {
+            let (negative, values) = match *term {
+                Term::Dice { count, sides, negative } => (negative, (0..count).map(|_| random(sides) as i64).collect()),
+                Term::Number(n) => (n < 0, vec![n.abs()]),
+            };
+            for value in values {
+                total += if negative { -value } else { value };
+                let sign = match (shown.is_empty(), negative) {
+                    (true, false) => "",
+                    (true, true) => "-",
+                    (false, false) => " + ",
+                    (false, true) => " - ",
+                };
+                shown.push_str(&format!("{}{}", sign, value));
+            }
+        }
+        format!("{} = {}", shown, total)
+    }
+}
+
+impl Term {
+    fn parse(term: &str, negative: bool) -> Option<Self> {
+        let Some((count, sides)) = term.split_once('d') else {
+            let n: i64 = term.parse().ok().filter(|n: &i64| *n <= i64::from(u32::MAX))?;
+            return Some(Term::Number(if negative { -n } else { n }));
+        };
+        let count = if count.is_empty() { 1 } else { count.parse().ok()? };
+        let sides = sides.parse().ok()?;
+        ((1..=MAX_DICE).contains(&count) && (1..=MAX_SIDES).contains(&sides)).then_some(Term::Dice { count, sides, negative })
+    }
+}
+
+// A number from 1 to `n`
+pub fn random(n: u32) -> u32 {
+    (uuid::Uuid::new_v4().as_u128() % u128::from(n)) as u32 + 1
+}
+
+// `/choose a|b|c`: the options, trimmed, with empty ones left out
+pub fn options(text: &str) -> Vec<&str> {
+    text.split('|').map(str::trim).filter(|option| !option.is_empty()).collect()
+}
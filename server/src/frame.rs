@@ -0,0 +1,54 @@
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+// Longest line a client may send, newline included; the rest of a longer line is skipped
+pub const MAX_LINE: usize = 64 * 1024;
+
+// One line from a client
+pub enum Frame {
+    Line(String),
+    TooLong,
+    NotUtf8,
+    Closed,
+}
+
+// Like `read_line`, but never buffers more than `MAX_LINE` bytes. Also returns the bytes
+// consumed, for connection stats.
+pub async fn read_frame<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<(Frame, usize)> {
+    let mut line = Vec::new();
+    let mut consumed = 0;
+    let mut too_long = false;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            // A last line without a newline still counts
+            if consumed == 0 {
+                return Ok((Frame::Closed, 0));
+            }
+            break;
+        }
+        let (chunk, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(end) => (&available[..=end], true),
+            None => (available, false),
+        };
+        if !too_long && line.len() + chunk.len() > MAX_LINE {
+            too_long = true;
+            line = Vec::new();
+        }
+        if !too_long {
+            line.extend_from_slice(chunk);
+        }
+        let len = chunk.len();
+        reader.consume(len);
+        consumed += len;
+        if done {
+            break;
+        }
+    }
+    let frame = match (too_long, String::from_utf8(line)) {
+        (true, _) => Frame::TooLong,
+        (false, Ok(line)) => Frame::Line(line),
+        (false, Err(_)) => Frame::NotUtf8,
+    };
+    Ok((frame, consumed))
+}
@@ -0,0 +1,135 @@
+use common::RoomName;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+// Room games, such as `/trivia`. Each room runs at most one game at a time. The game sees the
+// room's chat messages once they are posted, and a clock tick about once a second; what it
+// says back goes to the room as system lines, so it is translated like everything else.
+
+// A line a game says: a catalog key and its arguments
+pub struct Say {
+    pub key: &'static str,
+    pub args: Vec<String>,
+}
+
+impl Say {
+    pub fn new(key: &'static str, args: &[&str]) -> Self {
+        Self { key, args: args.iter().map(|arg| arg.to_string()).collect() }
+    }
+}
+
+pub trait Game: Send {
+    fn name(&self) -> &'static str;
+    fn start(&mut self, now: Instant) -> Vec<Say>;
+    // A chat message someone posted in the room
+    fn message(&mut self, user: &str, text: &str, now: Instant) -> Vec<Say>;
+    fn tick(&mut self, now: Instant) -> Vec<Say>;
+    // Standings so far, for `/<game> scores`
+    fn scores(&self) -> Say;
+    // A finished game is dropped, and the room can start another
+    fn finished(&self) -> bool;
+}
+
+// Turns for games played in timed rounds: each one ends when it is won or runs out of time
+pub struct Rounds {
+    pub round: usize, // From 1, once started
+    pub total: usize,
+    length: Duration,
+    deadline: Option<Instant>, // None between rounds
+}
+
+impl Rounds {
+    pub fn new(total: usize, length: Duration) -> Self {
+        Self { round: 0, total, length, deadline: None }
+    }
+
+    // Starts the next round; false when they have all been played
+    pub fn next(&mut self, now: Instant) -> bool {
+        self.deadline = None;
+        if self.round == self.total {
+            return false;
+        }
+        self.round += 1;
+        self.deadline = Some(now + self.length);
+        true
+    }
+
+    pub fn open(&self) -> bool {
+        self.deadline.is_some()
+    }
+
+    pub fn expired(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|deadline| now >= deadline)
+    }
+
+    pub fn length(&self) -> Duration {
+        self.length
+    }
+}
+
+// Points per player
+#[derive(Default)]
+pub struct Scoreboard(BTreeMap<String, u32>);
+
+impl Scoreboard {
+    pub fn add(&mut self, player: &str) -> u32 {
+        let score = self.0.entry(player.to_string()).or_default();
+        *score += 1;
+        *score
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // "alice 3, bob 1": highest first, by name on a tie
+    pub fn standings(&self) -> String {
+        let mut players: Vec<(&String, &u32)> = self.0.iter().collect();
+        players.sort_by(|(a, x), (b, y)| y.cmp(x).then(a.cmp(b)));
+        players.iter().map(|(player, score)| format!("{} {}", player, score)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+// What is being played in each room, with who started it
+#[derive(Default)]
+pub struct Games {
+    rooms: HashMap<RoomName, (Box<dyn Game>, String)>,
+}
+
+impl Games {
+    // None while the room is already playing something
+    pub fn start(&mut self, room: &RoomName, starter: &str, mut game: Box<dyn Game>, now: Instant) -> Option<Vec<Say>> {
+        if self.rooms.contains_key(room) {
+            return None;
+        }
+        let said = game.start(now);
+        self.rooms.insert(room.clone(), (game, starter.to_string()));
+        Some(said)
+    }
+
+    // The name of the game and who started it
+    pub fn playing(&self, room: &RoomName) -> Option<(&'static str, &str)> {
+        self.rooms.get(room).map(|(game, starter)| (game.name(), starter.as_str()))
+    }
+
+    pub fn stop(&mut self, room: &RoomName) -> Option<Box<dyn Game>> {
+        self.rooms.remove(room).map(|(game, _)| game)
+    }
+
+    pub fn scores(&self, room: &RoomName) -> Option<Say> {
+        self.rooms.get(room).map(|(game, _)| game.scores())
+    }
+
+    pub fn message(&mut self, room: &RoomName, user: &str, text: &str, now: Instant) -> Vec<Say> {
+        let Some((game, _)) = self.rooms.get_mut(room) else { return Vec::new() };
+        let said = game.message(user, text, now);
+        self.rooms.retain(|_, (game, _)| !game.finished());
+        said
+    }
+
+    pub fn tick(&mut self, now: Instant) -> Vec<(RoomName, Vec<Say>)> {
+        let said = self.rooms.iter_mut().map(|(room, (game, _))| (room.clone(), game.tick(now))).filter(|(_, said)| !said.is_empty()).collect();
+        self.rooms.retain(|_, (game, _)| !game.finished());
+        said
+    }
+}
@@ -0,0 +1,201 @@
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+// A reader for MaxMind DB files (GeoLite2 Country, City and ASN, or anything in that format),
+// loaded into memory at startup and never updated or fetched. See
+// https://maxmind.github.io/MaxMind-DB/ for the format.
+
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+const MAX_DEPTH: usize = 32; // Nesting and pointer chains; real databases need a handful
+
+struct Database {
+    bytes: Vec<u8>,
+    node_count: usize,
+    record_size: usize, // Bits: 24, 28 or 32
+    ip_version: u16,
+    data_start: usize,
+}
+
+// Country and network of an address, as far as the loaded databases know
+#[derive(Default, PartialEq, Debug)]
+pub struct Origin {
+    pub country: Option<String>, // ISO code, e.g. "DE"
+    pub asn: Option<u64>,
+    pub org: Option<String>,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let asn = self.asn.map(|asn| format!("AS{}", asn));
+        let parts: Vec<&str> = [self.country.as_deref(), asn.as_deref(), self.org.as_deref()].into_iter().flatten().collect();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+// Several databases can be loaded at once, such as a country one and an ASN one
+#[derive(Default)]
+pub struct GeoIp {
+    databases: Vec<Database>,
+}
+
+impl GeoIp {
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        self.databases.push(Database::load(path)?);
+        Ok(())
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Option<Origin> {
+        let mut origin = Origin::default();
+        for record in self.databases.iter().filter_map(|db| db.lookup(ip)) {
+            origin.country = origin.country.or_else(|| record["country"]["iso_code"].as_str().map(str::to_string));
+            origin.asn = origin.asn.or_else(|| record["autonomous_system_number"].as_u64());
+            origin.org = origin.org.or_else(|| record["autonomous_system_organization"].as_str().map(str::to_string));
+        }
+        (origin != Origin::default()).then_some(origin)
+    }
+}
+
+fn invalid(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, why.to_string())
+}
+
+impl Database {
+    fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let marker = bytes.windows(METADATA_MARKER.len()).rposition(|window| window == METADATA_MARKER).ok_or_else(|| invalid("not a MaxMind DB file"))?;
+        let metadata_start = marker + METADATA_MARKER.len();
+        let (metadata, _) = decode(&bytes[metadata_start..], 0, 0).ok_or_else(|| invalid("unreadable metadata"))?;
+        let field = |name: &str| metadata[name].as_u64().ok_or_else(|| invalid("incomplete metadata"));
+        let (node_count, record_size, ip_version) = (field("node_count")? as usize, field("record_size")? as usize, field("ip_version")? as u16);
+        if ![24, 28, 32].contains(&record_size) || ![4, 6].contains(&ip_version) {
+            return Err(invalid("unsupported record size or IP version"));
+        }
+        let data_start = node_count * record_size / 4 + 16;
+        if data_start > marker {
+            return Err(invalid("search tree runs past the end"));
+        }
+        Ok(Self { bytes, node_count, record_size, ip_version, data_start })
+    }
+
+    fn lookup(&self, ip: IpAddr) -> Option<Value> {
+        let bits: Vec<bool> = match (ip, self.ip_version) {
+            (IpAddr::V4(ip), 4) => to_bits(&ip.octets()),
+            // IPv4 addresses sit under ::/96 in an IPv6 tree
+            (IpAddr::V4(ip), _) => to_bits(&ip.to_ipv6_compatible().octets()),
+            (IpAddr::V6(ip), 6) => to_bits(&ip.octets()),
+            (IpAddr::V6(ip), _) => to_bits(&ip.to_ipv4_mapped()?.octets()),
+        };
+        let mut node = 0;
+        for bit in bits {
+            if node >= self.node_count {
+                break;
+            }
+            node = self.record(node, bit)?;
+        }
+        if node <= self.node_count {
+            return None; // Not in the database
+        }
+        let offset = node - self.node_count - 16;
+        let data = self.bytes.get(self.data_start..)?;
+        decode(data, offset, 0).map(|(value, _)| value)
+    }
+
+    fn record(&self, node: usize, right: bool) -> Option<usize> {
+        let size = self.record_size * 2 / 8;
+        let b = self.bytes.get(node * size..(node + 1) * size)?;
+        let be = |bytes: &[u8]| bytes.iter().fold(0usize, |n, &byte| n << 8 | byte as usize);
+        Some(match (self.record_size, right) {
+            (24, false) => be(&b[..3]),
+            (24, true) => be(&b[3..]),
+            (28, false) => (b[3] as usize & 0xf0) << 20 | be(&b[..3]),
+            (28, true) => (b[3] as usize & 0x0f) << 24 | be(&b[4..]),
+            (_, false) => be(&b[..4]),
+            (_, true) => be(&b[4..]),
+        })
+    }
+}
+
+fn to_bits(octets: &[u8]) -> Vec<bool> {
+    octets.iter().flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1 == 1)).collect()
+}
+
+// The value at `at` in a data section and where the next one starts. Pointers are offsets
+// into the same section.
+fn decode(data: &[u8], at: usize, depth: usize) -> Option<(Value, usize)> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let control = *data.get(at)?;
+    let mut at = at + 1;
+    let mut kind = control >> 5;
+    if kind == 1 {
+        let (extra, base) = match control >> 3 & 3 {
+            0 => (1, 0),
+            1 => (2, 2048),
+            2 => (3, 526336),
+            _ => (4, 0),
+        };
+        let mut pointer = if extra == 4 { 0 } else { (control & 7) as usize };
+        for byte in data.get(at..at + extra)? {
+            pointer = pointer << 8 | *byte as usize;
+        }
+        let (value, _) = decode(data, pointer + base, depth + 1)?;
+        return Some((value, at + extra));
+    }
+    if kind == 0 {
+        kind = 7 + *data.get(at)?;
+        at += 1;
+    }
+    let mut size = (control & 0x1f) as usize;
+    if size >= 29 {
+        let extra = size - 28;
+        let bytes = data.get(at..at + extra)?;
+        at += extra;
+        let n = bytes.iter().fold(0usize, |n, &byte| n << 8 | byte as usize);
+        size = match extra {
+            1 => 29 + n,
+            2 => 285 + n,
+            _ => 65821 + n,
+        };
+    }
+    let unsigned = |bytes: &[u8]| bytes.iter().fold(0u128, |n, &byte| n << 8 | byte as u128);
+    let value = match kind {
+        2 => Value::String(String::from_utf8_lossy(data.get(at..at + size)?).into_owned()),
+        3 => Value::from(f64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?)),
+        4 => Value::Null, // Raw bytes; nothing here needs them
+        5 | 6 | 9 | 10 => Value::from(u64::try_from(unsigned(data.get(at..at + size)?)).unwrap_or(u64::MAX)),
+        8 => Value::from(unsigned(data.get(at..at + size)?) as u32 as i32),
+        14 => return Some((Value::Bool(size != 0), at)),
+        15 => Value::from(f32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as f64),
+        7 => {
+            let mut map = Map::new();
+            for _ in 0..size {
+                let (key, next) = decode(data, at, depth + 1)?;
+                let (value, next) = decode(data, next, depth + 1)?;
+                map.insert(key.as_str()?.to_string(), value);
+                at = next;
+            }
+            return Some((Value::Object(map), at));
+        }
+        11 => {
+            let mut items = Vec::with_capacity(size.min(256));
+            for _ in 0..size {
+                let (value, next) = decode(data, at, depth + 1)?;
+                items.push(value);
+                at = next;
+            }
+            return Some((Value::Array(items), at));
+        }
+        _ => return None,
+    };
+    let width = match kind {
+        3 => 8,
+        15 => 4,
+        _ => size,
+    };
+    Some((value, at + width))
+}
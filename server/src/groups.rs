@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Admin-managed named sets of users, for `@group` mentions and room access. Group names
+// follow the username rules and are matched case-insensitively, like mentions.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Groups {
+    groups: BTreeMap<String, BTreeSet<String>>, // Lowercased name -> members
+}
+
+impl Groups {
+    // False if the group exists already
+    pub fn create(&mut self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        if self.groups.contains_key(&name) {
+            return false;
+        }
+        self.groups.insert(name, BTreeSet::new());
+        true
+    }
+
+    pub fn delete(&mut self, name: &str) -> bool {
+        self.groups.remove(&name.to_lowercase()).is_some()
+    }
+
+    // None if there is no such group, else whether the membership changed
+    pub fn add(&mut self, name: &str, username: &str) -> Option<bool> {
+        Some(self.groups.get_mut(&name.to_lowercase())?.insert(username.to_string()))
+    }
+
+    pub fn remove(&mut self, name: &str, username: &str) -> Option<bool> {
+        Some(self.groups.get_mut(&name.to_lowercase())?.remove(username))
+    }
+
+    pub fn members(&self, name: &str) -> Option<&BTreeSet<String>> {
+        self.groups.get(&name.to_lowercase())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.groups.keys()
+    }
+
+    pub fn is_member(&self, name: &str, username: &str) -> bool {
+        self.members(name).is_some_and(|members| members.contains(username))
+    }
+
+    // Everyone in the groups `@mentioned` in `content`, in name order, without duplicates
+    pub fn mentioned(&self, content: &str) -> BTreeSet<String> {
+        content
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '@'))
+            .filter_map(|word| word.strip_prefix('@'))
+            .filter_map(|name| self.members(name))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    // Drops a user everywhere, so a reused guest name starts in no groups
+    pub fn forget(&mut self, username: &str) {
+        for members in self.groups.values_mut() {
+            members.remove(username);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Storage checks for `/readyz`: a server that can't write its accounts, audit log or history
+// shouldn't be sent new users, even though it can still chat.
+
+// Where a file lives, for checking that it can be written
+pub fn dir_of(file: &Path) -> PathBuf {
+    file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf()
+}
+
+// Whether files can be created in `dir`, or in whichever parent it will be created under
+pub fn writable(dir: &Path) -> io::Result<()> {
+    let existing = dir.ancestors().find(|dir| dir.is_dir()).unwrap_or(Path::new("."));
+    let probe = existing.join(format!(".readyz-{}", std::process::id()));
+    fs::File::create(&probe)?;
+    fs::remove_file(&probe)
+}
@@ -0,0 +1,93 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Just enough HTTP/1.1 to POST JSON to, or GET from, a plain http:// service.
+// No HTTP crate is available to this build; https is not supported.
+
+const MAX_RESPONSE: usize = 1 << 20; // Read from POST answers at most
+
+pub struct Response {
+    pub status: u16,
+    headers: Vec<(String, String)>, // Names lowercased
+    pub body: String,               // Lossily decoded, and possibly cut short
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+// `http://host[:port]/path`, with `default_port` when none is given
+pub fn parse_url(url: &str, default_port: u16) -> Option<(String, String)> {
+    let rest = url.strip_prefix("http://")?;
+    let (host, path) = rest.split_once('/').map(|(host, path)| (host, format!("/{}", path))).unwrap_or((rest, "/".to_string()));
+    if host.is_empty() {
+        return None;
+    }
+    let host = if host.contains(':') { host.to_string() } else { format!("{}:{}", host, default_port) };
+    Some((host, path))
+}
+
+// The response body, if the status was 2xx
+pub async fn post_json(host: &str, path: &str, body: &str) -> anyhow::Result<String> {
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+    let response = send(host, &request, MAX_RESPONSE).await?;
+    if !response.is_success() {
+        anyhow::bail!("{} answered {}", host, response.status);
+    }
+    Ok(response.body)
+}
+
+// Reads no more than `limit` bytes of the answer, so a large file costs no more than its headers
+pub async fn get(host: &str, path: &str, limit: usize) -> anyhow::Result<Response> {
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nAccept: text/html, */*\r\nConnection: close\r\n\r\n", path, host);
+    send(host, &request, limit).await
+}
+
+async fn send(host: &str, request: &str, limit: usize) -> anyhow::Result<Response> {
+    let mut stream = TcpStream::connect(host).await?;
+    stream.write_all(request.as_bytes()).await?;
+    let mut raw = Vec::new();
+    let mut buf = [0; 8192];
+    while raw.len() < limit {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        raw.extend_from_slice(&buf[..n]);
+    }
+    let raw = String::from_utf8_lossy(&raw);
+    let (head, body) = raw.split_once("\r\n\r\n").unwrap_or((&raw, ""));
+    let mut lines = head.lines();
+    let status_line = lines.next().unwrap_or_default();
+    let Some(status) = status_line.split(' ').nth(1).and_then(|code| code.parse().ok()) else {
+        anyhow::bail!("{} answered {:?}", host, status_line);
+    };
+    let headers: Vec<(String, String)> = lines.filter_map(|line| line.split_once(':')).map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string())).collect();
+    let chunked = headers.iter().any(|(name, value)| name == "transfer-encoding" && value.eq_ignore_ascii_case("chunked"));
+    let body = if chunked { dechunk(body) } else { body.to_string() };
+    Ok(Response { status, headers, body })
+}
+
+// Joins the chunks of a chunked body, ignoring extensions and trailers
+fn dechunk(mut body: &str) -> String {
+    let mut joined = String::new();
+    while let Some((size, rest)) = body.split_once("\r\n") {
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16).unwrap_or(0);
+        let Some(chunk) = rest.get(..size).filter(|_| size > 0) else { break };
+        joined.push_str(chunk);
+        body = rest[size..].trim_start_matches("\r\n");
+    }
+    joined
+}
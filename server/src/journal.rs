@@ -0,0 +1,125 @@
+use common::ChatMessage;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+const BATCH_SIZE: usize = 50;
+
+enum Entry {
+    Append(Box<ChatMessage>),
+    Rewrite(Vec<ChatMessage>), // Replaces the file, after purges and evictions
+}
+
+// Room history on disk as JSON lines. A background task does the writing, so posting never
+// waits on the disk: appends go out every 100 ms or 50 at a time, whichever comes first.
+pub struct Journal {
+    tx: std::sync::Mutex<Option<mpsc::UnboundedSender<Entry>>>,
+    task: tokio::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Journal {
+    pub fn start(path: PathBuf) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(write_entries(path, rx));
+        Self { tx: std::sync::Mutex::new(Some(tx)), task: tokio::sync::Mutex::new(Some(task)) }
+    }
+
+    // Every message in the file, oldest first; a line that doesn't parse (say, cut off by a crash) is skipped
+    pub fn load(path: &Path) -> io::Result<Vec<ChatMessage>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn append(&self, msg: &ChatMessage) {
+        self.send(Entry::Append(Box::new(msg.clone())));
+    }
+
+    pub fn rewrite(&self, messages: Vec<ChatMessage>) {
+        self.send(Entry::Rewrite(messages));
+    }
+
+    fn send(&self, entry: Entry) {
+        if let Some(tx) = self.tx.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            let _ = tx.send(entry);
+        }
+    }
+
+    // Writes out everything queued so far and stops; later appends are ignored
+    pub async fn close(&self) {
+        self.tx.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(task) = self.task.lock().await.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+async fn write_entries(path: PathBuf, mut rx: mpsc::UnboundedReceiver<Entry>) {
+    let mut batch: Vec<ChatMessage> = Vec::new();
+    let mut interval = tokio::time::interval(BATCH_INTERVAL);
+    loop {
+        let open = tokio::select! {
+            entry = rx.recv() => match entry {
+                Some(Entry::Append(msg)) => {
+                    batch.push(*msg);
+                    if batch.len() < BATCH_SIZE {
+                        continue;
+                    }
+                    true
+                }
+                Some(Entry::Rewrite(messages)) => {
+                    // Anything batched is already part of `messages`
+                    batch.clear();
+                    report(&path, tokio::task::spawn_blocking({
+                        let path = path.clone();
+                        move || rewrite(&path, &messages)
+                    }).await);
+                    continue;
+                }
+                None => false,
+            },
+            _ = interval.tick() => true,
+        };
+        if !batch.is_empty() {
+            let messages = std::mem::take(&mut batch);
+            report(&path, tokio::task::spawn_blocking({
+                let path = path.clone();
+                move || append(&path, &messages)
+            }).await);
+        }
+        if !open {
+            break;
+        }
+    }
+}
+
+fn report(path: &Path, result: Result<io::Result<()>, tokio::task::JoinError>) {
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("Could not write history to {}: {}", path.display(), e),
+        Err(e) => eprintln!("Could not write history to {}: {}", path.display(), e),
+    }
+}
+
+fn append(path: &Path, messages: &[ChatMessage]) -> io::Result<()> {
+    let lines = to_lines(messages)?;
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines.as_bytes())
+}
+
+// Written beside the file and renamed over it, so a crash mid-write keeps the old one
+fn rewrite(path: &Path, messages: &[ChatMessage]) -> io::Result<()> {
+    let partial = path.with_extension("partial");
+    fs::write(&partial, to_lines(messages)?)?;
+    fs::rename(&partial, path)
+}
+
+fn to_lines(messages: &[ChatMessage]) -> io::Result<String> {
+    messages.iter().map(|msg| msg.to_json().map(|json| json + "\n")).collect::<Result<String, _>>().map_err(io::Error::other)
+}
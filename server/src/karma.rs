@@ -0,0 +1,57 @@
+use common::RoomName;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+const COOLDOWN: Duration = Duration::from_secs(60); // Between thanks from one user to the same other user
+
+// Scores in rooms with karma on, given with `name++` in a chat message or `/thank <user>`.
+// A room that turns karma off keeps its scores for when it is turned back on.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Karma {
+    scores: HashMap<RoomName, BTreeMap<String, u64>>,
+    #[serde(skip)]
+    recent: HashMap<(String, String), Instant>, // (giver, receiver) -> their last thanks
+}
+
+impl Karma {
+    // The receiver's new score, or None while the giver thanked them too recently
+    pub fn thank(&mut self, room: &RoomName, giver: &str, receiver: &str) -> Option<u64> {
+        let now = Instant::now();
+        self.recent.retain(|_, at| now.duration_since(*at) < COOLDOWN);
+        let key = (giver.to_string(), receiver.to_string());
+        if self.recent.contains_key(&key) {
+            return None;
+        }
+        self.recent.insert(key, now);
+        let score = self.scores.entry(room.clone()).or_default().entry(receiver.to_string()).or_default();
+        *score += 1;
+        Some(*score)
+    }
+
+    pub fn scores(&self, room: &RoomName) -> BTreeMap<String, u64> {
+        self.scores.get(room).cloned().unwrap_or_default()
+    }
+
+    // Highest first, by name on a tie
+    pub fn top(&self, room: &RoomName, n: usize) -> Vec<(String, u64)> {
+        let mut top: Vec<(String, u64)> = self.scores(room).into_iter().collect();
+        top.sort_by(|(a, x), (b, y)| y.cmp(x).then(a.cmp(b)));
+        top.truncate(n);
+        top
+    }
+}
+
+// Names thanked with `name++` or `@name++`, each once; the caller checks they are users
+pub fn plus_plus(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    for word in text.split_whitespace() {
+        // Punctuation after it still counts, as in "thanks bob++!"
+        let word = word.trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '+');
+        let Some(name) = word.strip_suffix("++").map(|name| name.trim_start_matches('@')) else { continue };
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
@@ -0,0 +1,3822 @@
+mod activity;
+mod archive;
+mod audit;
+mod auth;
+mod autojoin;
+mod backup;
+mod cancel;
+mod captcha;
+mod chaos;
+mod commands;
+mod config;
+mod dice;
+mod frame;
+mod games;
+mod geoip;
+mod groups;
+mod health;
+mod http;
+mod journal;
+mod karma;
+mod links;
+mod migrate;
+mod moderation;
+mod names;
+mod onboarding;
+mod preview;
+mod record;
+mod rooms;
+mod snapshot;
+mod supervise;
+mod text;
+mod trace;
+mod translate;
+mod trivia;
+mod warnings;
+mod web;
+mod when;
+
+use audit::{AuditLog, Chain};
+use auth::Accounts;
+use autojoin::AutoJoin;
+use cancel::CancellationToken;
+use captcha::Captcha;
+pub use chaos::Chaos;
+use config::{BackupSettings, Config, Overflow, Penalty};
+use frame::Frame;
+use games::{Games, Say};
+use geoip::GeoIp;
+use archive::Archiver;
+use journal::Journal;
+use links::Screen;
+use moderation::Verdict;
+use preview::PreviewCache;
+use record::Recorder;
+use chrono::SecondsFormat;
+use common::deflate::{self, Deflater};
+use common::msgpack;
+use common::recording::Inbound;
+use common::{i18n, ChatError, ChatMessage, CommandError, ConsoleConnection, ConsoleState, DiscoveredRoom, Discovery, ErrorReason, ProtocolError, Handshake, MessageId, MessageType, KarmaScores, ModerationMember, ModerationState, Report, RoomActivity, RoomEntry, RoomName, ServerCapabilities, ServerStats, UserProfile, Username};
+use activity::Activity;
+use groups::Groups;
+use karma::Karma;
+use names::Reserved;
+use onboarding::{Onboarding, Step};
+use rooms::{Action, Level, Rooms};
+use snapshot::Snapshot;
+use trace::{Span, Tracer};
+use translate::Translations;
+use trivia::Trivia;
+use warnings::Warnings;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
+
+const HISTORY_LIMIT: usize = 50; // Replayed on join
+const HISTORY_WINDOW: usize = 10; // Messages either side of a /history target
+const ARCHIVE_REPLAY: usize = 500; // Archived messages sent per `/history --archived`, within CLIENT_QUEUE
+const RECENT_PM_LIMIT: usize = 500;
+const STARRED_LIMIT: usize = 200; // Per user; the oldest star is dropped beyond this
+const FLAGGED_LIMIT: usize = 100; // Flagged and reported messages kept for `/flagged`, oldest dropped first
+const PINS_LIMIT: usize = 20; // Per room; pinning another unpins the oldest
+const LEADERBOARD_SIZE: usize = 10;
+const DISCOVER_RESULTS: usize = 20;
+const LINK_HITS_LIMIT: usize = 100; // Screened links kept for `/linkhits`
+const AUTH_ATTEMPTS: usize = 5; // Handshakes per connection before giving up
+const GUEST_PREFIX: &str = "guest-"; // Reserved for assigned names while guest access is on
+const GUEST_NAME_ATTEMPTS: usize = 100;
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30); // How often room TTLs are enforced
+const CLIENT_QUEUE: usize = 1024; // Direct messages waiting for a client's writer
+const DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500); // For a closing connection's last messages
+const WRITE_BATCH: usize = 64; // Queued messages written before a flush, during bursts like history replay
+const RATE_WINDOW: std::time::Duration = std::time::Duration::from_secs(60); // Span the config's `rate_limit` counts over
+const BACKUP_CHECK: std::time::Duration = std::time::Duration::from_secs(60); // How often the backup schedule is checked
+const GAME_TICK: std::time::Duration = std::time::Duration::from_secs(1); // How often games hear the clock, for their time limits
+const MAINTENANCE_WARNINGS: [i64; 5] = [600, 300, 60, 30, 10]; // Seconds before the drain when clients are reminded
+
+// Per-connection registration, keyed by username in the clients map
+struct Client {
+    room: watch::Sender<RoomName>, // Watched by the writer task, which filters broadcasts by it
+    tx: Outbox, // Direct delivery (PMs, command replies, history)
+    hangup: CancellationToken, // Cancelled to close the connection
+    is_admin: bool,
+    moderator: bool, // As last told in capabilities: moderates the room they are in
+    away: Option<String>, // Reason set by `/away`, cleared by `/back`
+    is_guest: bool,
+    sent: VecDeque<std::time::Instant>, // Chat messages within the rate window
+    conn: ConnInfo,
+}
+
+impl Client {
+    fn room(&self) -> RoomName {
+        self.room.borrow().clone()
+    }
+}
+
+// Sending half of a client's bounded queue, so one stuck connection can't grow without limit.
+// Room broadcasts are bounded separately: a lagging writer skips what it missed.
+#[derive(Clone)]
+struct Outbox {
+    tx: mpsc::Sender<ChatMessage>,
+    hangup: CancellationToken,
+    overflow: Overflow,
+    overflowed: Arc<AtomicBool>, // Reported once per connection
+}
+
+impl Outbox {
+    fn send(&self, msg: ChatMessage) {
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.tx.try_send(msg) {
+            if !self.overflowed.swap(true, Ordering::Relaxed) {
+                eprintln!("A client fell {} messages behind", CLIENT_QUEUE);
+            }
+            if self.overflow == Overflow::Disconnect {
+                self.hangup.cancel("fell behind");
+            }
+        }
+    }
+}
+
+// How a connection's messages are written after the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Wire {
+    Json,
+    MessagePack, // Framed like compressed chunks
+    Text,        // Readable lines for raw clients; see `text`
+}
+
+// A message as a protocol line or frame; one that can't be serialized is logged and not sent
+fn encode(msg: &ChatMessage, wire: Wire) -> Option<Line> {
+    let encoded = match wire {
+        Wire::Json => msg.to_json().map(|json| format!("{}\n", json).into_bytes()),
+        Wire::MessagePack => msg.to_msgpack().map(|bytes| deflate::frame(&bytes)),
+        Wire::Text => return text::render(msg).map(|line| line.into_bytes().into()),
+    };
+    match encoded {
+        Ok(bytes) => Some(bytes.into()),
+        Err(e) => {
+            eprintln!("Not sending message {}: {}", msg.id, e);
+            None
+        }
+    }
+}
+
+// A room broadcast, serialized at most once per locale and wire format however many clients
+// receive it
+struct Encoded {
+    msg: ChatMessage,
+    lines: std::sync::Mutex<HashMap<(&'static str, Wire), Option<Line>>>, // Untemplated messages share one per format
+}
+
+type Line = Arc<[u8]>; // A JSON line, or a MessagePack frame
+
+impl Encoded {
+    fn line(&self, locale: &'static str, wire: Wire) -> Option<Line> {
+        let key = if self.msg.template.is_some() { locale } else { "" };
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        lines.entry((key, wire)).or_insert_with(|| encode(&self.msg.localized(locale), wire)).clone()
+    }
+}
+
+// How a connection is doing, for `/conninfo`
+struct ConnInfo {
+    addr: std::net::SocketAddr,
+    since: chrono::DateTime<chrono::Utc>,
+    bytes_in: Arc<AtomicU64>,  // From the client, counted by the reader
+    bytes_out: Arc<AtomicU64>, // To the client, counted by the writer task
+    latency_ms: Option<u64>,   // Last round trip the client reported along with `/ping`
+    reconnects: u32,
+    options: String, // What the handshake settled, e.g. "json, es, password"
+    compressed: bool, // Everything after the handshake goes out deflated
+    wire: Wire,
+}
+
+impl ConnInfo {
+    fn new(addr: std::net::SocketAddr, handshake: &Handshake, raw: bool, locale: &str, bytes_in: Arc<AtomicU64>, bytes_out: Arc<AtomicU64>) -> Self {
+        let auth = match (&handshake.password, &handshake.totp) {
+            _ if handshake.guest => "guest",
+            (Some(_), Some(_)) => "password+totp",
+            (Some(_), None) => "password",
+            _ => "open",
+        };
+        let compressed = handshake.compression.iter().any(|c| c == deflate::NAME);
+        let wire = match () {
+            _ if raw => Wire::Text,
+            _ if handshake.encoding.iter().any(|e| e == msgpack::NAME) => Wire::MessagePack,
+            _ => Wire::Json,
+        };
+        let format = match wire {
+            Wire::Json => "json",
+            Wire::MessagePack => msgpack::NAME,
+            Wire::Text => "text",
+        };
+        let mut options = format!("{}, {}, {}", format, locale, auth);
+        if compressed {
+            options.push_str(", deflate");
+        }
+        Self { addr, since: chrono::Utc::now(), bytes_in, bytes_out, latency_ms: None, reconnects: handshake.reconnects, options, compressed, wire }
+    }
+}
+
+struct ServerState {
+    clients: Mutex<HashMap<String, Client>>,
+    history: Mutex<HashMap<RoomName, VecDeque<ChatMessage>>>, // Up to the config's `history_limit` per room, for replay and /history
+    last_seq: Mutex<HashMap<RoomName, u64>>, // Per room, kept apart from history so purged rooms never reuse numbers
+    read_markers: Mutex<HashMap<RoomName, HashMap<String, MessageId>>>, // room -> user -> last read message ID
+    recent_pms: Mutex<VecDeque<ChatMessage>>, // For PM read receipts and forwarding
+    starred: Mutex<HashMap<String, Vec<ChatMessage>>>, // user -> copies of saved messages, newest first
+    profiles: Mutex<HashMap<String, UserProfile>>,
+    broadcast_tx: broadcast::Sender<Arc<Encoded>>,
+    admins: Vec<String>,
+    accounts: Mutex<Accounts>,
+    guests: bool, // Anyone may join under a temporary name, without creating rooms
+    rooms: Mutex<Rooms>,
+    groups: Mutex<Groups>, // Locked after `rooms` when both are needed
+    reserved: Mutex<Reserved>,
+    warnings: Mutex<Warnings>,
+    flagged: Mutex<VecDeque<(ChatMessage, String)>>, // Posted messages moderation flagged or users reported, with the reason
+    pins: Mutex<HashMap<RoomName, Vec<ChatMessage>>>, // Newest first
+    activity: Mutex<Activity>,
+    karma: Mutex<Karma>,
+    onboarding: Mutex<Onboarding>,
+    auto_join: Mutex<AutoJoin>,
+    games: Mutex<Games>,
+    previews: Mutex<PreviewCache>,
+    translations: Mutex<Translations>,
+    link_hits: Mutex<VecDeque<String>>, // Described for `/linkhits`, newest last
+    mirrors: Mutex<Vec<(RoomName, RoomName)>>, // One-way (from, to) links; `to` may be a category like "projects/*"
+    audit: AuditLog,
+    maintenance: Mutex<Option<Maintenance>>,
+    config: Mutex<Config>,
+    config_path: PathBuf,
+    snapshot_path: PathBuf, // Where `/snapshot` writes
+    tracer: Tracer,
+    journal: Option<Journal>, // Keeps room history on disk when `HISTORY_FILE` is set
+    archiver: Archiver, // Writes to the config's `archive`, if it has one
+    chaos: Option<Chaos>, // Fault injection for testing clients; never set in production
+    recorder: Option<Recorder>, // Everything clients send, when `RECORD_FILE` is set
+    geoip: Option<GeoIp>, // Country and network of addresses, when `GEOIP_DB` is set
+    started: std::time::Instant,
+    services: supervise::Services,
+    listening: AtomicBool, // While the chat listener accepts connections
+    translates: AtomicBool, // The config has a translation service, mirrored for capabilities
+    storage: Vec<PathBuf>, // Directories the server writes to, checked by `/readyz`
+}
+
+// Set by `/maintenance`: new users are turned away, and everyone is disconnected at `drain_at`
+#[derive(Clone)]
+struct Maintenance {
+    id: String, // Lets a countdown notice that it was cancelled or replaced
+    drain_at: chrono::DateTime<chrono::Utc>,
+    reason: String,
+}
+
+impl ServerState {
+    fn new(admins: Vec<String>, accounts: Accounts, guests: bool, audit: AuditLog, config_path: PathBuf, snapshot_path: PathBuf, tracer: Tracer) -> Self {
+        let (broadcast_tx, _) = broadcast::channel(256);
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            history: Mutex::new(HashMap::new()),
+            last_seq: Mutex::new(HashMap::new()),
+            read_markers: Mutex::new(HashMap::new()),
+            recent_pms: Mutex::new(VecDeque::new()),
+            starred: Mutex::new(HashMap::new()),
+            profiles: Mutex::new(HashMap::new()),
+            broadcast_tx,
+            admins,
+            accounts: Mutex::new(accounts),
+            guests,
+            rooms: Mutex::new(Rooms::default()),
+            groups: Mutex::new(Groups::default()),
+            reserved: Mutex::new(Reserved::default()),
+            warnings: Mutex::new(Warnings::default()),
+            flagged: Mutex::new(VecDeque::new()),
+            pins: Mutex::new(HashMap::new()),
+            activity: Mutex::new(Activity::default()),
+            karma: Mutex::new(Karma::default()),
+            onboarding: Mutex::new(Onboarding::default()),
+            auto_join: Mutex::new(AutoJoin::default()),
+            games: Mutex::new(Games::default()),
+            previews: Mutex::new(PreviewCache::default()),
+            translations: Mutex::new(Translations::default()),
+            link_hits: Mutex::new(VecDeque::new()),
+            mirrors: Mutex::new(Vec::new()),
+            audit,
+            maintenance: Mutex::new(None),
+            config: Mutex::new(Config::default()),
+            config_path,
+            snapshot_path,
+            tracer,
+            journal: None,
+            archiver: Archiver::start(),
+            chaos: None,
+            recorder: None,
+            geoip: None,
+            started: std::time::Instant::now(),
+            services: supervise::Services::default(),
+            listening: AtomicBool::new(false),
+            translates: AtomicBool::new(false),
+            storage: Vec::new(),
+        }
+    }
+
+    // ` from=<ip>`, plus ` geo=<country ASN org>` when the GeoIP databases know the address
+    fn describe_addr(&self, addr: SocketAddr) -> String {
+        match self.geoip.as_ref().and_then(|geoip| geoip.lookup(addr.ip())) {
+            Some(origin) => format!(" from={} geo={}", addr.ip(), origin),
+            None => format!(" from={}", addr.ip()),
+        }
+    }
+
+    // For audit entries about someone who is online
+    async fn seen_from(&self, username: &str) -> String {
+        let addr = self.clients.lock().await.get(username).map(|c| c.conn.addr);
+        addr.map(|addr| self.describe_addr(addr)).unwrap_or_default()
+    }
+
+    // Adds a frame to the recording, if there is one
+    fn record(&self, conn: Option<u64>, frame: &Frame, bytes: usize) {
+        let (Some(recorder), Some(conn)) = (&self.recorder, conn) else { return };
+        recorder.record(conn, match frame {
+            Frame::Line(line) => Inbound::Line { line: line.clone() },
+            Frame::TooLong => Inbound::TooLong { bytes },
+            Frame::NotUtf8 => Inbound::NotUtf8 { bytes },
+            Frame::Closed => Inbound::Close,
+        });
+    }
+
+    fn capabilities(&self) -> ServerCapabilities {
+        let mut features = vec![
+            ServerCapabilities::STARS,
+            ServerCapabilities::FORWARD,
+            ServerCapabilities::READ_RECEIPTS,
+            ServerCapabilities::PRESENCE,
+            ServerCapabilities::PROFILES,
+            ServerCapabilities::HISTORY,
+            ServerCapabilities::MIRRORS,
+            ServerCapabilities::TTL,
+            ServerCapabilities::INVITE_CODES,
+            ServerCapabilities::TOTP,
+            ServerCapabilities::GROUPS,
+            ServerCapabilities::PERMISSIONS,
+            ServerCapabilities::MODERATION,
+            ServerCapabilities::ACTIVITY,
+            ServerCapabilities::KARMA,
+            ServerCapabilities::GAMES,
+            ServerCapabilities::DICE,
+            ServerCapabilities::TIME,
+            ServerCapabilities::DISCOVER,
+            ServerCapabilities::AUTO_JOIN,
+            ServerCapabilities::ONBOARDING,
+            ServerCapabilities::COMMANDS,
+        ];
+        if self.guests {
+            features.push(ServerCapabilities::GUESTS);
+        }
+        if self.translates.load(Ordering::Relaxed) {
+            features.push(ServerCapabilities::TRANSLATE);
+        }
+        ServerCapabilities {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            features: features.into_iter().map(str::to_string).collect(),
+            max_message_len: common::MAX_CONTENT_LEN,
+            max_line_bytes: frame::MAX_LINE,
+            history_limit: HISTORY_LIMIT,
+            moderator: false,
+        }
+    }
+
+    async fn stats(&self) -> ServerStats {
+        let minute_ago = chrono::Utc::now() - chrono::Duration::minutes(1);
+        let (history_messages, history_bytes, messages_per_minute) = {
+            let history = self.history.lock().await;
+            let messages = || history.values().flatten();
+            let size = |m: &ChatMessage| m.id.to_string().len() + m.username.len() + m.content.len() + m.room.len() + m.display_name.as_ref().map_or(0, String::len);
+            let recent = messages().filter(|m| m.mirrored_from.is_none() && m.timestamp > minute_ago).count();
+            (messages().count(), messages().map(size).sum(), recent)
+        };
+        let clients = self.clients.lock().await.len();
+        let rooms = self.known_rooms().await.len();
+        ServerStats {
+            uptime_secs: self.started.elapsed().as_secs(),
+            clients,
+            rooms,
+            messages_per_minute,
+            history_messages,
+            history_bytes,
+            broadcast_depth: self.broadcast_tx.len(),
+        }
+    }
+
+    // Connections, busy rooms and recent audit entries, for an admin's `/console`
+    async fn console(&self) -> ConsoleState {
+        let stats = self.stats().await;
+        let mut connections: Vec<ConsoleConnection> = self
+            .clients
+            .lock()
+            .await
+            .iter()
+            .map(|(name, client)| ConsoleConnection {
+                username: name.clone(),
+                room: client.room().to_string(),
+                addr: client.conn.addr.to_string(),
+                since: Some(client.conn.since),
+                admin: client.is_admin,
+                guest: client.is_guest,
+                away: client.away.clone(),
+                bytes_in: client.conn.bytes_in.load(Ordering::Relaxed),
+                bytes_out: client.conn.bytes_out.load(Ordering::Relaxed),
+            })
+            .collect();
+        connections.sort_by_key(|connection| connection.since);
+        let mut rooms: HashMap<String, RoomActivity> = HashMap::new();
+        for connection in &connections {
+            rooms.entry(connection.room.clone()).or_insert_with(|| RoomActivity { room: connection.room.clone(), ..Default::default() }).users += 1;
+        }
+        let minute_ago = chrono::Utc::now() - chrono::Duration::minutes(1);
+        for (room, history) in self.history.lock().await.iter() {
+            let recent = history.iter().rev().take_while(|m| m.timestamp > minute_ago).filter(|m| m.mirrored_from.is_none()).count();
+            if recent > 0 {
+                rooms.entry(room.to_string()).or_insert_with(|| RoomActivity { room: room.to_string(), ..Default::default() }).messages_per_minute = recent;
+            }
+        }
+        let mut rooms: Vec<RoomActivity> = rooms.into_values().collect();
+        rooms.sort_by(|a, b| (b.messages_per_minute, b.users).cmp(&(a.messages_per_minute, a.users)).then_with(|| a.room.cmp(&b.room)));
+        ConsoleState { stats, connections, rooms, actions: self.audit.recent() }
+    }
+
+    // Members of `room` with their warnings and mutes, the report queue and the room's pins, for `/modpanel`
+    // Reports from every room for admins; room moderators see only their room's
+    async fn moderation(&self, room: &RoomName, all_reports: bool) -> ModerationState {
+        let members: Vec<(String, bool)> = {
+            let clients = self.clients.lock().await;
+            let mut members: Vec<(String, bool)> = clients.iter().filter(|(_, c)| c.room() == *room).map(|(name, c)| (name.clone(), c.is_admin)).collect();
+            members.sort();
+            members
+        };
+        let members = {
+            let mut warnings = self.warnings.lock().await;
+            members
+                .into_iter()
+                .map(|(username, admin)| ModerationMember {
+                    warnings: warnings.history(&username).len(),
+                    muted_secs: warnings.muted_for(&username).map(|left| left.num_seconds().max(0) as u64),
+                    username,
+                    admin,
+                })
+                .collect()
+        };
+        let reports = self
+            .flagged
+            .lock()
+            .await
+            .iter()
+            .filter(|(message, _)| all_reports || message.room == *room)
+            .map(|(message, reason)| Report { message: message.clone(), reason: reason.clone() })
+            .collect();
+        let pins = self.pins.lock().await.get(room).cloned().unwrap_or_default();
+        ModerationState { members, reports, pins }
+    }
+
+    // One lock at a time; guards in a struct literal would all be held until it is built
+    async fn snapshot(&self) -> Snapshot {
+        let history = self.history.lock().await.clone();
+        let last_seq = self.last_seq.lock().await.clone();
+        let read_markers = self.read_markers.lock().await.clone();
+        let recent_pms = self.recent_pms.lock().await.clone();
+        let starred = self.starred.lock().await.clone();
+        let profiles = self.profiles.lock().await.clone();
+        let rooms = self.rooms.lock().await.clone();
+        let groups = self.groups.lock().await.clone();
+        let warnings = self.warnings.lock().await.clone();
+        let reserved = self.reserved.lock().await.clone();
+        let mirrors = self.mirrors.lock().await.clone();
+        let pins = self.pins.lock().await.clone();
+        let activity = self.activity.lock().await.clone();
+        let karma = self.karma.lock().await.clone();
+        let onboarding = self.onboarding.lock().await.clone();
+        let auto_join = self.auto_join.lock().await.clone();
+        let accounts = self.accounts.lock().await.export();
+        Snapshot { taken: chrono::Utc::now(), history, last_seq, read_markers, recent_pms, starred, profiles, rooms, groups, warnings, reserved, mirrors, pins, activity, karma, onboarding, auto_join, accounts }
+    }
+
+    // A snapshot plus the audit log so far, moved into the backup directory
+    async fn back_up(&self, settings: &BackupSettings) -> std::io::Result<()> {
+        let _span = self.tracer.span("backup");
+        std::fs::create_dir_all(&settings.dir)?;
+        let now = chrono::Utc::now();
+        self.snapshot().await.save(&backup::generation_path(&settings.dir, "snapshot", "json", now))?;
+        self.audit.rotate(&backup::generation_path(&settings.dir, "audit", "log", now))?;
+        backup::prune(&settings.dir, "snapshot", "json", settings.keep)?;
+        for pruned in backup::prune(&settings.dir, "audit", "log", settings.keep)? {
+            self.audit.pruned(&pruned);
+        }
+        Ok(())
+    }
+
+    // Only used at startup, before anyone is connected
+    async fn restore(&self, snapshot: Snapshot) -> std::io::Result<()> {
+        *self.history.lock().await = snapshot.history;
+        *self.last_seq.lock().await = snapshot.last_seq;
+        *self.read_markers.lock().await = snapshot.read_markers;
+        *self.recent_pms.lock().await = snapshot.recent_pms;
+        *self.starred.lock().await = snapshot.starred;
+        *self.profiles.lock().await = snapshot.profiles;
+        *self.rooms.lock().await = snapshot.rooms;
+        *self.groups.lock().await = snapshot.groups;
+        *self.warnings.lock().await = snapshot.warnings;
+        *self.reserved.lock().await = snapshot.reserved;
+        *self.mirrors.lock().await = snapshot.mirrors;
+        *self.pins.lock().await = snapshot.pins;
+        *self.activity.lock().await = snapshot.activity;
+        *self.karma.lock().await = snapshot.karma;
+        *self.onboarding.lock().await = snapshot.onboarding;
+        *self.auto_join.lock().await = snapshot.auto_join;
+        self.accounts.lock().await.import(snapshot.accounts)
+    }
+
+    // Re-reads the config file; on error the running settings stay as they were
+    async fn reload_config(&self, actor: &str) -> Result<(), String> {
+        let config = Config::load(&self.config_path)?;
+        self.apply_config(config).await;
+        self.audit.record(actor, "config_reload", &self.config_path.display().to_string());
+        Ok(())
+    }
+
+    // Rooms left out of the file keep whatever `/ttl` and `/roomset` set; banned users still online are disconnected
+    async fn apply_config(&self, config: Config) {
+        self.translates.store(config.translate.is_some(), Ordering::Relaxed);
+        // Allow and deny lists may have changed, and refusals are cached too
+        *self.previews.lock().await = PreviewCache::default();
+        {
+            let mut known = self.rooms.lock().await;
+            for (room, settings) in &config.rooms {
+                if let Some(ttl) = &settings.ttl {
+                    known.set_ttl(room, rooms::parse_lifetime(ttl));
+                }
+                for (action, level) in [(Action::Post, settings.post), (Action::Invite, settings.invite)] {
+                    if let Some(level) = level {
+                        known.set_permission(room, action, level);
+                    }
+                }
+                if let Some(moderated) = settings.moderation {
+                    known.set_moderated(room, moderated);
+                }
+                if let Some(karma) = settings.karma {
+                    known.set_karma(room, karma);
+                }
+                if let Some(broadcast) = settings.broadcast {
+                    known.set_broadcast(room, broadcast);
+                }
+                if let Some(description) = &settings.description {
+                    known.set_description(room, Some(description.clone()).filter(|d| !d.is_empty()));
+                }
+                if let Some(tags) = settings.tags.as_ref().and_then(|tags| rooms::parse_tags(tags.iter().map(String::as_str))) {
+                    known.set_tags(room, tags);
+                }
+            }
+        }
+        let banned: Vec<(String, CancellationToken, SocketAddr)> =
+            self.clients.lock().await.iter().filter(|(name, _)| config.is_banned(name)).map(|(name, c)| (name.clone(), c.hangup.clone(), c.conn.addr)).collect();
+        *self.config.lock().await = config;
+        for (name, hangup, addr) in banned {
+            self.audit.record("System", "ban_disconnect", &format!("user={}{}", name, self.describe_addr(addr)));
+            self.send_to(&name, ChatMessage::error(String::new()).with_template("err.banned", &[])).await;
+            hangup.cancel("banned");
+        }
+        self.purge_expired().await;
+    }
+
+    // Counts a chat message against the config's `rate_limit`; when it's one too many, how
+    // long until the oldest counted message leaves the window
+    async fn rate_limited_for(&self, username: &str) -> Option<std::time::Duration> {
+        let limit = self.config.lock().await.rate_limit?;
+        let mut clients = self.clients.lock().await;
+        let Some(client) = clients.get_mut(username) else { return Some(RATE_WINDOW) };
+        let now = std::time::Instant::now();
+        while client.sent.front().is_some_and(|sent| now.duration_since(*sent) >= RATE_WINDOW) {
+            client.sent.pop_front();
+        }
+        if client.sent.len() >= limit {
+            return Some(client.sent.front().map_or(RATE_WINDOW, |oldest| RATE_WINDOW - now.duration_since(*oldest)));
+        }
+        client.sent.push_back(now);
+        None
+    }
+
+    // Stores a room message and numbers it; sequence numbers never repeat within a room
+    async fn add_history(&self, msg: &mut ChatMessage) {
+        let limit = self.config.lock().await.history_limit;
+        let mut history = self.history.lock().await;
+        let room_history = history.entry(msg.room.clone()).or_default();
+        let mut last_seq = self.last_seq.lock().await;
+        let seq = last_seq.entry(msg.room.clone()).or_default();
+        *seq += 1;
+        msg.seq = Some(*seq);
+        room_history.push_back(msg.clone());
+        let over = room_history.len().saturating_sub(limit);
+        let dropped: Vec<ChatMessage> = room_history.drain(..over).collect();
+        if let Some(journal) = &self.journal {
+            journal.append(msg);
+        }
+        drop((history, last_seq));
+        self.archive(&msg.room, dropped).await;
+    }
+
+    // Keeps messages leaving memory in the archive, if the config has one. Written in the
+    // background; a failure is only reported.
+    async fn archive(&self, room: &RoomName, messages: Vec<ChatMessage>) {
+        let Some(settings) = self.config.lock().await.archive.clone() else { return };
+        if messages.is_empty() || self.rooms.lock().await.ttl(room).is_some() {
+            return;
+        }
+        self.archiver.store(&settings.dir, room, messages);
+    }
+
+    // Moves messages older than the archive's `after` out of memory. Rooms with a TTL are left
+    // for it, and messages that couldn't be written go back for the next try.
+    async fn archive_old(&self) {
+        let Some(settings) = self.config.lock().await.archive.clone() else { return };
+        let Some(after) = settings.after.as_deref().and_then(rooms::parse_lifetime) else { return };
+        let cutoff = chrono::Utc::now() - after;
+        let rooms: Vec<RoomName> = self.history.lock().await.iter().filter(|(_, msgs)| msgs.front().is_some_and(|m| m.timestamp < cutoff)).map(|(room, _)| room.clone()).collect();
+        let mut moved = false;
+        for room in rooms {
+            if self.rooms.lock().await.ttl(&room).is_some() {
+                continue;
+            }
+            // Taken by age under one lock, so a trim or purge in between can't shift what goes
+            let old: Vec<ChatMessage> = match self.history.lock().await.get_mut(&room) {
+                Some(room_history) => {
+                    let count = room_history.iter().take_while(|m| m.timestamp < cutoff).count();
+                    room_history.drain(..count).collect()
+                }
+                None => continue,
+            };
+            if old.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.archiver.store_and_wait(&settings.dir, &room, old.clone()).await {
+                eprintln!("Could not archive {} messages from #{} to {}: {}", old.len(), room, settings.dir.display(), e);
+                let mut history = self.history.lock().await;
+                let room_history = history.entry(room).or_default();
+                for msg in old.into_iter().rev() {
+                    room_history.push_front(msg);
+                }
+                continue;
+            }
+            moved = true;
+        }
+        if moved {
+            self.rewrite_journal().await;
+        }
+    }
+
+    // Replays a journal into history, keeping each room's newest `history_limit` messages
+    async fn load_history(&self, messages: Vec<ChatMessage>) {
+        let limit = self.config.lock().await.history_limit;
+        let mut history = self.history.lock().await;
+        let mut last_seq = self.last_seq.lock().await;
+        for msg in messages {
+            let seq = last_seq.entry(msg.room.clone()).or_default();
+            *seq = (*seq).max(msg.seq.unwrap_or_default());
+            let room_history = history.entry(msg.room.clone()).or_default();
+            room_history.push_back(msg);
+            if room_history.len() > limit {
+                room_history.pop_front();
+            }
+        }
+    }
+
+    // Replaces the journal with what is in memory, after messages were purged or evicted
+    async fn rewrite_journal(&self) {
+        if let Some(journal) = &self.journal {
+            // Queued under the history lock, so no append can land between the copy and the rewrite
+            let history = self.history.lock().await;
+            journal.rewrite(history.values().flatten().cloned().collect());
+        }
+    }
+
+    // Stores and broadcasts a room message, then copies it into every room mirrored from its
+    // room, directly or through other mirrors; each room gets one copy, so cycles end
+    async fn post(&self, mut msg: ChatMessage, parent: &Span) {
+        let mut span = parent.child("broadcast");
+        span.attr("chat.room", &msg.room);
+        span.attr("chat.receivers", self.broadcast_tx.receiver_count());
+        self.add_history(&mut msg).await;
+        self.activity.lock().await.record(&msg.room, msg.timestamp);
+        self.broadcast(msg.clone());
+        let targets = self.mirror_targets(&msg.room).await;
+        span.attr("chat.mirrors", targets.len());
+        for room in targets {
+            let mut copy = msg.clone();
+            copy.id = MessageId::new();
+            copy.mirrored_from = Some(msg.room.clone());
+            copy.room = room;
+            self.add_history(&mut copy).await;
+            self.broadcast(copy);
+        }
+    }
+
+    // `name++` in a message posted to a room with karma on thanks whoever is in the room by that name
+    async fn thank_named(&self, msg: &ChatMessage) {
+        if !self.rooms.lock().await.has_karma(&msg.room) {
+            return;
+        }
+        let present = self.users_in_room(&msg.room).await;
+        for name in karma::plus_plus(&msg.content) {
+            if name != msg.username && present.iter().any(|user| user == name) {
+                self.thank(&msg.room, &msg.username, name).await;
+            }
+        }
+    }
+
+    // False while `giver` must wait to thank `receiver` again
+    async fn thank(&self, room: &RoomName, giver: &str, receiver: &str) -> bool {
+        let Some(score) = self.karma.lock().await.thank(room, giver, receiver) else { return false };
+        let update = KarmaScores { scores: [(receiver.to_string(), score)].into(), replace: false };
+        self.broadcast(ChatMessage::new("System".to_string(), serde_json::to_string(&update).unwrap_or_default(), room.clone(), MessageType::Karma));
+        self.broadcast(ChatMessage::system(String::new(), room.clone()).with_template("sys.thanked", &[giver, receiver, &score.to_string()]));
+        true
+    }
+
+    // Every score in `room`, or none when karma is off there, for clients to show from scratch
+    async fn karma_scores(&self, room: &RoomName) -> ChatMessage {
+        let scores = if self.rooms.lock().await.has_karma(room) { self.karma.lock().await.scores(room) } else { Default::default() };
+        let scores = serde_json::to_string(&KarmaScores { scores, replace: true }).unwrap_or_default();
+        ChatMessage::new("System".to_string(), scores, room.clone(), MessageType::Karma)
+    }
+
+    // Passes a message posted in a room to the game being played there, if any
+    async fn play(&self, msg: &ChatMessage) {
+        let said = self.games.lock().await.message(&msg.room, &msg.username, &msg.content, std::time::Instant::now());
+        self.game_says(&msg.room, said);
+    }
+
+    // What a room's game said, as system lines in the room
+    fn game_says(&self, room: &RoomName, said: Vec<Say>) {
+        for say in said {
+            let args: Vec<&str> = say.args.iter().map(String::as_str).collect();
+            self.broadcast(ChatMessage::system(String::new(), room.clone()).with_template(say.key, &args));
+        }
+    }
+
+    async fn mirror_targets(&self, origin: &RoomName) -> Vec<RoomName> {
+        let mirrors = self.mirrors.lock().await.clone();
+        if mirrors.is_empty() {
+            return Vec::new();
+        }
+        let known = self.known_rooms().await;
+        let mut seen = HashSet::from([origin.clone()]);
+        let mut queue = VecDeque::from([origin.clone()]);
+        let mut targets = Vec::new();
+        while let Some(room) = queue.pop_front() {
+            for (_, to) in mirrors.iter().filter(|(from, _)| *from == room) {
+                let rooms: Vec<RoomName> = match to.strip_suffix("/*") {
+                    Some(category) => known.iter().filter(|r| r.strip_prefix(category).is_some_and(|rest| rest.starts_with('/'))).cloned().collect(),
+                    None => vec![to.clone()],
+                };
+                for target in rooms {
+                    if seen.insert(target.clone()) {
+                        targets.push(target.clone());
+                        queue.push_back(target);
+                    }
+                }
+            }
+        }
+        targets
+    }
+
+    async fn known_rooms(&self) -> HashSet<RoomName> {
+        let mut rooms: HashSet<RoomName> = self.history.lock().await.keys().cloned().collect();
+        rooms.extend(self.rooms.lock().await.names().cloned());
+        rooms.extend(self.clients.lock().await.values().map(|c| c.room()));
+        rooms
+    }
+
+    async fn room_history(&self, room: &str) -> Vec<ChatMessage> {
+        let history = self.history.lock().await;
+        let Some(room_history) = history.get(room) else { return Vec::new() };
+        room_history.range(room_history.len().saturating_sub(HISTORY_LIMIT)..).cloned().collect()
+    }
+
+    // The messages around `#room/seq`, or None once it has left the archive
+    async fn history_window(&self, room: &str, seq: u64) -> Option<Vec<ChatMessage>> {
+        let history = self.history.lock().await;
+        let room_history = history.get(room)?;
+        let index = room_history.iter().position(|m| m.seq == Some(seq))?;
+        let start = index.saturating_sub(HISTORY_WINDOW);
+        let end = (index + HISTORY_WINDOW + 1).min(room_history.len());
+        Some(room_history.range(start..end).cloned().collect())
+    }
+
+    // Rooms come into being when first joined; one exists once claimed, or while it has members or history
+    async fn room_exists(&self, room: &str) -> bool {
+        room == RoomName::general()
+            || self.rooms.lock().await.contains(room)
+            || self.clients.lock().await.values().any(|c| *c.room.borrow() == room)
+            || self.history.lock().await.contains_key(room)
+    }
+
+    async fn is_guest(&self, username: &str) -> bool {
+        self.clients.lock().await.get(username).is_some_and(|c| c.is_guest)
+    }
+
+    async fn is_admin(&self, username: &str) -> bool {
+        self.clients.lock().await.get(username).is_some_and(|c| c.is_admin)
+    }
+
+    // Admins, and the room's owner and moderators
+    async fn moderates(&self, username: &str, room: &str) -> bool {
+        self.is_admin(username).await || self.rooms.lock().await.level(room, username) == Level::Moderators
+    }
+
+    // Every room action is checked here, so admins and owners are treated the same everywhere
+    async fn permitted(&self, room: &str, username: &str, action: Action) -> bool {
+        self.is_admin(username).await || self.rooms.lock().await.permits(room, username, action)
+    }
+
+    // An admin, account or online user whose name can't be told apart from `username`
+    async fn lookalike(&self, username: &str) -> Option<String> {
+        let folded = names::skeleton(username);
+        let online: Vec<String> = self.clients.lock().await.keys().cloned().collect();
+        let registered: Vec<String> = self.accounts.lock().await.names().cloned().collect();
+        self.admins.iter().cloned().chain(online).chain(registered).find(|name| name != username && names::skeleton(name) == folded)
+    }
+
+    // Who a command for online users means by `target`: that user or, where `prefix` allows, the
+    // only one online whose name starts with it. Otherwise tells the sender who is closest, with
+    // the command redone for the first of them as the hint.
+    async fn online_target(&self, username: &str, target: &str, prefix: bool, usage: CommandError, rest: &str) -> Option<String> {
+        let online: Vec<String> = self.clients.lock().await.keys().cloned().collect();
+        if online.iter().any(|name| name == target) {
+            return Some(target.to_string());
+        }
+        if let Some(name) = names::unique_prefix(target, &online).filter(|_| prefix) {
+            return Some(name.clone());
+        }
+        let others: Vec<String> = online.into_iter().filter(|name| name != username).collect();
+        let suggestions = names::closest(target, &others);
+        let reply = match suggestions.first() {
+            Some(best) => {
+                let hint = format!("{} {} {}", usage.command, best, rest).trim_end().to_string();
+                ChatMessage::error(String::new())
+                    .with_template("err.not_online_suggest", &[target, &suggestions.join(", ")])
+                    .with_reason(ErrorReason::Usage(usage.with_hint(hint)))
+            }
+            None => ChatMessage::error(String::new()).with_template("err.not_online", &[target]),
+        };
+        self.send_to(username, reply).await;
+        None
+    }
+
+    // Tells a muted user so; true if they are
+    async fn refuse_muted(&self, username: &str) -> bool {
+        let (left, until) = {
+            let mut warnings = self.warnings.lock().await;
+            match (warnings.muted_for(username), warnings.muted_until(username)) {
+                (Some(left), Some(until)) => (left, until),
+                _ => return false,
+            }
+        };
+        self.send_to(username, ChatMessage::error(String::new()).with_template("err.muted", &[&rooms::format_lifetime(left)]).with_reason(ErrorReason::Muted { until })).await;
+        true
+    }
+
+    // Queues a message for admins to review with `/flagged` or the moderation panel
+    async fn flag(&self, msg: ChatMessage, reason: String) {
+        let mut flagged = self.flagged.lock().await;
+        flagged.push_back((msg, reason));
+        if flagged.len() > FLAGGED_LIMIT {
+            flagged.pop_front();
+        }
+    }
+
+    // Runs a room message past the config's `moderation`; false if it must not be posted
+    async fn moderate(&self, msg: &ChatMessage) -> bool {
+        let Some(settings) = self.config.lock().await.moderation.clone() else { return true };
+        if !self.rooms.lock().await.is_moderated(&msg.room) {
+            return true;
+        }
+        let (verdict, reason) = settings.check(&msg.room, &msg.username, &msg.content).await;
+        match verdict {
+            Verdict::Allow => true,
+            Verdict::Flag => {
+                self.audit.record(&msg.username, "flagged", &format!("room=#{} id={} reason={}", msg.room, msg.id, reason));
+                let preview: String = msg.content_lines().collect::<Vec<_>>().join(" ");
+                self.notify_admins(ChatMessage::system(String::new(), msg.room.clone()).with_template("sys.flagged", &[&msg.username, &msg.room, &reason, &preview])).await;
+                self.flag(msg.clone(), reason).await;
+                true
+            }
+            Verdict::Block => {
+                self.audit.record(&msg.username, "blocked", &format!("room=#{} reason={}", msg.room, reason));
+                self.send_to(&msg.username, ChatMessage::error(String::new()).with_template("err.blocked", &[&reason])).await;
+                false
+            }
+        }
+    }
+
+    // Checks the links in a room or private message against the config's `links`, adding a
+    // warning to the message or refusing it; false if it must not be delivered
+    async fn screen_links(&self, msg: &mut ChatMessage) -> bool {
+        let Some(settings) = self.config.lock().await.links.clone() else { return true };
+        let Some((screen, hit)) = settings.screen(&msg.content).await else { return true };
+        let action = if screen == Screen::Block { "link_blocked" } else { "link_warned" };
+        let place = if msg.msg_type == MessageType::PrivateMessage { format!("@{}", msg.recipient.as_deref().unwrap_or_default()) } else { format!("#{}", msg.room) };
+        self.audit.record(&msg.username, action, &format!("to={} url={} threat={}", place, hit.url, hit.threat));
+        let mut hits = self.link_hits.lock().await;
+        hits.push_back(format!("{} {} {} to {}: {} ({})", msg.timestamp.format("%Y-%m-%d %H:%M"), action, msg.username, place, hit.url, hit.threat));
+        if hits.len() > LINK_HITS_LIMIT {
+            hits.pop_front();
+        }
+        drop(hits);
+        if screen == Screen::Block {
+            self.send_to(&msg.username, ChatMessage::error(String::new()).with_template("err.link_blocked", &[&hit.url, &hit.threat])).await;
+            return false;
+        }
+        msg.link_warning = Some(hit);
+        true
+    }
+
+    // Sends `notice` to a user and closes their connection; the room they were in, if they were online
+    async fn kick(&self, username: &str, notice: ChatMessage) -> Option<RoomName> {
+        let (room, hangup) = self.clients.lock().await.get(username).map(|c| (c.room(), c.hangup.clone()))?;
+        self.send_to(username, notice).await;
+        hangup.cancel("kicked");
+        Some(room)
+    }
+
+    async fn may_enter(&self, room: &str, username: &str) -> bool {
+        if self.is_admin(username).await {
+            return true;
+        }
+        let rooms = self.rooms.lock().await;
+        rooms.may_enter(room, username, &*self.groups.lock().await)
+    }
+
+    // Guests leave nothing behind once they disconnect, so their names can be handed out again
+    async fn forget_guest(&self, username: &str) {
+        self.profiles.lock().await.remove(username);
+        self.starred.lock().await.remove(username);
+        for markers in self.read_markers.lock().await.values_mut() {
+            markers.remove(username);
+        }
+        self.rooms.lock().await.forget(username);
+        self.groups.lock().await.forget(username);
+    }
+
+    // Every known room with its member count, leaving out invite-only rooms the user can't enter
+    async fn room_list(&self, username: &str) -> Vec<RoomEntry> {
+        let mut counts: HashMap<RoomName, usize> = HashMap::from([(RoomName::general(), 0)]);
+        counts.extend(self.history.lock().await.keys().map(|room| (room.clone(), 0)));
+        let is_admin = {
+            let clients = self.clients.lock().await;
+            for client in clients.values() {
+                *counts.entry(client.room()).or_default() += 1;
+            }
+            clients.get(username).is_some_and(|c| c.is_admin)
+        };
+        let rooms = self.rooms.lock().await;
+        let groups = self.groups.lock().await;
+        for room in rooms.names() {
+            counts.entry(room.clone()).or_default();
+        }
+        counts
+            .into_iter()
+            .filter(|(room, _)| is_admin || rooms.may_enter(room, username, &groups))
+            .map(|(room, users)| RoomEntry {
+                invite_only: rooms.is_invite_only(&room),
+                ttl: rooms.ttl(&room).map(rooms::format_lifetime),
+                read_only: !is_admin && !rooms.permits(&room, username, Action::Post),
+                name: room.into(),
+                users,
+            })
+            .collect()
+    }
+
+    // Rooms that aren't invite-only where every word of `query` is in the name, a tag or the
+    // description, ignoring case; busiest first, by people in them and then messages today
+    async fn discover(&self, username: &str, query: &str) -> Discovery {
+        let words: Vec<String> = query.split_whitespace().map(|word| word.trim_start_matches('#').to_lowercase()).collect();
+        let now = chrono::Utc::now();
+        let entries = self.room_list(username).await;
+        let mut found = Vec::new();
+        {
+            let rooms = self.rooms.lock().await;
+            let history = self.history.lock().await;
+            let activity = self.activity.lock().await;
+            for entry in entries.into_iter().filter(|entry| !entry.invite_only) {
+                let description = rooms.description(&entry.name).map(str::to_string);
+                let tags = rooms.tags(&entry.name);
+                let haystack = format!("{} {} {}", entry.name, tags.join(" "), description.as_deref().unwrap_or_default()).to_lowercase();
+                if !words.iter().all(|word| haystack.contains(word.as_str())) {
+                    continue;
+                }
+                let Ok(room) = RoomName::new(&entry.name) else { continue };
+                found.push(DiscoveredRoom {
+                    messages_today: activity.last_day(&room, now),
+                    last_active: history.get(&room).and_then(|messages| messages.back()).map(|msg| msg.timestamp),
+                    name: entry.name,
+                    description,
+                    tags,
+                    users: entry.users,
+                });
+            }
+        }
+        found.sort_by(|a, b| (b.users, b.messages_today, b.last_active).cmp(&(a.users, a.messages_today, a.last_active)).then_with(|| a.name.cmp(&b.name)));
+        found.truncate(DISCOVER_RESULTS);
+        Discovery { query: query.to_string(), rooms: found }
+    }
+
+    // Drops messages older than their room's TTL, starred copies included, and tells
+    // each room which messages went
+    async fn purge_expired(&self) {
+        let ttls = self.rooms.lock().await.ttls();
+        let now = chrono::Utc::now();
+        let mut expired: HashSet<MessageId> = HashSet::new();
+        for (room, ttl) in ttls {
+            let ids: Vec<MessageId> = {
+                let mut history = self.history.lock().await;
+                let Some(room_history) = history.get_mut(&room) else { continue };
+                let old = room_history.iter().filter(|m| m.timestamp + ttl <= now).map(|m| m.id).collect();
+                room_history.retain(|m| m.timestamp + ttl > now);
+                old
+            };
+            if !ids.is_empty() {
+                self.broadcast(ChatMessage::new("System".to_string(), ids.iter().map(MessageId::to_string).collect::<Vec<_>>().join(","), room, MessageType::Expired));
+                expired.extend(ids);
+            }
+        }
+        if !expired.is_empty() {
+            for saved in self.starred.lock().await.values_mut() {
+                saved.retain(|m| !expired.contains(&m.id));
+            }
+            for pinned in self.pins.lock().await.values_mut() {
+                pinned.retain(|m| !expired.contains(&m.id));
+            }
+            self.flagged.lock().await.retain(|(m, _)| !expired.contains(&m.id));
+            self.rewrite_journal().await;
+        }
+    }
+
+    // Frees memory held for rooms nobody is in: those quiet past the config's `history_idle`,
+    // and the least recently active beyond `history_rooms`
+    async fn evict_history(&self) {
+        let (idle, max_rooms) = {
+            let config = self.config.lock().await;
+            (config.history_idle.as_deref().and_then(rooms::parse_lifetime), config.history_rooms)
+        };
+        if idle.is_none() && max_rooms.is_none() {
+            return;
+        }
+        let occupied: HashSet<RoomName> = self.clients.lock().await.values().map(|c| c.room()).collect();
+        let now = chrono::Utc::now();
+        let mut history = self.history.lock().await;
+        let over = max_rooms.map_or(0, |max| history.len().saturating_sub(max));
+        let mut idle_rooms: Vec<(chrono::DateTime<chrono::Utc>, RoomName)> = history
+            .iter()
+            .filter(|(room, _)| !occupied.contains(*room))
+            .map(|(room, msgs)| (msgs.back().map_or(chrono::DateTime::<chrono::Utc>::MIN_UTC, |m| m.timestamp), room.clone()))
+            .collect();
+        idle_rooms.sort();
+        let evicted: Vec<RoomName> = idle_rooms
+            .into_iter()
+            .enumerate()
+            .filter(|(i, (last, _))| *i < over || idle.is_some_and(|idle| now - *last > idle))
+            .map(|(_, (_, room))| room)
+            .collect();
+        let dropped: Vec<(RoomName, VecDeque<ChatMessage>)> = evicted.iter().filter_map(|room| Some((room.clone(), history.remove(room)?))).collect();
+        drop(history);
+        for (room, msgs) in dropped {
+            self.archive(&room, Vec::from(msgs)).await;
+        }
+        if !evicted.is_empty() {
+            println!("Dropped the history of {} idle rooms", evicted.len());
+            self.rewrite_journal().await;
+        }
+    }
+
+    async fn users_in_room(&self, room: &str) -> Vec<String> {
+        let clients = self.clients.lock().await;
+        let mut users: Vec<String> = clients.iter().filter(|(_, c)| *c.room.borrow() == room).map(|(name, _)| name.clone()).collect();
+        users.sort();
+        users
+    }
+
+    async fn away_in_room(&self, room: &str) -> Vec<(String, String)> {
+        let clients = self.clients.lock().await;
+        clients.iter().filter(|(_, c)| *c.room.borrow() == room).filter_map(|(name, c)| Some((name.clone(), c.away.clone()?))).collect()
+    }
+
+    // Sends to a single user, ignoring users that have already disconnected
+    async fn send_to(&self, username: &str, msg: ChatMessage) {
+        if let Some(client) = self.clients.lock().await.get(username) {
+            client.tx.send(msg);
+        }
+    }
+
+    // Records a read marker for a room message or a received PM; returns who should hear about it
+    async fn mark_read(&self, username: &str, message_id: MessageId) -> Option<ReadTarget> {
+        let room = {
+            let history = self.history.lock().await;
+            history.iter().find(|(_, msgs)| msgs.iter().any(|m| m.id == message_id)).map(|(room, _)| room.clone())
+        };
+        if let Some(room) = room {
+            self.read_markers.lock().await.entry(room.clone()).or_default().insert(username.to_string(), message_id);
+            return Some(ReadTarget::Room(room));
+        }
+        let pms = self.recent_pms.lock().await;
+        pms.iter()
+            .find(|m| m.id == message_id && m.recipient.as_deref() == Some(username))
+            .map(|m| ReadTarget::User(m.username.clone()))
+    }
+
+    async fn display_name(&self, username: &str) -> Option<String> {
+        self.profiles.lock().await.get(username).and_then(|p| p.display_name.clone())
+    }
+
+    // Why `value` can't be the user's display name, if it can't
+    async fn refuse_display_name(&self, username: &str, value: &str) -> Option<ChatMessage> {
+        let max_len = UserProfile::max_len("display_name");
+        if value.chars().count() > max_len {
+            return Some(ChatMessage::error(String::new()).with_template("err.profile_too_long", &["display_name", &max_len.to_string()]));
+        }
+        // Display names must not pass for someone else's username
+        let taken = !value.eq_ignore_ascii_case(username) && {
+            let online = self.clients.lock().await.keys().any(|u| u.eq_ignore_ascii_case(value));
+            online || self.accounts.lock().await.is_registered(value)
+        };
+        taken.then(|| ChatMessage::error(String::new()).with_template("err.display_name_taken", &[value]))
+    }
+
+    async fn auto_join_rooms(&self, username: &str) -> Vec<RoomName> {
+        let defaults = self.config.lock().await.auto_join.clone();
+        self.auto_join.lock().await.rooms(username, &defaults)
+    }
+
+    // Where a session starts: the first room of the user's auto-join list they may enter.
+    // Guests can't create rooms, so they skip ones that don't exist yet.
+    async fn landing_room(&self, username: &str, guest: bool) -> RoomName {
+        for room in self.auto_join_rooms(username).await {
+            if self.may_enter(&room, username).await && (!guest || self.room_exists(&room).await) {
+                return room;
+            }
+        }
+        RoomName::general()
+    }
+
+    // The user's auto-join list, for clients to keep those rooms at hand, followed by the
+    // broadcast rooms they may enter, which everyone is subscribed to
+    async fn send_auto_join(&self, username: &str) {
+        let mut rooms = self.auto_join_rooms(username).await;
+        let broadcasts: Vec<RoomName> = self.rooms.lock().await.broadcasts().cloned().collect();
+        for room in broadcasts {
+            if !rooms.contains(&room) && self.may_enter(&room, username).await {
+                rooms.push(room);
+            }
+        }
+        let rooms = serde_json::to_string(&rooms).unwrap_or_default();
+        self.send_to(username, ChatMessage::new("System".to_string(), rooms, RoomName::global(), MessageType::AutoJoin)).await;
+    }
+
+    // Puts the first onboarding question to someone on their first visit, when the config asks
+    async fn welcome(&self, username: &str) {
+        if !self.config.lock().await.onboarding.enabled || !self.onboarding.lock().await.begin(username) {
+            return;
+        }
+        self.ask(username, Some(Step::DisplayName)).await;
+    }
+
+    // Sends the onboarding form for `step`, or says they are done when there are no more steps.
+    // With no rooms to suggest, the room question is passed over.
+    async fn ask(&self, username: &str, mut step: Option<Step>) {
+        let rooms = self.suggested_rooms(username).await;
+        while step == Some(Step::Room) && rooms.is_empty() {
+            step = self.onboarding.lock().await.advance(username);
+        }
+        let Some(step) = step else {
+            let room = current_room(self, username).await;
+            self.send_to(username, ChatMessage::system(String::new(), room).with_template("sys.onboarded", &[])).await;
+            return;
+        };
+        let display_name = self.display_name(username).await.unwrap_or_else(|| username.to_string());
+        let picked = self.auto_join_rooms(username).await;
+        let content = serde_json::to_string(&onboarding::prompt(step, &display_name, &rooms, &picked)).unwrap_or_default();
+        self.send_to(username, ChatMessage::new("System".to_string(), content, RoomName::global(), MessageType::Prompt)).await;
+    }
+
+    // Rooms for new users: those the config names, or else the busiest open ones
+    async fn suggested_rooms(&self, username: &str) -> Vec<RoomName> {
+        let named = self.config.lock().await.onboarding.rooms.clone();
+        if !named.is_empty() {
+            let mut rooms = Vec::new();
+            for room in named {
+                if self.may_enter(&room, username).await {
+                    rooms.push(room);
+                }
+            }
+            return rooms;
+        }
+        let mut open: Vec<RoomEntry> = self.room_list(username).await.into_iter().filter(|entry| !entry.invite_only).collect();
+        open.sort_by(|a, b| b.users.cmp(&a.users).then_with(|| a.name.cmp(&b.name)));
+        open.into_iter().filter_map(|entry| RoomName::new(&entry.name).ok()).take(onboarding::SUGGESTED_ROOMS).collect()
+    }
+
+    // A room message still in history, or a recent PM the user sent or received
+    // A message `username` may see: in a room they may enter, or a private message to or from them
+    async fn find_message(&self, username: &str, message_id: MessageId) -> Option<ChatMessage> {
+        let found = self.history.lock().await.values().flatten().find(|m| m.id == message_id).cloned();
+        if let Some(found) = found {
+            return self.may_enter(&found.room, username).await.then_some(found);
+        }
+        let pms = self.recent_pms.lock().await;
+        pms.iter()
+            .find(|m| m.id == message_id && (m.username == username || m.recipient.as_deref() == Some(username)))
+            .cloned()
+    }
+
+    async fn room_read_markers(&self, room: &str) -> Vec<(String, MessageId)> {
+        self.read_markers.lock().await.get(room).map(|markers| markers.iter().map(|(u, id)| (u.clone(), *id)).collect()).unwrap_or_default()
+    }
+
+    // For each room `username` has read in, what others posted there since, as notices for
+    // `room`; rooms with nothing new are left out
+    async fn digest(&self, username: &str, room: &RoomName) -> Vec<ChatMessage> {
+        let mut markers: Vec<(RoomName, MessageId)> = {
+            let read_markers = self.read_markers.lock().await;
+            read_markers.iter().filter_map(|(room, markers)| markers.get(username).map(|id| (room.clone(), *id))).collect()
+        };
+        markers.sort_by(|a, b| a.0.cmp(&b.0));
+        let history = self.history.lock().await;
+        let mut notices = Vec::new();
+        for (read_room, marker) in markers {
+            let Some(messages) = history.get(&read_room) else { continue };
+            // A marker that is no longer stored is older than everything that is
+            let after = messages.iter().position(|m| m.id == marker).map_or(0, |i| i + 1);
+            let since = messages.get(after.saturating_sub(1)).map(|m| m.timestamp);
+            let new: Vec<&ChatMessage> = messages.iter().skip(after).filter(|m| m.msg_type == MessageType::Chat && m.username != username).collect();
+            let Some(since) = since.filter(|_| !new.is_empty()) else { continue };
+            let mentioned = new.iter().filter(|m| common::mentions(&m.content, username) || m.mentions.iter().any(|u| u == username)).count();
+            let ago = common::format_elapsed((chrono::Utc::now() - since).num_seconds().max(0) as u64);
+            let args = [read_room.as_str(), &new.len().to_string(), &mentioned.to_string(), &ago];
+            notices.push(ChatMessage::system(String::new(), room.clone()).with_template("sys.digest", &args));
+        }
+        notices
+    }
+
+    // Group members who are online but elsewhere hear about a group mention in their own room
+    async fn notify_mentioned(&self, msg: &ChatMessage) {
+        if msg.mentions.is_empty() {
+            return;
+        }
+        let preview: String = msg.content_lines().collect::<Vec<_>>().join(" ");
+        for (name, client) in self.clients.lock().await.iter() {
+            let room = client.room();
+            if *name != msg.username && room != msg.room && msg.mentions.iter().any(|member| member == name) {
+                client.tx.send(ChatMessage::system(String::new(), room).with_template("sys.group_mention", &[&msg.username, &msg.room, &preview]));
+            }
+        }
+    }
+
+    // A system notice to every connected user, in whatever room they are in
+    async fn announce(&self, key: &str, args: &[&str]) {
+        for client in self.clients.lock().await.values() {
+            client.tx.send(ChatMessage::system(String::new(), client.room()).with_template(key, args));
+        }
+    }
+
+    async fn notify_admins(&self, msg: ChatMessage) {
+        for client in self.clients.lock().await.values().filter(|c| c.is_admin) {
+            client.tx.send(msg.clone());
+        }
+    }
+
+    async fn maintenance_id(&self) -> Option<String> {
+        self.maintenance.lock().await.as_ref().map(|m| m.id.clone())
+    }
+
+    fn broadcast(&self, msg: ChatMessage) {
+        // No receivers just means nobody is connected
+        let _ = self.broadcast_tx.send(Arc::new(Encoded { msg, lines: Default::default() }));
+    }
+}
+
+enum ReadTarget {
+    Room(RoomName),
+    User(String),
+}
+
+// Where the server keeps its files and who may do what; `from_env` reads the variables
+// documented in the README, and tests point everything at a temporary directory
+pub struct ServerOptions {
+    pub admins: Vec<String>,
+    pub accounts_file: PathBuf,
+    pub guests: bool, // Anyone may join under a temporary name
+    pub audit_log: PathBuf,
+    pub config_file: PathBuf,
+    pub snapshot_file: PathBuf,
+    pub restore_snapshot: Option<PathBuf>, // Loaded at startup
+    pub history_file: Option<PathBuf>, // Keeps room history on disk across restarts
+    pub chaos: Option<Chaos>, // Misbehaves towards a share of clients, for testing them
+    pub record_file: Option<PathBuf>, // Records everything clients send, for `replay`
+    pub geoip: Vec<PathBuf>, // MaxMind DB files, such as GeoLite2 Country and ASN
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            admins: vec!["admin".to_string()],
+            accounts_file: "accounts.json".into(),
+            guests: false,
+            audit_log: "audit.log".into(),
+            config_file: "server.json".into(),
+            snapshot_file: "snapshot.json".into(),
+            restore_snapshot: None,
+            history_file: None,
+            chaos: None,
+            record_file: None,
+            geoip: Vec::new(),
+        }
+    }
+}
+
+impl ServerOptions {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let path = |name: &str, default: PathBuf| env::var_os(name).map(PathBuf::from).unwrap_or(default);
+        Self {
+            admins: env::var("ADMINS").map(|admins| admins.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect()).unwrap_or(defaults.admins),
+            accounts_file: path("ACCOUNTS_FILE", defaults.accounts_file),
+            guests: env::var("GUEST_ACCESS").is_ok_and(|v| matches!(v.as_str(), "1" | "true" | "yes")),
+            audit_log: path("AUDIT_LOG", defaults.audit_log),
+            config_file: path("CONFIG_FILE", defaults.config_file),
+            snapshot_file: path("SNAPSHOT_FILE", defaults.snapshot_file),
+            restore_snapshot: env::var_os("RESTORE_SNAPSHOT").map(PathBuf::from),
+            history_file: env::var_os("HISTORY_FILE").map(PathBuf::from),
+            chaos: env::var("CHAOS").ok().and_then(|value| {
+                let chaos = Chaos::parse(&value);
+                if chaos.is_none() {
+                    eprintln!("Ignoring CHAOS={}: expected a percentage from 1 to 100", value);
+                }
+                chaos
+            }),
+            record_file: env::var_os("RECORD_FILE").map(PathBuf::from),
+            geoip: env::var("GEOIP_DB").map(|paths| paths.split(',').map(|path| PathBuf::from(path.trim())).filter(|path| !path.as_os_str().is_empty()).collect()).unwrap_or_default(),
+        }
+    }
+}
+
+// A server with its state loaded, ready to accept connections
+pub struct ChatServer {
+    state: Arc<ServerState>,
+    http: Option<TcpListener>, // For feeds
+}
+
+impl ChatServer {
+    // Loads accounts, config, journaled history and any snapshot; needs the runtime for its background tasks
+    pub async fn new(options: ServerOptions) -> Result<Self, ChatError> {
+        let mut storage: Vec<PathBuf> = [&options.accounts_file, &options.audit_log, &options.snapshot_file].into_iter().chain(&options.history_file).chain(&options.record_file).map(|file| health::dir_of(file)).collect();
+        storage.sort();
+        storage.dedup();
+        let accounts = Accounts::load(options.accounts_file.clone()).map_err(|source| ChatError::File { action: "read accounts", path: options.accounts_file, source })?;
+        let audit = AuditLog::new(options.audit_log);
+        let config = Config::load(&options.config_file).map_err(ChatError::Config)?;
+        let tracer = Tracer::from_env();
+        let mut state = ServerState::new(options.admins, accounts, options.guests, audit, options.config_file, options.snapshot_file, tracer);
+        state.storage = storage;
+        let journaled = match &options.history_file {
+            Some(path) => Journal::load(path).map_err(|source| ChatError::File { action: "read history", path: path.clone(), source })?,
+            None => Vec::new(),
+        };
+        state.journal = options.history_file.map(Journal::start);
+        state.chaos = options.chaos;
+        if let Some(path) = options.record_file {
+            state.recorder = Some(Recorder::start(path.clone()).map_err(|source| ChatError::File { action: "create recording", path: path.clone(), source })?);
+            println!("Recording client input to {}", path.display());
+        }
+        if !options.geoip.is_empty() {
+            let mut geoip = GeoIp::default();
+            for path in options.geoip {
+                geoip.load(&path).map_err(|source| ChatError::File { action: "read GeoIP database", path: path.clone(), source })?;
+            }
+            state.geoip = Some(geoip);
+        }
+        if let Some(chaos) = options.chaos {
+            println!("Chaos mode: {}% of clients get delayed writes, lost room messages and dropped connections", chaos.percent);
+        }
+        let state = Arc::new(state);
+        // The rest of the config is applied after the snapshot below
+        state.config.lock().await.history_limit = config.history_limit;
+        state.load_history(journaled).await;
+        // Before the config, so its room settings win over the snapshot's
+        if let Some(path) = options.restore_snapshot {
+            let snapshot = Snapshot::load(&path).map_err(|source| ChatError::File { action: "read snapshot", path: path.clone(), source })?;
+            let taken = snapshot.taken;
+            state.restore(snapshot).await?;
+            state.audit.record("System", "snapshot_restore", &format!("file={} taken={}", path.display(), taken.to_rfc3339_opts(SecondsFormat::Secs, true)));
+            println!("Restored snapshot {} taken {}", path.display(), taken.format("%Y-%m-%d %H:%M UTC"));
+        }
+        state.apply_config(config).await;
+        // Compacts the journal down to what survived the limits, TTLs and any snapshot
+        state.rewrite_journal().await;
+        Ok(Self { state, http: None })
+    }
+
+    // Also answers HTTP requests on `listener` while running
+    pub fn with_http(mut self, listener: TcpListener) -> Self {
+        self.http = Some(listener);
+        self
+    }
+
+    // Serves a single connection over `reader` and `writer`, as if it had come from `addr`
+    pub async fn serve<R, W>(&self, reader: R, writer: W, addr: SocketAddr) -> anyhow::Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        serve_connection(reader, writer, addr, self.state.clone()).await
+    }
+
+    // Serves until Ctrl-C or SIGTERM
+    pub async fn run(self, listener: TcpListener) -> std::io::Result<()> {
+        self.run_until(listener, shutdown_signal()).await
+    }
+
+    // Serves until `shutdown` completes, then writes out any journaled history still queued
+    pub async fn run_until(self, listener: TcpListener, shutdown: impl std::future::Future<Output = ()>) -> std::io::Result<()> {
+        let state = self.state;
+        let mut tasks = Vec::new();
+
+        // Backups follow the config's schedule, so a reload can start, change or stop them
+        let backup_state = state.clone();
+        tasks.push(supervise::service("Backups", &state.services, move || {
+            let backup_state = backup_state.clone();
+            async move { backup_state.run_backups().await }
+        }));
+
+        // `kill -HUP` re-reads the config file without dropping anyone
+        #[cfg(unix)]
+        {
+            let hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+            // Shared so a restarted run picks up where the last one stopped
+            let (reload_state, hangup) = (state.clone(), Arc::new(Mutex::new(hangup)));
+            tasks.push(supervise::service("Config reloads", &state.services, move || {
+                let (reload_state, hangup) = (reload_state.clone(), hangup.clone());
+                async move {
+                    let mut hangup = hangup.lock().await;
+                    while hangup.recv().await.is_some() {
+                        match reload_state.reload_config("SIGHUP").await {
+                            Ok(()) => println!("Reloaded {}", reload_state.config_path.display()),
+                            Err(e) => eprintln!("Config not reloaded: {}", e),
+                        }
+                    }
+                }
+            }));
+        }
+
+        let purge_state = state.clone();
+        tasks.push(supervise::service("History upkeep", &state.services, move || {
+            let purge_state = purge_state.clone();
+            async move {
+                let mut interval = tokio::time::interval(PURGE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    purge_state.purge_expired().await;
+                    purge_state.evict_history().await;
+                    purge_state.archive_old().await;
+                }
+            }
+        }));
+
+        let game_state = state.clone();
+        tasks.push(supervise::service("Games", &state.services, move || {
+            let game_state = game_state.clone();
+            async move {
+                let mut interval = tokio::time::interval(GAME_TICK);
+                loop {
+                    interval.tick().await;
+                    let said = game_state.games.lock().await.tick(std::time::Instant::now());
+                    for (room, said) in said {
+                        game_state.game_says(&room, said);
+                    }
+                }
+            }
+        }));
+
+        if let Some(http) = self.http {
+            let (web_state, http) = (state.clone(), Arc::new(http));
+            tasks.push(supervise::service("HTTP listener", &state.services, move || {
+                let (web_state, http) = (web_state.clone(), http.clone());
+                async move {
+                    while let Ok((socket, addr)) = http.accept().await {
+                        let state = web_state.clone();
+                        supervise::task(format!("HTTP request from {}", addr), async move {
+                            if let Err(e) = web::serve(socket, |request| web_route(state, request)).await {
+                                eprintln!("HTTP request from {} failed: {}", addr, e);
+                            }
+                        });
+                    }
+                }
+            }));
+        }
+
+        tokio::pin!(shutdown);
+        state.listening.store(true, Ordering::Relaxed);
+        loop {
+            let (socket, addr) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        state.listening.store(false, Ordering::Relaxed);
+                        return Err(e);
+                    }
+                },
+                _ = &mut shutdown => break,
+            };
+            let state = state.clone();
+            supervise::task(format!("Connection from {}", addr), async move {
+                if let Err(e) = handle_client(socket, state).await {
+                    eprintln!("Connection {} ended with error: {}", addr, e);
+                }
+            });
+        }
+        state.listening.store(false, Ordering::Relaxed);
+        for task in tasks {
+            task.abort();
+        }
+        // Every message posted before the signal reaches the disk before exit
+        if let Some(journal) = &state.journal {
+            journal.close().await;
+        }
+        state.archiver.close().await;
+        if let Some(recorder) = &state.recorder {
+            recorder.close().await;
+        }
+        Ok(())
+    }
+}
+
+impl ServerState {
+    // Backs up whenever the config's schedule says one is due, checking every minute
+    async fn run_backups(&self) {
+        let mut last = chrono::Utc::now();
+        let mut interval = tokio::time::interval(BACKUP_CHECK);
+        loop {
+            interval.tick().await;
+            let settings = self.config.lock().await.backup.clone();
+            let Some(settings) = settings else { continue };
+            let every = rooms::parse_lifetime(&settings.every).unwrap_or(chrono::Duration::days(1));
+            if chrono::Utc::now() - last < every {
+                continue;
+            }
+            last = chrono::Utc::now();
+            match self.back_up(&settings).await {
+                Ok(()) => self.audit.record("System", "backup", &format!("dir={}", settings.dir.display())),
+                Err(e) => {
+                    eprintln!("Backup to {} failed: {}", settings.dir.display(), e);
+                    self.audit.record("System", "backup_failed", &format!("dir={} error={}", settings.dir.display(), e));
+                    let notice = ChatMessage::error(String::new()).with_template("err.backup_failed", &[&settings.dir.display().to_string(), &e.to_string()]);
+                    self.notify_admins(notice).await;
+                }
+            }
+        }
+    }
+}
+
+// What the HTTP listener serves: health checks, and RSS feeds of the rooms the config publishes
+async fn web_route(state: Arc<ServerState>, request: web::Request) -> web::Response {
+    match request.path.as_str() {
+        // Answering at all is enough to be alive
+        "/healthz" => return web::Response::ok("application/json", serde_json::json!({ "status": "ok", "uptime_secs": state.started.elapsed().as_secs() }).to_string()),
+        "/readyz" => {
+            let (ready, checks) = state.readiness().await;
+            let body = serde_json::json!({ "status": if ready { "ready" } else { "unavailable" }, "checks": checks }).to_string();
+            return if ready { web::Response::ok("application/json", body) } else { web::Response::unavailable("application/json", body) };
+        }
+        _ => {}
+    }
+    let Some(room) = web::feed_room(&request.path) else { return web::Response::not_found() };
+    // Unpublished rooms look the same as missing ones
+    if !state.config.lock().await.rooms.get(&room).is_some_and(|settings| settings.feed) {
+        return web::Response::not_found();
+    }
+    let history: Vec<ChatMessage> = state.history.lock().await.get(&room).map(|history| history.iter().cloned().collect()).unwrap_or_default();
+    web::Response::ok("application/rss+xml; charset=utf-8", web::feed(&room, request.host.as_deref(), &history))
+}
+
+impl ServerState {
+    // Whether new users should be sent here, with each check's result: "ok" or what is wrong
+    async fn readiness(&self) -> (bool, serde_json::Value) {
+        let listener = if self.listening.load(Ordering::Relaxed) { "ok".to_string() } else { "not accepting connections".to_string() };
+        let mut dirs = self.storage.clone();
+        {
+            let config = self.config.lock().await;
+            dirs.extend(config.archive.as_ref().map(|archive| archive.dir.clone()));
+            dirs.extend(config.backup.as_ref().map(|backup| backup.dir.clone()));
+        }
+        let failures: Vec<String> = dirs.iter().filter_map(|dir| health::writable(dir).err().map(|e| format!("{}: {}", dir.display(), e))).collect();
+        let storage = if failures.is_empty() { "ok".to_string() } else { failures.join("; ") };
+        let maintenance = match self.maintenance.lock().await.as_ref() {
+            Some(maintenance) => format!("draining at {}: {}", maintenance.drain_at.to_rfc3339_opts(SecondsFormat::Secs, true), maintenance.reason),
+            None => "ok".to_string(),
+        };
+        let services: serde_json::Map<String, serde_json::Value> = self
+            .services
+            .statuses()
+            .into_iter()
+            .map(|(name, status)| {
+                let status = match status {
+                    supervise::Status::Running => "ok".to_string(),
+                    supervise::Status::Restarting { panics } => format!("restarting after {} panics", panics),
+                    supervise::Status::Stopped => "stopped".to_string(),
+                };
+                (name.to_string(), status.into())
+            })
+            .collect();
+        let ready = [&listener, &storage, &maintenance].iter().all(|check| *check == "ok") && services.values().all(|status| status == "ok");
+        (ready, serde_json::json!({ "listener": listener, "storage": storage, "maintenance": maintenance, "services": services }))
+    }
+}
+
+// Ctrl-C, or SIGTERM from a service manager
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let Ok(mut terminate) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) else {
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+async fn handle_client(socket: TcpStream, state: Arc<ServerState>) -> anyhow::Result<()> {
+    let addr = socket.peer_addr()?;
+    let (reader, writer) = socket.into_split();
+    serve_connection(reader, writer, addr, state).await
+}
+
+// One client's session over any byte stream, so tests and fuzzing can drive it without a socket
+async fn serve_connection<R, W>(reader: R, mut writer: W, addr: SocketAddr, state: Arc<ServerState>) -> anyhow::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let mut reader = BufReader::new(reader);
+    let (bytes_in, bytes_out) = (Arc::new(AtomicU64::new(0)), Arc::new(AtomicU64::new(0)));
+    let recording = state.recorder.as_ref().map(|recorder| recorder.open(&addr.to_string()));
+
+    // Handshake: JSON from the TUI client, with a fallback for raw text (e.g. telnet).
+    // Registered accounts answer with an AuthRequired challenge until credentials check out.
+    let mut attempts = 0;
+    let (mut captcha, mut verified) = (None::<Captcha>, false);
+    let mut handshake_span = state.tracer.span("handshake");
+    let (username, locale, guest, conn) = loop {
+        let (frame, read) = frame::read_frame(&mut reader).await?;
+        bytes_in.fetch_add(read as u64, Ordering::Relaxed);
+        state.record(recording, &frame, read);
+        // Nothing is known about the client yet, so these are in English
+        let line = match frame {
+            Frame::Line(line) => line,
+            Frame::Closed => return Ok(()),
+            Frame::TooLong => {
+                writer.write_all(format!("Error: {}\n", ProtocolError::LineTooLong(frame::MAX_LINE)).as_bytes()).await?;
+                return Ok(());
+            }
+            Frame::NotUtf8 => {
+                writer.write_all(format!("Error: {}\n", ProtocolError::NotUtf8).as_bytes()).await?;
+                return Ok(());
+            }
+        };
+        let (handshake, raw) = match serde_json::from_str::<Handshake>(line.trim()) {
+            Ok(handshake) => (handshake, false),
+            // Raw clients send a bare name; anything JSON-like was meant as a handshake
+            Err(e) if line.trim_start().starts_with('{') => {
+                writer.write_all(format!("Error: {}\n", ProtocolError::MalformedHandshake(e.to_string())).as_bytes()).await?;
+                return Ok(());
+            }
+            Err(_) => (Handshake { username: line.trim().to_string(), ..Default::default() }, true),
+        };
+        let username = handshake.username.trim().to_string();
+        let locale = i18n::normalize(handshake.locale.as_deref().unwrap_or_default());
+        // Admins still get in, to do the work or call it off
+        let maintenance = state.maintenance.lock().await.as_ref().map(|m| m.reason.clone());
+        if let Some(reason) = maintenance.filter(|_| handshake.guest || !state.admins.contains(&username)) {
+            writer.write_all(format!("Error: {}\n", i18n::trf(locale, "err.maintenance", &[&reason]).trim_end()).as_bytes()).await?;
+            return Ok(());
+        }
+        if state.config.lock().await.is_banned(&username) {
+            writer.write_all(format!("Error: {}\n", i18n::tr(locale, "err.banned")).as_bytes()).await?;
+            return Ok(());
+        }
+        if let Some(left) = state.warnings.lock().await.banned_for(&username) {
+            writer.write_all(format!("Error: {}\n", i18n::trf(locale, "err.banned_for", &[&rooms::format_lifetime(left)])).as_bytes()).await?;
+            return Ok(());
+        }
+        // Reserved names need an account, whose password is asked for below. Not `is_registered`,
+        // which ignores case: another case is another name, and may be a lookalike.
+        let registered = !handshake.guest && state.accounts.lock().await.names().any(|name| *name == username);
+        if handshake.guest {
+            if !state.guests {
+                writer.write_all(format!("Error: {}\n", i18n::tr(locale, "err.guests_disabled")).as_bytes()).await?;
+                return Ok(());
+            }
+        } else {
+            if state.guests && username.to_lowercase().starts_with(GUEST_PREFIX) {
+                writer.write_all(format!("Error: {}\n", i18n::tr(locale, "err.guest_name_reserved")).as_bytes()).await?;
+                return Ok(());
+            }
+            if let Err(e) = Username::new(username.as_str()) {
+                writer.write_all(format!("Error: {}\n", e.localized(locale)).as_bytes()).await?;
+                return Ok(());
+            }
+            if !registered && state.reserved.lock().await.matches(&username) {
+                writer.write_all(format!("Error: {}\n", i18n::tr(locale, "err.name_reserved")).as_bytes()).await?;
+                return Ok(());
+            }
+            if let Some(similar) = state.lookalike(&username).await.filter(|_| !registered) {
+                println!("Refused {} from {}: looks like {}", username, addr, similar);
+                state.audit.record(&username, "lookalike_refused", &format!("like={}{}", similar, state.describe_addr(addr)));
+                state.notify_admins(ChatMessage::system(String::new(), RoomName::global()).with_template("sys.lookalike_refused", &[&username, &similar])).await;
+                writer.write_all(format!("Error: {}\n", i18n::trf(locale, "err.name_lookalike", &[&similar]).trim_end()).as_bytes()).await?;
+                return Ok(());
+            }
+        }
+        // Guests and users without an account answer a question first, when the config asks,
+        // so registering counts too
+        let kind = state.config.lock().await.captcha;
+        if let Some(kind) = kind.filter(|_| !registered && !verified) {
+            let answer = handshake.captcha.as_deref();
+            if captcha.as_ref().zip(answer).is_some_and(|(captcha, answer)| captcha.accepts(answer)) {
+                verified = true;
+            } else {
+                attempts += 1;
+                // Raw clients have no way to answer
+                let refusal = if raw { Some("err.captcha_required") } else { (attempts >= AUTH_ATTEMPTS).then_some("err.captcha_wrong") };
+                if let Some(error) = refusal {
+                    writer.write_all(format!("Error: {}\n", i18n::tr(locale, error)).as_bytes()).await?;
+                    return Ok(());
+                }
+                if captcha.is_some() && answer.is_some() {
+                    let msg = ChatMessage::error(String::new()).with_template("err.captcha_wrong", &[]).localized(locale);
+                    writer.write_all(format!("{}\n", msg.to_json()?).as_bytes()).await?;
+                }
+                let next = Captcha::new(kind);
+                let prompt = ChatMessage::new("System".to_string(), format!("captcha: {}", next.question(locale)), RoomName::global(), MessageType::AuthRequired);
+                writer.write_all(format!("{}\n", prompt.to_json()?).as_bytes()).await?;
+                captcha = Some(next);
+                continue;
+            }
+        }
+        // Guests are named once registered below
+        if handshake.guest {
+            break (String::new(), locale, true, ConnInfo::new(addr, &handshake, raw, locale, bytes_in.clone(), bytes_out.clone()));
+        }
+        let authenticated = {
+            let _span = handshake_span.child("accounts.authenticate");
+            state.accounts.lock().await.authenticate(&username, &handshake)
+        };
+        let challenge = match authenticated {
+            Ok(()) => break (username, locale, false, ConnInfo::new(addr, &handshake, raw, locale, bytes_in.clone(), bytes_out.clone())),
+            Err(challenge) => challenge,
+        };
+        attempts += 1;
+        // Raw clients have no way to answer a challenge
+        if raw || attempts >= AUTH_ATTEMPTS {
+            let error = challenge.error.unwrap_or("err.auth_required");
+            writer.write_all(format!("Error: {}\n", i18n::tr(locale, error)).as_bytes()).await?;
+            return Ok(());
+        }
+        if let Some(error) = challenge.error {
+            let msg = ChatMessage::error(String::new()).with_template(error, &[]).localized(locale);
+            writer.write_all(format!("{}\n", msg.to_json()?).as_bytes()).await?;
+        }
+        let prompt = ChatMessage::new("System".to_string(), challenge.kind.to_string(), RoomName::global(), MessageType::AuthRequired);
+        writer.write_all(format!("{}\n", prompt.to_json()?).as_bytes()).await?;
+    };
+
+    // Guests aren't named yet, so they start from the config's rooms
+    let landing = state.landing_room(&username, guest).await;
+    let (sender, mut rx) = mpsc::channel::<ChatMessage>(CLIENT_QUEUE);
+    let (room_tx, room_rx) = watch::channel(landing.clone());
+    let hangup = CancellationToken::new();
+    let overflow = state.config.lock().await.slow_clients;
+    let tx = Outbox { tx: sender, hangup: hangup.clone(), overflow, overflowed: Arc::new(AtomicBool::new(false)) };
+    let (compressed, wire) = (conn.compressed, conn.wire);
+    let username = {
+        let mut clients = state.clients.lock().await;
+        let username = if guest {
+            let name = (0..GUEST_NAME_ATTEMPTS)
+                .map(|_| format!("{}{}", GUEST_PREFIX, 1000 + uuid::Uuid::new_v4().as_u128() % 9000))
+                .find(|name| !clients.contains_key(name));
+            let Some(name) = name else {
+                drop(clients);
+                writer.write_all(format!("Error: {}\n", i18n::tr(locale, "err.guests_full")).as_bytes()).await?;
+                return Ok(());
+            };
+            name
+        } else {
+            username
+        };
+        if clients.contains_key(&username) {
+            drop(clients);
+            writer.write_all(format!("Error: {}\n", i18n::tr(locale, "err.username_taken")).as_bytes()).await?;
+            return Ok(());
+        }
+        clients.insert(username.clone(), Client {
+            room: room_tx,
+            tx: tx.clone(),
+            hangup: hangup.clone(),
+            is_admin: !guest && state.admins.contains(&username),
+            moderator: false,
+            away: None,
+            is_guest: guest,
+            sent: VecDeque::new(),
+            conn,
+        });
+        username
+    };
+    println!("{} connected{}", username, if guest { " as a guest" } else { "" });
+    let mut session = Session { state: state.clone(), username: username.clone(), guest, hangup: hangup.clone(), handling: None, open: true };
+    let chaos = state.chaos.and_then(|chaos| chaos.pick());
+    if let Some(chaos) = chaos {
+        println!("Chaos: {} will be dropped after {}s", username, chaos.lifetime.as_secs());
+    }
+    handshake_span.attr("chat.user", &username);
+    handshake_span.attr("chat.attempts", attempts + 1);
+    drop(handshake_span);
+    let mut deflater = compressed.then(Deflater::default);
+    if compressed {
+        let switch = ChatMessage::new("System".to_string(), deflate::NAME.to_string(), RoomName::global(), MessageType::Compression);
+        writer.write_all(format!("{}\n", switch.to_json()?).as_bytes()).await?;
+    }
+    // Announced inside the compressed stream, so the client decodes what it inflates
+    if wire == Wire::MessagePack {
+        let switch = ChatMessage::new("System".to_string(), msgpack::NAME.to_string(), RoomName::global(), MessageType::Encoding);
+        let line = format!("{}\n", switch.to_json()?);
+        match &mut deflater {
+            Some(deflater) => writer.write_all(&deflate::frame(&deflater.compress(line.as_bytes()))).await?,
+            None => writer.write_all(line.as_bytes()).await?,
+        }
+    }
+
+    // Writer task: direct messages plus room broadcasts filtered by the client's current room,
+    // with server-generated text rendered in the client's locale. Once the connection is
+    // cancelled it sends the direct messages already queued, such as a kick notice, and stops.
+    let mut broadcast_rx = state.broadcast_tx.subscribe();
+    let writer_bytes = bytes_out.clone();
+    let writer_hangup = hangup.clone();
+    let writer_handle = tokio::spawn(async move {
+        // A failed write, or a panic, ends the connection too
+        let _hangup = writer_hangup.drop_guard("connection lost");
+        let deadline = async {
+            writer_hangup.cancelled().await;
+            tokio::time::sleep(DRAIN_TIMEOUT).await;
+        };
+        let mut closing = false;
+        let mut writer = BufWriter::new(writer);
+        let mut batched = Vec::new(); // Lines waiting to be compressed together
+        let mut skipping = 0; // Room messages still to lose in a chaos lag spike
+        let writing = async {
+            'writer: loop {
+                let first: Line = tokio::select! {
+                    // Direct messages first, so a room change lands before that room's broadcasts
+                    biased;
+                    direct = rx.recv() => match direct {
+                        Some(msg) => match encode(&msg.localized(locale), wire) {
+                            Some(line) => line,
+                            None => continue,
+                        },
+                        None => break,
+                    },
+                    // Nothing more is queued after this, so the queue runs dry and ends the loop
+                    _ = writer_hangup.cancelled(), if !closing => {
+                        closing = true;
+                        rx.close();
+                        continue;
+                    }
+                    broadcast = broadcast_rx.recv(), if !closing => match broadcast {
+                        // No need for the clients lock: the room is watched
+                        Ok(encoded) if *room_rx.borrow() == encoded.msg.room => {
+                            if let Some(chaos) = chaos {
+                                skipping = if skipping > 0 { skipping - 1 } else { chaos.lag() };
+                                if skipping > 0 {
+                                    continue;
+                                }
+                            }
+                            match encoded.line(locale, wire) {
+                                Some(line) => line,
+                                None => continue,
+                            }
+                        }
+                        Ok(_) => continue,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                };
+                // Whatever else is already waiting goes out with it, in one flush
+                let mut next = Some(first);
+                let mut batch = 0;
+                while let Some(line) = next {
+                    if deflater.is_some() {
+                        batched.extend_from_slice(&line);
+                    } else {
+                        writer_bytes.fetch_add(line.len() as u64, Ordering::Relaxed);
+                        if writer.write_all(&line).await.is_err() {
+                            break 'writer;
+                        }
+                    }
+                    batch += 1;
+                    // Under chaos every line is flushed, and may be delayed, on its own
+                    next = if batch < WRITE_BATCH && chaos.is_none() { ready_line(&mut rx, &mut broadcast_rx, &room_rx, locale, wire) } else { None };
+                }
+                if let Some(deflater) = &mut deflater {
+                    let frame = deflate::frame(&deflater.compress(&batched));
+                    batched.clear();
+                    writer_bytes.fetch_add(frame.len() as u64, Ordering::Relaxed);
+                    if writer.write_all(&frame).await.is_err() {
+                        break;
+                    }
+                }
+                if let Some(delay) = chaos.and_then(|chaos| chaos.write_delay()) {
+                    tokio::time::sleep(delay).await;
+                }
+                if writer.flush().await.is_err() {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            _ = writing => {}
+            _ = deadline => {} // A client that isn't reading doesn't hold the connection open
+        }
+    });
+
+    enter_room(&state, &username, &landing).await;
+    // After the room change, which guests learn their name from
+    send_capabilities(&state, &username, true).await;
+    state.send_auto_join(&username).await;
+    let motd = state.config.lock().await.motd.clone();
+    if let Some(motd) = motd {
+        tx.send(ChatMessage::system(motd, landing.clone()));
+    }
+    if state.accounts.lock().await.is_registered(&username) {
+        for notice in state.digest(&username, &landing).await {
+            tx.send(notice);
+        }
+    }
+    // Raw clients have no forms to fill in
+    if !guest && wire != Wire::Text {
+        state.welcome(&username).await;
+    }
+
+    let chaos_drop = tokio::time::sleep(chaos.map_or(std::time::Duration::MAX, |chaos| chaos.lifetime));
+    tokio::pin!(chaos_drop);
+    loop {
+        let read = tokio::select! {
+            read = frame::read_frame(&mut reader) => read,
+            _ = hangup.cancelled() => break,
+            _ = &mut chaos_drop => {
+                hangup.cancel("chaos");
+                break;
+            }
+        };
+        let Ok((frame, read)) = read else {
+            // A reset connection ends the recording too
+            state.record(recording, &Frame::Closed, 0);
+            hangup.cancel("connection lost");
+            break;
+        };
+        bytes_in.fetch_add(read as u64, Ordering::Relaxed);
+        state.record(recording, &frame, read);
+        match frame {
+            Frame::Closed => {
+                hangup.cancel("closed");
+                break;
+            }
+            Frame::TooLong => {
+                state.send_to(&username, ProtocolError::LineTooLong(frame::MAX_LINE).to_message()).await;
+            }
+            Frame::NotUtf8 => state.send_to(&username, ProtocolError::NotUtf8.to_message()).await,
+            Frame::Line(line) => {
+                let text = line.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                session.handling = Some(if text.starts_with('/') { text.split(' ').next().unwrap_or_default().to_string() } else { "a chat message".to_string() });
+                if text.starts_with('/') {
+                    let mut span = state.tracer.span("command");
+                    span.attr("chat.command", text.split(' ').next().unwrap_or_default());
+                    span.attr("chat.user", &username);
+                    if !handle_command(&state, &username, text).await {
+                        hangup.cancel("quit");
+                        break;
+                    }
+                } else if let Err(e) = common::check_content(text) {
+                    state.send_to(&username, e.to_message()).await;
+                } else if state.refuse_muted(&username).await {
+                    // Already told for how long
+                } else if !state.permitted(&current_room(&state, &username).await, &username, Action::Post).await {
+                    let room = current_room(&state, &username).await;
+                    state.send_to(&username, ChatMessage::error(String::new()).with_template("err.may_not_post", &[&room])).await;
+                } else if let Some(wait) = state.rate_limited_for(&username).await {
+                    let limit = state.config.lock().await.rate_limit.unwrap_or_default().to_string();
+                    let reason = ErrorReason::RateLimited { retry_after: wait.as_secs_f64().ceil() as u64 };
+                    state.send_to(&username, ChatMessage::error(String::new()).with_template("err.rate_limited", &[&limit]).with_reason(reason)).await;
+                } else {
+                    let room = current_room(&state, &username).await;
+                    let text = state.config.lock().await.mask(text);
+                    let mut msg = ChatMessage::chat(username.clone(), text, room);
+                    msg.display_name = state.display_name(&username).await;
+                    msg.mentions = state.groups.lock().await.mentioned(&msg.content).into_iter().filter_map(|member| Username::new(member).ok()).collect();
+                    let mut span = state.tracer.span("chat.message");
+                    span.attr("chat.user", &username);
+                    let allowed = {
+                        let _moderation = span.child("moderation");
+                        state.moderate(&msg).await && state.screen_links(&mut msg).await
+                    };
+                    if allowed {
+                        state.notify_mentioned(&msg).await;
+                        unfurl_later(&state, &msg);
+                        state.post(msg.clone(), &span).await;
+                        state.thank_named(&msg).await;
+                        state.play(&msg).await;
+                    }
+                }
+            }
+        }
+    }
+
+    // Teardown, the same whatever cancelled the connection
+    let reason = hangup.cancelled().await;
+    let room = session.close().await;
+    if let Err(e) = writer_handle.await.map_err(|e| e.try_into_panic()) {
+        eprintln!("Writer for {} panicked: {}", username, e.map_or_else(|_| "(cancelled)".to_string(), supervise::message));
+    }
+    state.broadcast(ChatMessage::new(username.clone(), String::new(), room, MessageType::UserLeave).with_template("sys.left_room", &[&username]));
+    println!("{} disconnected ({})", username, reason);
+    Ok(())
+}
+
+// A signed-in client, signed out when their connection ends however it ends: the usual way,
+// with an error partway through, or with a panic, which would otherwise leave them listed as
+// online for good
+struct Session {
+    state: Arc<ServerState>,
+    username: String,
+    guest: bool,
+    hangup: CancellationToken,
+    handling: Option<String>, // The command or kind of message, for reporting a panic
+    open: bool,
+}
+
+impl Session {
+    // Signs out; returns the room they were in
+    async fn close(mut self) -> RoomName {
+        self.open = false;
+        sign_out(&self.state, &self.username, self.guest).await
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if !self.open {
+            return;
+        }
+        if std::thread::panicking() {
+            let handling = self.handling.as_ref().map_or_else(|| " before their first line".to_string(), |handling| format!(" handling {}", handling));
+            eprintln!("Session of {} panicked{}; signing them out", self.username, handling);
+        }
+        self.hangup.cancel("session ended");
+        let (state, username, guest) = (self.state.clone(), std::mem::take(&mut self.username), self.guest);
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let room = sign_out(&state, &username, guest).await;
+                state.broadcast(ChatMessage::new(username.clone(), String::new(), room, MessageType::UserLeave).with_template("sys.left_room", &[&username]));
+                println!("{} disconnected (session ended)", username);
+            });
+        }
+    }
+}
+
+async fn sign_out(state: &ServerState, username: &str, guest: bool) -> RoomName {
+    let room = current_room(state, username).await;
+    state.clients.lock().await.remove(username);
+    state.onboarding.lock().await.abandon(username);
+    if guest {
+        state.forget_guest(username).await;
+    }
+    room
+}
+
+// The writer's next line if one is queued already, direct messages first as in its select
+fn ready_line(rx: &mut mpsc::Receiver<ChatMessage>, broadcast_rx: &mut broadcast::Receiver<Arc<Encoded>>, room: &watch::Receiver<RoomName>, locale: &'static str, wire: Wire) -> Option<Line> {
+    if let Ok(msg) = rx.try_recv() {
+        return encode(&msg.localized(locale), wire);
+    }
+    loop {
+        match broadcast_rx.try_recv() {
+            Ok(encoded) if *room.borrow() == encoded.msg.room => return encoded.line(locale, wire),
+            Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+            Err(_) => return None,
+        }
+    }
+}
+
+// Reminds everyone as the drain approaches, then disconnects all but admins; gives up
+// quietly if the maintenance is called off or rescheduled meanwhile
+async fn run_maintenance(state: Arc<ServerState>, maintenance: Maintenance) {
+    let sleep_until = |at: chrono::DateTime<chrono::Utc>| tokio::time::sleep((at - chrono::Utc::now()).to_std().unwrap_or_default());
+    let total = (maintenance.drain_at - chrono::Utc::now()).num_seconds();
+    for warning in MAINTENANCE_WARNINGS.into_iter().filter(|w| *w < total) {
+        sleep_until(maintenance.drain_at - chrono::Duration::seconds(warning)).await;
+        if state.maintenance_id().await.as_ref() != Some(&maintenance.id) {
+            return;
+        }
+        let left = if warning >= 60 { format!("{}m", warning / 60) } else { format!("{}s", warning) };
+        state.announce("sys.maintenance_soon", &[&left, &maintenance.reason]).await;
+    }
+    sleep_until(maintenance.drain_at).await;
+    if state.maintenance_id().await.as_ref() != Some(&maintenance.id) {
+        return;
+    }
+    state.announce("sys.maintenance_now", &[&maintenance.reason]).await;
+    // Same path as a kick: queued notices are flushed before each connection closes
+    let draining: Vec<CancellationToken> = state.clients.lock().await.values().filter(|c| !c.is_admin).map(|c| c.hangup.clone()).collect();
+    state.audit.record("System", "maintenance_drain", &format!("connections={}", draining.len()));
+    for hangup in draining {
+        hangup.cancel("maintenance");
+    }
+}
+
+// Delivers a PM and echoes it to the sender, with an away notice if the recipient is away
+// Looks up the first link in a room message once it is posted, then attaches the preview to
+// the stored copy and tells the room. Mirrored copies go without.
+fn unfurl_later(state: &Arc<ServerState>, msg: &ChatMessage) {
+    let Some(url) = links::links(&msg.content).next().map(str::to_string) else { return };
+    let (state, message, room) = (state.clone(), msg.id, msg.room.clone());
+    supervise::task(format!("Preview of {}", url), async move {
+        let Some(settings) = state.config.lock().await.previews.clone() else { return };
+        let cached = state.previews.lock().await.get(&url);
+        let preview = match cached {
+            Some(preview) => preview,
+            None => {
+                let preview = preview::fetch(&settings, &url).await;
+                state.previews.lock().await.insert(url, preview.clone(), settings.cache);
+                preview
+            }
+        };
+        let Some(preview) = preview else { return };
+        if let Some(stored) = state.history.lock().await.get_mut(&room).and_then(|history| history.iter_mut().rev().find(|m| m.id == message)) {
+            stored.preview = Some(preview.clone());
+        }
+        let update = serde_json::to_string(&common::PreviewUpdate { message, preview }).unwrap_or_default();
+        state.broadcast(ChatMessage::new(String::new(), update, room, MessageType::Preview));
+    });
+}
+
+async fn send_private(state: &ServerState, username: &str, mut msg: ChatMessage) {
+    if state.refuse_muted(username).await || !state.screen_links(&mut msg).await {
+        return;
+    }
+    msg.display_name = state.display_name(username).await;
+    let Some(recipient) = msg.recipient.clone() else { return };
+    let (delivered, away) = {
+        let clients = state.clients.lock().await;
+        match clients.get(recipient.as_str()) {
+            Some(client) => {
+                client.tx.send(msg.clone());
+                (true, client.away.clone())
+            }
+            None => (false, None),
+        }
+    };
+    if !delivered {
+        state.send_to(username, ChatMessage::error(String::new()).with_template("err.not_online", &[&recipient])).await;
+        return;
+    }
+    let mut pms = state.recent_pms.lock().await;
+    pms.push_back(msg.clone());
+    if pms.len() > RECENT_PM_LIMIT {
+        pms.pop_front();
+    }
+    drop(pms);
+    if recipient != username {
+        state.send_to(username, msg).await;
+        if let Some(reason) = away {
+            let room = current_room(state, username).await;
+            state.send_to(username, ChatMessage::system(String::new(), room).with_template("sys.away_notice", &[&recipient, &reason])).await;
+        }
+    }
+}
+
+// Leaves the current room for `room`; false once the user has disconnected
+async fn switch_room(state: &ServerState, username: &str, room: &RoomName) -> bool {
+    let old_room = {
+        let mut clients = state.clients.lock().await;
+        match clients.get_mut(username) {
+            Some(client) => client.room.send_replace(room.clone()),
+            None => return false,
+        }
+    };
+    if old_room != *room {
+        state.broadcast(ChatMessage::new(username.to_string(), String::new(), old_room, MessageType::UserLeave).with_template("sys.left_room", &[username]));
+    }
+    enter_room(state, username, room).await;
+    send_capabilities(state, username, false).await;
+    true
+}
+
+// Capabilities and the command list, which depend on whether the user moderates the room they
+// are in; unless `always`, only sent when that changed
+async fn send_capabilities(state: &ServerState, username: &str, always: bool) {
+    let room = current_room(state, username).await;
+    let (moderator, admin) = (state.moderates(username, &room).await, state.is_admin(username).await);
+    let changed = match state.clients.lock().await.get_mut(username) {
+        Some(client) => std::mem::replace(&mut client.moderator, moderator) != moderator,
+        None => return,
+    };
+    if !changed && !always {
+        return;
+    }
+    let capabilities = ServerCapabilities { moderator, ..state.capabilities() };
+    let registry = serde_json::to_string(&commands::registry(&capabilities, admin)).unwrap_or_default();
+    let capabilities = serde_json::to_string(&capabilities).unwrap_or_default();
+    state.send_to(username, ChatMessage::new("System".to_string(), capabilities, RoomName::global(), MessageType::Capabilities)).await;
+    state.send_to(username, ChatMessage::new("System".to_string(), registry, RoomName::global(), MessageType::Commands)).await;
+}
+
+async fn send_room_list(state: &ServerState, username: &str, room: &RoomName) {
+    let content = serde_json::to_string(&state.room_list(username).await).unwrap_or_default();
+    state.send_to(username, ChatMessage::new("System".to_string(), content, room.clone(), MessageType::RoomList)).await;
+}
+
+// After a change to who may post in a room, so clients there know whether they are read-only
+async fn refresh_room_lists(state: &ServerState, room: &RoomName) {
+    for user in state.users_in_room(room).await {
+        send_room_list(state, &user, room).await;
+    }
+}
+
+async fn current_room(state: &ServerState, username: &str) -> RoomName {
+    state.clients.lock().await.get(username).map(|c| c.room()).unwrap_or_else(RoomName::general)
+}
+
+// A command's message ID argument; tells the user when it isn't one
+async fn message_id(state: &ServerState, username: &str, arg: &str) -> Option<MessageId> {
+    match arg.parse() {
+        Ok(message_id) => Some(message_id),
+        Err(e) => {
+            state.send_to(username, e.to_message()).await;
+            None
+        }
+    }
+}
+
+// Moves the user into a room: confirms the change, replays history, and announces the join
+async fn enter_room(state: &ServerState, username: &str, room: &RoomName) {
+    state.send_to(username, ChatMessage::new(username.to_string(), String::new(), room.clone(), MessageType::RoomChange).with_template("sys.room_change", &[room])).await;
+    // Ahead of the history, so clients know whether the room keeps messages
+    send_room_list(state, username, room).await;
+    for msg in state.room_history(room).await {
+        state.send_to(username, msg).await;
+    }
+    let users = state.users_in_room(room).await;
+    state.send_to(username, ChatMessage::new("System".to_string(), users.join(","), room.clone(), MessageType::UserList)).await;
+    for (reader, message_id) in state.room_read_markers(room).await {
+        state.send_to(username, ChatMessage::new(reader, message_id.to_string(), room.clone(), MessageType::ReadReceipt)).await;
+    }
+    for (user, reason) in state.away_in_room(room).await {
+        state.send_to(username, ChatMessage::new(user, reason, room.clone(), MessageType::Presence)).await;
+    }
+    if state.rooms.lock().await.has_karma(room) {
+        state.send_to(username, state.karma_scores(room).await).await;
+    }
+    state.broadcast(ChatMessage::new(username.to_string(), String::new(), room.clone(), MessageType::UserJoin).with_template("sys.joined_room", &[username]));
+}
+
+// Returns false when the connection should be closed
+// Takes the Arc so commands can leave work running in the background
+async fn handle_command(state: &Arc<ServerState>, username: &str, text: &str) -> bool {
+    let mut parts = text.splitn(3, ' ');
+    let command = parts.next().unwrap_or_default();
+    let arg = parts.next().unwrap_or_default().trim();
+    let rest = parts.next().unwrap_or_default().trim();
+
+    match command {
+        "/join" if arg == "--code" => {
+            let Some(room) = state.rooms.lock().await.redeem(rest, username) else {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.invite_invalid", &[])).await;
+                return true;
+            };
+            state.audit.record(username, "invite_redeem", &format!("room=#{} code={}", room, rest.to_uppercase()));
+            return switch_room(state, username, &room).await;
+        }
+        "/join" => {
+            if arg.is_empty() {
+                state.send_to(username, CommandError::new("/join", "<room> | --code <code>").to_message()).await;
+                return true;
+            }
+            let room = match RoomName::new(arg) {
+                Ok(room) => room,
+                Err(e) => {
+                    state.send_to(username, e.to_message()).await;
+                    return true;
+                }
+            };
+            if !state.room_exists(&room).await {
+                if state.is_guest(username).await {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.guest_no_new_rooms", &[])).await;
+                    return true;
+                }
+                state.rooms.lock().await.claim(&room, username);
+            } else if !state.may_enter(&room, username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.invite_only", &[&room])).await;
+                return true;
+            }
+            return switch_room(state, username, &room).await;
+        }
+        "/group" => {
+            // Admins manage groups; whoever may invite to a room decides which groups get in
+            let mut words = rest.split_whitespace();
+            let (name, member) = (words.next().unwrap_or_default().trim_start_matches('@'), words.next().unwrap_or_default());
+            let room = current_room(state, username).await;
+            let is_admin = state.is_admin(username).await;
+            let reply = match arg {
+                "" | "list" if name.is_empty() => {
+                    let groups = state.groups.lock().await;
+                    let list: Vec<String> = groups.names().map(|g| format!("@{} ({})", g, groups.members(g).map_or(0, |m| m.len()))).collect();
+                    if list.is_empty() {
+                        ChatMessage::system(String::new(), room).with_template("sys.no_groups", &[])
+                    } else {
+                        ChatMessage::system(String::new(), room).with_template("sys.group_list", &[&list.join(", ")])
+                    }
+                }
+                "list" => match state.groups.lock().await.members(name) {
+                    Some(members) => {
+                        let members: Vec<&str> = members.iter().map(String::as_str).collect();
+                        ChatMessage::system(String::new(), room).with_template("sys.group_members", &[name, &members.join(", ")])
+                    }
+                    None => ChatMessage::error(String::new()).with_template("err.group_not_found", &[name]),
+                },
+                "allow" | "deny" => {
+                    if !state.permitted(&room, username, Action::Invite).await || room == RoomName::general() {
+                        ChatMessage::error(String::new()).with_template("err.may_not_invite", &[&room])
+                    } else if name.is_empty() {
+                        let groups: Vec<String> = state.rooms.lock().await.allowed_groups(&room).iter().map(|g| format!("@{}", g)).collect();
+                        ChatMessage::system(String::new(), room.clone()).with_template("sys.room_groups", &[&room, &groups.join(", ")])
+                    } else if state.groups.lock().await.members(name).is_none() {
+                        ChatMessage::error(String::new()).with_template("err.group_not_found", &[name])
+                    } else if arg == "allow" {
+                        let present = state.users_in_room(&room).await;
+                        state.rooms.lock().await.allow_group(&room, name, present);
+                        state.audit.record(username, "group_allow", &format!("room=#{} group={}", room, name));
+                        ChatMessage::system(String::new(), room.clone()).with_template("sys.group_allowed", &[name, &room])
+                    } else if state.rooms.lock().await.disallow_group(&room, name) {
+                        state.audit.record(username, "group_deny", &format!("room=#{} group={}", room, name));
+                        ChatMessage::system(String::new(), room.clone()).with_template("sys.group_denied", &[name, &room])
+                    } else {
+                        ChatMessage::error(String::new()).with_template("err.group_not_allowed", &[name, &room])
+                    }
+                }
+                "create" | "delete" | "add" | "remove" if !is_admin => ChatMessage::error(String::new()).with_template("err.admin_only", &[]),
+                "create" | "delete" if name.is_empty() => CommandError::new("/group", "create|delete <name>").to_message(),
+                "create" if !common::is_valid_username(name) => ChatMessage::error(String::new()).with_template("err.group_name", &[name]),
+                "create" => {
+                    let created = state.groups.lock().await.create(name);
+                    if created {
+                        state.audit.record(username, "group_create", &format!("group={}", name));
+                        ChatMessage::system(String::new(), room).with_template("sys.group_created", &[name])
+                    } else {
+                        ChatMessage::error(String::new()).with_template("err.group_exists", &[name])
+                    }
+                }
+                "delete" => {
+                    let deleted = state.groups.lock().await.delete(name);
+                    if deleted {
+                        state.rooms.lock().await.drop_group(&name.to_lowercase());
+                        state.audit.record(username, "group_delete", &format!("group={}", name));
+                        ChatMessage::system(String::new(), room).with_template("sys.group_deleted", &[name])
+                    } else {
+                        ChatMessage::error(String::new()).with_template("err.group_not_found", &[name])
+                    }
+                }
+                "add" | "remove" => match Username::new(member) {
+                    Err(_) if member.is_empty() => CommandError::new("/group", "add|remove <name> <user>").to_message(),
+                    Err(e) => e.to_message(),
+                    Ok(member) => {
+                        let mut groups = state.groups.lock().await;
+                        let changed = if arg == "add" { groups.add(name, &member) } else { groups.remove(name, &member) };
+                        match changed {
+                            None => ChatMessage::error(String::new()).with_template("err.group_not_found", &[name]),
+                            Some(changed) => {
+                                if changed {
+                                    state.audit.record(username, &format!("group_{}", arg), &format!("group={} user={}", name, member));
+                                }
+                                let key = if arg == "add" { "sys.group_added" } else { "sys.group_removed" };
+                                ChatMessage::system(String::new(), room).with_template(key, &[&member, name])
+                            }
+                        }
+                    }
+                },
+                _ => CommandError::new("/group", "[list [name]] | create|delete <name> | add|remove <name> <user> | allow|deny [name]").to_message(),
+            };
+            state.send_to(username, reply).await;
+        }
+        "/invitecode" => {
+            // Whoever may invite to the room they are in manages its codes
+            let room = current_room(state, username).await;
+            if !state.permitted(&room, username, Action::Invite).await || room == RoomName::general() {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_invite", &[&room])).await;
+                return true;
+            }
+            let reply = match arg {
+                "create" => match rooms::parse_lifetime(rest) {
+                    Some(lifetime) => {
+                        let present = state.users_in_room(&room).await;
+                        let mut rooms = state.rooms.lock().await;
+                        let invite = rooms.create_invite(&room, lifetime, present);
+                        state.audit.record(username, "invite_create", &format!("room=#{} code={} expires={}", room, invite.code, invite.expires.to_rfc3339_opts(SecondsFormat::Secs, true)));
+                        ChatMessage::system(String::new(), room.clone()).with_template("sys.invite_created", &[&invite.code, &room, rest])
+                    }
+                    None => ChatMessage::error(String::new()).with_template("err.invite_lifetime", &[]),
+                },
+                "list" => {
+                    let mut rooms = state.rooms.lock().await;
+                    let invites: Vec<String> = rooms
+                        .invites_for(&room)
+                        .iter()
+                        .map(|i| format!("{} ({} UTC, {}×)", i.code, i.expires.format("%Y-%m-%d %H:%M"), i.uses))
+                        .collect();
+                    if invites.is_empty() {
+                        ChatMessage::system(String::new(), room.clone()).with_template("sys.no_invites", &[&room])
+                    } else {
+                        ChatMessage::system(String::new(), room.clone()).with_template("sys.invite_list", &[&room, &invites.join(", ")])
+                    }
+                }
+                "revoke" if state.rooms.lock().await.revoke(&room, rest) => {
+                    state.audit.record(username, "invite_revoke", &format!("room=#{} code={}", room, rest.to_uppercase()));
+                    ChatMessage::system(String::new(), room.clone()).with_template("sys.invite_revoked", &[&rest.to_uppercase()])
+                }
+                "revoke" => ChatMessage::error(String::new()).with_template("err.invite_invalid", &[]),
+                "off" => {
+                    state.rooms.lock().await.open(&room);
+                    state.audit.record(username, "room_open", &format!("room=#{}", room));
+                    ChatMessage::system(String::new(), room.clone()).with_template("sys.room_opened", &[&room])
+                }
+                _ => CommandError::new("/invitecode", "create <30m|24h|7d> | list | revoke <code> | off").to_message(),
+            };
+            state.send_to(username, reply).await;
+        }
+        "/msg" => {
+            if arg.is_empty() || rest.is_empty() {
+                state.send_to(username, CommandError::new("/msg", "<user> <text>").to_message()).await;
+                return true;
+            }
+            if let Err(e) = Username::new(arg).and_then(|_| common::check_content(rest)) {
+                state.send_to(username, e.to_message()).await;
+                return true;
+            }
+            // An unambiguous start of a name is enough to reach them
+            let Some(recipient) = state.online_target(username, arg, true, CommandError::new("/msg", "<user> <text>"), rest).await else { return true };
+            match Username::new(&recipient) {
+                Ok(recipient) => send_private(state, username, ChatMessage::private(username.to_string(), recipient, rest.to_string())).await,
+                Err(e) => state.send_to(username, e.to_message()).await,
+            }
+        }
+        "/forward" => {
+            // `/forward <message id> <#room|@user>`; bare names are rooms
+            if arg.is_empty() || rest.is_empty() {
+                state.send_to(username, CommandError::new("/forward", "<message id> <#room|@user>").to_message()).await;
+                return true;
+            }
+            let Some(message_id) = message_id(state, username, arg).await else { return true };
+            let Some(original) = state.find_message(username, message_id).await else {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.message_not_found", &[])).await;
+                return true;
+            };
+            let origin = original.origin();
+            if let Some(recipient) = rest.strip_prefix('@') {
+                match Username::new(recipient) {
+                    Ok(recipient) => send_private(state, username, ChatMessage::private(username.to_string(), recipient, original.content).with_forwarded(origin)).await,
+                    Err(e) => state.send_to(username, e.to_message()).await,
+                }
+            } else {
+                let room = match RoomName::new(rest.trim_start_matches('#')) {
+                    Ok(room) => room,
+                    Err(e) => {
+                        state.send_to(username, e.to_message()).await;
+                        return true;
+                    }
+                };
+                if state.is_guest(username).await && !state.room_exists(&room).await {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.guest_no_new_rooms", &[])).await;
+                    return true;
+                }
+                if !state.may_enter(&room, username).await {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.invite_only", &[&room])).await;
+                    return true;
+                }
+                if state.refuse_muted(username).await {
+                    return true;
+                }
+                if !state.permitted(&room, username, Action::Post).await {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_post", &[&room])).await;
+                    return true;
+                }
+                let mut msg = ChatMessage::chat(username.to_string(), original.content, room.clone()).with_forwarded(origin);
+                msg.display_name = state.display_name(username).await;
+                if !state.moderate(&msg).await || !state.screen_links(&mut msg).await {
+                    return true;
+                }
+                unfurl_later(state, &msg);
+                state.post(msg, &state.tracer.span("forward")).await;
+                // Forwarding elsewhere would otherwise give no sign it worked
+                let current = current_room(state, username).await;
+                if current != room {
+                    state.send_to(username, ChatMessage::system(String::new(), current).with_template("sys.forwarded", &[&room])).await;
+                }
+            }
+        }
+        "/ttl" => {
+            // `/ttl [30m|24h|7d|off]` for the current room; no argument shows it
+            let room = current_room(state, username).await;
+            let current = state.rooms.lock().await.ttl(&room);
+            if arg.is_empty() {
+                let reply = match current {
+                    Some(ttl) => ChatMessage::system(String::new(), room.clone()).with_template("sys.ttl_set", &[&room, &rooms::format_lifetime(ttl)]),
+                    None => ChatMessage::system(String::new(), room.clone()).with_template("sys.ttl_off", &[&room]),
+                };
+                state.send_to(username, reply).await;
+                return true;
+            }
+            let allowed = state.rooms.lock().await.is_owner(&room, username) || state.is_admin(username).await;
+            if !allowed {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.owner_only_ttl", &[&room])).await;
+                return true;
+            }
+            let ttl = match arg {
+                "off" => None,
+                _ => match rooms::parse_lifetime(arg) {
+                    Some(ttl) => Some(ttl),
+                    None => {
+                        state.send_to(username, CommandError::new("/ttl", "[30m|24h|7d|off]").to_message()).await;
+                        return true;
+                    }
+                },
+            };
+            state.rooms.lock().await.set_ttl(&room, ttl);
+            let label = ttl.map(rooms::format_lifetime);
+            state.audit.record(username, "ttl", &format!("room=#{} ttl={}", room, label.as_deref().unwrap_or("off")));
+            let notice = match &label {
+                Some(label) => ChatMessage::system(String::new(), room.clone()).with_template("sys.ttl_set", &[&room, label]),
+                None => ChatMessage::system(String::new(), room.clone()).with_template("sys.ttl_off", &[&room]),
+            };
+            state.broadcast(notice);
+            // Anything already too old goes right away
+            state.purge_expired().await;
+        }
+        "/roomset" => {
+            // `/roomset post|invite <level>`, `mod|demod`, `moderation|karma|broadcast on|off` for owners and admins,
+            // `voice|devoice` for moderators; no argument shows the current room's settings
+            let room = current_room(state, username).await;
+            let target = rest.split_whitespace().next().unwrap_or_default();
+            if arg.is_empty() {
+                let rooms = state.rooms.lock().await;
+                let permissions = rooms.permissions(&room);
+                let (moderators, voiced) = (rooms.members_at(&room, Level::Moderators), rooms.members_at(&room, Level::Voiced));
+                let list = |names: Vec<&String>| if names.is_empty() { "-".to_string() } else { names.iter().map(|n| n.as_str()).collect::<Vec<_>>().join(", ") };
+                let on_off = |on: bool| if on { "on" } else { "off" };
+                let description = rooms.description(&room).unwrap_or("-").to_string();
+                let tags = rooms.tags(&room).iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(" ");
+                let settings = [
+                    room.as_str(),
+                    permissions.post.name(),
+                    permissions.invite.name(),
+                    &list(moderators),
+                    &list(voiced),
+                    on_off(rooms.is_moderated(&room)),
+                    on_off(rooms.has_karma(&room)),
+                    on_off(rooms.is_broadcast(&room)),
+                    &description,
+                    if tags.is_empty() { "-" } else { &tags },
+                ];
+                let reply = ChatMessage::system(String::new(), room.clone()).with_template("sys.roomset", &settings);
+                drop(rooms);
+                state.send_to(username, reply).await;
+                return true;
+            }
+            let is_admin = state.is_admin(username).await;
+            let is_owner = state.rooms.lock().await.is_owner(&room, username);
+            let level = if is_admin { Level::Moderators } else { state.rooms.lock().await.level(&room, username) };
+            let (allowed, change) = match (arg, Action::parse(arg), Level::parse(target)) {
+                (_, Some(action), Some(required)) => (is_owner || is_admin, Some((action, required))),
+                ("mod" | "demod", _, _) if !target.is_empty() => (is_owner || is_admin, None),
+                ("moderation" | "karma" | "broadcast", _, _) if matches!(target, "on" | "off") => (is_owner || is_admin, None),
+                ("description" | "tags", _, _) => (is_owner || is_admin, None),
+                ("voice" | "devoice", _, _) if !target.is_empty() => (level == Level::Moderators, None),
+                _ => {
+                    state.send_to(username, CommandError::new("/roomset", "[post|invite everyone|voiced|moderators] | mod|demod|voice|devoice <user> | moderation|karma|broadcast on|off | description|tags [...]").to_message()).await;
+                    return true;
+                }
+            };
+            if !allowed {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_roomset", &[&room])).await;
+                return true;
+            }
+            if arg == "moderation" {
+                state.rooms.lock().await.set_moderated(&room, target == "on");
+                state.audit.record(username, "roomset", &format!("room=#{} moderation={}", room, target));
+                state.broadcast(ChatMessage::system(String::new(), room.clone()).with_template("sys.roomset_moderation", &[&room, target]));
+                return true;
+            }
+            if arg == "description" {
+                // No text clears it
+                if rest.chars().count() > rooms::MAX_DESCRIPTION_LEN {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.description_too_long", &[&rooms::MAX_DESCRIPTION_LEN.to_string()])).await;
+                    return true;
+                }
+                state.rooms.lock().await.set_description(&room, (!rest.is_empty()).then(|| rest.to_string()));
+                state.audit.record(username, "roomset", &format!("room=#{} description={}", room, rest));
+                let notice = if rest.is_empty() { ("sys.roomset_description_cleared", vec![room.as_str()]) } else { ("sys.roomset_description", vec![room.as_str(), rest]) };
+                state.broadcast(ChatMessage::system(String::new(), room.clone()).with_template(notice.0, &notice.1));
+                return true;
+            }
+            if arg == "tags" {
+                // Separated by spaces or commas; none clears them
+                let Some(tags) = rooms::parse_tags(rest.split([' ', ',']).filter(|word| !word.is_empty())) else {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.bad_tags", &[&rooms::MAX_TAGS.to_string(), &rooms::MAX_TAG_LEN.to_string()])).await;
+                    return true;
+                };
+                let shown = tags.iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(" ");
+                state.rooms.lock().await.set_tags(&room, tags);
+                state.audit.record(username, "roomset", &format!("room=#{} tags={}", room, shown));
+                let notice = if shown.is_empty() { ("sys.roomset_tags_cleared", vec![room.as_str()]) } else { ("sys.roomset_tags", vec![room.as_str(), shown.as_str()]) };
+                state.broadcast(ChatMessage::system(String::new(), room.clone()).with_template(notice.0, &notice.1));
+                return true;
+            }
+            if arg == "karma" {
+                state.rooms.lock().await.set_karma(&room, target == "on");
+                state.audit.record(username, "roomset", &format!("room=#{} karma={}", room, target));
+                state.broadcast(state.karma_scores(&room).await);
+                state.broadcast(ChatMessage::system(String::new(), room.clone()).with_template("sys.roomset_karma", &[&room, target]));
+                return true;
+            }
+            if arg == "broadcast" {
+                state.rooms.lock().await.set_broadcast(&room, target == "on");
+                state.audit.record(username, "roomset", &format!("room=#{} broadcast={}", room, target));
+                state.broadcast(ChatMessage::system(String::new(), room.clone()).with_template("sys.roomset_broadcast", &[&room, target]));
+                refresh_room_lists(state, &room).await;
+                let online: Vec<String> = state.clients.lock().await.keys().cloned().collect();
+                for user in online {
+                    state.send_auto_join(&user).await;
+                }
+                return true;
+            }
+            let notice = match change {
+                Some((action, required)) => {
+                    state.rooms.lock().await.set_permission(&room, action, required);
+                    state.audit.record(username, "roomset", &format!("room=#{} {}={}", room, action.name(), required.name()));
+                    ChatMessage::system(String::new(), room.clone()).with_template("sys.roomset_permission", &[action.name(), &room, required.name()])
+                }
+                None => {
+                    let target = match Username::new(target) {
+                        Ok(target) => target,
+                        Err(e) => {
+                            state.send_to(username, e.to_message()).await;
+                            return true;
+                        }
+                    };
+                    let given = match arg {
+                        "mod" => Level::Moderators,
+                        "voice" => Level::Voiced,
+                        _ => Level::Everyone,
+                    };
+                    // Devoicing a moderator, or demodding a voiced user, leaves them as they are
+                    let current = state.rooms.lock().await.level(&room, &target);
+                    if (arg == "demod" && current != Level::Moderators) || (arg == "devoice" && current != Level::Voiced) || !state.rooms.lock().await.set_level(&room, &target, given) {
+                        state.send_to(username, ChatMessage::error(String::new()).with_template("err.roomset_unchanged", &[&target, &room])).await;
+                        return true;
+                    }
+                    state.audit.record(username, "roomset", &format!("room=#{} {}={}", room, arg, target));
+                    send_capabilities(state, &target, false).await;
+                    ChatMessage::system(String::new(), room.clone()).with_template("sys.roomset_level", &[&target, &room, given.name()])
+                }
+            };
+            state.broadcast(notice);
+            refresh_room_lists(state, &room).await;
+        }
+        "/mirror" | "/unmirror" => {
+            // `/mirror <from> <to> [both]`; `to` may be a whole category, e.g. `projects/*`
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let room = current_room(state, username).await;
+            let mut words = rest.split_whitespace();
+            let (from, to, both) = (arg.trim_start_matches('#'), words.next().unwrap_or_default().trim_start_matches('#'), words.next() == Some("both"));
+            let reply = match (command, from, to) {
+                ("/mirror", "", _) => {
+                    let mirrors = state.mirrors.lock().await;
+                    let list: Vec<String> = mirrors.iter().map(|(from, to)| format!("#{} → #{}", from, to)).collect();
+                    if list.is_empty() {
+                        ChatMessage::system(String::new(), room).with_template("sys.no_mirrors", &[])
+                    } else {
+                        ChatMessage::system(String::new(), room).with_template("sys.mirror_list", &[&list.join(", ")])
+                    }
+                }
+                (_, _, "") => CommandError::new(command, if command == "/mirror" { "<from> <to> [both]" } else { "<from> <to>" }).to_message(),
+                _ if from == to || from.ends_with("/*") => ChatMessage::error(String::new()).with_template("err.mirror_invalid", &[]),
+                ("/mirror", _, _) => match (RoomName::new(from), RoomName::new(to)) {
+                    (Err(e), _) | (_, Err(e)) => e.to_message(),
+                    (Ok(from_room), Ok(to_room)) => {
+                        let mut mirrors = state.mirrors.lock().await;
+                        let mut links = vec![(from_room.clone(), to_room.clone())];
+                        if both && !to.ends_with("/*") {
+                            links.push((to_room, from_room));
+                        }
+                        for link in links {
+                            if !mirrors.contains(&link) {
+                                mirrors.push(link);
+                            }
+                        }
+                        let detail = format!("from=#{} to=#{}{}", from, to, if both { " both" } else { "" });
+                        state.audit.record(username, "mirror_add", &detail);
+                        ChatMessage::system(String::new(), room).with_template(if both { "sys.mirror_both" } else { "sys.mirror_added" }, &[from, to])
+                    }
+                },
+                _ => {
+                    // Removes the link in either direction
+                    let mut mirrors = state.mirrors.lock().await;
+                    let before = mirrors.len();
+                    mirrors.retain(|(a, b)| !(a == from && b == to || a == to && b == from));
+                    if mirrors.len() < before {
+                        state.audit.record(username, "mirror_remove", &format!("from=#{} to=#{}", from, to));
+                        ChatMessage::system(String::new(), room).with_template("sys.mirror_removed", &[from, to])
+                    } else {
+                        ChatMessage::error(String::new()).with_template("err.mirror_not_found", &[from, to])
+                    }
+                }
+            };
+            state.send_to(username, reply).await;
+        }
+        "/reload" => {
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let reply = match state.reload_config(username).await {
+                Ok(()) => ChatMessage::system(String::new(), current_room(state, username).await).with_template("sys.config_reloaded", &[]),
+                Err(e) => ChatMessage::error(String::new()).with_template("err.config_invalid", &[&e]),
+            };
+            state.send_to(username, reply).await;
+        }
+        "/stats" => {
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let stats = serde_json::to_string(&state.stats().await).unwrap_or_default();
+            state.send_to(username, ChatMessage::new("System".to_string(), stats, current_room(state, username).await, MessageType::Stats)).await;
+        }
+        "/console" => {
+            // What `client --console` polls for
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let console = serde_json::to_string(&state.console().await).unwrap_or_default();
+            state.send_to(username, ChatMessage::new("System".to_string(), console, current_room(state, username).await, MessageType::Console)).await;
+        }
+        "/modpanel" => {
+            // What the client's moderation panel shows for the current room
+            let room = current_room(state, username).await;
+            if !state.moderates(username, &room).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_moderate", &[&room])).await;
+                return true;
+            }
+            let all_reports = state.is_admin(username).await;
+            let moderation = serde_json::to_string(&state.moderation(&room, all_reports).await).unwrap_or_default();
+            state.send_to(username, ChatMessage::new("System".to_string(), moderation, room, MessageType::Moderation)).await;
+        }
+        "/snapshot" => {
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let _span = state.tracer.span("snapshot.save");
+            let snapshot = state.snapshot().await;
+            let path = state.snapshot_path.display().to_string();
+            let reply = match snapshot.save(&state.snapshot_path) {
+                Ok(()) => {
+                    state.audit.record(username, "snapshot", &format!("file={}", path));
+                    ChatMessage::system(String::new(), current_room(state, username).await).with_template("sys.snapshot_saved", &[&path])
+                }
+                Err(e) => ChatMessage::error(String::new()).with_template("err.snapshot_failed", &[&path, &e.to_string()]),
+            };
+            state.send_to(username, reply).await;
+        }
+        "/maintenance" => {
+            // `/maintenance <5m|1h> [reason]` starts the countdown, `/maintenance off` calls it off
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            if arg == "off" {
+                let cancelled = state.maintenance.lock().await.take();
+                match cancelled {
+                    Some(_) => {
+                        state.audit.record(username, "maintenance_off", "");
+                        state.announce("sys.maintenance_off", &[]).await;
+                    }
+                    None => state.send_to(username, ChatMessage::error(String::new()).with_template("err.no_maintenance", &[])).await,
+                }
+                return true;
+            }
+            let Some(window) = rooms::parse_lifetime(arg) else {
+                state.send_to(username, CommandError::new("/maintenance", "<5m|1h> [reason] | off").to_message()).await;
+                return true;
+            };
+            let maintenance = Maintenance { id: uuid::Uuid::new_v4().to_string(), drain_at: chrono::Utc::now() + window, reason: rest.to_string() };
+            // Replacing a pending countdown stops the old one
+            *state.maintenance.lock().await = Some(maintenance.clone());
+            state.audit.record(username, "maintenance", &format!("window={} reason={}", arg, rest));
+            state.announce("sys.maintenance_soon", &[arg, rest]).await;
+            supervise::task(format!("Maintenance {}", maintenance.id), run_maintenance(state.clone(), maintenance));
+        }
+        "/conninfo" => {
+            // Your own connection, or anyone's for admins
+            let target = if arg.is_empty() { username } else { arg };
+            if target != username && !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let info = state.clients.lock().await.get(target).map(|c| {
+                let conn = &c.conn;
+                let latency = conn.latency_ms.map(|ms| format!("{} ms", ms)).unwrap_or_else(|| "—".to_string());
+                let online = common::format_elapsed((chrono::Utc::now() - conn.since).num_seconds().max(0) as u64);
+                [
+                    latency,
+                    common::format_bytes(conn.bytes_in.load(Ordering::Relaxed)),
+                    common::format_bytes(conn.bytes_out.load(Ordering::Relaxed)),
+                    conn.reconnects.to_string(),
+                    online,
+                    match state.geoip.as_ref().and_then(|geoip| geoip.lookup(conn.addr.ip())) {
+                        Some(origin) => format!("{} ({})", conn.addr, origin),
+                        None => conn.addr.to_string(),
+                    },
+                    conn.options.clone(),
+                ]
+            });
+            let reply = match info {
+                Some(info) => {
+                    let mut args = vec![target];
+                    args.extend(info.iter().map(String::as_str));
+                    ChatMessage::system(String::new(), current_room(state, username).await).with_template("sys.conninfo", &args)
+                }
+                None => ChatMessage::error(String::new()).with_template("err.not_online", &[target]),
+            };
+            state.send_to(username, reply).await;
+        }
+        "/rooms" => {
+            let room = current_room(state, username).await;
+            send_room_list(state, username, &room).await;
+        }
+        "/activity" => {
+            // Anyone who may enter a room can see when it is busy
+            let room = if arg.is_empty() {
+                current_room(state, username).await
+            } else {
+                match RoomName::new(arg.trim_start_matches('#')) {
+                    Ok(room) => room,
+                    Err(e) => {
+                        state.send_to(username, e.to_message()).await;
+                        return true;
+                    }
+                }
+            };
+            if !state.may_enter(&room, username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.invite_only", &[&room])).await;
+                return true;
+            }
+            let report = state.activity.lock().await.report(&room, chrono::Utc::now());
+            let report = serde_json::to_string(&report).unwrap_or_default();
+            state.send_to(username, ChatMessage::new("System".to_string(), report, room, MessageType::Activity)).await;
+        }
+        "/autojoin" => {
+            // `/autojoin [add|remove [room]|clear]`; the room is the current one unless named
+            let room = current_room(state, username).await;
+            if state.is_guest(username).await && !arg.is_empty() {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.guest_autojoin", &[])).await;
+                return true;
+            }
+            let target = if rest.is_empty() { Ok(room.clone()) } else { RoomName::new(rest.trim_start_matches('#')) };
+            let target = match target {
+                Ok(target) => target,
+                Err(e) => {
+                    state.send_to(username, e.to_message()).await;
+                    return true;
+                }
+            };
+            let defaults = state.config.lock().await.auto_join.clone();
+            let reply = match arg {
+                "" => {
+                    let rooms: Vec<String> = state.auto_join_rooms(username).await.iter().map(|room| format!("#{}", room)).collect();
+                    let reply = if rooms.is_empty() { ChatMessage::system(String::new(), room).with_template("sys.autojoin_empty", &[]) } else { ChatMessage::system(String::new(), room).with_template("sys.autojoin_list", &[&rooms.join(", ")]) };
+                    state.send_to(username, reply).await;
+                    return true;
+                }
+                "add" if !state.may_enter(&target, username).await => ChatMessage::error(String::new()).with_template("err.invite_only", &[&target]),
+                "add" if state.auto_join.lock().await.add(username, &target, &defaults) => ChatMessage::system(String::new(), room).with_template("sys.autojoin_added", &[&target]),
+                "add" => ChatMessage::error(String::new()).with_template("err.autojoin_unchanged", &[&target, &autojoin::MAX_ROOMS.to_string()]),
+                "remove" if state.auto_join.lock().await.remove(username, &target, &defaults) => ChatMessage::system(String::new(), room).with_template("sys.autojoin_removed", &[&target]),
+                "remove" => ChatMessage::error(String::new()).with_template("err.autojoin_missing", &[&target]),
+                "clear" => {
+                    state.auto_join.lock().await.set(username, Vec::new());
+                    ChatMessage::system(String::new(), room).with_template("sys.autojoin_cleared", &[])
+                }
+                _ => CommandError::new("/autojoin", "[add|remove [room]|clear]").to_message(),
+            };
+            let changed = reply.msg_type != MessageType::Error;
+            state.send_to(username, reply).await;
+            if changed {
+                state.send_auto_join(username).await;
+            }
+        }
+        "/discover" => {
+            // `/discover [keyword...]`; nothing lists every open room
+            let query = text.split_once(' ').map(|(_, query)| query.trim()).unwrap_or_default();
+            let discovery = serde_json::to_string(&state.discover(username, query).await).unwrap_or_default();
+            let room = current_room(state, username).await;
+            state.send_to(username, ChatMessage::new("System".to_string(), discovery, room, MessageType::Discover)).await;
+        }
+        "/thank" => {
+            let room = current_room(state, username).await;
+            if arg.is_empty() {
+                state.send_to(username, CommandError::new("/thank", "<user>").to_message()).await;
+            } else if !state.rooms.lock().await.has_karma(&room) {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.karma_off", &[&room])).await;
+            } else if arg == username {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.thank_self", &[])).await;
+            } else if !state.users_in_room(&room).await.iter().any(|user| user == arg) {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.not_in_room", &[arg, &room])).await;
+            } else if !state.thank(&room, username, arg).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.thank_too_soon", &[arg])).await;
+            }
+        }
+        "/roll" | "/flip" | "/choose" => {
+            // Decided here and posted to the room, so the result is the same for everyone
+            let room = current_room(state, username).await;
+            let expression = text.split_once(' ').map(|(_, expression)| expression.trim()).unwrap_or_default();
+            let (key, args) = match command {
+                "/roll" => match dice::Expression::parse(expression) {
+                    Some(dice) => ("sys.rolled", vec![expression.to_string(), dice.roll(dice::random)]),
+                    None => {
+                        state.send_to(username, CommandError::new("/roll", "<dice>").with_hint("/roll 2d20+3").to_message()).await;
+                        return true;
+                    }
+                },
+                "/flip" => (if dice::random(2) == 1 { "sys.flipped_heads" } else { "sys.flipped_tails" }, Vec::new()),
+                _ => {
+                    let masked = state.config.lock().await.mask(expression);
+                    let options = dice::options(&masked);
+                    if options.len() < 2 {
+                        state.send_to(username, CommandError::new("/choose", "<option>|<option>[|...]").to_message()).await;
+                        return true;
+                    }
+                    let chosen = options[dice::random(options.len() as u32) as usize - 1];
+                    ("sys.chose", vec![chosen.to_string(), options.join(", ")])
+                }
+            };
+            if state.refuse_muted(username).await {
+                return true;
+            }
+            if !state.permitted(&room, username, Action::Post).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_post", &[&room])).await;
+                return true;
+            }
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let mut msg = ChatMessage::new(username.to_string(), String::new(), room, MessageType::Dice).with_template(key, &args);
+            msg.display_name = state.display_name(username).await;
+            if state.moderate(&msg).await {
+                state.post(msg, &state.tracer.span("dice")).await;
+            }
+        }
+        "/time" => {
+            // Posted to the room with the moment in the local time of everyone there whose
+            // profile has a timezone
+            let room = current_room(state, username).await;
+            let written = text.split_once(' ').map(|(_, when)| when.trim()).unwrap_or_default();
+            let zone = state.profiles.lock().await.get(username).and_then(|profile| profile.timezone.as_deref()).and_then(common::parse_utc_offset);
+            let moment = match when::parse(written, zone, chrono::Utc::now()) {
+                Ok(moment) => moment,
+                Err(when::WhenError::NoTimezone) => {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.time_no_zone", &[])).await;
+                    return true;
+                }
+                Err(when::WhenError::NotATime) => {
+                    state.send_to(username, CommandError::new("/time", "<when>").with_hint("/time tomorrow 9am UTC+2").to_message()).await;
+                    return true;
+                }
+            };
+            if state.refuse_muted(username).await {
+                return true;
+            }
+            if !state.permitted(&room, username, Action::Post).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_post", &[&room])).await;
+                return true;
+            }
+            let users = state.users_in_room(&room).await;
+            let local: Vec<String> = {
+                let profiles = state.profiles.lock().await;
+                users
+                    .iter()
+                    .filter_map(|user| {
+                        let zone = profiles.get(user)?.timezone.as_deref().and_then(common::parse_utc_offset)?;
+                        Some(format!("{} {}", user, moment.with_timezone(&zone).format("%a %H:%M")))
+                    })
+                    .collect()
+            };
+            let utc = moment.format("%a %Y-%m-%d %H:%M").to_string();
+            let without = users.len() - local.len();
+            let msg = match (local.is_empty(), without) {
+                (true, _) => ChatMessage::system(String::new(), room).with_template("sys.time_no_zones", &[username, &utc]),
+                (false, 0) => ChatMessage::system(String::new(), room).with_template("sys.time", &[username, &utc, &local.join(", ")]),
+                (false, _) => ChatMessage::system(String::new(), room).with_template("sys.time_some_unset", &[username, &utc, &local.join(", "), &without.to_string()]),
+            };
+            state.post(msg, &state.tracer.span("time")).await;
+        }
+        "/translate" => {
+            // Only to whoever asked, once the service answers; the same translation again comes from the cache
+            let Some(settings) = state.config.lock().await.translate.clone() else {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.translate_off", &[])).await;
+                return true;
+            };
+            if arg.is_empty() || !translate::valid_language(rest) {
+                state.send_to(username, CommandError::new("/translate", "<id> <language, e.g. de>").to_message()).await;
+                return true;
+            }
+            let Some(message_id) = message_id(state, username, arg).await else { return true };
+            let Some(original) = state.find_message(username, message_id).await else {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.message_not_found", &[])).await;
+                return true;
+            };
+            let language = rest.to_string();
+            let cached = state.translations.lock().await.cached(message_id, &language);
+            if let Some(text) = cached {
+                state.send_to(username, ChatMessage::system(String::new(), original.room.clone()).with_template("sys.translation", &[&language, original.sender_name(), &text])).await;
+                return true;
+            }
+            if !state.translations.lock().await.allow(username, settings.per_minute) {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.translate_rate_limited", &[&settings.per_minute.to_string()])).await;
+                return true;
+            }
+            let (state, username) = (state.clone(), username.to_string());
+            supervise::task(format!("Translation of {}", message_id), async move {
+                let reply = match settings.translate(&original.content, &language).await {
+                    Ok(text) => {
+                        state.translations.lock().await.insert(message_id, &language, text.clone(), settings.cache);
+                        ChatMessage::system(String::new(), original.room.clone()).with_template("sys.translation", &[&language, original.sender_name(), &text])
+                    }
+                    Err(e) => {
+                        eprintln!("Translation service at {} failed: {}", settings.url, e);
+                        ChatMessage::error(String::new()).with_template("err.translate_failed", &[])
+                    }
+                };
+                state.send_to(&username, reply).await;
+            });
+        }
+        "/trivia" => {
+            // Anyone who may post in a room can start a game there; whoever started it, room
+            // moderators and admins can stop it
+            let room = current_room(state, username).await;
+            let reply = match arg {
+                "start" if !state.permitted(&room, username, Action::Post).await => ChatMessage::error(String::new()).with_template("err.may_not_post", &[&room]),
+                "start" => {
+                    let trivia = Trivia::new(&state.config.lock().await.trivia.clone().unwrap_or_default());
+                    let started = state.games.lock().await.start(&room, username, Box::new(trivia), std::time::Instant::now());
+                    match started {
+                        Some(said) => {
+                            state.audit.record(username, "game_start", &format!("room=#{} game=trivia", room));
+                            state.game_says(&room, said);
+                            return true;
+                        }
+                        None => ChatMessage::error(String::new()).with_template("err.game_running", &[&room]),
+                    }
+                }
+                "stop" => {
+                    let starter = state.games.lock().await.playing(&room).map(|(_, starter)| starter.to_string());
+                    let Some(starter) = starter else {
+                        state.send_to(username, ChatMessage::error(String::new()).with_template("err.no_game", &[&room])).await;
+                        return true;
+                    };
+                    let moderates = state.rooms.lock().await.level(&room, username) == Level::Moderators;
+                    if starter != username && !moderates && !state.is_admin(username).await {
+                        state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_stop_game", &[&starter])).await;
+                        return true;
+                    }
+                    let stopped = state.games.lock().await.stop(&room);
+                    if let Some(game) = stopped {
+                        state.game_says(&room, vec![Say::new("sys.game_stopped", &[game.name(), username]), game.scores()]);
+                    }
+                    return true;
+                }
+                "scores" => match state.games.lock().await.scores(&room) {
+                    Some(say) => {
+                        let args: Vec<&str> = say.args.iter().map(String::as_str).collect();
+                        ChatMessage::system(String::new(), room.clone()).with_template(say.key, &args)
+                    }
+                    None => ChatMessage::error(String::new()).with_template("err.no_game", &[&room]),
+                },
+                _ => CommandError::new("/trivia", "start|stop|scores").to_message(),
+            };
+            state.send_to(username, reply).await;
+        }
+        "/leaderboard" => {
+            let room = current_room(state, username).await;
+            if !state.rooms.lock().await.has_karma(&room) {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.karma_off", &[&room])).await;
+                return true;
+            }
+            let top = state.karma.lock().await.top(&room, LEADERBOARD_SIZE);
+            let reply = if top.is_empty() {
+                ChatMessage::system(String::new(), room.clone()).with_template("sys.no_karma", &[&room])
+            } else {
+                let ranks: Vec<String> = top.iter().enumerate().map(|(i, (user, score))| format!("{}. {} ({})", i + 1, user, score)).collect();
+                ChatMessage::system(String::new(), room.clone()).with_template("sys.leaderboard", &[&room, &ranks.join(", ")])
+            };
+            state.send_to(username, reply).await;
+        }
+        "/users" => {
+            let room = current_room(state, username).await;
+            let users = state.users_in_room(&room).await;
+            state.send_to(username, ChatMessage::system(String::new(), room.clone()).with_template("sys.users_in_room", &[&room, &users.join(", ")])).await;
+        }
+        "/kick" => {
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            if arg.is_empty() {
+                state.send_to(username, CommandError::new("/kick", "<user>").to_message()).await;
+                return true;
+            }
+            // Never a guess: only the exact name kicks
+            let Some(arg) = state.online_target(username, arg, false, CommandError::new("/kick", "<user>"), "").await else { return true };
+            let arg = arg.as_str();
+            let seen = state.seen_from(arg).await;
+            match state.kick(arg, ChatMessage::error(String::new()).with_template("err.kicked", &[username])).await {
+                Some(room) => {
+                    state.audit.record(username, "kick", &format!("user={} room=#{}{}", arg, room, seen));
+                    state.broadcast(ChatMessage::system(String::new(), room).with_template("sys.kicked", &[arg, username]));
+                }
+                None => state.send_to(username, ChatMessage::error(String::new()).with_template("err.not_online", &[arg])).await,
+            }
+        }
+        "/warn" => {
+            // `/warn <user> <reason>`: admins anywhere, room moderators for users in their room
+            if arg.is_empty() || rest.is_empty() {
+                state.send_to(username, CommandError::new("/warn", "<user> <reason>").to_message()).await;
+                return true;
+            }
+            let target = match Username::new(arg) {
+                Ok(target) => target,
+                Err(e) => {
+                    state.send_to(username, e.to_message()).await;
+                    return true;
+                }
+            };
+            let room = current_room(state, username).await;
+            let moderates = state.rooms.lock().await.level(&room, username) == Level::Moderators && room != RoomName::general();
+            let in_room = state.clients.lock().await.get(target.as_str()).is_some_and(|c| c.room() == room);
+            let allowed = state.is_admin(username).await || (moderates && in_room);
+            if !allowed || state.admins.contains(&target.to_string()) || target.as_str() == username {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_warn", &[&target])).await;
+                return true;
+            }
+            let count = state.warnings.lock().await.warn(&target, username, rest);
+            let seen = state.seen_from(&target).await;
+            state.audit.record(username, "warn", &format!("user={} count={}{} reason={}", target, count, seen, rest));
+            let escalation = state.config.lock().await.escalation(count);
+            let count = count.to_string();
+            state.send_to(&target, ChatMessage::error(String::new()).with_template("err.warned", &[username, rest, &count])).await;
+            let outcome = match escalation {
+                Some((Penalty::Mute, Some(lasting))) => {
+                    state.warnings.lock().await.mute(&target, lasting);
+                    let until = ErrorReason::Muted { until: chrono::Utc::now() + lasting };
+                    let lasting = rooms::format_lifetime(lasting);
+                    state.send_to(&target, ChatMessage::error(String::new()).with_template("err.muted", &[&lasting]).with_reason(until)).await;
+                    state.audit.record(username, "mute", &format!("user={} for={}", target, lasting));
+                    ChatMessage::system(String::new(), room).with_template("sys.warned_mute", &[&target, &count, &lasting])
+                }
+                Some((Penalty::Ban, Some(lasting))) => {
+                    state.warnings.lock().await.ban(&target, lasting);
+                    let lasting = rooms::format_lifetime(lasting);
+                    state.kick(&target, ChatMessage::error(String::new()).with_template("err.banned_for", &[&lasting])).await;
+                    state.audit.record(username, "ban", &format!("user={} for={}", target, lasting));
+                    ChatMessage::system(String::new(), room).with_template("sys.warned_ban", &[&target, &count, &lasting])
+                }
+                Some((Penalty::Kick, _)) => {
+                    state.kick(&target, ChatMessage::error(String::new()).with_template("err.kicked_warnings", &[&count])).await;
+                    state.audit.record(username, "kick", &format!("user={} warnings={}", target, count));
+                    ChatMessage::system(String::new(), room).with_template("sys.warned_kick", &[&target, &count])
+                }
+                _ => ChatMessage::system(String::new(), room).with_template("sys.warned", &[&target, &count]),
+            };
+            state.send_to(username, outcome).await;
+        }
+        "/mute" | "/ban" => {
+            // What warnings escalate to, given straight away: `/mute bob 30m`, `/ban bob 1d`
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let Some(lasting) = rooms::parse_lifetime(rest).filter(|_| !arg.is_empty()) else {
+                state.send_to(username, CommandError::new(command, "<user> <30m|2h|1d>").to_message()).await;
+                return true;
+            };
+            if state.admins.contains(&arg.to_string()) || arg == username {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_punish", &[arg])).await;
+                return true;
+            }
+            let room = current_room(state, username).await;
+            let seen = state.seen_from(arg).await;
+            let shown = rooms::format_lifetime(lasting);
+            let reply = if command == "/mute" {
+                state.warnings.lock().await.mute(arg, lasting);
+                let until = ErrorReason::Muted { until: chrono::Utc::now() + lasting };
+                state.send_to(arg, ChatMessage::error(String::new()).with_template("err.muted", &[&shown]).with_reason(until)).await;
+                state.audit.record(username, "mute", &format!("user={} for={}{}", arg, shown, seen));
+                ChatMessage::system(String::new(), room).with_template("sys.muted", &[arg, &shown])
+            } else {
+                state.warnings.lock().await.ban(arg, lasting);
+                if let Some(left) = state.kick(arg, ChatMessage::error(String::new()).with_template("err.banned_for", &[&shown])).await {
+                    state.broadcast(ChatMessage::system(String::new(), left).with_template("sys.banned", &[arg, &shown]));
+                }
+                state.audit.record(username, "ban", &format!("user={} for={}{}", arg, shown, seen));
+                ChatMessage::system(String::new(), room).with_template("sys.banned", &[arg, &shown])
+            };
+            state.send_to(username, reply).await;
+        }
+        "/unmute" | "/unban" => {
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            if arg.is_empty() || !rest.is_empty() {
+                state.send_to(username, CommandError::new(command, "<user>").to_message()).await;
+                return true;
+            }
+            let room = current_room(state, username).await;
+            let unmute = command == "/unmute";
+            let lifted = if unmute { state.warnings.lock().await.unmute(arg) } else { state.warnings.lock().await.unban(arg) };
+            let reply = match (lifted, unmute) {
+                (true, _) => {
+                    state.audit.record(username, &command[1..], &format!("user={}", arg));
+                    ChatMessage::system(String::new(), room).with_template(if unmute { "sys.unmuted" } else { "sys.unbanned" }, &[arg])
+                }
+                (false, true) => ChatMessage::error(String::new()).with_template("err.not_muted", &[arg]),
+                (false, false) => ChatMessage::error(String::new()).with_template("err.not_banned", &[arg]),
+            };
+            state.send_to(username, reply).await;
+        }
+        "/report" => {
+            // Anyone can put a message in front of the admins, with an optional reason
+            let Some(message_id) = message_id(state, username, arg).await else { return true };
+            let Some(msg) = state.find_message(username, message_id).await else {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.message_not_found", &[])).await;
+                return true;
+            };
+            let room = current_room(state, username).await;
+            let reason = if rest.is_empty() { format!("reported by {}", username) } else { format!("reported by {}: {}", username, rest) };
+            state.audit.record(username, "report", &format!("room=#{} id={} user={}", msg.room, msg.id, msg.username));
+            let preview: String = msg.content_lines().collect::<Vec<_>>().join(" ");
+            state.notify_admins(ChatMessage::system(String::new(), msg.room.clone()).with_template("sys.flagged", &[&msg.username, &msg.room, &reason, &preview])).await;
+            state.flag(msg, reason).await;
+            state.send_to(username, ChatMessage::system(String::new(), room).with_template("sys.reported", &[])).await;
+        }
+        "/flagged" if arg == "dismiss" => {
+            // Takes a reviewed message off the queue: `/flagged dismiss <id>`
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let Some(message_id) = message_id(state, username, rest).await else { return true };
+            let room = current_room(state, username).await;
+            let dismissed = {
+                let mut flagged = state.flagged.lock().await;
+                let before = flagged.len();
+                flagged.retain(|(msg, _)| msg.id != message_id);
+                before - flagged.len()
+            };
+            let reply = if dismissed > 0 {
+                state.audit.record(username, "dismiss", &format!("id={}", message_id));
+                ChatMessage::system(String::new(), room).with_template("sys.dismissed", &[&dismissed.to_string()])
+            } else {
+                ChatMessage::error(String::new()).with_template("err.not_flagged", &[])
+            };
+            state.send_to(username, reply).await;
+        }
+        "/flagged" => {
+            // Admins review what moderation let through but flagged, newest last
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let room = current_room(state, username).await;
+            let flagged = state.flagged.lock().await;
+            let entries: Vec<String> = flagged
+                .iter()
+                .map(|(msg, reason)| format!("{} #{} {}: {} ({})", msg.id, msg.room, msg.username, msg.content_lines().collect::<Vec<_>>().join(" "), reason))
+                .collect();
+            let reply = if entries.is_empty() {
+                ChatMessage::system(String::new(), room).with_template("sys.no_flagged", &[])
+            } else {
+                ChatMessage::system(String::new(), room).with_template("sys.flagged_list", &[&entries.len().to_string(), &entries.join("; ")])
+            };
+            drop(flagged);
+            state.send_to(username, reply).await;
+        }
+        "/reserve" | "/unreserve" => {
+            // Admins keep names, or patterns like `admin*`, for whoever registers them first
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let room = current_room(state, username).await;
+            let reply = if arg.is_empty() && command == "/reserve" {
+                let reserved = state.reserved.lock().await;
+                let patterns: Vec<&str> = reserved.patterns().map(String::as_str).collect();
+                if patterns.is_empty() {
+                    ChatMessage::system(String::new(), room).with_template("sys.no_reserved", &[])
+                } else {
+                    ChatMessage::system(String::new(), room).with_template("sys.reserved_list", &[&patterns.join(", ")])
+                }
+            } else if arg.is_empty() || !rest.is_empty() {
+                CommandError::new(command, "<name|pattern*>").to_message()
+            } else if command == "/reserve" {
+                if state.reserved.lock().await.add(arg) {
+                    state.audit.record(username, "reserve", arg);
+                    ChatMessage::system(String::new(), room).with_template("sys.reserved", &[arg])
+                } else {
+                    ChatMessage::error(String::new()).with_template("err.already_reserved", &[arg])
+                }
+            } else if state.reserved.lock().await.remove(arg) {
+                state.audit.record(username, "unreserve", arg);
+                ChatMessage::system(String::new(), room).with_template("sys.unreserved", &[arg])
+            } else {
+                ChatMessage::error(String::new()).with_template("err.not_reserved", &[arg])
+            };
+            state.send_to(username, reply).await;
+        }
+        "/audit" => {
+            // Admins check that nobody has edited the audit log since it was written
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let room = current_room(state, username).await;
+            let reply = if arg != "verify" || !rest.is_empty() {
+                CommandError::new("/audit", "verify").to_message()
+            } else {
+                match state.audit.verify() {
+                    Ok(Chain::Intact { entries, head, files }) => {
+                        ChatMessage::system(String::new(), room).with_template("sys.audit_intact", &[&entries.to_string(), head.get(..12).unwrap_or("-"), &files.to_string()])
+                    }
+                    Ok(Chain::Broken { file, line }) => {
+                        let file = file.display().to_string();
+                        state.audit.record(username, "audit_broken", &format!("line {} of {}", line, file));
+                        ChatMessage::error(String::new()).with_template("err.audit_broken", &[&line.to_string(), &file])
+                    }
+                    Ok(Chain::Missing { file }) => {
+                        let file = file.display().to_string();
+                        state.audit.record(username, "audit_missing", &file);
+                        ChatMessage::error(String::new()).with_template("err.audit_missing", &[&file])
+                    }
+                    Err(e) => ChatMessage::error(String::new()).with_template("err.audit_unreadable", &[&e.to_string()]),
+                }
+            };
+            state.send_to(username, reply).await;
+        }
+        "/linkhits" => {
+            // Admins review links that were blocked or delivered with a warning
+            if !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.admin_only", &[])).await;
+                return true;
+            }
+            let room = current_room(state, username).await;
+            let hits: Vec<String> = state.link_hits.lock().await.iter().cloned().collect();
+            let reply = if hits.is_empty() {
+                ChatMessage::system(String::new(), room).with_template("sys.no_link_hits", &[])
+            } else {
+                ChatMessage::system(String::new(), room).with_template("sys.link_hits", &[&hits.len().to_string(), &hits.join("; ")])
+            };
+            state.send_to(username, reply).await;
+        }
+        "/warnings" => {
+            // `/warnings` for your own, `/warnings <user>` for moderators and admins, `/warnings clear <user>` for admins
+            let room = current_room(state, username).await;
+            let is_admin = state.is_admin(username).await;
+            let (clear, target) = match (arg, rest) {
+                ("", _) => (false, username),
+                ("clear", target) if !target.is_empty() => (true, target),
+                (target, _) => (false, target),
+            };
+            let allowed = if clear { is_admin } else { target == username || is_admin || state.rooms.lock().await.level(&room, username) == Level::Moderators };
+            let reply = if !allowed {
+                ChatMessage::error(String::new()).with_template(if clear { "err.admin_only" } else { "err.may_not_review" }, &[])
+            } else if clear {
+                let cleared = state.warnings.lock().await.clear(target);
+                if cleared {
+                    state.audit.record(username, "warnings_clear", &format!("user={}", target));
+                }
+                ChatMessage::system(String::new(), room).with_template(if cleared { "sys.warnings_cleared" } else { "sys.no_warnings" }, &[target])
+            } else {
+                let warnings = state.warnings.lock().await;
+                let history = warnings.history(target);
+                let entries: Vec<String> = history.iter().map(|w| format!("{} {}: {}", w.at.format("%Y-%m-%d %H:%M"), w.by, w.reason)).collect();
+                if entries.is_empty() {
+                    ChatMessage::system(String::new(), room).with_template("sys.no_warnings", &[target])
+                } else {
+                    ChatMessage::system(String::new(), room).with_template("sys.warnings", &[target, &entries.len().to_string(), &entries.join("; ")])
+                }
+            };
+            state.send_to(username, reply).await;
+        }
+        "/read" => {
+            // Opt-in read receipts: the client reports the newest message it has seen
+            let Some(message_id) = message_id(state, username, arg).await else { return true };
+            let receipt = |room: RoomName| ChatMessage::new(username.to_string(), message_id.to_string(), room, MessageType::ReadReceipt);
+            match state.mark_read(username, message_id).await {
+                Some(ReadTarget::Room(room)) => state.broadcast(receipt(room)),
+                Some(ReadTarget::User(sender)) => state.send_to(&sender, receipt(RoomName::private())).await,
+                None => {}
+            }
+        }
+        "/away" | "/back" => {
+            let reason = if command == "/back" { String::new() } else { text.trim_start_matches("/away").trim().to_string() };
+            let reason = if command == "/away" && reason.is_empty() { "away".to_string() } else { reason };
+            let room = {
+                let mut clients = state.clients.lock().await;
+                let Some(client) = clients.get_mut(username) else { return false };
+                client.away = (!reason.is_empty()).then(|| reason.clone());
+                client.room()
+            };
+            state.broadcast(ChatMessage::new(username.to_string(), reason, room, MessageType::Presence));
+        }
+        "/totp" => {
+            let room = current_room(state, username).await;
+            let mut accounts = state.accounts.lock().await;
+            let reply = match arg {
+                "on" => match accounts.enable_totp(username) {
+                    Ok(Some(secret)) => ChatMessage::system(String::new(), room).with_template("sys.totp_enabled", &[&secret, &auth::otpauth_uri(username, &secret)]),
+                    Ok(None) => ChatMessage::error(String::new()).with_template("err.not_registered", &[]),
+                    Err(_) => ChatMessage::error(String::new()).with_template("err.account_save", &[]),
+                },
+                "off" => match accounts.disable_totp(username) {
+                    Ok(true) => ChatMessage::system(String::new(), room).with_template("sys.totp_disabled", &[]),
+                    Ok(false) => ChatMessage::error(String::new()).with_template("err.not_registered", &[]),
+                    Err(_) => ChatMessage::error(String::new()).with_template("err.account_save", &[]),
+                },
+                _ => CommandError::new("/totp", "on|off").to_message(),
+            };
+            drop(accounts);
+            state.send_to(username, reply).await;
+        }
+        "/pin" | "/unpin" => {
+            // Room moderators and admins pin messages from the room's history for everyone in it
+            let Some(message_id) = message_id(state, username, arg).await else { return true };
+            let room = current_room(state, username).await;
+            let moderates = state.rooms.lock().await.level(&room, username) == Level::Moderators;
+            if !moderates && !state.is_admin(username).await {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.may_not_pin", &[&room])).await;
+                return true;
+            }
+            if command == "/pin" {
+                let Some(msg) = state.history.lock().await.get(&room).and_then(|history| history.iter().find(|m| m.id == message_id)).cloned() else {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.message_not_found", &[])).await;
+                    return true;
+                };
+                let preview: String = msg.content_lines().collect::<Vec<_>>().join(" ");
+                let author = msg.username.clone();
+                {
+                    let mut pins = state.pins.lock().await;
+                    let pinned = pins.entry(room.clone()).or_default();
+                    if pinned.iter().any(|m| m.id == message_id) {
+                        drop(pins);
+                        state.send_to(username, ChatMessage::error(String::new()).with_template("err.already_pinned", &[])).await;
+                        return true;
+                    }
+                    pinned.insert(0, msg);
+                    pinned.truncate(PINS_LIMIT);
+                }
+                state.audit.record(username, "pin", &format!("room=#{} id={}", room, message_id));
+                state.broadcast(ChatMessage::system(String::new(), room).with_template("sys.pinned", &[username, &author, &preview]));
+            } else {
+                let removed = {
+                    let mut pins = state.pins.lock().await;
+                    let pinned = pins.entry(room.clone()).or_default();
+                    let before = pinned.len();
+                    pinned.retain(|m| m.id != message_id);
+                    pinned.len() < before
+                };
+                if !removed {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.not_pinned", &[])).await;
+                    return true;
+                }
+                state.audit.record(username, "unpin", &format!("room=#{} id={}", room, message_id));
+                state.broadcast(ChatMessage::system(String::new(), room).with_template("sys.unpinned", &[username]));
+            }
+        }
+        "/pins" => {
+            let room = current_room(state, username).await;
+            let entries: Vec<String> = state
+                .pins
+                .lock()
+                .await
+                .get(&room)
+                .map(|pinned| pinned.iter().map(|m| format!("{} {}: {}", m.id, m.username, m.content_lines().collect::<Vec<_>>().join(" "))).collect())
+                .unwrap_or_default();
+            let reply = if entries.is_empty() {
+                ChatMessage::system(String::new(), room.clone()).with_template("sys.no_pins", &[&room])
+            } else {
+                ChatMessage::system(String::new(), room.clone()).with_template("sys.pins", &[&room, &entries.len().to_string(), &entries.join("; ")])
+            };
+            state.send_to(username, reply).await;
+        }
+        "/star" => {
+            // Copies are kept, so stars outlive the room's history window
+            let room = current_room(state, username).await;
+            let Some(message_id) = message_id(state, username, arg).await else { return true };
+            let Some(msg) = state.find_message(username, message_id).await else {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.message_not_found", &[])).await;
+                return true;
+            };
+            let author = msg.username.clone();
+            let mut starred = state.starred.lock().await;
+            let saved = starred.entry(username.to_string()).or_default();
+            if !saved.iter().any(|m| m.id == msg.id) {
+                saved.insert(0, msg);
+                saved.truncate(STARRED_LIMIT);
+            }
+            drop(starred);
+            state.send_to(username, ChatMessage::system(String::new(), room).with_template("sys.starred", &[&author])).await;
+        }
+        "/unstar" => {
+            let Some(message_id) = message_id(state, username, arg).await else { return true };
+            let room = current_room(state, username).await;
+            let removed = {
+                let mut starred = state.starred.lock().await;
+                let saved = starred.entry(username.to_string()).or_default();
+                let before = saved.len();
+                saved.retain(|m| m.id != message_id);
+                saved.len() < before
+            };
+            let reply = if removed {
+                ChatMessage::system(String::new(), room).with_template("sys.unstarred", &[])
+            } else {
+                ChatMessage::error(String::new()).with_template("err.not_starred", &[])
+            };
+            state.send_to(username, reply).await;
+        }
+        "/starred" => {
+            let room = current_room(state, username).await;
+            let saved = state.starred.lock().await.get(username).cloned().unwrap_or_default();
+            let content = serde_json::to_string(&saved).unwrap_or_default();
+            state.send_to(username, ChatMessage::new("System".to_string(), content, room, MessageType::Starred)).await;
+        }
+        "/history" if arg == "--archived" => {
+            // `/history --archived <from> [to]`: the current room's archive, days as YYYY-MM-DD
+            let room = current_room(state, username).await;
+            let Some(settings) = state.config.lock().await.archive.clone() else {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.archive_off", &[])).await;
+                return true;
+            };
+            let days: Vec<Option<chrono::NaiveDate>> = rest.split_whitespace().map(|day| chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()).collect();
+            let (from, to) = match days[..] {
+                [Some(from)] => (from, from),
+                [Some(from), Some(to)] if from <= to && (to - from).num_days() < archive::MAX_DAYS => (from, to),
+                _ => {
+                    let usage = CommandError::new("/history", &format!("--archived <YYYY-MM-DD> [YYYY-MM-DD], at most {} days", archive::MAX_DAYS));
+                    state.send_to(username, usage.to_message()).await;
+                    return true;
+                }
+            };
+            state.archiver.flush().await;
+            let (dir, reading) = (settings.dir.clone(), room.clone());
+            let loaded = tokio::task::spawn_blocking(move || archive::load(&dir, &reading, from, to, ARCHIVE_REPLAY)).await.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            match loaded {
+                Ok((messages, more)) => {
+                    let count = messages.len().to_string();
+                    for msg in messages {
+                        state.send_to(username, msg).await;
+                    }
+                    let key = if more { "sys.archive_truncated" } else { "sys.archive_replayed" };
+                    state.send_to(username, ChatMessage::system(String::new(), room.clone()).with_template(key, &[&count, &from.to_string(), &to.to_string()])).await;
+                }
+                Err(e) => {
+                    eprintln!("Could not read the archive of #{} in {}: {}", room, settings.dir.display(), e);
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.archive_unreadable", &[])).await;
+                }
+            }
+        }
+        "/history" => {
+            // `/history #room/seq`: the stored messages around a permalink
+            let window = match common::parse_reference(arg) {
+                Some((room, _)) if !state.may_enter(room, username).await => {
+                    state.send_to(username, ChatMessage::error(String::new()).with_template("err.invite_only", &[room])).await;
+                    return true;
+                }
+                Some((room, seq)) => state.history_window(room, seq).await,
+                None => {
+                    state.send_to(username, CommandError::new("/history", "<#room/number>").to_message()).await;
+                    return true;
+                }
+            };
+            match window {
+                Some(messages) => {
+                    for msg in messages {
+                        state.send_to(username, msg).await;
+                    }
+                }
+                None => state.send_to(username, ChatMessage::error(String::new()).with_template("err.message_not_found", &[])).await,
+            }
+        }
+        "/profile" if arg == "set" => {
+            // `/profile set <field> [value]`; no value clears the field
+            let (field, value) = rest.split_once(' ').map(|(f, v)| (f, v.trim())).unwrap_or((rest, ""));
+            let room = current_room(state, username).await;
+            let fields = UserProfile::FIELDS.join("|");
+            if !UserProfile::FIELDS.contains(&field) {
+                state.send_to(username, CommandError::new("/profile", &format!("set <{}> [value]", fields)).to_message()).await;
+                return true;
+            }
+            let max_len = UserProfile::max_len(field).to_string();
+            if value.chars().count() > UserProfile::max_len(field) {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.profile_too_long", &[field, &max_len])).await;
+                return true;
+            }
+            if field == "display_name" {
+                if let Some(refusal) = state.refuse_display_name(username, value).await {
+                    state.send_to(username, refusal).await;
+                    return true;
+                }
+            }
+            let mut profiles = state.profiles.lock().await;
+            let profile = profiles.entry(username.to_string()).or_default();
+            if let Some(slot) = profile.field_mut(field) {
+                *slot = (!value.is_empty()).then(|| value.to_string());
+            }
+            drop(profiles);
+            let reply = if value.is_empty() { ("sys.profile_cleared", vec![field]) } else { ("sys.profile_set", vec![field, value]) };
+            state.send_to(username, ChatMessage::system(String::new(), room).with_template(reply.0, &reply.1)).await;
+        }
+        "/answer" => {
+            // `/answer <id> [value]` to the onboarding question just put; no value skips it
+            let value = rest.trim();
+            let step = state.onboarding.lock().await.step(username);
+            let Some(step) = step.filter(|step| step.id() == arg) else {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.no_prompt", &[arg])).await;
+                return true;
+            };
+            if !value.is_empty() {
+                let refusal = match step {
+                    Step::DisplayName => state.refuse_display_name(username, value).await,
+                    // Rooms to start with each time, beginning now with the first
+                    Step::Room => {
+                        let suggested = state.suggested_rooms(username).await;
+                        let picked: Vec<Option<RoomName>> = value.split_whitespace().map(|room| RoomName::new(room).ok().filter(|room| suggested.contains(room))).collect();
+                        match picked.into_iter().collect::<Option<Vec<RoomName>>>() {
+                            Some(rooms) => {
+                                state.auto_join.lock().await.set(username, rooms.clone());
+                                state.send_auto_join(username).await;
+                                switch_room(state, username, &rooms[0]).await;
+                                None
+                            }
+                            None => Some(ChatMessage::error(String::new()).with_template("err.answer_invalid", &[value])),
+                        }
+                    }
+                    // The client keeps this one
+                    Step::Notifications => (!matches!(value, "all" | "mentions" | "none")).then(|| ChatMessage::error(String::new()).with_template("err.answer_invalid", &[value])),
+                };
+                if let Some(refusal) = refusal {
+                    // Asked again, so the client can show the form once more
+                    state.send_to(username, refusal).await;
+                    state.ask(username, Some(step)).await;
+                    return true;
+                }
+                if step == Step::DisplayName {
+                    state.profiles.lock().await.entry(username.to_string()).or_default().display_name = Some(value.to_string());
+                }
+            }
+            let next = state.onboarding.lock().await.advance(username);
+            state.ask(username, next).await;
+        }
+        "/profile" => {
+            let target = if arg.is_empty() { username } else { arg };
+            let known = state.clients.lock().await.contains_key(target) || state.profiles.lock().await.contains_key(target);
+            if !known {
+                state.send_to(username, ChatMessage::error(String::new()).with_template("err.no_profile", &[target])).await;
+                return true;
+            }
+            let profile = state.profiles.lock().await.get(target).cloned().unwrap_or_default();
+            let room = current_room(state, username).await;
+            let content = serde_json::to_string(&profile).unwrap_or_default();
+            state.send_to(username, ChatMessage::new(target.to_string(), content, room, MessageType::Profile)).await;
+        }
+        "/ping" => {
+            // Echo the client's token so it can measure round-trip latency; newer clients add their last one
+            if let Ok(latency) = rest.parse() {
+                if let Some(client) = state.clients.lock().await.get_mut(username) {
+                    client.conn.latency_ms = Some(latency);
+                }
+            }
+            let room = current_room(state, username).await;
+            state.send_to(username, ChatMessage::new("System".to_string(), arg.to_string(), room, MessageType::Pong)).await;
+        }
+        // The TUI client has its own; this is for telnet and headless users
+        "/help" => {
+            // The same list clients complete from, for users typing without one
+            let room = current_room(state, username).await;
+            let capabilities = ServerCapabilities { moderator: state.moderates(username, &room).await, ..state.capabilities() };
+            let commands: Vec<String> = commands::registry(&capabilities, state.is_admin(username).await)
+                .into_iter()
+                .map(|command| if command.args.is_empty() { command.name } else { format!("{} {}", command.name, command.args) })
+                .collect();
+            state.send_to(username, ChatMessage::system(String::new(), room).with_template("sys.help", &[&commands.join(", ")])).await;
+        }
+        "/quit" => return false,
+        _ => state.send_to(username, ChatMessage::error(String::new()).with_template("err.unknown_command", &[command])).await,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(name: &str) -> Arc<ServerState> {
+        let dir = std::env::temp_dir().join(format!("chat-lib-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let accounts = Accounts::load(dir.join("accounts.json")).unwrap();
+        let audit = AuditLog::new(dir.join("audit.log"));
+        Arc::new(ServerState::new(Vec::new(), accounts, false, audit, dir.join("config.json"), dir.join("snapshot.json"), Tracer::from_env()))
+    }
+
+    fn connect(state: &ServerState, clients: &mut HashMap<String, Client>, username: &str) -> CancellationToken {
+        let hangup = CancellationToken::new();
+        let (sender, _) = mpsc::channel(CLIENT_QUEUE);
+        let tx = Outbox { tx: sender, hangup: hangup.clone(), overflow: Overflow::Disconnect, overflowed: Arc::new(AtomicBool::new(false)) };
+        let conn = ConnInfo::new(SocketAddr::from(([127, 0, 0, 1], 0)), &Handshake::default(), false, "en", Arc::default(), Arc::default());
+        clients.insert(username.to_string(), Client {
+            room: watch::channel(RoomName::general()).0,
+            tx,
+            hangup: hangup.clone(),
+            is_admin: state.admins.iter().any(|admin| admin == username),
+            moderator: false,
+            away: None,
+            is_guest: false,
+            sent: VecDeque::new(),
+            conn,
+        });
+        hangup
+    }
+
+    #[tokio::test]
+    async fn a_panicking_session_signs_its_user_out() {
+        let state = state("panic");
+        let hangup = connect(&state, &mut *state.clients.lock().await, "alice");
+        let mut broadcasts = state.broadcast_tx.subscribe();
+        let session = Session { state: state.clone(), username: "alice".to_string(), guest: false, hangup: hangup.clone(), handling: Some("/boom".to_string()), open: true };
+        let run = tokio::spawn(async move {
+            let _session = session;
+            panic!("handling went wrong");
+        });
+        assert!(run.await.unwrap_err().is_panic());
+        assert_eq!(hangup.cancelled().await, "session ended");
+        let left = tokio::time::timeout(std::time::Duration::from_secs(5), broadcasts.recv()).await.unwrap().unwrap();
+        assert_eq!((&left.msg.msg_type, left.msg.username.as_str()), (&MessageType::UserLeave, "alice"));
+        assert!(!state.clients.lock().await.contains_key("alice"));
+    }
+
+    #[tokio::test]
+    async fn a_closed_session_leaves_the_goodbye_to_its_caller() {
+        let state = state("close");
+        let hangup = connect(&state, &mut *state.clients.lock().await, "alice");
+        let mut broadcasts = state.broadcast_tx.subscribe();
+        let session = Session { state: state.clone(), username: "alice".to_string(), guest: false, hangup: hangup.clone(), handling: None, open: true };
+        assert_eq!(session.close().await, RoomName::general());
+        assert!(!state.clients.lock().await.contains_key("alice"));
+        assert!(broadcasts.try_recv().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::http;
+use common::LinkWarning;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(2); // Senders wait this long at most
+const DEFAULT_LOOKUP_PORT: u16 = 80;
+
+// What a screened link leads to
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Screen {
+    #[default]
+    Warn,  // Delivered with a warning recipients see beside it
+    Block, // Not delivered; the sender is told why
+}
+
+// `links` in the server config: links in room and private messages are checked against
+// `block` and `warn` (hosts, matching their subdomains too), then, if `url` is set, sent to a
+// lookup service in the style of Safe Browsing. Its matches are handled as `lookup` says.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkSettings {
+    pub block: Vec<String>,
+    pub warn: Vec<String>,
+    pub url: Option<String>, // POSTed {"urls": [...]}, answering {"matches": [{"url", "threat"}]}
+    pub lookup: Screen,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Matches {
+    matches: Vec<LinkWarning>,
+}
+
+impl LinkSettings {
+    pub fn valid_url(&self) -> bool {
+        self.url.as_deref().is_none_or(|url| http::parse_url(url, DEFAULT_LOOKUP_PORT).is_some())
+    }
+
+    // The first bad link and what to do about it; blocks win over warnings
+    pub async fn screen(&self, content: &str) -> Option<(Screen, LinkWarning)> {
+        let found: Vec<&str> = links(content).collect();
+        if found.is_empty() {
+            return None;
+        }
+        let on_list = |list: &[String], threat: &str| {
+            found.iter().find(|link| host(link).is_some_and(|host| listed(&host, list))).map(|link| LinkWarning { url: link.to_string(), threat: threat.to_string() })
+        };
+        if let Some(hit) = on_list(&self.block, "blocklisted") {
+            return Some((Screen::Block, hit));
+        }
+        let listed_warning = on_list(&self.warn, "suspicious");
+        if let Some((host, path)) = self.url.as_deref().and_then(|url| http::parse_url(url, DEFAULT_LOOKUP_PORT)) {
+            let body = json!({ "urls": found }).to_string();
+            let answer = match tokio::time::timeout(LOOKUP_TIMEOUT, http::post_json(&host, &path, &body)).await {
+                Ok(Ok(answer)) => serde_json::from_str::<Matches>(&answer).map_err(anyhow::Error::from),
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err(anyhow::anyhow!("timed out")),
+            };
+            match answer {
+                Ok(answer) => {
+                    if let Some(hit) = answer.matches.into_iter().next() {
+                        return Some((self.lookup, hit));
+                    }
+                }
+                Err(e) => eprintln!("Link lookup at {} failed: {}", host, e),
+            }
+        }
+        listed_warning.map(|hit| (Screen::Warn, hit))
+    }
+}
+
+// Every http(s) link in a message, without trailing punctuation
+pub fn links(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .split_whitespace()
+        .filter(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .map(|word| word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'', '>']))
+}
+
+// Lowercased, without port or credentials
+pub fn host(url: &str) -> Option<String> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit('@').next()?;
+    let host = match authority.strip_prefix('[') {
+        Some(v6) => v6.split(']').next()?,
+        None => authority.split(':').next()?,
+    };
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+// Whether `host` is one of `domains` or a subdomain of one
+pub fn listed(host: &str, domains: &[String]) -> bool {
+    domains.iter().any(|domain| {
+        let domain = domain.to_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
@@ -1,19 +1,31 @@
-use tokio::net::TcpListener;
+use common::ChatError;
+use server::{ChatServer, ServerOptions};
 use std::env;
+use tokio::net::TcpListener;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), ChatError> {
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
     let addr = format!("0.0.0.0:{}", port);
-    
+    let mut server = ChatServer::new(ServerOptions::from_env()).await?;
+    if let Ok(http_port) = env::var("HTTP_PORT") {
+        server = server.with_http(TcpListener::bind(format!("0.0.0.0:{}", http_port)).await?);
+        println!("Serving feeds over HTTP on port {}", http_port);
+    }
+
     let listener = TcpListener::bind(&addr).await?;
     println!("╔══════════════════════════════════════════════╗");
     println!("║   🚀 Chat Server Running on Port {}        ║", port);
     println!("╚══════════════════════════════════════════════╝");
 
-    loop {
-        let (socket, _) = listener.accept().await?;
-        // Your existing connection handling logic goes here...
-        // (If you have specific message logic, make sure to keep it!)
-    }
+    server.run(listener).await?;
+    println!("Server stopped");
+    Ok(())
 }
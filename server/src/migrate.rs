@@ -0,0 +1,68 @@
+use serde_json::{Map, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Versions for the JSON files the server keeps. Each file records the `schema_version` it
+// was written with, and older ones are brought up to date a step at a time as they are read;
+// a file without one is version 0. A release that changes a file's layout appends a step to
+// its list. Steps that have shipped never change.
+
+const VERSION_KEY: &str = "schema_version";
+
+pub type Object = Map<String, Value>; // A file's top level
+
+pub struct Migration {
+    pub what: &'static str, // Shown if the step fails
+    pub apply: fn(Object) -> Result<Object, String>,
+}
+
+pub struct Schema {
+    pub name: &'static str,
+    pub migrations: &'static [Migration], // The step to version N is at N - 1
+}
+
+impl Schema {
+    pub fn current(&self) -> u64 {
+        self.migrations.len() as u64
+    }
+
+    // `value` brought up to the current version, and the version it had. Files from a newer
+    // server are refused rather than half understood.
+    pub fn upgrade(&self, value: Value) -> io::Result<(Value, u64)> {
+        let Value::Object(mut object) = value else {
+            return Err(invalid(format!("{} is not a JSON object", self.name)));
+        };
+        let from = match object.remove(VERSION_KEY) {
+            None => 0,
+            Some(version) => version.as_u64().ok_or_else(|| invalid(format!("{} has a malformed {}", self.name, VERSION_KEY)))?,
+        };
+        if from > self.current() {
+            return Err(invalid(format!("{} is schema version {}, newer than this server's {}", self.name, from, self.current())));
+        }
+        for (to, migration) in self.migrations.iter().enumerate().skip(from as usize).map(|(i, m)| (i + 1, m)) {
+            object = (migration.apply)(object).map_err(|why| invalid(format!("{} migration to version {} ({}) failed: {}", self.name, to, migration.what, why)))?;
+        }
+        Ok((self.stamp(Value::Object(object)), from))
+    }
+
+    // `value` marked as the current version, for writing
+    pub fn stamp(&self, mut value: Value) -> Value {
+        if let Value::Object(object) = &mut value {
+            object.insert(VERSION_KEY.to_string(), self.current().into());
+        }
+        value
+    }
+}
+
+// Copies a file about to be rewritten by an upgrade to `<file>.v<version>`, for going back
+pub fn keep_old(path: &Path, version: u64) -> io::Result<PathBuf> {
+    let mut old = path.as_os_str().to_owned();
+    old.push(format!(".v{}", version));
+    fs::copy(path, &old)?;
+    Ok(old.into())
+}
+
+fn invalid(why: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, why)
+}
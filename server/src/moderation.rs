@@ -0,0 +1,74 @@
+use crate::http;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+const CLASSIFIER_TIMEOUT: Duration = Duration::from_secs(2); // Senders wait this long at most
+const DEFAULT_CLASSIFIER_PORT: u16 = 80;
+
+// What happens to a room message before it is posted
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    #[default]
+    Allow,
+    Flag,  // Posted, and listed for admins to review
+    Block, // Not posted; the sender is told why
+}
+
+// `moderation` in the server config. The word lists are checked first, ignoring case as
+// `filters` are, and a message they pass goes to the classifier at `url` if there is one.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModerationSettings {
+    pub block: Vec<String>,
+    pub flag: Vec<String>,
+    pub url: Option<String>, // An http:// service answering {"action": "allow|flag|block", "reason": "..."}
+    pub on_error: Verdict,   // When the classifier is down, slow or answers something else
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    action: Verdict,
+    #[serde(default)]
+    reason: String,
+}
+
+impl ModerationSettings {
+    pub fn valid_url(&self) -> bool {
+        self.url.as_deref().is_none_or(|url| http::parse_url(url, DEFAULT_CLASSIFIER_PORT).is_some())
+    }
+
+    // The verdict and the reason for it, empty when allowed
+    pub async fn check(&self, room: &str, username: &str, content: &str) -> (Verdict, String) {
+        if let Some(word) = matching(&self.block, content) {
+            return (Verdict::Block, format!("contains \"{}\"", word));
+        }
+        if let Some(word) = matching(&self.flag, content) {
+            return (Verdict::Flag, format!("contains \"{}\"", word));
+        }
+        let Some((host, path)) = self.url.as_deref().and_then(|url| http::parse_url(url, DEFAULT_CLASSIFIER_PORT)) else {
+            return (Verdict::Allow, String::new());
+        };
+        let body = json!({ "room": room, "username": username, "content": content }).to_string();
+        let answer = match tokio::time::timeout(CLASSIFIER_TIMEOUT, http::post_json(&host, &path, &body)).await {
+            Ok(Ok(answer)) => serde_json::from_str::<Answer>(&answer).map_err(anyhow::Error::from),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(anyhow::anyhow!("timed out")),
+        };
+        match answer {
+            Ok(answer) => (answer.action, answer.reason),
+            Err(e) => {
+                eprintln!("Moderation classifier at {} failed: {}", host, e);
+                (self.on_error, "the moderation service is unavailable".to_string())
+            }
+        }
+    }
+}
+
+fn matching<'a>(words: &'a [String], content: &str) -> Option<&'a str> {
+    content
+        .split(|c: char| !c.is_alphanumeric())
+        .find_map(|word| words.iter().find(|listed| listed.to_lowercase() == word.to_lowercase()))
+        .map(String::as_str)
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+const MAX_SUGGESTIONS: usize = 3;
+
+// Usernames only registered accounts may use: admin-managed patterns where `*` stands for
+// any run of characters, e.g. `admin*`. Matched ignoring case and lookalike characters.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Reserved {
+    patterns: BTreeSet<String>, // Lowercased
+}
+
+impl Reserved {
+    // False if it was reserved already
+    pub fn add(&mut self, pattern: &str) -> bool {
+        self.patterns.insert(pattern.to_lowercase())
+    }
+
+    pub fn remove(&mut self, pattern: &str) -> bool {
+        self.patterns.remove(&pattern.to_lowercase())
+    }
+
+    pub fn patterns(&self) -> impl Iterator<Item = &String> {
+        self.patterns.iter()
+    }
+
+    pub fn matches(&self, username: &str) -> bool {
+        let (lower, folded) = (username.to_lowercase(), skeleton(username));
+        self.patterns.iter().any(|pattern| glob(pattern, &lower) || glob(&skeleton(pattern), &folded))
+    }
+}
+
+fn glob(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else { return false };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else { return rest.is_empty() };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+// What a name looks like: lowercased, with invisible characters and accents dropped and
+// characters from other scripts that pass for Latin letters replaced by them. Two names
+// with the same skeleton can't be told apart at a glance. ASCII is otherwise left as it
+// is, so "b0b" and "bob" stay different names.
+pub fn skeleton(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '\u{0300}'..='\u{036f}' | '\u{200b}'..='\u{200f}' | '\u{2060}' | '\u{feff}'))
+        .map(|c| match c {
+            // Fullwidth forms of ASCII
+            '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            _ => c,
+        })
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'а' | 'α' => 'a',
+            'в' | 'β' => 'b',
+            'с' | 'ϲ' => 'c',
+            'ԁ' => 'd',
+            'е' | 'ε' => 'e',
+            'ɡ' => 'g',
+            'һ' | 'н' | 'η' => 'h',
+            'і' | 'ι' | 'ı' => 'i',
+            'ј' => 'j',
+            'к' | 'κ' => 'k',
+            'ӏ' => 'l',
+            'м' | 'μ' => 'm',
+            'ν' => 'v',
+            'о' | 'ο' | 'σ' => 'o',
+            'р' | 'ρ' => 'p',
+            'ԛ' => 'q',
+            'ѕ' => 's',
+            'т' | 'τ' => 't',
+            'у' | 'γ' => 'y',
+            'ԝ' | 'ω' => 'w',
+            'х' | 'χ' => 'x',
+            'ζ' => 'z',
+            c => c,
+        })
+        .collect()
+}
+
+// The one name in `names` that `partial` starts, ignoring case; None when none or several do
+pub fn unique_prefix<'a>(partial: &str, names: &'a [String]) -> Option<&'a String> {
+    let partial = partial.to_lowercase();
+    let mut matching = names.iter().filter(|name| name.to_lowercase().starts_with(&partial));
+    let first = matching.next()?;
+    matching.next().is_none().then_some(first)
+}
+
+// Names in `names` that `partial` may have meant: those it starts, then those a few edits
+// away, closest first
+pub fn closest(partial: &str, names: &[String]) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    let within = (partial.chars().count() / 3).max(1);
+    let mut scored: Vec<(usize, &String)> = names
+        .iter()
+        .filter_map(|name| {
+            let lower = name.to_lowercase();
+            if lower.starts_with(&partial) {
+                return Some((0, name));
+            }
+            let distance = strsim::damerau_levenshtein(&partial, &lower);
+            (distance <= within).then_some((distance, name))
+        })
+        .collect();
+    scored.sort();
+    scored.into_iter().take(MAX_SUGGESTIONS).map(|(_, name)| name.clone()).collect()
+}
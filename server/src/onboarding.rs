@@ -0,0 +1,111 @@
+use common::{Prompt, PromptField, PromptOption, RoomName, UserProfile};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+pub const SUGGESTED_ROOMS: usize = 5; // Busiest rooms offered when the config names none
+
+// `onboarding` in the server config
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct OnboardingSettings {
+    pub enabled: bool,
+    pub rooms: Vec<RoomName>, // Suggested to new users, in this order
+}
+
+impl Default for OnboardingSettings {
+    fn default() -> Self {
+        Self { enabled: true, rooms: Vec::new() }
+    }
+}
+
+// The questions put to a new user, in order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    DisplayName,
+    Room,
+    Notifications, // Kept by the client, like `/notify`; the server only moves on
+}
+
+impl Step {
+    const ALL: [Step; 3] = [Step::DisplayName, Step::Room, Step::Notifications];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Step::DisplayName => "display_name",
+            Step::Room => "room",
+            Step::Notifications => "notifications",
+        }
+    }
+
+    fn next(self) -> Option<Step> {
+        Self::ALL.iter().skip_while(|step| **step != self).nth(1).copied()
+    }
+}
+
+// Who has been walked through onboarding, and how far those doing it now have got. Someone
+// who signs out partway is asked again from the start next time.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Onboarding {
+    done: BTreeSet<String>,
+    #[serde(skip)]
+    pending: HashMap<String, Step>,
+}
+
+impl Onboarding {
+    // Starts a user's first visit; false for anyone welcomed before
+    pub fn begin(&mut self, username: &str) -> bool {
+        if self.done.contains(username) {
+            return false;
+        }
+        self.pending.insert(username.to_string(), Step::DisplayName);
+        true
+    }
+
+    pub fn step(&self, username: &str) -> Option<Step> {
+        self.pending.get(username).copied()
+    }
+
+    // The step after the current one; None once the last is answered, and they count as welcomed
+    pub fn advance(&mut self, username: &str) -> Option<Step> {
+        let next = self.step(username).and_then(Step::next);
+        match next {
+            Some(step) => {
+                self.pending.insert(username.to_string(), step);
+            }
+            None => {
+                self.pending.remove(username);
+                self.done.insert(username.to_string());
+            }
+        }
+        next
+    }
+
+    pub fn abandon(&mut self, username: &str) {
+        self.pending.remove(username);
+    }
+}
+
+// The form for `step`; `rooms` are the ones to suggest, and `picked` those ticked to start with
+pub fn prompt(step: Step, display_name: &str, rooms: &[RoomName], picked: &[RoomName]) -> Prompt {
+    let field = match step {
+        Step::DisplayName => PromptField::Text { value: display_name.to_string(), max_len: UserProfile::max_len("display_name") },
+        Step::Room => PromptField::Choice {
+            options: rooms.iter().map(|room| PromptOption { value: room.to_string(), label: None }).collect(),
+            selected: rooms.iter().filter(|room| picked.contains(room)).map(|room| room.to_string()).collect(),
+            multiple: true,
+        },
+        Step::Notifications => PromptField::Choice {
+            options: ["all", "mentions", "none"].iter().map(|level| PromptOption { value: level.to_string(), label: Some(format!("ui.onboarding_notify_{}", level)) }).collect(),
+            selected: vec!["mentions".to_string()],
+            multiple: false,
+        },
+    };
+    Prompt {
+        id: step.id().to_string(),
+        step: Step::ALL.iter().position(|s| *s == step).unwrap_or_default() + 1,
+        steps: Step::ALL.len(),
+        title: "ui.onboarding_title".to_string(),
+        text: format!("ui.onboarding_{}", step.id()),
+        field,
+    }
+}
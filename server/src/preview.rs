@@ -0,0 +1,116 @@
+use crate::http;
+use crate::links::{self, host};
+use common::LinkPreview;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::time::Duration;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_READ: usize = 64 * 1024; // Titles are near the top; the rest of a page is never read
+const MAX_REDIRECTS: usize = 3;
+const MAX_TITLE: usize = 200; // Characters
+
+// `previews` in the server config. Hosts match themselves and their subdomains; `deny` wins
+// over `allow`, and an empty `allow` lets in every host except private and loopback
+// addresses, which have to be listed to be fetched.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreviewSettings {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    pub cache: usize, // Links remembered, fetched or not
+}
+
+impl Default for PreviewSettings {
+    fn default() -> Self {
+        Self { allow: Vec::new(), deny: Vec::new(), cache: 256 }
+    }
+}
+
+impl PreviewSettings {
+    pub fn allows(&self, host: &str) -> bool {
+        if links::listed(host, &self.deny) {
+            return false;
+        }
+        if self.allow.is_empty() {
+            return !is_private(host);
+        }
+        links::listed(host, &self.allow)
+    }
+}
+
+fn is_private(host: &str) -> bool {
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified(),
+        Ok(IpAddr::V6(ip)) => ip.is_loopback() || ip.is_unspecified(),
+        Err(_) => host == "localhost" || host.ends_with(".localhost"),
+    }
+}
+
+// Recent results by URL, including failures, so a link pasted again isn't fetched again
+#[derive(Default)]
+pub struct PreviewCache {
+    entries: HashMap<String, Option<LinkPreview>>,
+    order: VecDeque<String>, // Oldest first
+}
+
+impl PreviewCache {
+    pub fn get(&self, url: &str) -> Option<Option<LinkPreview>> {
+        self.entries.get(url).cloned()
+    }
+
+    pub fn insert(&mut self, url: String, preview: Option<LinkPreview>, capacity: usize) {
+        if self.entries.insert(url.clone(), preview).is_none() {
+            self.order.push_back(url);
+        }
+        while self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+// None for https links (no TLS here), errors, and anything but a 2xx in the end
+pub async fn fetch(settings: &PreviewSettings, url: &str) -> Option<LinkPreview> {
+    let mut target = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        if !host(&target).is_some_and(|host| settings.allows(&host)) {
+            return None;
+        }
+        let (host, path) = http::parse_url(&target, 80)?;
+        let response = match tokio::time::timeout(FETCH_TIMEOUT, http::get(&host, &path, MAX_READ)).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => {
+                eprintln!("Preview of {} failed: {}", target, e);
+                return None;
+            }
+            Err(_) => return None,
+        };
+        if (300..400).contains(&response.status) {
+            let location = response.header("location")?;
+            target = if location.starts_with('/') { format!("http://{}{}", host, location) } else { location.to_string() };
+            continue;
+        }
+        if !response.is_success() {
+            return None;
+        }
+        let content_type = response.header("content-type").map(|value| value.split(';').next().unwrap_or_default().trim().to_lowercase());
+        let title = content_type.as_deref().filter(|t| *t == "text/html").and_then(|_| title(&response.body));
+        let size = response.header("content-length").and_then(|length| length.parse().ok());
+        return Some(LinkPreview { url: url.to_string(), title, content_type, size });
+    }
+    None
+}
+
+// The text of the first <title>, with whitespace collapsed and common entities decoded
+fn title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let text = html[start..end].split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&#39;", "'").replace("&amp;", "&");
+    (!text.is_empty()).then(|| text.chars().take(MAX_TITLE).collect())
+}
@@ -0,0 +1,67 @@
+use common::recording::{Inbound, RecordedFrame};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const BATCH_SIZE: usize = 256;
+
+// Everything clients send, as JSON lines with timestamps, for reproducing bugs with `replay`.
+// Like the journal, writes happen on a background task and are flushed by `close`.
+pub struct Recorder {
+    started: Instant,
+    next_conn: AtomicU64,
+    tx: std::sync::Mutex<Option<mpsc::UnboundedSender<RecordedFrame>>>,
+    task: tokio::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Recorder {
+    // Each run starts a fresh recording, since times count from startup
+    pub fn start(path: PathBuf) -> io::Result<Self> {
+        let file = File::create(&path)?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(write_frames(path, file, rx));
+        Ok(Self { started: Instant::now(), next_conn: AtomicU64::new(1), tx: std::sync::Mutex::new(Some(tx)), task: tokio::sync::Mutex::new(Some(task)) })
+    }
+
+    // Numbers a new connection and records where it came from
+    pub fn open(&self, addr: &str) -> u64 {
+        let conn = self.next_conn.fetch_add(1, Ordering::Relaxed);
+        self.record(conn, Inbound::Open { addr: addr.to_string() });
+        conn
+    }
+
+    pub fn record(&self, conn: u64, frame: Inbound) {
+        let at_ms = self.started.elapsed().as_millis() as u64;
+        if let Some(tx) = self.tx.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            let _ = tx.send(RecordedFrame { at_ms, conn, frame });
+        }
+    }
+
+    // Writes out everything queued so far and stops
+    pub async fn close(&self) {
+        self.tx.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(task) = self.task.lock().await.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+async fn write_frames(path: PathBuf, file: File, mut rx: mpsc::UnboundedReceiver<RecordedFrame>) {
+    let mut file = Some(file);
+    let mut batch = Vec::new();
+    while rx.recv_many(&mut batch, BATCH_SIZE).await > 0 {
+        let lines: String = batch.drain(..).filter_map(|frame| serde_json::to_string(&frame).ok()).map(|line| line + "\n").collect();
+        let Some(mut out) = file.take() else { continue };
+        let written = tokio::task::spawn_blocking(move || out.write_all(lines.as_bytes()).map(|()| out)).await;
+        match written {
+            Ok(Ok(out)) => file = Some(out),
+            // Stops rather than leaving a recording with holes in it
+            Ok(Err(e)) => eprintln!("Could not write recording to {}, recording stopped: {}", path.display(), e),
+            Err(e) => eprintln!("Could not write recording to {}, recording stopped: {}", path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use chrono::{DateTime, Duration, Utc};
+use common::RoomName;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::groups::Groups;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+const CODE_LENGTH: usize = 8;
+const MAX_INVITE_DAYS: i64 = 30;
+pub const MAX_TAGS: usize = 5;
+pub const MAX_TAG_LEN: usize = 24;
+pub const MAX_DESCRIPTION_LEN: usize = 200;
+
+// Ownership and access for rooms; a room with no entry is open and unowned
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct RoomInfo {
+    owner: Option<String>, // Whoever created the room by joining it first
+    invite_only: bool,
+    members: HashSet<String>, // Allowed into an invite-only room: present when it closed, or redeemed a code
+    #[serde(default)]
+    groups: HashSet<String>, // Whose members are allowed in too
+    #[serde(with = "seconds")]
+    ttl: Option<Duration>, // Messages older than this are purged from history
+    #[serde(default)]
+    permissions: Permissions,
+    #[serde(default)]
+    moderators: HashSet<String>, // Besides the owner
+    #[serde(default)]
+    voiced: HashSet<String>,
+    #[serde(default)]
+    unmoderated: bool, // Skipped by the config's `moderation`
+    #[serde(default)]
+    karma: bool, // Off unless turned on, as karma is opt-in
+    #[serde(default)]
+    description: Option<String>, // For `/discover`
+    #[serde(default)]
+    tags: BTreeSet<String>,
+    #[serde(default)]
+    broadcast: bool, // Announcements: only voiced users and moderators post, and everyone is subscribed
+}
+
+// Who may do something in a room, from least to most trusted. Admins count as moderators
+// everywhere, and owners in their own rooms.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    #[default]
+    Everyone,
+    Voiced,
+    Moderators,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Everyone => "everyone",
+            Level::Voiced => "voiced",
+            Level::Moderators => "moderators",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [Level::Everyone, Level::Voiced, Level::Moderators].into_iter().find(|level| level.name() == name)
+    }
+}
+
+// What a room's permissions cover
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Post,   // Chat messages and forwards into the room
+    Invite, // Invite codes and which groups may enter
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Post => "post",
+            Action::Invite => "invite",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [Action::Post, Action::Invite].into_iter().find(|action| action.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Permissions {
+    pub post: Level,
+    pub invite: Level,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self { post: Level::Everyone, invite: Level::Moderators }
+    }
+}
+
+impl Permissions {
+    pub fn required(&self, action: Action) -> Level {
+        match action {
+            Action::Post => self.post,
+            Action::Invite => self.invite,
+        }
+    }
+
+    fn set(&mut self, action: Action, level: Level) {
+        match action {
+            Action::Post => self.post = level,
+            Action::Invite => self.invite = level,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Invite {
+    pub code: String,
+    pub room: RoomName,
+    pub expires: DateTime<Utc>,
+    pub uses: u32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Rooms {
+    rooms: HashMap<RoomName, RoomInfo>,
+    invites: HashMap<String, Invite>, // By code
+}
+
+impl Rooms {
+    pub fn contains(&self, room: &str) -> bool {
+        self.rooms.contains_key(room)
+    }
+
+    pub fn claim(&mut self, room: &RoomName, owner: &str) {
+        self.rooms.entry(room.clone()).or_default().owner.get_or_insert_with(|| owner.to_string());
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &RoomName> {
+        self.rooms.keys()
+    }
+
+    pub fn is_invite_only(&self, room: &str) -> bool {
+        self.rooms.get(room).is_some_and(|r| r.invite_only)
+    }
+
+    pub fn ttl(&self, room: &str) -> Option<Duration> {
+        self.rooms.get(room).and_then(|r| r.ttl)
+    }
+
+    pub fn set_ttl(&mut self, room: &RoomName, ttl: Option<Duration>) {
+        self.rooms.entry(room.clone()).or_default().ttl = ttl;
+    }
+
+    pub fn ttls(&self) -> Vec<(RoomName, Duration)> {
+        self.rooms.iter().filter_map(|(room, info)| Some((room.clone(), info.ttl?))).collect()
+    }
+
+    pub fn is_owner(&self, room: &str, username: &str) -> bool {
+        self.rooms.get(room).is_some_and(|r| r.owner.as_deref() == Some(username))
+    }
+
+    pub fn permissions(&self, room: &str) -> Permissions {
+        self.rooms.get(room).map(|r| r.permissions).unwrap_or_default()
+    }
+
+    pub fn set_permission(&mut self, room: &RoomName, action: Action, level: Level) {
+        self.rooms.entry(room.clone()).or_default().permissions.set(action, level);
+    }
+
+    pub fn is_moderated(&self, room: &str) -> bool {
+        !self.rooms.get(room).is_some_and(|r| r.unmoderated)
+    }
+
+    pub fn set_moderated(&mut self, room: &RoomName, moderated: bool) {
+        self.rooms.entry(room.clone()).or_default().unmoderated = !moderated;
+    }
+
+    pub fn has_karma(&self, room: &str) -> bool {
+        self.rooms.get(room).is_some_and(|r| r.karma)
+    }
+
+    pub fn set_karma(&mut self, room: &RoomName, karma: bool) {
+        self.rooms.entry(room.clone()).or_default().karma = karma;
+    }
+
+    pub fn description(&self, room: &str) -> Option<&str> {
+        self.rooms.get(room).and_then(|r| r.description.as_deref())
+    }
+
+    pub fn set_description(&mut self, room: &RoomName, description: Option<String>) {
+        self.rooms.entry(room.clone()).or_default().description = description;
+    }
+
+    pub fn tags(&self, room: &str) -> Vec<String> {
+        self.rooms.get(room).map(|r| r.tags.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn set_tags(&mut self, room: &RoomName, tags: BTreeSet<String>) {
+        self.rooms.entry(room.clone()).or_default().tags = tags;
+    }
+
+    pub fn is_broadcast(&self, room: &str) -> bool {
+        self.rooms.get(room).is_some_and(|r| r.broadcast)
+    }
+
+    pub fn set_broadcast(&mut self, room: &RoomName, broadcast: bool) {
+        self.rooms.entry(room.clone()).or_default().broadcast = broadcast;
+    }
+
+    pub fn broadcasts(&self) -> impl Iterator<Item = &RoomName> {
+        self.rooms.iter().filter(|(_, info)| info.broadcast).map(|(room, _)| room)
+    }
+
+    // Callers treat admins as moderators regardless
+    pub fn level(&self, room: &str, username: &str) -> Level {
+        match self.rooms.get(room) {
+            Some(info) if info.owner.as_deref() == Some(username) || info.moderators.contains(username) => Level::Moderators,
+            Some(info) if info.voiced.contains(username) => Level::Voiced,
+            _ => Level::Everyone,
+        }
+    }
+
+    // Broadcast rooms need at least voice to post, whatever their `post` level
+    pub fn permits(&self, room: &str, username: &str, action: Action) -> bool {
+        let mut required = self.permissions(room).required(action);
+        if action == Action::Post && self.is_broadcast(room) {
+            required = required.max(Level::Voiced);
+        }
+        self.level(room, username) >= required
+    }
+
+    // Gives `username` exactly `level` in the room; false if they had it already
+    pub fn set_level(&mut self, room: &RoomName, username: &str, level: Level) -> bool {
+        let info = self.rooms.entry(room.clone()).or_default();
+        let changed = match level {
+            Level::Moderators => info.moderators.insert(username.to_string()),
+            Level::Voiced => info.voiced.insert(username.to_string()),
+            Level::Everyone => false,
+        };
+        let removed_mod = level != Level::Moderators && info.moderators.remove(username);
+        let removed_voice = level != Level::Voiced && info.voiced.remove(username);
+        changed || removed_mod || removed_voice
+    }
+
+    // Moderators and voiced users besides the owner, sorted
+    pub fn members_at(&self, room: &str, level: Level) -> Vec<&String> {
+        let mut names: Vec<&String> = match (self.rooms.get(room), level) {
+            (Some(info), Level::Moderators) => info.moderators.iter().collect(),
+            (Some(info), Level::Voiced) => info.voiced.iter().collect(),
+            _ => Vec::new(),
+        };
+        names.sort();
+        names
+    }
+
+    // Callers let admins in regardless
+    pub fn may_enter(&self, room: &str, username: &str, groups: &Groups) -> bool {
+        match self.rooms.get(room) {
+            Some(info) if info.invite_only => {
+                info.owner.as_deref() == Some(username) || info.members.contains(username) || info.groups.iter().any(|g| groups.is_member(g, username))
+            }
+            _ => true,
+        }
+    }
+
+    // Closes the room, unless it is already; `present` keeps access for whoever is in it right now
+    fn close(&mut self, room: &RoomName, present: Vec<String>) -> &mut RoomInfo {
+        let info = self.rooms.entry(room.clone()).or_default();
+        if !info.invite_only {
+            info.invite_only = true;
+            info.members.extend(present);
+        }
+        info
+    }
+
+    // Closes the room on first use, like an invite; false if the group was allowed already
+    pub fn allow_group(&mut self, room: &RoomName, group: &str, present: Vec<String>) -> bool {
+        self.close(room, present).groups.insert(group.to_lowercase())
+    }
+
+    pub fn disallow_group(&mut self, room: &str, group: &str) -> bool {
+        self.rooms.get_mut(room).is_some_and(|info| info.groups.remove(&group.to_lowercase()))
+    }
+
+    pub fn allowed_groups(&self, room: &str) -> Vec<&String> {
+        let mut groups: Vec<&String> = self.rooms.get(room).map(|info| info.groups.iter().collect()).unwrap_or_default();
+        groups.sort();
+        groups
+    }
+
+    // For a deleted group
+    pub fn drop_group(&mut self, group: &str) {
+        for info in self.rooms.values_mut() {
+            info.groups.remove(group);
+        }
+    }
+
+    // Closes the room on first use; `present` keeps access for whoever is in it right now
+    pub fn create_invite(&mut self, room: &RoomName, lifetime: Duration, present: Vec<String>) -> &Invite {
+        self.close(room, present);
+        let code = loop {
+            let code = uuid::Uuid::new_v4().simple().to_string()[..CODE_LENGTH].to_uppercase();
+            if !self.invites.contains_key(&code) {
+                break code;
+            }
+        };
+        let invite = Invite { code: code.clone(), room: room.clone(), expires: Utc::now() + lifetime, uses: 0 };
+        self.invites.entry(code).or_insert(invite)
+    }
+
+    // The room the code lets the user into, or None for an unknown or expired code
+    pub fn redeem(&mut self, code: &str, username: &str) -> Option<RoomName> {
+        self.expire();
+        let invite = self.invites.get_mut(&code.to_uppercase())?;
+        invite.uses += 1;
+        self.rooms.entry(invite.room.clone()).or_default().members.insert(username.to_string());
+        Some(invite.room.clone())
+    }
+
+    pub fn invites_for(&mut self, room: &str) -> Vec<&Invite> {
+        self.expire();
+        let mut invites: Vec<&Invite> = self.invites.values().filter(|i| i.room == room).collect();
+        invites.sort_by_key(|i| i.expires);
+        invites
+    }
+
+    pub fn revoke(&mut self, room: &str, code: &str) -> bool {
+        let code = code.to_uppercase();
+        if self.invites.get(&code).is_some_and(|i| i.room == room) {
+            self.invites.remove(&code);
+            return true;
+        }
+        false
+    }
+
+    // Makes an invite-only room open again and drops its codes
+    pub fn open(&mut self, room: &str) {
+        if let Some(info) = self.rooms.get_mut(room) {
+            info.invite_only = false;
+            info.members.clear();
+            info.groups.clear();
+        }
+        self.invites.retain(|_, i| i.room != room);
+    }
+
+    // Drops a user's access, so a reused guest name starts with none
+    pub fn forget(&mut self, username: &str) {
+        for info in self.rooms.values_mut() {
+            info.members.remove(username);
+            info.moderators.remove(username);
+            info.voiced.remove(username);
+        }
+    }
+
+    fn expire(&mut self) {
+        let now = Utc::now();
+        self.invites.retain(|_, i| i.expires > now);
+    }
+}
+
+// TTLs as whole seconds in snapshots
+mod seconds {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(ttl: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        ttl.map(|ttl| ttl.num_seconds()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<i64>::deserialize(deserializer)?.and_then(Duration::try_seconds))
+    }
+}
+
+// The inverse of `parse_lifetime`, in the largest unit that divides evenly
+pub fn format_lifetime(lifetime: Duration) -> String {
+    let minutes = lifetime.num_minutes();
+    match minutes {
+        m if m % (24 * 60) == 0 => format!("{}d", m / (24 * 60)),
+        m if m % 60 == 0 => format!("{}h", m / 60),
+        m => format!("{}m", m),
+    }
+}
+
+// Tags as `/roomset tags` and the config give them: lowercased, without a leading `#`, each
+// letters, digits and dashes, up to `MAX_TAGS` of them. None if any tag isn't like that.
+pub fn parse_tags<'a>(words: impl IntoIterator<Item = &'a str>) -> Option<BTreeSet<String>> {
+    let mut tags = BTreeSet::new();
+    for word in words {
+        let tag = word.trim_start_matches('#').to_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LEN || !tag.chars().all(|c| c.is_alphanumeric() || c == '-') {
+            return None;
+        }
+        tags.insert(tag);
+    }
+    (tags.len() <= MAX_TAGS).then_some(tags)
+}
+
+// "30m", "24h" or "7d", up to 30 days
+pub fn parse_lifetime(text: &str) -> Option<Duration> {
+    let unit = text.chars().last()?;
+    // Bounded first, as an out-of-range Duration panics
+    let amount: i64 = text[..text.len() - unit.len_utf8()].parse().ok().filter(|n| (1..=MAX_INVITE_DAYS * 24 * 60).contains(n))?;
+    let lifetime = match unit {
+        'm' => Duration::minutes(amount),
+        'h' => Duration::hours(amount),
+        'd' => Duration::days(amount),
+        _ => return None,
+    };
+    (lifetime <= Duration::days(MAX_INVITE_DAYS)).then_some(lifetime)
+}
@@ -0,0 +1,113 @@
+use crate::activity::Activity;
+use crate::autojoin::AutoJoin;
+use crate::groups::Groups;
+use crate::karma::Karma;
+use crate::migrate::{Migration, Object, Schema};
+use crate::names::Reserved;
+use crate::onboarding::Onboarding;
+use crate::rooms::Rooms;
+use crate::warnings::Warnings;
+use chrono::{DateTime, Utc};
+use common::{ChatMessage, MessageId, RoomName, UserProfile};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const SCHEMA: Schema = Schema {
+    name: "snapshot",
+    migrations: &[
+        Migration { what: "add groups, warnings and reserved names", apply: add_moderation },
+        Migration { what: "add pinned messages", apply: add_pins },
+        Migration { what: "add room activity", apply: add_activity },
+        Migration { what: "add karma", apply: add_karma },
+        Migration { what: "add onboarding", apply: add_onboarding },
+        Migration { what: "add auto-join lists", apply: add_auto_join },
+    ],
+};
+
+// Everything the server keeps in memory, plus registered accounts, so a restart or
+// another host can carry on where this one stopped. Connections can't be carried over.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub taken: DateTime<Utc>,
+    pub history: HashMap<RoomName, VecDeque<ChatMessage>>,
+    pub last_seq: HashMap<RoomName, u64>,
+    pub read_markers: HashMap<RoomName, HashMap<String, MessageId>>,
+    pub recent_pms: VecDeque<ChatMessage>,
+    pub starred: HashMap<String, Vec<ChatMessage>>,
+    pub profiles: HashMap<String, UserProfile>,
+    pub rooms: Rooms,
+    pub groups: Groups,
+    pub warnings: Warnings,
+    pub reserved: Reserved,
+    pub mirrors: Vec<(RoomName, RoomName)>,
+    pub pins: HashMap<RoomName, Vec<ChatMessage>>,
+    pub activity: Activity,
+    pub karma: Karma,
+    pub onboarding: Onboarding,
+    pub auto_join: AutoJoin,
+    pub accounts: serde_json::Value,
+}
+
+impl Snapshot {
+    // Written beside the target and renamed over it, so a crash mid-write keeps the last good one
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let partial = path.with_extension("partial");
+        fs::write(&partial, serde_json::to_string(&SCHEMA.stamp(serde_json::to_value(self)?))?)?;
+        fs::rename(&partial, path)
+    }
+
+    // Older snapshots are upgraded in memory; the file is left as it is
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let (value, _) = SCHEMA.upgrade(serde_json::from_str(&fs::read_to_string(path)?)?)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+// Version 0 snapshots may come from before groups, warnings or reserved names existed
+fn add_moderation(mut snapshot: Object) -> Result<Object, String> {
+    let empty = [
+        ("groups", serde_json::to_value(Groups::default())),
+        ("warnings", serde_json::to_value(Warnings::default())),
+        ("reserved", serde_json::to_value(Reserved::default())),
+    ];
+    for (key, value) in empty {
+        if !snapshot.contains_key(key) {
+            snapshot.insert(key.to_string(), value.map_err(|e| e.to_string())?);
+        }
+    }
+    Ok(snapshot)
+}
+
+fn add_pins(mut snapshot: Object) -> Result<Object, String> {
+    snapshot.entry("pins").or_insert_with(|| serde_json::json!({}));
+    Ok(snapshot)
+}
+
+fn add_activity(mut snapshot: Object) -> Result<Object, String> {
+    let empty = serde_json::to_value(Activity::default()).map_err(|e| e.to_string())?;
+    snapshot.entry("activity").or_insert(empty);
+    Ok(snapshot)
+}
+
+fn add_karma(mut snapshot: Object) -> Result<Object, String> {
+    let empty = serde_json::to_value(Karma::default()).map_err(|e| e.to_string())?;
+    snapshot.entry("karma").or_insert(empty);
+    Ok(snapshot)
+}
+
+// Anyone with a profile already was around before onboarding, so isn't asked
+fn add_onboarding(mut snapshot: Object) -> Result<Object, String> {
+    let profiles = snapshot.get("profiles").and_then(|profiles| profiles.as_object());
+    let done: Vec<String> = profiles.map(|profiles| profiles.keys().cloned().collect()).unwrap_or_default();
+    snapshot.entry("onboarding").or_insert_with(|| serde_json::json!({ "done": done }));
+    Ok(snapshot)
+}
+
+fn add_auto_join(mut snapshot: Object) -> Result<Object, String> {
+    let empty = serde_json::to_value(AutoJoin::default()).map_err(|e| e.to_string())?;
+    snapshot.entry("auto_join").or_insert(empty);
+    Ok(snapshot)
+}
@@ -0,0 +1,140 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+// Keeps the server's tasks honest about panics. A panicking task is otherwise only noticed
+// by the default hook's one line on stderr, and whatever it did stops for good.
+
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const STABLE: Duration = Duration::from_secs(300); // Running this long resets the backoff
+
+// What each background service is doing, for `/readyz`
+#[derive(Clone, Default)]
+pub struct Services(Arc<Mutex<BTreeMap<&'static str, Status>>>);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Status {
+    Running,
+    Restarting { panics: u32 }, // Waiting out the backoff
+    Stopped,
+}
+
+impl Services {
+    fn set(&self, name: &'static str, status: Status) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).insert(name, status);
+    }
+
+    pub fn statuses(&self) -> Vec<(&'static str, Status)> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|(name, status)| (*name, *status)).collect()
+    }
+}
+
+// Runs a background service, starting it again after a panic: one second later at first,
+// doubling up to a minute while it keeps failing. `start` is called for each run. Aborting
+// the returned handle stops the service too.
+pub fn service<F, Fut>(name: &'static str, services: &Services, start: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let services = services.clone();
+    tokio::spawn(async move {
+        let mut backoff = FIRST_BACKOFF;
+        let mut panics = 0;
+        loop {
+            let started = Instant::now();
+            services.set(name, Status::Running);
+            let mut run = Aborting(tokio::spawn(start()));
+            match (&mut run.0).await {
+                Ok(()) => break, // Finished on its own
+                Err(e) if e.is_cancelled() => break,
+                Err(e) => {
+                    if started.elapsed() > STABLE {
+                        backoff = FIRST_BACKOFF;
+                    }
+                    panics += 1;
+                    services.set(name, Status::Restarting { panics });
+                    eprintln!("{} panicked: {}; restarting in {}s", name, message(e.into_panic()), backoff.as_secs());
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+        services.set(name, Status::Stopped);
+    })
+}
+
+// Runs one connection, or anything else that isn't restarted, and logs a panic with `what`
+pub fn task<Fut>(what: String, task: Fut)
+where
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(e) = tokio::spawn(task).await {
+            if e.is_panic() {
+                eprintln!("{} panicked: {}", what, message(e.into_panic()));
+            }
+        }
+    });
+}
+
+// What a panic was raised with, for the ones raised with a message
+pub fn message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(text) => *text,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("(no message)", |text| text).to_string(),
+    }
+}
+
+// A run that is aborted along with the service watching it
+struct Aborting(JoinHandle<()>);
+
+impl Drop for Aborting {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn a_panicking_service_is_started_again() {
+        let services = Services::default();
+        let runs = Arc::new(AtomicU32::new(0));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = service("Flaky", &services, move || {
+            let (runs, tx) = (runs.clone(), tx.clone());
+            async move {
+                let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                let _ = tx.send(run);
+                if run == 1 {
+                    panic!("first run fails");
+                }
+                std::future::pending::<()>().await;
+            }
+        });
+        assert_eq!(rx.recv().await, Some(1));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(services.statuses(), [("Flaky", Status::Restarting { panics: 1 })]);
+        // Restarted after the first backoff
+        let second = tokio::time::timeout(FIRST_BACKOFF * 3, rx.recv()).await;
+        assert_eq!(second, Ok(Some(2)));
+        assert_eq!(services.statuses(), [("Flaky", Status::Running)]);
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn a_service_that_finishes_is_stopped() {
+        let services = Services::default();
+        service("Once", &services, || async {}).await.unwrap();
+        assert_eq!(services.statuses(), [("Once", Status::Stopped)]);
+    }
+}
@@ -0,0 +1,96 @@
+use common::{ActivityReport, ChatMessage, Discovery, MessageType, PreviewUpdate, RoomEntry, UserProfile};
+
+// Messages as plain lines for connections that logged in with a bare name (telnet, netcat),
+// e.g. "[12:01] alice: hi". Times are UTC, since nothing says where the reader is.
+// Protocol bookkeeping (member lists, receipts, presence) is left out.
+
+// Telnet clients expect CRLF; a bare LF leaves the next line starting mid-screen
+const NEWLINE: &str = "\r\n";
+const CONTINUATION: &str = "\r\n    "; // Before the second and later lines of a message
+
+pub fn render(msg: &ChatMessage) -> Option<String> {
+    let time = msg.format_time();
+    let mut content = msg.content_lines().collect::<Vec<_>>().join(CONTINUATION);
+    if let Some(forwarded) = &msg.forwarded {
+        content = format!("[forwarded from {}] {}", forwarded.label(), content);
+    }
+    if let Some(origin) = &msg.mirrored_from {
+        content = format!("[via #{}] {}", origin, content);
+    }
+    if let Some(warning) = &msg.link_warning {
+        content = format!("{}{}! {} is flagged as {}", content, CONTINUATION, warning.url, warning.threat);
+    }
+    if let Some(preview) = &msg.preview {
+        content = format!("{}{}-> {}", content, CONTINUATION, preview.summary());
+    }
+    let line = match msg.msg_type {
+        MessageType::Chat => format!("[{}] {}: {}", time, msg.username, content),
+        MessageType::PrivateMessage => {
+            let recipient = msg.recipient.as_deref().unwrap_or_default();
+            format!("[{}] [pm] {} -> {}: {}", time, msg.username, recipient, content)
+        }
+        MessageType::Error => format!("[{}] ! {}", time, content),
+        MessageType::System | MessageType::UserJoin | MessageType::UserLeave | MessageType::RoomChange => format!("[{}] * {}", time, content),
+        MessageType::Pong => format!("[{}] * pong {}", time, content),
+        MessageType::Dice => format!("[{}] * {} {}", time, msg.username, content),
+        MessageType::RoomList => {
+            let entries: Vec<RoomEntry> = serde_json::from_str(&msg.content).unwrap_or_default();
+            let rooms: Vec<String> = entries.iter().map(|entry| format!("#{} ({})", entry.name, entry.users)).collect();
+            format!("[{}] * rooms: {}", time, rooms.join(", "))
+        }
+        MessageType::Discover => {
+            let discovery: Discovery = serde_json::from_str(&msg.content).ok()?;
+            let lines = discovery.rooms.iter().map(|room| {
+                let tags: String = room.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+                let description = room.description.as_ref().map(|d| format!(" - {}", d)).unwrap_or_default();
+                format!("{}#{} ({} here, {} today){}{}", CONTINUATION, room.name, room.users, room.messages_today, tags, description)
+            });
+            format!("[{}] * {} rooms found{}", time, discovery.rooms.len(), lines.collect::<String>())
+        }
+        MessageType::Starred => {
+            let saved: Vec<ChatMessage> = serde_json::from_str(&msg.content).unwrap_or_default();
+            let lines = saved.iter().map(|m| format!("{}{} #{} {}: {}", CONTINUATION, m.id, m.room, m.username, m.content_lines().collect::<Vec<_>>().join(" ")));
+            format!("[{}] * {} starred{}", time, saved.len(), lines.collect::<String>())
+        }
+        MessageType::Profile => {
+            let profile: UserProfile = serde_json::from_str(&msg.content).unwrap_or_default();
+            let fields = [("display name", &profile.display_name), ("pronouns", &profile.pronouns), ("timezone", &profile.timezone), ("bio", &profile.bio)];
+            let details: Vec<String> = fields.iter().filter_map(|(label, value)| value.as_ref().map(|v| format!("{}: {}", label, v))).collect();
+            format!("[{}] * profile of {}: {}", time, msg.username, if details.is_empty() { "empty".to_string() } else { details.join(", ") })
+        }
+        MessageType::Stats => format!("[{}] * server stats: {}", time, msg.content),
+        MessageType::Console => format!("[{}] * console: {}", time, msg.content),
+        MessageType::Moderation => format!("[{}] * moderation: {}", time, msg.content),
+        MessageType::Activity => {
+            let report: ActivityReport = serde_json::from_str(&msg.content).ok()?;
+            let busiest = report.busiest_hour().map(|hour| format!(", busiest around {:02}:00 UTC", hour)).unwrap_or_default();
+            format!("[{}] * #{}: {} messages in the last 24 hours{}", time, report.room, report.last_day.iter().sum::<u64>(), busiest)
+        }
+        MessageType::Preview => {
+            let update: PreviewUpdate = serde_json::from_str(&msg.content).ok()?;
+            format!("[{}] * link: {}", time, update.preview.summary())
+        }
+        MessageType::Expired => format!("[{}] * {} expired messages removed from #{}", time, msg.content.split(',').count(), msg.room),
+        MessageType::UserList
+        | MessageType::AutoJoin
+        | MessageType::Commands
+        | MessageType::Karma
+        | MessageType::ReadReceipt
+        | MessageType::Presence
+        | MessageType::AuthRequired
+        | MessageType::Capabilities
+        | MessageType::Compression
+        | MessageType::Encoding
+        | MessageType::Prompt // Never sent to raw clients
+        | MessageType::Unknown => return None,
+    };
+    Some(format!("{}{}", strip_controls(&line), NEWLINE))
+}
+
+// Names and text come from other users and would reach the reader's terminal as written, where
+// an ESC sequence can clear the screen or rewrite earlier lines. Tabs are harmless; any other
+// C0 or C1 control shows as U+FFFD, leaving only the line breaks added above.
+fn strip_controls(line: &str) -> String {
+    let clean = |part: &str| part.chars().map(|c| if c.is_control() && c != '\t' { char::REPLACEMENT_CHARACTER } else { c }).collect::<String>();
+    line.split(CONTINUATION).map(clean).collect::<Vec<_>>().join(CONTINUATION)
+}
@@ -0,0 +1,165 @@
+use crate::http;
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_BATCH: usize = 512;
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_OTLP_PORT: u16 = 4318;
+
+// OpenTelemetry spans sent as OTLP/JSON over plain HTTP to `OTEL_EXPORTER_OTLP_ENDPOINT`,
+// e.g. `http://localhost:4318`. No OTel crate is available to this build, so this speaks
+// the wire format directly. Without the variable every span is a no-op.
+pub struct Tracer {
+    tx: Option<mpsc::UnboundedSender<SpanData>>,
+}
+
+struct SpanData {
+    trace_id: String,
+    span_id: String,
+    parent_id: Option<String>,
+    name: &'static str,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: Vec<(&'static str, String)>,
+}
+
+// Ends, and is queued for export, when dropped
+pub struct Span {
+    tx: Option<mpsc::UnboundedSender<SpanData>>,
+    trace_id: String,
+    span_id: String,
+    parent_id: Option<String>,
+    name: &'static str,
+    start: SystemTime,
+    attributes: Vec<(&'static str, String)>,
+}
+
+// Where to POST: `host:port` to connect to, and the request path
+struct Endpoint {
+    host: String,
+    path: String,
+}
+
+impl Tracer {
+    // Needs the runtime, as it starts the exporter task
+    pub fn from_env() -> Self {
+        let Ok(url) = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") else {
+            return Self { tx: None };
+        };
+        let Some(endpoint) = Endpoint::parse(&url) else {
+            eprintln!("Tracing off: {} is not an http:// URL (https is not supported)", url);
+            return Self { tx: None };
+        };
+        let service = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "ultimate-chat-server".to_string());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(export(endpoint, service, rx));
+        println!("Exporting traces to {}", url);
+        Self { tx: Some(tx) }
+    }
+
+    // Starts a new trace
+    pub fn span(&self, name: &'static str) -> Span {
+        let trace_id = if self.tx.is_some() { uuid::Uuid::new_v4().simple().to_string() } else { String::new() };
+        Span::start(self.tx.clone(), trace_id, None, name)
+    }
+}
+
+impl Span {
+    fn start(tx: Option<mpsc::UnboundedSender<SpanData>>, trace_id: String, parent_id: Option<String>, name: &'static str) -> Self {
+        // Span IDs are 8 bytes to the 16 of a trace ID
+        let span_id = if tx.is_some() { uuid::Uuid::new_v4().simple().to_string()[..16].to_string() } else { String::new() };
+        Self { tx, trace_id, span_id, parent_id, name, start: SystemTime::now(), attributes: Vec::new() }
+    }
+
+    pub fn child(&self, name: &'static str) -> Span {
+        Span::start(self.tx.clone(), self.trace_id.clone(), Some(self.span_id.clone()), name)
+    }
+
+    pub fn attr(&mut self, key: &'static str, value: impl ToString) {
+        if self.tx.is_some() {
+            self.attributes.push((key, value.to_string()));
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(SpanData {
+                trace_id: std::mem::take(&mut self.trace_id),
+                span_id: std::mem::take(&mut self.span_id),
+                parent_id: self.parent_id.take(),
+                name: self.name,
+                start: self.start,
+                end: SystemTime::now(),
+                attributes: std::mem::take(&mut self.attributes),
+            });
+        }
+    }
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Option<Self> {
+        let (host, base) = http::parse_url(url, DEFAULT_OTLP_PORT)?;
+        Some(Self { host, path: format!("{}/v1/traces", base.trim_end_matches('/')) })
+    }
+}
+
+// Batches spans and sends them every few seconds; a failed export is reported and dropped
+async fn export(endpoint: Endpoint, service: String, mut rx: mpsc::UnboundedReceiver<SpanData>) {
+    let mut batch = Vec::new();
+    let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+    loop {
+        tokio::select! {
+            span = rx.recv() => match span {
+                Some(span) => {
+                    batch.push(span);
+                    if batch.len() < MAX_BATCH {
+                        continue;
+                    }
+                }
+                None => break,
+            },
+            _ = interval.tick() => {}
+        }
+        if batch.is_empty() {
+            continue;
+        }
+        let body = request_body(&service, std::mem::take(&mut batch));
+        match tokio::time::timeout(EXPORT_TIMEOUT, http::post_json(&endpoint.host, &endpoint.path, &body)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => eprintln!("Trace export to {} failed: {}", endpoint.host, e),
+            Err(_) => eprintln!("Trace export to {} timed out", endpoint.host),
+        }
+    }
+}
+
+// An OTLP ExportTraceServiceRequest in its JSON encoding
+fn request_body(service: &str, spans: Vec<SpanData>) -> String {
+    let nanos = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string();
+    let spans: Vec<Value> = spans
+        .into_iter()
+        .map(|span| {
+            let attributes: Vec<Value> = span.attributes.iter().map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } })).collect();
+            json!({
+                "traceId": span.trace_id,
+                "spanId": span.span_id,
+                "parentSpanId": span.parent_id.unwrap_or_default(),
+                "name": span.name,
+                "kind": 1, // SPAN_KIND_INTERNAL
+                "startTimeUnixNano": nanos(span.start),
+                "endTimeUnixNano": nanos(span.end),
+                "attributes": attributes,
+            })
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [{ "key": "service.name", "value": { "stringValue": service } }] },
+            "scopeSpans": [{ "scope": { "name": "ultimate-chat" }, "spans": spans }],
+        }]
+    })
+    .to_string()
+}
@@ -0,0 +1,93 @@
+use crate::http;
+use common::MessageId;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+const TRANSLATE_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TRANSLATE_PORT: u16 = 80;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+// `translate` in the server config: a LibreTranslate-style http:// service for `/translate`
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranslateSettings {
+    pub url: String, // POSTed {"q", "source": "auto", "target", "format": "text"}, answering {"translatedText"}
+    pub api_key: Option<String>, // Sent as "api_key" for services that want one
+    pub per_minute: usize,       // Translations each user may ask the service for; cached ones are free
+    pub cache: usize,            // Translations remembered
+}
+
+impl Default for TranslateSettings {
+    fn default() -> Self {
+        Self { url: String::new(), api_key: None, per_minute: 10, cache: 256 }
+    }
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+}
+
+impl TranslateSettings {
+    pub fn valid_url(&self) -> bool {
+        http::parse_url(&self.url, DEFAULT_TRANSLATE_PORT).is_some()
+    }
+
+    pub async fn translate(&self, text: &str, target: &str) -> anyhow::Result<String> {
+        let (host, path) = http::parse_url(&self.url, DEFAULT_TRANSLATE_PORT).ok_or_else(|| anyhow::anyhow!("bad url {}", self.url))?;
+        let mut body = json!({ "q": text, "source": "auto", "target": target, "format": "text" });
+        if let Some(key) = &self.api_key {
+            body["api_key"] = json!(key);
+        }
+        let answer = tokio::time::timeout(TRANSLATE_TIMEOUT, http::post_json(&host, &path, &body.to_string())).await.map_err(|_| anyhow::anyhow!("timed out"))??;
+        Ok(serde_json::from_str::<Answer>(&answer)?.translated_text)
+    }
+}
+
+// Language codes as services name them: "de", "pt-BR", "zh-Hans"
+pub fn valid_language(code: &str) -> bool {
+    (2..=8).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphabetic() || c == '-') && !code.starts_with('-')
+}
+
+// Translations already made, and who asked the service for one lately
+#[derive(Default)]
+pub struct Translations {
+    cache: HashMap<(MessageId, String), String>,
+    order: VecDeque<(MessageId, String)>, // Oldest first
+    asked: HashMap<String, VecDeque<Instant>>,
+}
+
+impl Translations {
+    pub fn cached(&self, message: MessageId, language: &str) -> Option<String> {
+        self.cache.get(&(message, language.to_string())).cloned()
+    }
+
+    pub fn insert(&mut self, message: MessageId, language: &str, text: String, capacity: usize) {
+        let key = (message, language.to_string());
+        if self.cache.insert(key.clone(), text).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.cache.remove(&oldest);
+            }
+        }
+    }
+
+    // Counts a request from `username`; false if they have used up the last minute's
+    pub fn allow(&mut self, username: &str, per_minute: usize) -> bool {
+        let now = Instant::now();
+        let asked = self.asked.entry(username.to_string()).or_default();
+        while asked.front().is_some_and(|at| now.duration_since(*at) >= RATE_WINDOW) {
+            asked.pop_front();
+        }
+        if asked.len() >= per_minute {
+            return false;
+        }
+        asked.push_back(now);
+        true
+    }
+}
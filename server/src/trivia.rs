@@ -0,0 +1,136 @@
+use crate::games::{Game, Rounds, Say, Scoreboard};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+const BUILT_IN: &[(&str, &str)] = &[
+    ("What is the largest planet in the solar system?", "Jupiter"),
+    ("How many legs does a spider have?", "8"),
+    ("What is the chemical symbol for gold?", "Au"),
+    ("Which ocean is the largest?", "Pacific"),
+    ("What is the capital of Japan?", "Tokyo"),
+    ("How many minutes are in a day?", "1440"),
+    ("Which planet is known as the red planet?", "Mars"),
+    ("What is the freezing point of water in Fahrenheit?", "32"),
+    ("Who wrote Romeo and Juliet?", "Shakespeare"),
+    ("What is the hardest natural substance?", "Diamond"),
+    ("How many sides does a hexagon have?", "6"),
+    ("What gas do plants take in from the air?", "Carbon dioxide"),
+    ("In which country are the pyramids of Giza?", "Egypt"),
+    ("What is the smallest prime number?", "2"),
+    ("Which metal is liquid at room temperature?", "Mercury"),
+];
+
+// `trivia` in the server config
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TriviaSettings {
+    pub questions: Vec<Question>, // Asked instead of the built-in ones
+    pub rounds: usize,
+    pub seconds: u64, // To answer each question
+}
+
+impl Default for TriviaSettings {
+    fn default() -> Self {
+        Self { questions: Vec::new(), rounds: 5, seconds: 30 }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Question {
+    pub question: String,
+    pub answer: String,
+}
+
+// `/trivia start`: questions in turn, the first right answer in the room scoring a point
+pub struct Trivia {
+    questions: Vec<Question>, // In the order they will be asked
+    rounds: Rounds,
+    scores: Scoreboard,
+    finished: bool,
+}
+
+impl Trivia {
+    pub fn new(settings: &TriviaSettings) -> Self {
+        let mut questions = if settings.questions.is_empty() {
+            BUILT_IN.iter().map(|(question, answer)| Question { question: question.to_string(), answer: answer.to_string() }).collect()
+        } else {
+            settings.questions.clone()
+        };
+        // Shuffled, so each game asks different ones first
+        for i in (1..questions.len()).rev() {
+            questions.swap(i, (uuid::Uuid::new_v4().as_u128() % (i as u128 + 1)) as usize);
+        }
+        let rounds = Rounds::new(settings.rounds.clamp(1, questions.len().max(1)), Duration::from_secs(settings.seconds.max(1)));
+        Self { questions, rounds, scores: Scoreboard::default(), finished: false }
+    }
+
+    fn question(&self) -> &Question {
+        &self.questions[self.rounds.round - 1]
+    }
+
+    // Asks the next question, or ends the game after the last
+    fn next(&mut self, now: Instant, said: &mut Vec<Say>) {
+        if self.rounds.next(now) {
+            let (round, total) = (self.rounds.round.to_string(), self.rounds.total.to_string());
+            said.push(Say::new("sys.trivia_question", &[&round, &total, &self.question().question]));
+            return;
+        }
+        self.finished = true;
+        said.push(if self.scores.is_empty() { Say::new("sys.trivia_over_no_winner", &[]) } else { Say::new("sys.trivia_over", &[&self.scores.standings()]) });
+    }
+}
+
+impl Game for Trivia {
+    fn name(&self) -> &'static str {
+        "trivia"
+    }
+
+    fn start(&mut self, now: Instant) -> Vec<Say> {
+        if self.questions.is_empty() {
+            self.finished = true;
+            return Vec::new();
+        }
+        let mut said = vec![Say::new("sys.trivia_start", &[&self.rounds.total.to_string(), &self.rounds.length().as_secs().to_string()])];
+        self.next(now, &mut said);
+        said
+    }
+
+    fn message(&mut self, user: &str, text: &str, now: Instant) -> Vec<Say> {
+        if !self.rounds.open() || normalize(text) != normalize(&self.question().answer) {
+            return Vec::new();
+        }
+        let score = self.scores.add(user);
+        let mut said = vec![Say::new("sys.trivia_correct", &[user, &self.question().answer, &score.to_string()])];
+        self.next(now, &mut said);
+        said
+    }
+
+    fn tick(&mut self, now: Instant) -> Vec<Say> {
+        if !self.rounds.expired(now) {
+            return Vec::new();
+        }
+        let mut said = vec![Say::new("sys.trivia_timeout", &[&self.question().answer])];
+        self.next(now, &mut said);
+        said
+    }
+
+    fn scores(&self) -> Say {
+        let (round, total) = (self.rounds.round.to_string(), self.rounds.total.to_string());
+        if self.scores.is_empty() {
+            Say::new("sys.trivia_no_scores", &[&round, &total])
+        } else {
+            Say::new("sys.trivia_scores", &[&round, &total, &self.scores.standings()])
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.finished
+    }
+}
+
+// Answers match ignoring case, punctuation and extra spaces
+fn normalize(text: &str) -> String {
+    let kept: String = text.to_lowercase().chars().map(|c| if c.is_alphanumeric() { c } else { ' ' }).collect();
+    kept.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Warnings given with `/warn`, and the mutes and temporary bans they escalated to.
+// Kept by username, so they outlast the connection they were given on.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Warnings {
+    given: HashMap<String, Vec<Warning>>,
+    muted: HashMap<String, DateTime<Utc>>,  // Until
+    banned: HashMap<String, DateTime<Utc>>, // Until
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Warning {
+    pub by: String,
+    pub reason: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Warnings {
+    // Returns how many warnings the user has now
+    pub fn warn(&mut self, username: &str, by: &str, reason: &str) -> usize {
+        let given = self.given.entry(username.to_string()).or_default();
+        given.push(Warning { by: by.to_string(), reason: reason.to_string(), at: Utc::now() });
+        given.len()
+    }
+
+    pub fn history(&self, username: &str) -> &[Warning] {
+        self.given.get(username).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // Forgets the warnings and lifts any mute or ban; false if there was nothing to clear
+    pub fn clear(&mut self, username: &str) -> bool {
+        let warned = self.given.remove(username).is_some();
+        let muted = self.muted.remove(username).is_some();
+        let banned = self.banned.remove(username).is_some();
+        warned || muted || banned
+    }
+
+    pub fn mute(&mut self, username: &str, lasting: Duration) {
+        self.muted.insert(username.to_string(), Utc::now() + lasting);
+    }
+
+    pub fn ban(&mut self, username: &str, lasting: Duration) {
+        self.banned.insert(username.to_string(), Utc::now() + lasting);
+    }
+
+    // Lifts a mute or ban early, keeping the warnings; false if there was none
+    pub fn unmute(&mut self, username: &str) -> bool {
+        remaining(&mut self.muted, username).is_some() && self.muted.remove(username).is_some()
+    }
+
+    pub fn unban(&mut self, username: &str) -> bool {
+        remaining(&mut self.banned, username).is_some() && self.banned.remove(username).is_some()
+    }
+
+    // What is left of a mute or ban; expired ones are dropped as they are found
+    pub fn muted_for(&mut self, username: &str) -> Option<Duration> {
+        remaining(&mut self.muted, username)
+    }
+
+    // When a mute ends, to the second
+    pub fn muted_until(&mut self, username: &str) -> Option<DateTime<Utc>> {
+        self.muted_for(username)?;
+        self.muted.get(username).copied()
+    }
+
+    pub fn banned_for(&mut self, username: &str) -> Option<Duration> {
+        remaining(&mut self.banned, username)
+    }
+}
+
+fn remaining(until: &mut HashMap<String, DateTime<Utc>>, username: &str) -> Option<Duration> {
+    let left = *until.get(username)? - Utc::now();
+    if left <= Duration::zero() {
+        until.remove(username);
+        return None;
+    }
+    // Whole minutes, rounded up, so the last seconds don't read as "0m"
+    Some(Duration::minutes((left.num_seconds() + 59) / 60))
+}
@@ -0,0 +1,121 @@
+use common::{ChatMessage, MessageType, RoomName};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+// The HTTP listener, for things that can't speak the chat protocol, such as feed readers and
+// load balancers: GET requests only, one per connection. Like `http`, just enough HTTP/1.1
+// for the job.
+
+const MAX_HEAD: usize = 8 * 1024; // Request line and headers
+const FEED_ITEMS: usize = 50;
+const ITEM_TITLE: usize = 80; // Characters of the message shown as the item title
+
+pub struct Request {
+    pub path: String,
+    pub host: Option<String>,
+}
+
+pub struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    pub fn ok(content_type: &'static str, body: String) -> Self {
+        Self { status: "200 OK", content_type, body }
+    }
+
+    pub fn unavailable(content_type: &'static str, body: String) -> Self {
+        Self { status: "503 Service Unavailable", content_type, body }
+    }
+
+    pub fn not_found() -> Self {
+        Self { status: "404 Not Found", content_type: "text/plain; charset=utf-8", body: "Not found\n".to_string() }
+    }
+
+    fn method_not_allowed() -> Self {
+        Self { status: "405 Method Not Allowed", content_type: "text/plain; charset=utf-8", body: "Only GET is supported\n".to_string() }
+    }
+
+    fn bad_request() -> Self {
+        Self { status: "400 Bad Request", content_type: "text/plain; charset=utf-8", body: "Bad request\n".to_string() }
+    }
+}
+
+// Reads one request, asks `route` for the answer and closes the connection
+pub async fn serve<F, Fut>(socket: TcpStream, route: F) -> std::io::Result<()>
+where
+    F: FnOnce(Request) -> Fut,
+    Fut: std::future::Future<Output = Response>,
+{
+    let (reader, mut writer) = socket.into_split();
+    let mut reader = BufReader::new(reader);
+    let (mut head, mut line) = (Vec::new(), String::new());
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 || head.iter().map(String::len).sum::<usize>() > MAX_HEAD {
+            return Ok(());
+        }
+        if line.trim_end().is_empty() {
+            break;
+        }
+        head.push(line.trim_end().to_string());
+    }
+    let mut request_line = head.first().map(|line| line.split(' ')).into_iter().flatten();
+    let (method, target) = (request_line.next().unwrap_or_default(), request_line.next().unwrap_or_default());
+    let host = head.iter().skip(1).filter_map(|line| line.split_once(':')).find(|(name, _)| name.trim().eq_ignore_ascii_case("host")).map(|(_, value)| value.trim().to_string());
+    let response = match method {
+        "GET" if target.starts_with('/') => route(Request { path: target.split('?').next().unwrap_or_default().to_string(), host }).await,
+        "GET" | "" => Response::bad_request(),
+        _ => Response::method_not_allowed(),
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(response.body.as_bytes()).await?;
+    writer.shutdown().await
+}
+
+// The room a `/feeds/<room>.xml` path asks for; room names may contain `/`
+pub fn feed_room(path: &str) -> Option<RoomName> {
+    let room = path.strip_prefix("/feeds/")?.strip_suffix(".xml")?;
+    RoomName::new(room).ok()
+}
+
+// RSS 2.0 of a room's newest chat messages, newest first
+pub fn feed(room: &RoomName, host: Option<&str>, history: &[ChatMessage]) -> String {
+    let link = format!("http://{}/feeds/{}.xml", host.unwrap_or("localhost"), room);
+    let posts: Vec<&ChatMessage> = history.iter().rev().filter(|msg| msg.msg_type == MessageType::Chat).take(FEED_ITEMS).collect();
+    let updated = posts.first().map(|msg| msg.timestamp).unwrap_or_else(chrono::Utc::now);
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\">\n<channel>\n<title>#{}</title>\n<link>{}</link>\n<description>Messages posted in #{}</description>\n<lastBuildDate>{}</lastBuildDate>\n",
+        escape(room),
+        escape(&link),
+        escape(room),
+        updated.to_rfc2822()
+    );
+    for msg in posts {
+        let text = msg.content_lines().collect::<Vec<_>>().join(" ");
+        let title: String = text.chars().take(ITEM_TITLE).collect();
+        let title = if title.len() < text.len() { format!("{}…", title) } else { title };
+        xml.push_str(&format!(
+            "<item>\n<title>{}: {}</title>\n<description>{}</description>\n<guid isPermaLink=\"false\">{}</guid>\n<pubDate>{}</pubDate>\n</item>\n",
+            escape(&msg.username),
+            escape(&title),
+            escape(&msg.content),
+            msg.id,
+            msg.timestamp.to_rfc2822()
+        ));
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
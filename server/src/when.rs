@@ -0,0 +1,91 @@
+use crate::rooms;
+use chrono::{DateTime, Datelike, Days, FixedOffset, NaiveDate, NaiveTime, Utc, Weekday};
+
+// Times for `/time`: "15:00", "9:30pm", "tomorrow 9am", "fri 14:00 UTC+2",
+// "2025-03-01 10:00", "in 2h" or "now". Without a timezone in the text, the sender's
+// profile timezone is used.
+
+#[derive(Debug, PartialEq)]
+pub enum WhenError {
+    NotATime,
+    NoTimezone, // Neither the text nor the sender's profile has one
+}
+
+pub fn parse(text: &str, zone: Option<FixedOffset>, now: DateTime<Utc>) -> Result<DateTime<Utc>, WhenError> {
+    let text = text.to_lowercase();
+    let mut words: Vec<&str> = text.split_whitespace().collect();
+    match words.as_slice() {
+        ["now"] => return Ok(now),
+        ["in", amounts @ ..] if !amounts.is_empty() => {
+            // Each amount as `/ttl` takes it: "in 1h 30m"
+            return amounts.iter().try_fold(now, |at, amount| rooms::parse_lifetime(amount).map(|d| at + d)).ok_or(WhenError::NotATime);
+        }
+        _ => {}
+    }
+    let written_zone = words.last().filter(|word| word.starts_with("utc") || word.starts_with("gmt") || word.starts_with('+') || word.starts_with('-'));
+    let zone = match written_zone {
+        Some(word) => {
+            let zone = common::parse_utc_offset(&word.to_uppercase()).ok_or(WhenError::NotATime)?;
+            words.pop();
+            zone
+        }
+        None => zone.ok_or(WhenError::NoTimezone)?,
+    };
+    let today = now.with_timezone(&zone).date_naive();
+    let day = match words.first().and_then(|word| day(word, today)) {
+        Some(day) => {
+            words.remove(0);
+            Some(day)
+        }
+        None => None,
+    };
+    let time = clock(&words.concat()).ok_or(WhenError::NotATime)?;
+    let at = |date: NaiveDate| date.and_time(time).and_local_timezone(zone).single().map(|at| at.with_timezone(&Utc));
+    let moment = at(day.unwrap_or(today)).ok_or(WhenError::NotATime)?;
+    // A bare time that has passed today means tomorrow
+    match day {
+        None if moment < now => today.checked_add_days(Days::new(1)).and_then(at).ok_or(WhenError::NotATime),
+        _ => Ok(moment),
+    }
+}
+
+// "today", "tomorrow", a weekday (the next one, today included) or "2025-03-01"
+fn day(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match word {
+        "today" => Some(today),
+        "tomorrow" => today.checked_add_days(Days::new(1)),
+        _ => {
+            if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+                return Some(date);
+            }
+            let weekday: Weekday = word.parse().ok()?;
+            let ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+            today.checked_add_days(Days::new(ahead.into()))
+        }
+    }
+}
+
+// "15:00", "9am", "9:30pm" or "noon"; "9 pm" arrives here as "9pm"
+fn clock(text: &str) -> Option<NaiveTime> {
+    match text {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+    let (digits, half) = match (text.strip_suffix("am"), text.strip_suffix("pm")) {
+        (Some(digits), _) => (digits, Some(0)),
+        (_, Some(digits)) => (digits, Some(12)),
+        _ => (text, None),
+    };
+    let (hours, minutes) = digits.split_once(':').unwrap_or((digits, "00"));
+    let (mut hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if let Some(half) = half {
+        if !(1..=12).contains(&hours) {
+            return None;
+        }
+        hours = hours % 12 + half;
+    } else if !digits.contains(':') {
+        return None; // A lone number could be anything
+    }
+    NaiveTime::from_hms_opt(hours, minutes, 0)
+}
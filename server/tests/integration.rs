@@ -0,0 +1,1583 @@
+use common::recording::{Inbound, RecordedFrame};
+use common::deflate::{self, Inflater};
+use common::{ActivityReport, ChatMessage, CommandError, CommandInfo, ConsoleState, Discovery, ErrorReason, Handshake, KarmaScores, MessageType, ModerationState, PreviewUpdate, Prompt, PromptField, RoomEntry, RoomName, ServerCapabilities};
+use server::{ChatServer, ServerOptions};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use test_client::{chat, TestClient};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+const QUIET: Duration = Duration::from_millis(300); // How long to listen when expecting nothing
+
+// A server on an ephemeral port, with its files in a fresh directory
+async fn start_server() -> SocketAddr {
+    start_server_with_config(None).await
+}
+
+async fn start_server_with_config(config: Option<&str>) -> SocketAddr {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir: PathBuf = std::env::temp_dir().join(format!("chat-test-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+    std::fs::create_dir_all(&dir).unwrap();
+    if let Some(config) = config {
+        std::fs::write(dir.join("server.json"), config).unwrap();
+    }
+    let options = ServerOptions {
+        admins: vec!["root".to_string()],
+        accounts_file: dir.join("accounts.json"),
+        audit_log: dir.join("audit.log"),
+        config_file: dir.join("server.json"),
+        snapshot_file: dir.join("snapshot.json"),
+        ..Default::default()
+    };
+    let server = ChatServer::new(options).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server.run_until(listener, std::future::pending()));
+    addr
+}
+
+#[tokio::test]
+async fn join_moves_the_client_and_tells_the_room() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    alice.send("/join lounge").await.unwrap();
+    let change = alice.expect(|m| m.msg_type == MessageType::RoomChange).await.unwrap();
+    assert_eq!(change.room, "lounge");
+    bob.send("/join lounge").await.unwrap();
+    let joined = alice.expect(|m| m.msg_type == MessageType::UserJoin && m.username == "bob").await.unwrap();
+    assert_eq!(joined.room, "lounge");
+}
+
+#[tokio::test]
+async fn broadcasts_reach_only_the_senders_room() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    carol.send("/join elsewhere").await.unwrap();
+    carol.expect(|m| m.msg_type == MessageType::RoomChange).await.unwrap();
+
+    alice.send("hello general").await.unwrap();
+    let msg = bob.expect(chat("hello general")).await.unwrap();
+    assert_eq!(msg.username, "alice");
+    assert_eq!(msg.room, "general");
+    alice.expect(chat("hello general")).await.unwrap();
+    carol.expect_none(chat("hello general"), QUIET).await.unwrap();
+}
+
+#[tokio::test]
+async fn private_messages_reach_only_the_recipient() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+
+    alice.send("/msg bob just between us").await.unwrap();
+    let pm = bob.expect(|m| m.msg_type == MessageType::PrivateMessage).await.unwrap();
+    assert_eq!(pm.username, "alice");
+    assert_eq!(pm.recipient.as_deref(), Some("bob"));
+    assert_eq!(pm.content, "just between us");
+    carol.expect_none(|m| m.content == "just between us", QUIET).await.unwrap();
+}
+
+#[tokio::test]
+async fn admins_can_kick_and_others_cannot() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+
+    alice.send("/kick bob").await.unwrap();
+    alice.expect(|m| m.msg_type == MessageType::Error).await.unwrap();
+    bob.expect_none(|m| m.msg_type == MessageType::Error, QUIET).await.unwrap();
+
+    root.send("/kick bob").await.unwrap();
+    bob.expect(|m| m.msg_type == MessageType::Error).await.unwrap();
+    bob.expect_closed().await.unwrap();
+    let left = alice.expect(|m| m.msg_type == MessageType::UserLeave).await.unwrap();
+    assert_eq!(left.username, "bob");
+}
+
+// Closing its sending side ends the whole connection: the server stops writing and signs the user out
+#[tokio::test]
+async fn hanging_up_ends_the_connection_both_ways() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"carol\r\n").await.unwrap();
+    let mut first = String::new();
+    reader.read_line(&mut first).await.unwrap();
+    alice.expect(|m| m.msg_type == MessageType::UserJoin && m.username == "carol").await.unwrap();
+
+    writer.shutdown().await.unwrap();
+    let mut rest = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), reader.read_to_end(&mut rest)).await.expect("the server kept the connection open").unwrap();
+    let left = alice.expect(|m| m.msg_type == MessageType::UserLeave).await.unwrap();
+    assert_eq!(left.username, "carol");
+    TestClient::connect(addr, "carol").await.expect("carol was still signed in");
+}
+
+// Floods "slow", who logs in and then never reads, with private messages from bob until the
+// server must have given up on fitting them in. Returns what bob saw by the time all were handled,
+// and slow's connection.
+async fn flood_a_client_that_never_reads(addr: SocketAddr) -> (Vec<ChatMessage>, TcpStream) {
+    let mut slow = BufReader::new(TcpStream::connect(addr).await.unwrap());
+    handshake_reply(&mut slow, &Handshake { username: "slow".to_string(), ..Default::default() }).await;
+    let (reader, mut writer) = TcpStream::connect(addr).await.unwrap().into_split();
+    writer.write_all(format!("{}\n", serde_json::to_string(&Handshake { username: "bob".to_string(), ..Default::default() }).unwrap()).as_bytes()).await.unwrap();
+    // Bob keeps reading, so only slow falls behind
+    let seen = tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        let mut seen = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let msg = ChatMessage::from_json(&line).unwrap();
+            let done = msg.msg_type == MessageType::Chat && msg.content == "done";
+            seen.push(msg);
+            if done {
+                return seen;
+            }
+        }
+        panic!("bob was disconnected");
+    });
+    // Far more than the queue and the socket buffers hold together
+    let text = "x".repeat(3900);
+    for _ in 0..4000 {
+        writer.write_all(format!("/msg slow {}\n", text).as_bytes()).await.unwrap();
+    }
+    writer.write_all(b"done\n").await.unwrap();
+    let seen = tokio::time::timeout(Duration::from_secs(30), seen).await.expect("bob's messages were not all handled").unwrap();
+    (seen, slow.into_inner())
+}
+
+#[tokio::test]
+async fn clients_that_stop_reading_are_disconnected() {
+    let addr = start_server().await;
+    let (_, mut slow) = flood_a_client_that_never_reads(addr).await;
+    // What was already written can still be read, and then the connection ends
+    let mut rest = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), slow.read_to_end(&mut rest)).await.expect("the server kept the connection open").unwrap();
+    TestClient::connect(addr, "slow").await.expect("slow was still signed in");
+}
+
+#[tokio::test]
+async fn clients_that_stop_reading_can_lose_messages_instead() {
+    let addr = start_server_with_config(Some(r#"{"slow_clients": "drop"}"#)).await;
+    let (seen, slow) = flood_a_client_that_never_reads(addr).await;
+    assert!(!seen.iter().any(|m| m.msg_type == MessageType::UserLeave && m.username == "slow"));
+    // Still connected, and some of the messages never came
+    let (reader, mut writer) = slow.into_split();
+    writer.write_all(b"still here\n").await.unwrap();
+    let mut lines = BufReader::new(reader).lines();
+    let mut received = 0;
+    loop {
+        let line = tokio::time::timeout(Duration::from_secs(10), lines.next_line()).await.unwrap().unwrap().expect("slow was disconnected");
+        let msg = ChatMessage::from_json(&line).unwrap();
+        match msg.msg_type {
+            MessageType::PrivateMessage => received += 1,
+            MessageType::Chat if msg.content == "still here" => break,
+            _ => {}
+        }
+    }
+    assert!(received > 0 && received < 4000, "{} of 4000 private messages arrived", received);
+}
+
+#[tokio::test]
+async fn oversized_and_malformed_lines_are_refused_without_disconnecting() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+
+    alice.send(&"x".repeat(100 * 1024)).await.unwrap();
+    alice.expect(|m| m.msg_type == MessageType::Error && m.content.contains("bytes")).await.unwrap();
+    alice.send(&"y".repeat(common::MAX_CONTENT_LEN + 1)).await.unwrap();
+    alice.expect(|m| m.msg_type == MessageType::Error && m.content.contains("characters")).await.unwrap();
+    alice.send_raw(b"\xff\xfe\n").await.unwrap();
+    alice.expect(|m| m.msg_type == MessageType::Error && m.content.contains("UTF-8")).await.unwrap();
+    bob.expect_none(|m| m.msg_type == MessageType::Chat, QUIET).await.unwrap();
+
+    alice.send("still here").await.unwrap();
+    bob.expect(chat("still here")).await.unwrap();
+}
+
+#[tokio::test]
+async fn console_shows_admins_who_is_connected() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+
+    alice.send("/console").await.unwrap();
+    alice.expect(|msg| msg.content == "Only admins can do that").await.unwrap();
+    alice.send("hello").await.unwrap();
+    root.expect(|msg| msg.content == "hello").await.unwrap();
+    root.send("/reserve staff").await.unwrap();
+    root.expect(|msg| msg.content.ends_with("is reserved for registered accounts")).await.unwrap();
+
+    root.send("/console").await.unwrap();
+    let reply = root.expect(|msg| msg.msg_type == MessageType::Console).await.unwrap();
+    let console: ConsoleState = serde_json::from_str(&reply.content).unwrap();
+    let names: Vec<&str> = console.connections.iter().map(|c| c.username.as_str()).collect();
+    assert_eq!(names, ["root", "alice"]); // Longest connected first
+    assert!(console.connections[0].admin && !console.connections[1].admin);
+    assert!(console.connections[1].bytes_in > 0);
+    assert_eq!((console.rooms[0].room.as_str(), console.rooms[0].users, console.rooms[0].messages_per_minute), ("general", 2, 1));
+    assert!(console.actions.iter().any(|action| action.contains("root reserve staff")), "{:?}", console.actions);
+}
+
+#[tokio::test]
+async fn activity_counts_messages_per_hour() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    for text in ["one", "two", "three"] {
+        alice.send(text).await.unwrap();
+        bob.expect(chat(text)).await.unwrap();
+    }
+    bob.send("/join eng").await.unwrap();
+    bob.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "eng").await.unwrap();
+
+    bob.send("/activity #general").await.unwrap();
+    let reply = bob.expect(|msg| msg.msg_type == MessageType::Activity).await.unwrap();
+    let report: ActivityReport = serde_json::from_str(&reply.content).unwrap();
+    assert_eq!((report.room.as_str(), report.last_day.len(), report.by_hour.len()), ("general", 24, 24));
+    // Both sums, in case the messages straddle an hour
+    assert_eq!(report.last_day.iter().sum::<u64>(), 3);
+    assert_eq!(report.by_hour.iter().sum::<u64>(), 3);
+
+    bob.send("/activity").await.unwrap();
+    let reply = bob.expect(|msg| msg.msg_type == MessageType::Activity).await.unwrap();
+    let report: ActivityReport = serde_json::from_str(&reply.content).unwrap();
+    assert_eq!((report.room.as_str(), report.last_day.iter().sum::<u64>(), report.busiest_hour()), ("eng", 0, None));
+}
+
+#[tokio::test]
+async fn karma_counts_thanks_in_rooms_that_turn_it_on() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    alice.send("/leaderboard").await.unwrap();
+    alice.expect(|msg| msg.content == "Karma is off in #general").await.unwrap();
+    root.send("/roomset karma on").await.unwrap();
+    bob.expect(|msg| msg.content == "Karma in #general: on").await.unwrap();
+
+    // Only people in the room count, so c++ is just a language
+    alice.send("thanks bob++, and c++ c++").await.unwrap();
+    let update = bob.expect(|msg| msg.msg_type == MessageType::Karma).await.unwrap();
+    let update: KarmaScores = serde_json::from_str(&update.content).unwrap();
+    assert_eq!((update.scores.into_iter().collect::<Vec<_>>(), update.replace), (vec![("bob".to_string(), 1)], false));
+    bob.expect(|msg| msg.content == "alice thanked bob (bob now has 1 karma)").await.unwrap();
+    alice.send("/thank bob").await.unwrap();
+    alice.expect(|msg| msg.content == "You thanked bob less than a minute ago").await.unwrap();
+    bob.send("/thank bob").await.unwrap();
+    bob.expect(|msg| msg.content == "You can't thank yourself").await.unwrap();
+    bob.send("/thank carol").await.unwrap();
+    bob.expect(|msg| msg.content == "carol is not in #general").await.unwrap();
+    bob.send("/thank alice").await.unwrap();
+    bob.expect(|msg| msg.content == "bob thanked alice (alice now has 1 karma)").await.unwrap();
+    root.send("/thank bob").await.unwrap();
+    root.expect(|msg| msg.content == "root thanked bob (bob now has 2 karma)").await.unwrap();
+    alice.send("/leaderboard").await.unwrap();
+    alice.expect(|msg| msg.content == "Top in #general: 1. bob (2), 2. alice (1)").await.unwrap();
+
+    // Scores come with the room, for newcomers' badges
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    let scores = carol.expect(|msg| msg.msg_type == MessageType::Karma).await.unwrap();
+    let scores: KarmaScores = serde_json::from_str(&scores.content).unwrap();
+    assert_eq!((scores.scores.len(), scores.replace), (2, true));
+}
+
+#[tokio::test]
+async fn trivia_asks_scores_and_ends() {
+    let config = r#"{ "trivia": { "questions": [{ "question": "What is 6 x 7?", "answer": "Forty-two" }, { "question": "Capital of France?", "answer": "Paris" }], "rounds": 2, "seconds": 1 } }"#;
+    let addr = start_server_with_config(Some(config)).await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    bob.send("/trivia scores").await.unwrap();
+    bob.expect(|msg| msg.content == "No game is being played in #general").await.unwrap();
+    alice.send("/trivia start").await.unwrap();
+    bob.expect(|msg| msg.content == "Trivia! 2 questions, 1 seconds each. Answer in the chat").await.unwrap();
+    let question = bob.expect(|msg| msg.content.starts_with("Question 1/2: ")).await.unwrap();
+    bob.send("/trivia start").await.unwrap();
+    bob.expect(|msg| msg.content == "A game is already being played in #general").await.unwrap();
+
+    // Answers are chat messages everyone sees, matched loosely
+    let (answer, shown) = if question.content.contains("6 x 7") { ("forty two!", "Forty-two") } else { ("PARIS", "Paris") };
+    bob.send(answer).await.unwrap();
+    alice.expect(chat(answer)).await.unwrap();
+    alice.expect(|msg| msg.content == format!("bob got it: {} (bob has 1)", shown)).await.unwrap();
+    alice.expect(|msg| msg.content.starts_with("Question 2/2: ")).await.unwrap();
+    bob.send("/trivia stop").await.unwrap();
+    bob.expect(|msg| msg.content == "Only alice, who started the game, or a moderator can stop it").await.unwrap();
+    // Nobody answers the second in time
+    alice.expect(|msg| msg.content.starts_with("Time's up! The answer was ")).await.unwrap();
+    alice.expect(|msg| msg.content == "Trivia over: bob 1").await.unwrap();
+    alice.send("/trivia scores").await.unwrap();
+    alice.expect(|msg| msg.content == "No game is being played in #general").await.unwrap();
+}
+
+#[tokio::test]
+async fn dice_rolls_are_decided_by_the_server_for_the_room() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    alice.send("/roll 3d1+2").await.unwrap();
+    let roll = bob.expect(|msg| msg.msg_type == MessageType::Dice).await.unwrap();
+    assert_eq!((roll.username.as_str(), roll.content.as_str()), ("alice", "rolled 3d1+2: 1 + 1 + 1 + 2 = 5"));
+    alice.send("/roll 2d20 - 1d1 - 4").await.unwrap();
+    let roll = bob.expect(|msg| msg.msg_type == MessageType::Dice).await.unwrap();
+    let total: i64 = roll.content.rsplit(' ').next().unwrap().parse().unwrap();
+    assert!((-3..=35).contains(&total), "{}", roll.content);
+    alice.send("/roll 2d").await.unwrap();
+    let usage = alice.expect(|msg| msg.content == "Usage: /roll <dice>, e.g. /roll 2d20+3").await.unwrap();
+    assert_eq!(usage.reason, Some(ErrorReason::Usage(CommandError::new("/roll", "<dice>").with_hint("/roll 2d20+3"))));
+
+    bob.send("/flip").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::Dice && msg.content.starts_with("flipped a coin: ")).await.unwrap();
+    bob.send("/choose pizza | pizza ").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::Dice && msg.content == "asked for a choice: pizza (from pizza, pizza)").await.unwrap();
+    bob.send("/choose pizza").await.unwrap();
+    bob.expect(|msg| msg.content == "Usage: /choose <option>|<option>[|...]").await.unwrap();
+
+    // Kept in history like chat, so whoever joins later sees the same result
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    carol.expect(|msg| msg.content == "rolled 3d1+2: 1 + 1 + 1 + 2 = 5").await.unwrap();
+}
+
+#[tokio::test]
+async fn times_are_shown_in_everyones_timezone() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    alice.send("/profile set timezone UTC+2").await.unwrap();
+    alice.expect(|msg| msg.content == "Profile timezone set to UTC+2").await.unwrap();
+    bob.send("/profile set timezone -04:00").await.unwrap();
+    bob.expect(|msg| msg.content == "Profile timezone set to -04:00").await.unwrap();
+
+    // In the sender's timezone unless the time says otherwise
+    alice.send("/time 2030-01-07 3pm").await.unwrap();
+    carol.expect(|msg| msg.content == "alice means Mon 2030-01-07 13:00 UTC: alice Mon 15:00, bob Mon 09:00 (1 more with no timezone in their profile)").await.unwrap();
+    carol.send("/time 2030-01-07 23:30 UTC").await.unwrap();
+    bob.expect(|msg| msg.content == "carol means Mon 2030-01-07 23:30 UTC: alice Tue 01:30, bob Mon 19:30 (1 more with no timezone in their profile)").await.unwrap();
+    carol.send("/time 15:00").await.unwrap();
+    carol.expect(|msg| msg.content == "Add a timezone such as UTC+2 to the time, or set yours with /profile set timezone UTC+2").await.unwrap();
+    alice.send("/time whenever").await.unwrap();
+    alice.expect(|msg| msg.content.starts_with("Usage: /time <when>")).await.unwrap();
+}
+
+#[tokio::test]
+async fn new_users_are_walked_through_onboarding_once() {
+    let addr = start_server_with_config(Some(r#"{"onboarding": {"rooms": ["lounge", "general"]}}"#)).await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let prompt = |msg: &ChatMessage| serde_json::from_str::<Prompt>(&msg.content).unwrap();
+
+    let first = prompt(&alice.expect(|msg| msg.msg_type == MessageType::Prompt).await.unwrap());
+    assert_eq!((first.id.as_str(), first.step, first.steps), ("display_name", 1, 3));
+    alice.send("/answer room lounge").await.unwrap();
+    alice.expect(|msg| msg.content == "There is no question room to answer").await.unwrap();
+    alice.send("/answer display_name Alice A").await.unwrap();
+    let rooms = prompt(&alice.expect(|msg| msg.msg_type == MessageType::Prompt).await.unwrap());
+    let PromptField::Choice { options, multiple, .. } = rooms.field else { panic!("expected a choice") };
+    assert_eq!(options.iter().map(|option| option.value.as_str()).collect::<Vec<_>>(), ["lounge", "general"]);
+    assert!(multiple);
+
+    // A wrong answer asks again
+    alice.send("/answer room elsewhere").await.unwrap();
+    alice.expect(|msg| msg.content == "elsewhere isn't one of the choices").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::Prompt && prompt(msg).id == "room").await.unwrap();
+    alice.send("/answer room lounge general").await.unwrap();
+    let auto_join = alice.expect(|msg| msg.msg_type == MessageType::AutoJoin).await.unwrap();
+    assert_eq!(auto_join.content, r#"["lounge","general"]"#);
+    alice.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "lounge").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::Prompt && prompt(msg).id == "notifications").await.unwrap();
+    alice.send("/answer notifications").await.unwrap();
+    alice.expect(|msg| msg.content == "You're all set. /help lists everything you can do").await.unwrap();
+    alice.send("/profile").await.unwrap();
+    let profile = alice.expect(|msg| msg.msg_type == MessageType::Profile).await.unwrap();
+    assert!(profile.content.contains("Alice A"));
+
+    alice.send("/quit").await.unwrap();
+    alice.expect_closed().await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::AutoJoin).await.unwrap();
+    alice.expect_none(|msg| msg.msg_type == MessageType::Prompt, QUIET).await.unwrap();
+    alice.send("/autojoin").await.unwrap();
+    alice.expect(|msg| msg.content == "You start with #lounge, #general").await.unwrap();
+}
+
+#[tokio::test]
+async fn users_start_in_their_auto_join_rooms() {
+    let addr = start_server_with_config(Some(r#"{"auto_join": ["lobby", "general"], "onboarding": {"enabled": false}}"#)).await;
+    let mut admin = TestClient::connect(addr, "admin").await.unwrap();
+    admin.send("/join lobby").await.unwrap();
+    admin.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "lobby").await.unwrap();
+
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let auto_join = alice.expect(|msg| msg.msg_type == MessageType::AutoJoin).await.unwrap();
+    assert_eq!(auto_join.content, r#"["lobby","general"]"#);
+    alice.send("/autojoin").await.unwrap();
+    let list = alice.expect(|msg| msg.content == "You start with #lobby, #general").await.unwrap();
+    assert_eq!(list.room, "lobby");
+    alice.send("/autojoin remove lobby").await.unwrap();
+    alice.expect(|msg| msg.content == "#lobby removed from the rooms you start with").await.unwrap();
+    alice.send("/autojoin remove lobby").await.unwrap();
+    alice.expect(|msg| msg.content == "#lobby isn't on your list").await.unwrap();
+    alice.send("/autojoin add games").await.unwrap();
+    alice.expect(|msg| msg.content == "#games added to the rooms you start with").await.unwrap();
+    alice.send("/autojoin add games").await.unwrap();
+    alice.expect(|msg| msg.content == "#games is on your list already, or it has 10 rooms").await.unwrap();
+    alice.send("/quit").await.unwrap();
+    alice.expect_closed().await.unwrap();
+
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let auto_join = alice.expect(|msg| msg.msg_type == MessageType::AutoJoin).await.unwrap();
+    assert_eq!(auto_join.content, r#"["general","games"]"#);
+    alice.send("/autojoin clear").await.unwrap();
+    alice.expect(|msg| msg.content == "You'll start in #general").await.unwrap();
+    alice.send("/autojoin").await.unwrap();
+    alice.expect(|msg| msg.content == "You start in #general. Add rooms with /autojoin add").await.unwrap();
+}
+
+#[tokio::test]
+async fn open_rooms_can_be_discovered_by_tag_and_description() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    alice.send("/join lounge").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::RoomChange).await.unwrap();
+    alice.send("/roomset tags #Music, chill").await.unwrap();
+    alice.expect(|msg| msg.content == "#lounge is now tagged #chill #music").await.unwrap();
+    alice.send("/roomset description Records, mixes and whatever is playing").await.unwrap();
+    alice.expect(|msg| msg.content == "#lounge is now described as: Records, mixes and whatever is playing").await.unwrap();
+    alice.send("/roomset tags not/a/tag").await.unwrap();
+    alice.expect(|msg| msg.content.starts_with("Rooms can have up to 5 tags")).await.unwrap();
+    alice.send("spinning something new").await.unwrap();
+    alice.expect(chat("spinning something new")).await.unwrap();
+    alice.send("/join hideout").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "hideout").await.unwrap();
+    alice.send("/roomset tags music").await.unwrap();
+    alice.expect(|msg| msg.content == "#hideout is now tagged #music").await.unwrap();
+    alice.send("/invitecode create 1h").await.unwrap();
+    alice.expect(|msg| msg.content.starts_with("Invite code")).await.unwrap();
+
+    // Only owners describe their rooms, and invite-only rooms stay out of the results
+    bob.send("/join lounge").await.unwrap();
+    bob.expect(|msg| msg.msg_type == MessageType::RoomChange).await.unwrap();
+    bob.send("/roomset tags hijacked").await.unwrap();
+    bob.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+    bob.send("/discover music").await.unwrap();
+    let reply = bob.expect(|msg| msg.msg_type == MessageType::Discover).await.unwrap();
+    let discovery: Discovery = serde_json::from_str(&reply.content).unwrap();
+    assert_eq!(discovery.rooms.len(), 1);
+    let lounge = &discovery.rooms[0];
+    assert_eq!((lounge.name.as_str(), lounge.users, lounge.messages_today), ("lounge", 1, 1));
+    assert_eq!(lounge.tags, ["chill", "music"]);
+    assert!(lounge.last_active.is_some());
+    bob.send("/discover WHATEVER playing").await.unwrap();
+    let reply = bob.expect(|msg| msg.msg_type == MessageType::Discover).await.unwrap();
+    assert_eq!(serde_json::from_str::<Discovery>(&reply.content).unwrap().rooms.len(), 1);
+    bob.send("/discover jazz").await.unwrap();
+    let reply = bob.expect(|msg| msg.msg_type == MessageType::Discover).await.unwrap();
+    assert!(serde_json::from_str::<Discovery>(&reply.content).unwrap().rooms.is_empty());
+}
+
+#[tokio::test]
+async fn near_miss_names_are_suggested() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bobby = TestClient::connect(addr, "bobby").await.unwrap();
+    let _bobcat = TestClient::connect(addr, "bobcat").await.unwrap();
+    let _carol = TestClient::connect(addr, "carol").await.unwrap();
+
+    // A start only one name has is enough for a PM
+    alice.send("/msg BOBB are you there?").await.unwrap();
+    let pm = bobby.expect(|msg| msg.msg_type == MessageType::PrivateMessage).await.unwrap();
+    assert_eq!(pm.content, "are you there?");
+    alice.send("/msg bob hi").await.unwrap();
+    let refused = alice.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+    assert_eq!(refused.content, "User 'bob' is not online. Did you mean bobby, bobcat?");
+    let Some(ErrorReason::Usage(usage)) = refused.reason else { panic!("expected a correction") };
+    assert_eq!(usage.hint.as_deref(), Some("/msg bobby hi"));
+    alice.send("/msg crol hey").await.unwrap();
+    alice.expect(|msg| msg.content == "User 'crol' is not online. Did you mean carol?").await.unwrap();
+    alice.send("/msg zed hey").await.unwrap();
+    alice.expect(|msg| msg.content == "User 'zed' is not online").await.unwrap();
+
+    // Kicks only ever go to the exact name
+    root.send("/kick bobb").await.unwrap();
+    let refused = root.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+    assert_eq!(refused.content, "User 'bobb' is not online. Did you mean bobby?");
+    let Some(ErrorReason::Usage(usage)) = refused.reason else { panic!("expected a correction") };
+    assert_eq!(usage.hint.as_deref(), Some("/kick bobby"));
+    bobby.expect_none(|msg| msg.msg_type == MessageType::Error, QUIET).await.unwrap();
+}
+
+#[tokio::test]
+async fn refused_logins_say_why() {
+    let addr = start_server().await;
+    let _alice = TestClient::connect(addr, "alice").await.unwrap();
+    match TestClient::connect(addr, "bad name").await {
+        Err(test_client::TestClientError::Rejected(_)) => {}
+        other => panic!("expected a refusal, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn reserved_and_lookalike_names_are_refused() {
+    let addr = start_server().await;
+    let login = Handshake { username: "alice".to_string(), password: Some("secret".to_string()), register: true, ..Default::default() };
+    let mut alice = TestClient::connect_with(addr, login).await.unwrap();
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    root.send("/reserve alice").await.unwrap();
+    root.expect(|msg| msg.content == "alice is reserved for registered accounts").await.unwrap();
+    root.send("/reserve staff*").await.unwrap();
+    root.expect(|msg| msg.content == "staff* is reserved for registered accounts").await.unwrap();
+    alice.send("/reserve bob").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+
+    for name in ["Staffer", "ѕtaff", "аlice", "rооt", "ALICE"] {
+        match TestClient::connect(addr, name).await {
+            Err(test_client::TestClientError::Rejected(_)) => {}
+            other => panic!("{} got in: {:?}", name, other.err()),
+        }
+    }
+    root.expect(|msg| msg.content == "Refused rооt at login: it looks like root").await.unwrap();
+    let _staffing = TestClient::connect(addr, "stuff").await.unwrap();
+}
+
+// Sends a handshake and returns the reply, after any error explaining it
+async fn handshake_reply(stream: &mut BufReader<TcpStream>, handshake: &Handshake) -> (Option<String>, ChatMessage) {
+    stream.get_mut().write_all(format!("{}\n", serde_json::to_string(handshake).unwrap()).as_bytes()).await.unwrap();
+    let mut error = None;
+    loop {
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        let msg = ChatMessage::from_json(&line).unwrap();
+        if msg.msg_type != MessageType::Error {
+            return (error, msg);
+        }
+        error = Some(msg.content);
+    }
+}
+
+#[tokio::test]
+async fn new_users_answer_a_captcha_first() {
+    let addr = start_server_with_config(Some(r#"{"captcha": "arithmetic"}"#)).await;
+    let mut stream = BufReader::new(TcpStream::connect(addr).await.unwrap());
+    let mut handshake = Handshake { username: "alice".to_string(), ..Default::default() };
+    let (_, prompt) = handshake_reply(&mut stream, &handshake).await;
+    assert_eq!(prompt.msg_type, MessageType::AuthRequired);
+    assert!(prompt.content.starts_with("captcha: What is "), "{}", prompt.content);
+
+    handshake.captcha = Some("nonsense".to_string());
+    let (error, prompt) = handshake_reply(&mut stream, &handshake).await;
+    assert_eq!(error.as_deref(), Some("That answer is wrong"));
+    let question = prompt.content.strip_prefix("captcha: What is ").and_then(|q| q.strip_suffix('?')).unwrap();
+    let sum: u32 = question.split(" + ").map(|n| n.parse::<u32>().unwrap()).sum();
+    handshake.captcha = Some(sum.to_string());
+    let (_, welcome) = handshake_reply(&mut stream, &handshake).await;
+    assert_ne!(welcome.msg_type, MessageType::AuthRequired, "{}", welcome.content);
+
+    match TestClient::connect(addr, "bob").await {
+        Err(test_client::TestClientError::Rejected(reason)) => assert!(reason.starts_with("the server wants a captcha: "), "{}", reason),
+        other => panic!("expected a captcha, got {:?}", other.err()),
+    }
+    let mut raw = TcpStream::connect(addr).await.unwrap();
+    raw.write_all(b"carol\r\n").await.unwrap();
+    let mut refusal = String::new();
+    raw.read_to_string(&mut refusal).await.unwrap();
+    assert!(refusal.starts_with("Error: This server asks new users a question first"), "{}", refusal);
+}
+
+#[tokio::test]
+async fn command_arguments_are_checked_before_use() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+
+    alice.send("/join a//b").await.unwrap();
+    alice.expect(|m| m.msg_type == MessageType::Error && m.content.contains("a//b")).await.unwrap();
+    alice.send("/star not-an-id").await.unwrap();
+    alice.expect(|m| m.msg_type == MessageType::Error && m.content == "Not a message ID: not-an-id").await.unwrap();
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    root.send("/mirror general bad//room").await.unwrap();
+    root.expect(|m| m.msg_type == MessageType::Error && m.content.contains("bad//room")).await.unwrap();
+    alice.expect_none(|m| m.msg_type == MessageType::RoomChange, QUIET).await.unwrap();
+}
+
+// Posted in an invite-only room by alice, where bob may not go
+async fn secret_from_invite_only_room(alice: &mut TestClient) -> ChatMessage {
+    alice.send("/join hideout").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "hideout").await.unwrap();
+    alice.send("/invitecode create 1h").await.unwrap();
+    alice.expect(|msg| msg.content.starts_with("Invite code")).await.unwrap();
+    alice.send("the secret plans").await.unwrap();
+    alice.expect(chat("the secret plans")).await.unwrap()
+}
+
+#[tokio::test]
+async fn messages_from_rooms_out_of_reach_cannot_be_forwarded() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    let secret = secret_from_invite_only_room(&mut alice).await;
+
+    for target in ["general", "@carol"] {
+        bob.send(&format!("/forward {} {}", secret.id, target)).await.unwrap();
+        bob.expect(|msg| msg.msg_type == MessageType::Error && msg.content == "That message is no longer available").await.unwrap();
+    }
+    carol.expect_none(|msg| msg.content.contains("secret plans"), QUIET).await.unwrap();
+    // Those who can read it still can
+    alice.send(&format!("/forward {} @carol", secret.id)).await.unwrap();
+    carol.expect(|msg| msg.msg_type == MessageType::PrivateMessage && msg.content == "the secret plans").await.unwrap();
+}
+
+#[tokio::test]
+async fn messages_from_rooms_out_of_reach_cannot_be_starred() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let secret = secret_from_invite_only_room(&mut alice).await;
+
+    bob.send(&format!("/star {}", secret.id)).await.unwrap();
+    bob.expect(|msg| msg.msg_type == MessageType::Error && msg.content == "That message is no longer available").await.unwrap();
+    bob.send("/starred").await.unwrap();
+    let starred = bob.expect(|msg| msg.msg_type == MessageType::Starred).await.unwrap();
+    assert_eq!(starred.content, "[]");
+    alice.send(&format!("/star {}", secret.id)).await.unwrap();
+    alice.send("/starred").await.unwrap();
+    let starred = alice.expect(|msg| msg.msg_type == MessageType::Starred).await.unwrap();
+    assert!(starred.content.contains("the secret plans"), "{}", starred.content);
+}
+
+#[tokio::test]
+async fn messages_from_rooms_out_of_reach_cannot_be_reported() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let secret = secret_from_invite_only_room(&mut alice).await;
+
+    bob.send(&format!("/report {} spam", secret.id)).await.unwrap();
+    bob.expect(|msg| msg.msg_type == MessageType::Error && msg.content == "That message is no longer available").await.unwrap();
+    root.send("/modpanel").await.unwrap();
+    let panel = root.expect(|msg| msg.msg_type == MessageType::Moderation).await.unwrap();
+    assert!(serde_json::from_str::<ModerationState>(&panel.content).unwrap().reports.is_empty());
+}
+
+#[tokio::test]
+async fn malformed_handshakes_are_explained() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"{\"username\": 42}\n").await.unwrap();
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).await.unwrap();
+    assert!(reply.starts_with("Error: Malformed handshake: invalid type"), "{}", reply);
+}
+
+#[tokio::test]
+async fn compressed_connections_get_deflated_frames() {
+    let addr = start_server().await;
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let handshake = Handshake { username: "alice".to_string(), compression: vec!["deflate".to_string()], ..Default::default() };
+    writer.write_all(format!("{}\n", serde_json::to_string(&handshake).unwrap()).as_bytes()).await.unwrap();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    reader.read_line(&mut line).await.unwrap();
+    assert_eq!(ChatMessage::from_json(&line).unwrap().msg_type, MessageType::Compression);
+
+    // Frames carry whole lines and may refer back to earlier frames
+    let mut inflater = Inflater::default();
+    let mut text = String::new();
+    writer.write_all(b"hello from alice\n").await.unwrap();
+    while !text.contains("hello from alice") {
+        let mut header = [0; deflate::FRAME_HEADER];
+        reader.read_exact(&mut header).await.unwrap();
+        let mut frame = vec![0; deflate::frame_len(header).unwrap()];
+        reader.read_exact(&mut frame).await.unwrap();
+        text.push_str(std::str::from_utf8(&inflater.inflate(&frame).unwrap()).unwrap());
+    }
+    let messages: Vec<ChatMessage> = text.lines().map(|line| ChatMessage::from_json(line).unwrap()).collect();
+    assert_eq!(messages[0].msg_type, MessageType::RoomChange);
+    bob.expect(chat("hello from alice")).await.unwrap();
+}
+
+#[tokio::test]
+async fn raw_connections_read_and_write_plain_lines() {
+    let addr = start_server().await;
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"carol\r\n").await.unwrap();
+    let mut first = String::new();
+    reader.read_line(&mut first).await.unwrap();
+    assert!(first.starts_with('[') && first.ends_with("] * Joined #general\r\n"), "{:?}", first);
+    let mut lines = reader.lines();
+
+    writer.write_all(b"hi from telnet\r\n").await.unwrap();
+    bob.expect(chat("hi from telnet")).await.unwrap();
+    bob.send("hi carol").await.unwrap();
+    writer.write_all(b"/help\r\n").await.unwrap();
+    let mut seen = Vec::new();
+    while !(seen.iter().any(|line: &String| line.contains("Commands: /join <room>")) && seen.iter().any(|line| line.ends_with("] bob: hi carol"))) {
+        seen.push(lines.next_line().await.unwrap().unwrap());
+    }
+    assert!(seen.iter().all(|line| !line.starts_with('{')), "{:?}", seen);
+}
+
+// Raw connections are bare terminals: escape sequences in others' text must not reach them
+#[tokio::test]
+async fn raw_connections_never_receive_control_characters() {
+    let addr = start_server().await;
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    writer.write_all(b"carol\r\n").await.unwrap();
+    let mut lines = BufReader::new(reader).lines();
+    lines.next_line().await.unwrap().unwrap();
+
+    bob.send("/profile set bio \u{1b}]0;owned\u{7}").await.unwrap();
+    bob.expect(|msg| msg.template.as_ref().is_some_and(|t| t.key == "sys.profile_set")).await.unwrap();
+    let said = "\u{1b}[2Jhi\u{9b}1A\tthere\u{8}\u{8}\r\u{2028}second line";
+    bob.send(said).await.unwrap();
+    bob.expect(chat(said)).await.unwrap();
+    writer.write_all(b"/profile bob\r\n").await.unwrap();
+    let mut seen = Vec::new();
+    while !seen.iter().any(|line: &String| line.contains("profile of bob")) {
+        seen.push(lines.next_line().await.unwrap().unwrap());
+    }
+    let said = seen.iter().position(|line| line.contains("] bob: ")).unwrap();
+    assert!(seen[said].ends_with("] bob: \u{fffd}[2Jhi\u{fffd}1A\tthere\u{fffd}\u{fffd}\u{fffd}"), "{:?}", seen[said]);
+    assert_eq!(seen[said + 1], "    second line");
+    assert!(seen.last().unwrap().ends_with("bio: \u{fffd}]0;owned\u{fffd}"), "{:?}", seen);
+    assert!(seen.iter().all(|line| !line.contains(|c: char| c.is_control() && c != '\t')), "{:?}", seen);
+}
+
+#[tokio::test]
+async fn returning_users_hear_what_they_missed() {
+    let addr = start_server().await;
+    let login = |register| Handshake { username: "alice".to_string(), password: Some("secret".to_string()), register, ..Default::default() };
+    let mut alice = TestClient::connect_with(addr, login(true)).await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    bob.send("before").await.unwrap();
+    let read = alice.expect(chat("before")).await.unwrap();
+    alice.send(&format!("/read {}", read.id)).await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::ReadReceipt).await.unwrap();
+    alice.send("/quit").await.unwrap();
+    alice.expect_closed().await.unwrap();
+
+    bob.send("after").await.unwrap();
+    bob.send("alice, are you there?").await.unwrap();
+    bob.expect(chat("alice, are you there?")).await.unwrap();
+    let mut alice = TestClient::connect_with(addr, login(false)).await.unwrap();
+    let digest = alice.expect(|msg| msg.template.as_ref().is_some_and(|t| t.key == "sys.digest")).await.unwrap();
+    assert!(digest.content.starts_with("#general: 2 new since"), "{}", digest.content);
+    assert!(digest.content.ends_with("1 mentioning you"), "{}", digest.content);
+}
+
+#[tokio::test]
+async fn groups_are_mentioned_and_let_into_rooms() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    bob.send("/group create devs").await.unwrap();
+    bob.expect(|msg| msg.content == "Only admins can do that").await.unwrap();
+    for command in ["/group create devs", "/group add devs alice", "/group add devs carol"] {
+        root.send(command).await.unwrap();
+    }
+    root.expect(|msg| msg.content == "carol is in @devs").await.unwrap();
+
+    // Only room owners and admins decide, and the room closes to everyone else
+    root.send("/join eng").await.unwrap();
+    root.expect(|msg| msg.msg_type == MessageType::RoomChange).await.unwrap();
+    root.send("/group allow devs").await.unwrap();
+    root.expect(|msg| msg.content == "Members of @devs may now enter #eng").await.unwrap();
+    bob.send("/join eng").await.unwrap();
+    bob.expect(|msg| msg.msg_type == MessageType::Error && msg.content.contains("#eng")).await.unwrap();
+    alice.send("/join eng").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "eng").await.unwrap();
+
+    bob.send("@devs standup in 5").await.unwrap();
+    let msg = carol.expect(chat("@devs standup in 5")).await.unwrap();
+    assert_eq!(msg.mentions, ["alice", "carol"]);
+    alice.expect(|msg| msg.content == "bob mentioned a group you are in, in #general: @devs standup in 5").await.unwrap();
+}
+
+#[tokio::test]
+async fn room_permissions_decide_who_may_post() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    for client in [&mut alice, &mut bob, &mut carol] {
+        client.send("/join eng").await.unwrap();
+        client.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "eng").await.unwrap();
+    }
+    // alice created the room and owns it
+    bob.send("/roomset post voiced").await.unwrap();
+    bob.expect(|msg| msg.content == "You may not change the settings of #eng").await.unwrap();
+    alice.send("/roomset post voiced").await.unwrap();
+    carol.expect(|msg| msg.content == "Who may post in #eng: voiced").await.unwrap();
+    alice.send("/roomset mod bob").await.unwrap();
+    bob.expect(|msg| msg.content == "bob's level in #eng is now moderators").await.unwrap();
+
+    carol.send("too early").await.unwrap();
+    carol.expect(|msg| msg.content == "You may not post in #eng").await.unwrap();
+    carol.send("/invitecode create 1h").await.unwrap();
+    carol.expect(|msg| msg.content == "You may not manage invitations to #eng").await.unwrap();
+    bob.send("/roomset voice carol").await.unwrap();
+    carol.expect(|msg| msg.content == "carol's level in #eng is now voiced").await.unwrap();
+    carol.send("now I can").await.unwrap();
+    alice.expect(chat("now I can")).await.unwrap();
+
+    bob.send("/roomset").await.unwrap();
+    bob.expect(|msg| msg.content == "#eng: posting is open to voiced, inviting to moderators. Moderators: bob. Voiced: carol. Moderation: on. Karma: off. Broadcast: off. Description: -. Tags: -").await.unwrap();
+    for setting in ["karma on", "broadcast on", "description Where the build gets fixed", "tags ci release"] {
+        alice.send(&format!("/roomset {}", setting)).await.unwrap();
+    }
+    alice.expect(|msg| msg.content == "#eng is now tagged #ci #release").await.unwrap();
+    bob.send("/roomset").await.unwrap();
+    bob.expect(|msg| msg.content.ends_with("Moderation: on. Karma: on. Broadcast: on. Description: Where the build gets fixed. Tags: #ci #release")).await.unwrap();
+}
+
+#[tokio::test]
+async fn broadcast_rooms_are_read_only_for_most() {
+    let addr = start_server_with_config(Some(r#"{"rooms": {"news": {"broadcast": true}}}"#)).await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let auto_join = alice.expect(|msg| msg.msg_type == MessageType::AutoJoin).await.unwrap();
+    assert_eq!(auto_join.content, r#"["news"]"#);
+    let read_only = |msg: &ChatMessage| serde_json::from_str::<Vec<RoomEntry>>(&msg.content).unwrap().iter().find(|room| room.name == "news").unwrap().read_only;
+    for client in [&mut root, &mut alice] {
+        client.send("/join news").await.unwrap();
+        client.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "news").await.unwrap();
+    }
+    assert!(read_only(&alice.expect(|msg| msg.msg_type == MessageType::RoomList).await.unwrap()));
+    alice.send("me too").await.unwrap();
+    alice.expect(|msg| msg.content == "You may not post in #news").await.unwrap();
+    root.send("Maintenance tonight").await.unwrap();
+    alice.expect(chat("Maintenance tonight")).await.unwrap();
+
+    // Voice lets someone post, and their client hears it is no longer read-only
+    root.send("/roomset voice alice").await.unwrap();
+    assert!(!read_only(&alice.expect(|msg| msg.msg_type == MessageType::RoomList).await.unwrap()));
+    alice.send("thanks for the heads-up").await.unwrap();
+    root.expect(chat("thanks for the heads-up")).await.unwrap();
+    root.send("/roomset broadcast off").await.unwrap();
+    root.expect(|msg| msg.content == "Broadcast in #news: off").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::AutoJoin && msg.content == "[]").await.unwrap();
+}
+
+#[tokio::test]
+async fn the_command_registry_follows_the_user() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let names = |msg: ChatMessage| -> Vec<String> { serde_json::from_str::<Vec<CommandInfo>>(&msg.content).unwrap().into_iter().map(|command| command.name).collect() };
+    let admin = names(root.expect(|msg| msg.msg_type == MessageType::Commands).await.unwrap());
+    let user = names(alice.expect(|msg| msg.msg_type == MessageType::Commands).await.unwrap());
+    assert!(admin.iter().any(|name| name == "/kick") && admin.iter().any(|name| name == "/join"));
+    assert!(user.iter().any(|name| name == "/join") && user.iter().any(|name| name == "/msg"));
+    assert!(!user.iter().any(|name| name == "/kick" || name == "/reload"));
+    // Only what a client would type itself
+    assert!(!user.iter().any(|name| name == "/answer" || name == "/ping"));
+
+    // `/help` lists the same commands, for users without a client to complete them
+    alice.send("/help").await.unwrap();
+    let help = alice.expect(|msg| msg.content.starts_with("Commands: ")).await.unwrap();
+    for name in &user {
+        assert!(help.content.contains(name.as_str()), "{} in {}", name, help.content);
+    }
+    assert!(help.content.contains("/forward <message id> <#room|@user>") && help.content.contains("/roll <dice>"), "{}", help.content);
+    assert!(!help.content.contains("/kick"), "{}", help.content);
+    root.send("/help").await.unwrap();
+    let help = root.expect(|msg| msg.content.starts_with("Commands: ")).await.unwrap();
+    assert!(help.content.contains("/kick <user>"), "{}", help.content);
+}
+
+#[tokio::test]
+async fn reports_mutes_and_pins_reach_the_moderation_panel() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let capabilities = root.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap();
+    assert!(serde_json::from_str::<ServerCapabilities>(&capabilities.content).unwrap().moderator);
+    let capabilities = alice.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap();
+    assert!(!serde_json::from_str::<ServerCapabilities>(&capabilities.content).unwrap().moderator);
+
+    bob.send("buy my stuff").await.unwrap();
+    let spam = alice.expect(chat("buy my stuff")).await.unwrap();
+    alice.send(&format!("/report {} spam", spam.id)).await.unwrap();
+    alice.expect(|msg| msg.content == "Reported to the admins").await.unwrap();
+    root.expect(|msg| msg.content == "Flagged for review: bob in #general (reported by alice: spam): buy my stuff").await.unwrap();
+
+    alice.send(&format!("/pin {}", spam.id)).await.unwrap();
+    alice.expect(|msg| msg.content == "Only moderators of #general and admins can pin messages").await.unwrap();
+    root.send(&format!("/pin {}", spam.id)).await.unwrap();
+    alice.expect(|msg| msg.content == "root pinned a message by bob: buy my stuff").await.unwrap();
+    alice.send("/pins").await.unwrap();
+    alice.expect(|msg| msg.content == format!("Pinned in #general (1): {} bob: buy my stuff", spam.id)).await.unwrap();
+
+    root.send("/mute bob 10m").await.unwrap();
+    root.expect(|msg| msg.content == "bob is muted for 10m").await.unwrap();
+    bob.send("more stuff").await.unwrap();
+    bob.expect(|msg| msg.content.starts_with("You are muted")).await.unwrap();
+
+    root.send("/modpanel").await.unwrap();
+    let panel = root.expect(|msg| msg.msg_type == MessageType::Moderation).await.unwrap();
+    let panel: ModerationState = serde_json::from_str(&panel.content).unwrap();
+    let members: Vec<(&str, Option<u64>)> = panel.members.iter().map(|m| (m.username.as_str(), m.muted_secs)).collect();
+    assert_eq!(members, [("alice", None), ("bob", Some(600)), ("root", None)]);
+    assert_eq!((panel.reports[0].message.id, panel.reports[0].reason.as_str()), (spam.id, "reported by alice: spam"));
+    assert_eq!(panel.pins[0].id, spam.id);
+
+    root.send("/unmute bob").await.unwrap();
+    root.expect(|msg| msg.content == "bob may post again").await.unwrap();
+    root.send(&format!("/flagged dismiss {}", spam.id)).await.unwrap();
+    root.expect(|msg| msg.content == "Dismissed 1 flagged entries").await.unwrap();
+    root.send("/flagged").await.unwrap();
+    root.expect(|msg| msg.content == "No flagged messages").await.unwrap();
+    root.send("/ban bob 1d").await.unwrap();
+    alice.expect(|msg| msg.content == "bob is banned for 1d").await.unwrap();
+    bob.expect_closed().await.unwrap();
+}
+
+// Pins and reports are copies, and must go when a room's TTL takes the original
+#[tokio::test]
+async fn expired_messages_leave_pins_and_reports() {
+    let dir = std::env::temp_dir().join(format!("chat-test-{}-expiry", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let history_file = dir.join("history.jsonl");
+    let mut old = ChatMessage::chat("bob".to_string(), "last week's news".to_string(), RoomName::general());
+    old.timestamp = chrono::Utc::now() - chrono::Duration::days(7);
+    old.seq = Some(1);
+    std::fs::write(&history_file, format!("{}\n", old.to_json().unwrap())).unwrap();
+    let options = ServerOptions {
+        admins: vec!["root".to_string()],
+        accounts_file: dir.join("accounts.json"),
+        audit_log: dir.join("audit.log"),
+        config_file: dir.join("server.json"),
+        snapshot_file: dir.join("snapshot.json"),
+        history_file: Some(history_file),
+        ..Default::default()
+    };
+    let server = ChatServer::new(options).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server.run_until(listener, std::future::pending()));
+
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    alice.send(&format!("/report {} stale", old.id)).await.unwrap();
+    alice.expect(|msg| msg.content == "Reported to the admins").await.unwrap();
+    root.send(&format!("/pin {}", old.id)).await.unwrap();
+    alice.expect(|msg| msg.content == "root pinned a message by bob: last week's news").await.unwrap();
+
+    root.send("/ttl 1d").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::Expired && msg.content == old.id.to_string()).await.unwrap();
+    root.send("/modpanel").await.unwrap();
+    let panel = root.expect(|msg| msg.msg_type == MessageType::Moderation).await.unwrap();
+    let panel: ModerationState = serde_json::from_str(&panel.content).unwrap();
+    assert!(panel.reports.is_empty() && panel.pins.is_empty(), "{:?} {:?}", panel.reports, panel.pins);
+    root.send("/flagged").await.unwrap();
+    root.expect(|msg| msg.content == "No flagged messages").await.unwrap();
+}
+
+#[tokio::test]
+async fn room_moderators_get_the_moderation_panel_for_their_room() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    let moderator = |msg: &ChatMessage| serde_json::from_str::<ServerCapabilities>(&msg.content).unwrap().moderator;
+    let offers_panel = |msg: &ChatMessage| serde_json::from_str::<Vec<CommandInfo>>(&msg.content).unwrap().iter().any(|command| command.name == "/modpanel");
+    for client in [&mut alice, &mut bob] {
+        assert!(!moderator(&client.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap()));
+        assert!(!offers_panel(&client.expect(|msg| msg.msg_type == MessageType::Commands).await.unwrap()));
+    }
+    carol.send("spam in general").await.unwrap();
+    let elsewhere = carol.expect(chat("spam in general")).await.unwrap();
+    carol.send(&format!("/report {}", elsewhere.id)).await.unwrap();
+
+    // Owning a room makes alice its moderator
+    alice.send("/join eng").await.unwrap();
+    assert!(moderator(&alice.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap()));
+    assert!(offers_panel(&alice.expect(|msg| msg.msg_type == MessageType::Commands).await.unwrap()));
+    for client in [&mut bob, &mut carol] {
+        client.send("/join eng").await.unwrap();
+        client.expect(|msg| msg.msg_type == MessageType::RoomChange && msg.room == "eng").await.unwrap();
+    }
+    bob.send("/modpanel").await.unwrap();
+    bob.expect(|msg| msg.content == "Only moderators of #eng and admins can open the moderation panel").await.unwrap();
+    alice.send("/roomset mod bob").await.unwrap();
+    assert!(moderator(&bob.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap()));
+    assert!(offers_panel(&bob.expect(|msg| msg.msg_type == MessageType::Commands).await.unwrap()));
+
+    carol.send("spam in eng").await.unwrap();
+    let here = carol.expect(chat("spam in eng")).await.unwrap();
+    carol.send(&format!("/report {}", here.id)).await.unwrap();
+    carol.expect(|msg| msg.content == "Reported to the admins").await.unwrap();
+    bob.send("/modpanel").await.unwrap();
+    let panel = bob.expect(|msg| msg.msg_type == MessageType::Moderation).await.unwrap();
+    let panel: ModerationState = serde_json::from_str(&panel.content).unwrap();
+    assert_eq!(panel.reports.iter().map(|report| report.message.id).collect::<Vec<_>>(), [here.id]);
+
+    // Only in that room, and only while they are a moderator
+    bob.send("/join general").await.unwrap();
+    assert!(!moderator(&bob.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap()));
+    bob.send("/join eng").await.unwrap();
+    assert!(moderator(&bob.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap()));
+    alice.send("/roomset demod bob").await.unwrap();
+    assert!(!moderator(&bob.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap()));
+    carol.send("/join general").await.unwrap();
+    carol.expect_none(|msg| msg.msg_type == MessageType::Capabilities, QUIET).await.unwrap();
+}
+
+#[tokio::test]
+async fn refusals_say_when_to_try_again() {
+    let addr = start_server_with_config(Some(r#"{"rate_limit": 2}"#)).await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    bob.send(&"x".repeat(4001)).await.unwrap();
+    let refused = bob.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+    assert_eq!(refused.reason, Some(ErrorReason::TooLong { limit: 4000 }));
+
+    for text in ["one", "two"] {
+        bob.send(text).await.unwrap();
+        bob.expect(chat(text)).await.unwrap();
+    }
+    bob.send("three").await.unwrap();
+    let refused = bob.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+    assert_eq!(refused.content, "Slow down: at most 2 messages a minute");
+    let Some(ErrorReason::RateLimited { retry_after }) = refused.reason else { panic!("expected a retry-after") };
+    assert!((59..=60).contains(&retry_after));
+
+    root.send("/mute bob 10m").await.unwrap();
+    let muted = bob.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+    let Some(ErrorReason::Muted { until }) = muted.reason else { panic!("expected an end to the mute") };
+    assert!((until - chrono::Utc::now()).num_seconds() > 590);
+}
+
+#[tokio::test]
+async fn warnings_escalate_to_a_mute_then_a_kick() {
+    let addr = start_server().await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    alice.send("/warn bob spam").await.unwrap();
+    alice.expect(|msg| msg.content == "You may not warn bob").await.unwrap();
+
+    // By default the second warning mutes for 10 minutes and the third kicks
+    root.send("/warn bob spam").await.unwrap();
+    bob.expect(|msg| msg.content == "root warned you: spam. You have 1 warnings").await.unwrap();
+    root.send("/warn bob more spam").await.unwrap();
+    root.expect(|msg| msg.content == "bob now has 2 warnings and is muted for 10m").await.unwrap();
+    bob.send("still here").await.unwrap();
+    bob.expect(|msg| msg.content == "You are muted for another 10m").await.unwrap();
+    alice.expect_none(chat("still here"), QUIET).await.unwrap();
+    root.send("/warn bob and again").await.unwrap();
+    bob.expect(|msg| msg.content == "You were disconnected after 3 warnings").await.unwrap();
+    bob.expect_closed().await.unwrap();
+
+    alice.send("/warnings bob").await.unwrap();
+    alice.expect(|msg| msg.content == "Only moderators and admins can see other users' warnings").await.unwrap();
+    root.send("/warnings bob").await.unwrap();
+    let msg = root.expect(|msg| msg.content.starts_with("bob has 3 warnings: ")).await.unwrap();
+    assert!(msg.content.ends_with("root: and again"));
+    root.send("/warnings clear bob").await.unwrap();
+    root.expect(|msg| msg.content == "Warnings, mutes and bans for bob cleared").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    bob.send("back again").await.unwrap();
+    alice.expect(chat("back again")).await.unwrap();
+}
+
+// Answers each POST with whatever `answer` makes of the request
+async fn start_json_service(answer: fn(&str) -> &'static str) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            // Content-Length is all the server sends, so read until the body is complete
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                let Some((head, body)) = text.split_once("\r\n\r\n") else { continue };
+                let length: usize = head.lines().find_map(|line| line.strip_prefix("Content-Length: ")).unwrap().parse().unwrap();
+                if n == 0 || body.len() >= length {
+                    break;
+                }
+            }
+            let body = answer(&String::from_utf8_lossy(&request));
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn moderation_blocks_and_flags_before_posting() {
+    let classifier = start_json_service(|request| if request.contains("buy now") { r#"{"action":"block","reason":"spam"}"# } else { r#"{"action":"allow"}"# }).await;
+    let config = format!(r#"{{"moderation": {{"block": ["darn"], "flag": ["heck"], "url": "http://{}/classify"}}}}"#, classifier);
+    let addr = start_server_with_config(Some(&config)).await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    alice.send("darn it").await.unwrap();
+    alice.expect(|msg| msg.content == "Your message was not posted: contains \"darn\"").await.unwrap();
+    alice.send("buy now, cheap").await.unwrap();
+    alice.expect(|msg| msg.content == "Your message was not posted: spam").await.unwrap();
+    root.expect_none(|msg| msg.msg_type == MessageType::Chat, QUIET).await.unwrap();
+
+    alice.send("oh heck").await.unwrap();
+    alice.expect(chat("oh heck")).await.unwrap(); // Flagged messages are still posted
+    root.expect(|msg| msg.content == "Flagged for review: alice in #general (contains \"heck\"): oh heck").await.unwrap();
+    root.send("/flagged").await.unwrap();
+    root.expect(|msg| msg.content.starts_with("1 flagged messages: ")).await.unwrap();
+
+    // Owners can turn it off for their room
+    root.send("/join scratch").await.unwrap();
+    root.expect(|msg| msg.msg_type == MessageType::RoomChange).await.unwrap();
+    root.send("/roomset moderation off").await.unwrap();
+    root.expect(|msg| msg.content == "Moderation in #scratch: off").await.unwrap();
+    alice.send("/join scratch").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::RoomChange).await.unwrap();
+    alice.send("darn it").await.unwrap();
+    root.expect(chat("darn it")).await.unwrap();
+}
+
+#[tokio::test]
+async fn translations_go_only_to_whoever_asked() {
+    let service = start_json_service(|request| if request.contains(r#""target":"es""#) { r#"{"translatedText":"hola mundo"}"# } else { r#"{"translatedText":"hallo welt"}"# }).await;
+    let config = format!(r#"{{"translate": {{"url": "http://{}/translate", "per_minute": 1}}}}"#, service);
+    let addr = start_server_with_config(Some(&config)).await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    alice.send("hello world").await.unwrap();
+    let original = bob.expect(chat("hello world")).await.unwrap();
+
+    bob.send(&format!("/translate {} es", original.id)).await.unwrap();
+    bob.expect(|msg| msg.content == "Translation (es) of alice: hola mundo").await.unwrap();
+    // From the cache, so it doesn't count against the limit
+    bob.send(&format!("/translate {} es", original.id)).await.unwrap();
+    bob.expect(|msg| msg.content == "Translation (es) of alice: hola mundo").await.unwrap();
+    bob.send(&format!("/translate {} de", original.id)).await.unwrap();
+    bob.expect(|msg| msg.content == "You can ask for 1 translations a minute; try again shortly").await.unwrap();
+    bob.send(&format!("/translate {} spanish!", original.id)).await.unwrap();
+    bob.expect(|msg| msg.content == "Usage: /translate <id> <language, e.g. de>").await.unwrap();
+    alice.expect_none(|msg| msg.content.starts_with("Translation"), QUIET).await.unwrap();
+}
+
+#[tokio::test]
+async fn bad_links_are_blocked_or_come_with_a_warning() {
+    let lookup = start_json_service(|request| if request.contains("phish") { r#"{"matches":[{"url":"http://login.phish.test/","threat":"phishing"}]}"# } else { "{}" }).await;
+    let config = format!(r#"{{"links": {{"block": ["evil.test"], "warn": ["short.test"], "url": "http://{}/lookup"}}}}"#, lookup);
+    let addr = start_server_with_config(Some(&config)).await;
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    alice.send("see https://www.evil.test/prize").await.unwrap();
+    alice.expect(|msg| msg.content == "Your message was not sent: https://www.evil.test/prize is blocklisted").await.unwrap();
+    root.expect_none(|msg| msg.msg_type == MessageType::Chat, QUIET).await.unwrap();
+
+    alice.send("short link http://short.test/x").await.unwrap();
+    let msg = root.expect(chat("short link http://short.test/x")).await.unwrap();
+    assert_eq!(msg.link_warning.unwrap().threat, "suspicious");
+    alice.send("/msg root log in at http://login.phish.test/").await.unwrap();
+    let msg = root.expect(|msg| msg.msg_type == MessageType::PrivateMessage).await.unwrap();
+    assert_eq!(msg.link_warning.unwrap().threat, "phishing");
+
+    root.send("/linkhits").await.unwrap();
+    let msg = root.expect(|msg| msg.content.starts_with("3 screened links: ")).await.unwrap();
+    assert!(msg.content.contains("link_blocked alice to #general: https://www.evil.test/prize (blocklisted)"));
+}
+
+// Serves one small page, chunked as many web servers send them
+async fn start_web_page() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf).await;
+            let page = "<html><head><TITLE>\n  Release notes &amp; plans\n</TITLE></head><body>...</body></html>";
+            let (first, second) = page.split_at(30);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                first.len(),
+                first,
+                second.len(),
+                second
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn links_get_previews_after_posting() {
+    let page = start_web_page().await;
+    // Loopback addresses are only fetched when listed
+    let addr = start_server_with_config(Some(r#"{"previews": {"allow": ["127.0.0.1"]}}"#)).await;
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    alice.send(&format!("plans at http://{}/notes.", page)).await.unwrap();
+    let posted = bob.expect(|msg| msg.msg_type == MessageType::Chat).await.unwrap();
+    assert!(posted.preview.is_none());
+    let msg = bob.expect(|msg| msg.msg_type == MessageType::Preview).await.unwrap();
+    let update: PreviewUpdate = serde_json::from_str(&msg.content).unwrap();
+    assert_eq!(update.message, posted.id);
+    assert_eq!(update.preview.title.as_deref(), Some("Release notes & plans"));
+    assert_eq!(update.preview.content_type.as_deref(), Some("text/html"));
+
+    // Later arrivals get it with the message
+    let mut carol = TestClient::connect(addr, "carol").await.unwrap();
+    let replayed = carol.expect(|msg| msg.id == posted.id).await.unwrap();
+    assert_eq!(replayed.preview, Some(update.preview));
+
+    alice.send(&format!("not this one http://localhost:{}/", page.port())).await.unwrap();
+    bob.expect(chat(&format!("not this one http://localhost:{}/", page.port()))).await.unwrap();
+    bob.expect_none(|msg| msg.msg_type == MessageType::Preview, QUIET).await.unwrap();
+}
+
+#[tokio::test]
+async fn capabilities_follow_the_first_room_change() {
+    let addr = start_server().await;
+    // Connecting waits for the room change, so capabilities are still to come
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    let msg = alice.expect(|msg| msg.msg_type == MessageType::Capabilities).await.unwrap();
+    let capabilities: ServerCapabilities = serde_json::from_str(&msg.content).unwrap();
+    assert!(capabilities.supports(ServerCapabilities::STARS));
+    assert!(!capabilities.supports(ServerCapabilities::GUESTS)); // Off unless configured
+    assert_eq!(capabilities.max_message_len, common::MAX_CONTENT_LEN);
+}
+
+#[tokio::test]
+async fn msgpack_connections_get_framed_messages() {
+    let addr = start_server().await;
+    let mut bob = TestClient::connect(addr, "bob").await.unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let handshake = Handshake { username: "alice".to_string(), encoding: vec!["msgpack".to_string()], ..Default::default() };
+    writer.write_all(format!("{}\n", serde_json::to_string(&handshake).unwrap()).as_bytes()).await.unwrap();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    reader.read_line(&mut line).await.unwrap();
+    assert_eq!(ChatMessage::from_json(&line).unwrap().msg_type, MessageType::Encoding);
+
+    // What the client sends stays JSON lines or plain text
+    writer.write_all(b"hello from alice\n").await.unwrap();
+    let mut messages = Vec::new();
+    while !messages.iter().any(|msg: &ChatMessage| msg.content == "hello from alice") {
+        let mut header = [0; deflate::FRAME_HEADER];
+        reader.read_exact(&mut header).await.unwrap();
+        let mut frame = vec![0; deflate::frame_len(header).unwrap()];
+        reader.read_exact(&mut frame).await.unwrap();
+        messages.push(ChatMessage::from_msgpack(&frame).unwrap());
+    }
+    assert_eq!(messages[0].msg_type, MessageType::RoomChange);
+    bob.expect(chat("hello from alice")).await.unwrap();
+}
+
+#[tokio::test]
+async fn audit_log_edits_break_the_chain() {
+    let dir = std::env::temp_dir().join(format!("chat-test-{}-audit", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let audit_log = dir.join("audit.log");
+    std::fs::write(&audit_log, "2024-01-01T00:00:00Z root kick bob\n").unwrap(); // From before the chain
+    let options = ServerOptions {
+        admins: vec!["root".to_string()],
+        accounts_file: dir.join("accounts.json"),
+        audit_log: audit_log.clone(),
+        config_file: dir.join("server.json"),
+        snapshot_file: dir.join("snapshot.json"),
+        ..Default::default()
+    };
+    let server = ChatServer::new(options).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server.run_until(listener, std::future::pending()));
+
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    for pattern in ["staff", "mod*", "admin*"] {
+        root.send(&format!("/reserve {}", pattern)).await.unwrap();
+        root.expect(|msg| msg.content.ends_with("is reserved for registered accounts")).await.unwrap();
+    }
+    root.send("/audit verify").await.unwrap();
+    root.expect(|msg| msg.content.starts_with("Audit log intact: 3 chained entries")).await.unwrap();
+
+    let log = std::fs::read_to_string(&audit_log).unwrap();
+    std::fs::write(&audit_log, log.replace("reserve mod*", "reserve mods")).unwrap();
+    root.send("/audit verify").await.unwrap();
+    let broken = root.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+    assert!(broken.content.contains("breaks at line 3"), "{}", broken.content);
+}
+
+#[tokio::test]
+async fn old_accounts_files_are_migrated_at_startup() {
+    let dir = std::env::temp_dir().join(format!("chat-test-{}-migrate", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let accounts = dir.join("accounts.json");
+    let start = || async {
+        let options = ServerOptions {
+            accounts_file: accounts.clone(),
+            audit_log: dir.join("audit.log"),
+            config_file: dir.join("server.json"),
+            snapshot_file: dir.join("snapshot.json"),
+            ..Default::default()
+        };
+        let server = ChatServer::new(options).await?;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.run_until(listener, std::future::pending()));
+        Ok::<_, common::ChatError>(addr)
+    };
+
+    let addr = start().await.unwrap();
+    let login = Handshake { username: "alice".to_string(), password: Some("secret".to_string()), register: true, ..Default::default() };
+    TestClient::connect_with(addr, login).await.unwrap();
+    let current: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&accounts).unwrap()).unwrap();
+    assert_eq!(current["schema_version"], 1);
+    std::fs::write(&accounts, current["accounts"].to_string()).unwrap(); // As written before versions
+
+    let addr = start().await.unwrap();
+    let returning = Handshake { username: "alice".to_string(), password: Some("secret".to_string()), ..Default::default() };
+    TestClient::connect_with(addr, returning).await.unwrap();
+    let upgraded: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&accounts).unwrap()).unwrap();
+    assert_eq!(upgraded, current);
+    assert_eq!(std::fs::read_to_string(dir.join("accounts.json.v0")).unwrap(), current["accounts"].to_string());
+
+    std::fs::write(&accounts, r#"{"schema_version": 99, "accounts": {}}"#).unwrap();
+    match start().await {
+        Err(e) => assert!(e.to_string().contains("schema version 99, newer than this server's 1"), "{}", e),
+        Ok(_) => panic!("started with an accounts file from a newer server"),
+    }
+}
+
+// A MaxMind DB file placing 127.0.0.0/8 in country ZZ, network AS64512 "Test Net"
+fn loopback_geoip_db() -> Vec<u8> {
+    let text = |s: &str| match s.len() {
+        len @ 0..29 => [&[0x40 | len as u8][..], s.as_bytes()].concat(),
+        len => [&[0x40 | 29, (len - 29) as u8][..], s.as_bytes()].concat(), // Longer sizes take a byte of their own
+    };
+    let uint32 = |n: u32| [&[0xc4][..], &n.to_be_bytes()[..]].concat();
+    let map = |pairs: &[(&str, Vec<u8>)]| {
+        let mut bytes = vec![0xe0 | pairs.len() as u8];
+        for (key, value) in pairs {
+            bytes.extend(text(key));
+            bytes.extend(value);
+        }
+        bytes
+    };
+    // One node per bit of 127 (0111 1111); the other branch of each is empty (node_count)
+    let node_count = 8u32;
+    let mut db = Vec::new();
+    for (i, bit) in [0, 1, 1, 1, 1, 1, 1, 1].into_iter().enumerate() {
+        let next = if i == 7 { node_count + 16 } else { i as u32 + 1 };
+        let (left, right) = if bit == 1 { (node_count, next) } else { (next, node_count) };
+        db.extend(&left.to_be_bytes()[1..]);
+        db.extend(&right.to_be_bytes()[1..]);
+    }
+    db.extend([0; 16]);
+    db.extend(map(&[
+        ("country", map(&[("iso_code", text("ZZ"))])),
+        ("autonomous_system_number", uint32(64512)),
+        ("autonomous_system_organization", text("Test Net")),
+    ]));
+    db.extend(b"\xab\xcd\xefMaxMind.com");
+    db.extend(map(&[("node_count", uint32(node_count)), ("record_size", uint32(24)), ("ip_version", uint32(4))]));
+    db
+}
+
+#[tokio::test]
+async fn connections_are_placed_with_geoip() {
+    let dir = std::env::temp_dir().join(format!("chat-test-{}-geoip", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("test.mmdb"), loopback_geoip_db()).unwrap();
+    let options = ServerOptions {
+        admins: vec!["root".to_string()],
+        accounts_file: dir.join("accounts.json"),
+        audit_log: dir.join("audit.log"),
+        config_file: dir.join("server.json"),
+        snapshot_file: dir.join("snapshot.json"),
+        geoip: vec![dir.join("test.mmdb")],
+        ..Default::default()
+    };
+    let server = ChatServer::new(options).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server.run_until(listener, std::future::pending()));
+
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+    let _bob = TestClient::connect(addr, "bob").await.unwrap();
+    root.send("/conninfo bob").await.unwrap();
+    let info = root.expect(|msg| msg.content.starts_with("bob: latency")).await.unwrap();
+    assert!(info.content.contains(" (ZZ AS64512 Test Net) "), "{}", info.content);
+    root.send("/kick bob").await.unwrap();
+    root.expect(|msg| msg.content.contains("bob")).await.unwrap();
+    let audit = std::fs::read_to_string(dir.join("audit.log")).unwrap();
+    assert!(audit.contains("kick user=bob room=#general from=127.0.0.1 geo=ZZ AS64512 Test Net sha256="), "{}", audit);
+}
+
+async fn http_get(addr: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn published_rooms_have_rss_feeds() {
+    let dir = std::env::temp_dir().join(format!("chat-test-{}-feeds", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("server.json"), r#"{"rooms": {"news/announcements": {"feed": true}}}"#).unwrap();
+    let options = ServerOptions {
+        accounts_file: dir.join("accounts.json"),
+        audit_log: dir.join("audit.log"),
+        config_file: dir.join("server.json"),
+        snapshot_file: dir.join("snapshot.json"),
+        ..Default::default()
+    };
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let server = ChatServer::new(options).await.unwrap().with_http(http);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server.run_until(listener, std::future::pending()));
+
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    alice.send("not in any feed").await.unwrap();
+    alice.send("/join news/announcements").await.unwrap();
+    alice.expect(|msg| msg.msg_type == MessageType::RoomChange).await.unwrap();
+    alice.send("Version 2 is out <today> & it's fast").await.unwrap();
+    alice.expect(chat("Version 2 is out <today> & it's fast")).await.unwrap();
+
+    let response = http_get(http_addr, "GET /feeds/news/announcements.xml HTTP/1.1\r\nHost: chat.example:8081\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("Content-Type: application/rss+xml"), "{}", response);
+    assert!(response.contains("<link>http://chat.example:8081/feeds/news/announcements.xml</link>"), "{}", response);
+    assert!(response.contains("<title>alice: Version 2 is out &lt;today&gt; &amp; it&apos;s fast</title>"), "{}", response);
+    assert_eq!(response.matches("<item>").count(), 1, "{}", response);
+
+    let response = http_get(http_addr, "GET /feeds/general.xml HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 404 "), "{}", response);
+    let response = http_get(http_addr, "POST /feeds/news/announcements.xml HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 405 "), "{}", response);
+}
+
+#[tokio::test]
+async fn health_checks_follow_maintenance() {
+    let dir = std::env::temp_dir().join(format!("chat-test-{}-health", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let options = ServerOptions {
+        admins: vec!["root".to_string()],
+        accounts_file: dir.join("accounts.json"),
+        audit_log: dir.join("audit.log"),
+        config_file: dir.join("server.json"),
+        snapshot_file: dir.join("snapshot.json"),
+        ..Default::default()
+    };
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http.local_addr().unwrap();
+    let server = ChatServer::new(options).await.unwrap().with_http(http);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server.run_until(listener, std::future::pending()));
+    let mut root = TestClient::connect(addr, "root").await.unwrap();
+
+    let response = http_get(http_addr, "GET /healthz HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    let response = http_get(http_addr, "GET /readyz HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let ready: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(ready["checks"]["storage"], "ok");
+    assert_eq!(ready["checks"]["services"]["History upkeep"], "ok");
+
+    root.send("/maintenance 1h upgrade").await.unwrap();
+    root.expect(|msg| msg.content.contains("upgrade")).await.unwrap();
+    let response = http_get(http_addr, "GET /readyz HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 503 "), "{}", response);
+    assert!(response.contains("upgrade"), "{}", response);
+    let response = http_get(http_addr, "GET /healthz HTTP/1.1\r\n\r\n").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+}
+
+#[tokio::test]
+async fn history_past_the_limit_goes_to_the_archive() {
+    let dir = std::env::temp_dir().join(format!("chat-test-{}-archive", std::process::id()));
+    let config = serde_json::json!({ "history_limit": 3, "archive": { "dir": dir } }).to_string();
+    let addr = start_server_with_config(Some(&config)).await;
+
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    alice.send("/history --archived 2024-01-01").await.unwrap();
+    alice.expect(|msg| msg.content.starts_with("0 archived messages")).await.unwrap();
+    for text in ["one", "two", "three", "four", "five"] {
+        alice.send(text).await.unwrap();
+        alice.expect(chat(text)).await.unwrap();
+    }
+    // Written in the background, but reading the archive waits for what was queued
+    let today = chrono::Utc::now().date_naive();
+    alice.send(&format!("/history --archived {}", today)).await.unwrap();
+    alice.expect(chat("one")).await.unwrap();
+    alice.expect(chat("two")).await.unwrap();
+    alice.expect(|msg| msg.content.ends_with(&format!("archived messages from {} to {}", today, today))).await.unwrap();
+    let archived = std::fs::read_to_string(dir.join("general").join(format!("{}.jsonl", today))).unwrap();
+    let texts: Vec<String> = archived.lines().map(|line| ChatMessage::from_json(line).unwrap().content).collect();
+    assert!(texts.ends_with(&["one".to_string(), "two".to_string()]), "{:?}", texts);
+    assert!(!texts.contains(&"three".to_string()), "{:?}", texts);
+    alice.send("/history --archived 2024-01-01 2024-03-01").await.unwrap();
+    let refused = alice.expect(|msg| msg.msg_type == MessageType::Error).await.unwrap();
+    assert!(refused.content.contains("at most 31 days"), "{}", refused.content);
+}
+
+#[tokio::test]
+async fn recordings_capture_what_clients_send() {
+    let dir = std::env::temp_dir().join(format!("chat-test-{}-recording", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let recording = dir.join("recording.jsonl");
+    let options = ServerOptions {
+        accounts_file: dir.join("accounts.json"),
+        audit_log: dir.join("audit.log"),
+        config_file: dir.join("server.json"),
+        snapshot_file: dir.join("snapshot.json"),
+        record_file: Some(recording.clone()),
+        ..Default::default()
+    };
+    let server = ChatServer::new(options).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let running = tokio::spawn(server.run_until(listener, async { let _ = stopped.await; }));
+
+    let mut alice = TestClient::connect(addr, "alice").await.unwrap();
+    alice.send("hello").await.unwrap();
+    alice.expect(chat("hello")).await.unwrap();
+    drop(alice);
+    tokio::time::sleep(QUIET).await;
+    stop.send(()).unwrap();
+    running.await.unwrap().unwrap();
+
+    let frames: Vec<RecordedFrame> = std::fs::read_to_string(&recording).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let kinds: Vec<&Inbound> = frames.iter().map(|recorded| &recorded.frame).collect();
+    assert!(matches!(kinds[..], [Inbound::Open { .. }, Inbound::Line { .. }, Inbound::Line { .. }, Inbound::Close]), "{:?}", kinds);
+    assert_eq!(kinds[2], &Inbound::Line { line: "hello\n".to_string() });
+    assert!(frames.iter().all(|recorded| recorded.conn == 1));
+    assert!(frames.windows(2).all(|pair| pair[0].at_ms <= pair[1].at_ms));
+}
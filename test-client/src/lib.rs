@@ -0,0 +1,149 @@
+// A thin scripted client for the chat protocol: log in, send lines, and wait for the
+// messages you expect, each wait bounded by a timeout. The server's integration tests use it,
+// and it is small enough to build bots on.
+//
+//     let mut bot = TestClient::connect("127.0.0.1:8080", "bot").await?;
+//     bot.send("/join lounge").await?;
+//     let hello = bot.expect(|m| m.content == "hello bot").await?;
+
+use common::{ChatMessage, Handshake, MessageType};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum TestClientError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("the server refused the login: {0}")]
+    Rejected(String),
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("the server closed the connection")]
+    Closed,
+    #[error("unreadable line from the server: {0}")]
+    Malformed(String),
+    #[error("unexpected message: {0:?}")]
+    Unexpected(Box<ChatMessage>),
+}
+
+pub type Result<T> = std::result::Result<T, TestClientError>;
+
+pub struct TestClient {
+    pub username: String, // As assigned by the server, which matters for guests
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    timeout: Duration,
+}
+
+impl TestClient {
+    // Logs in as `username` and waits until the server has put us in a room
+    pub async fn connect(addr: impl ToSocketAddrs, username: &str) -> Result<Self> {
+        Self::connect_with(addr, Handshake { username: username.to_string(), ..Default::default() }).await
+    }
+
+    // For passwords, guests or a locale
+    pub async fn connect_with(addr: impl ToSocketAddrs, handshake: Handshake) -> Result<Self> {
+        let (reader, writer) = TcpStream::connect(addr).await?.into_split();
+        let mut client = Self { username: handshake.username.clone(), lines: BufReader::new(reader).lines(), writer, timeout: DEFAULT_TIMEOUT };
+        client.send(&serde_json::to_string(&handshake).expect("handshakes serialize")).await?;
+        let first = tokio::time::timeout(client.timeout, client.lines.next_line()).await.map_err(|_| TestClientError::Timeout(client.timeout))??;
+        let Some(first) = first else { return Err(TestClientError::Closed) };
+        // Refusals before login are plain `Error:` lines
+        if let Some(error) = first.strip_prefix("Error:") {
+            return Err(TestClientError::Rejected(error.trim().to_string()));
+        }
+        let first = ChatMessage::from_json(&first).map_err(|_| TestClientError::Malformed(first))?;
+        if first.msg_type == MessageType::AuthRequired {
+            return Err(TestClientError::Rejected(format!("the server wants a {}", first.content)));
+        }
+        if first.msg_type != MessageType::RoomChange {
+            client.expect(|m| m.msg_type == MessageType::RoomChange).await?;
+        }
+        if handshake.guest {
+            client.username = first.username;
+        }
+        Ok(client)
+    }
+
+    // How long `recv` and the `expect` calls wait
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // A chat message or a /command, exactly as typed in the TUI
+    pub async fn send(&mut self, line: &str) -> Result<()> {
+        self.send_raw(format!("{}\n", line).as_bytes()).await
+    }
+
+    // Bytes as they are, for testing how the server copes with bad input
+    pub async fn send_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        Ok(self.writer.write_all(bytes).await?)
+    }
+
+    // The next message, or None once the server hangs up
+    pub async fn recv(&mut self) -> Result<Option<ChatMessage>> {
+        self.recv_within(self.timeout).await
+    }
+
+    async fn recv_within(&mut self, wait: Duration) -> Result<Option<ChatMessage>> {
+        let line = tokio::time::timeout(wait, self.lines.next_line()).await.map_err(|_| TestClientError::Timeout(wait))??;
+        match line {
+            Some(line) => ChatMessage::from_json(&line).map(Some).map_err(|_| TestClientError::Malformed(line)),
+            None => Ok(None),
+        }
+    }
+
+    // Skips messages until one matches
+    pub async fn expect(&mut self, matches: impl Fn(&ChatMessage) -> bool) -> Result<ChatMessage> {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let wait = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.recv_within(wait).await {
+                Ok(Some(msg)) if matches(&msg) => return Ok(msg),
+                Ok(Some(_)) => {}
+                Ok(None) => return Err(TestClientError::Closed),
+                Err(TestClientError::Timeout(_)) => return Err(TestClientError::Timeout(self.timeout)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Fails if a matching message arrives within `within`
+    pub async fn expect_none(&mut self, matches: impl Fn(&ChatMessage) -> bool, within: Duration) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + within;
+        loop {
+            let wait = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.recv_within(wait).await {
+                Ok(Some(msg)) if matches(&msg) => return Err(TestClientError::Unexpected(Box::new(msg))),
+                Ok(Some(_)) => {}
+                Ok(None) | Err(TestClientError::Timeout(_)) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Waits for the server to hang up, skipping whatever it sends first
+    pub async fn expect_closed(&mut self) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let wait = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.recv_within(wait).await {
+                Ok(None) => return Ok(()),
+                Ok(Some(_)) => {}
+                Err(TestClientError::Timeout(_)) => return Err(TestClientError::Timeout(self.timeout)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+// Matches a chat message with exactly this text
+pub fn chat(content: &str) -> impl Fn(&ChatMessage) -> bool + '_ {
+    move |m| m.msg_type == MessageType::Chat && m.content == content
+}